@@ -56,8 +56,10 @@ mod backends {
     use parking_lot::RwLock;
     use pulsectl::controllers::{DeviceControl, SinkController};
     use std::cell::RefCell;
+    use std::time::Duration;
 
     use crate::audio::AudioError;
+    use crate::constants;
 
     use super::Result;
 
@@ -83,6 +85,8 @@ mod backends {
 
         fn play_samples(&self, data: &[u8]) -> Result<()>;
         fn record_samples(&self) -> Result<()>;
+
+        fn get_recorder_latency(&self) -> Result<Duration>;
     }
 
     pub struct PulseAudioBackend {
@@ -123,17 +127,23 @@ mod backends {
             if !self.is_recorder_open {
                 let spec = sample::Spec {
                     format: sample::Format::S16NE,
-                    channels: 2,
-                    rate: 44100,
+                    channels: constants::CHANNELS,
+                    rate: constants::SAMPLE_RATE,
                 };
 
                 assert!(spec.is_valid());
 
+                // record either the monitor of the default sink or the default input
+                let device = match crate::AUDIO_SOURCE.read().source() {
+                    crate::protocol::AudioSource::Output => "@DEFAULT_MONITOR@",
+                    crate::protocol::AudioSource::Microphone => "@DEFAULT_SOURCE@",
+                };
+
                 let result = Simple::new(
                     None,
                     "Eruption",
                     Direction::Record,
-                    Some("@DEFAULT_MONITOR@"),
+                    Some(device),
                     "Audio Grabber",
                     &spec,
                     None,
@@ -150,8 +160,8 @@ mod backends {
 
                 let spec = sample::Spec {
                     format: sample::Format::S16NE,
-                    channels: 2,
-                    rate: 44100,
+                    channels: constants::CHANNELS,
+                    rate: constants::SAMPLE_RATE,
                 };
 
                 assert!(spec.is_valid());
@@ -185,8 +195,8 @@ mod backends {
             if !self.is_playback_open {
                 let spec = sample::Spec {
                     format: sample::Format::S16NE,
-                    channels: 2,
-                    rate: 44100,
+                    channels: constants::CHANNELS,
+                    rate: constants::SAMPLE_RATE,
                 };
 
                 assert!(spec.is_valid());
@@ -335,5 +345,22 @@ mod backends {
                 .into())
             }
         }
+
+        fn get_recorder_latency(&self) -> Result<Duration> {
+            if let Some(grabber) = &*self.recorder_handle.read() {
+                let latency = grabber
+                    .get_latency()
+                    .map_err(|e| AudioError::GrabberError {
+                        description: format!("Could not query the latency: {}", e),
+                    })?;
+
+                Ok(Duration::from_micros(latency.0))
+            } else {
+                Err(AudioError::GrabberError {
+                    description: "Audio subsystem is not available".to_string(),
+                }
+                .into())
+            }
+        }
     }
 }
@@ -0,0 +1,269 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use crate::constants;
+
+lazy_static! {
+    /// Global state of the beat estimator
+    pub static ref BEAT_ESTIMATOR: Arc<Mutex<BeatEstimator>> =
+        Arc::new(Mutex::new(BeatEstimator::new()));
+}
+
+/// An estimate of the tempo and the beat phase of the audio stream
+#[derive(Debug, Clone, Copy)]
+pub struct BeatInfo {
+    /// Tempo in beats per minute
+    pub bpm: f32,
+
+    /// Position within the current beat in the range `0.0..1.0`, a value
+    /// of `0.0` is exactly on the beat
+    pub phase: f32,
+
+    /// Confidence of the estimate in the range `0.0..=1.0`
+    pub confidence: f32,
+}
+
+/// Estimates the tempo and the beat phase of an audio stream. Onsets are detected
+/// by comparing the energy of each block of samples against a running average; the
+/// tempo is the median of the recent inter-onset intervals and the phase is anchored
+/// to the onsets that fall close to a predicted beat
+#[derive(Debug, Clone)]
+pub struct BeatEstimator {
+    /// Running average of the energy of the signal
+    average_energy: f32,
+
+    /// Point in time of the last detected onset
+    last_onset: Option<Instant>,
+
+    /// Recent inter-onset intervals in seconds, folded into the supported tempo range
+    intervals: VecDeque<f32>,
+
+    /// Estimated duration of a beat in seconds
+    period: Option<f32>,
+
+    /// Point in time of a beat, the reference of the phase
+    reference: Option<Instant>,
+
+    confidence: f32,
+}
+
+impl BeatEstimator {
+    pub fn new() -> Self {
+        Self {
+            average_energy: 0.0,
+            last_onset: None,
+            intervals: VecDeque::with_capacity(constants::BEAT_HISTORY_SIZE),
+            period: None,
+            reference: None,
+            confidence: 0.0,
+        }
+    }
+
+    /// Discard all state, e.g. after the recording has been stopped
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Process a block of interleaved 16-bit samples (native endianness) that has
+    /// been recorded at the point in time `captured_at`
+    pub fn process(&mut self, data: &[u8], captured_at: Instant) {
+        let samples = data
+            .chunks_exact(2)
+            .map(|c| i16::from_ne_bytes([c[0], c[1]]) as f32 / i16::MAX as f32);
+
+        let (sum, count) = samples.fold((0.0, 0), |(sum, count), s| (sum + s * s, count + 1));
+
+        if count == 0 {
+            return;
+        }
+
+        let energy = sum / count as f32;
+
+        // the samples of the block span the duration of the block, so we use
+        // the center of the block as the point in time of a potential onset
+        let block_duration = Duration::from_secs_f32(
+            count as f32 / (constants::SAMPLE_RATE as f32 * constants::CHANNELS as f32),
+        );
+        let timestamp = captured_at
+            .checked_sub(block_duration / 2)
+            .unwrap_or(captured_at);
+
+        let min_interval = Duration::from_secs_f32(60.0 / constants::MAX_BPM);
+
+        let is_onset = energy > constants::ONSET_MIN_ENERGY
+            && energy > self.average_energy * constants::ONSET_THRESHOLD
+            && self
+                .last_onset
+                .map(|t| timestamp.saturating_duration_since(t) >= min_interval)
+                .unwrap_or(true);
+
+        self.average_energy = self.average_energy * 0.95 + energy * 0.05;
+
+        if is_onset {
+            self.onset(timestamp);
+        }
+    }
+
+    fn onset(&mut self, timestamp: Instant) {
+        if let Some(last_onset) = self.last_onset {
+            let interval = timestamp
+                .saturating_duration_since(last_onset)
+                .as_secs_f32();
+
+            // intervals of more than a few beats do not tell us anything about the tempo
+            if interval > 0.0 && interval <= 4.0 * 60.0 / constants::MIN_BPM {
+                if self.intervals.len() >= constants::BEAT_HISTORY_SIZE {
+                    self.intervals.pop_front();
+                }
+
+                self.intervals.push_back(fold_interval(interval));
+
+                self.update_tempo();
+            }
+        }
+
+        self.last_onset = Some(timestamp);
+
+        match (self.period, self.reference) {
+            (Some(period), Some(_reference)) => {
+                // move the reference halfway towards onsets that are close to a predicted
+                // beat, onsets between two beats do not affect the phase
+                let error = centered(self.phase_at(timestamp));
+
+                if error.abs() < 0.25 {
+                    let correction = Duration::from_secs_f32(error.abs() * period * 0.5);
+
+                    self.reference = if error > 0.0 {
+                        timestamp.checked_sub(correction)
+                    } else {
+                        Some(timestamp + correction)
+                    };
+                }
+            }
+
+            _ => self.reference = Some(timestamp),
+        }
+    }
+
+    fn update_tempo(&mut self) {
+        let mut sorted = self.intervals.iter().copied().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let period = sorted[sorted.len() / 2];
+
+        let consistent = sorted
+            .iter()
+            .filter(|interval| ((*interval - period) / period).abs() < 0.1)
+            .count();
+
+        self.period = Some(period);
+        self.confidence = consistent as f32 / constants::BEAT_HISTORY_SIZE as f32;
+    }
+
+    /// Returns the phase of the beat at the point in time `t`
+    fn phase_at(&self, t: Instant) -> f32 {
+        match (self.period, self.reference) {
+            (Some(period), Some(reference)) => {
+                let elapsed = if t >= reference {
+                    (t - reference).as_secs_f32()
+                } else {
+                    -(reference - t).as_secs_f32()
+                };
+
+                (elapsed / period).rem_euclid(1.0)
+            }
+
+            _ => 0.0,
+        }
+    }
+
+    /// Returns the current estimate, with the phase shifted ahead by `latency_millis`
+    /// (or behind, if negative). Returns `None` if no tempo could be detected
+    pub fn beat_info(&self, latency_millis: i64) -> Option<BeatInfo> {
+        let period = self.period?;
+
+        let offset = Duration::from_millis(latency_millis.unsigned_abs());
+        let now = Instant::now();
+
+        let t = if latency_millis >= 0 {
+            now + offset
+        } else {
+            now.checked_sub(offset).unwrap_or(now)
+        };
+
+        // confidence decays if we did not detect any onsets for a while, e.g. when
+        // the music stopped playing
+        let silence = self
+            .last_onset
+            .map(|o| now.saturating_duration_since(o).as_secs_f32())
+            .unwrap_or(f32::MAX);
+
+        let confidence = if silence > 4.0 * period {
+            0.0
+        } else {
+            self.confidence
+        };
+
+        Some(BeatInfo {
+            bpm: 60.0 / period,
+            phase: self.phase_at(t),
+            confidence,
+        })
+    }
+}
+
+impl Default for BeatEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fold an inter-onset interval into the supported tempo range, by doubling or halving it
+fn fold_interval(mut interval: f32) -> f32 {
+    let min_interval = 60.0 / constants::MAX_BPM;
+    let max_interval = 60.0 / constants::MIN_BPM;
+
+    while interval < min_interval {
+        interval *= 2.0;
+    }
+
+    while interval > max_interval {
+        interval /= 2.0;
+    }
+
+    interval
+}
+
+/// Map a phase in the range `0.0..1.0` to the range `-0.5..0.5`
+fn centered(phase: f32) -> f32 {
+    if phase >= 0.5 {
+        phase - 1.0
+    } else {
+        phase
+    }
+}
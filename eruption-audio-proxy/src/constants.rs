@@ -19,6 +19,9 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+/// Default path of the configuration file
+pub const AUDIO_PROXY_CONFIG_FILE: &str = "/etc/eruption/audio-proxy.conf";
+
 /// Eruption daemon audio data UNIX domain socket
 pub const AUDIO_SOCKET_NAME: &str = "/run/eruption/audio.sock";
 
@@ -40,3 +43,37 @@ pub const SLEEP_TIME_TIMEOUT: u64 = 2000;
 
 /// Main loop sleep time, when we are disconnected from Eruption
 pub const SLEEP_TIME_WHILE_DISCONNECTED: u64 = 1000;
+
+/// Sample rate of the recorded audio stream
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// Number of channels of the recorded audio stream
+pub const CHANNELS: u8 = 2;
+
+/// Default latency compensation in milliseconds, beats are reported ahead of time by this amount
+pub const DEFAULT_LATENCY_COMPENSATION_MILLIS: i64 = 0;
+
+/// Time in milliseconds that has to pass before we query PipeWire/PulseAudio for
+/// the latency of the recording stream again
+pub const LATENCY_POLL_INTERVAL: u64 = 1000;
+
+/// Tempo range of the beat estimator, in beats per minute
+pub const MIN_BPM: f32 = 60.0;
+pub const MAX_BPM: f32 = 200.0;
+
+/// An onset is detected when the energy of a block exceeds the running average by this factor
+pub const ONSET_THRESHOLD: f32 = 1.5;
+
+/// Minimum energy of a block (normalized) that may be considered an onset, ignores silence
+pub const ONSET_MIN_ENERGY: f32 = 0.0005;
+
+/// Default threshold of the noise gate of the microphone input in dBFS, used until
+/// the Eruption daemon sent its configuration
+pub const DEFAULT_NOISE_GATE_THRESHOLD: f32 = -50.0;
+
+/// Time in milliseconds that the noise gate stays open after the microphone input fell
+/// below the threshold, so that the gaps between syllables are not cut off
+pub const NOISE_GATE_HOLD_MILLIS: u64 = 150;
+
+/// Number of inter-onset intervals that are taken into account when estimating the tempo
+pub const BEAT_HISTORY_SIZE: usize = 16;
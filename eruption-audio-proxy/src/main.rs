@@ -23,17 +23,19 @@ use std::collections::HashMap;
 use std::io::Cursor;
 use std::mem::MaybeUninit;
 use std::os::unix::io::AsRawFd;
-use std::sync::atomic::AtomicI32;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, AtomicI64};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
 use std::time::{Duration, Instant};
-use std::{env, thread};
+use std::{env, process, thread};
 
 use clap::CommandFactory;
 use clap::Parser;
 use clap_complete::Shell;
+use config::Config;
 use flume::{unbounded, Receiver};
 use i18n_embed::{
     fluent::{fluent_language_loader, FluentLanguageLoader},
@@ -55,6 +57,7 @@ use protocol::CommandType;
 use crate::audio::AudioBackend;
 
 mod audio;
+mod beat;
 mod constants;
 mod util;
 
@@ -75,6 +78,22 @@ lazy_static! {
     /// Audio device master volume
     pub static ref AUDIO_MUTED: AtomicBool = AtomicBool::new(false);
 
+    /// Latency compensation in milliseconds, as configured by the user
+    pub static ref LATENCY_COMPENSATION_MILLIS: AtomicI64 = AtomicI64::new(constants::DEFAULT_LATENCY_COMPENSATION_MILLIS);
+
+    /// Should the measured latency of the loopback recording stream be compensated for?
+    pub static ref MEASURE_LOOPBACK_LATENCY: AtomicBool = AtomicBool::new(true);
+
+    /// Measured latency of the loopback recording stream in milliseconds
+    pub static ref LOOPBACK_LATENCY_MILLIS: AtomicI64 = AtomicI64::new(0);
+
+    /// The audio stream that is recorded, and the threshold of the noise gate of the microphone input
+    pub static ref AUDIO_SOURCE: Arc<RwLock<protocol::AudioSourceConfig>> =
+        Arc::new(RwLock::new(protocol::AudioSourceConfig {
+            source: protocol::AudioSource::Output as i32,
+            noise_gate_threshold: constants::DEFAULT_NOISE_GATE_THRESHOLD,
+        }));
+
     /// A queue of packets that will be send to the Eruption daemon
     pub static ref PACKET_TX_QUEUE: Arc<Mutex<Vec<Vec<u8>>>> = Arc::new(Mutex::new(Vec::new()));
 }
@@ -103,6 +122,7 @@ pub mod protocol {
 type Result<T> = std::result::Result<T, eyre::Error>;
 
 type SoundFxMap = HashMap<u32, Vec<u8>>;
+type SampleMap = HashMap<PathBuf, Vec<u8>>;
 
 lazy_static! {
     // /// Global command line options
@@ -114,6 +134,12 @@ lazy_static! {
 
     pub static ref PENDING_SFX_ID: Arc<Mutex<Option<u32>>> = Arc::new(Mutex::new(None));
 
+    /// Samples that have been requested by the daemon, indexed by the path of the file
+    pub static ref SAMPLES: Arc<RwLock<SampleMap>> = Arc::new(RwLock::new(SampleMap::new()));
+
+    /// The file name and the volume of the sample that shall be played back next
+    pub static ref PENDING_SAMPLE: Arc<Mutex<Option<(PathBuf, f32)>>> = Arc::new(Mutex::new(None));
+
     /// Global "quit" status flag
     pub static ref QUIT: AtomicBool = AtomicBool::new(false);
 }
@@ -207,6 +233,12 @@ pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
                 let mut last_device_update = Instant::now()
                     .checked_sub(Duration::from_millis(constants::DEVICE_POLL_INTERVAL + 1))
                     .unwrap();
+                let mut last_latency_update = Instant::now()
+                    .checked_sub(Duration::from_millis(constants::LATENCY_POLL_INTERVAL + 1))
+                    .unwrap();
+
+                // point in time when the microphone input last exceeded the noise gate threshold
+                let mut noise_gate_opened_at: Option<Instant> = None;
 
                 'EVENT_LOOP: loop {
                     if QUIT.load(Ordering::SeqCst) {
@@ -239,6 +271,46 @@ pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
 
                             debug!("Re-opening audio device");
                             audio_backend.open_recorder()?;
+                        } else {
+                            let audio_source = AUDIO_SOURCE.read().clone();
+
+                            // replace quiet blocks of microphone input with silence, so that
+                            // background noise is not visualized
+                            if audio_source.source() == protocol::AudioSource::Microphone {
+                                let mut buf = audio::AUDIO_BUFFER.write();
+
+                                if util::rms_dbfs(&buf) >= audio_source.noise_gate_threshold {
+                                    noise_gate_opened_at = Some(Instant::now());
+                                } else if noise_gate_opened_at.map_or(true, |t| {
+                                    t.elapsed()
+                                        >= Duration::from_millis(constants::NOISE_GATE_HOLD_MILLIS)
+                                }) {
+                                    buf.fill(0);
+                                }
+                            }
+
+                            beat::BEAT_ESTIMATOR
+                                .lock()
+                                .process(&audio::AUDIO_BUFFER.read(), Instant::now());
+                        }
+
+                        // query the latency of the loopback recording stream every n milliseconds
+                        if MEASURE_LOOPBACK_LATENCY.load(Ordering::SeqCst)
+                            && last_latency_update.elapsed()
+                                >= Duration::from_millis(constants::LATENCY_POLL_INTERVAL)
+                        {
+                            match audio_backend.get_recorder_latency() {
+                                Ok(latency) => {
+                                    trace!("Loopback latency: {} ms", latency.as_millis());
+
+                                    LOOPBACK_LATENCY_MILLIS
+                                        .store(latency.as_millis() as i64, Ordering::SeqCst);
+                                }
+
+                                Err(e) => debug!("Could not query the loopback latency: {}", e),
+                            }
+
+                            last_latency_update = Instant::now();
                         }
                     }
 
@@ -258,6 +330,21 @@ pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
                         *PENDING_SFX_ID.lock() = None;
                     }
 
+                    let pending_sample = PENDING_SAMPLE.lock().take();
+
+                    if let Some((file, volume)) = pending_sample {
+                        match load_sample(&file) {
+                            Ok(data) => {
+                                let mut audio_backend = AUDIO_BACKEND.lock();
+
+                                audio_backend.open_playback()?;
+                                audio_backend.play_samples(&util::scale_samples(&data, volume))?;
+                            }
+
+                            Err(e) => warn!("Could not load sample {}: {}", file.display(), e),
+                        }
+                    }
+
                     // wait for socket to be ready
                     let mut poll_fds = [PollFd::new(
                         socket.as_raw_fd(),
@@ -316,6 +403,53 @@ pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
 
                                                     RECORDING.store(false, Ordering::SeqCst);
 
+                                                    beat::BEAT_ESTIMATOR.lock().reset();
+
+                                                    response.set_response_type(CommandType::Noop);
+                                                }
+
+                                                CommandType::SetAudioSource => {
+                                                    match message.payload {
+                                                        Some(
+                                                            protocol::command::Payload::AudioSource(
+                                                                config,
+                                                            ),
+                                                        ) => {
+                                                            debug!(
+                                                                "Request to record from: {:?}, noise gate threshold: {} dBFS",
+                                                                config.source(),
+                                                                config.noise_gate_threshold
+                                                            );
+
+                                                            let changed =
+                                                                AUDIO_SOURCE.read().source
+                                                                    != config.source;
+
+                                                            *AUDIO_SOURCE.write() = config;
+
+                                                            // re-open the recorder on the new audio stream
+                                                            if changed
+                                                                && RECORDING.load(Ordering::SeqCst)
+                                                            {
+                                                                info!("Re-opening audio device");
+
+                                                                let mut audio_backend =
+                                                                    AUDIO_BACKEND.lock();
+
+                                                                audio_backend.close_recorder()?;
+                                                                audio_backend.open_recorder()?;
+
+                                                                beat::BEAT_ESTIMATOR.lock().reset();
+                                                            }
+                                                        }
+
+                                                        _ => {
+                                                            error!(
+                                                                "Protocol error: Invalid payload"
+                                                            );
+                                                        }
+                                                    }
+
                                                     response.set_response_type(CommandType::Noop);
                                                 }
 
@@ -371,6 +505,34 @@ pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
                                                     response.set_response_type(CommandType::Noop);
                                                 }
 
+                                                CommandType::PlaySample => {
+                                                    match message.payload {
+                                                        Some(
+                                                            protocol::command::Payload::Sample(
+                                                                sample,
+                                                            ),
+                                                        ) => {
+                                                            debug!(
+                                                                "Request to play sample: {}",
+                                                                sample.file
+                                                            );
+
+                                                            *PENDING_SAMPLE.lock() = Some((
+                                                                PathBuf::from(sample.file),
+                                                                sample.volume,
+                                                            ));
+                                                        }
+
+                                                        _ => {
+                                                            error!(
+                                                                "Protocol error: Invalid payload"
+                                                            );
+                                                        }
+                                                    }
+
+                                                    response.set_response_type(CommandType::Noop);
+                                                }
+
                                                 _ => {
                                                     error!("Protocol error: Unknown command");
                                                 }
@@ -411,6 +573,32 @@ pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
                                 response.encode_length_delimited(&mut buf)?;
 
                                 PACKET_TX_QUEUE.lock().push(buf);
+
+                                // tempo and beat phase, shifted ahead by the latency compensation
+                                let latency_millis = LATENCY_COMPENSATION_MILLIS
+                                    .load(Ordering::SeqCst)
+                                    + LOOPBACK_LATENCY_MILLIS.load(Ordering::SeqCst);
+
+                                if let Some(beat_info) =
+                                    beat::BEAT_ESTIMATOR.lock().beat_info(latency_millis)
+                                {
+                                    let mut response = protocol::Response::default();
+
+                                    response.set_response_type(CommandType::BeatInfo);
+                                    response.payload = Some(protocol::response::Payload::BeatInfo(
+                                        protocol::BeatInfo {
+                                            bpm: beat_info.bpm,
+                                            phase: beat_info.phase,
+                                            confidence: beat_info.confidence,
+                                            latency_millis: latency_millis as i32,
+                                        },
+                                    ));
+
+                                    let mut buf = Vec::new();
+                                    response.encode_length_delimited(&mut buf)?;
+
+                                    PACKET_TX_QUEUE.lock().push(buf);
+                                }
                             }
 
                             // send unsolicited audio state updates every n milliseconds
@@ -512,6 +700,18 @@ pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
     }
 }
 
+/// Returns the sample data of `file`, samples are loaded on first use and cached afterwards
+fn load_sample(file: &Path) -> Result<Vec<u8>> {
+    if let Some(data) = SAMPLES.read().get(file) {
+        return Ok(data.clone());
+    }
+
+    let data = util::load_audio_file(file)?;
+    SAMPLES.write().insert(file.to_path_buf(), data.clone());
+
+    Ok(data)
+}
+
 pub async fn async_main() -> std::result::Result<(), eyre::Error> {
     cfg_if::cfg_if! {
         if #[cfg(debug_assertions)] {
@@ -539,6 +739,10 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
     let opts = Options::parse();
     let daemon = matches!(opts.command, Subcommands::Daemon);
 
+    let config_file = opts
+        .config
+        .unwrap_or_else(|| constants::AUDIO_PROXY_CONFIG_FILE.to_string());
+
     if unsafe { libc::isatty(0) != 0 } && daemon {
         // initialize logging on console
         if env::var("RUST_LOG").is_err() {
@@ -596,6 +800,32 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             })
             .unwrap_or_else(|e| error!("Could not set CTRL-C handler: {}", e));
 
+            // process configuration file
+            let config = Config::builder()
+                .add_source(config::File::new(&config_file, config::FileFormat::Toml))
+                .build()
+                .unwrap_or_else(|e| {
+                    log::error!("Could not parse configuration file: {}", e);
+                    process::exit(4);
+                });
+
+            let latency_compensation_millis = config
+                .get::<i64>("audio.latency_compensation_millis")
+                .unwrap_or(constants::DEFAULT_LATENCY_COMPENSATION_MILLIS);
+
+            LATENCY_COMPENSATION_MILLIS.store(latency_compensation_millis, Ordering::SeqCst);
+
+            let measure_loopback_latency = config
+                .get::<bool>("audio.measure_loopback_latency")
+                .unwrap_or(true);
+
+            MEASURE_LOOPBACK_LATENCY.store(measure_loopback_latency, Ordering::SeqCst);
+
+            info!(
+                "Latency compensation: {} ms (measure loopback latency: {})",
+                latency_compensation_millis, measure_loopback_latency
+            );
+
             // load sound effects
             info!("Loading sound effects...");
 
@@ -29,8 +29,7 @@ pub fn load_audio_file<P: AsRef<Path>>(file: P) -> Result<Vec<u8>> {
     let mut reader = hound::WavReader::open(file.as_ref())?;
     let samples = reader
         .samples::<i16>()
-        .map(|sample| sample.unwrap())
-        .collect::<Vec<i16>>();
+        .collect::<std::result::Result<Vec<i16>, _>>()?;
 
     let mut buffer: Vec<u8> = vec![];
     for s in samples {
@@ -39,3 +38,34 @@ pub fn load_audio_file<P: AsRef<Path>>(file: P) -> Result<Vec<u8>> {
 
     Ok(buffer)
 }
+
+/// Returns the RMS level of a buffer of 16-bit samples (little endian) in dBFS
+pub fn rms_dbfs(data: &[u8]) -> f32 {
+    let samples = data.chunks_exact(2);
+    let len = samples.len();
+
+    if len == 0 {
+        return f32::NEG_INFINITY;
+    }
+
+    let sum = samples
+        .map(|c| {
+            let sample = i16::from_le_bytes([c[0], c[1]]) as f32 / i16::MAX as f32;
+            sample * sample
+        })
+        .sum::<f32>();
+
+    20.0 * (sum / len as f32).sqrt().log10()
+}
+
+/// Scale a buffer of 16-bit samples (little endian) by `volume` in the range `0.0..=1.0`
+pub fn scale_samples(data: &[u8], volume: f32) -> Vec<u8> {
+    let volume = volume.clamp(0.0, 1.0);
+
+    data.chunks_exact(2)
+        .flat_map(|c| {
+            let sample = i16::from_le_bytes([c[0], c[1]]) as f32 * volume;
+            (sample as i16).to_le_bytes()
+        })
+        .collect()
+}
@@ -0,0 +1,225 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use eruption_sdk::{canvas::Canvas, color::Color};
+
+use crate::backends::{Backend, BackendData, Damage, Region};
+use crate::constants;
+use crate::hwdevices::KeyboardDevice;
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// The screen, downscaled to the key grid of a keyboard. Each key shows the average color
+/// of its cell of the screen. Only the cells that are touched by the damage reported by the
+/// backend are captured and averaged again, so that mostly static desktops are cheap
+pub struct AmbientState {
+    cols: usize,
+    rows: usize,
+
+    /// The area of the screen, or `None` if the whole screen has to be captured
+    screen: Option<Region>,
+
+    /// The average color of each cell, in row major order
+    cells: Vec<[u8; 4]>,
+}
+
+impl AmbientState {
+    pub fn new(device: &KeyboardDevice) -> Self {
+        let cols = device.get_num_cols();
+        let rows = device.get_num_rows();
+
+        Self {
+            cols,
+            rows,
+            screen: None,
+            cells: vec![[0, 0, 0, 0]; cols * rows],
+        }
+    }
+
+    /// Returns the area of the screen `screen` that is covered by the cell `col`/`row`
+    fn cell_region(&self, screen: &Region, col: usize, row: usize) -> Region {
+        let x0 = screen.width as usize * col / self.cols;
+        let x1 = screen.width as usize * (col + 1) / self.cols;
+        let y0 = screen.height as usize * row / self.rows;
+        let y1 = screen.height as usize * (row + 1) / self.rows;
+
+        Region::new(
+            screen.x + x0 as i32,
+            screen.y + y0 as i32,
+            (x1 - x0) as u32,
+            (y1 - y0) as u32,
+        )
+    }
+
+    /// Capture the parts of the screen that changed since the previous call, and update
+    /// the affected cells. Returns `true` if any cell has been updated
+    pub fn update(&mut self, backend: &mut Box<dyn Backend + 'static>) -> Result<bool> {
+        // always fetch the damage, so that it does not accumulate
+        let damage = backend.poll_damage()?;
+
+        let (screen, damage) = match self.screen {
+            Some(screen) => (screen, damage),
+
+            None => {
+                let image = backend.poll()?;
+                let screen = Region::new(0, 0, image.width(), image.height());
+
+                if screen.is_empty() {
+                    return Ok(false);
+                }
+
+                self.screen = Some(screen);
+
+                let cells = self.all_cells();
+                self.update_cells(&screen, &image, &cells);
+
+                return Ok(true);
+            }
+        };
+
+        let (bounds, cells) = match damage {
+            Damage::Empty => return Ok(false),
+
+            Damage::Full => (screen, self.all_cells()),
+
+            Damage::Regions(regions) => {
+                let cells = self.damaged_cells(&screen, &regions);
+
+                // capture whole cells, so that their averages are complete
+                let bounds = cells
+                    .iter()
+                    .map(|(col, row)| self.cell_region(&screen, *col, *row))
+                    .reduce(|a, b| a.union(&b));
+
+                match bounds {
+                    Some(bounds) => (bounds, cells),
+                    None => return Ok(false),
+                }
+            }
+        };
+
+        let image = match backend.poll_region(bounds) {
+            Ok(image) if image.width() == bounds.width && image.height() == bounds.height => image,
+
+            // the screen has probably been resized, capture it as a whole next time
+            result => {
+                log::debug!(
+                    "Could not capture the region {:?}, resetting: {:?}",
+                    bounds,
+                    result.err()
+                );
+
+                self.screen = None;
+
+                return Ok(false);
+            }
+        };
+
+        self.update_cells(&bounds, &image, &cells);
+
+        Ok(true)
+    }
+
+    fn all_cells(&self) -> Vec<(usize, usize)> {
+        (0..self.rows)
+            .flat_map(|row| (0..self.cols).map(move |col| (col, row)))
+            .collect()
+    }
+
+    /// Returns the cells that overlap with at least one of the regions `regions`
+    fn damaged_cells(&self, screen: &Region, regions: &[Region]) -> Vec<(usize, usize)> {
+        self.all_cells()
+            .into_iter()
+            .filter(|(col, row)| {
+                let cell = self.cell_region(screen, *col, *row);
+
+                regions.iter().any(|r| r.intersection(&cell).is_some())
+            })
+            .collect()
+    }
+
+    /// Average the pixels of the cells `cells`. The image `image` shows the area `origin` of
+    /// the screen. Only every n-th pixel is sampled, which is sufficient for a key
+    fn update_cells(&mut self, origin: &Region, image: &BackendData, cells: &[(usize, usize)]) {
+        let screen = match self.screen {
+            Some(screen) => screen,
+            None => return,
+        };
+
+        let step = constants::AMBIENT_SAMPLE_STEP.max(1);
+
+        for (col, row) in cells.iter() {
+            let cell = match self.cell_region(&screen, *col, *row).intersection(origin) {
+                Some(cell) => cell,
+                None => continue,
+            };
+
+            let mut sum = [0u64; 4];
+            let mut count = 0u64;
+
+            for y in (0..cell.height).step_by(step) {
+                for x in (0..cell.width).step_by(step) {
+                    let px = (cell.x - origin.x) as u32 + x;
+                    let py = (cell.y - origin.y) as u32 + y;
+
+                    if let Some(pixel) = image.get_pixel_checked(px, py) {
+                        for (s, c) in sum.iter_mut().zip(pixel.0.iter()) {
+                            *s += *c as u64;
+                        }
+
+                        count += 1;
+                    }
+                }
+            }
+
+            if count > 0 {
+                self.cells[row * self.cols + col] = [
+                    (sum[0] / count) as u8,
+                    (sum[1] / count) as u8,
+                    (sum[2] / count) as u8,
+                    (sum[3] / count) as u8,
+                ];
+            }
+        }
+    }
+
+    /// Map the cells to the keys of the keyboard `device`
+    pub fn to_canvas(&self, device: &KeyboardDevice) -> Canvas {
+        let mut result = Canvas::new();
+
+        for x in 0..self.cols {
+            for y in 0..self.rows {
+                let key_index: usize =
+                    (device.get_rows_topology()[x + (y * (self.cols + 1))]) as usize + 1;
+
+                if !(1..=device.get_num_keys()).contains(&key_index) {
+                    continue;
+                }
+
+                let [r, g, b, a] = self.cells[y * self.cols + x];
+
+                result[key_index] = Color::new(r, g, b, a);
+            }
+        }
+
+        result
+    }
+}
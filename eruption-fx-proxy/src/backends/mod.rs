@@ -23,7 +23,7 @@ use std::cell::RefCell;
 
 use dyn_clonable::{clonable, dyn_clone};
 
-use image::{ImageBuffer, Rgba};
+use image::{imageops, ImageBuffer, Rgba};
 
 #[cfg(feature = "backend-gnome")]
 pub mod gnome;
@@ -66,6 +66,76 @@ thread_local! {
 
 pub type BackendData = ImageBuffer<Rgba<u8>, Vec<u8>>;
 
+/// A rectangular area of the screen, in pixels
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    /// Returns the area that is covered by both regions, or `None` if they do not overlap
+    pub fn intersection(&self, other: &Region) -> Option<Region> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+
+        if right > x && bottom > y {
+            Some(Region::new(x, y, (right - x) as u32, (bottom - y) as u32))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the bounding box of both regions
+    pub fn union(&self, other: &Region) -> Region {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+
+        Region::new(x, y, (right - x) as u32, (bottom - y) as u32)
+    }
+}
+
+/// The parts of the screen that changed since the previous frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Damage {
+    /// The backend does not track damage, or lost track of it, so the whole screen
+    /// has to be processed
+    Full,
+
+    /// The screen did not change
+    Empty,
+
+    /// Only these regions of the screen changed
+    Regions(Vec<Region>),
+}
+
 #[clonable]
 pub trait Backend: Clone {
     fn initialize(&mut self) -> Result<()>;
@@ -77,8 +147,35 @@ pub trait Backend: Clone {
     fn is_failed(&self) -> bool;
     fn set_failed(&mut self, failed: bool);
 
+    /// Capture the whole screen
     fn poll(&mut self) -> Result<BackendData>;
 
+    /// Returns the parts of the screen that changed since the previous call. Backends whose
+    /// capture API does not provide damage information report `Damage::Full`
+    fn poll_damage(&mut self) -> Result<Damage> {
+        Ok(Damage::Full)
+    }
+
+    /// Capture the region `region` of the screen only. Backends that are not able to capture
+    /// a part of the screen capture the whole screen, and crop it afterwards
+    fn poll_region(&mut self, region: Region) -> Result<BackendData> {
+        let screen = self.poll()?;
+        let bounds = Region::new(0, 0, screen.width(), screen.height());
+
+        match region.intersection(&bounds) {
+            Some(region) => Ok(imageops::crop_imm(
+                &screen,
+                region.x as u32,
+                region.y as u32,
+                region.width,
+                region.height,
+            )
+            .to_image()),
+
+            None => Ok(ImageBuffer::new(0, 0)),
+        }
+    }
+
     fn as_any(&self) -> &dyn std::any::Any;
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
 }
@@ -19,7 +19,9 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
-use super::{Backend, BackendData, BackendError};
+use std::sync::Arc;
+
+use super::{Backend, BackendData, BackendError, Damage, Region};
 
 type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -27,6 +29,9 @@ type Result<T> = std::result::Result<T, eyre::Error>;
 pub struct X11Backend {
     pub display: Option<xwrap::Display>,
 
+    /// Tracks the damage of the root window, `None` if the X server lacks the DAMAGE extension
+    pub damage: Option<Arc<damage_tracker::DamageTracker>>,
+
     pub failed: bool,
 }
 
@@ -34,6 +39,7 @@ impl X11Backend {
     pub fn new() -> Result<Self> {
         Ok(Self {
             display: None,
+            damage: None,
             failed: true,
         })
     }
@@ -47,6 +53,11 @@ impl Backend for X11Backend {
 
         self.display = Some(xwrap::Display::open(None).unwrap());
 
+        self.damage = damage_tracker::DamageTracker::new()
+            .map_err(|e| log::warn!("Damage tracking is not available: {}", e))
+            .ok()
+            .map(Arc::new);
+
         // if we made it up to here, the initialization succeeded
         self.failed = false;
 
@@ -96,6 +107,34 @@ impl Backend for X11Backend {
         Ok(result)
     }
 
+    fn poll_damage(&mut self) -> Result<Damage> {
+        match self.damage.as_ref() {
+            Some(damage) => damage.poll(),
+            None => Ok(Damage::Full),
+        }
+    }
+
+    fn poll_region(&mut self, region: Region) -> Result<BackendData> {
+        let display = self.display.as_ref().ok_or(BackendError::NoDisplay)?;
+
+        let window = display.get_default_root();
+
+        let sel = xwrap::Rect {
+            x: region.x,
+            y: region.y,
+            w: region.width as i32,
+            h: region.height as i32,
+        };
+
+        let result = display
+            .get_image(window, sel, xwrap::ALL_PLANES, x11::xlib::ZPixmap)
+            .ok_or(BackendError::Poll)?
+            .into_image_buffer()
+            .ok_or(BackendError::DataConversion)?;
+
+        Ok(result)
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -105,6 +144,83 @@ impl Backend for X11Backend {
     }
 }
 
+mod damage_tracker {
+    use parking_lot::Mutex;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::damage::{self, ConnectionExt as _};
+    use x11rb::protocol::xfixes::ConnectionExt as _;
+    use x11rb::rust_connection::RustConnection;
+
+    use super::super::{Damage, Region};
+
+    type Result<T> = std::result::Result<T, eyre::Error>;
+
+    /// Accumulates the damage of the root window via the DAMAGE extension
+    pub struct DamageTracker {
+        conn: Mutex<RustConnection>,
+        damage: damage::Damage,
+    }
+
+    impl DamageTracker {
+        pub fn new() -> Result<Self> {
+            let (conn, screen_num) = x11rb::connect(None)?;
+            let root = conn.setup().roots[screen_num].root;
+
+            // regions are provided by the XFIXES extension
+            conn.xfixes_query_version(5, 0)?.reply()?;
+            conn.damage_query_version(1, 1)?.reply()?;
+
+            let damage = conn.generate_id()?;
+            conn.damage_create(damage, root, damage::ReportLevel::NON_EMPTY)?;
+            conn.flush()?;
+
+            Ok(Self {
+                conn: Mutex::new(conn),
+                damage,
+            })
+        }
+
+        /// Returns the damage that accumulated since the previous call, and resets it
+        pub fn poll(&self) -> Result<Damage> {
+            let conn = self.conn.lock();
+
+            // only the accumulated region is of interest, discard the notifications
+            while conn.poll_for_event()?.is_some() {}
+
+            let region = conn.generate_id()?;
+            conn.xfixes_create_region(region, &[])?;
+            conn.damage_subtract(self.damage, x11rb::NONE, region)?;
+
+            let reply = conn.xfixes_fetch_region(region)?.reply();
+
+            conn.xfixes_destroy_region(region)?;
+            conn.flush()?;
+
+            let regions: Vec<Region> = reply?
+                .rectangles
+                .iter()
+                .map(|r| Region::new(r.x as i32, r.y as i32, r.width as u32, r.height as u32))
+                .filter(|r| !r.is_empty())
+                .collect();
+
+            if regions.is_empty() {
+                Ok(Damage::Empty)
+            } else {
+                Ok(Damage::Regions(regions))
+            }
+        }
+    }
+
+    impl Drop for DamageTracker {
+        fn drop(&mut self) {
+            let conn = self.conn.lock();
+
+            let _ = conn.damage_destroy(self.damage);
+            let _ = conn.flush();
+        }
+    }
+}
+
 pub mod xwrap {
     // This Source Code Form is subject to the terms of the Mozilla Public
     // License, v. 2.0. If a copy of the MPL was not distributed with this
@@ -0,0 +1,417 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::f64::consts::PI;
+use std::fs;
+use std::process::Command;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use config::Config;
+use eruption_sdk::canvas::Canvas;
+use lazy_static::lazy_static;
+use log::{debug, error, warn};
+use parking_lot::{Mutex, RwLock};
+
+use crate::util::argb_to_color;
+use crate::{constants, hwdevices::KeyboardDevice, QUIT};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Settings of the calendar widget
+    pub static ref SETTINGS: Arc<RwLock<CalendarSettings>> =
+        Arc::new(RwLock::new(CalendarSettings::default()));
+
+    /// Upcoming calendar events, ordered by their start time
+    pub static ref EVENTS: Arc<RwLock<Vec<CalendarEvent>>> = Arc::new(RwLock::new(vec![]));
+
+    /// State of the countdown pulse
+    static ref PULSE: Arc<Mutex<Pulse>> = Arc::new(Mutex::new(Pulse::default()));
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CalendarError {
+    #[error("Unknown calendar provider: {name}")]
+    UnknownProvider { name: String },
+}
+
+/// The sources that calendar events may be fetched from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalendarProvider {
+    /// An iCalendar (.ics) file, either a local file or a http(s) or webcal URL
+    Ics { url: String },
+
+    /// A shell command, that prints one line per event, consisting of the start
+    /// time of the event in seconds since the UNIX epoch, followed by its summary
+    Command { command: String },
+}
+
+/// Settings of the calendar widget, loaded from the `[calendar]` section
+#[derive(Debug, Clone)]
+pub struct CalendarSettings {
+    /// Pulse the keyboard when a calendar event is coming up
+    pub enabled: bool,
+
+    pub provider: Option<CalendarProvider>,
+
+    /// Interval between two updates of the calendar events
+    pub refresh_secs: u64,
+
+    /// Time span before the start of an event during which the keyboard pulses
+    pub lead_minutes: u64,
+
+    /// Color of the pulse (ARGB)
+    pub color: u32,
+}
+
+impl Default for CalendarSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: None,
+            refresh_secs: constants::DEFAULT_CALENDAR_REFRESH_SECS,
+            lead_minutes: constants::DEFAULT_CALENDAR_LEAD_MINUTES,
+            color: constants::DEFAULT_CALENDAR_COLOR,
+        }
+    }
+}
+
+/// An upcoming calendar event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarEvent {
+    pub summary: String,
+    pub start: DateTime<Local>,
+}
+
+impl CalendarEvent {
+    /// Returns the time span until the start of the event, or `None` if it already started
+    pub fn time_until(&self) -> Option<Duration> {
+        (self.start - Local::now()).to_std().ok()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Pulse {
+    /// Phase of the pulse in the range `0.0..1.0`
+    phase: f64,
+
+    last_frame: Option<Instant>,
+}
+
+/// Load the settings of the calendar widget from the configuration
+pub fn load_settings(config: &Config) {
+    let defaults = CalendarSettings::default();
+
+    let provider = match load_provider(config) {
+        Ok(provider) => provider,
+
+        Err(e) => {
+            error!("Could not load the calendar provider: {}", e);
+            None
+        }
+    };
+
+    let settings = CalendarSettings {
+        enabled: config
+            .get::<bool>("calendar.enabled")
+            .unwrap_or(defaults.enabled),
+        provider,
+        refresh_secs: config
+            .get::<u64>("calendar.refresh_secs")
+            .unwrap_or(defaults.refresh_secs),
+        lead_minutes: config
+            .get::<u64>("calendar.lead_minutes")
+            .unwrap_or(defaults.lead_minutes),
+        color: config
+            .get::<u32>("calendar.color")
+            .unwrap_or(defaults.color),
+    };
+
+    *SETTINGS.write() = settings;
+}
+
+fn load_provider(config: &Config) -> Result<Option<CalendarProvider>> {
+    let name = match config.get::<String>("calendar.provider") {
+        Ok(name) => name,
+        Err(_) => return Ok(None),
+    };
+
+    let provider = match name.as_str() {
+        "ics" => CalendarProvider::Ics {
+            url: config.get::<String>("calendar.url")?,
+        },
+
+        "command" => CalendarProvider::Command {
+            command: config.get::<String>("calendar.command")?,
+        },
+
+        _ => return Err(CalendarError::UnknownProvider { name }.into()),
+    };
+
+    Ok(Some(provider))
+}
+
+/// Spawns a thread that periodically fetches the upcoming events from the configured provider
+pub fn spawn_calendar_thread() -> Result<()> {
+    thread::Builder::new()
+        .name("calendar".into())
+        .spawn(move || {
+            let mut last_update: Option<Instant> = None;
+
+            while !QUIT.load(Ordering::SeqCst) {
+                let settings = SETTINGS.read().clone();
+
+                let due = last_update
+                    .is_none_or(|t| t.elapsed() >= Duration::from_secs(settings.refresh_secs));
+
+                if settings.enabled && due {
+                    if let Some(provider) = settings.provider.as_ref() {
+                        last_update = Some(Instant::now());
+
+                        match fetch_events(provider) {
+                            Ok(mut events) => {
+                                let now = Local::now();
+
+                                events.retain(|event| event.start > now);
+                                events.sort_by_key(|event| event.start);
+
+                                debug!("{} upcoming calendar events", events.len());
+
+                                *EVENTS.write() = events;
+                            }
+
+                            Err(e) => warn!("Could not fetch the calendar events: {}", e),
+                        }
+                    }
+                }
+
+                thread::sleep(Duration::from_millis(constants::WIDGET_POLL_MILLIS));
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Fetch the calendar events from `provider`
+fn fetch_events(provider: &CalendarProvider) -> Result<Vec<CalendarEvent>> {
+    match provider {
+        CalendarProvider::Ics { url } => {
+            let url = match url.strip_prefix("webcal://") {
+                Some(rest) => format!("https://{}", rest),
+                None => url.clone(),
+            };
+
+            let data = if url.starts_with("http://") || url.starts_with("https://") {
+                ureq::get(&url)
+                    .timeout(Duration::from_millis(constants::WIDGET_HTTP_TIMEOUT_MILLIS))
+                    .call()?
+                    .into_string()?
+            } else {
+                fs::read_to_string(&url)?
+            };
+
+            Ok(parse_ics(&data))
+        }
+
+        CalendarProvider::Command { command } => {
+            let output = Command::new("sh").args(["-c", command]).output()?;
+            let output = String::from_utf8_lossy(&output.stdout);
+
+            let events = output
+                .lines()
+                .filter_map(|line| {
+                    let line = line.trim();
+                    let (timestamp, summary) = line.split_once(' ').unwrap_or((line, ""));
+
+                    let start = Local
+                        .timestamp_opt(timestamp.parse::<i64>().ok()?, 0)
+                        .single()?;
+
+                    Some(CalendarEvent {
+                        summary: summary.trim().to_string(),
+                        start,
+                    })
+                })
+                .collect();
+
+            Ok(events)
+        }
+    }
+}
+
+/// Extract the events from an iCalendar document. Only the start time and the summary
+/// of each event are considered; all-day events and recurrence rules are not supported
+fn parse_ics(data: &str) -> Vec<CalendarEvent> {
+    // unfold long lines, continuation lines start with a single whitespace
+    let mut lines: Vec<String> = vec![];
+
+    for line in data.lines() {
+        match lines.last_mut() {
+            Some(last) if line.starts_with([' ', '\t']) => last.push_str(&line[1..]),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut result = vec![];
+
+    let mut in_event = false;
+    let mut summary = None;
+    let mut start = None;
+
+    for line in lines.iter() {
+        let (name, value) = match line.split_once(':') {
+            Some(property) => property,
+            None => continue,
+        };
+
+        // the name of a property may be followed by parameters, like e.g. "DTSTART;TZID=..."
+        let (name, params) = name.split_once(';').unwrap_or((name, ""));
+
+        match name {
+            "BEGIN" if value == "VEVENT" => {
+                in_event = true;
+                summary = None;
+                start = None;
+            }
+
+            "END" if value == "VEVENT" => {
+                in_event = false;
+
+                if let Some(start) = start.take() {
+                    result.push(CalendarEvent {
+                        summary: summary.take().unwrap_or_default(),
+                        start,
+                    });
+                }
+            }
+
+            "SUMMARY" if in_event => {
+                summary = Some(
+                    value
+                        .replace("\\n", " ")
+                        .replace("\\,", ",")
+                        .replace("\\;", ";")
+                        .replace("\\\\", "\\"),
+                );
+            }
+
+            "DTSTART" if in_event => start = parse_ics_date_time(value, params),
+
+            _ => (),
+        }
+    }
+
+    result
+}
+
+/// Parse an iCalendar date-time value, either in UTC (e.g. "20240102T150000Z") or in
+/// local time. Time zone parameters are not supported, such values are assumed to be in
+/// local time
+fn parse_ics_date_time(value: &str, params: &str) -> Option<DateTime<Local>> {
+    if params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME") {
+        // all-day events do not have a start time to count down to
+        return None;
+    }
+
+    match value.strip_suffix('Z') {
+        Some(value) => {
+            let date_time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+
+            Some(Utc.from_utc_datetime(&date_time).with_timezone(&Local))
+        }
+
+        None => {
+            let date_time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+
+            Local.from_local_datetime(&date_time).earliest()
+        }
+    }
+}
+
+/// Returns the next upcoming event
+pub fn next_event() -> Option<CalendarEvent> {
+    EVENTS
+        .read()
+        .iter()
+        .find(|event| event.time_until().is_some())
+        .cloned()
+}
+
+/// Returns the time span until the next event starts, if it starts within the lead time
+fn countdown() -> Option<(Duration, Duration)> {
+    let lead = Duration::from_secs(SETTINGS.read().lead_minutes * 60);
+    let remaining = next_event()?.time_until()?;
+
+    if remaining <= lead {
+        Some((remaining, lead))
+    } else {
+        None
+    }
+}
+
+/// Returns `true` if the calendar widget needs to be rendered
+pub fn is_active() -> bool {
+    SETTINGS.read().enabled && countdown().is_some()
+}
+
+/// Render a pulse across the keyboard, that speeds up as the start of the next event approaches
+pub fn render(canvas: &mut Canvas, device: &KeyboardDevice) {
+    let (remaining, lead) = match countdown() {
+        Some(countdown) => countdown,
+        None => return,
+    };
+
+    let argb = SETTINGS.read().color;
+
+    let t = if lead.is_zero() {
+        0.0
+    } else {
+        remaining.as_secs_f64() / lead.as_secs_f64()
+    };
+
+    let period_millis = constants::CALENDAR_PULSE_FAST_MILLIS as f64
+        + (constants::CALENDAR_PULSE_SLOW_MILLIS - constants::CALENDAR_PULSE_FAST_MILLIS) as f64
+            * t;
+
+    let intensity = {
+        let mut pulse = PULSE.lock();
+
+        if let Some(last_frame) = pulse.last_frame {
+            let delta = last_frame.elapsed().as_millis() as f64 / period_millis;
+            pulse.phase = (pulse.phase + delta) % 1.0;
+        }
+
+        pulse.last_frame = Some(Instant::now());
+
+        0.5 - 0.5 * (2.0 * PI * pulse.phase).cos()
+    };
+
+    let alpha = (((argb >> 24) & 0xff) as f64 * intensity) as u32;
+    let color = argb_to_color((argb & 0x00ffffff) | (alpha << 24));
+
+    for key_index in 1..=device.get_num_keys() {
+        canvas[key_index] = color;
+    }
+}
@@ -27,6 +27,10 @@ pub const DEFAULT_ANIMATION_DELAY_MILLIS: u64 = 83;
 /// Default delay between screenshots, used for ambient mode
 pub const DEFAULT_FRAME_DELAY_MILLIS: u64 = 37;
 
+/// Only every n-th pixel of a row and a column is sampled, when the screen is downscaled
+/// to the keys of the keyboard by the ambient effect
+pub const AMBIENT_SAMPLE_STEP: usize = 4;
+
 /// Timeout value to use for D-Bus connections
 pub const DBUS_TIMEOUT_MILLIS: u64 = 5000;
 
@@ -40,3 +44,85 @@ pub const DBUS_TIMEOUT_MILLIS_INTERACTIVE: u64 = 30000;
 
 /// Default X11 display used by the X11 sensor plugin
 pub const DEFAULT_X11_DISPLAY: &str = ":0";
+
+/// Default duration of the countdown timer effect (one pomodoro)
+pub const DEFAULT_TIMER_DURATION_SECS: u64 = 25 * 60;
+
+/// Default color of the keys that represent the remaining time (ARGB)
+pub const DEFAULT_TIMER_FOREGROUND_COLOR: u32 = 0xffff2000;
+
+/// Default color of the keys that represent the elapsed time (ARGB)
+pub const DEFAULT_TIMER_BACKGROUND_COLOR: u32 = 0x40000000;
+
+/// Time span during which a finished countdown is indicated by blinking
+pub const TIMER_FINISHED_BLINK_MILLIS: u64 = 10000;
+
+/// Blink interval used to indicate a finished countdown
+pub const TIMER_BLINK_INTERVAL_MILLIS: u64 = 500;
+
+/// Default configuration file of the fx-proxy daemon
+pub const FX_PROXY_CONFIG_FILE: &str = "/etc/eruption/fx-proxy.conf";
+
+/// Default color of the flash for notifications with the urgency level "low" (ARGB)
+pub const DEFAULT_NOTIFICATION_LOW_COLOR: u32 = 0x800080ff;
+
+/// Default color of the flash for notifications with the urgency level "normal" (ARGB)
+pub const DEFAULT_NOTIFICATION_NORMAL_COLOR: u32 = 0xc0ffffff;
+
+/// Default color of the flash for notifications with the urgency level "critical" (ARGB)
+pub const DEFAULT_NOTIFICATION_CRITICAL_COLOR: u32 = 0xffff0000;
+
+/// Default duration of the flash that indicates a notification
+pub const DEFAULT_NOTIFICATION_FLASH_MILLIS: u64 = 500;
+
+/// Default minimum interval between two notification flashes, notifications
+/// that arrive in between are not indicated
+pub const DEFAULT_NOTIFICATION_RATE_LIMIT_MILLIS: u64 = 2000;
+
+/// Interval at which the widget threads check whether an update is due
+pub const WIDGET_POLL_MILLIS: u64 = 1000;
+
+/// Timeout of HTTP requests of the weather and calendar providers
+pub const WIDGET_HTTP_TIMEOUT_MILLIS: u64 = 10000;
+
+/// Endpoint of the Open-Meteo weather service
+pub const OPEN_METEO_URL: &str = "https://api.open-meteo.com/v1/forecast";
+
+/// Default interval between two updates of the weather report
+pub const DEFAULT_WEATHER_REFRESH_SECS: u64 = 15 * 60;
+
+/// Default temperature that is rendered blue (degrees Celsius)
+pub const DEFAULT_WEATHER_COLD_TEMPERATURE: f64 = -10.0;
+
+/// Default temperature that is rendered red (degrees Celsius)
+pub const DEFAULT_WEATHER_HOT_TEMPERATURE: f64 = 35.0;
+
+/// Default interval between two updates of the calendar events
+pub const DEFAULT_CALENDAR_REFRESH_SECS: u64 = 5 * 60;
+
+/// Default time span before the start of an event during which the keyboard pulses
+pub const DEFAULT_CALENDAR_LEAD_MINUTES: u64 = 15;
+
+/// Default color of the calendar countdown pulse (ARGB)
+pub const DEFAULT_CALENDAR_COLOR: u32 = 0xffff8000;
+
+/// Period of the countdown pulse at the beginning of the lead time
+pub const CALENDAR_PULSE_SLOW_MILLIS: u64 = 2000;
+
+/// Period of the countdown pulse right before the start of an event
+pub const CALENDAR_PULSE_FAST_MILLIS: u64 = 300;
+
+/// Name of the color scheme that is updated with the palette of the wallpaper
+pub const WALLPAPER_COLOR_SCHEME: &str = "wallpaper";
+
+/// Default number of colors of the palette extracted from the wallpaper
+pub const DEFAULT_WALLPAPER_COLORS: usize = 5;
+
+/// Max. number of colors of the palette extracted from the wallpaper
+pub const MAX_WALLPAPER_COLORS: usize = 16;
+
+/// Default interval between two checks whether the wallpaper changed
+pub const DEFAULT_WALLPAPER_REFRESH_SECS: u64 = 10;
+
+/// The wallpaper is scaled down to this size, before the palette is extracted
+pub const WALLPAPER_SAMPLE_SIZE: u32 = 64;
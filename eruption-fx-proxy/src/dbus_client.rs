@@ -79,6 +79,23 @@ pub fn get_managed_devices() -> Result<(Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u
     Ok(result)
 }
 
+/// Create or update the named color scheme `name`, `data` holds the RGBA components
+/// of its colors
+pub fn set_color_scheme(name: &str, data: Vec<u8>) -> Result<()> {
+    use self::config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let _result = proxy.set_color_scheme(name, data)?;
+
+    Ok(())
+}
+
 #[allow(clippy::all)]
 mod config {
     // This code was autogenerated with `dbus-codegen-rust -s -d org.eruption -p /org/eruption/config -m None`, see https://github.com/diwic/dbus-rs
@@ -94,6 +111,7 @@ mod config {
         fn set_brightness(&self, value: i64) -> Result<(), dbus::Error>;
         fn enable_sfx(&self) -> Result<bool, dbus::Error>;
         fn set_enable_sfx(&self, value: bool) -> Result<(), dbus::Error>;
+        fn set_color_scheme(&self, name: &str, data: Vec<u8>) -> Result<bool, dbus::Error>;
     }
 
     #[derive(Debug)]
@@ -138,6 +156,11 @@ mod config {
                 .and_then(|r: (bool,)| Ok(r.0))
         }
 
+        fn set_color_scheme(&self, name: &str, data: Vec<u8>) -> Result<bool, dbus::Error> {
+            self.method_call("org.eruption.Config", "SetColorScheme", (name, data))
+                .and_then(|r: (bool,)| Ok(r.0))
+        }
+
         fn brightness(&self) -> Result<i64, dbus::Error> {
             <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
                 &self,
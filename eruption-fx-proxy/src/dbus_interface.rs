@@ -20,10 +20,13 @@
 */
 
 use dbus::{ffidisp::Connection, ffidisp::NameFlag};
-use dbus_tree::{Access, EmitsChangedSignal, Factory};
+use dbus_tree::{Access, EmitsChangedSignal, Factory, MethodErr, Signal};
 use flume::Sender;
 use log::*;
 use std::sync::{atomic::Ordering, Arc};
+use std::time::Duration;
+
+use crate::{calendar, notifications, timer, weather};
 
 /// D-Bus messages and signals that are processed by the main thread
 #[derive(Debug, Clone)]
@@ -45,7 +48,7 @@ pub enum DbusApiError {
 /// D-Bus API support
 pub struct DbusApi {
     connection: Option<Arc<Connection>>,
-    // status_changed: Arc<Signal<()>>,
+    status_changed: Arc<Signal<()>>,
 }
 
 #[allow(dead_code)]
@@ -68,7 +71,10 @@ impl DbusApi {
         let status_changed_signal_0 = status_changed_signal.clone();
         let status_changed_signal_1 = status_changed_signal.clone();
         let status_changed_signal_2 = status_changed_signal.clone();
-        let status_changed_signal_clone = status_changed_signal;
+        let status_changed_signal_3 = status_changed_signal.clone();
+        let status_changed_signal_4 = status_changed_signal.clone();
+        let status_changed_signal_5 = status_changed_signal.clone();
+        let status_changed_signal_clone = status_changed_signal.clone();
 
         let tree = f.tree(()).add(
             f.object_path("/org/eruption/fx_proxy/effects", ())
@@ -117,6 +123,197 @@ impl DbusApi {
                             );
 
                             Ok(vec![m.msg.method_return()])
+                        }))
+                        .add_p(
+                            f.property::<u64, _>("TimerDuration", ())
+                                .emits_changed(EmitsChangedSignal::True)
+                                .access(Access::ReadWrite)
+                                .on_get(|i, _m| {
+                                    i.append(timer::TIMER.read().duration.as_secs());
+                                    Ok(())
+                                })
+                                .on_set(|i, _m| {
+                                    let secs: u64 = i.read()?;
+
+                                    if secs == 0 {
+                                        return Err(MethodErr::invalid_arg(&"TimerDuration"));
+                                    }
+
+                                    timer::TIMER.write().duration = Duration::from_secs(secs);
+
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<u32, _>("TimerForegroundColor", ())
+                                .emits_changed(EmitsChangedSignal::True)
+                                .access(Access::ReadWrite)
+                                .on_get(|i, _m| {
+                                    i.append(timer::TIMER.read().foreground);
+                                    Ok(())
+                                })
+                                .on_set(|i, _m| {
+                                    timer::TIMER.write().foreground = i.read()?;
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<u32, _>("TimerBackgroundColor", ())
+                                .emits_changed(EmitsChangedSignal::True)
+                                .access(Access::ReadWrite)
+                                .on_get(|i, _m| {
+                                    i.append(timer::TIMER.read().background);
+                                    Ok(())
+                                })
+                                .on_set(|i, _m| {
+                                    timer::TIMER.write().background = i.read()?;
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<String, _>("TimerState", ())
+                                .emits_changed(EmitsChangedSignal::False)
+                                .access(Access::Read)
+                                .on_get(|i, _m| {
+                                    i.append(timer::TIMER.read().state().as_str().to_string());
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<u64, _>("TimerRemaining", ())
+                                .emits_changed(EmitsChangedSignal::False)
+                                .access(Access::Read)
+                                .on_get(|i, _m| {
+                                    i.append(timer::TIMER.read().remaining().as_secs());
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<bool, _>("NotificationsEnabled", ())
+                                .emits_changed(EmitsChangedSignal::True)
+                                .access(Access::ReadWrite)
+                                .on_get(|i, _m| {
+                                    i.append(notifications::SETTINGS.read().enabled);
+                                    Ok(())
+                                })
+                                .on_set(|i, _m| {
+                                    notifications::SETTINGS.write().enabled = i.read()?;
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<bool, _>("DoNotDisturb", ())
+                                .emits_changed(EmitsChangedSignal::True)
+                                .access(Access::ReadWrite)
+                                .on_get(|i, _m| {
+                                    i.append(notifications::DO_NOT_DISTURB.load(Ordering::SeqCst));
+                                    Ok(())
+                                })
+                                .on_set(|i, _m| {
+                                    notifications::DO_NOT_DISTURB
+                                        .store(i.read()?, Ordering::SeqCst);
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<bool, _>("WeatherEnabled", ())
+                                .emits_changed(EmitsChangedSignal::True)
+                                .access(Access::ReadWrite)
+                                .on_get(|i, _m| {
+                                    i.append(weather::SETTINGS.read().enabled);
+                                    Ok(())
+                                })
+                                .on_set(|i, _m| {
+                                    weather::SETTINGS.write().enabled = i.read()?;
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<f64, _>("Temperature", ())
+                                .emits_changed(EmitsChangedSignal::False)
+                                .access(Access::Read)
+                                .on_get(|i, _m| {
+                                    let temperature = weather::WEATHER
+                                        .read()
+                                        .map(|report| report.temperature)
+                                        .unwrap_or(f64::NAN);
+
+                                    i.append(temperature);
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<bool, _>("CalendarEnabled", ())
+                                .emits_changed(EmitsChangedSignal::True)
+                                .access(Access::ReadWrite)
+                                .on_get(|i, _m| {
+                                    i.append(calendar::SETTINGS.read().enabled);
+                                    Ok(())
+                                })
+                                .on_set(|i, _m| {
+                                    calendar::SETTINGS.write().enabled = i.read()?;
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<String, _>("NextEvent", ())
+                                .emits_changed(EmitsChangedSignal::False)
+                                .access(Access::Read)
+                                .on_get(|i, _m| {
+                                    let summary = calendar::next_event()
+                                        .map(|event| event.summary)
+                                        .unwrap_or_default();
+
+                                    i.append(summary);
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(
+                            f.property::<u64, _>("NextEventIn", ())
+                                .emits_changed(EmitsChangedSignal::False)
+                                .access(Access::Read)
+                                .on_get(|i, _m| {
+                                    let secs = calendar::next_event()
+                                        .and_then(|event| event.time_until())
+                                        .map(|d| d.as_secs())
+                                        .unwrap_or(0);
+
+                                    i.append(secs);
+                                    Ok(())
+                                }),
+                        )
+                        .add_m(f.method("StartTimer", (), move |m| {
+                            timer::TIMER.write().start();
+
+                            let signal = status_changed_signal_3.emit(
+                                &"/org/eruption/fx_proxy/effects".into(),
+                                &"org.eruption.fx_proxy.Effects".into(),
+                                &["StartTimer"],
+                            );
+
+                            Ok(vec![m.msg.method_return(), signal])
+                        }))
+                        .add_m(f.method("PauseTimer", (), move |m| {
+                            timer::TIMER.write().pause();
+
+                            let signal = status_changed_signal_4.emit(
+                                &"/org/eruption/fx_proxy/effects".into(),
+                                &"org.eruption.fx_proxy.Effects".into(),
+                                &["PauseTimer"],
+                            );
+
+                            Ok(vec![m.msg.method_return(), signal])
+                        }))
+                        .add_m(f.method("ResetTimer", (), move |m| {
+                            timer::TIMER.write().reset();
+
+                            let signal = status_changed_signal_5.emit(
+                                &"/org/eruption/fx_proxy/effects".into(),
+                                &"org.eruption.fx_proxy.Effects".into(),
+                                &["ResetTimer"],
+                            );
+
+                            Ok(vec![m.msg.method_return(), signal])
                         })),
                 ),
         );
@@ -127,10 +324,26 @@ impl DbusApi {
 
         Ok(Self {
             connection: Some(c_clone),
-            // status_changed: status_changed_signal,
+            status_changed: status_changed_signal,
         })
     }
 
+    /// Notify D-Bus clients that the countdown timer reached zero
+    pub fn notify_timer_finished(&self) -> Result<()> {
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(self.status_changed.emit(
+                &"/org/eruption/fx_proxy/effects".into(),
+                &"org.eruption.fx_proxy.Effects".into(),
+                &["TimerFinished"],
+            ))
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
     /// Returns true if an event is pending on the D-Bus connection
     pub fn has_pending_event(&self) -> Result<bool> {
         match self.connection {
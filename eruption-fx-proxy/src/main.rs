@@ -22,6 +22,7 @@
 use clap::CommandFactory;
 use clap::Parser;
 use clap_complete::Shell;
+use config::Config;
 
 use flume::unbounded;
 use flume::Receiver;
@@ -37,7 +38,7 @@ use parking_lot::{Mutex, RwLock};
 use rust_embed::RustEmbed;
 use std::sync::Arc;
 use std::{
-    env,
+    env, process,
     sync::atomic::{AtomicBool, Ordering},
     thread,
 };
@@ -49,12 +50,18 @@ use eruption_sdk::canvas::Canvas;
 use eruption_sdk::color::Color;
 use eruption_sdk::connection::{Connection, ConnectionType};
 
+mod ambient;
 mod backends;
+mod calendar;
 mod constants;
 mod dbus_client;
 mod dbus_interface;
 mod hwdevices;
+mod notifications;
+mod timer;
 mod util;
+mod wallpaper;
+mod weather;
 
 #[derive(RustEmbed)]
 #[folder = "i18n"] // path to the compiled localization resources
@@ -171,7 +178,9 @@ Copyright (c) 2019-2022, The Eruption Development Team
 }
 
 #[derive(Debug, Clone)]
-pub enum DbusApiEvent {}
+pub enum DbusApiEvent {
+    TimerFinished,
+}
 
 /// Spawns the D-Bus API thread and executes it's main loop
 fn spawn_dbus_api_thread(dbus_tx: Sender<dbus_interface::Message>) -> Result<Sender<DbusApiEvent>> {
@@ -185,7 +194,9 @@ fn spawn_dbus_api_thread(dbus_tx: Sender<dbus_interface::Message>) -> Result<Sen
             loop {
                 // process events, destined for the dbus api
                 match dbus_api_rx.recv_timeout(Duration::from_millis(0)) {
-                    Ok(result) => match result {},
+                    Ok(result) => match result {
+                        DbusApiEvent::TimerFinished => dbus.notify_timer_finished()?,
+                    },
 
                     // ignore timeout errors
                     Err(_e) => (),
@@ -199,7 +210,10 @@ fn spawn_dbus_api_thread(dbus_tx: Sender<dbus_interface::Message>) -> Result<Sen
     Ok(dbus_api_tx)
 }
 
-pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
+pub async fn run_main_loop(
+    dbus_api_tx: &Sender<DbusApiEvent>,
+    _ctrl_c_rx: &Receiver<bool>,
+) -> Result<()> {
     debug!("Entering the main loop now...");
 
     'MAIN_LOOP: loop {
@@ -226,6 +240,9 @@ pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
 
         let mut canvas_cleared = false;
 
+        // the screen, downscaled to the keys of the keyboard
+        let mut ambient = ambient::AmbientState::new(&device);
+
         // create a new canvas
         let mut canvas = Canvas::new();
         canvas.fill(Color::new(0, 0, 0, 0));
@@ -239,14 +256,51 @@ pub async fn run_main_loop(_ctrl_c_rx: &Receiver<bool>) -> Result<()> {
                 break 'EVENT_LOOP;
             }
 
+            // effects are drawn on top of the ambient effect, if enabled
+            if !ENABLE_AMBIENT_EFFECT.load(Ordering::SeqCst) {
+                canvas.fill(Color::new(0, 0, 0, 0));
+            }
+
             if ENABLE_AMBIENT_EFFECT.load(Ordering::SeqCst) {
-                // request a screenshot from the backend and convert the image to the device's topology
-                let image_buffer = backend.poll()?;
-                let result = util::process_image_buffer(image_buffer, &device)?;
+                // capture the parts of the screen that changed, and convert them to the device's topology
+                if ambient.update(&mut backend)? {
+                    log::trace!("Screen content changed");
+                }
 
                 // TODO: Implement blend code
                 // utils::blend(&mut canvas, &result);
-                canvas = result;
+                canvas = ambient.to_canvas(&device);
+
+                any_updates = true;
+            }
+
+            {
+                let mut timer = timer::TIMER.write();
+
+                if timer.update() {
+                    log::info!("Countdown timer finished");
+
+                    dbus_api_tx
+                        .send(DbusApiEvent::TimerFinished)
+                        .unwrap_or_else(|e| error!("Could not send on a channel: {}", e));
+                }
+
+                if timer.is_active() {
+                    timer.render(&mut canvas, &device);
+
+                    any_updates = true;
+                }
+            }
+
+            if calendar::is_active() {
+                calendar::render(&mut canvas, &device);
+
+                any_updates = true;
+            }
+
+            // the temperature band is drawn on top of the calendar pulse
+            if weather::is_active() {
+                weather::render(&mut canvas, &device);
 
                 any_updates = true;
             }
@@ -310,6 +364,10 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
 
     let daemon = matches!(opts.command, Subcommands::Daemon);
 
+    let config_file = opts
+        .config
+        .unwrap_or_else(|| constants::FX_PROXY_CONFIG_FILE.to_string());
+
     if unsafe { libc::isatty(0) != 0 } && daemon {
         // initialize logging on console
         if env::var("RUST_LOG").is_err() {
@@ -367,17 +425,41 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             })
             .unwrap_or_else(|e| error!("Could not set CTRL-C handler: {}", e));
 
+            // process configuration file
+            let config = Config::builder()
+                .add_source(config::File::new(&config_file, config::FileFormat::Toml))
+                .build()
+                .unwrap_or_else(|e| {
+                    log::error!("Could not parse configuration file: {}", e);
+                    process::exit(4);
+                });
+
+            notifications::load_settings(&config);
+            weather::load_settings(&config);
+            calendar::load_settings(&config);
+            wallpaper::load_settings(&config);
+
             // initialize the D-Bus API
             let (dbus_tx, _dbus_rx) = unbounded();
-            let _dbus_api_tx = spawn_dbus_api_thread(dbus_tx)?;
+            let dbus_api_tx = spawn_dbus_api_thread(dbus_tx)?;
 
             // register all available screenshot backends
             backends::register_backends()?;
 
+            // flash the keyboard when a desktop notification is shown
+            notifications::spawn_notification_monitor_thread()?;
+
+            // fetch the data of the weather and calendar widgets
+            weather::spawn_weather_thread()?;
+            calendar::spawn_calendar_thread()?;
+
+            // keep the named color scheme "wallpaper" in sync with the desktop
+            wallpaper::spawn_wallpaper_thread()?;
+
             log::info!("Startup completed");
 
             // enter the main loop
-            run_main_loop(&ctrl_c_rx)
+            run_main_loop(&dbus_api_tx, &ctrl_c_rx)
                 .await
                 .unwrap_or_else(|e| error!("{}", e));
 
@@ -0,0 +1,331 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use config::Config;
+use dbus::arg::{PropMap, RefArg};
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::Connection as DbusConnection;
+use dbus::message::{MatchRule, MessageType};
+use dbus::Message;
+use eruption_sdk::canvas::Canvas;
+use eruption_sdk::connection::{Connection, ConnectionType};
+use lazy_static::lazy_static;
+use log::{debug, error, info, warn};
+use parking_lot::{Mutex, RwLock};
+
+use crate::util::argb_to_color;
+use crate::{constants, QUIT};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Settings of the notification bridge
+    pub static ref SETTINGS: Arc<RwLock<NotificationSettings>> =
+        Arc::new(RwLock::new(NotificationSettings::default()));
+
+    /// Do-not-disturb mode, set via D-Bus; suppresses all notification flashes
+    pub static ref DO_NOT_DISTURB: AtomicBool = AtomicBool::new(false);
+
+    /// Point in time of the most recent notification flash, used for rate limiting
+    static ref LAST_FLASH: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
+}
+
+/// The urgency levels defined by the desktop notifications specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    fn from_hint(value: Option<u64>) -> Self {
+        match value {
+            Some(0) => Urgency::Low,
+            Some(2) => Urgency::Critical,
+            _ => Urgency::Normal,
+        }
+    }
+}
+
+/// Settings of the notification bridge, loaded from the `[notifications]` section
+#[derive(Debug, Clone)]
+pub struct NotificationSettings {
+    /// Flash the keyboard when a desktop notification is shown
+    pub enabled: bool,
+
+    /// Colors of the flash for each of the urgency levels (ARGB)
+    pub low_color: u32,
+    pub normal_color: u32,
+    pub critical_color: u32,
+
+    /// Colors of the flash for specific applications (ARGB), indexed by the lower-case
+    /// application name or desktop entry; these take precedence over the urgency colors
+    pub app_colors: HashMap<String, u32>,
+
+    /// Duration of the flash
+    pub flash_millis: u64,
+
+    /// Minimum interval between two flashes
+    pub rate_limit_millis: u64,
+
+    /// Do not flash while the desktop environment is in do-not-disturb mode
+    pub respect_do_not_disturb: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            low_color: constants::DEFAULT_NOTIFICATION_LOW_COLOR,
+            normal_color: constants::DEFAULT_NOTIFICATION_NORMAL_COLOR,
+            critical_color: constants::DEFAULT_NOTIFICATION_CRITICAL_COLOR,
+            app_colors: HashMap::new(),
+            flash_millis: constants::DEFAULT_NOTIFICATION_FLASH_MILLIS,
+            rate_limit_millis: constants::DEFAULT_NOTIFICATION_RATE_LIMIT_MILLIS,
+            respect_do_not_disturb: true,
+        }
+    }
+}
+
+impl NotificationSettings {
+    /// Returns the color of the flash for a notification of the application
+    /// `app_name` (or `desktop_entry`) with the urgency level `urgency`
+    pub fn color_for(&self, app_name: &str, desktop_entry: Option<&str>, urgency: Urgency) -> u32 {
+        let app_color = [Some(app_name), desktop_entry]
+            .into_iter()
+            .flatten()
+            .find_map(|name| self.app_colors.get(&name.to_lowercase()));
+
+        match app_color {
+            Some(color) => *color,
+
+            None => match urgency {
+                Urgency::Low => self.low_color,
+                Urgency::Normal => self.normal_color,
+                Urgency::Critical => self.critical_color,
+            },
+        }
+    }
+}
+
+/// Load the settings of the notification bridge from the configuration
+pub fn load_settings(config: &Config) {
+    let defaults = NotificationSettings::default();
+
+    let app_colors = config
+        .get::<HashMap<String, u32>>("notifications.apps")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, color)| (name.to_lowercase(), color))
+        .collect();
+
+    let settings = NotificationSettings {
+        enabled: config
+            .get::<bool>("notifications.enabled")
+            .unwrap_or(defaults.enabled),
+        low_color: config
+            .get::<u32>("notifications.low_color")
+            .unwrap_or(defaults.low_color),
+        normal_color: config
+            .get::<u32>("notifications.normal_color")
+            .unwrap_or(defaults.normal_color),
+        critical_color: config
+            .get::<u32>("notifications.critical_color")
+            .unwrap_or(defaults.critical_color),
+        app_colors,
+        flash_millis: config
+            .get::<u64>("notifications.flash_millis")
+            .unwrap_or(defaults.flash_millis),
+        rate_limit_millis: config
+            .get::<u64>("notifications.rate_limit_millis")
+            .unwrap_or(defaults.rate_limit_millis),
+        respect_do_not_disturb: config
+            .get::<bool>("notifications.respect_do_not_disturb")
+            .unwrap_or(defaults.respect_do_not_disturb),
+    };
+
+    *SETTINGS.write() = settings;
+}
+
+/// Spawns a thread that monitors the session bus for desktop notifications
+/// and flashes the keyboard whenever a notification is shown
+pub fn spawn_notification_monitor_thread() -> Result<()> {
+    thread::Builder::new()
+        .name("notifications".into())
+        .spawn(move || {
+            run_notification_monitor()
+                .unwrap_or_else(|e| error!("Could not monitor desktop notifications: {}", e));
+        })?;
+
+    Ok(())
+}
+
+fn run_notification_monitor() -> Result<()> {
+    let conn = DbusConnection::new_session()?;
+
+    let rule = MatchRule::new()
+        .with_type(MessageType::MethodCall)
+        .with_interface("org.freedesktop.Notifications")
+        .with_member("Notify");
+
+    // a monitor receives copies of all messages that match the rule, but may not
+    // send any messages by itself from now on
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    proxy.method_call::<(), _, _, _>(
+        "org.freedesktop.DBus.Monitoring",
+        "BecomeMonitor",
+        (vec![rule.match_str()], 0u32),
+    )?;
+
+    info!("Monitoring desktop notifications");
+
+    conn.start_receive(
+        rule,
+        Box::new(|msg, _| {
+            handle_notification(&msg)
+                .unwrap_or_else(|e| warn!("Could not handle a desktop notification: {}", e));
+
+            true
+        }),
+    );
+
+    while !QUIT.load(Ordering::SeqCst) {
+        conn.process(Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS))?;
+    }
+
+    Ok(())
+}
+
+fn handle_notification(msg: &Message) -> Result<()> {
+    let settings = SETTINGS.read().clone();
+
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    let (app_name, _replaces_id, _app_icon, _summary, _body, _actions, hints): (
+        String,
+        u32,
+        String,
+        String,
+        String,
+        Vec<String>,
+        PropMap,
+    ) = msg.read_all()?;
+
+    let urgency = Urgency::from_hint(hints.get("urgency").and_then(|v| v.0.as_u64()));
+    let desktop_entry = hints.get("desktop-entry").and_then(|v| v.0.as_str());
+
+    debug!("Notification from '{}' (urgency: {:?})", app_name, urgency);
+
+    if is_do_not_disturb_active(&settings) {
+        debug!("Do-not-disturb mode is active, ignoring the notification");
+
+        return Ok(());
+    }
+
+    {
+        let mut last_flash = LAST_FLASH.lock();
+
+        let rate_limited = last_flash
+            .is_some_and(|t| t.elapsed() < Duration::from_millis(settings.rate_limit_millis));
+
+        if rate_limited {
+            debug!("Rate limit exceeded, ignoring the notification");
+
+            return Ok(());
+        }
+
+        *last_flash = Some(Instant::now());
+    }
+
+    let color = settings.color_for(&app_name, desktop_entry, urgency);
+
+    flash(color, Duration::from_millis(settings.flash_millis))
+}
+
+/// Returns `true` if flashes shall currently be suppressed, either because
+/// do-not-disturb mode has been enabled via D-Bus, or because the desktop
+/// environment is in do-not-disturb mode
+fn is_do_not_disturb_active(settings: &NotificationSettings) -> bool {
+    if DO_NOT_DISTURB.load(Ordering::SeqCst) {
+        return true;
+    }
+
+    if !settings.respect_do_not_disturb {
+        return false;
+    }
+
+    is_desktop_inhibited().unwrap_or(false) || is_gnome_banners_disabled().unwrap_or(false)
+}
+
+/// Query the `Inhibited` property of the notification server, as supported e.g. by KDE Plasma
+fn is_desktop_inhibited() -> Result<bool> {
+    // the monitor connection may not send any messages, so we use a separate connection
+    let conn = DbusConnection::new_session()?;
+
+    let proxy = conn.with_proxy(
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let inhibited: bool = proxy.get("org.freedesktop.Notifications", "Inhibited")?;
+
+    Ok(inhibited)
+}
+
+/// Query the GNOME "show-banners" setting, which is disabled in do-not-disturb mode
+fn is_gnome_banners_disabled() -> Result<bool> {
+    let output = Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.notifications", "show-banners"])
+        .output()?;
+
+    Ok(output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "false")
+}
+
+/// Flash all keys in the color `argb`, using an overlay that is removed
+/// by the Eruption daemon after `duration`
+fn flash(argb: u32, duration: Duration) -> Result<()> {
+    let connection = Connection::new(ConnectionType::Local)?;
+    connection.connect()?;
+
+    let mut canvas = Canvas::new();
+    canvas.fill(argb_to_color(argb));
+
+    connection.submit_overlay(&canvas, duration)?;
+    connection.disconnect()?;
+
+    Ok(())
+}
@@ -0,0 +1,244 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use eruption_sdk::{canvas::Canvas, color::Color};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+
+use crate::util::argb_to_color;
+use crate::{constants, hwdevices::KeyboardDevice};
+
+lazy_static! {
+    /// Global state of the countdown timer (pomodoro) effect
+    pub static ref TIMER: Arc<RwLock<Timer>> = Arc::new(RwLock::new(Timer::new()));
+}
+
+/// The states of the countdown timer effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerState {
+    Idle,
+    Running,
+    Paused,
+    Finished,
+}
+
+impl TimerState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimerState::Idle => "idle",
+            TimerState::Running => "running",
+            TimerState::Paused => "paused",
+            TimerState::Finished => "finished",
+        }
+    }
+}
+
+/// A countdown timer that renders its progress across the keyboard
+#[derive(Debug, Clone)]
+pub struct Timer {
+    /// Total duration of the countdown
+    pub duration: Duration,
+
+    /// Color of the keys that represent the remaining time (ARGB)
+    pub foreground: u32,
+
+    /// Color of the keys that represent the elapsed time (ARGB)
+    pub background: u32,
+
+    state: TimerState,
+
+    /// Point in time when the timer has been started or resumed
+    started_at: Option<Instant>,
+
+    /// Time that elapsed before the timer has been paused
+    elapsed: Duration,
+
+    /// Point in time when the countdown reached zero
+    finished_at: Option<Instant>,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self {
+            duration: Duration::from_secs(constants::DEFAULT_TIMER_DURATION_SECS),
+            foreground: constants::DEFAULT_TIMER_FOREGROUND_COLOR,
+            background: constants::DEFAULT_TIMER_BACKGROUND_COLOR,
+            state: TimerState::Idle,
+            started_at: None,
+            elapsed: Duration::ZERO,
+            finished_at: None,
+        }
+    }
+
+    pub fn state(&self) -> TimerState {
+        self.state
+    }
+
+    /// Returns `true` if the timer effect needs to be rendered
+    pub fn is_active(&self) -> bool {
+        self.state != TimerState::Idle
+    }
+
+    /// Start the countdown, or resume it if it has been paused
+    pub fn start(&mut self) {
+        match self.state {
+            TimerState::Running => (),
+
+            TimerState::Paused => {
+                self.started_at = Some(Instant::now());
+                self.state = TimerState::Running;
+            }
+
+            TimerState::Idle | TimerState::Finished => {
+                self.elapsed = Duration::ZERO;
+                self.finished_at = None;
+                self.started_at = Some(Instant::now());
+                self.state = TimerState::Running;
+            }
+        }
+    }
+
+    /// Pause a running countdown
+    pub fn pause(&mut self) {
+        if self.state == TimerState::Running {
+            self.elapsed = self.elapsed();
+            self.started_at = None;
+            self.state = TimerState::Paused;
+        }
+    }
+
+    /// Stop the countdown and clear all progress
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+        self.started_at = None;
+        self.finished_at = None;
+        self.state = TimerState::Idle;
+    }
+
+    /// Returns the amount of time that has elapsed since the countdown has been started
+    pub fn elapsed(&self) -> Duration {
+        let elapsed = match self.started_at {
+            Some(started_at) => self.elapsed + started_at.elapsed(),
+            None => self.elapsed,
+        };
+
+        elapsed.min(self.duration)
+    }
+
+    /// Returns the amount of time that remains until the countdown reaches zero
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.elapsed())
+    }
+
+    /// Returns the remaining fraction of the countdown in the range `0.0..=1.0`
+    pub fn progress(&self) -> f64 {
+        if self.duration.is_zero() {
+            0.0
+        } else {
+            self.remaining().as_secs_f64() / self.duration.as_secs_f64()
+        }
+    }
+
+    /// Advance the state machine, returns `true` if the countdown just reached zero
+    pub fn update(&mut self) -> bool {
+        match self.state {
+            TimerState::Running if self.remaining().is_zero() => {
+                self.elapsed = self.duration;
+                self.started_at = None;
+                self.finished_at = Some(Instant::now());
+                self.state = TimerState::Finished;
+
+                true
+            }
+
+            TimerState::Finished => {
+                let expired = match self.finished_at {
+                    Some(t) => {
+                        t.elapsed() >= Duration::from_millis(constants::TIMER_FINISHED_BLINK_MILLIS)
+                    }
+                    None => true,
+                };
+
+                if expired {
+                    self.reset();
+                }
+
+                false
+            }
+
+            _ => false,
+        }
+    }
+
+    /// Render the progress of the countdown to the canvas. The remaining time is
+    /// shown as a bar of keys that shrinks from the right to the left, a finished
+    /// countdown is indicated by blinking all keys
+    pub fn render(&self, canvas: &mut Canvas, device: &KeyboardDevice) {
+        let foreground = argb_to_color(self.foreground);
+        let background = argb_to_color(self.background);
+
+        let num_cols = device.get_num_cols();
+        let num_rows = device.get_num_rows();
+
+        let lit_cols = match self.state {
+            TimerState::Finished => {
+                let blink_on = match self.finished_at {
+                    Some(t) => {
+                        (t.elapsed().as_millis() / constants::TIMER_BLINK_INTERVAL_MILLIS as u128)
+                            % 2
+                            == 0
+                    }
+                    None => false,
+                };
+
+                if blink_on {
+                    num_cols
+                } else {
+                    0
+                }
+            }
+
+            _ => (self.progress() * num_cols as f64).ceil() as usize,
+        };
+
+        for x in 0..num_cols {
+            for y in 0..num_rows {
+                let key_index: usize =
+                    (device.get_rows_topology()[x + (y * (num_cols + 1))]) as usize + 1;
+
+                if !(1..=device.get_num_keys()).contains(&key_index) {
+                    continue;
+                }
+
+                canvas[key_index] = if x < lit_cols { foreground } else { background };
+            }
+        }
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -23,48 +23,10 @@ use crate::{
     dbus_client,
     hwdevices::{self, KeyboardDevice},
 };
-use eruption_sdk::{canvas::Canvas, color::Color};
-use image::{imageops::FilterType, DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use eruption_sdk::color::Color;
 
 type Result<T> = std::result::Result<T, eyre::Error>;
 
-/// Converts an image buffer to fit a specific device topology
-pub fn process_image_buffer(
-    buffer: ImageBuffer<Rgba<u8>, Vec<u8>>,
-    device: &KeyboardDevice,
-) -> Result<Canvas> {
-    let mut result = Canvas::new();
-
-    let img = DynamicImage::ImageRgba8(buffer);
-    let img = img.resize_exact(
-        device.get_num_cols() as u32,
-        device.get_num_rows() as u32,
-        FilterType::Gaussian,
-    );
-
-    for x in 0..device.get_num_cols() {
-        for y in 0..device.get_num_rows() {
-            let key_index: usize =
-                (device.get_rows_topology()[x + (y * (device.get_num_cols() + 1))]) as usize + 1;
-
-            if !(1..=device.get_num_keys()).contains(&key_index) {
-                continue;
-            }
-
-            let pixel = img.get_pixel(x as u32, y as u32);
-
-            let r = pixel[0];
-            let g = pixel[1];
-            let b = pixel[2];
-            let a = pixel[3];
-
-            result[key_index] = Color::new(r, g, b, a);
-        }
-    }
-
-    Ok(result)
-}
-
 // pub fn blend(_canvas: &mut Canvas, _src: &Canvas) {
 //     let brightness = 1.0;
 
@@ -93,3 +55,13 @@ pub fn get_primary_keyboard_device() -> Result<KeyboardDevice> {
 
     Ok(device)
 }
+
+/// Converts an ARGB color value to a color of the SDK
+pub fn argb_to_color(argb: u32) -> Color {
+    Color::new(
+        ((argb >> 16) & 0xff) as u8,
+        ((argb >> 8) & 0xff) as u8,
+        (argb & 0xff) as u8,
+        ((argb >> 24) & 0xff) as u8,
+    )
+}
@@ -0,0 +1,371 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::cmp::Reverse;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use config::Config;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use parking_lot::RwLock;
+
+use crate::{constants, dbus_client, QUIT};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Settings of the wallpaper palette extraction
+    pub static ref SETTINGS: Arc<RwLock<WallpaperSettings>> =
+        Arc::new(RwLock::new(WallpaperSettings::default()));
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WallpaperError {
+    #[error("Could not determine the current wallpaper")]
+    UnknownWallpaper {},
+}
+
+/// Settings of the wallpaper palette extraction, loaded from the `[wallpaper]` section
+#[derive(Debug, Clone)]
+pub struct WallpaperSettings {
+    /// Update the named color scheme "wallpaper" whenever the wallpaper changes
+    pub enabled: bool,
+
+    /// The wallpaper image, if not set it is looked up in the settings of the desktop
+    pub path: Option<PathBuf>,
+
+    /// A shell command, that prints the path or the URI of the current wallpaper
+    pub command: Option<String>,
+
+    /// Number of colors of the extracted palette
+    pub colors: usize,
+
+    /// Interval between two checks whether the wallpaper changed
+    pub refresh_secs: u64,
+}
+
+impl Default for WallpaperSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: None,
+            command: None,
+            colors: constants::DEFAULT_WALLPAPER_COLORS,
+            refresh_secs: constants::DEFAULT_WALLPAPER_REFRESH_SECS,
+        }
+    }
+}
+
+/// Load the settings of the wallpaper palette extraction from the configuration
+pub fn load_settings(config: &Config) {
+    let defaults = WallpaperSettings::default();
+
+    let settings = WallpaperSettings {
+        enabled: config
+            .get::<bool>("wallpaper.enabled")
+            .unwrap_or(defaults.enabled),
+        path: config
+            .get::<String>("wallpaper.path")
+            .ok()
+            .map(PathBuf::from),
+        command: config.get::<String>("wallpaper.command").ok(),
+        colors: config
+            .get::<usize>("wallpaper.colors")
+            .unwrap_or(defaults.colors)
+            .clamp(2, constants::MAX_WALLPAPER_COLORS),
+        refresh_secs: config
+            .get::<u64>("wallpaper.refresh_secs")
+            .unwrap_or(defaults.refresh_secs),
+    };
+
+    *SETTINGS.write() = settings;
+}
+
+/// Spawns a thread that watches the wallpaper, and updates the named color scheme
+/// "wallpaper" with a palette extracted from it whenever it changes
+pub fn spawn_wallpaper_thread() -> Result<()> {
+    thread::Builder::new()
+        .name("wallpaper".into())
+        .spawn(move || {
+            let mut last_update: Option<Instant> = None;
+
+            // the wallpaper and its modification time that the palette has been extracted from
+            let mut current: Option<(PathBuf, Option<SystemTime>)> = None;
+
+            while !QUIT.load(Ordering::SeqCst) {
+                let settings = SETTINGS.read().clone();
+
+                let due = last_update
+                    .is_none_or(|t| t.elapsed() >= Duration::from_secs(settings.refresh_secs));
+
+                if settings.enabled && due {
+                    last_update = Some(Instant::now());
+
+                    match find_wallpaper(&settings) {
+                        Ok(path) => {
+                            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                            let wallpaper = Some((path.clone(), modified));
+
+                            if wallpaper != current {
+                                match update_color_scheme(&path, settings.colors) {
+                                    Ok(()) => current = wallpaper,
+
+                                    Err(e) => warn!(
+                                        "Could not extract a palette from the wallpaper {}: {}",
+                                        path.display(),
+                                        e
+                                    ),
+                                }
+                            }
+                        }
+
+                        Err(e) => debug!("{}", e),
+                    }
+                }
+
+                thread::sleep(Duration::from_millis(constants::WIDGET_POLL_MILLIS));
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Extract a palette from the wallpaper at `path`, and submit it to the Eruption daemon
+fn update_color_scheme(path: &Path, count: usize) -> Result<()> {
+    let image = image::open(path)?
+        .thumbnail(
+            constants::WALLPAPER_SAMPLE_SIZE,
+            constants::WALLPAPER_SAMPLE_SIZE,
+        )
+        .to_rgb8();
+
+    let pixels = image.pixels().map(|p| p.0).collect::<Vec<_>>();
+    let palette = extract_palette(&pixels, count);
+
+    info!(
+        "Updating the color scheme '{}' from the wallpaper {}",
+        constants::WALLPAPER_COLOR_SCHEME,
+        path.display()
+    );
+
+    let data = palette
+        .iter()
+        .flat_map(|[r, g, b]| [*r, *g, *b, 0xff])
+        .collect::<Vec<_>>();
+
+    dbus_client::set_color_scheme(constants::WALLPAPER_COLOR_SCHEME, data)?;
+
+    Ok(())
+}
+
+/// Returns the path of the current wallpaper
+fn find_wallpaper(settings: &WallpaperSettings) -> Result<PathBuf> {
+    if let Some(path) = settings.path.as_ref() {
+        return Ok(path.clone());
+    }
+
+    let uri = if let Some(command) = settings.command.as_ref() {
+        let output = Command::new("sh").args(["-c", command]).output()?;
+
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        query_gnome_wallpaper().or_else(query_plasma_wallpaper)
+    };
+
+    uri.filter(|uri| !uri.is_empty())
+        .map(|uri| uri_to_path(&uri))
+        .filter(|path| path.is_file())
+        .ok_or_else(|| WallpaperError::UnknownWallpaper {}.into())
+}
+
+/// Query the wallpaper of GNOME and derived desktops, honoring the dark style preference
+fn query_gnome_wallpaper() -> Option<String> {
+    let gsettings = |schema: &str, key: &str| -> Option<String> {
+        let output = Command::new("gsettings")
+            .args(["get", schema, key])
+            .output()
+            .ok()?;
+
+        if output.status.success() {
+            Some(
+                String::from_utf8_lossy(&output.stdout)
+                    .trim()
+                    .trim_matches('\'')
+                    .to_string(),
+            )
+        } else {
+            None
+        }
+    };
+
+    let prefer_dark = gsettings("org.gnome.desktop.interface", "color-scheme")
+        .map_or(false, |scheme| scheme == "prefer-dark");
+
+    let dark = if prefer_dark {
+        gsettings("org.gnome.desktop.background", "picture-uri-dark")
+    } else {
+        None
+    };
+
+    dark.filter(|uri| !uri.is_empty())
+        .or_else(|| gsettings("org.gnome.desktop.background", "picture-uri"))
+}
+
+/// Query the wallpaper of the Plasma desktop, from the configuration of its containments
+fn query_plasma_wallpaper() -> Option<String> {
+    let config_dir = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok()?;
+
+    let data =
+        fs::read_to_string(config_dir.join("plasma-org.kde.plasma.desktop-appletsrc")).ok()?;
+
+    data.lines()
+        .filter_map(|line| line.trim().strip_prefix("Image="))
+        .map(|uri| uri.to_string())
+        .next()
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    PathBuf::from(percent_decode(path))
+}
+
+/// Decode the percent-encoded characters of a file URI
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 3 <= bytes.len() {
+            if let Some(byte) = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                result.push(byte);
+                i += 3;
+
+                continue;
+            }
+        }
+
+        result.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&result).to_string()
+}
+
+/// Extract a palette of `count` colors using the median cut algorithm, the colors
+/// are ordered by the share of the image that they represent
+fn extract_palette(pixels: &[[u8; 3]], count: usize) -> Vec<[u8; 3]> {
+    if pixels.is_empty() {
+        return vec![];
+    }
+
+    let mut boxes = vec![pixels.to_vec()];
+
+    while boxes.len() < count {
+        // split the box with the widest range of a color channel
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_index, pixels)| pixels.len() > 1)
+            .map(|(index, pixels)| {
+                let (channel, range) = widest_channel(pixels);
+                (index, channel, range)
+            })
+            .max_by_key(|(_index, _channel, range)| *range);
+
+        match widest {
+            Some((index, channel, range)) if range > 0 => {
+                let mut pixels = boxes.swap_remove(index);
+                pixels.sort_unstable_by_key(|p| p[channel]);
+
+                let upper = pixels.split_off(pixels.len() / 2);
+
+                boxes.push(pixels);
+                boxes.push(upper);
+            }
+
+            // all remaining boxes are uniformly colored
+            _ => break,
+        }
+    }
+
+    boxes.sort_by_key(|pixels| Reverse(pixels.len()));
+
+    boxes.iter().map(|pixels| average(pixels)).collect()
+}
+
+/// Returns the color channel with the widest range of values, along with the range
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let min = pixels.iter().map(|p| p[channel]).min().unwrap_or(0);
+            let max = pixels.iter().map(|p| p[channel]).max().unwrap_or(0);
+
+            (channel, max - min)
+        })
+        .max_by_key(|(_channel, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+fn average(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+
+    for p in pixels {
+        for (sum, value) in sum.iter_mut().zip(p.iter()) {
+            *sum += *value as u64;
+        }
+    }
+
+    let len = pixels.len().max(1) as u64;
+
+    [
+        (sum[0] / len) as u8,
+        (sum[1] / len) as u8,
+        (sum[2] / len) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_escapes() {
+        assert_eq!(percent_decode("/a%20b"), "/a b");
+        assert_eq!(percent_decode("/%C3%A4%41"), "/äA");
+        assert_eq!(percent_decode("/50%"), "/50%");
+        assert_eq!(percent_decode("/50%4"), "/50%4");
+    }
+}
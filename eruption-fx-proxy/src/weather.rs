@@ -0,0 +1,302 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::process::Command;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use config::Config;
+use eruption_sdk::canvas::Canvas;
+use lazy_static::lazy_static;
+use log::{debug, error, warn};
+use parking_lot::RwLock;
+
+use crate::util::argb_to_color;
+use crate::{constants, hwdevices::KeyboardDevice, QUIT};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Settings of the weather widget
+    pub static ref SETTINGS: Arc<RwLock<WeatherSettings>> =
+        Arc::new(RwLock::new(WeatherSettings::default()));
+
+    /// The most recent weather report
+    pub static ref WEATHER: Arc<RwLock<Option<WeatherReport>>> = Arc::new(RwLock::new(None));
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WeatherError {
+    #[error("Unknown weather provider: {name}")]
+    UnknownProvider { name: String },
+
+    #[error("Invalid weather data: {description}")]
+    InvalidData { description: String },
+}
+
+/// The sources that the current temperature may be fetched from
+#[derive(Debug, Clone, PartialEq)]
+pub enum WeatherProvider {
+    /// The Open-Meteo web service, queried for the configured location
+    OpenMeteo { latitude: f64, longitude: f64 },
+
+    /// A shell command, that prints the current temperature in degrees Celsius
+    Command { command: String },
+}
+
+/// Settings of the weather widget, loaded from the `[weather]` section
+#[derive(Debug, Clone)]
+pub struct WeatherSettings {
+    /// Render the current temperature as a color-coded band of keys
+    pub enabled: bool,
+
+    pub provider: Option<WeatherProvider>,
+
+    /// Interval between two updates of the weather report
+    pub refresh_secs: u64,
+
+    /// Temperatures at or below `cold_temperature` are rendered blue, temperatures
+    /// at or above `hot_temperature` are rendered red (degrees Celsius)
+    pub cold_temperature: f64,
+    pub hot_temperature: f64,
+
+    /// The row of keys that the band is rendered to
+    pub row: usize,
+}
+
+impl Default for WeatherSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: None,
+            refresh_secs: constants::DEFAULT_WEATHER_REFRESH_SECS,
+            cold_temperature: constants::DEFAULT_WEATHER_COLD_TEMPERATURE,
+            hot_temperature: constants::DEFAULT_WEATHER_HOT_TEMPERATURE,
+            row: 0,
+        }
+    }
+}
+
+/// The current weather, as reported by the provider
+#[derive(Debug, Clone, Copy)]
+pub struct WeatherReport {
+    /// Temperature in degrees Celsius
+    pub temperature: f64,
+
+    /// Point in time when the report has been fetched
+    pub updated_at: Instant,
+}
+
+/// Load the settings of the weather widget from the configuration
+pub fn load_settings(config: &Config) {
+    let defaults = WeatherSettings::default();
+
+    let provider = match load_provider(config) {
+        Ok(provider) => provider,
+
+        Err(e) => {
+            error!("Could not load the weather provider: {}", e);
+            None
+        }
+    };
+
+    let settings = WeatherSettings {
+        enabled: config
+            .get::<bool>("weather.enabled")
+            .unwrap_or(defaults.enabled),
+        provider,
+        refresh_secs: config
+            .get::<u64>("weather.refresh_secs")
+            .unwrap_or(defaults.refresh_secs),
+        cold_temperature: config
+            .get::<f64>("weather.cold_temperature")
+            .unwrap_or(defaults.cold_temperature),
+        hot_temperature: config
+            .get::<f64>("weather.hot_temperature")
+            .unwrap_or(defaults.hot_temperature),
+        row: config.get::<usize>("weather.row").unwrap_or(defaults.row),
+    };
+
+    *SETTINGS.write() = settings;
+}
+
+fn load_provider(config: &Config) -> Result<Option<WeatherProvider>> {
+    let name = match config.get::<String>("weather.provider") {
+        Ok(name) => name,
+        Err(_) => return Ok(None),
+    };
+
+    let provider = match name.as_str() {
+        "open-meteo" => WeatherProvider::OpenMeteo {
+            latitude: config.get::<f64>("weather.latitude")?,
+            longitude: config.get::<f64>("weather.longitude")?,
+        },
+
+        "command" => WeatherProvider::Command {
+            command: config.get::<String>("weather.command")?,
+        },
+
+        _ => return Err(WeatherError::UnknownProvider { name }.into()),
+    };
+
+    Ok(Some(provider))
+}
+
+/// Spawns a thread that periodically fetches the current weather from the configured provider
+pub fn spawn_weather_thread() -> Result<()> {
+    thread::Builder::new()
+        .name("weather".into())
+        .spawn(move || {
+            let mut last_update: Option<Instant> = None;
+
+            while !QUIT.load(Ordering::SeqCst) {
+                let settings = SETTINGS.read().clone();
+
+                let due = last_update
+                    .is_none_or(|t| t.elapsed() >= Duration::from_secs(settings.refresh_secs));
+
+                if settings.enabled && due {
+                    if let Some(provider) = settings.provider.as_ref() {
+                        last_update = Some(Instant::now());
+
+                        match fetch_temperature(provider) {
+                            Ok(temperature) => {
+                                debug!("Current temperature: {:.1} °C", temperature);
+
+                                *WEATHER.write() = Some(WeatherReport {
+                                    temperature,
+                                    updated_at: Instant::now(),
+                                });
+                            }
+
+                            Err(e) => warn!("Could not fetch the current weather: {}", e),
+                        }
+                    }
+                }
+
+                thread::sleep(Duration::from_millis(constants::WIDGET_POLL_MILLIS));
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Fetch the current temperature in degrees Celsius from `provider`
+fn fetch_temperature(provider: &WeatherProvider) -> Result<f64> {
+    match provider {
+        WeatherProvider::OpenMeteo {
+            latitude,
+            longitude,
+        } => {
+            let response: serde_json::Value = ureq::get(constants::OPEN_METEO_URL)
+                .query("latitude", &latitude.to_string())
+                .query("longitude", &longitude.to_string())
+                .query("current_weather", "true")
+                .timeout(Duration::from_millis(constants::WIDGET_HTTP_TIMEOUT_MILLIS))
+                .call()?
+                .into_json()?;
+
+            response["current_weather"]["temperature"]
+                .as_f64()
+                .ok_or_else(|| {
+                    WeatherError::InvalidData {
+                        description: "The response lacks the current temperature".to_string(),
+                    }
+                    .into()
+                })
+        }
+
+        WeatherProvider::Command { command } => {
+            let output = Command::new("sh").args(["-c", command]).output()?;
+            let output = String::from_utf8_lossy(&output.stdout);
+
+            output.trim().parse::<f64>().map_err(|_| {
+                WeatherError::InvalidData {
+                    description: format!("Not a temperature: '{}'", output.trim()),
+                }
+                .into()
+            })
+        }
+    }
+}
+
+/// Returns `true` if the weather widget needs to be rendered
+pub fn is_active() -> bool {
+    SETTINGS.read().enabled && WEATHER.read().is_some()
+}
+
+/// Render the current temperature to the canvas, as a band of keys whose color
+/// ranges from blue (cold) over green to red (hot)
+pub fn render(canvas: &mut Canvas, device: &KeyboardDevice) {
+    let settings = SETTINGS.read();
+
+    let report = match *WEATHER.read() {
+        Some(report) => report,
+        None => return,
+    };
+
+    let color = argb_to_color(temperature_color(
+        report.temperature,
+        settings.cold_temperature,
+        settings.hot_temperature,
+    ));
+
+    let num_cols = device.get_num_cols();
+    let y = settings.row.min(device.get_num_rows().saturating_sub(1));
+
+    for x in 0..num_cols {
+        let key_index: usize = (device.get_rows_topology()[x + (y * (num_cols + 1))]) as usize + 1;
+
+        if !(1..=device.get_num_keys()).contains(&key_index) {
+            continue;
+        }
+
+        canvas[key_index] = color;
+    }
+}
+
+/// Map `temperature` to a color (ARGB), by sweeping the hue from blue at `cold`
+/// over green to red at `hot`
+fn temperature_color(temperature: f64, cold: f64, hot: f64) -> u32 {
+    let p = if hot > cold {
+        ((temperature - cold) / (hot - cold)).clamp(0.0, 1.0)
+    } else {
+        0.5
+    };
+
+    let hue = 240.0 * (1.0 - p);
+
+    // convert the fully saturated hue to RGB
+    let h = hue / 60.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        _ => (x, 0.0, 1.0),
+    };
+
+    0xff000000 | (((r * 255.0) as u32) << 16) | (((g * 255.0) as u32) << 8) | ((b * 255.0) as u32)
+}
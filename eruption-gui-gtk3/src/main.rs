@@ -53,6 +53,7 @@ mod error_log;
 mod preferences;
 mod profiles;
 mod scripting;
+mod state_file;
 mod ui;
 mod util;
 
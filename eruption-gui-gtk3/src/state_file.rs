@@ -0,0 +1 @@
+../../eruption/src/state_file.rs
\ No newline at end of file
@@ -89,6 +89,7 @@ macro_rules! declare_config_widget_numeric {
                 default: i64,
                 min: Option<i64>,
                 max: Option<i64>,
+                step: Option<i64>,
                 value:i64,
                 callback: F,
             ) -> Result<gtk::Box> {
@@ -154,7 +155,7 @@ macro_rules! declare_config_widget_numeric {
                 let mut adjustment = gtk::Adjustment::builder();
 
                 adjustment = adjustment.value(value as f64);
-                adjustment = adjustment.step_increment(1.0);
+                adjustment = adjustment.step_increment(step.unwrap_or(1) as f64);
 
                 if let Some(min) = min {
                     adjustment = adjustment.lower(min as f64);
@@ -177,7 +178,15 @@ macro_rules! declare_config_widget_numeric {
                 row2.pack_start(&scale, false, true, 8);
 
                 scale.connect_value_changed(move |c| {
-                    let value = c.value() as i64;
+                    let value = match step {
+                        Some(step) if step > 0 => {
+                            let base = min.unwrap_or(0);
+                            base + ((c.value() - base as f64) / step as f64).round() as i64 * step
+                        }
+
+                        _ => c.value() as i64,
+                    };
+
                     callback(value);
                 });
 
@@ -198,6 +207,7 @@ macro_rules! declare_config_widget_numeric {
                 default: $t,
                 min: Option<$t>,
                 max: Option<$t>,
+                step: Option<$t>,
                 value: $t,
                 callback: F,
             ) -> Result<gtk::Box> {
@@ -263,7 +273,7 @@ macro_rules! declare_config_widget_numeric {
                 let mut adjustment = gtk::Adjustment::builder();
 
                 adjustment = adjustment.value(value as f64);
-                adjustment = adjustment.step_increment(0.01);
+                adjustment = adjustment.step_increment(step.map(|s| s as f64).unwrap_or(0.01));
 
                 if let Some(min) = min {
                     adjustment = adjustment.lower(min as f64);
@@ -484,6 +494,101 @@ macro_rules! declare_config_widget_color {
     };
 }
 
+macro_rules! declare_config_widget_enum {
+    ($t:ty) => {
+        paste! {
+            fn [<build_config_widget_enum_ $t:lower>] <F: Fn($t) + 'static>(
+                name: &str,
+                description: &str,
+                default: String,
+                variants: &[String],
+                value: String,
+                callback: F,
+            ) -> Result<gtk::Box> {
+                let container = gtk::Box::builder()
+                    .border_width(16)
+                    .halign(Align::Fill)
+                    .valign(Align::Fill)
+                    .orientation(Orientation::Vertical)
+                    .homogeneous(false)
+                    .build();
+
+                let row1 = gtk::Box::builder()
+                    .halign(Align::Fill)
+                    .valign(Align::Fill)
+                    .spacing(8)
+                    .orientation(Orientation::Horizontal)
+                    .homogeneous(false)
+                    .build();
+
+                container.pack_start(&row1, true, true, 8);
+
+                let row2 = gtk::Box::builder()
+                    .halign(Align::Fill)
+                    .valign(Align::Fill)
+                    .spacing(8)
+                    .orientation(Orientation::Horizontal)
+                    .homogeneous(false)
+                    .build();
+
+                container.pack_start(&row2, true, true, 8);
+
+                let label = gtk::Label::builder()
+                    .expand(false)
+                    .halign(Align::Start)
+                    .justify(Justification::Left)
+                    .use_markup(true)
+                    .label(&format!("<b>{}</b>", name))
+                    .build();
+
+                row1.pack_start(&label, false, false, 8);
+
+                let label = gtk::Label::builder()
+                    .expand(false)
+                    .halign(Align::Start)
+                    .justify(Justification::Left)
+                    .label(description)
+                    .build();
+
+                row1.pack_start(&label, false, false, 8);
+
+                // "reset to default value" button
+                let image = Image::from_icon_name(Some("reload"), IconSize::Button);
+                let reset_button = gtk::Button::builder()
+                    .halign(Align::Start)
+                    .image(&image)
+                    .tooltip_text("Reset this parameter to its default value")
+                    .build();
+
+                row2.pack_start(&reset_button, false, false, 8);
+
+                // combo box widget
+                let combo_box = gtk::ComboBoxText::new();
+
+                for variant in variants {
+                    combo_box.append(Some(variant), variant);
+                }
+
+                combo_box.set_active_id(Some(&value));
+
+                row2.pack_start(&combo_box, false, true, 8);
+
+                combo_box.connect_changed(move |c| {
+                    if let Some(value) = c.active_id() {
+                        callback(value.to_string());
+                    }
+                });
+
+                reset_button.connect_clicked(clone!(@weak combo_box, @strong default => move |_b| {
+                    combo_box.set_active_id(Some(&default));
+                }));
+
+                Ok(container)
+            }
+        }
+    };
+}
+
 macro_rules! declare_config_widget_switch {
     ($t:ty) => {
         paste! {
@@ -579,6 +684,7 @@ declare_config_widget_numeric!(i64);
 declare_config_widget_numeric!(f64);
 
 declare_config_widget_input!(String);
+declare_config_widget_enum!(String);
 declare_config_widget_color!(u32);
 declare_config_widget_switch!(bool);
 
@@ -623,48 +729,74 @@ fn create_config_editor(
     };
 
     let widget = match (profile_value_or_default, &manifest_parameter.manifest) {
-        (TypedValue::Int(value), ManifestValue::Int { min, max, default }) => {
-            build_config_widget_i64(
-                name,
-                description,
-                *default,
-                *min,
-                *max,
-                value,
-                clone!(@strong profile, @strong script, @strong name => move |value| {
-                    parameter_changed(&profile, &script, &name, value);
-                }),
-            )
-        }
-        (TypedValue::Float(value), ManifestValue::Float { min, max, default }) => {
-            build_config_widget_f64(
+        (
+            TypedValue::Int(value),
+            ManifestValue::Int {
+                min,
+                max,
+                step,
+                default,
+            },
+        ) => build_config_widget_i64(
+            name,
+            description,
+            *default,
+            *min,
+            *max,
+            *step,
+            value,
+            clone!(@strong profile, @strong script, @strong name => move |value| {
+                parameter_changed(&profile, &script, &name, value);
+            }),
+        ),
+        (
+            TypedValue::Float(value),
+            ManifestValue::Float {
+                min,
+                max,
+                step,
+                default,
+            },
+        ) => build_config_widget_f64(
+            name,
+            description,
+            *default,
+            *min,
+            *max,
+            *step,
+            value,
+            clone!(@strong profile, @strong script, @strong name => move |value| {
+                parameter_changed(&profile, &script, &name, value);
+            }),
+        ),
+        (TypedValue::Bool(value), ManifestValue::Bool { default }) => {
+            build_config_widget_switch_bool(
                 name,
                 description,
                 *default,
-                *min,
-                *max,
                 value,
                 clone!(@strong profile, @strong script, @strong name => move |value| {
                     parameter_changed(&profile, &script, &name, value);
                 }),
             )
         }
-        (TypedValue::Bool(value), ManifestValue::Bool { default }) => {
-            build_config_widget_switch_bool(
+        (TypedValue::String(value), ManifestValue::String { default }) => {
+            build_config_widget_input_string(
                 name,
                 description,
-                *default,
+                default.to_owned(),
                 value,
                 clone!(@strong profile, @strong script, @strong name => move |value| {
                     parameter_changed(&profile, &script, &name, value);
                 }),
             )
         }
-        (TypedValue::String(value), ManifestValue::String { default }) => {
-            build_config_widget_input_string(
+        (TypedValue::String(value), ManifestValue::Enum { default, variants }) => {
+            build_config_widget_enum_string(
                 name,
                 description,
                 default.to_owned(),
+                variants,
                 value,
                 clone!(@strong profile, @strong script, @strong name => move |value| {
                     parameter_changed(&profile, &script, &name, value);
@@ -1,43 +0,0 @@
-#    SPDX-License-Identifier: GPL-3.0-or-later
-#
-#    This file is part of Eruption.
-#
-#    Eruption is free software: you can redistribute it and/or modify
-#    it under the terms of the GNU General Public License as published by
-#    the Free Software Foundation, either version 3 of the License, or
-#    (at your option) any later version.
-#
-#    Eruption is distributed in the hope that it will be useful,
-#    but WITHOUT ANY WARRANTY; without even the implied warranty of
-#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
-#    GNU General Public License for more details.
-#
-#    You should have received a copy of the GNU General Public License
-#    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
-#
-#    Copyright (c) 2019-2022, The Eruption Development Team
-
-
-## eruption-hotplug-helper
-
-about = Eine Hilfsanwendung die Eruption über neu angeschlossene Geräte informiert
-verbose-about = Verboser Modus (-v, -vv, -vvv, etc.)
-hotplug-about = Ein "Hotplug" Ereignis auslösen
-completions-about = Shell Definitionen für Autovervollständigung erzeugen
-
-license-header = Eruption ist freie Software. Sie können es unter den Bedingungen der
- GNU General Public License, wie von der Free Software Foundation
- veröffentlicht, weitergeben und/oder modifizieren, entweder gemäß
- Version 3 der Lizenz oder (nach Ihrer Option) jeder späteren Version.
-
- Die Veröffentlichung von Eruption erfolgt in der Hoffnung,
- daß es Ihnen von Nutzen sein wird, aber OHNE IRGENDEINE GARANTIE,
- sogar ohne die implizite Garantie der MARKTREIFE oder der
- VERWENDBARKEIT FÜR EINEN BESTIMMTEN ZWECK.
- Details finden Sie in der GNU General Public License.
-
- Sie sollten ein Exemplar der GNU General Public License zusammen
- mit diesem Programm erhalten haben. Falls nicht,
- siehe <http://www.gnu.org/licenses/>.
-
- Copyright (c) 2019-2022, Das Eruption Entwicklerteam
@@ -1 +0,0 @@
-../../eruption/src/constants.rs
\ No newline at end of file
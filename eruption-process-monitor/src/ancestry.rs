@@ -0,0 +1,188 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Tracks the process subtrees of processes that have been matched by a rule with
+//! `include_descendants` set, so that the previous state is only restored after the
+//! matched process and all of its descendants have exited
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::util;
+
+lazy_static! {
+    /// Maps the pid of each member of a tracked subtree to the pid of its root process
+    static ref MEMBERS: Arc<RwLock<HashMap<i32, i32>>> = Arc::new(RwLock::new(HashMap::new()));
+
+    /// The live members of each tracked subtree, indexed by the pid of its root process
+    static ref SUBTREES: Arc<RwLock<HashMap<i32, HashSet<i32>>>> = Arc::new(RwLock::new(HashMap::new()));
+}
+
+/// Outcome of the exit of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitOutcome {
+    /// The process is not part of a tracked subtree
+    Untracked,
+
+    /// Other members of the subtree rooted at `root` are still alive
+    SubtreeAlive { root: i32 },
+
+    /// The last member of the subtree rooted at `root` exited
+    SubtreeExited { root: i32 },
+}
+
+/// Start tracking the subtree rooted at the process `root`
+pub fn track(root: i32) {
+    let mut members = MEMBERS.write();
+    let mut subtrees = SUBTREES.write();
+
+    // the process may already be a descendant of another tracked process
+    if let Some(previous_root) = members.insert(root, root) {
+        if let Some(subtree) = subtrees.get_mut(&previous_root) {
+            subtree.remove(&root);
+        }
+    }
+
+    subtrees.entry(root).or_default().insert(root);
+}
+
+/// Returns `true` if no subtrees are being tracked
+fn is_empty() -> bool {
+    MEMBERS.read().is_empty()
+}
+
+/// Record the fork of the process `child`. The child joins the subtree of its parent, if
+/// the parent is tracked. `parent` may refer to a thread of the parent process
+pub fn on_fork(parent: i32, child: i32) {
+    if is_empty() {
+        return;
+    }
+
+    let root = {
+        let members = MEMBERS.read();
+
+        members.get(&parent).copied().or_else(|| {
+            util::get_process_tgid(parent)
+                .ok()
+                .and_then(|tgid| members.get(&tgid).copied())
+        })
+    };
+
+    if let Some(root) = root {
+        MEMBERS.write().insert(child, root);
+        SUBTREES.write().entry(root).or_default().insert(child);
+    }
+}
+
+/// Record the exit of the process `pid`
+pub fn on_exit(pid: i32) -> ExitOutcome {
+    let root = match MEMBERS.write().remove(&pid) {
+        Some(root) => root,
+        None => return ExitOutcome::Untracked,
+    };
+
+    let mut subtrees = SUBTREES.write();
+
+    match subtrees.get_mut(&root) {
+        Some(subtree) => {
+            subtree.remove(&pid);
+
+            if subtree.is_empty() {
+                subtrees.remove(&root);
+
+                ExitOutcome::SubtreeExited { root }
+            } else {
+                ExitOutcome::SubtreeAlive { root }
+            }
+        }
+
+        None => ExitOutcome::Untracked,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the state is global, so each test uses its own range of (non-existent) pids
+
+    #[test]
+    fn subtree_exits_with_its_last_member() {
+        track(100_001);
+        on_fork(100_001, 100_002);
+        on_fork(100_002, 100_003);
+
+        assert_eq!(
+            on_exit(100_001),
+            ExitOutcome::SubtreeAlive { root: 100_001 }
+        );
+        assert_eq!(
+            on_exit(100_003),
+            ExitOutcome::SubtreeAlive { root: 100_001 }
+        );
+        assert_eq!(
+            on_exit(100_002),
+            ExitOutcome::SubtreeExited { root: 100_001 }
+        );
+
+        // exits are only reported once
+        assert_eq!(on_exit(100_002), ExitOutcome::Untracked);
+    }
+
+    #[test]
+    fn untracked_processes_are_ignored() {
+        track(200_001);
+
+        on_fork(200_100, 200_101);
+
+        assert_eq!(on_exit(200_101), ExitOutcome::Untracked);
+        assert_eq!(on_exit(200_100), ExitOutcome::Untracked);
+        assert_eq!(
+            on_exit(200_001),
+            ExitOutcome::SubtreeExited { root: 200_001 }
+        );
+    }
+
+    #[test]
+    fn matched_descendants_start_their_own_subtree() {
+        track(300_001);
+        on_fork(300_001, 300_002);
+
+        // the descendant has been matched by a rule of its own
+        track(300_002);
+        on_fork(300_002, 300_003);
+
+        assert_eq!(
+            on_exit(300_001),
+            ExitOutcome::SubtreeExited { root: 300_001 }
+        );
+        assert_eq!(
+            on_exit(300_002),
+            ExitOutcome::SubtreeAlive { root: 300_002 }
+        );
+        assert_eq!(
+            on_exit(300_003),
+            ExitOutcome::SubtreeExited { root: 300_002 }
+        );
+    }
+}
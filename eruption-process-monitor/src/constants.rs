@@ -30,6 +30,15 @@ pub const STATE_DIR: &str = "~/.local/share/eruption-process-monitor/";
 /// Main loop delay
 pub const MAIN_LOOP_SLEEP_MILLIS: u64 = 250;
 
+/// Report the position of the mouse pointer to the Eruption daemon at least every n milliseconds
+pub const POINTER_REFRESH_INTERVAL_MILLIS: u64 = 2000;
+
+/// Report the state of the keyboard lock indicators to the Eruption daemon at least every n milliseconds
+pub const INDICATORS_REFRESH_INTERVAL_MILLIS: u64 = 2000;
+
+/// Query the state of the lid and of the connected displays every n milliseconds
+pub const HARDWARE_POLL_INTERVAL_MILLIS: u64 = 1000;
+
 /// Timeout of D-Bus operations
 pub const DBUS_TIMEOUT_MILLIS: u64 = 5000;
 
@@ -43,3 +52,6 @@ pub const DEFAULT_X11_DISPLAY: &str = ":0";
 
 /// The default profile to use
 pub const DEFAULT_PROFILE: &str = "default.profile";
+
+/// Number of profile slots of the Eruption daemon
+pub const NUM_SLOTS: usize = 4;
@@ -110,6 +110,91 @@ pub fn get_active_slot() -> Result<u64> {
     Ok(result)
 }
 
+/// Report the position of the mouse pointer, normalized to the range `0.0..=1.0`
+pub fn set_pointer_position(x: f64, y: f64) -> Result<()> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let _result = proxy.set_pointer_position(x, y)?;
+
+    Ok(())
+}
+
+/// Report the state of the keyboard lock indicators (Caps Lock, Num Lock, Scroll Lock)
+/// and the active xkb group along with the name of its layout
+pub fn set_keyboard_indicators(
+    lock_state: (bool, bool, bool),
+    group: u32,
+    layout: &str,
+) -> Result<()> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let _result = proxy.set_keyboard_indicators(lock_state, group, layout)?;
+
+    Ok(())
+}
+
+/// Report the state of the lid and the DRM connectors of the connected external displays
+pub fn set_hardware_state(lid_closed: bool, displays: &[String]) -> Result<()> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let _result = proxy.set_hardware_state(lid_closed, displays.to_vec())?;
+
+    Ok(())
+}
+
+/// Enable privacy mode for the zones `zones` (all zones if empty), or disable it
+pub fn set_privacy_mode(enable: bool, zones: &[String]) -> Result<()> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let _result = proxy.set_privacy_mode(enable, zones.to_vec())?;
+
+    Ok(())
+}
+
+/// Report the metadata of the currently focused window
+pub fn set_active_window(class: &str, title: &str, app_id: &str) -> Result<()> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let _result = proxy.set_active_window(class, title, app_id)?;
+
+    Ok(())
+}
+
 pub mod slot {
     // This code was autogenerated with `dbus-codegen-rust -s -d org.eruption -p /org/eruption/slot -m None`, see https://github.com/diwic/dbus-rs
     use dbus::arg;
@@ -511,6 +596,25 @@ pub mod config {
         fn set_brightness(&self, value: i64) -> Result<(), dbus::Error>;
         fn enable_sfx(&self) -> Result<bool, dbus::Error>;
         fn set_enable_sfx(&self, value: bool) -> Result<(), dbus::Error>;
+        fn set_pointer_position(&self, x: f64, y: f64) -> Result<bool, dbus::Error>;
+        fn set_keyboard_indicators(
+            &self,
+            lock_state: (bool, bool, bool),
+            group: u32,
+            layout: &str,
+        ) -> Result<bool, dbus::Error>;
+        fn set_privacy_mode(&self, enable: bool, zones: Vec<String>) -> Result<bool, dbus::Error>;
+        fn set_hardware_state(
+            &self,
+            lid_closed: bool,
+            displays: Vec<String>,
+        ) -> Result<bool, dbus::Error>;
+        fn set_active_window(
+            &self,
+            class: &str,
+            title: &str,
+            app_id: &str,
+        ) -> Result<bool, dbus::Error>;
     }
 
     impl<'a, C: ::std::ops::Deref<Target = blocking::Connection>> OrgEruptionConfig
@@ -521,6 +625,57 @@ pub mod config {
                 .map(|r: (bool,)| r.0)
         }
 
+        fn set_pointer_position(&self, x: f64, y: f64) -> Result<bool, dbus::Error> {
+            self.method_call("org.eruption.Config", "SetPointerPosition", (x, y))
+                .map(|r: (bool,)| r.0)
+        }
+
+        fn set_keyboard_indicators(
+            &self,
+            lock_state: (bool, bool, bool),
+            group: u32,
+            layout: &str,
+        ) -> Result<bool, dbus::Error> {
+            self.method_call(
+                "org.eruption.Config",
+                "SetKeyboardIndicators",
+                (lock_state, group, layout),
+            )
+            .map(|r: (bool,)| r.0)
+        }
+
+        fn set_privacy_mode(&self, enable: bool, zones: Vec<String>) -> Result<bool, dbus::Error> {
+            self.method_call("org.eruption.Config", "SetPrivacyMode", (enable, zones))
+                .map(|r: (bool,)| r.0)
+        }
+
+        fn set_hardware_state(
+            &self,
+            lid_closed: bool,
+            displays: Vec<String>,
+        ) -> Result<bool, dbus::Error> {
+            self.method_call(
+                "org.eruption.Config",
+                "SetHardwareState",
+                (lid_closed, displays),
+            )
+            .map(|r: (bool,)| r.0)
+        }
+
+        fn set_active_window(
+            &self,
+            class: &str,
+            title: &str,
+            app_id: &str,
+        ) -> Result<bool, dbus::Error> {
+            self.method_call(
+                "org.eruption.Config",
+                "SetActiveWindow",
+                (class, title, app_id),
+            )
+            .map(|r: (bool,)| r.0)
+        }
+
         fn brightness(&self) -> Result<i64, dbus::Error> {
             <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
                 self,
@@ -24,13 +24,19 @@ use dbus_tree::{Factory, Signal};
 use flume::Sender;
 use indexmap::IndexMap;
 use log::*;
+use regex::Regex;
 use std::sync::Arc;
 
-use crate::{Action, RuleMetadata, Selector, WindowFocusedSelectorMode};
+use crate::constants;
+use crate::time_window::TimeWindow;
+use crate::{parse_privacy_action, Action, RuleMetadata, Selector, WindowFocusedSelectorMode};
 
 /// D-Bus messages and signals that are processed by the main thread
 #[derive(Debug, Clone)]
-pub enum Message {}
+pub enum Message {
+    /// An event that has been pushed by a third-party script, like e.g. "build-failed"
+    ExternalEvent { name: String },
+}
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -53,9 +59,7 @@ pub struct DbusApi {
 #[allow(dead_code)]
 impl DbusApi {
     /// Initialize the D-Bus API
-    pub fn new(_dbus_tx: Sender<Message>) -> Result<Self> {
-        // let dbus_tx_clone = dbus_tx.clone();
-
+    pub fn new(dbus_tx: Sender<Message>) -> Result<Self> {
         let c = Connection::new_session()?;
         c.register_name(
             "org.eruption.process_monitor",
@@ -84,47 +88,7 @@ impl DbusApi {
                                 let s = rules_map
                                     .iter()
                                     .map(|(selector, (metadata, action))| {
-                                        let (sensor_val, selector_val) = match selector {
-                                            Selector::ProcessExec { comm } => {
-                                                ("exec".to_string(), comm)
-                                            }
-
-                                            Selector::WindowFocused { mode, regex } => match mode {
-                                                WindowFocusedSelectorMode::WindowName => {
-                                                    ("window-name".to_string(), regex)
-                                                }
-                                                WindowFocusedSelectorMode::WindowInstance => {
-                                                    ("window-instance".to_string(), regex)
-                                                }
-                                                WindowFocusedSelectorMode::WindowClass => {
-                                                    ("window-class".to_string(), regex)
-                                                }
-                                            },
-                                        };
-
-                                        let action_val = match action {
-                                            Action::SwitchToProfile { profile_name } => {
-                                                profile_name.to_string()
-                                            }
-                                            Action::SwitchToSlot { slot_index } => {
-                                                format!("{}", slot_index)
-                                            }
-                                        };
-
-                                        let mut metadata_val = String::new();
-                                        if metadata.enabled {
-                                            metadata_val.push_str("enabled");
-                                        } else {
-                                            metadata_val.push_str("disabled");
-                                        }
-
-                                        if metadata.internal {
-                                            metadata_val.push_str(",internal");
-                                        } else {
-                                            metadata_val.push_str(",user-defined");
-                                        }
-
-                                        (sensor_val, selector_val, action_val, metadata_val)
+                                        format_rule(selector, metadata, action)
                                     })
                                     .collect::<Vec<_>>();
                                 Ok(vec![m.msg.method_return().append1(s)])
@@ -138,93 +102,165 @@ impl DbusApi {
                                 let rules: Vec<(String, String, String, String)> = m.msg.read1()?;
 
                                 for rule in rules {
-                                    let sensor_val = rule.0;
-                                    let selector_val = rule.1;
-                                    let action_val = rule.2;
-                                    let metadata_val = rule.3;
-
-                                    fn parse_rule(
-                                        sensor_val: &str,
-                                        selector_val: &str,
-                                        action_val: &str,
-                                        metadata_val: &str,
-                                    ) -> Result<(Selector, (RuleMetadata, Action))>
-                                    {
-                                        let sensor;
-
-                                        let action;
-
-                                        match sensor_val {
-                                            "exec" => {
-                                                sensor = Selector::ProcessExec {
-                                                    comm: selector_val.into(),
-                                                }
-                                            }
-
-                                            "window-name" => {
-                                                sensor = Selector::WindowFocused {
-                                                    mode: WindowFocusedSelectorMode::WindowName,
-                                                    regex: selector_val.into(),
-                                                }
-                                            }
-
-                                            "window-instance" => {
-                                                sensor = Selector::WindowFocused {
-                                                    mode: WindowFocusedSelectorMode::WindowInstance,
-                                                    regex: selector_val.into(),
-                                                }
-                                            }
-
-                                            "window-class" => {
-                                                sensor = Selector::WindowFocused {
-                                                    mode: WindowFocusedSelectorMode::WindowClass,
-                                                    regex: selector_val.into(),
-                                                }
-                                            }
-
-                                            _ => {
-                                                return Err(DbusApiError::InvalidArgument {}.into())
-                                            }
-                                        }
-
-                                        let enabled = metadata_val.contains("enabled");
-                                        let internal = metadata_val.contains("internal");
-
-                                        let metadata = RuleMetadata { enabled, internal };
-
-                                        if action_val.contains(".profile") {
-                                            action = Action::SwitchToProfile {
-                                                profile_name: action_val.to_string(),
-                                            };
-                                        } else {
-                                            action = Action::SwitchToSlot {
-                                                slot_index: action_val.parse::<u64>()?,
-                                            };
-                                        }
-
-                                        Ok((sensor, (metadata, action)))
-                                    }
-
-                                    let (selector, (metadata, action)) = parse_rule(
-                                        &sensor_val,
-                                        &selector_val,
-                                        &action_val,
-                                        &metadata_val,
-                                    )
-                                    .map_err(|_e| MethodErr::invalid_arg("rules"))?;
+                                    let (selector, (metadata, action)) =
+                                        parse_rule(&rule.0, &rule.1, &rule.2, &rule.3)
+                                            .map_err(|_e| MethodErr::invalid_arg("rules"))?;
 
                                     rules_map.insert(selector, (metadata, action));
                                 }
 
                                 *crate::RULES_MAP.write() = rules_map;
 
-                                crate::save_rules_map().map_err(|_e| {
-                                    dbus::Error::new_failed("Could not save the rules map")
-                                })?;
+                                save_rules_map()?;
 
                                 Ok(vec![m.msg.method_return()])
                             })
                             .inarg::<Vec<(String, String, String, String)>, _>("rules"),
+                        )
+                        .add_m(
+                            f.method("ListRules", (), move |m| {
+                                let rules_map = crate::RULES_MAP.read();
+                                let s = rules_map
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(index, (selector, (metadata, action)))| {
+                                        let (sensor, selector, action, metadata) =
+                                            format_rule(selector, metadata, action);
+
+                                        (index as u64, sensor, selector, action, metadata)
+                                    })
+                                    .collect::<Vec<_>>();
+                                Ok(vec![m.msg.method_return().append1(s)])
+                            })
+                            .outarg::<Vec<(u64, String, String, String, String)>, _>("rules"),
+                        )
+                        .add_m(
+                            f.method("AddRule", (), move |m| {
+                                let (sensor_val, selector_val, action_val, metadata_val): (
+                                    String,
+                                    String,
+                                    String,
+                                    String,
+                                ) = m.msg.read4()?;
+
+                                let (selector, (metadata, action)) = parse_rule(
+                                    &sensor_val,
+                                    &selector_val,
+                                    &action_val,
+                                    &metadata_val,
+                                )
+                                .map_err(|_e| MethodErr::invalid_arg("rule"))?;
+
+                                validate_rule(&selector, &metadata, &action)?;
+
+                                let index = {
+                                    let mut rules_map = crate::RULES_MAP.write();
+
+                                    if rules_map.contains_key(&selector) {
+                                        return Err(MethodErr::invalid_arg("selector"));
+                                    }
+
+                                    rules_map.insert_full(selector, (metadata, action)).0
+                                };
+
+                                save_rules_map()?;
+
+                                Ok(vec![m.msg.method_return().append1(index as u64)])
+                            })
+                            .inarg::<String, _>("sensor")
+                            .inarg::<String, _>("selector")
+                            .inarg::<String, _>("action")
+                            .inarg::<String, _>("metadata")
+                            .outarg::<u64, _>("index"),
+                        )
+                        .add_m(
+                            f.method("RemoveRule", (), move |m| {
+                                let index: u64 = m.msg.read1()?;
+
+                                {
+                                    let mut rules_map = crate::RULES_MAP.write();
+                                    let index = user_defined_rule_index(&rules_map, index)?;
+
+                                    rules_map.shift_remove_index(index);
+                                }
+
+                                save_rules_map()?;
+
+                                Ok(vec![m.msg.method_return().append1(true)])
+                            })
+                            .inarg::<u64, _>("index")
+                            .outarg::<bool, _>("status"),
+                        )
+                        .add_m(
+                            f.method("SetRuleEnabled", (), move |m| {
+                                let (index, enabled): (u64, bool) = m.msg.read2()?;
+
+                                {
+                                    let mut rules_map = crate::RULES_MAP.write();
+                                    let index = user_defined_rule_index(&rules_map, index)?;
+
+                                    if let Some((_, (metadata, _))) = rules_map.get_index_mut(index)
+                                    {
+                                        metadata.enabled = enabled;
+                                    }
+                                }
+
+                                save_rules_map()?;
+
+                                Ok(vec![m.msg.method_return().append1(true)])
+                            })
+                            .inarg::<u64, _>("index")
+                            .inarg::<bool, _>("enabled")
+                            .outarg::<bool, _>("status"),
+                        )
+                        .add_m(
+                            f.method("MoveRule", (), move |m| {
+                                let (from, to): (u64, u64) = m.msg.read2()?;
+
+                                {
+                                    let mut rules_map = crate::RULES_MAP.write();
+                                    let from = user_defined_rule_index(&rules_map, from)?;
+
+                                    if to as usize >= rules_map.len() {
+                                        return Err(MethodErr::invalid_arg("to"));
+                                    }
+
+                                    rules_map.move_index(from, to as usize);
+                                }
+
+                                save_rules_map()?;
+
+                                Ok(vec![m.msg.method_return().append1(true)])
+                            })
+                            .inarg::<u64, _>("from")
+                            .inarg::<u64, _>("to")
+                            .outarg::<bool, _>("status"),
+                        ),
+                ),
+        );
+
+        // external events, pushed by third-party scripts
+        let tree = tree.add(
+            f.object_path("/org/eruption/process_monitor/events", ())
+                .introspectable()
+                .add(
+                    f.interface("org.eruption.process_monitor.Events", ())
+                        .add_m(
+                            f.method("PushEvent", (), move |m| {
+                                let name: String = m.msg.read1()?;
+
+                                if name.trim().is_empty() {
+                                    return Err(MethodErr::invalid_arg("name"));
+                                }
+
+                                dbus_tx
+                                    .send(Message::ExternalEvent { name })
+                                    .map_err(|e| MethodErr::failed(&e))?;
+
+                                Ok(vec![m.msg.method_return().append1(true)])
+                            })
+                            .inarg::<String, _>("name")
+                            .outarg::<bool, _>("status"),
                         ),
                 ),
         );
@@ -243,43 +279,7 @@ impl DbusApi {
         let rules_map = crate::RULES_MAP.read();
         let s = rules_map
             .iter()
-            .map(|(selector, (metadata, action))| {
-                let (sensor_val, selector_val) = match selector {
-                    Selector::ProcessExec { comm } => ("exec".to_string(), comm),
-
-                    Selector::WindowFocused { mode, regex } => match mode {
-                        WindowFocusedSelectorMode::WindowName => ("window-name".to_string(), regex),
-                        WindowFocusedSelectorMode::WindowInstance => {
-                            ("window-instance".to_string(), regex)
-                        }
-                        WindowFocusedSelectorMode::WindowClass => {
-                            ("window-class".to_string(), regex)
-                        }
-                    },
-                };
-
-                let action_val = match action {
-                    Action::SwitchToProfile { profile_name } => profile_name.to_string(),
-                    Action::SwitchToSlot { slot_index } => {
-                        format!("{}", slot_index)
-                    }
-                };
-
-                let mut metadata_val = String::new();
-                if metadata.enabled {
-                    metadata_val.push_str("enabled");
-                } else {
-                    metadata_val.push_str("disabled");
-                }
-
-                if metadata.internal {
-                    metadata_val.push_str(",internal");
-                } else {
-                    metadata_val.push_str(",user-defined");
-                }
-
-                (sensor_val, selector_val, action_val, metadata_val)
-            })
+            .map(|(selector, (metadata, action))| format_rule(selector, metadata, action))
             .collect::<Vec<_>>();
 
         self.connection
@@ -353,6 +353,222 @@ pub fn initialize(dbus_tx: Sender<Message>) -> Result<DbusApi> {
     DbusApi::new(dbus_tx)
 }
 
+/// Convert a rule to the `(sensor, selector, action, metadata)` representation used on D-Bus
+fn format_rule(
+    selector: &Selector,
+    metadata: &RuleMetadata,
+    action: &Action,
+) -> (String, String, String, String) {
+    let (sensor_val, selector_val) = match selector {
+        Selector::ProcessExec { comm } => ("exec".to_string(), comm.to_owned()),
+
+        Selector::WindowFocused { mode, regex } => match mode {
+            WindowFocusedSelectorMode::WindowName => ("window-name".to_string(), regex.to_owned()),
+            WindowFocusedSelectorMode::WindowInstance => {
+                ("window-instance".to_string(), regex.to_owned())
+            }
+            WindowFocusedSelectorMode::WindowClass => {
+                ("window-class".to_string(), regex.to_owned())
+            }
+        },
+
+        Selector::External { regex } => ("external".to_string(), regex.to_owned()),
+
+        Selector::Hardware { regex } => ("hardware".to_string(), regex.to_owned()),
+    };
+
+    let action_val = match action {
+        Action::SwitchToProfile { profile_name } => profile_name.to_string(),
+        Action::SwitchToSlot { slot_index } => {
+            format!("{}", slot_index)
+        }
+        Action::EnablePrivacyMode { zones } => {
+            if zones.is_empty() {
+                "privacy".to_string()
+            } else {
+                format!("privacy:{}", zones.join(","))
+            }
+        }
+    };
+
+    let mut metadata_val = String::new();
+    if metadata.enabled {
+        metadata_val.push_str("enabled");
+    } else {
+        metadata_val.push_str("disabled");
+    }
+
+    if metadata.internal {
+        metadata_val.push_str(",internal");
+    } else {
+        metadata_val.push_str(",user-defined");
+    }
+
+    if metadata.include_descendants {
+        metadata_val.push_str(",descendants");
+    }
+
+    // the time window has to be the last element
+    if let Some(time_window) = &metadata.time_window {
+        metadata_val.push_str(&format!(",time={}", time_window));
+    }
+
+    (sensor_val, selector_val, action_val, metadata_val)
+}
+
+/// Parse a rule from the `(sensor, selector, action, metadata)` representation used on D-Bus
+fn parse_rule(
+    sensor_val: &str,
+    selector_val: &str,
+    action_val: &str,
+    metadata_val: &str,
+) -> Result<(Selector, (RuleMetadata, Action))> {
+    let sensor;
+
+    let action;
+
+    match sensor_val {
+        "exec" => {
+            sensor = Selector::ProcessExec {
+                comm: selector_val.into(),
+            }
+        }
+
+        "window-name" => {
+            sensor = Selector::WindowFocused {
+                mode: WindowFocusedSelectorMode::WindowName,
+                regex: selector_val.into(),
+            }
+        }
+
+        "window-instance" => {
+            sensor = Selector::WindowFocused {
+                mode: WindowFocusedSelectorMode::WindowInstance,
+                regex: selector_val.into(),
+            }
+        }
+
+        "window-class" => {
+            sensor = Selector::WindowFocused {
+                mode: WindowFocusedSelectorMode::WindowClass,
+                regex: selector_val.into(),
+            }
+        }
+
+        "external" => {
+            sensor = Selector::External {
+                regex: selector_val.into(),
+            }
+        }
+
+        "hardware" => {
+            sensor = Selector::Hardware {
+                regex: selector_val.into(),
+            }
+        }
+
+        _ => return Err(DbusApiError::InvalidArgument {}.into()),
+    }
+
+    let enabled = metadata_val.contains("enabled");
+    let internal = metadata_val.contains("internal");
+    let include_descendants = metadata_val.contains("descendants");
+
+    let time_window = metadata_val
+        .split_once("time=")
+        .map(|(_, s)| {
+            s.trim_end_matches(|c: char| c == ')' || c.is_whitespace())
+                .parse::<TimeWindow>()
+        })
+        .transpose()?;
+
+    let metadata = RuleMetadata {
+        enabled,
+        internal,
+        time_window,
+        include_descendants,
+    };
+
+    if let Some(zones) = parse_privacy_action(action_val) {
+        action = Action::EnablePrivacyMode { zones };
+    } else if action_val.contains(".profile") {
+        action = Action::SwitchToProfile {
+            profile_name: action_val.to_string(),
+        };
+    } else {
+        action = Action::SwitchToSlot {
+            slot_index: action_val.parse::<u64>()?,
+        };
+    }
+
+    Ok((sensor, (metadata, action)))
+}
+
+/// Verify that a rule submitted by a client may be added to the rules map
+fn validate_rule(
+    selector: &Selector,
+    metadata: &RuleMetadata,
+    action: &Action,
+) -> std::result::Result<(), MethodErr> {
+    // internal rules are auto-generated, they may not be added by clients
+    if metadata.internal {
+        return Err(MethodErr::invalid_arg("metadata"));
+    }
+
+    // only processes have descendants
+    if metadata.include_descendants && !matches!(selector, Selector::ProcessExec { .. }) {
+        return Err(MethodErr::invalid_arg("metadata"));
+    }
+
+    // privacy mode is bound to the focused window
+    if matches!(action, Action::EnablePrivacyMode { .. })
+        && !matches!(selector, Selector::WindowFocused { .. })
+    {
+        return Err(MethodErr::invalid_arg("action"));
+    }
+
+    let regex = match selector {
+        Selector::ProcessExec { comm } => comm,
+        Selector::WindowFocused { regex, .. } => regex,
+        Selector::External { regex } => regex,
+        Selector::Hardware { regex } => regex,
+    };
+
+    if regex.is_empty() || Regex::new(regex).is_err() {
+        return Err(MethodErr::invalid_arg("selector"));
+    }
+
+    match action {
+        Action::SwitchToProfile { profile_name } if profile_name.trim().is_empty() => {
+            Err(MethodErr::invalid_arg("action"))
+        }
+
+        Action::SwitchToSlot { slot_index } if *slot_index as usize >= constants::NUM_SLOTS => {
+            Err(MethodErr::invalid_arg("action"))
+        }
+
+        _ => Ok(()),
+    }
+}
+
+/// Returns the index of a rule as `usize`, or an error if the index is out of range
+/// or refers to an internal rule that may not be modified by clients
+fn user_defined_rule_index(
+    rules_map: &IndexMap<Selector, (RuleMetadata, Action)>,
+    index: u64,
+) -> std::result::Result<usize, MethodErr> {
+    match rules_map.get_index(index as usize) {
+        Some((_, (metadata, _))) if !metadata.internal => Ok(index as usize),
+
+        _ => Err(MethodErr::invalid_arg("index")),
+    }
+}
+
+fn save_rules_map() -> std::result::Result<(), MethodErr> {
+    crate::save_rules_map()
+        .map_err(|_e| MethodErr::from(dbus::Error::new_failed("Could not save the rules map")))
+}
+
 #[allow(dead_code)]
 mod perms {
     use dbus::{arg::RefArg, arg::Variant, blocking::Connection};
@@ -24,9 +24,18 @@ use crate::dbus_client::Message;
 #[cfg(feature = "sensor-procmon")]
 use crate::sensors::PROCESS_SENSOR_FAILED;
 
+#[cfg(feature = "sensor-procfs")]
+use crate::sensors::{ProcfsSensorData, PROCFS_SENSOR_FALLBACK};
+
 #[cfg(feature = "sensor-gnome-shellext")]
 use crate::sensors::GnomeShellExtSensorData;
 
+#[cfg(feature = "sensor-hardware")]
+use crate::sensors::HardwareSensorData;
+
+#[cfg(feature = "sensor-kwin")]
+use crate::sensors::KWinSensorData;
+
 #[cfg(feature = "sensor-mutter")]
 use crate::sensors::MutterSensorData;
 
@@ -41,7 +50,7 @@ use crate::sensors::SensorConfiguration;
 use crate::sensors::SENSORS_CONFIGURATION;
 
 #[cfg(feature = "sensor-x11")]
-use crate::sensors::X11SensorData;
+use crate::sensors::{KeyboardIndicators, X11SensorData};
 
 use clap::CommandFactory;
 use clap::Parser;
@@ -67,10 +76,22 @@ use regex::Regex;
 use rust_embed::RustEmbed;
 use sensors::WindowSensorData;
 use serde::{Deserialize, Serialize};
-use std::{env, fmt, fs, path::PathBuf, process, sync::atomic::AtomicBool, sync::Arc};
+use std::{
+    env, fmt, fs,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicBool, AtomicI32},
+    sync::Arc,
+};
 use std::{sync::atomic::Ordering, thread, time::Duration};
 use syslog::Facility;
+use time_window::TimeWindow;
+
+#[cfg(feature = "sensor-x11")]
+use std::time::Instant;
 
+#[cfg(feature = "sensor-procmon")]
+mod ancestry;
 mod constants;
 mod dbus_client;
 mod dbus_interface;
@@ -79,6 +100,7 @@ mod logger;
 #[cfg(feature = "sensor-procmon")]
 mod procmon;
 mod sensors;
+mod time_window;
 mod util;
 
 #[derive(RustEmbed)]
@@ -120,6 +142,9 @@ lazy_static! {
     /// Currently selected slot and profile
     pub static ref CURRENT_STATE: Arc<RwLock<(Option<u64>, Option<String>)>> = Arc::new(RwLock::new((None, None)));
 
+    /// Zones that are currently blacked out by privacy mode, or `None` if privacy mode is disabled
+    pub static ref PRIVACY_STATE: Arc<RwLock<Option<Vec<String>>>> = Arc::new(RwLock::new(None));
+
     // Flags
 
     /// Global "enable experimental features" flag
@@ -128,6 +153,24 @@ lazy_static! {
     /// Signals that we initiated a profile change
     pub static ref PROFILE_CHANGING: AtomicBool = AtomicBool::new(false);
 
+    /// PID of the foreground process, as seen by the /proc polling sensor
+    pub static ref LAST_FOREGROUND_PID: AtomicI32 = AtomicI32::new(0);
+
+    /// Position of the mouse pointer that has last been reported to the Eruption daemon
+    #[cfg(feature = "sensor-x11")]
+    pub static ref LAST_POINTER_POSITION: Arc<Mutex<Option<((f64, f64), Instant)>>> = Arc::new(Mutex::new(None));
+
+    /// Metadata of the focused window (class, title, app_id) that has last been reported to the Eruption daemon
+    pub static ref LAST_ACTIVE_WINDOW: Arc<Mutex<Option<(String, String, String)>>> = Arc::new(Mutex::new(None));
+
+    /// State of the keyboard lock indicators that has last been reported to the Eruption daemon
+    #[cfg(feature = "sensor-x11")]
+    pub static ref LAST_KEYBOARD_INDICATORS: Arc<Mutex<Option<(KeyboardIndicators, Instant)>>> = Arc::new(Mutex::new(None));
+
+    /// State of the lid and of the external displays that has last been reported to the Eruption daemon
+    #[cfg(feature = "sensor-hardware")]
+    pub static ref LAST_HARDWARE_STATE: Arc<Mutex<Option<HardwareSensorData>>> = Arc::new(Mutex::new(None));
+
     /// Global "quit" status flag
     pub static ref QUIT: AtomicBool = AtomicBool::new(false);
 }
@@ -191,6 +234,15 @@ pub enum Selector {
         mode: WindowFocusedSelectorMode,
         regex: String,
     },
+    /// Matches events that have been pushed by third-party scripts via D-Bus
+    External {
+        regex: String,
+    },
+    /// Matches changes of the lid and hotplugging of external displays, like
+    /// `lid-closed` or `display-connected/HDMI-A-1`
+    Hardware {
+        regex: String,
+    },
 }
 
 impl fmt::Display for Selector {
@@ -203,6 +255,14 @@ impl fmt::Display for Selector {
             Selector::WindowFocused { mode, regex } => {
                 write!(f, "On window focused: {}: '{}'", mode, regex)?;
             }
+
+            Selector::External { regex } => {
+                write!(f, "On external event: '{}'", regex)?;
+            }
+
+            Selector::Hardware { regex } => {
+                write!(f, "On hardware event: '{}'", regex)?;
+            }
         };
 
         Ok(())
@@ -211,8 +271,35 @@ impl fmt::Display for Selector {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Action {
-    SwitchToProfile { profile_name: String },
-    SwitchToSlot { slot_index: u64 },
+    SwitchToProfile {
+        profile_name: String,
+    },
+    SwitchToSlot {
+        slot_index: u64,
+    },
+
+    /// Black out the zones `zones`, or the whole canvas if `zones` is empty
+    EnablePrivacyMode {
+        zones: Vec<String>,
+    },
+}
+
+/// Parse the action `privacy` or `privacy:<zone>[,<zone>...]`, returns the zones that
+/// shall be blacked out, or `None` if `action` is not a privacy mode action
+pub fn parse_privacy_action(action: &str) -> Option<Vec<String>> {
+    let zones = action.strip_prefix("privacy")?;
+
+    if zones.is_empty() {
+        Some(vec![])
+    } else {
+        zones.strip_prefix(':').map(|zones| {
+            zones
+                .split(',')
+                .map(|zone| zone.trim().to_string())
+                .filter(|zone| !zone.is_empty())
+                .collect()
+        })
+    }
 }
 
 impl fmt::Display for Action {
@@ -225,6 +312,14 @@ impl fmt::Display for Action {
             Action::SwitchToSlot { slot_index } => {
                 write!(f, "Switch to slot: {}", slot_index + 1)?;
             }
+
+            Action::EnablePrivacyMode { zones } => {
+                if zones.is_empty() {
+                    write!(f, "Enable privacy mode")?;
+                } else {
+                    write!(f, "Enable privacy mode: {}", zones.join(", "))?;
+                }
+            }
         };
 
         Ok(())
@@ -238,6 +333,29 @@ pub struct RuleMetadata {
 
     /// Set to true if the rule is auto-generated
     pub internal: bool,
+
+    /// Optional time window, the rule only matches while the local time is inside of it
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_window: Option<TimeWindow>,
+
+    /// Apply the rule to the descendants of a matched process too, the previous state is
+    /// restored after the whole process subtree exited
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub include_descendants: bool,
+}
+
+impl RuleMetadata {
+    /// Returns `true` if the rule is enabled, and its time window (if any) is currently open
+    pub fn is_active(&self) -> bool {
+        self.enabled
+            && self
+                .time_window
+                .as_ref()
+                .map(|time_window| time_window.is_active_now())
+                .unwrap_or(true)
+    }
 }
 
 impl std::default::Default for RuleMetadata {
@@ -245,6 +363,8 @@ impl std::default::Default for RuleMetadata {
         RuleMetadata {
             enabled: true,
             internal: false,
+            time_window: None,
+            include_descendants: false,
         }
     }
 }
@@ -254,6 +374,14 @@ impl fmt::Display for RuleMetadata {
         write!(f, "enabled: {}", self.enabled)?;
         write!(f, ", internal: {}", self.internal)?;
 
+        if self.include_descendants {
+            write!(f, ", include descendants")?;
+        }
+
+        if let Some(time_window) = &self.time_window {
+            write!(f, ", time: {}", time_window)?;
+        }
+
         Ok(())
     }
 }
@@ -323,7 +451,13 @@ pub enum RulesSubcommands {
 
     /// Add a new rule
     #[clap(display_order = 1)]
-    Add { rule: Vec<String> },
+    Add {
+        /// Apply an exec rule to the descendants of a matched process too
+        #[clap(short, long)]
+        descendants: bool,
+
+        rule: Vec<String>,
+    },
 
     /// Remove a rule by its index
     #[clap(display_order = 2)]
@@ -409,6 +543,114 @@ fn process_action(action: &Action) -> Result<()> {
 
             CURRENT_STATE.write().0 = Some(*slot_index);
         }
+
+        Action::EnablePrivacyMode { zones } => set_privacy_mode(Some(zones))?,
+    }
+
+    Ok(())
+}
+
+/// Enable privacy mode for the zones `zones`, or disable it if `zones` is `None`
+fn set_privacy_mode(zones: Option<&Vec<String>>) -> Result<()> {
+    if PRIVACY_STATE.read().as_ref() != zones {
+        match zones {
+            Some(zones) => {
+                info!(
+                    "Triggered action: {}",
+                    Action::EnablePrivacyMode {
+                        zones: zones.clone()
+                    }
+                );
+
+                dbus_client::set_privacy_mode(true, zones)?;
+            }
+
+            None => {
+                info!("Disabling privacy mode");
+
+                dbus_client::set_privacy_mode(false, &[])?;
+            }
+        }
+
+        *PRIVACY_STATE.write() = zones.cloned();
+    }
+
+    Ok(())
+}
+
+/// Match the process exec rules against a newly started process, and save the
+/// current state so that it may be restored after the process exited
+#[cfg(any(feature = "sensor-procmon", feature = "sensor-procfs"))]
+fn process_exec_rules(pid: i32, comm: &str) -> Result<()> {
+    for (selector, (metadata, action)) in RULES_MAP.read().iter() {
+        match selector {
+            Selector::ProcessExec { comm: regex } => {
+                if metadata.is_active() {
+                    let re = Regex::new(regex)?;
+
+                    if re.is_match(comm) {
+                        debug!("Matching rule for: {}", comm);
+
+                        match action {
+                            Action::SwitchToProfile { profile_name: _ } => {
+                                let profile_name = dbus_client::get_active_profile()?;
+                                let return_action = Action::SwitchToProfile { profile_name };
+                                PREVIOUS_STATES_MAP.write().insert(pid, return_action);
+                            }
+
+                            Action::SwitchToSlot { slot_index: _ } => {
+                                let slot_index = dbus_client::get_active_slot()?;
+                                let return_action = Action::SwitchToSlot { slot_index };
+                                PREVIOUS_STATES_MAP.write().insert(pid, return_action);
+                            }
+
+                            Action::EnablePrivacyMode { .. } => {
+                                // privacy mode is bound to the focused window
+                                continue;
+                            }
+                        }
+
+                        #[cfg(feature = "sensor-procmon")]
+                        if metadata.include_descendants {
+                            ancestry::track(pid);
+                        }
+
+                        process_action(action)?;
+                        break;
+                    }
+                }
+            }
+
+            _ => { /* Ignore others */ }
+        }
+    }
+
+    Ok(())
+}
+
+/// Return to the state that was active before the process `pid` has been started
+#[cfg(any(feature = "sensor-procmon", feature = "sensor-procfs"))]
+fn restore_previous_state(pid: i32) -> Result<()> {
+    match PREVIOUS_STATES_MAP.read().get(&pid) {
+        Some(action) => match action {
+            Action::SwitchToProfile { profile_name } => {
+                debug!("Returning to profile: {}", profile_name);
+
+                dbus_client::switch_profile(profile_name)?;
+            }
+
+            Action::SwitchToSlot { slot_index } => {
+                debug!("Returning to slot: {}", slot_index + 1);
+
+                dbus_client::switch_slot(*slot_index)?;
+            }
+
+            Action::EnablePrivacyMode { .. } => { /* never saved as a previous state */ }
+        },
+
+        None => {
+            // no saved state available
+        }
     }
 
     Ok(())
@@ -424,68 +666,51 @@ fn process_system_event(event: &SystemEvent) -> Result<()> {
             comm,
         } => {
             if let Some(comm) = comm {
-                for (selector, (metadata, action)) in RULES_MAP.read().iter() {
-                    match selector {
-                        Selector::ProcessExec { comm: regex } => {
-                            if metadata.enabled {
-                                let re = Regex::new(regex)?;
-
-                                if re.is_match(comm) {
-                                    debug!("Matching rule for: {}", comm);
-
-                                    match action {
-                                        Action::SwitchToProfile { profile_name: _ } => {
-                                            let profile_name = dbus_client::get_active_profile()?;
-                                            let return_action =
-                                                Action::SwitchToProfile { profile_name };
-                                            PREVIOUS_STATES_MAP
-                                                .write()
-                                                .insert(event.pid, return_action);
-                                        }
-
-                                        Action::SwitchToSlot { slot_index: _ } => {
-                                            let slot_index = dbus_client::get_active_slot()?;
-                                            let return_action = Action::SwitchToSlot { slot_index };
-                                            PREVIOUS_STATES_MAP
-                                                .write()
-                                                .insert(event.pid, return_action);
-                                        }
-                                    }
-
-                                    process_action(action)?;
-                                    break;
-                                }
-                            }
-                        }
-
-                        _ => { /* Ignore others */ }
-                    }
-                }
+                process_exec_rules(event.pid, comm)?;
             } else {
                 debug!("Could not get the process comm. The process vanished.");
             }
         }
 
-        SystemEvent::ProcessExit { event } => {
-            match PREVIOUS_STATES_MAP.read().get(&event.pid) {
-                Some(action) => match action {
-                    Action::SwitchToProfile { profile_name } => {
-                        debug!("Returning to profile: {}", profile_name);
-
-                        dbus_client::switch_profile(profile_name)?;
-                    }
+        SystemEvent::ProcessExit { event } => match ancestry::on_exit(event.pid) {
+            ancestry::ExitOutcome::Untracked => {
+                restore_previous_state(event.pid)?;
+            }
 
-                    Action::SwitchToSlot { slot_index } => {
-                        debug!("Returning to slot: {}", slot_index + 1);
+            ancestry::ExitOutcome::SubtreeAlive { root } => {
+                // other members of the subtree are still running, but a descendant
+                // may have been matched by a rule of its own
+                if event.pid != root {
+                    restore_previous_state(event.pid)?;
+                }
+            }
 
-                        dbus_client::switch_slot(*slot_index)?;
-                    }
-                },
+            ancestry::ExitOutcome::SubtreeExited { root } => {
+                restore_previous_state(root)?;
 
-                None => {
-                    // no saved state available
-                }
+                PREVIOUS_STATES_MAP.write().shift_remove(&root);
             }
+        },
+    }
+
+    Ok(())
+}
+
+/// Process data of the /proc polling sensor. A change of the foreground process is
+/// treated like the exit of the previous process and the exec of the new one
+#[cfg(feature = "sensor-procfs")]
+fn process_procfs_event(data: &ProcfsSensorData) -> Result<()> {
+    let previous_pid = LAST_FOREGROUND_PID.swap(data.pid, Ordering::SeqCst);
+
+    if previous_pid != data.pid {
+        if previous_pid != 0 {
+            restore_previous_state(previous_pid)?;
+
+            PREVIOUS_STATES_MAP.write().shift_remove(&previous_pid);
+        }
+
+        if !data.comm.is_empty() {
+            process_exec_rules(data.pid, &data.comm)?;
         }
     }
 
@@ -513,6 +738,88 @@ fn process_fs_event(event: &FileSystemEvent, dbus_api_tx: &Sender<DbusApiEvent>)
     Ok(())
 }
 
+/// Process messages of our D-Bus API
+fn process_dbus_api_message(message: &dbus_interface::Message) -> Result<()> {
+    match message {
+        dbus_interface::Message::ExternalEvent { name } => process_external_event(name)?,
+    }
+
+    Ok(())
+}
+
+/// Match the rules against an event that has been pushed by a third-party script
+fn process_external_event(name: &str) -> Result<()> {
+    debug!("External event: {}", name);
+
+    for (selector, (metadata, action)) in RULES_MAP.read().iter() {
+        if let Selector::External { regex } = selector {
+            if metadata.is_active() {
+                let re = Regex::new(regex)?;
+
+                if re.is_match(name) {
+                    debug!("Matching rule for external event: {}", name);
+
+                    process_action(action)?;
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Process data of the hardware sensor. Changes of the lid and of the connected external
+/// displays are matched against the rules, and reported to the Eruption daemon
+#[cfg(feature = "sensor-hardware")]
+fn process_hardware_event(data: &HardwareSensorData) -> Result<()> {
+    // record the new state first, so that a failing action does not lead to the same
+    // events being generated over and over again
+    let previous = {
+        let mut last_state = LAST_HARDWARE_STATE.lock();
+
+        if last_state.as_ref() == Some(data) {
+            return Ok(());
+        }
+
+        last_state.replace(data.clone())
+    };
+
+    if let Err(e) =
+        dbus_client::set_hardware_state(data.lid_closed.unwrap_or(false), &data.external_displays)
+    {
+        error!("Could not publish the hardware state: {}", e);
+    }
+
+    // the initial state is not a change, so no rules are matched against it
+    if let Some(previous) = previous {
+        for event in sensors::hardware_events(&previous, data) {
+            debug!("Hardware event: {}", event);
+
+            for (selector, (metadata, action)) in RULES_MAP.read().iter() {
+                if let Selector::Hardware { regex } = selector {
+                    if metadata.is_active() && matches_hardware_event(regex, &event)? {
+                        debug!("Matching rule for hardware event: {}", event);
+
+                        process_action(action)?;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if the rule pattern `regex` matches the whole name of the hardware
+/// event `event`, so that e.g. `docked` does not match `undocked`
+fn matches_hardware_event(regex: &str, event: &str) -> Result<bool> {
+    let re = Regex::new(&format!("^(?:{})$", regex))?;
+
+    Ok(re.is_match(event))
+}
+
 /// Process D-Bus related events
 fn process_dbus_event(event: &dbus_client::Message) -> Result<()> {
     match event {
@@ -556,34 +863,52 @@ fn process_dbus_event(event: &dbus_client::Message) -> Result<()> {
 fn process_window_event(event: &dyn WindowSensorData) -> Result<()> {
     trace!("Sensor data: {:#?}", event);
 
-    for (selector, (metadata, action)) in RULES_MAP.read().iter() {
-        match selector {
-            Selector::WindowFocused { mode, regex } => {
-                if metadata.enabled {
-                    let re = Regex::new(regex)?;
+    // failing to report the window must not prevent the rules from being processed
+    if let Err(e) = report_active_window(event) {
+        error!("Could not report the active window: {}", e);
+    }
 
-                    match mode {
-                        WindowFocusedSelectorMode::WindowName => {
-                            if re.is_match(event.window_name().unwrap_or_default()) {
-                                process_action(action)?;
-                                break;
-                            }
-                        }
+    let rules_map = RULES_MAP.read();
 
-                        WindowFocusedSelectorMode::WindowInstance => {
-                            if re.is_match(event.window_instance().unwrap_or_default()) {
-                                process_action(action)?;
-                                break;
-                            }
-                        }
+    let is_match = |mode: &WindowFocusedSelectorMode, regex: &str| -> Result<bool> {
+        let re = Regex::new(regex)?;
 
-                        WindowFocusedSelectorMode::WindowClass => {
-                            if re.is_match(event.window_class().unwrap_or_default()) {
-                                process_action(action)?;
-                                break;
-                            }
-                        }
-                    }
+        let value = match mode {
+            WindowFocusedSelectorMode::WindowName => event.window_name(),
+            WindowFocusedSelectorMode::WindowInstance => event.window_instance(),
+            WindowFocusedSelectorMode::WindowClass => event.window_class(),
+        };
+
+        Ok(re.is_match(value.unwrap_or_default()))
+    };
+
+    // privacy mode stays enabled only while a matching window is focused, independent
+    // of the rules that switch profiles or slots
+    let mut privacy_zones = None;
+
+    for (selector, (metadata, action)) in rules_map.iter() {
+        if let (Selector::WindowFocused { mode, regex }, Action::EnablePrivacyMode { zones }) =
+            (selector, action)
+        {
+            if metadata.is_active() && is_match(mode, regex)? {
+                privacy_zones = Some(zones);
+                break;
+            }
+        }
+    }
+
+    set_privacy_mode(privacy_zones)?;
+
+    for (selector, (metadata, action)) in rules_map.iter() {
+        match selector {
+            Selector::WindowFocused { mode, regex } => {
+                if matches!(action, Action::EnablePrivacyMode { .. }) {
+                    continue;
+                }
+
+                if metadata.is_active() && is_match(mode, regex)? {
+                    process_action(action)?;
+                    break;
                 }
             }
 
@@ -594,6 +919,85 @@ fn process_window_event(event: &dyn WindowSensorData) -> Result<()> {
     Ok(())
 }
 
+/// Report the metadata of the focused window to the Eruption daemon, if it has changed,
+/// so that Lua scripts may react to the active application
+fn report_active_window(event: &dyn WindowSensorData) -> Result<()> {
+    let window = (
+        event.window_class().unwrap_or_default().to_string(),
+        event.window_name().unwrap_or_default().to_string(),
+        event.window_instance().unwrap_or_default().to_string(),
+    );
+
+    let mut last_window = LAST_ACTIVE_WINDOW.lock();
+
+    if last_window.as_ref() != Some(&window) {
+        dbus_client::set_active_window(&window.0, &window.1, &window.2)?;
+
+        *last_window = Some(window);
+    }
+
+    Ok(())
+}
+
+/// Report the position of the mouse pointer to the Eruption daemon, if it has moved,
+/// or if the daemon would otherwise consider the last reported position stale
+#[cfg(feature = "sensor-x11")]
+fn process_pointer_position(position: (f64, f64)) -> Result<()> {
+    let mut last_position = LAST_POINTER_POSITION.lock();
+
+    let is_due = match *last_position {
+        Some((last, timestamp)) => {
+            last != position
+                || timestamp.elapsed()
+                    >= Duration::from_millis(constants::POINTER_REFRESH_INTERVAL_MILLIS)
+        }
+
+        None => true,
+    };
+
+    if is_due {
+        dbus_client::set_pointer_position(position.0, position.1)?;
+
+        *last_position = Some((position, Instant::now()));
+    }
+
+    Ok(())
+}
+
+/// Report the state of the keyboard lock indicators and the active layout to the
+/// Eruption daemon, if it has changed, or if the daemon would otherwise consider
+/// the last reported state stale
+#[cfg(feature = "sensor-x11")]
+fn process_keyboard_indicators(indicators: &KeyboardIndicators) -> Result<()> {
+    let mut last_indicators = LAST_KEYBOARD_INDICATORS.lock();
+
+    let is_due = match &*last_indicators {
+        Some((last, timestamp)) => {
+            last != indicators
+                || timestamp.elapsed()
+                    >= Duration::from_millis(constants::INDICATORS_REFRESH_INTERVAL_MILLIS)
+        }
+
+        None => true,
+    };
+
+    if is_due {
+        dbus_client::set_keyboard_indicators(
+            (
+                indicators.caps_lock,
+                indicators.num_lock,
+                indicators.scroll_lock,
+            ),
+            indicators.group,
+            &indicators.layout,
+        )?;
+
+        *last_indicators = Some((indicators.clone(), Instant::now()));
+    }
+
+    Ok(())
+}
+
 /// Watch filesystem events
 pub fn register_filesystem_watcher(
     fsevents_tx: Sender<FileSystemEvent>,
@@ -809,8 +1213,10 @@ mod thread_util {
 pub fn run_main_loop(
     #[cfg(feature = "sensor-procmon")] sysevents_rx: &Receiver<SystemEvent>,
     #[cfg(feature = "sensor-wayland")] wayland_rx: &Receiver<WaylandSensorData>,
+    #[cfg(feature = "sensor-kwin")] kwin_rx: &Receiver<KWinSensorData>,
     fsevents_rx: &Receiver<FileSystemEvent>,
     dbusevents_rx: &Receiver<dbus_client::Message>,
+    dbus_rx: &Receiver<dbus_interface::Message>,
     ctrl_c_rx: &Receiver<bool>,
     dbus_api_tx: &Sender<DbusApiEvent>,
 ) -> Result<()> {
@@ -843,6 +1249,14 @@ pub fn run_main_loop(
                 } else {
                     error!("{}", event.as_ref().unwrap_err());
                 }
+            })
+            .recv(dbus_rx, |message| {
+                if let Ok(message) = message {
+                    process_dbus_api_message(&message)
+                        .unwrap_or_else(|e| error!("Could not process a D-Bus API message: {}", e))
+                } else {
+                    error!("{}", message.as_ref().unwrap_err());
+                }
             });
 
         #[cfg(feature = "sensor-procmon")]
@@ -885,8 +1299,43 @@ pub fn run_main_loop(
             }
         }
 
+        #[cfg(feature = "sensor-kwin")]
+        {
+            if SENSORS_CONFIGURATION
+                .read()
+                .contains(&SensorConfiguration::EnableKWin)
+            {
+                sel = sel.recv(kwin_rx, |event| {
+                    log::trace!("Sensor data: {:?}", event);
+
+                    if let Ok(event) = event {
+                        process_window_event(&event as &dyn WindowSensorData).unwrap_or_else(|e| {
+                            error!("Could not process a KWin sensor event: {}", e)
+                        });
+                    } else {
+                        error!("{}", event.as_ref().unwrap_err());
+                    }
+                });
+            }
+        }
+
         let _result = sel.wait_timeout(Duration::from_millis(constants::MAIN_LOOP_SLEEP_MILLIS));
 
+        // fall back to polling /proc, if none of the other sensors is operational
+        #[cfg(feature = "sensor-procfs")]
+        {
+            let fallback_required = sensors::is_fallback_required();
+
+            if PROCFS_SENSOR_FALLBACK.swap(fallback_required, Ordering::SeqCst) != fallback_required
+            {
+                if fallback_required {
+                    warn!("No other sensor is operational, falling back to polling /proc");
+                } else {
+                    info!("Sensors are operational again, no longer polling /proc");
+                }
+            }
+        }
+
         // poll all pollable sensors that do not notify us via messages
         for sensor in sensors::SENSORS.write().iter_mut() {
             if sensor.is_enabled() && sensor.is_pollable() && !sensor.is_failed() {
@@ -925,12 +1374,44 @@ pub fn run_main_loop(
                         //     handled = true;
                         // }
 
+                        #[cfg(feature = "sensor-hardware")]
+                        if let Some(data) = data.as_any().downcast_ref::<HardwareSensorData>() {
+                            log::trace!("Processing hardware sensor data");
+
+                            process_hardware_event(data).unwrap_or_else(|e| {
+                                error!("Could not process a hardware event: {}", e)
+                            });
+
+                            handled = true;
+                        }
+
+                        #[cfg(feature = "sensor-procfs")]
+                        if let Some(data) = data.as_any().downcast_ref::<ProcfsSensorData>() {
+                            log::trace!("Processing procfs sensor data");
+
+                            process_procfs_event(data)?;
+
+                            handled = true;
+                        }
+
                         #[cfg(feature = "sensor-x11")]
                         if let Some(data) = data.as_any().downcast_ref::<X11SensorData>() {
                             log::trace!("Processing X11 sensor data");
 
                             process_window_event(data)?;
 
+                            if let Some(position) = data.pointer {
+                                process_pointer_position(position).unwrap_or_else(|e| {
+                                    error!("Could not report the pointer position: {}", e)
+                                });
+                            }
+
+                            if let Some(indicators) = &data.indicators {
+                                process_keyboard_indicators(indicators).unwrap_or_else(|e| {
+                                    error!("Could not report the keyboard indicators: {}", e)
+                                });
+                            }
+
                             handled = true;
                         }
 
@@ -965,6 +1446,16 @@ fn autodetect_sensor_configuration() -> Result<()> {
         == "gnome"
     {
         SensorConfiguration::profile_gnome_desktop()
+    } else if env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_lowercase()
+        == "kde"
+        && env::var("XDG_SESSION_TYPE")
+            .unwrap_or_default()
+            .to_lowercase()
+            == "wayland"
+    {
+        SensorConfiguration::profile_kde_plasma_desktop()
     } else if env::var("XDG_SESSION_TYPE")
         .unwrap_or_default()
         .to_lowercase()
@@ -1199,7 +1690,7 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             spawn_dbus_thread(dbusevents_tx)?;
 
             // initialize the D-Bus API
-            let (dbus_tx, _dbus_rx) = unbounded();
+            let (dbus_tx, dbus_rx) = unbounded();
             let dbus_api_tx = spawn_dbus_api_thread(dbus_tx)?;
 
             let (fsevents_tx, fsevents_rx) = unbounded();
@@ -1232,6 +1723,19 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 wayland_sensor.spawn_wayland_events_thread(wayland_tx)?;
             }
 
+            #[cfg(feature = "sensor-kwin")]
+            let (kwin_tx, kwin_rx) = unbounded();
+
+            #[cfg(feature = "sensor-kwin")]
+            if let Some(mut s) = sensors::find_sensor_by_id("kwin") {
+                let kwin_sensor = s
+                    .as_any_mut()
+                    .downcast_mut::<sensors::KWinSensor>()
+                    .unwrap();
+
+                kwin_sensor.spawn_kwin_events_thread(kwin_tx)?;
+            }
+
             info!("Loading global state from Eruption daemon");
 
             let active_slot = dbus_client::get_active_slot()?;
@@ -1251,14 +1755,20 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 &sysevents_rx,
                 #[cfg(feature = "sensor-wayland")]
                 &wayland_rx,
+                #[cfg(feature = "sensor-kwin")]
+                &kwin_rx,
                 &fsevents_rx,
                 &dbusevents_rx,
+                &dbus_rx,
                 &ctrl_c_rx,
                 &dbus_api_tx,
             )
             .unwrap_or_else(|e| error!("{}", e));
 
             debug!("Left the main loop");
+
+            set_privacy_mode(None)
+                .unwrap_or_else(|e| error!("Could not disable privacy mode: {}", e));
         }
 
         Subcommands::Rules { command } => match command {
@@ -1268,16 +1778,44 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 }
             }
 
-            RulesSubcommands::Add { rule } => {
+            RulesSubcommands::Add { descendants, rule } => {
                 fn print_usage_examples() {
                     eprintln!("\nPlease see below for some examples:");
 
                     for s in sensors::SENSORS.read().iter() {
                         eprintln!("{}", s.get_usage_example());
                     }
+
+                    eprintln!(
+                        r#"
+Time windows:
+rules add <sensor> <selector> [<profile-name.profile>|<slot number>] HH:MM-HH:MM[/<weekdays>]
+
+rules add exec gnome-calc.* 2 09:00-17:00/mon-fri
+rules add window-class firefox 3 18:00-01:00/sat,sun
+
+External events, pushed by third-party scripts via D-Bus:
+rules add external <regex> [<profile-name.profile>|<slot number>]
+
+rules add external build-failed alert.profile
+
+Hardware events, the lid of laptops and hotplugging of external displays:
+rules add hardware [lid-closed|lid-opened|docked|undocked] [<profile-name.profile>|<slot number>]
+rules add hardware display-[connected|disconnected]/<connector regex> [<profile-name.profile>|<slot number>]
+
+rules add hardware docked desk.profile
+rules add hardware 'display-connected/HDMI-.*' 2
+
+Privacy mode, blacks out the whole canvas or the specified zones while a matching window is focused:
+rules add window-[class|instance|name] <regex> privacy[:<zone>[,<zone>...]]
+
+rules add window-class keepassxc privacy
+rules add window-name '.*Online Banking.*' privacy:numbers,numpad
+"#
+                    );
                 }
 
-                if rule.len() != 3 {
+                if rule.len() != 3 && rule.len() != 4 {
                     eprintln!("Malformed rule definition");
                     print_usage_examples();
                 } else {
@@ -1285,6 +1823,12 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     let selector = &rule[1];
                     let action = &rule[2];
 
+                    let metadata = RuleMetadata {
+                        time_window: rule.get(3).map(|s| s.parse::<TimeWindow>()).transpose()?,
+                        include_descendants: descendants,
+                        ..Default::default()
+                    };
+
                     let mut parsed_selector = None;
                     let parsed_action;
 
@@ -1308,20 +1852,45 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                             mode: WindowFocusedSelectorMode::WindowName,
                             regex: selector.clone(),
                         });
+                    } else if sensor.contains("external") {
+                        parsed_selector = Some(Selector::External {
+                            regex: selector.clone(),
+                        });
+                    } else if sensor.contains("hardware") {
+                        parsed_selector = Some(Selector::Hardware {
+                            regex: selector.clone(),
+                        });
                     }
 
                     if parsed_selector.is_none() {
                         eprintln!("Syntax error in selector");
                         print_usage_examples();
+                    } else if descendants
+                        && !matches!(parsed_selector, Some(Selector::ProcessExec { .. }))
+                    {
+                        eprintln!("Only exec rules may apply to descendants of a process");
+                        print_usage_examples();
+                    } else if parse_privacy_action(action).is_some()
+                        && !matches!(parsed_selector, Some(Selector::WindowFocused { .. }))
+                    {
+                        eprintln!("Only window rules may enable privacy mode");
+                        print_usage_examples();
                     } else {
-                        if action.contains(".profile") {
+                        if let Some(zones) = parse_privacy_action(action) {
+                            parsed_action = Action::EnablePrivacyMode { zones };
+
+                            RULES_MAP.write().insert(
+                                parsed_selector.clone().unwrap(),
+                                (metadata.clone(), parsed_action.clone()),
+                            );
+                        } else if action.contains(".profile") {
                             parsed_action = Action::SwitchToProfile {
                                 profile_name: action.clone(),
                             };
 
                             RULES_MAP.write().insert(
                                 parsed_selector.clone().unwrap(),
-                                (RuleMetadata::default(), parsed_action.clone()),
+                                (metadata.clone(), parsed_action.clone()),
                             );
                         } else {
                             parsed_action = Action::SwitchToSlot {
@@ -1330,12 +1899,17 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
 
                             RULES_MAP.write().insert(
                                 parsed_selector.clone().unwrap(),
-                                (RuleMetadata::default(), parsed_action.clone()),
+                                (metadata.clone(), parsed_action.clone()),
                             );
                         }
 
                         // print resulting action to console
-                        println!("{} => {}", parsed_selector.unwrap(), parsed_action);
+                        println!(
+                            "{} => {} ({})",
+                            parsed_selector.unwrap(),
+                            parsed_action,
+                            metadata
+                        );
 
                         save_rules_map()?;
                     }
@@ -1438,3 +2012,29 @@ pub fn main() -> std::result::Result<(), eyre::Error> {
 
     runtime.block_on(async move { async_main().await })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hardware_rules_match_whole_events() {
+        assert!(matches_hardware_event("docked", "docked").unwrap());
+        assert!(!matches_hardware_event("docked", "undocked").unwrap());
+        assert!(!matches_hardware_event("lid-closed", "lid-closed/extra").unwrap());
+
+        assert!(
+            matches_hardware_event("display-connected/HDMI-.*", "display-connected/HDMI-A-1")
+                .unwrap()
+        );
+        assert!(!matches_hardware_event(
+            "display-connected/HDMI-.*",
+            "display-disconnected/HDMI-A-1"
+        )
+        .unwrap());
+
+        // alternations are anchored as a whole
+        assert!(matches_hardware_event("docked|undocked", "undocked").unwrap());
+        assert!(!matches_hardware_event("docked|lid", "lid-opened").unwrap());
+    }
+}
@@ -0,0 +1,359 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::Connection;
+use log::*;
+
+use crate::constants;
+
+use super::{Sensor, SensorConfiguration, SENSORS_CONFIGURATION};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+thread_local! {
+    /// Connection to the system bus, re-used across polls and re-established after errors
+    static SYSTEM_BUS: RefCell<Option<Connection>> = RefCell::new(None);
+}
+
+/// Prefixes of the DRM connectors of built-in panels, all other connectors are external
+const INTERNAL_CONNECTORS: &[&str] = &["eDP", "LVDS", "DSI"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HardwareSensorData {
+    /// State of the lid, `None` if the device has no lid
+    pub lid_closed: Option<bool>,
+
+    /// DRM connectors of the external displays that are connected, e.g. `HDMI-A-1`
+    pub external_displays: Vec<String>,
+}
+
+impl super::SensorData for HardwareSensorData {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HardwareSensor {
+    /// The most recently polled state, re-used until the poll interval elapsed
+    last_data: HardwareSensorData,
+    last_poll: Option<Instant>,
+}
+
+impl HardwareSensor {
+    pub fn new() -> Self {
+        HardwareSensor {
+            last_data: HardwareSensorData::default(),
+            last_poll: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Sensor for HardwareSensor {
+    fn get_id(&self) -> String {
+        "hardware".to_string()
+    }
+
+    fn get_name(&self) -> String {
+        "Hardware".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Watches the lid of laptops and the hotplugging of external displays".to_string()
+    }
+
+    fn get_usage_example(&self) -> String {
+        r#"
+Hardware:
+rules add hardware lid-closed [<profile-name.profile>|<slot number>]
+rules add hardware lid-opened [<profile-name.profile>|<slot number>]
+rules add hardware docked [<profile-name.profile>|<slot number>]
+rules add hardware undocked [<profile-name.profile>|<slot number>]
+rules add hardware display-connected/<connector regex> [<profile-name.profile>|<slot number>]
+rules add hardware display-disconnected/<connector regex> [<profile-name.profile>|<slot number>]
+
+rules add hardware docked desk.profile
+rules add hardware 'display-connected/HDMI-.*' 2
+"#
+        .to_string()
+    }
+
+    fn initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_enabled(&self) -> bool {
+        SENSORS_CONFIGURATION
+            .read()
+            .contains(&SensorConfiguration::EnableHardware)
+    }
+
+    fn is_pollable(&self) -> bool {
+        true
+    }
+
+    fn is_failed(&self) -> bool {
+        false
+    }
+
+    fn set_failed(&mut self, _failed: bool) {
+        // no op
+    }
+
+    fn poll(&mut self) -> Result<Box<dyn super::SensorData>> {
+        let is_due = self.last_poll.map_or(true, |t| {
+            t.elapsed() >= Duration::from_millis(constants::HARDWARE_POLL_INTERVAL_MILLIS)
+        });
+
+        if is_due {
+            self.last_data = HardwareSensorData {
+                lid_closed: query_lid_state(),
+                external_displays: query_external_displays(),
+            };
+
+            self.last_poll = Some(Instant::now());
+        }
+
+        Ok(Box::from(self.last_data.clone()))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Query the state of the lid from logind, fall back to ACPI if logind is not available
+fn query_lid_state() -> Option<bool> {
+    match query_logind_lid_state() {
+        Ok(result) => result,
+
+        Err(e) => {
+            trace!("Could not query the lid state from logind: {}", e);
+
+            query_acpi_lid_state()
+        }
+    }
+}
+
+fn query_logind_lid_state() -> Result<Option<bool>> {
+    SYSTEM_BUS.with(|system_bus| {
+        let mut system_bus = system_bus.borrow_mut();
+
+        if system_bus.is_none() {
+            *system_bus = Some(Connection::new_system()?);
+        }
+
+        let result = system_bus
+            .as_ref()
+            .map_or(Ok(None), query_logind_lid_state_with);
+
+        if result.is_err() {
+            // the connection may have been dropped, e.g. when the system bus restarted
+            *system_bus = None;
+        }
+
+        result
+    })
+}
+
+fn query_logind_lid_state_with(conn: &Connection) -> Result<Option<bool>> {
+    let proxy = conn.with_proxy(
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let present: bool = proxy.get("org.freedesktop.login1.Manager", "LidPresent")?;
+
+    if present {
+        let closed: bool = proxy.get("org.freedesktop.login1.Manager", "LidClosed")?;
+
+        Ok(Some(closed))
+    } else {
+        Ok(None)
+    }
+}
+
+fn query_acpi_lid_state() -> Option<bool> {
+    let entry = fs::read_dir("/proc/acpi/button/lid")
+        .ok()?
+        .flatten()
+        .next()?;
+    let state = fs::read_to_string(entry.path().join("state")).ok()?;
+
+    // the file reads e.g. "state:      open"
+    Some(state.contains("closed"))
+}
+
+/// Returns the DRM connectors of all connected external displays, in alphabetical order
+fn query_external_displays() -> Vec<String> {
+    let entries = match fs::read_dir("/sys/class/drm") {
+        Ok(entries) => entries,
+
+        Err(e) => {
+            trace!("Could not enumerate the DRM connectors: {}", e);
+
+            return vec![];
+        }
+    };
+
+    let mut result = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            // connectors are named like "card0-HDMI-A-1"
+            let connector = name.split_once('-')?.1.to_string();
+
+            if is_internal_connector(&connector) || !is_connected(&entry.path()) {
+                None
+            } else {
+                Some(connector)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    result.sort();
+    result.dedup();
+
+    result
+}
+
+fn is_internal_connector(connector: &str) -> bool {
+    INTERNAL_CONNECTORS
+        .iter()
+        .any(|prefix| connector.starts_with(prefix))
+}
+
+fn is_connected(path: &Path) -> bool {
+    fs::read_to_string(path.join("status"))
+        .map(|status| status.trim() == "connected")
+        .unwrap_or(false)
+}
+
+/// Returns the names of the events that lead from the state `previous` to `current`, as matched
+/// by the rules of the `hardware` sensor: `lid-closed`, `lid-opened`, `display-connected/<connector>`,
+/// `display-disconnected/<connector>`, `docked` and `undocked`
+pub fn hardware_events(previous: &HardwareSensorData, current: &HardwareSensorData) -> Vec<String> {
+    let mut result = vec![];
+
+    match (previous.lid_closed, current.lid_closed) {
+        (Some(false), Some(true)) => result.push("lid-closed".to_string()),
+        (Some(true), Some(false)) => result.push("lid-opened".to_string()),
+        _ => {}
+    }
+
+    for connector in &current.external_displays {
+        if !previous.external_displays.contains(connector) {
+            result.push(format!("display-connected/{}", connector));
+        }
+    }
+
+    for connector in &previous.external_displays {
+        if !current.external_displays.contains(connector) {
+            result.push(format!("display-disconnected/{}", connector));
+        }
+    }
+
+    match (
+        previous.external_displays.is_empty(),
+        current.external_displays.is_empty(),
+    ) {
+        (true, false) => result.push("docked".to_string()),
+        (false, true) => result.push("undocked".to_string()),
+        _ => {}
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(lid_closed: Option<bool>, external_displays: &[&str]) -> HardwareSensorData {
+        HardwareSensorData {
+            lid_closed,
+            external_displays: external_displays.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn lid_events() {
+        assert_eq!(
+            hardware_events(&state(Some(false), &[]), &state(Some(true), &[])),
+            vec!["lid-closed"]
+        );
+        assert_eq!(
+            hardware_events(&state(Some(true), &[]), &state(Some(false), &[])),
+            vec!["lid-opened"]
+        );
+
+        // devices without a lid do not generate lid events
+        assert!(hardware_events(&state(None, &[]), &state(Some(true), &[])).is_empty());
+    }
+
+    #[test]
+    fn display_and_dock_events() {
+        assert_eq!(
+            hardware_events(&state(None, &[]), &state(None, &["HDMI-A-1"])),
+            vec!["display-connected/HDMI-A-1", "docked"]
+        );
+        assert_eq!(
+            hardware_events(&state(None, &["DP-1", "HDMI-A-1"]), &state(None, &["DP-1"])),
+            vec!["display-disconnected/HDMI-A-1"]
+        );
+        assert_eq!(
+            hardware_events(&state(None, &["DP-1"]), &state(None, &["DP-2"])),
+            vec!["display-connected/DP-2", "display-disconnected/DP-1"]
+        );
+        assert_eq!(
+            hardware_events(&state(None, &["DP-1"]), &state(None, &[])),
+            vec!["display-disconnected/DP-1", "undocked"]
+        );
+
+        assert!(hardware_events(
+            &state(Some(false), &["DP-1"]),
+            &state(Some(false), &["DP-1"])
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn internal_connectors_are_recognized() {
+        assert!(is_internal_connector("eDP-1"));
+        assert!(is_internal_connector("LVDS-1"));
+        assert!(!is_internal_connector("HDMI-A-1"));
+        assert!(!is_internal_connector("DP-1"));
+    }
+}
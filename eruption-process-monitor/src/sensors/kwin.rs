@@ -0,0 +1,336 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use async_trait::async_trait;
+use dbus::blocking::Connection;
+use dbus::channel::{MatchingReceiver, Sender as _};
+use dbus::message::MatchRule;
+use dbus::Message;
+use flume::Sender;
+use std::{env, fs, path::PathBuf, sync::atomic::Ordering, thread, time::Duration};
+
+use crate::{constants, QUIT};
+
+use super::{Sensor, SensorConfiguration, SENSORS_CONFIGURATION};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Plugin name of the KWin script, that reports the focused window to us
+const KWIN_SCRIPT_PLUGIN_NAME: &str = "eruption-sensor";
+
+/// Object path, interface and method that the KWin script calls on our connection
+const KWIN_SENSOR_PATH: &str = "/org/eruption/process_monitor/kwin";
+const KWIN_SENSOR_INTERFACE: &str = "org.eruption.process_monitor.KWin";
+const KWIN_SENSOR_METHOD: &str = "WindowActivated";
+
+/// JavaScript code that reports the properties of the focused window, whenever it changes.
+/// Plasma 6 calls them windows, while Plasma 5 calls them clients
+const KWIN_TOPLEVEL_WINDOW_PROPS_SCRIPT: &str = r#"
+        let active = null;
+
+        function report() {
+            if (!active)
+                return;
+
+            callDBus('{bus_name}', '{path}', '{interface}', '{method}',
+                     String(active.caption), String(active.resourceName),
+                     String(active.resourceClass), String(active.pid));
+        }
+
+        function activated(window) {
+            if (active)
+                active.captionChanged.disconnect(report);
+
+            active = window;
+
+            if (active)
+                active.captionChanged.connect(report);
+
+            report();
+        }
+
+        if (workspace.windowActivated) {
+            workspace.windowActivated.connect(activated);
+            activated(workspace.activeWindow);
+        } else {
+            workspace.clientActivated.connect(activated);
+            activated(workspace.activeClient);
+        }
+"#;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KWinSensorError {
+    #[error("KWin is not running on the session bus")]
+    KWinNotRunning,
+
+    #[error("Could not load the KWin script: {description}")]
+    ScriptError { description: String },
+
+    #[error("Operation not supported")]
+    NotSupported,
+}
+
+#[derive(Debug, Clone)]
+pub struct KWinSensorData {
+    pub window_title: String,
+    pub window_instance: String,
+    pub window_class: String,
+    pub pid: i32,
+}
+
+impl super::SensorData for KWinSensorData {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl super::WindowSensorData for KWinSensorData {
+    fn window_name(&self) -> Option<&str> {
+        Some(&self.window_title)
+    }
+
+    fn window_instance(&self) -> Option<&str> {
+        Some(&self.window_instance)
+    }
+
+    fn window_class(&self) -> Option<&str> {
+        Some(&self.window_class)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KWinSensor {
+    pub is_failed: bool,
+}
+
+impl KWinSensor {
+    pub fn new() -> Self {
+        Self { is_failed: false }
+    }
+
+    /// Load our script into KWin and relay the focused window, as reported by the script,
+    /// to the main thread. The script calls back into a private connection of this thread
+    pub fn spawn_kwin_events_thread(&mut self, kwin_tx: Sender<KWinSensorData>) -> Result<()> {
+        thread::Builder::new()
+            .name("kwin-events".to_owned())
+            .spawn(move || {
+                run_kwin_events_loop(kwin_tx)
+                    .unwrap_or_else(|e| log::info!("The KWin sensor is unavailable: {}", e));
+            })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sensor for KWinSensor {
+    fn get_id(&self) -> String {
+        "kwin".to_string()
+    }
+
+    fn get_name(&self) -> String {
+        "KWin".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Watches the state of windows on a KDE Plasma desktop using the KWin scripting interface"
+            .to_string()
+    }
+
+    fn get_usage_example(&self) -> String {
+        r#"
+KWin:
+rules add window-[class|instance|name] <regex> [<profile-name.profile>|<slot number>]
+
+rules add window-name '.*YouTube.*Mozilla Firefox' /var/lib/eruption/profiles/profile1.profile
+rules add window-class org.kde.kcalc 2
+"#
+        .to_string()
+    }
+
+    fn initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_enabled(&self) -> bool {
+        SENSORS_CONFIGURATION
+            .read()
+            .contains(&SensorConfiguration::EnableKWin)
+    }
+
+    fn is_pollable(&self) -> bool {
+        false
+    }
+
+    fn is_failed(&self) -> bool {
+        self.is_failed
+    }
+
+    fn set_failed(&mut self, failed: bool) {
+        self.is_failed = failed;
+    }
+
+    fn poll(&mut self) -> Result<Box<dyn super::SensorData>> {
+        Err(KWinSensorError::NotSupported {}.into())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+fn run_kwin_events_loop(kwin_tx: Sender<KWinSensorData>) -> Result<()> {
+    let conn = Connection::new_session()?;
+
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let (kwin_running,): (bool,) =
+        proxy.method_call("org.freedesktop.DBus", "NameHasOwner", ("org.kde.KWin",))?;
+
+    if !kwin_running {
+        return Err(KWinSensorError::KWinNotRunning {}.into());
+    }
+
+    let rule = MatchRule::new_method_call()
+        .with_path(KWIN_SENSOR_PATH)
+        .with_interface(KWIN_SENSOR_INTERFACE)
+        .with_member(KWIN_SENSOR_METHOD);
+
+    conn.start_receive(
+        rule,
+        Box::new(move |msg: Message, conn: &Connection| {
+            match parse_window_activated(&msg) {
+                Ok(data) => {
+                    // only relay events while the sensor is enabled, the main loop does
+                    // not drain the channel otherwise
+                    if SENSORS_CONFIGURATION
+                        .read()
+                        .contains(&SensorConfiguration::EnableKWin)
+                    {
+                        kwin_tx.send(data).unwrap_or_else(|e| {
+                            log::error!("Could not relay a KWin sensor event: {}", e)
+                        });
+                    }
+                }
+
+                Err(e) => log::error!("Invalid KWin sensor event: {}", e),
+            }
+
+            conn.send(msg.method_return()).ok();
+
+            true
+        }),
+    );
+
+    let script_file = load_script(&conn)?;
+
+    log::info!("Loaded the KWin script: {}", script_file.display());
+
+    while !QUIT.load(Ordering::SeqCst) {
+        conn.process(Duration::from_millis(constants::MAIN_LOOP_SLEEP_MILLIS))?;
+    }
+
+    unload_script(&conn)?;
+
+    fs::remove_file(&script_file)?;
+
+    Ok(())
+}
+
+/// Parse the arguments of a call of the KWin script
+fn parse_window_activated(msg: &Message) -> Result<KWinSensorData> {
+    let (window_title, window_instance, window_class, pid): (String, String, String, String) =
+        msg.read4()?;
+
+    Ok(KWinSensorData {
+        window_title,
+        window_instance,
+        window_class,
+        pid: pid.parse().unwrap_or(-1),
+    })
+}
+
+/// Write our script to the runtime directory and load it into KWin, the script then
+/// calls back into `conn`. Returns the path of the script file
+fn load_script(conn: &Connection) -> Result<PathBuf> {
+    let script_file = PathBuf::from(
+        env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_e| "/run/user/1000/".to_string()),
+    )
+    .join(format!("{KWIN_SCRIPT_PLUGIN_NAME}.js"));
+
+    let source = KWIN_TOPLEVEL_WINDOW_PROPS_SCRIPT
+        .replace("{bus_name}", &conn.unique_name())
+        .replace("{path}", KWIN_SENSOR_PATH)
+        .replace("{interface}", KWIN_SENSOR_INTERFACE)
+        .replace("{method}", KWIN_SENSOR_METHOD);
+
+    fs::write(&script_file, source)?;
+
+    // a previous instance of the process monitor may have left its script behind
+    unload_script(conn)?;
+
+    let proxy = kwin_scripting_proxy(conn);
+
+    let (id,): (i32,) = proxy.method_call(
+        "org.kde.kwin.Scripting",
+        "loadScript",
+        (&*script_file.to_string_lossy(), KWIN_SCRIPT_PLUGIN_NAME),
+    )?;
+
+    if id < 0 {
+        return Err(KWinSensorError::ScriptError {
+            description: format!("KWin rejected {}", script_file.display()),
+        }
+        .into());
+    }
+
+    // run all scripts that have been loaded, but not yet started
+    proxy.method_call::<(), _, _, _>("org.kde.kwin.Scripting", "start", ())?;
+
+    Ok(script_file)
+}
+
+/// Unload our script from KWin, if it is loaded
+fn unload_script(conn: &Connection) -> Result<()> {
+    let (_unloaded,): (bool,) = kwin_scripting_proxy(conn).method_call(
+        "org.kde.kwin.Scripting",
+        "unloadScript",
+        (KWIN_SCRIPT_PLUGIN_NAME,),
+    )?;
+
+    Ok(())
+}
+
+fn kwin_scripting_proxy(conn: &Connection) -> dbus::blocking::Proxy<'_, &Connection> {
+    conn.with_proxy(
+        "org.kde.KWin",
+        "/Scripting",
+        Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS),
+    )
+}
@@ -29,10 +29,16 @@ use parking_lot::RwLock;
 
 #[cfg(feature = "sensor-gnome-shellext")]
 mod gnome_shellext;
+#[cfg(feature = "sensor-hardware")]
+mod hardware;
+#[cfg(feature = "sensor-kwin")]
+mod kwin;
 #[cfg(feature = "sensor-mutter")]
 mod mutter;
 #[cfg(feature = "sensor-procmon")]
 mod process;
+#[cfg(feature = "sensor-procfs")]
+mod procfs;
 #[cfg(feature = "sensor-wayland")]
 mod wayland;
 #[cfg(feature = "sensor-x11")]
@@ -40,10 +46,16 @@ mod x11;
 
 #[cfg(feature = "sensor-gnome-shellext")]
 pub use gnome_shellext::*;
+#[cfg(feature = "sensor-hardware")]
+pub use hardware::*;
+#[cfg(feature = "sensor-kwin")]
+pub use kwin::*;
 #[cfg(feature = "sensor-mutter")]
 pub use mutter::*;
 #[cfg(feature = "sensor-procmon")]
 pub use process::*;
+#[cfg(feature = "sensor-procfs")]
+pub use procfs::*;
 #[cfg(feature = "sensor-wayland")]
 pub use wayland::*;
 #[cfg(feature = "sensor-x11")]
@@ -67,12 +79,21 @@ pub enum SensorConfiguration {
     #[cfg(feature = "sensor-procmon")]
     EnableProcmon,
 
+    #[cfg(feature = "sensor-procfs")]
+    EnableProcfs,
+
+    #[cfg(feature = "sensor-hardware")]
+    EnableHardware,
+
     #[cfg(feature = "sensor-gnome-shellext")]
     EnableGnomeShellExt,
 
     #[cfg(feature = "sensor-mutter")]
     EnableMutter,
 
+    #[cfg(feature = "sensor-kwin")]
+    EnableKWin,
+
     #[cfg(feature = "sensor-wayland")]
     EnableWayland,
 
@@ -89,6 +110,8 @@ impl SensorConfiguration {
                 HashSet::from_iter([
                     #[cfg(feature = "sensor-procmon")]
                     SensorConfiguration::EnableProcmon,
+                    #[cfg(feature = "sensor-hardware")]
+                    SensorConfiguration::EnableHardware,
 
                     #[cfg(feature = "sensor-gnome-shellext")]
                     SensorConfiguration::EnableGnomeShellExt,
@@ -98,6 +121,8 @@ impl SensorConfiguration {
                 HashSet::from_iter([
                     #[cfg(feature = "sensor-procmon")]
                     SensorConfiguration::EnableProcmon,
+                    #[cfg(feature = "sensor-hardware")]
+                    SensorConfiguration::EnableHardware,
 
                     #[cfg(feature = "sensor-mutter")]
                     SensorConfiguration::EnableMutter,
@@ -106,6 +131,8 @@ impl SensorConfiguration {
                HashSet::from_iter([
                    #[cfg(feature = "sensor-procmon")]
                    SensorConfiguration::EnableProcmon,
+                   #[cfg(feature = "sensor-hardware")]
+                   SensorConfiguration::EnableHardware,
 
                    #[cfg(feature = "sensor-x11")]
                    SensorConfiguration::EnableX11,
@@ -114,11 +141,33 @@ impl SensorConfiguration {
         }
     }
 
+    #[allow(unused)]
+    pub fn profile_kde_plasma_desktop() -> HashSet<Self> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "sensor-kwin")] {
+                // KWin does not implement the wlr foreign toplevel protocol on Wayland,
+                // so we let a KWin script report the focused window to us instead
+                HashSet::from_iter([
+                    #[cfg(feature = "sensor-procmon")]
+                    SensorConfiguration::EnableProcmon,
+                    #[cfg(feature = "sensor-hardware")]
+                    SensorConfiguration::EnableHardware,
+
+                    SensorConfiguration::EnableKWin,
+                ])
+            } else {
+                Self::profile_generic_wayland_compositor()
+            }
+        }
+    }
+
     #[allow(unused)]
     pub fn profile_generic_wayland_compositor() -> HashSet<Self> {
         HashSet::from_iter([
             #[cfg(feature = "sensor-procmon")]
             SensorConfiguration::EnableProcmon,
+            #[cfg(feature = "sensor-hardware")]
+            SensorConfiguration::EnableHardware,
             #[cfg(feature = "sensor-wayland")]
             SensorConfiguration::EnableWayland,
         ])
@@ -129,6 +178,8 @@ impl SensorConfiguration {
         HashSet::from_iter([
             #[cfg(feature = "sensor-procmon")]
             SensorConfiguration::EnableProcmon,
+            #[cfg(feature = "sensor-hardware")]
+            SensorConfiguration::EnableHardware,
             #[cfg(feature = "sensor-x11")]
             SensorConfiguration::EnableX11,
         ])
@@ -144,8 +195,12 @@ impl SensorConfiguration {
         HashSet::from_iter([
             #[cfg(feature = "sensor-procmon")]
             SensorConfiguration::EnableProcmon,
+            #[cfg(feature = "sensor-hardware")]
+            SensorConfiguration::EnableHardware,
             #[cfg(feature = "sensor-mutter")]
             SensorConfiguration::EnableMutter,
+            #[cfg(feature = "sensor-kwin")]
+            SensorConfiguration::EnableKWin,
             #[cfg(feature = "sensor-wayland")]
             SensorConfiguration::EnableWayland,
             #[cfg(feature = "sensor-x11")]
@@ -225,26 +280,50 @@ pub fn register_sensors() -> Result<()> {
     #[cfg(feature = "sensor-procmon")]
     register_sensor(ProcessSensor::new());
 
+    #[cfg(feature = "sensor-procfs")]
+    register_sensor(ProcfsSensor::new());
+
+    #[cfg(feature = "sensor-hardware")]
+    register_sensor(HardwareSensor::new());
+
     #[cfg(feature = "sensor-gnome-shellext")]
     register_sensor(GnomeShellExtensionSensor::new());
 
     #[cfg(feature = "sensor-mutter")]
     register_sensor(MutterSensor::new());
 
+    #[cfg(feature = "sensor-kwin")]
+    register_sensor(KWinSensor::new());
+
     #[cfg(feature = "sensor-wayland")]
     register_sensor(WaylandSensor::new());
 
     #[cfg(feature = "sensor-x11")]
     register_sensor(X11Sensor::new());
 
-    // initialize all registered sensors
+    // initialize all registered sensors, a failing sensor must not take down
+    // the whole daemon, since there may be other sensors that are still usable
     for s in SENSORS.write().iter_mut() {
-        s.initialize()?;
+        if let Err(e) = s.initialize() {
+            error!("Could not initialize sensor '{}': {}", s.get_id(), e);
+
+            s.set_failed(true);
+        }
     }
 
     Ok(())
 }
 
+/// Returns `true` if none of the enabled sensors is operational, except for
+/// the /proc polling sensor, which then needs to take over. The hardware sensor
+/// does not report any processes or windows, so it is not taken into account
+#[cfg(feature = "sensor-procfs")]
+pub fn is_fallback_required() -> bool {
+    !SENSORS.read().iter().any(|s| {
+        s.get_id() != "procfs" && s.get_id() != "hardware" && s.is_enabled() && !s.is_failed()
+    })
+}
+
 /// Find a sensor by its respective id
 #[allow(dead_code)]
 pub fn find_sensor_by_id(id: &str) -> Option<Box<dyn Sensor + Send + Sync + 'static>> {
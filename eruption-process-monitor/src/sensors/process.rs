@@ -21,7 +21,7 @@
 
 use super::{Sensor, SensorConfiguration, SENSORS_CONFIGURATION};
 use crate::procmon::{self, ProcMon};
-use crate::{util, SystemEvent};
+use crate::{ancestry, util, SystemEvent};
 use async_trait::async_trait;
 use flume::Sender;
 use lazy_static::lazy_static;
@@ -92,6 +92,13 @@ impl ProcessSensor {
                     }
 
                     match event.event_type {
+                        procmon::EventType::Fork => {
+                            // only track new processes, not new threads
+                            if event.pid == event.tgid {
+                                ancestry::on_fork(event.ppid, event.pid);
+                            }
+                        }
+
                         procmon::EventType::Exec => {
                             let pid = event.pid;
 
@@ -147,6 +154,9 @@ rules add exec <comm> [<profile-name.profile>|<slot number>]
 
 rules add exec gnome-calc.* /var/lib/eruption/profiles/profile1.profile
 rules add exec gnome-calc.* 2
+
+Apply the rule to all processes spawned by the matched process, until they exited:
+rules add --descendants exec steam 3
 "#
         .to_string()
     }
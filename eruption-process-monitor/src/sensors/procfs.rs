@@ -0,0 +1,254 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use log::*;
+
+use super::{Sensor, SensorConfiguration, SENSORS_CONFIGURATION};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Set when all other sensors are unavailable, so that the /proc polling
+    /// sensor takes over as a fallback
+    pub static ref PROCFS_SENSOR_FALLBACK: AtomicBool = AtomicBool::new(false);
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProcfsSensorError {
+    #[error("Malformed stat file of process {pid}")]
+    MalformedStat { pid: i32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcfsSensorData {
+    pub comm: String,
+    pub pid: i32,
+}
+
+impl super::SensorData for ProcfsSensorData {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// The subset of fields of `/proc/<pid>/stat` that we are interested in
+#[derive(Debug, Clone)]
+struct ProcessStat {
+    pid: i32,
+    comm: String,
+    pgrp: i32,
+    tty_nr: i32,
+    tpgid: i32,
+    cpu_time: u64,
+    start_time: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcfsSensor {
+    /// CPU time consumed by each process, as seen on the previous poll
+    cpu_times: HashMap<i32, u64>,
+}
+
+impl ProcfsSensor {
+    pub fn new() -> Self {
+        ProcfsSensor {
+            cpu_times: HashMap::new(),
+        }
+    }
+
+    /// Find the process that is most likely the one the user is interacting with.
+    /// Prefer the most recently started member of a terminal's foreground process
+    /// group, otherwise use the process that consumed the most CPU time since the
+    /// previous poll
+    fn find_foreground_process(&mut self) -> Result<Option<ProcessStat>> {
+        let uid = nix::unistd::getuid().as_raw();
+
+        let mut processes = vec![];
+
+        for entry in fs::read_dir("/proc")? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+
+            let pid = match entry.file_name().to_string_lossy().parse::<i32>() {
+                Ok(pid) => pid,
+                Err(_) => continue,
+            };
+
+            // only consider processes of the user that we are running as
+            match entry.metadata() {
+                Ok(metadata) if metadata.uid() == uid => (),
+                _ => continue,
+            }
+
+            // the process may have vanished in the meantime
+            if let Ok(stat) = read_process_stat(pid) {
+                processes.push(stat);
+            }
+        }
+
+        let foreground = processes
+            .iter()
+            .filter(|p| p.tty_nr != 0 && p.tpgid > 0 && p.pgrp == p.tpgid)
+            .max_by_key(|p| p.start_time)
+            .cloned();
+
+        let cpu_times: HashMap<i32, u64> = processes.iter().map(|p| (p.pid, p.cpu_time)).collect();
+
+        let result = match foreground {
+            Some(process) => Some(process),
+
+            None => processes
+                .iter()
+                .filter_map(|p| {
+                    self.cpu_times
+                        .get(&p.pid)
+                        .map(|previous| (p, p.cpu_time.saturating_sub(*previous)))
+                })
+                .filter(|(_, delta)| *delta > 0)
+                .max_by_key(|(_, delta)| *delta)
+                .map(|(p, _)| p.clone()),
+        };
+
+        self.cpu_times = cpu_times;
+
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Sensor for ProcfsSensor {
+    fn get_id(&self) -> String {
+        "procfs".to_string()
+    }
+
+    fn get_name(&self) -> String {
+        "Procfs".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Polls /proc for the foreground process, used as a fallback if all other sensors are unavailable"
+            .to_string()
+    }
+
+    fn get_usage_example(&self) -> String {
+        r#"
+Procfs:
+Uses the same rules as the Process sensor
+
+rules add exec <comm> [<profile-name.profile>|<slot number>]
+"#
+        .to_string()
+    }
+
+    fn initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_enabled(&self) -> bool {
+        SENSORS_CONFIGURATION
+            .read()
+            .contains(&SensorConfiguration::EnableProcfs)
+            || PROCFS_SENSOR_FALLBACK.load(Ordering::SeqCst)
+    }
+
+    fn is_pollable(&self) -> bool {
+        true
+    }
+
+    fn is_failed(&self) -> bool {
+        false
+    }
+
+    fn set_failed(&mut self, _failed: bool) {
+        // no op
+    }
+
+    fn poll(&mut self) -> Result<Box<dyn super::SensorData>> {
+        let result = match self.find_foreground_process()? {
+            Some(process) => ProcfsSensorData {
+                comm: process.comm,
+                pid: process.pid,
+            },
+
+            None => {
+                trace!("Could not determine the foreground process");
+
+                ProcfsSensorData {
+                    comm: "".to_string(),
+                    pid: 0,
+                }
+            }
+        };
+
+        Ok(Box::from(result))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+fn read_process_stat(pid: i32) -> Result<ProcessStat> {
+    let stat = fs::read_to_string(Path::new(&format!("/proc/{}/stat", pid)))?;
+
+    // the comm field is enclosed in parentheses and may itself contain spaces or parentheses
+    let (start, end) = match (stat.find('('), stat.rfind(')')) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => return Err(ProcfsSensorError::MalformedStat { pid }.into()),
+    };
+
+    let comm = stat[start + 1..end].to_string();
+    let fields = stat[end + 1..].split_whitespace().collect::<Vec<&str>>();
+
+    Ok(ProcessStat {
+        pid,
+        comm,
+        pgrp: stat_field(&fields, 5, pid)?.parse()?,
+        tty_nr: stat_field(&fields, 7, pid)?.parse()?,
+        tpgid: stat_field(&fields, 8, pid)?.parse()?,
+        cpu_time: stat_field(&fields, 14, pid)?.parse::<u64>()?
+            + stat_field(&fields, 15, pid)?.parse::<u64>()?,
+        start_time: stat_field(&fields, 22, pid)?.parse()?,
+    })
+}
+
+/// Get a field of `/proc/<pid>/stat` by its number, as documented in proc(5).
+/// The slice of `fields` starts after the comm field, so the numbering is offset by 3
+fn stat_field<'a>(fields: &[&'a str], number: usize, pid: i32) -> Result<&'a str> {
+    fields
+        .get(number - 3)
+        .copied()
+        .ok_or_else(|| ProcfsSensorError::MalformedStat { pid }.into())
+}
@@ -28,6 +28,7 @@ use byteorder::{ByteOrder, LittleEndian};
 use parking_lot::Mutex;
 use std::sync::Arc;
 use x11rb::connection::Connection;
+use x11rb::protocol::xkb::{self, ConnectionExt as _};
 use x11rb::protocol::xproto::*;
 use x11rb::x11_utils::TryParse;
 use x11rb::xcb_ffi::XCBConnection;
@@ -53,6 +54,26 @@ pub struct X11SensorData {
     pub window_instance: String,
     pub window_class: String,
     pub pid: i32,
+
+    /// Position of the mouse pointer, normalized to the size of the screen
+    pub pointer: Option<(f64, f64)>,
+
+    /// State of the keyboard lock indicators and the active layout
+    pub indicators: Option<KeyboardIndicators>,
+}
+
+/// The state of the keyboard lock indicators and the active layout, as seen by xkb
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeyboardIndicators {
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+
+    /// Index of the active xkb group
+    pub group: u32,
+
+    /// Name of the active layout, like e.g. "English (US)"
+    pub layout: String,
 }
 
 impl super::SensorData for X11SensorData {
@@ -81,6 +102,7 @@ pub struct X11Sensor {
     pub is_failed: bool,
     pub conn: Option<Arc<Mutex<XCBConnection>>>,
     pub screen: Option<usize>,
+    pub xkb_supported: bool,
 }
 
 impl X11Sensor {
@@ -99,6 +121,7 @@ impl X11Sensor {
             is_failed: false,
             conn: None,
             screen: None,
+            xkb_supported: false,
         }
     }
 }
@@ -108,6 +131,13 @@ impl Sensor for X11Sensor {
     fn initialize(&mut self) -> Result<()> {
         let (conn, screen) = XCBConnection::connect(Some(&CString::new(self.display.clone())?))?;
 
+        // the XKB extension is required to query the lock indicators
+        self.xkb_supported = conn
+            .xkb_use_extension(1, 0)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|reply| reply.supported);
+
         self.conn = Some(Arc::new(Mutex::new(conn)));
         self.screen = Some(screen);
 
@@ -171,6 +201,12 @@ You may want to use the command line tool `xprop` to find the relevant informati
             let cardinal = conn.intern_atom(false, b"CARDINAL")?.reply()?;
 
             let focus = find_active_window(&*conn, root, net_active_window)?;
+            let pointer = query_pointer_position(&*conn, &conn.setup().roots[screen]).ok();
+            let indicators = if self.xkb_supported {
+                query_keyboard_indicators(&*conn).ok()
+            } else {
+                None
+            };
 
             if focus == 0 {
                 // found the root window
@@ -180,6 +216,8 @@ You may want to use the command line tool `xprop` to find the relevant informati
                     window_instance: "".to_string(),
                     window_class: "".to_string(),
                     pid: 0,
+                    pointer,
+                    indicators,
                 };
 
                 Ok(Box::from(result))
@@ -216,6 +254,8 @@ You may want to use the command line tool `xprop` to find the relevant informati
                     window_instance: instance.to_string(),
                     window_class: class.to_string(),
                     pid,
+                    pointer,
+                    indicators,
                 };
 
                 if result.window_name.is_empty()
@@ -247,6 +287,63 @@ You may want to use the command line tool `xprop` to find the relevant informati
     }
 }
 
+/// Returns the position of the mouse pointer on the screen, normalized to the range `0.0..=1.0`
+fn query_pointer_position(conn: &impl Connection, screen: &Screen) -> Result<(f64, f64)> {
+    let reply = conn.query_pointer(screen.root)?.reply()?;
+
+    let x = reply.root_x as f64 / screen.width_in_pixels.max(1) as f64;
+    let y = reply.root_y as f64 / screen.height_in_pixels.max(1) as f64;
+
+    Ok((x.clamp(0.0, 1.0), y.clamp(0.0, 1.0)))
+}
+
+/// Returns the state of the lock indicators of the core keyboard, and the active layout
+fn query_keyboard_indicators(conn: &impl Connection) -> Result<KeyboardIndicators> {
+    let device_spec = xkb::ID::USE_CORE_KBD.into();
+
+    let state = conn.xkb_get_state(device_spec)?.reply()?;
+    let indicator_state = conn.xkb_get_indicator_state(device_spec)?.reply()?.state;
+    let names = conn
+        .xkb_get_names(
+            device_spec,
+            xkb::NameDetail::INDICATOR_NAMES | xkb::NameDetail::GROUP_NAMES,
+        )?
+        .reply()?;
+
+    let group = u8::from(state.group) as u32;
+
+    let mut result = KeyboardIndicators {
+        group,
+        ..Default::default()
+    };
+
+    // the names are listed in the order of the bits that are set in the `indicators` mask
+    let indicator_bits = (0..32).filter(|bit| names.indicators & (1 << bit) != 0);
+    let indicator_names = names.value_list.indicator_names.unwrap_or_default();
+
+    for (bit, atom) in indicator_bits.zip(indicator_names) {
+        let active = indicator_state & (1 << bit) != 0;
+
+        match conn.get_atom_name(atom)?.reply()?.name.as_slice() {
+            b"Caps Lock" => result.caps_lock = active,
+            b"Num Lock" => result.num_lock = active,
+            b"Scroll Lock" => result.scroll_lock = active,
+            _ => {}
+        }
+    }
+
+    if let Some(atom) = names
+        .value_list
+        .groups
+        .and_then(|groups| groups.get(group as usize).copied())
+    {
+        result.layout =
+            String::from_utf8_lossy(&conn.get_atom_name(atom)?.reply()?.name).to_string();
+    }
+
+    Ok(result)
+}
+
 fn find_active_window(
     conn: &impl Connection,
     root: Window,
@@ -0,0 +1,312 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TimeWindowError {
+    #[error("Invalid time window: {description}")]
+    ParseError { description: String },
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// A time-of-day window, optionally restricted to a set of weekdays.
+///
+/// The textual representation is `HH:MM-HH:MM[/DAYS]`, where `DAYS` is a comma separated
+/// list of weekdays or ranges of weekdays, e.g.: `09:00-17:00/mon-fri` or `18:00-01:00/sat,sun`.
+/// A window that ends before it starts extends past midnight, a window that ends when it
+/// starts, e.g. `00:00-00:00/sat,sun`, spans the whole day
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+
+    /// Weekdays on which the window is active, an empty set means every day
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+}
+
+impl TimeWindow {
+    /// Returns `true` if the local time is inside the window
+    pub fn is_active_now(&self) -> bool {
+        self.contains(&Local::now().naive_local())
+    }
+
+    /// Returns `true` if `datetime` is inside the window. The weekday of a window that
+    /// extends past midnight refers to the day on which the window has been opened
+    pub fn contains(&self, datetime: &NaiveDateTime) -> bool {
+        let time = datetime.time();
+        let weekday = datetime.weekday();
+
+        if self.start == self.end {
+            self.is_active_on(weekday)
+        } else if self.start < self.end {
+            self.is_active_on(weekday) && time >= self.start && time < self.end
+        } else {
+            (self.is_active_on(weekday) && time >= self.start)
+                || (self.is_active_on(weekday.pred()) && time < self.end)
+        }
+    }
+
+    fn is_active_on(&self, weekday: Weekday) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&weekday)
+    }
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").map_err(|_| {
+        TimeWindowError::ParseError {
+            description: format!("Invalid time of day: '{}'", s),
+        }
+        .into()
+    })
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday> {
+    Weekday::from_str(s.trim()).map_err(|_| {
+        TimeWindowError::ParseError {
+            description: format!("Invalid weekday: '{}'", s),
+        }
+        .into()
+    })
+}
+
+fn parse_weekdays(s: &str) -> Result<Vec<Weekday>> {
+    let mut result = Vec::new();
+
+    for item in s.split(',') {
+        match item.split_once('-') {
+            Some((first, last)) => {
+                let mut day = parse_weekday(first)?;
+                let last = parse_weekday(last)?;
+
+                loop {
+                    if !result.contains(&day) {
+                        result.push(day);
+                    }
+
+                    if day == last {
+                        break;
+                    }
+
+                    day = day.succ();
+                }
+            }
+
+            None => {
+                let day = parse_weekday(item)?;
+
+                if !result.contains(&day) {
+                    result.push(day);
+                }
+            }
+        }
+    }
+
+    result.sort_by_key(|day| day.num_days_from_monday());
+
+    Ok(result)
+}
+
+impl FromStr for TimeWindow {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (times, weekdays) = match s.split_once('/') {
+            Some((times, weekdays)) => (times, parse_weekdays(weekdays)?),
+            None => (s, vec![]),
+        };
+
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| TimeWindowError::ParseError {
+                description: format!("Expected a range of the form HH:MM-HH:MM: '{}'", times),
+            })?;
+
+        Ok(Self {
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+            weekdays,
+        })
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )?;
+
+        if !self.weekdays.is_empty() {
+            let mut days = Vec::new();
+
+            let contains = |index: usize| self.weekdays.contains(&WEEKDAYS[index % WEEKDAYS.len()]);
+
+            // a range that wraps around from sunday to monday starts after the last gap
+            let offset = if contains(0) && contains(WEEKDAYS.len() - 1) {
+                (0..WEEKDAYS.len())
+                    .rev()
+                    .find(|index| !contains(*index))
+                    .map_or(0, |index| index + 1)
+            } else {
+                0
+            };
+
+            // collapse consecutive weekdays into ranges
+            let mut index = offset;
+            while index < offset + WEEKDAYS.len() {
+                if !contains(index) {
+                    index += 1;
+                    continue;
+                }
+
+                let first = index;
+                while index + 1 < offset + WEEKDAYS.len() && contains(index + 1) {
+                    index += 1;
+                }
+
+                let name =
+                    |index: usize| WEEKDAYS[index % WEEKDAYS.len()].to_string().to_lowercase();
+
+                if index - first >= 2 {
+                    days.push(format!("{}-{}", name(first), name(index)));
+                } else {
+                    for day in first..=index {
+                        days.push(name(day));
+                    }
+                }
+
+                index += 1;
+            }
+
+            write!(f, "/{}", days.join(","))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// Returns the point in time `time` on a day of the week of 2023-10-16, a monday
+    fn at(weekday: Weekday, time: &str) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 10, 16 + weekday.num_days_from_monday())
+            .unwrap()
+            .and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for text in [
+            "09:00-17:00",
+            "09:00-17:00/mon-fri",
+            "18:00-01:00/sat,sun",
+            "22:00-02:00/sat-mon",
+            "08:00-12:00/mon,wed,thu",
+            "00:00-00:00/mon-sun",
+        ] {
+            let window = text.parse::<TimeWindow>().unwrap();
+
+            assert_eq!(window.to_string(), text);
+            assert_eq!(window.to_string().parse::<TimeWindow>().unwrap(), window);
+        }
+
+        let window = "09:00-17:00/sat-mon".parse::<TimeWindow>().unwrap();
+        assert_eq!(
+            window.weekdays,
+            vec![Weekday::Mon, Weekday::Sat, Weekday::Sun]
+        );
+
+        // overlapping and unordered lists are normalized
+        let window = "09:00-17:00/fri,mon-wed,tue,thu"
+            .parse::<TimeWindow>()
+            .unwrap();
+        assert_eq!(window.to_string(), "09:00-17:00/mon-fri");
+    }
+
+    #[test]
+    fn windows_may_extend_past_midnight() {
+        let window = "18:00-01:00/sat,sun".parse::<TimeWindow>().unwrap();
+
+        assert!(window.contains(&at(Weekday::Sat, "18:00")));
+        assert!(window.contains(&at(Weekday::Sat, "23:59")));
+
+        // the weekday refers to the day on which the window has been opened
+        assert!(window.contains(&at(Weekday::Sun, "00:30")));
+        assert!(window.contains(&at(Weekday::Mon, "00:30")));
+        assert!(!window.contains(&at(Weekday::Sat, "00:30")));
+
+        assert!(!window.contains(&at(Weekday::Sun, "01:00")));
+        assert!(!window.contains(&at(Weekday::Sun, "17:59")));
+        assert!(!window.contains(&at(Weekday::Fri, "23:00")));
+    }
+
+    #[test]
+    fn windows_of_a_single_day() {
+        let window = "09:00-17:00/mon-fri".parse::<TimeWindow>().unwrap();
+
+        assert!(window.contains(&at(Weekday::Mon, "09:00")));
+        assert!(!window.contains(&at(Weekday::Mon, "17:00")));
+        assert!(!window.contains(&at(Weekday::Sat, "12:00")));
+
+        // a window that ends when it starts spans the whole day
+        let window = "06:00-06:00/sun".parse::<TimeWindow>().unwrap();
+
+        assert!(window.contains(&at(Weekday::Sun, "00:00")));
+        assert!(window.contains(&at(Weekday::Sun, "23:59")));
+        assert!(!window.contains(&at(Weekday::Mon, "06:00")));
+    }
+
+    #[test]
+    fn malformed_windows_are_rejected() {
+        for text in [
+            "",
+            "09:00",
+            "9-17",
+            "09:00-24:00",
+            "09:00-17:00/",
+            "09:00-17:00/funday",
+            "09:00-17:00/mon-",
+            "09:00-17:00/mon-fri/sat",
+        ] {
+            assert!(text.parse::<TimeWindow>().is_err(), "{}", text);
+        }
+    }
+}
@@ -41,6 +41,21 @@ pub fn get_process_comm(pid: i32) -> Result<String> {
     )
 }
 
+/// Returns the thread group id (the process id) of the thread `tid`
+#[cfg(feature = "sensor-procmon")]
+pub fn get_process_tgid(tid: i32) -> Result<i32> {
+    let status = std::fs::read_to_string(Path::new(&format!("/proc/{}/status", tid)))?;
+
+    let tgid = status
+        .lines()
+        .find_map(|line| line.strip_prefix("Tgid:"))
+        .ok_or(UtilError::OpFailed {})?
+        .trim()
+        .parse::<i32>()?;
+
+    Ok(tgid)
+}
+
 #[cfg(feature = "sensor-procmon")]
 pub fn get_process_file_name(pid: i32) -> Result<String> {
     let tmp = format!("/proc/{}/exe", pid);
@@ -47,6 +47,7 @@ use std::{
 
 mod constants;
 mod hwdevices;
+mod topology;
 mod util;
 
 type Result<T> = std::result::Result<T, eyre::Error>;
@@ -167,6 +168,10 @@ pub enum RecordTopologySubcommands {
 
     /// Generate neighbor topology information
     Neighbor { device_index: usize },
+
+    /// Generate all topology tables at once, from the recorded rows and columns topology
+    /// of the device. Use 'rows' and 'cols' first, if the device uses a new layout
+    Auto { device_index: usize },
 }
 
 /// Sub-commands of the "TestTopology" command
@@ -1373,6 +1378,102 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     }
                 }
             }
+
+            RecordTopologySubcommands::Auto { device_index } => {
+                println!();
+                println!("Generate all topology tables for the selected device");
+                println!();
+
+                // create the one and only hidapi instance
+                match hidapi::HidApi::new() {
+                    Ok(hidapi) => {
+                        if let Some((index, device)) =
+                            hidapi.device_list().enumerate().nth(device_index)
+                        {
+                            println!(
+                                "Index: {}: ID: {:x}:{:x} {}/{} Subdev: {}",
+                                format!("{:02}", index).bold(),
+                                device.vendor_id(),
+                                device.product_id(),
+                                device.manufacturer_string().unwrap_or("<unknown>").bold(),
+                                device.product_string().unwrap_or("<unknown>").bold(),
+                                device.interface_number()
+                            );
+
+                            if let Ok(dev) = device.open_device(&hidapi) {
+                                let hwdev = hwdevices::bind_device(
+                                    dev,
+                                    &hidapi,
+                                    device.vendor_id(),
+                                    device.product_id(),
+                                )?;
+
+                                hwdev.send_init_sequence()?;
+
+                                let num_cols = hwdev.get_num_cols();
+                                let num_rows = hwdev.get_num_rows();
+
+                                let keys_per_row = num_cols + 1;
+                                let keys_per_col = num_rows + 1;
+
+                                let positions = topology::positions_from_tables(
+                                    &hwdev.get_row_topology(),
+                                    num_rows,
+                                    keys_per_row,
+                                    &hwdev.get_col_topology(),
+                                    num_cols,
+                                    keys_per_col,
+                                );
+
+                                if positions.is_empty() {
+                                    error!("No key positions available, please record the rows and columns topology manually");
+                                } else {
+                                    println!();
+                                    println!("Dumping generated tables:");
+                                    println!();
+
+                                    topology::print_table(
+                                        "ROWS_TOPOLOGY",
+                                        "rows_topology",
+                                        &topology::rows_topology(
+                                            &positions,
+                                            num_rows,
+                                            keys_per_row,
+                                        ),
+                                        keys_per_row,
+                                    );
+
+                                    topology::print_table(
+                                        "COLS_TOPOLOGY",
+                                        "cols_topology",
+                                        &topology::cols_topology(
+                                            &positions,
+                                            num_cols,
+                                            keys_per_col,
+                                        ),
+                                        keys_per_col,
+                                    );
+
+                                    topology::print_table(
+                                        "NEIGHBOR_TOPOLOGY",
+                                        "neighbor_topology",
+                                        &topology::neighbor_topology(&positions),
+                                        topology::NEIGHBORS_PER_KEY,
+                                    );
+
+                                    println!("Please verify the generated tables with the 'test-topology' subcommands");
+                                }
+                            } else {
+                                error!("Could not open the device, is the device in use?");
+                            }
+                        }
+                    }
+
+                    Err(_) => {
+                        error!("Could not open HIDAPI");
+                    }
+                }
+            }
         },
 
         // Topology testing related sub-commands
@@ -0,0 +1,234 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Derivation of the topology tables from the physical positions of the keys
+
+/// Marks an unused slot in a topology table
+pub const EMPTY: u8 = 0xff;
+
+/// Number of slots per key in the neighbor topology table
+pub const NEIGHBORS_PER_KEY: usize = 10;
+
+/// Size of the neighbor topology table
+pub const NEIGHBOR_TOPOLOGY_SIZE: usize = 2900;
+
+/// The physical position of a key, as derived from the recorded topology tables
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyPosition {
+    /// The key index, as used in the topology tables
+    pub index: u8,
+
+    pub row: usize,
+    pub col: usize,
+}
+
+/// Derive the positions of the keys from the first `num_rows` rows of a rows topology
+/// table, that holds `keys_per_row` slots per row. The keys of a row are packed, so the
+/// slot of a key is not its column; the column is looked up in the first `num_cols`
+/// columns of the columns topology table, that holds `keys_per_col` slots per column
+pub fn positions_from_tables(
+    rows_topology: &[u8],
+    num_rows: usize,
+    keys_per_row: usize,
+    cols_topology: &[u8],
+    num_cols: usize,
+    keys_per_col: usize,
+) -> Vec<KeyPosition> {
+    let column_of = |index: u8| {
+        cols_topology
+            .chunks(keys_per_col)
+            .take(num_cols)
+            .position(|keys| keys.contains(&index))
+    };
+
+    rows_topology
+        .chunks(keys_per_row)
+        .take(num_rows)
+        .enumerate()
+        .flat_map(|(row, keys)| {
+            keys.iter()
+                .enumerate()
+                .filter(|(_slot, index)| **index != EMPTY)
+                .map(move |(slot, index)| KeyPosition {
+                    index: *index,
+                    row,
+                    // keys that are missing from the columns table keep their slot
+                    col: column_of(*index).unwrap_or(slot),
+                })
+        })
+        .collect()
+}
+
+/// Generate the rows topology table, with `keys_per_row` slots per row
+pub fn rows_topology(positions: &[KeyPosition], num_rows: usize, keys_per_row: usize) -> Vec<u8> {
+    let mut result = vec![EMPTY; num_rows * keys_per_row];
+
+    for row in 0..num_rows {
+        let mut keys = positions
+            .iter()
+            .filter(|p| p.row == row)
+            .collect::<Vec<_>>();
+
+        keys.sort_by_key(|p| p.col);
+
+        for (slot, key) in keys.iter().take(keys_per_row).enumerate() {
+            result[row * keys_per_row + slot] = key.index;
+        }
+    }
+
+    result
+}
+
+/// Generate the columns topology table, with `keys_per_col` slots per column
+pub fn cols_topology(positions: &[KeyPosition], num_cols: usize, keys_per_col: usize) -> Vec<u8> {
+    let mut result = vec![EMPTY; num_cols * keys_per_col];
+
+    for col in 0..num_cols {
+        let mut keys = positions
+            .iter()
+            .filter(|p| p.col == col)
+            .collect::<Vec<_>>();
+
+        keys.sort_by_key(|p| p.row);
+
+        for (slot, key) in keys.iter().take(keys_per_col).enumerate() {
+            result[col * keys_per_col + slot] = key.index;
+        }
+    }
+
+    result
+}
+
+/// Generate the neighbor topology table, the direct neighbors of a key are all keys
+/// in the adjacent rows and columns, including the diagonals
+pub fn neighbor_topology(positions: &[KeyPosition]) -> Vec<u8> {
+    let mut result = vec![EMPTY; NEIGHBOR_TOPOLOGY_SIZE];
+
+    for key in positions {
+        let base = key.index as usize * NEIGHBORS_PER_KEY;
+
+        if base + NEIGHBORS_PER_KEY > result.len() {
+            continue;
+        }
+
+        let neighbors = positions.iter().filter(|other| {
+            other.index != key.index
+                && other.row.abs_diff(key.row) <= 1
+                && other.col.abs_diff(key.col) <= 1
+        });
+
+        for (slot, neighbor) in neighbors.take(NEIGHBORS_PER_KEY).enumerate() {
+            result[base + slot] = neighbor.index;
+        }
+    }
+
+    result
+}
+
+/// Print a topology table, both as Rust source code and in the Lua table format
+pub fn print_table(rust_name: &str, lua_name: &str, table: &[u8], chunk_size: usize) {
+    println!("pub static {}: [u8; {}] = [", rust_name, table.len());
+    print_rows(table, chunk_size);
+    println!("];");
+
+    println!();
+
+    println!("{} = {{", lua_name);
+    print_rows(table, chunk_size);
+    println!("}}");
+
+    println!();
+}
+
+fn print_rows(table: &[u8], chunk_size: usize) {
+    for row in table.chunks(chunk_size) {
+        print!("\t");
+
+        for e in row {
+            print!("0x{:02x}, ", e);
+        }
+
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 2x3 keyboard, with a wide key (2) spanning columns 0 and 1 of the first row:
+    //
+    //   [   2   ][ 5 ]
+    //   [ 3 ][ 4 ][ 6 ]
+    const ROWS: [u8; 8] = [2, 5, EMPTY, EMPTY, 3, 4, 6, EMPTY];
+    const COLS: [u8; 9] = [2, 3, EMPTY, 4, EMPTY, EMPTY, 5, 6, EMPTY];
+
+    #[test]
+    fn columns_are_looked_up_in_the_columns_table() {
+        let positions = positions_from_tables(&ROWS, 2, 4, &COLS, 3, 3);
+
+        let position = |index: u8| {
+            positions
+                .iter()
+                .find(|p| p.index == index)
+                .map(|p| (p.row, p.col))
+        };
+
+        assert_eq!(position(2), Some((0, 0)));
+
+        // the second key of the first row is in the last column
+        assert_eq!(position(5), Some((0, 2)));
+
+        assert_eq!(position(3), Some((1, 0)));
+        assert_eq!(position(4), Some((1, 1)));
+        assert_eq!(position(6), Some((1, 2)));
+    }
+
+    #[test]
+    fn tables_round_trip() {
+        let positions = positions_from_tables(&ROWS, 2, 4, &COLS, 3, 3);
+
+        assert_eq!(rows_topology(&positions, 2, 4), ROWS);
+
+        assert_eq!(cols_topology(&positions, 3, 3), COLS);
+    }
+
+    #[test]
+    fn neighbors_include_diagonals() {
+        let positions = positions_from_tables(&ROWS, 2, 4, &COLS, 3, 3);
+        let neighbors = neighbor_topology(&positions);
+
+        let neighbors_of = |index: usize| {
+            let mut result = neighbors[index * NEIGHBORS_PER_KEY..(index + 1) * NEIGHBORS_PER_KEY]
+                .iter()
+                .copied()
+                .filter(|n| *n != EMPTY)
+                .collect::<Vec<_>>();
+
+            result.sort();
+            result
+        };
+
+        assert_eq!(neighbors_of(2), vec![3, 4]);
+        assert_eq!(neighbors_of(4), vec![2, 3, 5, 6]);
+        assert_eq!(neighbors_of(6), vec![4, 5]);
+    }
+}
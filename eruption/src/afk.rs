@@ -0,0 +1,627 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::animation::Easing;
+use crate::plugins::{PersistencePlugin, ProfilesPlugin};
+use crate::{constants, hotkeys};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AfkError {
+    #[error("Invalid AFK action: {msg}")]
+    InvalidAction { msg: String },
+}
+
+lazy_static! {
+    /// Actions that are taken while the user is away from the keyboard, as configured
+    /// in the [afk] section of eruption.conf
+    static ref ACTIONS: Arc<RwLock<Vec<AfkAction>>> = Arc::new(RwLock::new(vec![]));
+
+    /// State of the AFK state machine
+    static ref STATE: Arc<RwLock<AfkState>> = Arc::new(RwLock::new(AfkState::default()));
+
+    /// The AFK timeout, and the idle periods that the adaptive timeout is learned from
+    static ref TIMEOUT: Arc<RwLock<AfkTimeout>> = Arc::new(RwLock::new(AfkTimeout::default()));
+
+    /// Point in time when AFK mode has been entered manually, it is left with the next input
+    static ref FORCED_AT: Arc<RwLock<Option<Instant>>> = Arc::new(RwLock::new(None));
+}
+
+fn default_fade_secs() -> u64 {
+    constants::DEFAULT_AFK_FADE_SECS
+}
+
+/// An action that is taken after the user has been away from the keyboard for
+/// `delay_secs` seconds, and that is reverted when the user returns
+#[derive(Debug, Clone, Deserialize)]
+pub struct AfkAction {
+    /// Time span after entering AFK mode, after which the action is taken
+    #[serde(default)]
+    pub delay_secs: u64,
+
+    /// Profile to switch to, relative paths are looked up in the profile directories
+    #[serde(default)]
+    pub profile: Option<PathBuf>,
+
+    /// Dim the LEDs gradually to the specified percentage of the global brightness
+    #[serde(default)]
+    pub dim: Option<u64>,
+
+    /// Duration of the dimming
+    #[serde(default = "default_fade_secs")]
+    pub fade_secs: u64,
+
+    /// Turn off all LEDs
+    #[serde(default)]
+    pub leds_off: bool,
+
+    /// Shell command to run
+    #[serde(default)]
+    pub command: Option<String>,
+
+    /// Shell command to run when the user returns
+    #[serde(default)]
+    pub resume_command: Option<String>,
+
+    /// Lock the Easy Shift+ layer, so that its macros are not triggered
+    #[serde(default)]
+    pub lock_easy_shift: bool,
+}
+
+impl AfkAction {
+    fn validate(&self) -> Result<()> {
+        let num_actions = [
+            self.profile.is_some(),
+            self.dim.is_some(),
+            self.leds_off,
+            self.command.is_some(),
+            self.lock_easy_shift,
+        ]
+        .iter()
+        .filter(|action| **action)
+        .count();
+
+        if num_actions != 1 {
+            return Err(AfkError::InvalidAction {
+                msg: "Exactly one of 'profile', 'dim', 'leds_off', 'command' or 'lock_easy_shift' needs to be specified".to_owned(),
+            }
+            .into());
+        }
+
+        if self.resume_command.is_some() && self.command.is_none() {
+            return Err(AfkError::InvalidAction {
+                msg: "'resume_command' may only be specified along with 'command'".to_owned(),
+            }
+            .into());
+        }
+
+        match self.dim {
+            Some(dim) if dim > 100 => Err(AfkError::InvalidAction {
+                msg: format!("Brightness out of range: {dim}"),
+            }
+            .into()),
+
+            _ => Ok(()),
+        }
+    }
+
+    fn take(&self) {
+        if let Some(profile) = &self.profile {
+            let profile_file = hotkeys::resolve_profile_path(profile);
+
+            info!("AFK: Switching to profile: {}", profile_file.display());
+
+            *crate::ACTIVE_PROFILE_NAME_BEFORE_AFK.lock() = crate::ACTIVE_PROFILE
+                .lock()
+                .as_ref()
+                .map(|p| p.profile_file.to_string_lossy().to_string());
+
+            ProfilesPlugin::switch_to_profile(profile_file.to_string_lossy().to_string());
+        } else if let Some(dim) = self.dim {
+            info!("AFK: Dimming the LEDs to {}%", dim);
+        } else if self.leds_off {
+            info!("AFK: Turning off the LEDs");
+        } else if let Some(command) = &self.command {
+            info!("AFK: Running command: {}", command);
+
+            run_command(command);
+        } else if self.lock_easy_shift {
+            info!("AFK: Locking the Easy Shift+ layer");
+
+            set_easy_shift_locked(true);
+        }
+    }
+
+    fn revert(&self) {
+        if self.profile.is_some() {
+            if let Some(profile) = crate::ACTIVE_PROFILE_NAME_BEFORE_AFK.lock().take() {
+                ProfilesPlugin::switch_to_profile(profile);
+            }
+        } else if let Some(command) = &self.resume_command {
+            run_command(command);
+        } else if self.lock_easy_shift {
+            set_easy_shift_locked(false);
+        }
+    }
+
+    /// Returns the factor that the LED maps shall be dimmed by, if the action
+    /// has been taken at `taken_at`
+    fn brightness_factor(&self, taken_at: Instant, time_scale: f32) -> Option<f32> {
+        if self.leds_off {
+            Some(0.0)
+        } else if let Some(dim) = self.dim {
+            let target = dim as f32 / 100.0;
+            let fade_secs = self.fade_secs as f32 * time_scale;
+
+            let progress = if fade_secs <= 0.0 {
+                1.0
+            } else {
+                (taken_at.elapsed().as_secs_f32() / fade_secs).min(1.0)
+            };
+
+            let progress = Easing::InOutSine.apply(progress as f64) as f32;
+
+            Some(1.0 - (1.0 - target) * progress)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct AfkState {
+    /// Point in time when AFK mode has been entered
+    entered_at: Option<Instant>,
+
+    /// Points in time when each of the actions has been taken
+    taken_at: Vec<Option<Instant>>,
+}
+
+/// The AFK timeout. In adaptive mode, the timeout is learned from the lengths of the
+/// idle periods of the user, e.g. short breaks vs. lunch
+#[derive(Debug)]
+struct AfkTimeout {
+    /// The configured timeout, 0 disables AFK mode
+    timeout_secs: u64,
+
+    adaptive: bool,
+
+    /// Percentage of the idle periods that shall end before the timeout expires
+    percentile: u64,
+
+    min_timeout_secs: u64,
+    max_timeout_secs: u64,
+
+    /// Lengths of the most recent idle periods in seconds, oldest first
+    idle_periods: VecDeque<u64>,
+
+    /// Length of the current idle period, as of the previous call of `observe_idle()`
+    current_idle: Duration,
+
+    /// The learned timeout, `None` until enough idle periods have been observed
+    learned_timeout_secs: Option<u64>,
+}
+
+impl Default for AfkTimeout {
+    fn default() -> Self {
+        Self {
+            timeout_secs: constants::AFK_TIMEOUT_SECS,
+            adaptive: false,
+            percentile: constants::DEFAULT_ADAPTIVE_AFK_PERCENTILE,
+            min_timeout_secs: constants::DEFAULT_ADAPTIVE_AFK_MIN_TIMEOUT_SECS,
+            max_timeout_secs: constants::DEFAULT_ADAPTIVE_AFK_MAX_TIMEOUT_SECS,
+            idle_periods: VecDeque::new(),
+            current_idle: Duration::ZERO,
+            learned_timeout_secs: None,
+        }
+    }
+}
+
+impl AfkTimeout {
+    /// Returns the timeout that is currently in effect
+    fn effective_secs(&self) -> u64 {
+        match self.learned_timeout_secs {
+            Some(learned) if self.adaptive && self.timeout_secs > 0 => learned.clamp(
+                self.min_timeout_secs,
+                self.max_timeout_secs.max(self.min_timeout_secs),
+            ),
+
+            _ => self.timeout_secs,
+        }
+    }
+
+    fn update_learned_timeout(&mut self) {
+        let periods: Vec<u64> = self.idle_periods.iter().copied().collect();
+        let learned = learned_timeout(&periods, self.percentile);
+
+        if learned != self.learned_timeout_secs {
+            self.learned_timeout_secs = learned;
+
+            if self.adaptive && learned.is_some() {
+                debug!(
+                    "Adaptive AFK timeout is now {} seconds",
+                    self.effective_secs()
+                );
+            }
+        }
+    }
+}
+
+/// Returns the length of an idle period, such that `percentile` percent of the
+/// `idle_periods` are shorter or equal, or `None` if too few idle periods are known
+fn learned_timeout(idle_periods: &[u64], percentile: u64) -> Option<u64> {
+    if idle_periods.len() < constants::ADAPTIVE_AFK_MIN_SAMPLES {
+        return None;
+    }
+
+    let mut sorted = idle_periods.to_vec();
+    sorted.sort_unstable();
+
+    // nearest-rank method
+    let rank = (percentile.min(100) as usize * sorted.len()).div_ceil(100);
+
+    Some(sorted[rank.saturating_sub(1)])
+}
+
+/// Load the AFK actions from the configuration file. If no actions are configured,
+/// we switch to the `afk_profile` from the [global] section, like before
+pub fn load_settings() -> Result<()> {
+    let config = crate::CONFIG.lock();
+    let config = config.as_ref().unwrap();
+
+    let mut actions = vec![];
+
+    match config.get::<Vec<AfkAction>>("afk.actions") {
+        Ok(configured_actions) => {
+            for action in configured_actions {
+                match action.validate() {
+                    Ok(()) => actions.push(action),
+                    Err(e) => error!("Skipping AFK action: {}", e),
+                }
+            }
+        }
+
+        Err(_) => {
+            let afk_profile = config
+                .get::<String>("global.afk_profile")
+                .unwrap_or_else(|_| constants::DEFAULT_AFK_PROFILE.to_owned());
+
+            actions.push(AfkAction {
+                delay_secs: 0,
+                profile: Some(PathBuf::from(afk_profile)),
+                dim: None,
+                fade_secs: default_fade_secs(),
+                leds_off: false,
+                command: None,
+                resume_command: None,
+                lock_easy_shift: false,
+            });
+        }
+    }
+
+    info!("Loaded {} AFK action(s)", actions.len());
+
+    *ACTIONS.write() = actions;
+
+    let mut timeout = TIMEOUT.write();
+
+    timeout.timeout_secs = config
+        .get::<u64>("global.afk_timeout_secs")
+        .unwrap_or(constants::AFK_TIMEOUT_SECS);
+    timeout.adaptive = config.get::<bool>("afk.adaptive").unwrap_or(false);
+    timeout.percentile = config
+        .get::<u64>("afk.adaptive_percentile")
+        .unwrap_or(constants::DEFAULT_ADAPTIVE_AFK_PERCENTILE);
+    timeout.min_timeout_secs = config
+        .get::<u64>("afk.adaptive_min_timeout_secs")
+        .unwrap_or(constants::DEFAULT_ADAPTIVE_AFK_MIN_TIMEOUT_SECS);
+    timeout.max_timeout_secs = config
+        .get::<u64>("afk.adaptive_max_timeout_secs")
+        .unwrap_or(constants::DEFAULT_ADAPTIVE_AFK_MAX_TIMEOUT_SECS);
+
+    timeout.update_learned_timeout();
+
+    if timeout.adaptive {
+        info!(
+            "Adaptive AFK timeout enabled, learned from {} idle period(s)",
+            timeout.idle_periods.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Advance the AFK state machine, take all actions whose delay expired while the
+/// user is away, and revert the actions that have been taken when the user returns
+pub fn update(afk: bool) {
+    let actions = ACTIONS.read();
+    let mut state = STATE.write();
+
+    if afk {
+        let entered_at = match state.entered_at {
+            Some(entered_at) => entered_at,
+
+            None => {
+                info!("Entering AFK mode now...");
+
+                let now = Instant::now();
+
+                state.entered_at = Some(now);
+                state.taken_at = vec![None; actions.len()];
+
+                now
+            }
+        };
+
+        let elapsed = entered_at.elapsed();
+        let time_scale = get_time_scale();
+
+        for (action, taken_at) in actions.iter().zip(state.taken_at.iter_mut()) {
+            if taken_at.is_none()
+                && elapsed >= Duration::from_secs_f32(action.delay_secs as f32 * time_scale)
+            {
+                *taken_at = Some(Instant::now());
+                action.take();
+            }
+        }
+    } else if state.entered_at.is_some() {
+        info!("Leaving AFK mode now...");
+
+        // revert the actions in the reverse order in which they have been taken
+        for (action, taken_at) in actions.iter().zip(state.taken_at.iter()).rev() {
+            if taken_at.is_some() {
+                action.revert();
+            }
+        }
+
+        *state = AfkState::default();
+    }
+}
+
+/// Returns the factor that the LED maps shall be dimmed by while the user is away,
+/// or `None` if they shall be sent to the devices unaltered
+pub fn get_brightness_factor() -> Option<f32> {
+    let actions = ACTIONS.read();
+    let state = STATE.read();
+
+    let time_scale = get_time_scale();
+
+    actions
+        .iter()
+        .zip(state.taken_at.iter())
+        .filter_map(|(action, taken_at)| action.brightness_factor((*taken_at)?, time_scale))
+        .reduce(f32::min)
+}
+
+/// Returns `true` if the user is away from the keyboard, or AFK mode has been entered manually
+pub fn is_active() -> bool {
+    crate::AFK.load(Ordering::SeqCst)
+}
+
+/// Enter or leave AFK mode manually, e.g. via the D-Bus API. AFK mode that has been entered
+/// manually is left with the next input of the user. Returns `false` if AFK mode already is
+/// in the requested state
+pub fn set_active(enable: bool) -> bool {
+    if is_active() == enable {
+        return false;
+    }
+
+    if enable {
+        *FORCED_AT.write() = Some(Instant::now());
+        crate::AFK.store(true, Ordering::SeqCst);
+    } else {
+        *FORCED_AT.write() = None;
+
+        // restart the idle period, otherwise the timeout would re-enter AFK mode right away
+        *crate::LAST_INPUT_TIME.lock() = Instant::now();
+        crate::AFK.store(false, Ordering::SeqCst);
+    }
+
+    true
+}
+
+/// Enter or leave AFK mode manually, e.g. via a quick action.
+/// Returns true if AFK mode has been entered
+pub fn toggle() -> bool {
+    let enable = !is_active();
+
+    set_active(enable);
+
+    enable
+}
+
+/// Returns the AFK state while AFK mode has been entered manually: true until the user
+/// returns, then false once. Returns `None` if the AFK timeout is in charge
+pub fn get_forced_state(last_input: Instant) -> Option<bool> {
+    let mut forced_at = FORCED_AT.write();
+
+    let result = forced_state(*forced_at, last_input);
+
+    if result == Some(false) {
+        *forced_at = None;
+    }
+
+    result
+}
+
+/// Input during the grace period after AFK mode has been entered manually is ignored,
+/// otherwise e.g. the release of the Enter key that issued `eruptionctl afk on` would
+/// leave AFK mode right away
+fn forced_state(forced_at: Option<Instant>, last_input: Instant) -> Option<bool> {
+    let grace_period = Duration::from_millis(constants::AFK_FORCED_GRACE_MILLIS);
+
+    forced_at.map(|at| last_input <= at + grace_period)
+}
+
+/// Returns the AFK timeout that is currently in effect, 0 if AFK mode is disabled
+pub fn get_timeout_secs() -> u64 {
+    TIMEOUT.read().effective_secs()
+}
+
+/// Returns the number of seconds until the AFK timeout expires, 0 if the user already is
+/// away or AFK mode is disabled
+pub fn get_time_remaining_secs() -> u64 {
+    let timeout_secs = get_timeout_secs();
+
+    if timeout_secs == 0 || is_active() {
+        return 0;
+    }
+
+    let idle = crate::LAST_INPUT_TIME.lock().elapsed();
+
+    Duration::from_secs(timeout_secs)
+        .saturating_sub(idle)
+        .as_secs()
+}
+
+/// Returns the factor that the delays and fade durations of the actions are scaled by,
+/// so that the dimming curve follows the adaptive timeout
+fn get_time_scale() -> f32 {
+    let timeout = TIMEOUT.read();
+
+    if timeout.timeout_secs > 0 {
+        timeout.effective_secs() as f32 / timeout.timeout_secs as f32
+    } else {
+        1.0
+    }
+}
+
+/// Observe the time since the last input of the user, called periodically by the main
+/// loop. An idle period has ended if the time shrinks, its length is learned in adaptive mode
+pub fn observe_idle(idle: Duration) {
+    let mut timeout = TIMEOUT.write();
+
+    if !timeout.adaptive {
+        return;
+    }
+
+    if idle < timeout.current_idle {
+        let secs = timeout.current_idle.as_secs();
+
+        if secs >= constants::ADAPTIVE_AFK_MIN_IDLE_SECS {
+            trace!("Observed an idle period of {} seconds", secs);
+
+            timeout.idle_periods.push_back(secs);
+
+            while timeout.idle_periods.len() > constants::ADAPTIVE_AFK_MAX_SAMPLES {
+                timeout.idle_periods.pop_front();
+            }
+
+            timeout.update_learned_timeout();
+        }
+    }
+
+    timeout.current_idle = idle;
+}
+
+/// Returns the lengths of the idle periods that have been learned, used to persist
+/// them in the runtime state
+pub fn get_idle_periods() -> Vec<u64> {
+    TIMEOUT.read().idle_periods.iter().copied().collect()
+}
+
+/// Replace the learned idle periods, e.g. with the idle periods of the saved runtime state
+pub fn set_idle_periods(idle_periods: Vec<u64>) {
+    let mut timeout = TIMEOUT.write();
+
+    let skip = idle_periods
+        .len()
+        .saturating_sub(constants::ADAPTIVE_AFK_MAX_SAMPLES);
+
+    timeout.idle_periods = idle_periods.into_iter().skip(skip).collect();
+    timeout.update_learned_timeout();
+}
+
+/// Run a shell command in the background
+fn run_command(command: &str) {
+    let command = command.to_string();
+
+    thread::Builder::new()
+        .name("afk-command".to_owned())
+        .spawn(
+            move || match Command::new("sh").args(["-c", &command]).status() {
+                Ok(status) if !status.success() => warn!("AFK command failed: {}", status),
+                Ok(_) => (),
+
+                Err(e) => error!("Could not run AFK command: {}", e),
+            },
+        )
+        .map(|_| ())
+        .unwrap_or_else(|e| error!("Could not spawn a thread: {}", e));
+}
+
+/// Lock or unlock the Easy Shift+ layer of the macros script
+fn set_easy_shift_locked(locked: bool) {
+    PersistencePlugin::store_bool_transient(constants::EASY_SHIFT_LOCKED_KEY.to_owned(), locked)
+        .unwrap_or_else(|e| error!("Could not lock the Easy Shift+ layer: {}", e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn learned_timeout_ignores_long_breaks() {
+        // mostly short breaks, plus a lunch break and a meeting
+        let mut idle_periods = vec![40, 45, 60, 90, 120, 30, 50, 75, 100, 80, 65, 55, 110, 85];
+        idle_periods.extend([3600, 1800]);
+
+        assert_eq!(learned_timeout(&idle_periods, 80), Some(110));
+        assert_eq!(learned_timeout(&idle_periods, 100), Some(3600));
+
+        // too few idle periods
+        assert_eq!(learned_timeout(&idle_periods[..5], 80), None);
+    }
+
+    #[test]
+    fn forced_afk_ignores_input_during_the_grace_period() {
+        let at = Instant::now();
+
+        assert_eq!(forced_state(None, at), None);
+
+        // e.g. the release of the key that issued the command
+        assert_eq!(
+            forced_state(Some(at), at + Duration::from_millis(200)),
+            Some(true)
+        );
+
+        assert_eq!(
+            forced_state(
+                Some(at),
+                at + Duration::from_millis(constants::AFK_FORCED_GRACE_MILLIS + 1)
+            ),
+            Some(false)
+        );
+    }
+}
@@ -0,0 +1,529 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Easing curves, tweens and keyframe timelines. Animations are evaluated based on the
+//! elapsed time, so that they are independent of the frame rate
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AnimationError {
+    #[error("Unknown easing curve: {name}")]
+    UnknownEasing { name: String },
+
+    #[error("Unknown playback mode: {name}")]
+    UnknownPlayback { name: String },
+
+    #[error("A timeline requires at least one keyframe")]
+    NoKeyframes {},
+}
+
+/// An easing curve, the names follow Robert Penner's easing equations, as used by
+/// the Lua library `easing.lua`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    InQuad,
+    OutQuad,
+    InOutQuad,
+    InCubic,
+    OutCubic,
+    InOutCubic,
+    InQuart,
+    OutQuart,
+    InOutQuart,
+    InSine,
+    OutSine,
+    InOutSine,
+    InExpo,
+    OutExpo,
+    InOutExpo,
+    InCirc,
+    OutCirc,
+    InOutCirc,
+    InBack,
+    OutBack,
+    InOutBack,
+    InElastic,
+    OutElastic,
+    InBounce,
+    OutBounce,
+    InOutBounce,
+}
+
+impl FromStr for Easing {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let easing = match s {
+            "linear" => Easing::Linear,
+            "inQuad" => Easing::InQuad,
+            "outQuad" => Easing::OutQuad,
+            "inOutQuad" => Easing::InOutQuad,
+            "inCubic" => Easing::InCubic,
+            "outCubic" => Easing::OutCubic,
+            "inOutCubic" => Easing::InOutCubic,
+            "inQuart" => Easing::InQuart,
+            "outQuart" => Easing::OutQuart,
+            "inOutQuart" => Easing::InOutQuart,
+            "inSine" => Easing::InSine,
+            "outSine" => Easing::OutSine,
+            "inOutSine" => Easing::InOutSine,
+            "inExpo" => Easing::InExpo,
+            "outExpo" => Easing::OutExpo,
+            "inOutExpo" => Easing::InOutExpo,
+            "inCirc" => Easing::InCirc,
+            "outCirc" => Easing::OutCirc,
+            "inOutCirc" => Easing::InOutCirc,
+            "inBack" => Easing::InBack,
+            "outBack" => Easing::OutBack,
+            "inOutBack" => Easing::InOutBack,
+            "inElastic" => Easing::InElastic,
+            "outElastic" => Easing::OutElastic,
+            "inBounce" => Easing::InBounce,
+            "outBounce" => Easing::OutBounce,
+            "inOutBounce" => Easing::InOutBounce,
+
+            _ => return Err(AnimationError::UnknownEasing { name: s.to_owned() }.into()),
+        };
+
+        Ok(easing)
+    }
+}
+
+impl Easing {
+    /// Map the normalized time `t` (0.0..=1.0) to the progress of the animation. The
+    /// progress is 0.0 at the start and 1.0 at the end, but may overshoot in between
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+
+        // constants of the "back" curves
+        const C1: f64 = 1.70158;
+        const C2: f64 = C1 * 1.525;
+        const C3: f64 = C1 + 1.0;
+
+        match self {
+            Easing::Linear => t,
+
+            Easing::InQuad => t * t,
+            Easing::OutQuad => 1.0 - (1.0 - t).powi(2),
+            Easing::InOutQuad => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+
+            Easing::InCubic => t.powi(3),
+            Easing::OutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::InOutCubic => {
+                if t < 0.5 {
+                    4.0 * t.powi(3)
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+
+            Easing::InQuart => t.powi(4),
+            Easing::OutQuart => 1.0 - (1.0 - t).powi(4),
+            Easing::InOutQuart => {
+                if t < 0.5 {
+                    8.0 * t.powi(4)
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(4) / 2.0
+                }
+            }
+
+            Easing::InSine => 1.0 - (t * PI / 2.0).cos(),
+            Easing::OutSine => (t * PI / 2.0).sin(),
+            Easing::InOutSine => -((PI * t).cos() - 1.0) / 2.0,
+
+            Easing::InExpo => {
+                if t == 0.0 {
+                    0.0
+                } else {
+                    2.0_f64.powf(10.0 * t - 10.0)
+                }
+            }
+            Easing::OutExpo => {
+                if t == 1.0 {
+                    1.0
+                } else {
+                    1.0 - 2.0_f64.powf(-10.0 * t)
+                }
+            }
+            Easing::InOutExpo => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    2.0_f64.powf(20.0 * t - 10.0) / 2.0
+                } else {
+                    (2.0 - 2.0_f64.powf(-20.0 * t + 10.0)) / 2.0
+                }
+            }
+
+            Easing::InCirc => 1.0 - (1.0 - t * t).sqrt(),
+            Easing::OutCirc => (1.0 - (t - 1.0).powi(2)).sqrt(),
+            Easing::InOutCirc => {
+                if t < 0.5 {
+                    (1.0 - (1.0 - (2.0 * t).powi(2)).sqrt()) / 2.0
+                } else {
+                    ((1.0 - (-2.0 * t + 2.0).powi(2)).sqrt() + 1.0) / 2.0
+                }
+            }
+
+            Easing::InBack => C3 * t.powi(3) - C1 * t * t,
+            Easing::OutBack => 1.0 + C3 * (t - 1.0).powi(3) + C1 * (t - 1.0).powi(2),
+            Easing::InOutBack => {
+                if t < 0.5 {
+                    ((2.0 * t).powi(2) * ((C2 + 1.0) * 2.0 * t - C2)) / 2.0
+                } else {
+                    ((2.0 * t - 2.0).powi(2) * ((C2 + 1.0) * (t * 2.0 - 2.0) + C2) + 2.0) / 2.0
+                }
+            }
+
+            Easing::InElastic => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    -(2.0_f64.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * (2.0 * PI / 3.0)).sin()
+                }
+            }
+            Easing::OutElastic => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2.0_f64.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+                }
+            }
+
+            Easing::InBounce => 1.0 - out_bounce(1.0 - t),
+            Easing::OutBounce => out_bounce(t),
+            Easing::InOutBounce => {
+                if t < 0.5 {
+                    (1.0 - out_bounce(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + out_bounce(2.0 * t - 1.0)) / 2.0
+                }
+            }
+        }
+    }
+}
+
+fn out_bounce(t: f64) -> f64 {
+    const N1: f64 = 7.5625;
+    const D1: f64 = 2.75;
+
+    if t < 1.0 / D1 {
+        N1 * t * t
+    } else if t < 2.0 / D1 {
+        let t = t - 1.5 / D1;
+        N1 * t * t + 0.75
+    } else if t < 2.5 / D1 {
+        let t = t - 2.25 / D1;
+        N1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D1;
+        N1 * t * t + 0.984375
+    }
+}
+
+/// Specifies what happens after an animation reached its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Playback {
+    /// Play the animation once, and hold the final value
+    #[default]
+    Once,
+
+    /// Restart the animation from the beginning
+    Loop,
+
+    /// Alternately play the animation forwards and backwards
+    PingPong,
+}
+
+impl FromStr for Playback {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "once" => Ok(Playback::Once),
+            "loop" => Ok(Playback::Loop),
+            "ping-pong" | "pingpong" => Ok(Playback::PingPong),
+
+            _ => Err(AnimationError::UnknownPlayback { name: s.to_owned() }.into()),
+        }
+    }
+}
+
+impl Playback {
+    /// Map the time `elapsed` since the start of an animation of length `duration` to
+    /// the normalized time of the current iteration (0.0..=1.0)
+    pub fn normalized_time(self, elapsed: Duration, duration: Duration) -> f64 {
+        if duration.is_zero() {
+            return 1.0;
+        }
+
+        let t = elapsed.as_secs_f64() / duration.as_secs_f64();
+
+        match self {
+            Playback::Once => t.min(1.0),
+            Playback::Loop => t.fract(),
+            Playback::PingPong => {
+                let t = t % 2.0;
+
+                if t > 1.0 {
+                    2.0 - t
+                } else {
+                    t
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if an animation of length `duration` ended after `elapsed`
+    pub fn is_finished(self, elapsed: Duration, duration: Duration) -> bool {
+        self == Playback::Once && elapsed >= duration
+    }
+}
+
+/// Animates a value from `from` to `to`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tween {
+    pub from: f64,
+    pub to: f64,
+    pub duration: Duration,
+    pub easing: Easing,
+    pub playback: Playback,
+}
+
+impl Tween {
+    pub fn new(from: f64, to: f64, duration: Duration, easing: Easing) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            easing,
+            playback: Playback::Once,
+        }
+    }
+
+    pub fn with_playback(mut self, playback: Playback) -> Self {
+        self.playback = playback;
+        self
+    }
+
+    /// Returns the eased progress (0.0..=1.0 at the ends) after `elapsed`
+    pub fn progress(&self, elapsed: Duration) -> f64 {
+        self.easing
+            .apply(self.playback.normalized_time(elapsed, self.duration))
+    }
+
+    /// Returns the value of the animation after `elapsed`
+    pub fn value_at(&self, elapsed: Duration) -> f64 {
+        self.from + (self.to - self.from) * self.progress(elapsed)
+    }
+
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        self.playback.is_finished(elapsed, self.duration)
+    }
+}
+
+/// A keyframe of a timeline, the easing curve is applied to the segment that ends
+/// at this keyframe
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    pub offset: Duration,
+    pub value: f64,
+    pub easing: Easing,
+}
+
+/// Animates a value along a sequence of keyframes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    keyframes: Vec<Keyframe>,
+    pub playback: Playback,
+}
+
+impl Timeline {
+    /// Create a timeline, the keyframes are ordered by their offset
+    pub fn new(mut keyframes: Vec<Keyframe>, playback: Playback) -> Result<Self> {
+        if keyframes.is_empty() {
+            return Err(AnimationError::NoKeyframes {}.into());
+        }
+
+        keyframes.sort_by_key(|keyframe| keyframe.offset);
+
+        Ok(Self {
+            keyframes,
+            playback,
+        })
+    }
+
+    /// Returns the offset of the last keyframe
+    pub fn duration(&self) -> Duration {
+        self.keyframes.last().map(|k| k.offset).unwrap_or_default()
+    }
+
+    /// Returns the value of the timeline after `elapsed`
+    pub fn value_at(&self, elapsed: Duration) -> f64 {
+        let duration = self.duration();
+        let time = duration.mul_f64(self.playback.normalized_time(elapsed, duration));
+
+        let next = self
+            .keyframes
+            .iter()
+            .position(|keyframe| keyframe.offset >= time);
+
+        match next {
+            Some(0) => self.keyframes[0].value,
+
+            Some(index) => {
+                let prev = &self.keyframes[index - 1];
+                let next = &self.keyframes[index];
+
+                let segment = next.offset - prev.offset;
+                let t = if segment.is_zero() {
+                    1.0
+                } else {
+                    (time - prev.offset).as_secs_f64() / segment.as_secs_f64()
+                };
+
+                prev.value + (next.value - prev.value) * next.easing.apply(t)
+            }
+
+            None => self.keyframes[self.keyframes.len() - 1].value,
+        }
+    }
+
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        self.playback.is_finished(elapsed, self.duration())
+    }
+}
+
+/// A running animation, either a tween or a timeline
+#[derive(Debug, Clone, PartialEq)]
+pub enum Animation {
+    Tween(Tween),
+    Timeline(Timeline),
+}
+
+impl Animation {
+    pub fn value_at(&self, elapsed: Duration) -> f64 {
+        match self {
+            Animation::Tween(tween) => tween.value_at(elapsed),
+            Animation::Timeline(timeline) => timeline.value_at(elapsed),
+        }
+    }
+
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        match self {
+            Animation::Tween(tween) => tween.is_finished(elapsed),
+            Animation::Timeline(timeline) => timeline.is_finished(elapsed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing_curves_start_and_end() {
+        for name in [
+            "linear",
+            "inQuad",
+            "outQuad",
+            "inOutQuad",
+            "inOutCubic",
+            "inOutSine",
+            "inOutExpo",
+            "inOutCirc",
+            "inOutBack",
+            "outElastic",
+            "inOutBounce",
+        ] {
+            let easing = name.parse::<Easing>().unwrap();
+
+            assert!(easing.apply(0.0).abs() < 1e-9, "{}", name);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-9, "{}", name);
+        }
+
+        assert!("unknown".parse::<Easing>().is_err());
+    }
+
+    #[test]
+    fn tween_playback() {
+        let tween = Tween::new(10.0, 20.0, Duration::from_secs(2), Easing::Linear);
+
+        assert_eq!(tween.value_at(Duration::from_secs(1)), 15.0);
+        assert_eq!(tween.value_at(Duration::from_secs(5)), 20.0);
+        assert!(tween.is_finished(Duration::from_secs(2)));
+
+        let tween = tween.with_playback(Playback::PingPong);
+
+        assert_eq!(tween.value_at(Duration::from_secs(3)), 15.0);
+        assert!(!tween.is_finished(Duration::from_secs(5)));
+
+        let tween = tween.with_playback(Playback::Loop);
+
+        assert_eq!(tween.value_at(Duration::from_millis(2500)), 12.5);
+    }
+
+    #[test]
+    fn timeline_keyframes() {
+        let timeline = Timeline::new(
+            vec![
+                Keyframe {
+                    offset: Duration::from_secs(2),
+                    value: 0.0,
+                    easing: Easing::Linear,
+                },
+                Keyframe {
+                    offset: Duration::ZERO,
+                    value: 0.0,
+                    easing: Easing::Linear,
+                },
+                Keyframe {
+                    offset: Duration::from_secs(1),
+                    value: 1.0,
+                    easing: Easing::Linear,
+                },
+            ],
+            Playback::Once,
+        )
+        .unwrap();
+
+        assert_eq!(timeline.duration(), Duration::from_secs(2));
+        assert_eq!(timeline.value_at(Duration::from_millis(500)), 0.5);
+        assert_eq!(timeline.value_at(Duration::from_secs(1)), 1.0);
+        assert_eq!(timeline.value_at(Duration::from_millis(1500)), 0.5);
+        assert_eq!(timeline.value_at(Duration::from_secs(3)), 0.0);
+
+        assert!(Timeline::new(vec![], Playback::Once).is_err());
+    }
+}
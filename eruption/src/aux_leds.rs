@@ -0,0 +1,184 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use crate::device_id;
+use crate::hwdevices::{DeviceTrait, RGBA};
+use crate::state::DeviceMakeModelSerial;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuxLedError {
+    #[error("Unknown auxiliary LED: {name}")]
+    UnknownLed { name: String },
+}
+
+lazy_static! {
+    /// Colors of the auxiliary LEDs that have been set, indexed by the stable identifier of their device
+    static ref AUX_LED_COLORS: Arc<RwLock<HashMap<DeviceMakeModelSerial, BTreeMap<String, u32>>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+}
+
+/// State of an auxiliary LED, as returned by `GetAuxLeds`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuxLedState {
+    pub name: String,
+    pub monochrome: bool,
+
+    /// The color that has been set, as 0xRRGGBB, or `None` if the LED shows its firmware default
+    pub color: Option<u32>,
+}
+
+fn to_rgba(color: u32) -> RGBA {
+    RGBA {
+        r: ((color >> 16) & 0xff) as u8,
+        g: ((color >> 8) & 0xff) as u8,
+        b: (color & 0xff) as u8,
+        a: 0xff,
+    }
+}
+
+/// Returns the auxiliary LEDs of `device`, along with the colors that have been set
+pub fn get_aux_leds(device: &dyn DeviceTrait) -> Vec<AuxLedState> {
+    let colors = AUX_LED_COLORS.read();
+    let colors = colors.get(&device_id::device_id(device));
+
+    device
+        .get_aux_leds()
+        .into_iter()
+        .map(|led| AuxLedState {
+            color: colors.and_then(|c| c.get(&led.name).copied()),
+            name: led.name,
+            monochrome: led.monochrome,
+        })
+        .collect()
+}
+
+/// Set the auxiliary LED `name` of `device` to `color` (0xRRGGBB). The device is
+/// only written to if the color actually changed
+pub fn set_aux_led(device: &mut dyn DeviceTrait, name: &str, color: u32) -> Result<()> {
+    if !device.get_aux_leds().iter().any(|led| led.name == name) {
+        return Err(AuxLedError::UnknownLed {
+            name: name.to_string(),
+        }
+        .into());
+    }
+
+    let id = device_id::device_id(device);
+    let color = color & 0xffffff;
+
+    let previous = AUX_LED_COLORS
+        .read()
+        .get(&id)
+        .and_then(|c| c.get(name).copied());
+
+    if previous != Some(color) {
+        device.set_aux_led(name, to_rgba(color))?;
+
+        AUX_LED_COLORS
+            .write()
+            .entry(id)
+            .or_default()
+            .insert(name.to_string(), color);
+    }
+
+    Ok(())
+}
+
+/// Set the auxiliary LED `name` of all connected devices that feature it.
+/// Returns the number of devices that have been updated
+pub fn set_aux_led_all(name: &str, color: u32) -> Result<usize> {
+    let mut result = 0;
+
+    for_each_device(|device| {
+        if device.get_aux_leds().iter().any(|led| led.name == name) {
+            set_aux_led(device, name, color)?;
+            result += 1;
+        }
+
+        Ok(())
+    })?;
+
+    if result == 0 {
+        Err(AuxLedError::UnknownLed {
+            name: name.to_string(),
+        }
+        .into())
+    } else {
+        Ok(result)
+    }
+}
+
+/// Returns the names of the auxiliary LEDs of all connected devices
+pub fn get_aux_led_names() -> Vec<String> {
+    let mut result = BTreeSet::new();
+
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        result.extend(device.read().get_aux_leds().into_iter().map(|led| led.name));
+    }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        result.extend(device.read().get_aux_leds().into_iter().map(|led| led.name));
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        result.extend(device.read().get_aux_leds().into_iter().map(|led| led.name));
+    }
+
+    result.into_iter().collect()
+}
+
+/// Apply the colors that have been set previously, e.g. after the device has been re-initialized
+pub fn restore_aux_leds(device: &mut dyn DeviceTrait) {
+    let colors = AUX_LED_COLORS
+        .read()
+        .get(&device_id::device_id(device))
+        .cloned();
+
+    for (name, color) in colors.unwrap_or_default() {
+        device
+            .set_aux_led(&name, to_rgba(color))
+            .unwrap_or_else(|e| warn!("Could not restore the auxiliary LED '{}': {}", name, e));
+    }
+}
+
+fn for_each_device(mut f: impl FnMut(&mut dyn DeviceTrait) -> Result<()>) -> Result<()> {
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        f(device.write().as_device_mut())?;
+    }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        f(device.write().as_device_mut())?;
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        f(device.write().as_device_mut())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::brightness_curve::BrightnessCurve;
+use crate::constants;
+use crate::scripting::script;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Settings of the display backlight sync, as configured in the [backlight] section of eruption.conf
+    static ref SETTINGS: Arc<RwLock<BacklightSettings>> = Arc::new(RwLock::new(BacklightSettings::default()));
+
+    /// The level of the display backlight in the range `0.0..=1.0`, or `None` if it is unknown
+    static ref DISPLAY_LEVEL: Arc<RwLock<Option<f64>>> = Arc::new(RwLock::new(None));
+}
+
+/// The mapping of the display backlight level to the brightness of the LEDs
+#[derive(Debug, Clone)]
+struct BacklightSettings {
+    /// Scale the brightness of the LEDs with the display backlight
+    enabled: bool,
+
+    /// Name of the backlight device, like e.g. "intel_backlight". The first one is used if unset
+    device: Option<String>,
+
+    /// Maps the level of the display backlight to the brightness of the LEDs
+    curve: BrightnessCurve,
+
+    /// Lower bound of the brightness in percent, relative to the global brightness
+    min_brightness: u64,
+}
+
+impl Default for BacklightSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            device: None,
+            curve: BrightnessCurve::Linear,
+            min_brightness: 0,
+        }
+    }
+}
+
+impl BacklightSettings {
+    /// Map the display backlight level `level` to a factor of the global brightness
+    fn brightness_factor(&self, level: f64) -> f32 {
+        let min = self.min_brightness.min(100) as f64 / 100.0;
+
+        (min + (1.0 - min) * self.curve.apply(level)) as f32
+    }
+}
+
+/// Load the display backlight sync settings from the global configuration
+pub fn load_settings() -> Result<()> {
+    let config = crate::CONFIG.lock();
+    let config = config.as_ref().unwrap();
+
+    let curve = match config.get::<String>("backlight.curve") {
+        Ok(curve) => curve.parse::<BrightnessCurve>().unwrap_or_else(|e| {
+            warn!("Ignoring the setting backlight.curve: {}", e);
+
+            BrightnessCurve::Linear
+        }),
+
+        Err(_) => BrightnessCurve::Linear,
+    };
+
+    let settings = BacklightSettings {
+        enabled: config.get::<bool>("backlight.enabled").unwrap_or(false),
+        device: config.get::<String>("backlight.device").ok(),
+        curve,
+        min_brightness: config
+            .get::<u64>("backlight.min_brightness")
+            .unwrap_or(0)
+            .min(100),
+    };
+
+    *SETTINGS.write() = settings;
+
+    Ok(())
+}
+
+/// Returns the sysfs directory of the display backlight device that shall be followed
+fn find_backlight_device(name: Option<&str>) -> Option<PathBuf> {
+    let base = Path::new(constants::BACKLIGHT_SYSFS_PATH);
+
+    match name {
+        Some(name) => Some(base.join(name)).filter(|path| path.exists()),
+
+        None => {
+            let mut entries = fs::read_dir(base)
+                .ok()?
+                .flatten()
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>();
+
+            entries.sort();
+            entries.into_iter().next()
+        }
+    }
+}
+
+/// Read the level of the display backlight from sysfs, in the range `0.0..=1.0`
+fn read_display_level(device: &Path) -> Option<f64> {
+    let read = |attribute: &str| {
+        fs::read_to_string(device.join(attribute))
+            .ok()
+            .and_then(|s| s.trim().parse::<f64>().ok())
+    };
+
+    let brightness = read("actual_brightness").or_else(|| read("brightness"))?;
+    let max_brightness = read("max_brightness").filter(|max| *max > 0.0)?;
+
+    Some((brightness / max_brightness).clamp(0.0, 1.0))
+}
+
+/// Re-read the level of the display backlight, returns `true` if it changed
+pub fn update_display_level() -> bool {
+    let (enabled, device) = {
+        let settings = SETTINGS.read();
+
+        (settings.enabled, settings.device.clone())
+    };
+
+    if !enabled {
+        return false;
+    }
+
+    let level = find_backlight_device(device.as_deref())
+        .as_deref()
+        .and_then(read_display_level);
+
+    let previous = std::mem::replace(&mut *DISPLAY_LEVEL.write(), level);
+
+    if previous != level {
+        debug!("Display backlight level changed: {:?}", level);
+
+        // the LED map needs to be uploaded again, even if the profile is static
+        script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+        true
+    } else {
+        false
+    }
+}
+
+/// Returns the factor that the LED maps shall be dimmed by to follow the display
+/// backlight, or `None` if they shall be sent to the devices unaltered
+pub fn get_brightness_factor() -> Option<f32> {
+    let settings = SETTINGS.read();
+
+    if settings.enabled {
+        DISPLAY_LEVEL
+            .read()
+            .map(|level| settings.brightness_factor(level))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brightness_follows_the_display() {
+        let settings = BacklightSettings {
+            enabled: true,
+            min_brightness: 20,
+            ..Default::default()
+        };
+
+        assert!((settings.brightness_factor(1.0) - 1.0).abs() < 1e-6);
+        assert!((settings.brightness_factor(0.5) - 0.6).abs() < 1e-6);
+
+        // the keyboard does not go dark along with the display
+        assert!((settings.brightness_factor(0.0) - 0.2).abs() < 1e-6);
+    }
+}
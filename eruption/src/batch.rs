@@ -0,0 +1,408 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use log::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+use crate::profiles::Profile;
+use crate::scripting::{parameters_util, script};
+use crate::{constants, hotkeys, plugins, state_file};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BatchError {
+    #[error("Could not parse the batch: {description}")]
+    ParseError { description: String },
+
+    #[error("Operation #{index} failed, all changes have been rolled back: {source}")]
+    OperationFailed {
+        index: usize,
+
+        #[source]
+        source: eyre::Error,
+    },
+
+    #[error("Slot index out of range: {slot}")]
+    InvalidSlot { slot: usize },
+
+    #[error("Brightness out of range: {brightness}")]
+    InvalidBrightness { brightness: i64 },
+
+    #[error("The profile {profile} does not run the script {script}")]
+    NoSuchScript { profile: String, script: String },
+
+    #[error("No profile is assigned to the slot")]
+    NoProfile {},
+}
+
+/// A single operation of a batch, as submitted by e.g. `eruptionctl batch`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum Operation {
+    /// Switch to the slot `slot`, starting at 1
+    SwitchSlot { slot: usize },
+
+    /// Set a parameter of a script. The profile defaults to the profile of the slot that
+    /// is active once the preceding operations have been applied
+    SetParameter {
+        #[serde(default)]
+        profile: Option<PathBuf>,
+        script: String,
+        parameter: String,
+        value: serde_json::Value,
+    },
+
+    /// Set the global brightness in percent
+    SetBrightness { brightness: i64 },
+
+    /// Apply a named color scheme to the gradient and color parameters of a profile
+    ApplyScheme {
+        scheme: String,
+        #[serde(default)]
+        profile: Option<PathBuf>,
+        #[serde(default)]
+        persist: bool,
+    },
+}
+
+/// The changes of a batch, that are reverted if one of its operations fails
+#[derive(Debug, Default)]
+struct Journal {
+    /// The profile state files that have been modified, along with their previous contents
+    state_files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl Journal {
+    /// Remember the state file of the profile `profile_file`, before it is modified
+    fn record(&mut self, profile_file: &Path) {
+        let state_file = profile_file.with_extension("profile.state");
+
+        if !self.state_files.iter().any(|(path, _)| *path == state_file) {
+            let contents = fs::read(&state_file).ok();
+
+            self.state_files.push((state_file, contents));
+        }
+    }
+
+    fn rollback(self) {
+        if self.state_files.is_empty() {
+            return;
+        }
+
+        for (state_file, contents) in self.state_files.into_iter().rev() {
+            let result = match contents {
+                Some(contents) => state_file::restore(&state_file, &contents),
+                None => match fs::remove_file(&state_file) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                    _ => Ok(()),
+                },
+            };
+
+            result.unwrap_or_else(|e| {
+                error!(
+                    "Could not restore the state file {}: {}",
+                    state_file.display(),
+                    e
+                )
+            });
+        }
+
+        // the scripts of the active profile may already have received the new parameters
+        crate::REQUEST_PROFILE_RELOAD.store(true, Ordering::SeqCst);
+    }
+}
+
+/// The state of the daemon, as it will be once the batch has been committed
+struct Pending {
+    slot: usize,
+    brightness: Option<i64>,
+}
+
+impl Pending {
+    /// Returns the profile `profile`, or the profile of the pending slot
+    fn profile_file(&self, profile: &Option<PathBuf>) -> Result<PathBuf> {
+        match profile {
+            Some(profile) => Ok(hotkeys::resolve_profile_path(profile)),
+
+            None => crate::SLOT_PROFILES
+                .lock()
+                .as_ref()
+                .and_then(|slot_profiles| slot_profiles.get(self.slot).cloned())
+                .ok_or_else(|| BatchError::NoProfile {}.into()),
+        }
+    }
+
+    /// Returns `true` if the profile `profile_file` will be the active profile
+    fn is_active(&self, profile_file: &Path) -> bool {
+        let active_slot = crate::ACTIVE_SLOT.load(Ordering::SeqCst);
+
+        self.slot == active_slot
+            && crate::ACTIVE_PROFILE
+                .lock()
+                .as_ref()
+                .map(|profile| {
+                    same_file::is_same_file(&profile.profile_file, profile_file).unwrap_or(false)
+                })
+                .unwrap_or(false)
+    }
+}
+
+/// Returns the path of the script `script` of the profile `profile_file`
+fn find_script(profile_file: &Path, script: &str) -> Result<PathBuf> {
+    let profile = Profile::load_fully(profile_file)?;
+
+    profile
+        .manifests
+        .values()
+        .map(|manifest| &manifest.script_file)
+        .find(|script_file| {
+            script_file.as_path() == Path::new(script)
+                || script_file.file_name() == Path::new(script).file_name()
+        })
+        .cloned()
+        .ok_or_else(|| {
+            BatchError::NoSuchScript {
+                profile: profile_file.display().to_string(),
+                script: script.to_string(),
+            }
+            .into()
+        })
+}
+
+fn apply(operation: &Operation, pending: &mut Pending, journal: &mut Journal) -> Result<()> {
+    match operation {
+        Operation::SwitchSlot { slot } => {
+            if !(1..=constants::NUM_SLOTS).contains(slot) {
+                return Err(BatchError::InvalidSlot { slot: *slot }.into());
+            }
+
+            pending.slot = slot - 1;
+        }
+
+        Operation::SetParameter {
+            profile,
+            script,
+            parameter,
+            value,
+        } => {
+            let profile_file = pending.profile_file(profile)?;
+            let script_file = find_script(&profile_file, script)?;
+
+            let value = match value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+
+            journal.record(&profile_file);
+
+            parameters_util::apply_parameters(
+                &profile_file.to_string_lossy(),
+                &script_file.to_string_lossy(),
+                &[crate::scripting::parameters::UntypedParameter {
+                    name: parameter.clone(),
+                    value,
+                }],
+            )?;
+        }
+
+        Operation::SetBrightness { brightness } => {
+            if !(0..=100).contains(brightness) {
+                return Err(BatchError::InvalidBrightness {
+                    brightness: *brightness,
+                }
+                .into());
+            }
+
+            pending.brightness = Some(*brightness);
+        }
+
+        Operation::ApplyScheme {
+            scheme,
+            profile,
+            persist,
+        } => {
+            let profile_file = pending.profile_file(profile)?;
+
+            // profiles that are not active yet only pick up persisted parameters
+            let persist = *persist || !pending.is_active(&profile_file);
+
+            journal.record(&profile_file);
+
+            parameters_util::apply_color_scheme(&profile_file.to_string_lossy(), scheme, persist)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply the operations `operations` in order with `apply`. If one of them fails, the changes
+/// of the preceding operations are rolled back
+fn apply_all<F>(operations: &[Operation], pending: &mut Pending, apply: F) -> Result<()>
+where
+    F: Fn(&Operation, &mut Pending, &mut Journal) -> Result<()>,
+{
+    let mut journal = Journal::default();
+
+    for (index, operation) in operations.iter().enumerate() {
+        debug!("Batch operation #{}: {:?}", index + 1, operation);
+
+        if let Err(e) = apply(operation, pending, &mut journal) {
+            warn!("Batch operation #{} failed, rolling back: {}", index + 1, e);
+
+            journal.rollback();
+
+            return Err(BatchError::OperationFailed {
+                index: index + 1,
+                source: e,
+            }
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Execute the batch of operations `data`, a JSON array. The operations are applied in order;
+/// if one of them fails, the changes of the preceding operations are rolled back. Slot
+/// switches and the brightness are committed only after all operations succeeded.
+/// Returns the number of operations that have been applied
+pub fn execute(data: &str) -> Result<usize> {
+    let operations: Vec<Operation> =
+        serde_json::from_str(data).map_err(|e| BatchError::ParseError {
+            description: format!("{}", e),
+        })?;
+
+    let mut pending = Pending {
+        slot: crate::ACTIVE_SLOT.load(Ordering::SeqCst),
+        brightness: None,
+    };
+
+    apply_all(&operations, &mut pending, apply)?;
+
+    // commit
+    if let Some(brightness) = pending.brightness {
+        crate::BRIGHTNESS.store(brightness as isize, Ordering::SeqCst);
+        script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+
+    if pending.slot != crate::ACTIVE_SLOT.load(Ordering::SeqCst) {
+        // reset the audio backend, it will be enabled again if needed
+        plugins::audio::reset_audio_backend();
+
+        // the main loop picks up the new slot and switches profiles
+        crate::ACTIVE_SLOT.store(pending.slot, Ordering::SeqCst);
+    }
+
+    Ok(operations.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_operations() -> Result<()> {
+        let data = r#"[
+            { "op": "switch-slot", "slot": 2 },
+            { "op": "set-parameter", "script": "organic.lua", "parameter": "opacity", "value": 0.5 },
+            { "op": "set-brightness", "brightness": 80 },
+            { "op": "apply-scheme", "scheme": "sunset", "persist": true }
+        ]"#;
+
+        let operations: Vec<Operation> = serde_json::from_str(data)?;
+
+        assert_eq!(operations.len(), 4);
+        assert!(matches!(operations[0], Operation::SwitchSlot { slot: 2 }));
+        assert!(matches!(
+            &operations[1],
+            Operation::SetParameter { profile: None, value, .. } if value.to_string() == "0.5"
+        ));
+        assert!(matches!(
+            &operations[3],
+            Operation::ApplyScheme { persist: true, .. }
+        ));
+
+        assert!(serde_json::from_str::<Vec<Operation>>(r#"[{ "op": "reboot" }]"#).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_operations_restore_the_state_files() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("eruption-batch-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let existing = dir.join("existing.profile");
+        let created = dir.join("created.profile");
+
+        fs::write(existing.with_extension("profile.state"), "opacity = 0.5\n")?;
+
+        let operations: Vec<Operation> = serde_json::from_str(
+            r#"[
+                { "op": "set-brightness", "brightness": 80 },
+                { "op": "set-brightness", "brightness": 90 },
+                { "op": "switch-slot", "slot": 0 }
+            ]"#,
+        )?;
+
+        let mut pending = Pending {
+            slot: 0,
+            brightness: None,
+        };
+
+        // the first two operations modify a state file each, the last one fails
+        let result = apply_all(&operations, &mut pending, |operation, pending, journal| {
+            let profile_file = match operation {
+                Operation::SetBrightness { brightness: 80 } => &existing,
+                Operation::SetBrightness { .. } => &created,
+                _ => return apply(operation, pending, journal),
+            };
+
+            journal.record(profile_file);
+
+            fs::write(
+                profile_file.with_extension("profile.state"),
+                "opacity = 1.0\n",
+            )?;
+
+            Ok(())
+        });
+
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<BatchError>(),
+            Some(BatchError::OperationFailed { index: 3, .. })
+        ));
+
+        assert_eq!(
+            fs::read_to_string(existing.with_extension("profile.state"))?,
+            "opacity = 0.5\n"
+        );
+        assert!(!created.with_extension("profile.state").exists());
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::hwdevices::RGBA;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlendingError {
+    #[error("Slot index out of bounds")]
+    InvalidSlot {},
+
+    #[error("Invalid blend weight {weight}, expected a value between 0.0 and 1.0")]
+    InvalidWeight { weight: f64 },
+
+    #[error("The active slot does not blend two profiles")]
+    NotBlended {},
+}
+
+lazy_static! {
+    /// The blended (secondary) profiles of the slots
+    static ref SLOT_BLENDS: Arc<Mutex<Vec<SlotBlend>>> = Arc::new(Mutex::new(Vec::new()));
+
+    /// The currently effective blend weight, and the transition to the target weight
+    static ref TRANSITION: Arc<Mutex<Transition>> = Arc::new(Mutex::new(Transition::new(0.0)));
+}
+
+/// A slot that runs a second profile, alongside its primary profile
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotBlend {
+    pub slot: usize,
+
+    /// The blended (secondary) profile
+    pub profile: PathBuf,
+
+    /// Weight of the secondary profile, 0.0 shows only the primary profile, 1.0 only the secondary
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Transition {
+    from: f64,
+    to: f64,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Transition {
+    fn new(weight: f64) -> Self {
+        Self {
+            from: weight,
+            to: weight,
+            start: Instant::now(),
+            duration: Duration::ZERO,
+        }
+    }
+
+    fn current(&self) -> f64 {
+        let elapsed = self.start.elapsed();
+
+        if elapsed >= self.duration {
+            self.to
+        } else {
+            let t = elapsed.as_secs_f64() / self.duration.as_secs_f64();
+            self.from + (self.to - self.from) * t
+        }
+    }
+}
+
+fn check_weight(weight: f64) -> Result<()> {
+    if (0.0..=1.0).contains(&weight) {
+        Ok(())
+    } else {
+        Err(BlendingError::InvalidWeight { weight }.into())
+    }
+}
+
+/// Returns the blend of `slot`, if the slot blends two profiles
+pub fn get_slot_blend(slot: usize) -> Option<SlotBlend> {
+    SLOT_BLENDS.lock().iter().find(|b| b.slot == slot).cloned()
+}
+
+/// Returns the blend of the active slot, if it blends two profiles
+pub fn get_active_blend() -> Option<SlotBlend> {
+    get_slot_blend(crate::ACTIVE_SLOT.load(Ordering::SeqCst))
+}
+
+/// Returns all blended slots, used to persist them in the runtime state
+pub fn get_slot_blends() -> Vec<SlotBlend> {
+    SLOT_BLENDS.lock().clone()
+}
+
+/// Replace all blended slots, e.g. with the blends of the saved runtime state
+pub fn set_slot_blends(blends: Vec<SlotBlend>) {
+    *SLOT_BLENDS.lock() = blends
+        .into_iter()
+        .filter(|b| b.slot < crate::constants::NUM_SLOTS && check_weight(b.weight).is_ok())
+        .collect();
+}
+
+/// Blend `profile` into `slot` with the weight `weight`, or remove the blend of the slot if
+/// `profile` is `None`. The active profile is reloaded if `slot` is the active slot
+pub fn set_slot_blend(slot: usize, profile: Option<&Path>, weight: f64) -> Result<()> {
+    if slot >= crate::constants::NUM_SLOTS {
+        return Err(BlendingError::InvalidSlot {}.into());
+    }
+
+    check_weight(weight)?;
+
+    {
+        let mut blends = SLOT_BLENDS.lock();
+        blends.retain(|b| b.slot != slot);
+
+        if let Some(profile) = profile {
+            info!(
+                "Blending profile {} into slot #{} ({:.0}%)",
+                profile.display(),
+                slot + 1,
+                weight * 100.0
+            );
+
+            blends.push(SlotBlend {
+                slot,
+                profile: profile.to_path_buf(),
+                weight,
+            });
+        } else {
+            info!("Removed the blended profile of slot #{}", slot + 1);
+        }
+    }
+
+    if slot == crate::ACTIVE_SLOT.load(Ordering::SeqCst) {
+        crate::REQUEST_PROFILE_RELOAD.store(true, Ordering::SeqCst);
+    }
+
+    Ok(())
+}
+
+/// Fade the blend weight of the active slot to `weight`, over a period of `duration`
+pub fn fade_to(weight: f64, duration: Duration) -> Result<()> {
+    check_weight(weight)?;
+
+    let active_slot = crate::ACTIVE_SLOT.load(Ordering::SeqCst);
+
+    let mut blends = SLOT_BLENDS.lock();
+    let blend = blends
+        .iter_mut()
+        .find(|b| b.slot == active_slot)
+        .ok_or(BlendingError::NotBlended {})?;
+
+    blend.weight = weight;
+
+    let mut transition = TRANSITION.lock();
+    *transition = Transition {
+        from: transition.current(),
+        to: weight,
+        start: Instant::now(),
+        duration,
+    };
+
+    Ok(())
+}
+
+/// Set the effective blend weight immediately, e.g. after a profile switch
+pub fn reset_weight(weight: f64) {
+    *TRANSITION.lock() = Transition::new(weight);
+}
+
+/// Returns the effective blend weight, a transition may be in progress
+pub fn current_weight() -> f64 {
+    TRANSITION.lock().current()
+}
+
+/// Composite the canvas of the primary profile with the canvas of the secondary profile,
+/// using the effective blend weight. The result is stored in `secondary`
+pub fn blend_layers(primary: &[RGBA], secondary: &mut [RGBA]) {
+    blend_with_weight(primary, secondary, current_weight() as f32);
+}
+
+fn blend_with_weight(primary: &[RGBA], secondary: &mut [RGBA], weight: f32) {
+    let mix = |a: u8, b: u8| -> u8 {
+        (a as f32 * (1.0 - weight) + b as f32 * weight)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    };
+
+    for (bg, fg) in primary.iter().zip(secondary.iter_mut()) {
+        *fg = RGBA {
+            r: mix(bg.r, fg.r),
+            g: mix(bg.g, fg.g),
+            b: mix(bg.b, fg.b),
+            a: mix(bg.a, fg.a),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(r: u8, g: u8, b: u8, a: u8) -> RGBA {
+        RGBA { r, g, b, a }
+    }
+
+    fn components(colors: &[RGBA]) -> Vec<(u8, u8, u8, u8)> {
+        colors.iter().map(|c| (c.r, c.g, c.b, c.a)).collect()
+    }
+
+    #[test]
+    fn layers_are_mixed_by_weight() {
+        let primary = vec![color(200, 0, 100, 255); 2];
+
+        let mut secondary = vec![color(0, 200, 100, 255); 2];
+        blend_with_weight(&primary, &mut secondary, 0.0);
+        assert_eq!(components(&secondary), vec![(200, 0, 100, 255); 2]);
+
+        let mut secondary = vec![color(0, 200, 100, 255); 2];
+        blend_with_weight(&primary, &mut secondary, 1.0);
+        assert_eq!(components(&secondary), vec![(0, 200, 100, 255); 2]);
+
+        let mut secondary = vec![color(0, 200, 100, 0); 2];
+        blend_with_weight(&primary, &mut secondary, 0.25);
+        assert_eq!(components(&secondary), vec![(150, 50, 100, 191); 2]);
+    }
+
+    #[test]
+    fn transitions_are_interpolated() {
+        assert_eq!(Transition::new(0.5).current(), 0.5);
+
+        let transition = Transition {
+            from: 0.0,
+            to: 1.0,
+            start: Instant::now() - Duration::from_secs(1),
+            duration: Duration::from_secs(4),
+        };
+
+        let weight = transition.current();
+        assert!((0.25..0.5).contains(&weight), "{}", weight);
+
+        let finished = Transition {
+            start: Instant::now() - Duration::from_secs(5),
+            ..transition
+        };
+
+        assert_eq!(finished.current(), 1.0);
+    }
+
+    #[test]
+    fn invalid_blends_are_rejected() {
+        assert!(check_weight(0.0).is_ok());
+        assert!(check_weight(1.0).is_ok());
+        assert!(check_weight(1.5).is_err());
+        assert!(check_weight(f64::NAN).is_err());
+
+        assert!(set_slot_blend(crate::constants::NUM_SLOTS, None, 0.5).is_err());
+
+        let blend = |slot, weight| SlotBlend {
+            slot,
+            profile: PathBuf::from("secondary.profile"),
+            weight,
+        };
+
+        // blends of the saved runtime state are validated too
+        set_slot_blends(vec![
+            blend(0, 0.5),
+            blend(1, -1.0),
+            blend(crate::constants::NUM_SLOTS, 0.5),
+        ]);
+
+        assert_eq!(get_slot_blends(), vec![blend(0, 0.5)]);
+        assert_eq!(get_slot_blend(0), Some(blend(0, 0.5)));
+        assert_eq!(get_slot_blend(1), None);
+    }
+}
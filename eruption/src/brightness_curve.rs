@@ -0,0 +1,297 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::hwdevices::{DeviceTrait, RGBA};
+use crate::scripting::script;
+use crate::state::DeviceMakeModelSerial;
+use crate::{constants, device_id, state_file};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BrightnessCurveError {
+    #[error("Invalid brightness curve: {description}")]
+    InvalidCurve { description: String },
+}
+
+lazy_static! {
+    /// The curve of all devices without an override, from `global.brightness_curve`
+    static ref DEFAULT_CURVE: Arc<RwLock<BrightnessCurve>> =
+        Arc::new(RwLock::new(BrightnessCurve::default()));
+
+    /// Brightness curves of all devices that have been overridden so far, persisted across restarts
+    pub static ref DEVICE_CURVES: Arc<RwLock<BTreeMap<DeviceMakeModelSerial, BrightnessCurve>>> =
+        Arc::new(RwLock::new(BTreeMap::new()));
+}
+
+/// Maps the brightness, as set by the user, to the intensity of the LEDs. LEDs respond
+/// linearly to their duty cycle, but the eye does not, so with a linear curve low
+/// brightness values look much brighter than expected
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BrightnessCurve {
+    /// The brightness is applied as is
+    #[default]
+    Linear,
+
+    /// The brightness is raised to the power of the exponent
+    Gamma(f64),
+
+    /// The brightness is treated as the perceived lightness of the CIE 1931 color space
+    Cie,
+}
+
+impl BrightnessCurve {
+    pub fn is_linear(&self) -> bool {
+        matches!(self, BrightnessCurve::Linear) || *self == BrightnessCurve::Gamma(1.0)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let BrightnessCurve::Gamma(gamma) = self {
+            if !(gamma.is_finite() && (0.1..=5.0).contains(gamma)) {
+                return Err(BrightnessCurveError::InvalidCurve {
+                    description: format!("Gamma out of range (0.1 - 5.0): {}", gamma),
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Map the brightness `value` in the range `0.0..=1.0` to the intensity of the LEDs
+    pub fn apply(&self, value: f64) -> f64 {
+        let value = value.clamp(0.0, 1.0);
+
+        match self {
+            BrightnessCurve::Linear => value,
+
+            BrightnessCurve::Gamma(gamma) => value.powf(*gamma),
+
+            BrightnessCurve::Cie => {
+                let lightness = value * 100.0;
+
+                if lightness <= 8.0 {
+                    lightness / 903.3
+                } else {
+                    ((lightness + 16.0) / 116.0).powi(3)
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for BrightnessCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BrightnessCurve::Linear => write!(f, "linear"),
+            BrightnessCurve::Gamma(gamma) => write!(f, "gamma={}", gamma),
+            BrightnessCurve::Cie => write!(f, "cie"),
+        }
+    }
+}
+
+impl FromStr for BrightnessCurve {
+    type Err = eyre::Error;
+
+    /// Parse a curve like e.g. "cie", "linear" or "gamma=2.2"
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || BrightnessCurveError::InvalidCurve {
+            description: s.to_string(),
+        };
+
+        let result = match s.trim().split_once('=') {
+            Some(("gamma", value)) => {
+                BrightnessCurve::Gamma(value.trim().parse().map_err(|_| invalid())?)
+            }
+
+            None if s.trim() == "linear" => BrightnessCurve::Linear,
+            None if s.trim() == "cie" => BrightnessCurve::Cie,
+
+            _ => return Err(invalid().into()),
+        };
+
+        result.validate()?;
+
+        Ok(result)
+    }
+}
+
+/// Load the default brightness curve from the configuration
+pub fn initialize() {
+    let curve = crate::CONFIG
+        .lock()
+        .as_ref()
+        .and_then(|config| config.get::<String>("global.brightness_curve").ok());
+
+    if let Some(curve) = curve {
+        match curve.parse::<BrightnessCurve>() {
+            Ok(curve) => *DEFAULT_CURVE.write() = curve,
+
+            Err(e) => warn!("Ignoring the setting global.brightness_curve: {}", e),
+        }
+    }
+}
+
+/// Returns the brightness curve that has been assigned to a device, if any
+pub fn get_device_curve(device: &dyn DeviceTrait) -> Option<BrightnessCurve> {
+    device_id::lookup(&DEVICE_CURVES.read(), device).copied()
+}
+
+/// Returns the brightness curve of a device, the default curve is used if it has not been overridden
+pub fn get_curve(device: &dyn DeviceTrait) -> BrightnessCurve {
+    get_device_curve(device).unwrap_or_else(|| *DEFAULT_CURVE.read())
+}
+
+/// Override the brightness curve of a device, or restore the default curve if `curve` is `None`,
+/// and persist it
+pub fn set_curve(device: &dyn DeviceTrait, curve: Option<BrightnessCurve>) -> Result<()> {
+    if let Some(curve) = curve {
+        curve.validate()?;
+    }
+
+    {
+        let mut device_curves = DEVICE_CURVES.write();
+        let key = device_id::migrate(&mut device_curves, device);
+
+        match curve {
+            Some(curve) => device_curves.insert(key, curve),
+            None => device_curves.remove(&key),
+        };
+    }
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    save_device_curves()
+}
+
+/// Apply the brightness curve of a device to a LED map. The LED map has already been scaled
+/// linearly by the global brightness, and the device scales it by `local_brightness`, so it
+/// is scaled by the ratio of the curve and the linear brightness
+pub fn correct_led_map<'a>(
+    device: &dyn DeviceTrait,
+    local_brightness: i32,
+    led_map: &'a [RGBA],
+) -> Cow<'a, [RGBA]> {
+    let curve = get_curve(device);
+
+    if curve.is_linear() {
+        return Cow::Borrowed(led_map);
+    }
+
+    let brightness = (crate::BRIGHTNESS.load(Ordering::SeqCst) as f64 / 100.0)
+        * (local_brightness as f64 / 100.0);
+    let brightness = brightness.clamp(0.0, 1.0);
+
+    if brightness <= 0.0 {
+        return Cow::Borrowed(led_map);
+    }
+
+    let factor = curve.apply(brightness) / brightness;
+
+    Cow::Owned(
+        led_map
+            .iter()
+            .map(|color| RGBA {
+                r: (color.r as f64 * factor).round().min(255.0) as u8,
+                g: (color.g as f64 * factor).round().min(255.0) as u8,
+                b: (color.b as f64 * factor).round().min(255.0) as u8,
+                a: color.a,
+            })
+            .collect(),
+    )
+}
+
+pub fn save_device_curves() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("device-brightness-curves.state");
+
+    let data = toml::to_string_pretty(&*DEVICE_CURVES.read())?;
+    state_file::write(&file_name, &data)?;
+
+    Ok(())
+}
+
+pub fn load_device_curves() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("device-brightness-curves.state");
+
+    let device_curves: BTreeMap<DeviceMakeModelSerial, BrightnessCurve> =
+        state_file::load(&file_name, |data| Ok(toml::from_str(data)?))?;
+
+    *DEVICE_CURVES.write() = device_curves
+        .into_iter()
+        .filter(|(_, curve)| curve.validate().is_ok())
+        .collect();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BrightnessCurve;
+
+    #[test]
+    fn parse_and_format() {
+        for curve in ["linear", "cie", "gamma=2.2"] {
+            assert_eq!(curve.parse::<BrightnessCurve>().unwrap().to_string(), curve);
+        }
+
+        assert!("gamma=0".parse::<BrightnessCurve>().is_err());
+        assert!("gamma".parse::<BrightnessCurve>().is_err());
+        assert!("srgb".parse::<BrightnessCurve>().is_err());
+
+        // existing installations keep their brightness, unless they opt in to another curve
+        assert_eq!(BrightnessCurve::default(), BrightnessCurve::Linear);
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        for curve in [
+            BrightnessCurve::Linear,
+            BrightnessCurve::Gamma(2.2),
+            BrightnessCurve::Cie,
+        ] {
+            assert_eq!(curve.apply(0.0), 0.0);
+            assert!((curve.apply(1.0) - 1.0).abs() < 1e-6);
+
+            let values = (0..=100)
+                .map(|i| curve.apply(i as f64 / 100.0))
+                .collect::<Vec<_>>();
+
+            assert!(values.windows(2).all(|w| w[0] <= w[1]));
+        }
+
+        // 10% perceived lightness corresponds to about 1% luminance
+        let cie = BrightnessCurve::Cie.apply(0.1);
+        assert!((0.01..0.015).contains(&cie));
+    }
+}
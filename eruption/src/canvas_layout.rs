@@ -0,0 +1,383 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::hwdevices::{KeyboardDevice, RGBA};
+use crate::scripting::script;
+use crate::state::DeviceMakeModelSerial;
+use crate::{constants, device_id, state_file, util};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Placements of the keyboards on the shared canvas, persisted across restarts
+    pub static ref CANVAS_LAYOUT: Arc<RwLock<LayoutState>> =
+        Arc::new(RwLock::new(LayoutState::default()));
+}
+
+/// The position of a keyboard on the shared canvas, in keys
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Placement {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// The persisted state of the canvas layout
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutState {
+    /// If set, the canvas is stretched across all keyboards, otherwise each keyboard
+    /// shows the whole canvas
+    #[serde(default)]
+    pub tiling: bool,
+
+    /// Placements that have been assigned by the user, keyboards without a placement
+    /// are lined up to the right of all other keyboards
+    #[serde(default)]
+    pub placements: BTreeMap<DeviceMakeModelSerial, Placement>,
+}
+
+/// The area of the shared canvas that is covered by a single keyboard
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Tile {
+    pub device: DeviceMakeModelSerial,
+
+    pub x: i32,
+    pub y: i32,
+
+    pub width: usize,
+    pub height: usize,
+
+    /// Set if the tile has been placed automatically
+    pub auto: bool,
+}
+
+/// The arrangement of all connected keyboards on the shared canvas
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CanvasLayout {
+    pub tiling: bool,
+
+    /// The tiles, in the order of the device indices of the keyboards
+    pub tiles: Vec<Tile>,
+}
+
+impl CanvasLayout {
+    /// Arrange the keyboards `keyboards`, given by their identifier, number of columns and
+    /// number of rows, in the order of their device indices
+    pub fn compute(
+        state: &LayoutState,
+        keyboards: &[(DeviceMakeModelSerial, usize, usize)],
+    ) -> Self {
+        // keyboards without a placement are lined up to the right of the placed ones
+        let mut next_x = keyboards
+            .iter()
+            .filter_map(|(id, width, _)| {
+                state
+                    .placements
+                    .get(id)
+                    .map(|placement| placement.x + *width as i32)
+            })
+            .max()
+            .unwrap_or(0);
+
+        let tiles = keyboards
+            .iter()
+            .map(|(id, width, height)| {
+                let (placement, auto) = match state.placements.get(id) {
+                    Some(placement) => (*placement, false),
+
+                    None => {
+                        let placement = Placement { x: next_x, y: 0 };
+                        next_x += *width as i32;
+
+                        (placement, true)
+                    }
+                };
+
+                Tile {
+                    device: id.clone(),
+                    x: placement.x,
+                    y: placement.y,
+                    width: *width,
+                    height: *height,
+                    auto,
+                }
+            })
+            .collect();
+
+        Self {
+            tiling: state.tiling,
+            tiles,
+        }
+    }
+
+    /// Returns `true` if the keyboards show different parts of the canvas
+    pub fn is_active(&self) -> bool {
+        self.tiling && self.tiles.len() > 1
+    }
+
+    /// Returns the bounding box of all tiles, as `(x, y, width, height)`
+    pub fn bounds(&self) -> (i32, i32, usize, usize) {
+        let min_x = self.tiles.iter().map(|t| t.x).min().unwrap_or(0);
+        let min_y = self.tiles.iter().map(|t| t.y).min().unwrap_or(0);
+        let max_x = self
+            .tiles
+            .iter()
+            .map(|t| t.x + t.width as i32)
+            .max()
+            .unwrap_or(0);
+        let max_y = self
+            .tiles
+            .iter()
+            .map(|t| t.y + t.height as i32)
+            .max()
+            .unwrap_or(0);
+
+        (
+            min_x,
+            min_y,
+            (max_x - min_x).max(1) as usize,
+            (max_y - min_y).max(1) as usize,
+        )
+    }
+
+    /// Returns the part of the LED map that is shown by the keyboard with the index `index`.
+    /// The bounding box of all tiles is stretched over the keyboard area of the canvas, so
+    /// that effects flow from one keyboard to the next. Indices of the canvas are in column
+    /// major order, like the key indices of the keyboards
+    pub fn apply(&self, index: usize, led_map: &[RGBA]) -> Vec<RGBA> {
+        let mut result = led_map.to_vec();
+
+        let tile = match self.tiles.get(index) {
+            Some(tile) => tile,
+            None => return result,
+        };
+
+        let (min_x, min_y, width, height) = self.bounds();
+
+        let scale_x = constants::CANVAS_KEYBOARD_WIDTH as f64 / width as f64;
+        let scale_y = constants::CANVAS_HEIGHT as f64 / height as f64;
+
+        for x in 0..tile.width.min(constants::CANVAS_WIDTH) {
+            for y in 0..tile.height.min(constants::CANVAS_HEIGHT) {
+                let gx = (tile.x - min_x) as f64 + x as f64 + 0.5;
+                let gy = (tile.y - min_y) as f64 + y as f64 + 0.5;
+
+                let sx =
+                    ((gx * scale_x).floor() as usize).min(constants::CANVAS_KEYBOARD_WIDTH - 1);
+                let sy = ((gy * scale_y).floor() as usize).min(constants::CANVAS_HEIGHT - 1);
+
+                let index = util::canvas_index(x, y);
+                let source = util::canvas_index(sx, sy);
+
+                if let (Some(color), Some(target)) = (led_map.get(source), result.get_mut(index)) {
+                    *target = *color;
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Returns the current arrangement of the keyboards `keyboards`
+pub fn get_layout(keyboards: &[KeyboardDevice]) -> CanvasLayout {
+    let keyboards = keyboards
+        .iter()
+        .map(|device| {
+            let device = device.read();
+
+            (
+                device_id::device_id(device.as_device()),
+                device.get_num_cols(),
+                device.get_num_rows(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    CanvasLayout::compute(&CANVAS_LAYOUT.read(), &keyboards)
+}
+
+/// Returns the arrangement of the keyboards `keyboards`, or `None` if tiling is disabled,
+/// or if there is nothing to tile
+pub fn get_active_layout(keyboards: &[KeyboardDevice]) -> Option<CanvasLayout> {
+    if !CANVAS_LAYOUT.read().tiling || keyboards.len() < 2 {
+        return None;
+    }
+
+    Some(get_layout(keyboards)).filter(|layout| layout.is_active())
+}
+
+/// Returns the part of the LED map that is shown by the keyboard with the index `index`,
+/// the LED map is passed through unaltered if tiling is not active
+pub fn tile_led_map<'a>(
+    layout: Option<&CanvasLayout>,
+    index: usize,
+    led_map: &'a [RGBA],
+) -> Cow<'a, [RGBA]> {
+    match layout {
+        Some(layout) => Cow::Owned(layout.apply(index, led_map)),
+        None => Cow::Borrowed(led_map),
+    }
+}
+
+/// Enable or disable tiling of the canvas across all keyboards, and persist it
+pub fn set_tiling(enable: bool) -> Result<()> {
+    CANVAS_LAYOUT.write().tiling = enable;
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    save_canvas_layout()
+}
+
+/// Move the keyboard `id` to the position `placement` on the shared canvas, or back to
+/// its automatic position if `placement` is `None`, and persist it
+pub fn set_placement(id: &DeviceMakeModelSerial, placement: Option<Placement>) -> Result<()> {
+    {
+        let mut state = CANVAS_LAYOUT.write();
+
+        match placement {
+            Some(placement) => state.placements.insert(id.clone(), placement),
+            None => state.placements.remove(id),
+        };
+    }
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    save_canvas_layout()
+}
+
+/// Forget all placements, so that the keyboards are lined up automatically again
+pub fn reset_placements() -> Result<()> {
+    CANVAS_LAYOUT.write().placements.clear();
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    save_canvas_layout()
+}
+
+pub fn save_canvas_layout() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("canvas-layout.state");
+
+    let data = toml::to_string_pretty(&*CANVAS_LAYOUT.read())?;
+    state_file::write(&file_name, &data)?;
+
+    Ok(())
+}
+
+pub fn load_canvas_layout() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("canvas-layout.state");
+
+    let state: LayoutState = state_file::load(&file_name, |data| Ok(toml::from_str(data)?))?;
+
+    *CANVAS_LAYOUT.write() = state;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_led_map() -> Vec<RGBA> {
+        (0..constants::CANVAS_SIZE)
+            .map(|i| RGBA {
+                r: (i / constants::CANVAS_HEIGHT) as u8,
+                g: (i % constants::CANVAS_HEIGHT) as u8,
+                b: 0x00,
+                a: 0xff,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn auto_layout() {
+        let mut state = LayoutState {
+            tiling: true,
+            ..Default::default()
+        };
+
+        let keyboards = vec![
+            ("0x1e7d:0x3098:a".to_string(), 21, 6),
+            ("0x1e7d:0x2fee:b".to_string(), 16, 6),
+            ("0x1e7d:0x2fee:c".to_string(), 16, 6),
+        ];
+
+        let layout = CanvasLayout::compute(&state, &keyboards);
+        assert!(layout.is_active());
+        assert_eq!(
+            layout.tiles.iter().map(|t| t.x).collect::<Vec<_>>(),
+            vec![0, 21, 37]
+        );
+        assert_eq!(layout.bounds(), (0, 0, 53, 6));
+
+        // placed keyboards keep their position, the others are lined up to their right
+        state
+            .placements
+            .insert("0x1e7d:0x2fee:b".to_string(), Placement { x: -16, y: 0 });
+
+        let layout = CanvasLayout::compute(&state, &keyboards);
+        assert_eq!(
+            layout
+                .tiles
+                .iter()
+                .map(|t| (t.x, t.auto))
+                .collect::<Vec<_>>(),
+            vec![(0, true), (-16, false), (21, true)]
+        );
+    }
+
+    #[test]
+    fn effects_flow_across_keyboards() {
+        let state = LayoutState {
+            tiling: true,
+            ..Default::default()
+        };
+
+        let keyboards = vec![
+            ("0x1e7d:0x3098:a".to_string(), 11, 6),
+            ("0x1e7d:0x3098:b".to_string(), 11, 6),
+        ];
+
+        let layout = CanvasLayout::compute(&state, &keyboards);
+        let led_map = test_led_map();
+
+        let left = layout.apply(0, &led_map);
+        let right = layout.apply(1, &led_map);
+
+        // the left keyboard shows the left half of the canvas, the right one the right half
+        assert_eq!(left[0].r, 0);
+        assert_eq!(right[0].r, 11);
+        assert_eq!(right[10 * constants::CANVAS_HEIGHT + 5].r, 21);
+        assert_eq!(right[10 * constants::CANVAS_HEIGHT + 5].g, 5);
+
+        // a single keyboard is not tiled
+        let layout = CanvasLayout::compute(&state, &keyboards[..1]);
+        assert!(!layout.is_active());
+    }
+}
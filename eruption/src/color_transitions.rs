@@ -0,0 +1,249 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use csscolorparser::Color;
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::animation::Easing;
+use crate::color_scheme::ColorScheme;
+use crate::constants;
+
+lazy_static! {
+    /// Named color schemes that are currently transitioning to new colors
+    static ref TRANSITIONS: Arc<Mutex<HashMap<String, Transition>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+
+    /// Incremented each time the colors of a named color scheme change, gradients that
+    /// have been created from a named color scheme are rebuilt on the next access
+    pub static ref GENERATION: AtomicU64 = AtomicU64::new(0);
+}
+
+#[derive(Debug, Clone)]
+struct Transition {
+    from: ColorScheme,
+    to: ColorScheme,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Transition {
+    fn progress(&self) -> f64 {
+        if self.duration.is_zero() {
+            1.0
+        } else {
+            (self.start.elapsed().as_secs_f64() / self.duration.as_secs_f64()).min(1.0)
+        }
+    }
+}
+
+/// Returns the duration of a transition between two states of a named color scheme
+pub fn transition_duration() -> Duration {
+    let millis = crate::CONFIG
+        .lock()
+        .as_ref()
+        .unwrap()
+        .get_int("global.color_scheme_transition_milliseconds")
+        .unwrap_or(constants::COLOR_SCHEME_TRANSITION_MILLIS as i64);
+
+    Duration::from_millis(millis.max(0) as u64)
+}
+
+/// Replace the colors of the named color scheme `name`. An existing color scheme is
+/// transitioned to the new colors, so that all gradients that reference it change
+/// smoothly. A new color scheme requires the active profile to be reloaded
+pub fn set_color_scheme(name: &str, color_scheme: ColorScheme) {
+    let current = crate::NAMED_COLOR_SCHEMES.read().get(name).cloned();
+
+    match current {
+        Some(from) if !from.colors.is_empty() && !color_scheme.colors.is_empty() => {
+            let duration = transition_duration();
+
+            debug!(
+                "Transitioning color scheme '{}' over {} milliseconds",
+                name,
+                duration.as_millis()
+            );
+
+            TRANSITIONS.lock().insert(
+                name.to_owned(),
+                Transition {
+                    from,
+                    to: color_scheme,
+                    start: Instant::now(),
+                    duration,
+                },
+            );
+
+            update();
+        }
+
+        _ => {
+            cancel(name);
+
+            crate::NAMED_COLOR_SCHEMES
+                .write()
+                .insert(name.to_owned(), color_scheme);
+
+            GENERATION.fetch_add(1, Ordering::SeqCst);
+
+            crate::REQUEST_PROFILE_RELOAD.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Abort a pending transition of the named color scheme `name`, the colors are
+/// left as they currently are
+pub fn cancel(name: &str) {
+    TRANSITIONS.lock().remove(name);
+}
+
+/// Returns the colors that the named color schemes are transitioning to
+pub fn get_targets() -> HashMap<String, ColorScheme> {
+    TRANSITIONS
+        .lock()
+        .iter()
+        .map(|(name, transition)| (name.clone(), transition.to.clone()))
+        .collect()
+}
+
+/// Advance all pending transitions, should be called once per iteration of the main loop
+pub fn update() {
+    let mut transitions = TRANSITIONS.lock();
+
+    if transitions.is_empty() {
+        return;
+    }
+
+    {
+        let mut color_schemes = crate::NAMED_COLOR_SCHEMES.write();
+
+        for (name, transition) in transitions.iter() {
+            let t = transition.progress();
+
+            let color_scheme = if t >= 1.0 {
+                transition.to.clone()
+            } else {
+                interpolate(&transition.from, &transition.to, Easing::InOutSine.apply(t))
+            };
+
+            color_schemes.insert(name.clone(), color_scheme);
+        }
+    }
+
+    transitions.retain(|_name, transition| transition.progress() < 1.0);
+
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Sample the color scheme at the position `pos` (0.0..=1.0), interpolating between
+/// its two nearest colors
+fn sample(color_scheme: &ColorScheme, pos: f64) -> Color {
+    let colors = &color_scheme.colors;
+
+    if colors.len() == 1 {
+        return colors[0].clone();
+    }
+
+    let pos = pos.clamp(0.0, 1.0) * (colors.len() - 1) as f64;
+    let index = (pos.floor() as usize).min(colors.len() - 2);
+
+    mix(&colors[index], &colors[index + 1], pos - index as f64)
+}
+
+fn mix(a: &Color, b: &Color, t: f64) -> Color {
+    Color::new(
+        a.r + (b.r - a.r) * t,
+        a.g + (b.g - a.g) * t,
+        a.b + (b.b - a.b) * t,
+        a.a + (b.a - a.a) * t,
+    )
+}
+
+/// Interpolate between two color schemes, `t` is in the range 0.0..=1.0. Color schemes
+/// with a differing number of colors are resampled to the number of colors of `to`
+fn interpolate(from: &ColorScheme, to: &ColorScheme, t: f64) -> ColorScheme {
+    let count = to.colors.len();
+
+    let colors = to
+        .colors
+        .iter()
+        .enumerate()
+        .map(|(index, color)| {
+            let pos = if count > 1 {
+                index as f64 / (count - 1) as f64
+            } else {
+                0.0
+            };
+
+            mix(&sample(from, pos), color, t)
+        })
+        .collect();
+
+    ColorScheme { colors }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheme(colors: &[(u8, u8, u8)]) -> ColorScheme {
+        ColorScheme {
+            colors: colors
+                .iter()
+                .map(|(r, g, b)| Color::from_rgba8(*r, *g, *b, 255))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn interpolate_color_schemes() {
+        let from = scheme(&[(0, 0, 0), (255, 255, 255)]);
+        let to = scheme(&[(255, 0, 0), (0, 0, 255)]);
+
+        let start = interpolate(&from, &to, 0.0);
+        assert_eq!(start.colors[0].to_rgba8(), [0, 0, 0, 255]);
+        assert_eq!(start.colors[1].to_rgba8(), [255, 255, 255, 255]);
+
+        let end = interpolate(&from, &to, 1.0);
+        assert_eq!(end.colors[0].to_rgba8(), [255, 0, 0, 255]);
+        assert_eq!(end.colors[1].to_rgba8(), [0, 0, 255, 255]);
+
+        let half = interpolate(&from, &to, 0.5);
+        assert_eq!(half.colors[0].to_rgba8(), [128, 0, 0, 255]);
+    }
+
+    #[test]
+    fn resample_differing_lengths() {
+        let from = scheme(&[(0, 0, 0), (255, 255, 255)]);
+        let to = scheme(&[(0, 0, 0), (0, 0, 0), (0, 0, 0)]);
+
+        // the middle color of `to` starts out at the middle of `from`
+        let start = interpolate(&from, &to, 0.0);
+        assert_eq!(start.colors.len(), 3);
+        assert_eq!(start.colors[1].to_rgba8(), [128, 128, 128, 255]);
+    }
+}
@@ -0,0 +1,358 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::constants;
+use crate::hwdevices::RGBA;
+use crate::plugins::sdk_support;
+use crate::scripting::script;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CompositorError {
+    #[error("Unknown layer: {name}")]
+    UnknownLayer { name: String },
+
+    #[error("The layer {priority} is not submitted via the compositor")]
+    ReservedLayer { priority: Priority },
+
+    #[error("Invalid opacity {alpha}, expected a value between 0.0 and 1.0")]
+    InvalidAlpha { alpha: f64 },
+
+    #[error("Invalid size of the LED map: {size}")]
+    InvalidSize { size: usize },
+}
+
+/// The producers of LED maps, in the order in which they are stacked, lowest priority
+/// first. A layer is blended on top of all layers with a lower priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// The scripts of the active profile, the bottom of the stack
+    Profile,
+
+    /// Overlays that are submitted by the process monitor, e.g. while a certain window is focused
+    ProcessMonitor,
+
+    /// The canvas of the connected SDK client
+    Sdk,
+
+    /// Short-lived flashes, like the overlays of `eruptionctl canvas`
+    Notification,
+
+    /// The failsafe profile, it hides all other layers while it is active
+    Failsafe,
+}
+
+impl Priority {
+    /// The layers that are blended on top of the profile, in ascending priority
+    pub const OVERLAYS: [Priority; 3] = [
+        Priority::ProcessMonitor,
+        Priority::Sdk,
+        Priority::Notification,
+    ];
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Priority::Profile => "profile",
+            Priority::ProcessMonitor => "process-monitor",
+            Priority::Sdk => "sdk",
+            Priority::Notification => "notification",
+            Priority::Failsafe => "failsafe",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Priority {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "profile" => Ok(Priority::Profile),
+            "process-monitor" => Ok(Priority::ProcessMonitor),
+            "sdk" => Ok(Priority::Sdk),
+            "notification" => Ok(Priority::Notification),
+            "failsafe" => Ok(Priority::Failsafe),
+
+            _ => Err(CompositorError::UnknownLayer { name: s.to_owned() }.into()),
+        }
+    }
+}
+
+/// A LED map that has been submitted at a certain priority
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub led_map: Vec<RGBA>,
+
+    /// Opacity of the whole layer in the range `0.0..=1.0`, multiplied with the alpha
+    /// channel of each key
+    pub alpha: f32,
+
+    /// Point in time when the layer will be removed, or `None` to keep it indefinitely
+    pub expires_at: Option<Instant>,
+}
+
+impl Layer {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|t| Instant::now() >= t)
+    }
+}
+
+lazy_static! {
+    /// Layers that have been submitted via the compositor, e.g. via D-Bus
+    static ref LAYERS: Arc<RwLock<BTreeMap<Priority, Layer>>> =
+        Arc::new(RwLock::new(BTreeMap::new()));
+
+    /// Set while the failsafe profile is active
+    static ref FAILSAFE_ACTIVE: AtomicBool = AtomicBool::new(false);
+}
+
+/// Submit the LED map `led_map` as the layer `priority`, replacing the previous LED map of
+/// that layer. The layer is removed after `timeout` has elapsed, if specified
+pub fn set_layer(
+    priority: Priority,
+    led_map: Vec<RGBA>,
+    alpha: f64,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    // the profile, the SDK client and the failsafe profile submit their LED maps themselves
+    if !matches!(priority, Priority::ProcessMonitor | Priority::Notification) {
+        return Err(CompositorError::ReservedLayer { priority }.into());
+    }
+
+    if !(0.0..=1.0).contains(&alpha) {
+        return Err(CompositorError::InvalidAlpha { alpha }.into());
+    }
+
+    if led_map.len() != constants::CANVAS_SIZE {
+        return Err(CompositorError::InvalidSize {
+            size: led_map.len(),
+        }
+        .into());
+    }
+
+    debug!("Submitted the layer: {}", priority);
+
+    LAYERS.write().insert(
+        priority,
+        Layer {
+            led_map,
+            alpha: alpha as f32,
+            expires_at: timeout.map(|timeout| Instant::now() + timeout),
+        },
+    );
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    Ok(())
+}
+
+/// Remove the layer `priority`, returns `false` if the layer has not been set
+pub fn clear_layer(priority: Priority) -> bool {
+    let removed = LAYERS.write().remove(&priority).is_some();
+
+    if removed {
+        debug!("Removed the layer: {}", priority);
+
+        script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+
+    removed
+}
+
+/// Remove all expired layers, this is called on every iteration of the main loop
+pub fn expire_layers() {
+    let expired = LAYERS.read().values().any(|layer| layer.is_expired());
+
+    if expired {
+        LAYERS.write().retain(|priority, layer| {
+            if layer.is_expired() {
+                debug!("The layer expired: {}", priority);
+            }
+
+            !layer.is_expired()
+        });
+
+        script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Enter or leave failsafe mode; while the failsafe profile is active, it is the only
+/// visible layer
+pub fn set_failsafe(active: bool) {
+    FAILSAFE_ACTIVE.store(active, Ordering::SeqCst);
+}
+
+/// Blend the layers of the stack on top of `led_map`, which holds the output of the
+/// profile. If `with_sdk` is not set, the canvas of the SDK client is left out, e.g.
+/// for devices that have not been targeted by the SDK client
+pub fn compose(led_map: &mut [RGBA], with_sdk: bool) {
+    if FAILSAFE_ACTIVE.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst) as f32;
+    let layers = LAYERS.read();
+
+    for priority in Priority::OVERLAYS {
+        match priority {
+            Priority::Sdk => {
+                if with_sdk && sdk_support::is_canvas_visible() {
+                    blend(
+                        led_map,
+                        &sdk_support::LED_MAP.read(),
+                        1.0,
+                        brightness,
+                        false,
+                    );
+                }
+            }
+
+            Priority::Notification => {
+                // the temporary overlay of the SDK support plugin is a notification
+                if let Some(overlay) = sdk_support::OVERLAY.read().as_ref() {
+                    blend(led_map, &overlay.led_map, 1.0, brightness, true);
+                }
+
+                if let Some(layer) = layers.get(&priority) {
+                    blend(led_map, &layer.led_map, layer.alpha, brightness, true);
+                }
+            }
+
+            _ => {
+                if let Some(layer) = layers.get(&priority) {
+                    blend(led_map, &layer.led_map, layer.alpha, brightness, true);
+                }
+            }
+        }
+    }
+}
+
+/// Blend `foreground` on top of each canvas of `led_map`. If `skip_transparent` is set,
+/// keys that are not covered by the foreground are left untouched
+fn blend(
+    led_map: &mut [RGBA],
+    foreground: &[RGBA],
+    alpha: f32,
+    brightness: f32,
+    skip_transparent: bool,
+) {
+    for chunks in led_map.chunks_exact_mut(constants::CANVAS_SIZE) {
+        for (background, fg) in chunks.iter_mut().zip(foreground.iter()) {
+            let a = (fg.a as f32 * alpha).round();
+
+            if skip_transparent && a == 0.0 {
+                continue;
+            }
+
+            let bg = *background;
+
+            #[rustfmt::skip]
+            let color = RGBA {
+                r: (((a * fg.r as f32 + (255.0 - a) * bg.r as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
+                g: (((a * fg.g as f32 + (255.0 - a) * bg.g as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
+                b: (((a * fg.b as f32 + (255.0 - a) * bg.b as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
+                a: a as u8,
+            };
+
+            *background = color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_are_ordered() {
+        assert!(Priority::Profile < Priority::ProcessMonitor);
+        assert!(Priority::ProcessMonitor < Priority::Sdk);
+        assert!(Priority::Sdk < Priority::Notification);
+        assert!(Priority::Notification < Priority::Failsafe);
+
+        for priority in Priority::OVERLAYS {
+            assert_eq!(priority.to_string().parse::<Priority>().unwrap(), priority);
+        }
+    }
+
+    #[test]
+    fn layer_alpha_scales_key_alpha() {
+        let mut led_map = vec![
+            RGBA {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 255,
+            };
+            constants::CANVAS_SIZE
+        ];
+
+        let mut overlay = vec![
+            RGBA {
+                r: 255,
+                g: 255,
+                b: 255,
+                a: 255,
+            };
+            constants::CANVAS_SIZE
+        ];
+
+        overlay[1].a = 0;
+
+        blend(&mut led_map, &overlay, 0.5, 100.0, true);
+
+        assert_eq!(led_map[0].a, 128);
+        assert!((125..=129).contains(&led_map[0].r));
+
+        // keys that are not covered by the layer are left untouched
+        assert_eq!((led_map[1].r, led_map[1].a), (0, 255));
+    }
+
+    #[test]
+    fn reserved_layers_are_rejected() {
+        let led_map = vec![
+            RGBA {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            };
+            constants::CANVAS_SIZE
+        ];
+
+        assert!(set_layer(Priority::Sdk, led_map.clone(), 1.0, None).is_err());
+        assert!(set_layer(Priority::ProcessMonitor, led_map, 1.5, None).is_err());
+    }
+}
@@ -36,12 +36,22 @@ pub const DEFAULT_MACRO_DIR: &str = "/usr/share/eruption/scripts/lib/macros";
 /// Default script directory
 pub const DEFAULT_KEYMAP_DIR: &str = "/usr/share/eruption/scripts/lib/keymaps";
 
+/// Default directory of the sound effects (waveform audio files)
+pub const DEFAULT_SFX_DIR: &str = "/usr/share/eruption/sfx/";
+
+/// Directory of the lookup tables of the default profiles of the supported device models
+pub const DEFAULT_DEVICE_DEFAULTS_DIR: &str = "/usr/share/eruption/defaults/";
+
 /// The `/run/eruption/` directory
 pub const RUN_ERUPTION_DIR: &str = "/run/eruption/";
 
 /// State directory
 pub const STATE_DIR: &str = "/var/lib/eruption/";
 
+/// Number of previous generations of each state file that are kept, for the recovery of
+/// corrupted state files
+pub const STATE_FILE_GENERATIONS: usize = 3;
+
 /// Eruption daemon PID file
 pub const PID_FILE: &str = "/run/eruption/eruption.pid";
 
@@ -63,6 +73,25 @@ pub const CONTROL_SOCKET_NAME: &str = "/run/eruption/control.sock";
 /// Eruption daemon audio data UNIX domain socket
 pub const AUDIO_SOCKET_NAME: &str = "/run/eruption/audio.sock";
 
+/// Default duration of an overlay that has been submitted via `eruptionctl canvas`
+pub const DEFAULT_OVERLAY_TIMEOUT_MILLIS: u64 = 5000;
+
+/// Max number of canvas snapshots of SDK clients that are kept, older snapshots are discarded
+pub const MAX_CANVAS_SNAPSHOTS: usize = 16;
+
+/// Max number of named parameters that an SDK client may register
+pub const MAX_SDK_NAMED_PARAMETERS: usize = 64;
+
+/// Max number of keys on the blackboard that the scripts of a profile share
+pub const MAX_BLACKBOARD_ENTRIES: usize = 1024;
+
+/// Max number of removed keys that are remembered by the blackboard, until the scripts have
+/// been notified of their removal. The scripts are notified once per frame
+pub const MAX_BLACKBOARD_REMOVED_ENTRIES: usize = 1024;
+
+/// Max nesting depth of tables that are stored on the blackboard
+pub const MAX_BLACKBOARD_TABLE_DEPTH: usize = 8;
+
 /// Number of slots
 pub const NUM_SLOTS: usize = 4;
 
@@ -75,6 +104,34 @@ pub const AFK_TIMEOUT_SECS: u64 = 0;
 /// Default AFK profile
 pub const DEFAULT_AFK_PROFILE: &str = "/var/lib/eruption/profiles/rainbow-wave.profile";
 
+/// Input within this time span after AFK mode has been entered manually does not leave it,
+/// e.g. the release of the key that issued the command
+pub const AFK_FORCED_GRACE_MILLIS: u64 = 1000;
+
+/// Default duration of the dimming of the LEDs while the user is AFK
+pub const DEFAULT_AFK_FADE_SECS: u64 = 10;
+
+/// Idle periods shorter than this are not considered by the adaptive AFK timeout
+pub const ADAPTIVE_AFK_MIN_IDLE_SECS: u64 = 30;
+
+/// Number of idle periods that need to be observed, before the AFK timeout is adapted
+pub const ADAPTIVE_AFK_MIN_SAMPLES: usize = 10;
+
+/// Max number of idle periods that are kept, older idle periods are forgotten
+pub const ADAPTIVE_AFK_MAX_SAMPLES: usize = 256;
+
+/// Default percentage of the idle periods that shall end before the adaptive AFK timeout expires
+pub const DEFAULT_ADAPTIVE_AFK_PERCENTILE: u64 = 90;
+
+/// Default lower bound of the adaptive AFK timeout
+pub const DEFAULT_ADAPTIVE_AFK_MIN_TIMEOUT_SECS: u64 = 60;
+
+/// Default upper bound of the adaptive AFK timeout
+pub const DEFAULT_ADAPTIVE_AFK_MAX_TIMEOUT_SECS: u64 = 3600;
+
+/// Key in the transient store of the flag that locks the Easy Shift+ layer
+pub const EASY_SHIFT_LOCKED_KEY: &str = "global.easy_shift_locked";
+
 /// Notify the software watchdog every n milliseconds
 pub const WATCHDOG_NOTIFY_MILLIS: u64 = 1499;
 
@@ -84,24 +141,158 @@ pub const PROCESS_SPAWN_WAIT_MILLIS: u64 = 800;
 /// Target frames per second
 pub const TARGET_FPS: u64 = 24;
 
+/// Number of rendered frames in which the frame overruns are counted
+pub const FRAME_BUDGET_WINDOW: usize = 48;
+
+/// Number of frame overruns within `FRAME_BUDGET_WINDOW` frames, after which the frame rate is halved
+pub const FRAME_BUDGET_MAX_OVERRUNS: usize = 12;
+
+/// The full frame rate is restored after no frame overruns occurred for n seconds
+pub const FRAME_BUDGET_RESTORE_SECS: u64 = 30;
+
+/// Maximum LED map update rate of wireless devices in frames per second, wireless
+/// receivers tend to drop frames when they are updated at the full frame rate
+pub const WIRELESS_MAX_UPDATE_RATE: u32 = 15;
+
+/// Number of colors that are sampled from a stock gradient, when it is queried via D-Bus
+pub const GRADIENT_SAMPLE_COUNT: usize = 32;
+
+/// Number of disconnects after which a device is considered unhealthy
+pub const HEALTH_MAX_DISCONNECTS: u64 = 5;
+
+/// Number of failed writes after which a device is considered unhealthy
+pub const HEALTH_MAX_FAILED_WRITES: u64 = 100;
+
+/// Number of re-initializations after which a device is considered unhealthy
+pub const HEALTH_MAX_REINIT_ATTEMPTS: u64 = 10;
+
+/// Number of failed initializations after which a device is considered unhealthy
+pub const HEALTH_MAX_FAILED_INITS: u64 = 1;
+
+/// Number of attempts to initialize a device, before it is marked as failed
+pub const DEVICE_INIT_MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry of a failed device initialization, doubled after each retry
+pub const DEVICE_INIT_INITIAL_BACKOFF_MILLIS: u64 = 100;
+
+/// Delay between the first two attempts to re-initialize a device, doubled after each attempt
+pub const REINIT_INITIAL_BACKOFF_MILLIS: u64 = 250;
+
+/// Maximum delay between attempts to re-initialize a device, devices that do not recover
+/// are eventually quarantined
+pub const REINIT_MAX_BACKOFF_MILLIS: u64 = 5000;
+
+/// Number of failures within `QUARANTINE_WINDOW_SECS` after which a device is quarantined
+pub const QUARANTINE_MAX_FAILURES: u64 = 10;
+
+/// Time window in which the failures of a device are counted
+pub const QUARANTINE_WINDOW_SECS: u64 = 60;
+
+/// Delay before a quarantined device is retried for the first time, doubled after each failed retry
+pub const QUARANTINE_INITIAL_BACKOFF_SECS: u64 = 5;
+
+/// Maximum delay between retries of a quarantined device
+pub const QUARANTINE_MAX_BACKOFF_SECS: u64 = 600;
+
+/// Number of consecutive failed startups after which the daemon boots into safe mode
+pub const SAFE_MODE_THRESHOLD: u32 = 3;
+
+/// Uptime after which a startup is considered successful, and the crash-loop counter is reset
+pub const SAFE_MODE_STABLE_SECS: u64 = 60;
+
+/// Default delay before a held key starts to repeat, if a custom key repeat rate has been set
+pub const DEFAULT_KEY_REPEAT_DELAY_MILLIS: u64 = 500;
+
+/// Upper bound of the key debounce period
+pub const MAX_KEY_DEBOUNCE_MILLIS: u64 = 100;
+
+/// Upper bound of the custom key repeat rate, in repeats per second
+pub const MAX_KEY_REPEAT_RATE: u32 = 100;
+
+/// Lower bound of the factor that scales the animation speed of a zone
+pub const MIN_ZONE_SPEED: f64 = 0.1;
+
+/// Upper bound of the factor that scales the animation speed of a zone
+pub const MAX_ZONE_SPEED: f64 = 10.0;
+
+/// Number of seconds of profiling data that the span profiler keeps
+pub const MAX_PROFILE_HISTORY_SECS: u64 = 60;
+
+/// Delay between the key events of text that is typed by a macro
+pub const TEXT_ENTRY_KEY_DELAY_MILLIS: u64 = 5;
+
 /// Fade in on profile switch for n milliseconds
 pub const FADE_MILLIS: u64 = 1333;
 
+/// Duration of the transition of a named color scheme to new colors
+pub const COLOR_SCHEME_TRANSITION_MILLIS: u64 = 1000;
+
 /// The number of "pixels" on the canvas
 pub const CANVAS_SIZE: usize = 144 + 36;
 
 /// The width of the canvas
 pub const CANVAS_WIDTH: usize = 22 + 8;
 
+/// Number of frames that static profiles are rendered after they have faded in, before
+/// rendering is suspended
+pub const STATIC_PROFILE_SETTLE_FRAMES: usize = 3;
+
+/// The width of the part of the canvas that is covered by keyboards
+pub const CANVAS_KEYBOARD_WIDTH: usize = 22;
+
 /// The height of the canvas
 pub const CANVAS_HEIGHT: usize = 6;
 
+/// The width of the auxiliary display surface, used for devices with an LCD/OLED screen
+pub const DISPLAY_WIDTH: usize = 128;
+
+/// The height of the auxiliary display surface, used for devices with an LCD/OLED screen
+pub const DISPLAY_HEIGHT: usize = 64;
+
 /// The capacity of the buffer used for receiving audio samples
 pub const NET_BUFFER_CAPACITY: usize = 4096;
 
 /// Timeout for waiting on condition variables of Lua upcalls
 pub const TIMEOUT_CONDITION_MILLIS: u64 = 25;
 
+/// Max amount of time to wait for an injected input event to be processed by the main loop
+pub const INJECTED_EVENT_TIMEOUT_MILLIS: u64 = 1000;
+
+/// Max amount of time to wait for the release of the keys that are held down on the virtual
+/// devices, before the input devices are handed back to the OS
+pub const RELEASE_HELD_KEYS_TIMEOUT_MILLIS: u64 = 250;
+
+/// Max amount of time that the D-Bus method `SwitchProfileAndWait` waits for a profile switch
+pub const PROFILE_SWITCH_TIMEOUT_MILLIS: u64 = 10000;
+
+/// Number of completed profile switches that are remembered, for late waiters
+pub const PROFILE_SWITCH_HISTORY: usize = 16;
+
+/// Max number of idle Lua threads, that are kept around to speed up switching of profiles
+pub const LUA_VM_POOL_SIZE: usize = 8;
+
+/// Max. length of the names of OS threads in bytes, excluding the terminating NUL byte
+pub const MAX_THREAD_NAME_LEN: usize = 15;
+
+/// Default amount of time that all keys of a chord need to be held down, before it triggers
+pub const DEFAULT_CHORD_HOLD_MILLIS: u64 = 200;
+
+/// Default color (ARGB) of the keys that are inhibited while game mode is enabled
+pub const DEFAULT_GAME_MODE_COLOR: u32 = 0xff400000;
+
+/// Default global volume of sound effects, in percent
+pub const DEFAULT_SFX_VOLUME: i64 = 100;
+
+/// Default threshold of the noise gate of the microphone input in dBFS
+pub const DEFAULT_NOISE_GATE_THRESHOLD: i64 = -50;
+
+/// Time budget of the `on_input_filter` handlers of the Lua scripts, for each key event.
+/// If it is exceeded, input filters are bypassed for `INPUT_FILTER_BYPASS_MILLIS`
+pub const INPUT_FILTER_BUDGET_MILLIS: u64 = 8;
+
+/// Time span that input filters are bypassed for, after they exceeded their time budget
+pub const INPUT_FILTER_BYPASS_MILLIS: u64 = 5000;
+
 /// Max number of events that will be processed in each iteration of the main loop
 pub const MAX_EVENTS_PER_ITERATION: u64 = 128;
 
@@ -111,10 +302,75 @@ pub const EVENTS_UPCALL_RATE_LIMIT_MILLIS: u64 = 25;
 /// Amount of time that has to pass before we retry sending a command to the LED control device
 pub const DEVICE_SETTLE_MILLIS: u64 = 25;
 
+/// Amount of time without further udev events, before a burst of hotplug events is processed
+pub const HOTPLUG_DEBOUNCE_MILLIS: u64 = 1500;
+
+/// Timeout of a single poll of the udev monitor socket
+pub const HOTPLUG_POLL_TIMEOUT_MILLIS: i32 = 250;
+
+/// Amount of time that each of the RGB primaries is shown during a device self-test
+pub const SELF_TEST_PRIMARY_MILLIS: u64 = 500;
+
 /// Update sensors every n seconds
 /// It is recommended to use a prime number value here
 pub const SENSOR_UPDATE_TICKS: u64 = 19; // TARGET_FPS /* * 1 */;
 
+/// Distance the estimated pointer position moves per unit of relative mouse motion,
+/// relative to the size of the screen
+pub const POINTER_SENSITIVITY: f64 = 1.0 / 2000.0;
+
+/// Start pulling the estimated pointer position towards the center of the screen
+/// after the mouse has been idle for n milliseconds
+pub const POINTER_DECAY_DELAY_MILLIS: u64 = 3000;
+
+/// Half-life of the distance of the estimated pointer position to the center of the screen
+pub const POINTER_DECAY_HALF_LIFE_SECS: f64 = 2.0;
+
+/// Positions reported by a compositor sensor are considered stale after n milliseconds
+pub const POINTER_ABSOLUTE_TIMEOUT_MILLIS: u64 = 5000;
+
+/// Minimum amount of time between two consecutive reads of the hwmon sensors
+pub const HWMON_REFRESH_INTERVAL_MILLIS: u64 = 1000;
+
+/// The sysfs directory that contains the hwmon sensor devices
+pub const HWMON_SYSFS_PATH: &str = "/sys/class/hwmon/";
+
+/// Interval in which the states of the watched systemd units are refreshed in the background
+pub const UNIT_STATE_REFRESH_INTERVAL_MILLIS: u64 = 2000;
+
+/// Units whose state has not been read by any script for this long are no longer refreshed
+pub const UNIT_STATE_EXPIRY_SECS: u64 = 60;
+
+/// The sysfs directory that contains the power supplies, like e.g. AC adapters and batteries
+pub const POWER_SUPPLY_SYSFS_PATH: &str = "/sys/class/power_supply/";
+
+/// Check the power source every n milliseconds
+/// It is recommended to use a prime number value here
+pub const POWER_SOURCE_POLL_MILLIS: u64 = 1999;
+
+/// The sysfs directory that contains the display backlights of laptops
+pub const BACKLIGHT_SYSFS_PATH: &str = "/sys/class/backlight/";
+
+/// Check the level of the display backlight every n milliseconds
+/// It is recommended to use a prime number value here
+pub const BACKLIGHT_POLL_MILLIS: u64 = 499;
+
+/// The sysfs directory that contains the LEDs, like e.g. the Caps Lock LEDs of all keyboards
+pub const LEDS_SYSFS_PATH: &str = "/sys/class/leds/";
+
+/// Check the state of the keyboard lock LEDs every n milliseconds
+/// It is recommended to use a prime number value here
+pub const INDICATORS_POLL_MILLIS: u64 = 149;
+
+/// Lock states reported by a session sensor are considered stale after n milliseconds
+pub const INDICATORS_REPORT_TIMEOUT_MILLIS: u64 = 5000;
+
+/// Default color (ARGB) that the keys of active lock indicators are tinted with
+pub const DEFAULT_INDICATOR_OVERLAY_COLOR: u32 = 0x80ffffff;
+
+/// Names of the script parameters that select a gradient or color scheme
+pub const GRADIENT_PARAMETER_NAMES: &[&str] = &["stock_gradient"];
+
 /// Timeout value to use for D-Bus connections
 pub const DBUS_TIMEOUT_MILLIS: u32 = 250;
 
@@ -22,30 +22,50 @@
 use colorgrad::Color;
 use dbus::{ffidisp::BusType, ffidisp::Connection, ffidisp::NameFlag, message::SignalArgs};
 use dbus_tree::{
-    Access, MethodErr, Signal, {EmitsChangedSignal, Factory},
+    Access, Signal, {EmitsChangedSignal, Factory},
 };
-use flume::Sender;
+use flume::{Receiver, Sender};
 use log::*;
-use std::path::PathBuf;
-use std::sync::atomic::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::{
+    afk, aux_leds, batch, blending,
+    brightness_curve::{self, BrightnessCurve},
+    canvas_layout::{self, Placement},
     color_scheme::ColorScheme,
-    constants, hwdevices,
+    color_transitions, compositor, constants, device_id, device_init, frame_budget, game_mode,
+    hardware_mode, headsets, health, hwdevices, indicators, input_grab,
+    key_filter::{self, KeyFilterConfig},
+    pacing,
     plugins::{self, audio},
-    profiles, script,
+    power, privacy, profiler, profiles, quarantine, quick_actions, safe_mode, script,
+    scripting::inspect,
+    scripting::lint,
+    scripting::manifest::Manifest,
     scripting::parameters,
     scripting::parameters_util,
+    sdk_clients, standby,
+    transform::{self, CanvasTransform},
+    util, zone_controls,
 };
 
 /// D-Bus messages and signals that are processed by the main thread
 #[derive(Debug, Clone)]
 pub enum Message {
     SwitchSlot(usize),
-    SwitchProfile(PathBuf),
+
+    /// Switch to the profile, the switch is identified by a correlation id
+    SwitchProfile(PathBuf, u64),
 }
 
+/// The correlation id of the next profile switch that is requested via D-Bus
+static NEXT_PROFILE_SWITCH_ID: AtomicU64 = AtomicU64::new(1);
+
 pub type Result<T> = std::result::Result<T, eyre::Error>;
 
 #[derive(Debug, thiserror::Error)]
@@ -58,6 +78,12 @@ pub enum DbusApiError {
 
     #[error("Invalid parameter")]
     InvalidParameter {},
+
+    #[error("Invalid script index")]
+    InvalidScript {},
+
+    #[error("The device is busy")]
+    DeviceBusy {},
     // #[error("Operation not supported")]
     // OpNotSupported {},
 }
@@ -74,12 +100,24 @@ pub struct DeviceStatus {
 pub struct DbusApi {
     connection: Option<Arc<Connection>>,
 
+    /// Replies to method calls that completed on other threads, they are sent by the D-Bus thread
+    pending_replies: Receiver<dbus::Message>,
+
     active_slot_changed: Arc<Signal<()>>,
     active_profile_changed: Arc<Signal<()>>,
+    profile_switch_started: Arc<Signal<()>>,
+    profile_switch_completed: Arc<Signal<()>>,
     profiles_changed: Arc<Signal<()>>,
+    profile_assigned: Arc<Signal<()>>,
     brightness_changed: Arc<Signal<()>>,
+    game_mode_changed: Arc<Signal<()>>,
+    hardware_mode_changed: Arc<Signal<()>>,
+    afk_changed: Arc<Signal<()>>,
     device_status_changed: Arc<Signal<()>>,
     device_hotplug: Arc<Signal<()>>,
+    lock_state_changed: Arc<Signal<()>>,
+    layout_changed: Arc<Signal<()>>,
+    frame_rate_changed: Arc<Signal<()>>,
 }
 
 #[allow(dead_code)]
@@ -87,6 +125,10 @@ impl DbusApi {
     /// Initialize the D-Bus API
     pub fn new(dbus_tx: Sender<Message>) -> Result<Self> {
         let dbus_tx_clone = dbus_tx.clone();
+        let dbus_tx_clone2 = dbus_tx.clone();
+
+        let (reply_tx, pending_replies) = flume::unbounded();
+        let reply_tx_clone = reply_tx.clone();
 
         let c = Connection::get_private(BusType::System)?;
         c.register_name("org.eruption", NameFlag::ReplaceExisting as u32)?;
@@ -113,15 +155,50 @@ impl DbusApi {
         );
         let active_profile_changed_signal_clone = active_profile_changed_signal.clone();
 
+        let profile_switch_started_signal = Arc::new(
+            f.signal("ProfileSwitchStarted", ())
+                .sarg::<u64, _>("id")
+                .sarg::<String, _>("profile_file"),
+        );
+        let profile_switch_started_signal_clone = profile_switch_started_signal.clone();
+
+        let profile_switch_completed_signal = Arc::new(
+            f.signal("ProfileSwitchCompleted", ())
+                .sarg::<u64, _>("id")
+                .sarg::<String, _>("profile_file")
+                .sarg::<bool, _>("succeeded"),
+        );
+        let profile_switch_completed_signal_clone = profile_switch_completed_signal.clone();
+
         let profiles_changed_signal = Arc::new(f.signal("ProfilesChanged", ()));
         let profiles_changed_signal_clone = profiles_changed_signal.clone();
 
+        let profile_assigned_signal = Arc::new(
+            f.signal("ProfileAssigned", ())
+                .sarg::<u64, _>("slot")
+                .sarg::<String, _>("profile_file"),
+        );
+        let profile_assigned_signal_clone = profile_assigned_signal.clone();
+
         let brightness_changed_signal = Arc::new(
             f.signal("BrightnessChanged", ())
                 .sarg::<i64, _>("brightness"),
         );
         let brightness_changed_signal_clone = brightness_changed_signal.clone();
 
+        let game_mode_changed_signal =
+            Arc::new(f.signal("GameModeChanged", ()).sarg::<bool, _>("enabled"));
+        let game_mode_changed_signal_clone = game_mode_changed_signal.clone();
+
+        let hardware_mode_changed_signal = Arc::new(
+            f.signal("HardwareModeChanged", ())
+                .sarg::<bool, _>("enabled"),
+        );
+        let hardware_mode_changed_signal_clone = hardware_mode_changed_signal.clone();
+
+        let afk_changed_signal = Arc::new(f.signal("AfkChanged", ()).sarg::<bool, _>("enabled"));
+        let afk_changed_signal_clone = afk_changed_signal.clone();
+
         let device_status_changed_signal = Arc::new(
             f.signal("DeviceStatusChanged", ())
                 .sarg::<String, _>("status"),
@@ -134,6 +211,25 @@ impl DbusApi {
         );
         let device_hotplug_signal_clone = device_hotplug_signal.clone();
 
+        let lock_state_changed_signal =
+            Arc::new(
+                f.signal("LockStateChanged", ())
+                    .sarg::<(bool, bool, bool), _>("lock_state"),
+            );
+        let lock_state_changed_signal_clone = lock_state_changed_signal.clone();
+
+        let layout_changed_signal = Arc::new(
+            f.signal("LayoutChanged", ())
+                .sarg::<(u32, String), _>("layout"),
+        );
+        let layout_changed_signal_clone = layout_changed_signal.clone();
+
+        let frame_rate_changed_signal = Arc::new(
+            f.signal("FrameRateChanged", ())
+                .sarg::<(u64, bool), _>("frame_rate"),
+        );
+        let frame_rate_changed_signal_clone = frame_rate_changed_signal.clone();
+
         let active_slot_property = f
             .property::<u64, _>("ActiveSlot", ())
             .emits_changed(EmitsChangedSignal::Const)
@@ -144,7 +240,7 @@ impl DbusApi {
 
                     Ok(())
                 } else {
-                    Err(MethodErr::failed("Authentication failed"))
+                    Err(errors::not_authorized())
                 }
             });
 
@@ -162,9 +258,9 @@ impl DbusApi {
                         .map(|p| {
                             i.append(&*p.profile_file.to_string_lossy());
                         })
-                        .ok_or_else(|| MethodErr::failed("Method failed"))
+                        .ok_or_else(|| errors::invalid_profile("No profile is active"))
                 } else {
-                    Err(MethodErr::failed("Authentication failed"))
+                    Err(errors::not_authorized())
                 }
             });
 
@@ -181,7 +277,7 @@ impl DbusApi {
 
                     Ok(())
                 } else {
-                    Err(MethodErr::failed("Authentication failed"))
+                    Err(errors::not_authorized())
                 }
             })
             .on_set(|i, m| {
@@ -191,12 +287,260 @@ impl DbusApi {
 
                     Ok(())
                 } else {
-                    Err(MethodErr::failed("Authentication failed"))
+                    Err(errors::not_authorized())
                 }
             });
 
         let enable_sfx_property_clone = Arc::new(enable_sfx_property);
 
+        let sfx_volume_property = f
+            .property::<i64, _>("SfxVolume", ())
+            .emits_changed(EmitsChangedSignal::True)
+            .access(Access::ReadWrite)
+            .auto_emit_on_set(true)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(audio::SFX_VOLUME.load(Ordering::SeqCst));
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            })
+            .on_set(|i, m| {
+                if perms::has_settings_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false)
+                {
+                    let volume = i.read::<i64>()?;
+
+                    if !(0..=100).contains(&volume) {
+                        return Err(errors::invalid_argument("volume"));
+                    }
+
+                    audio::SFX_VOLUME.store(volume, Ordering::SeqCst);
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let sfx_volume_property_clone = Arc::new(sfx_volume_property);
+
+        let audio_source_property = f
+            .property::<String, _>("AudioSource", ())
+            .emits_changed(EmitsChangedSignal::True)
+            .access(Access::ReadWrite)
+            .auto_emit_on_set(true)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(audio::get_audio_source().to_string());
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            })
+            .on_set(|i, m| {
+                if perms::has_settings_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false)
+                {
+                    let source = i
+                        .read::<String>()?
+                        .parse::<audio::AudioSource>()
+                        .map_err(|_e| errors::invalid_argument("source"))?;
+
+                    audio::set_audio_source(source);
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let audio_source_property_clone = Arc::new(audio_source_property);
+
+        let noise_gate_threshold_property = f
+            .property::<i64, _>("NoiseGateThreshold", ())
+            .emits_changed(EmitsChangedSignal::True)
+            .access(Access::ReadWrite)
+            .auto_emit_on_set(true)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(audio::NOISE_GATE_THRESHOLD.load(Ordering::SeqCst));
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            })
+            .on_set(|i, m| {
+                if perms::has_settings_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false)
+                {
+                    let threshold = i.read::<i64>()?;
+
+                    if !(-96..=0).contains(&threshold) {
+                        return Err(errors::invalid_argument("threshold"));
+                    }
+
+                    audio::set_noise_gate_threshold(threshold);
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let noise_gate_threshold_property_clone = Arc::new(noise_gate_threshold_property);
+
+        let power_saving_property = f
+            .property::<String, _>("PowerSaving", ())
+            .emits_changed(EmitsChangedSignal::True)
+            .access(Access::ReadWrite)
+            .auto_emit_on_set(true)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(power::get_power_saving_mode().to_string());
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            })
+            .on_set(|i, m| {
+                if perms::has_settings_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false)
+                {
+                    let mode = i
+                        .read::<String>()?
+                        .parse::<power::PowerSavingMode>()
+                        .map_err(|_e| errors::invalid_argument("mode"))?;
+
+                    power::set_power_saving_mode(mode);
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let power_saving_property_clone = Arc::new(power_saving_property);
+
+        let power_source_property = f
+            .property::<String, _>("PowerSource", ())
+            .emits_changed(EmitsChangedSignal::False)
+            .access(Access::Read)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(power::get_power_source().to_string());
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let power_source_property_clone = Arc::new(power_source_property);
+
+        let game_mode_property = f
+            .property::<bool, _>("GameMode", ())
+            .emits_changed(EmitsChangedSignal::True)
+            .access(Access::ReadWrite)
+            .auto_emit_on_set(true)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(game_mode::is_active());
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            })
+            .on_set(|i, m| {
+                if perms::has_settings_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false)
+                {
+                    game_mode::set_active(i.read::<bool>()?);
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let game_mode_property_clone = Arc::new(game_mode_property);
+
+        let hardware_mode_property = f
+            .property::<bool, _>("HardwareMode", ())
+            .emits_changed(EmitsChangedSignal::True)
+            .access(Access::ReadWrite)
+            .auto_emit_on_set(true)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(hardware_mode::is_active());
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            })
+            .on_set(|i, m| {
+                if perms::has_settings_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false)
+                {
+                    hardware_mode::set_active(i.read::<bool>()?);
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let hardware_mode_property_clone = Arc::new(hardware_mode_property);
+
+        let afk_property = f
+            .property::<bool, _>("Afk", ())
+            .emits_changed(EmitsChangedSignal::False)
+            .access(Access::Read)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(afk::is_active());
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let afk_property_clone = Arc::new(afk_property);
+
+        let afk_timeout_property = f
+            .property::<u64, _>("AfkTimeout", ())
+            .emits_changed(EmitsChangedSignal::False)
+            .access(Access::Read)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(afk::get_timeout_secs());
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let afk_timeout_property_clone = Arc::new(afk_timeout_property);
+
+        let afk_time_remaining_property = f
+            .property::<u64, _>("AfkTimeRemaining", ())
+            .emits_changed(EmitsChangedSignal::False)
+            .access(Access::Read)
+            .on_get(|i, m| {
+                if perms::has_monitor_permission_cached(&m.msg.sender().unwrap()).unwrap_or(false) {
+                    i.append(afk::get_time_remaining_secs());
+
+                    Ok(())
+                } else {
+                    Err(errors::not_authorized())
+                }
+            });
+
+        let afk_time_remaining_property_clone = Arc::new(afk_time_remaining_property);
+
         let brightness_property = f
             .property::<i64, _>("Brightness", ())
             .emits_changed(EmitsChangedSignal::True)
@@ -209,7 +553,7 @@ impl DbusApi {
 
                     Ok(())
                 } else {
-                    Err(MethodErr::failed("Authentication failed"))
+                    Err(errors::not_authorized())
                 }
             })
             .on_set(|i, m| {
@@ -220,7 +564,7 @@ impl DbusApi {
 
                     Ok(())
                 } else {
-                    Err(MethodErr::failed("Authentication failed"))
+                    Err(errors::not_authorized())
                 }
             });
 
@@ -250,14 +594,14 @@ impl DbusApi {
                         })
                         .collect::<Vec<DeviceStatus>>();
 
-                    let result = serde_json::to_string_pretty(&device_status)
-                        .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+                    let result =
+                        serde_json::to_string_pretty(&device_status).map_err(errors::from_error)?;
 
                     i.append(result);
 
                     Ok(())
                 } else {
-                    Err(MethodErr::failed("Authentication failed"))
+                    Err(errors::not_authorized())
                 }
             });
 
@@ -270,6 +614,7 @@ impl DbusApi {
                     .introspectable()
                     .add(
                         f.interface("org.eruption.Status", ())
+                            .add_s(frame_rate_changed_signal_clone)
                             .add_p(
                                 f.property::<bool, _>("Running", ())
                                     .emits_changed(EmitsChangedSignal::True)
@@ -282,7 +627,7 @@ impl DbusApi {
                                             i.append(true);
                                             Ok(())
                                         } else {
-                                            Err(MethodErr::failed("Authentication failed"))
+                                            Err(errors::not_authorized())
                                         }
                                     })
                                     .on_set(|i, m| {
@@ -298,7 +643,7 @@ impl DbusApi {
 
                                             Ok(())
                                         } else {
-                                            Err(MethodErr::failed("Authentication failed"))
+                                            Err(errors::not_authorized())
                                         }
                                     }),
                             )
@@ -317,11 +662,86 @@ impl DbusApi {
 
                                         Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
                                 .outarg::<Vec<(u8, u8, u8, u8)>, _>("values"),
                             )
+                            .add_m(
+                                f.method("GetFrameRate", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let result = (
+                                            frame_budget::get_target_fps(),
+                                            frame_budget::is_degraded(),
+                                        );
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<(u64, bool), _>("frame_rate"),
+                            )
+                            .add_m(
+                                f.method("GetSafeMode", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let result =
+                                            serde_json::to_string_pretty(&safe_mode::get_status())
+                                                .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<String, _>("status"),
+                            )
+                            .add_m(
+                                f.method("ExitSafeMode", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let s = safe_mode::exit();
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("DumpProfile", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let seconds: u64 = m.msg.read1()?;
+
+                                        let s = profiler::dump_profile(seconds).map_err(|e| {
+                                            errors::failed(format!(
+                                                "Error dumping the profile: {}",
+                                                e
+                                            ))
+                                        })?;
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("seconds")
+                                .outarg::<String, _>("stacks"),
+                            )
                             // .add_m(
                             //     f.method("SetLedColors", (), move |m| {
                             //         *crate::LAST_DBUS_EVENT_TIME.lock() = Instant::now();
@@ -337,7 +757,7 @@ impl DbusApi {
                                     .unwrap_or(false)
                                     {
                                         if crate::QUIT.load(Ordering::SeqCst) {
-                                            return Err(MethodErr::failed(
+                                            return Err(errors::failed(
                                                 "Eruption is shutting down",
                                             ));
                                         }
@@ -386,7 +806,7 @@ impl DbusApi {
                                             .method_return()
                                             .append1((keyboards, mice, misc))])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
                                 .outarg::<(
@@ -396,25 +816,64 @@ impl DbusApi {
                                 ), _>(
                                     "values"
                                 ),
-                            ),
-                    ),
-            )
-            .add(
-                f.object_path("/org/eruption/devices", ())
-                    .introspectable()
-                    .add(
-                        f.interface("org.eruption.Device", ())
-                            .add_s(device_status_changed_signal_clone)
-                            .add_s(device_hotplug_signal_clone)
+                            )
                             .add_m(
-                                f.method("SetDeviceConfig", (), move |m| {
-                                    if perms::has_settings_permission_cached(
+                                f.method("GetSdkClients", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        let (device, param, value): (u64, String, String) =
-                                            m.msg.read3()?;
+                                        let result = serde_json::to_string_pretty(
+                                            &sdk_clients::get_clients(),
+                                        )
+                                        .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<String, _>("clients"),
+                            )
+                            .add_m(
+                                f.method("KickSdkClient", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let id: u64 = m.msg.read1()?;
+
+                                        sdk_clients::kick_client(id).map_err(errors::from_error)?;
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("id")
+                                .outarg::<bool, _>("status"),
+                            ),
+                    ),
+            )
+            .add(
+                f.object_path("/org/eruption/devices", ())
+                    .introspectable()
+                    .add(
+                        f.interface("org.eruption.Device", ())
+                            .add_s(device_status_changed_signal_clone)
+                            .add_s(device_hotplug_signal_clone)
+                            .add_m(
+                                f.method("SetDeviceConfig", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, param, value): (u64, String, String) =
+                                            m.msg.read3()?;
 
                                         debug!(
                                             "Setting device [{}] config parameter '{}' to '{}'",
@@ -422,11 +881,13 @@ impl DbusApi {
                                         );
 
                                         apply_device_specific_configuration(device, &param, &value)
-                                            .map_err(|_e| MethodErr::invalid_arg(&param))?;
+                                            .map_err(|e| {
+                                                errors::from_error_or_invalid_argument(e, &param)
+                                            })?;
 
                                         Ok(vec![m.msg.method_return().append1(true)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
                                 .inarg::<u64, _>("device")
@@ -451,11 +912,15 @@ impl DbusApi {
 
                                         let result =
                                             query_device_specific_configuration(device, &param)
-                                                .map_err(|_e| MethodErr::invalid_arg(&param))?;
+                                                .map_err(|e| {
+                                                    errors::from_error_or_invalid_argument(
+                                                        e, &param,
+                                                    )
+                                                })?;
 
                                         Ok(vec![m.msg.method_return().append1(result)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
                                 .inarg::<u64, _>("device")
@@ -463,260 +928,1169 @@ impl DbusApi {
                                 .outarg::<String, _>("value"),
                             )
                             .add_m(
-                                f.method("GetDeviceStatus", (), move |m| {
-                                    if perms::has_monitor_permission_cached(
+                                f.method("GetPollRate", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        trace!("Querying device [{}] poll rate", device);
+
+                                        let result =
+                                            query_poll_rate(device).map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<i32, _>("rate"),
+                            )
+                            .add_m(
+                                f.method("SetPollRate", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, rate): (u64, i32) = m.msg.read2()?;
+
+                                        debug!(
+                                            "Setting device [{}] poll rate to {} Hz",
+                                            device, rate
+                                        );
+
+                                        apply_poll_rate(device, rate)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<i32, _>("rate")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetDeviceStatus", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        trace!("Querying device [{}] status", device);
+
+                                        let result = query_device_specific_status(device)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<String, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetDeviceHealth", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        trace!("Querying device health statistics");
+
+                                        let result =
+                                            query_device_health().map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<String, _>("health"),
+                            )
+                            .add_m(
+                                f.method("GetDeviceInitStatus", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        trace!("Querying the initialization status of all devices");
+
+                                        let result = query_device_init_status()
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<String, _>("status"),
+                            )
+                            .add_m(
+                                f.method("ResetDeviceHealth", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        info!("Resetting device health statistics");
+
+                                        health::reset_device_health();
+                                        quarantine::release_all();
+
+                                        health::save_device_health().unwrap_or_else(|e| {
+                                            error!("Could not save device health statistics: {}", e)
+                                        });
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("RunSelfTest", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        info!("Running self-test of device [{}]", device);
+
+                                        claim_device_self_test(device)
+                                            .map_err(errors::from_error)?;
+
+                                        // the self-test takes several seconds, run it on a separate
+                                        // thread, so that the D-Bus thread stays responsive
+                                        let reply = m.msg.method_return();
+                                        let call = m.msg.duplicate().map_err(errors::failed)?;
+
+                                        let reply_tx = reply_tx_clone.clone();
+
+                                        thread::Builder::new()
+                                            .name("dbus-self-test".into())
+                                            .spawn(move || {
+                                                let reply = match run_device_self_test(device) {
+                                                    Ok(report) => reply.append1(report),
+                                                    Err(e) => {
+                                                        errors::from_error(e).to_message(&call)
+                                                    }
+                                                };
+
+                                                reply_tx.send(reply).unwrap_or_else(|e| {
+                                                    error!("Could not send a D-Bus reply: {}", e)
+                                                });
+                                            })
+                                            .map_err(|e| {
+                                                crate::DEVICE_SELF_TEST_ACTIVE
+                                                    .store(false, Ordering::SeqCst);
+
+                                                errors::failed(e)
+                                            })?;
+
+                                        Ok(vec![])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<String, _>("report"),
+                            )
+                            .add_m(
+                                f.method("GetAuxLeds", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        trace!("Querying device [{}] auxiliary LEDs", device);
+
+                                        let result =
+                                            query_aux_leds(device).map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<String, _>("aux_leds"),
+                            )
+                            .add_m(
+                                f.method("GetHeadsets", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        trace!("Querying the state of the headsets");
+
+                                        let result =
+                                            serde_json::to_string_pretty(&headsets::get_headsets())
+                                                .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<String, _>("headsets"),
+                            )
+                            .add_m(
+                                f.method("SetAuxLed", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, name, color): (u64, String, u32) =
+                                            m.msg.read3()?;
+
+                                        debug!(
+                                            "Setting device [{}] auxiliary LED '{}' to #{:06x}",
+                                            device, &name, color
+                                        );
+
+                                        apply_aux_led(device, &name, color)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<String, _>("name")
+                                .inarg::<u32, _>("color")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetInputGrab", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        trace!("Querying device [{}] input grab", device);
+
+                                        let result = with_device(device, |device| {
+                                            Ok(input_grab::is_device_grabbed(device))
+                                        })
+                                        .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<bool, _>("grabbed"),
+                            )
+                            .add_m(
+                                f.method("SetInputGrab", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, grab): (u64, bool) = m.msg.read2()?;
+
+                                        debug!(
+                                            "Setting device [{}] input grab to {}",
+                                            device, grab
+                                        );
+
+                                        with_device(device, |device| {
+                                            input_grab::set_device_grabbed(device, grab)
+                                        })
+                                        .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<bool, _>("grab")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetManagedDevices", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        if crate::QUIT.load(Ordering::SeqCst) {
+                                            return Err(errors::failed(
+                                                "Eruption is shutting down",
+                                            ));
+                                        }
+
+                                        let keyboards = {
+                                            let keyboards = crate::KEYBOARD_DEVICES.read();
+
+                                            let keyboards: Vec<(u16, u16)> = keyboards
+                                                .iter()
+                                                .map(|device| {
+                                                    (
+                                                        device.read().get_usb_vid(),
+                                                        device.read().get_usb_pid(),
+                                                    )
+                                                })
+                                                .collect();
+
+                                            keyboards
+                                        };
+
+                                        let mice = {
+                                            let mice = crate::MOUSE_DEVICES.read();
+
+                                            let mice: Vec<(u16, u16)> = mice
+                                                .iter()
+                                                .map(|device| {
+                                                    (
+                                                        device.read().get_usb_vid(),
+                                                        device.read().get_usb_pid(),
+                                                    )
+                                                })
+                                                .collect();
+
+                                            mice
+                                        };
+
+                                        let misc = {
+                                            let misc = crate::MISC_DEVICES.read();
+
+                                            let misc: Vec<(u16, u16)> = misc
+                                                .iter()
+                                                .map(|device| {
+                                                    (
+                                                        device.read().get_usb_vid(),
+                                                        device.read().get_usb_pid(),
+                                                    )
+                                                })
+                                                .collect();
+
+                                            misc
+                                        };
+
+                                        Ok(vec![m
+                                            .msg
+                                            .method_return()
+                                            .append1((keyboards, mice, misc))])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<(
+                                    Vec<(u16, u16)>,
+                                    Vec<(u16, u16)>,
+                                    Vec<(u16, u16)>,
+                                ), _>(
+                                    "values"
+                                ),
+                            )
+                            .add_m(
+                                f.method("GetDeviceIds", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let result = device_id::get_device_ids();
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<Vec<String>, _>("ids"),
+                            )
+                            .add_m(
+                                f.method("FindDevice", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let id: String = m.msg.read1()?;
+
+                                        let index =
+                                            device_id::find_device_index(&id).ok_or_else(|| {
+                                                errors::invalid_device(format!(
+                                                    "No device with the ID {} is connected",
+                                                    id
+                                                ))
+                                            })?;
+
+                                        Ok(vec![m.msg.method_return().append1(index)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("id")
+                                .outarg::<u64, _>("device"),
+                            )
+                            .add_m(
+                                f.method("GetCanvasLayout", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        trace!("Querying the canvas layout");
+
+                                        let result =
+                                            query_canvas_layout().map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<String, _>("layout"),
+                            )
+                            .add_m(
+                                f.method("SetCanvasTiling", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let enable: bool = m.msg.read1()?;
+
+                                        info!("Setting canvas tiling: {}", enable);
+
+                                        canvas_layout::set_tiling(enable)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<bool, _>("enable")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("SetCanvasPlacement", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, x, y): (u64, i32, i32) = m.msg.read3()?;
+
+                                        info!(
+                                            "Moving device [{}] to {}:{} on the canvas",
+                                            device, x, y
+                                        );
+
+                                        set_canvas_placement(device, Placement { x, y })
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<i32, _>("x")
+                                .inarg::<i32, _>("y")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("ResetCanvasLayout", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        info!("Resetting the canvas layout");
+
+                                        canvas_layout::reset_placements()
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_p(device_status_property_clone),
+                    ),
+            )
+            .add(
+                f.object_path("/org/eruption/config", ())
+                    .introspectable()
+                    .add(
+                        f.interface("org.eruption.Config", ())
+                            .add_s(brightness_changed_signal_clone)
+                            .add_s(game_mode_changed_signal_clone)
+                            .add_s(hardware_mode_changed_signal_clone)
+                            .add_s(afk_changed_signal_clone)
+                            .add_s(lock_state_changed_signal_clone)
+                            .add_s(layout_changed_signal_clone)
+                            .add_p(enable_sfx_property_clone)
+                            .add_p(sfx_volume_property_clone)
+                            .add_p(audio_source_property_clone)
+                            .add_p(noise_gate_threshold_property_clone)
+                            .add_p(power_saving_property_clone)
+                            .add_p(power_source_property_clone)
+                            .add_p(brightness_property_clone)
+                            .add_p(game_mode_property_clone)
+                            .add_p(hardware_mode_property_clone)
+                            .add_p(afk_property_clone)
+                            .add_p(afk_timeout_property_clone)
+                            .add_p(afk_time_remaining_property_clone)
+                            .add_m(
+                                f.method("WriteFile", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let (filename, data): (String, String) = m.msg.read2()?;
+
+                                        crate::util::write_file(&PathBuf::from(filename), &data)
+                                            .map_err(|e| {
+                                                errors::failed(format!("Error writing file: {}", e))
+                                            })?;
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("filename")
+                                .inarg::<String, _>("data")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("ExecuteBatch", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let operations: &str = m.msg.read1()?;
+
+                                        let applied = batch::execute(operations)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(applied as u64)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<&str, _>("operations")
+                                .outarg::<u64, _>("applied"),
+                            )
+                            .add_m(
+                                f.method("Ping", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("PingPrivileged", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetColorSchemes", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let color_schemes: Vec<String> = crate::NAMED_COLOR_SCHEMES
+                                            .read()
+                                            .keys()
+                                            .cloned()
+                                            .collect();
+
+                                        Ok(vec![m.msg.method_return().append1(color_schemes)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<Vec<String>, _>("color_schemes"),
+                            )
+                            .add_m(
+                                f.method("GetColorScheme", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let name: String = m.msg.read1()?;
+
+                                        let data = query_color_scheme(&name)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(data)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("name")
+                                .outarg::<Vec<u8>, _>("data"),
+                            )
+                            .add_m(
+                                f.method("SetColorScheme", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (name, data): (String, Vec<u8>) = m.msg.read2()?;
+
+                                        if !is_valid_color_scheme_name(&name) {
+                                            Err(errors::invalid_argument("name"))
+                                        } else {
+                                            color_transitions::set_color_scheme(
+                                                &name,
+                                                parse_color_scheme(&data),
+                                            );
+
+                                            let s = true;
+                                            Ok(vec![m.msg.method_return().append1(s)])
+                                        }
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("name")
+                                .inarg::<Vec<u8>, _>("data")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("RemoveColorScheme", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let name: String = m.msg.read1()?;
+
+                                        color_transitions::cancel(&name);
+
+                                        let s = crate::NAMED_COLOR_SCHEMES
+                                            .write()
+                                            .remove(&name)
+                                            .is_some();
+
+                                        if s {
+                                            crate::REQUEST_PROFILE_RELOAD
+                                                .store(true, Ordering::SeqCst);
+                                        }
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("name")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("PreviewColorScheme", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (name, data): (String, Vec<u8>) = m.msg.read2()?;
+
+                                        if !is_valid_color_scheme_name(&name) {
+                                            Err(errors::invalid_argument("name"))
+                                        } else {
+                                            parameters_util::preview_color_scheme(
+                                                &name,
+                                                parse_color_scheme(&data),
+                                            )
+                                            .map_err(
+                                                |e| {
+                                                    errors::failed(format!(
+                                                        "Could not preview color scheme: {}",
+                                                        e
+                                                    ))
+                                                },
+                                            )?;
+
+                                            let s = true;
+                                            Ok(vec![m.msg.method_return().append1(s)])
+                                        }
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("name")
+                                .inarg::<Vec<u8>, _>("data")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("CommitColorScheme", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let name: String = m.msg.read1()?;
+
+                                        let s = parameters_util::commit_color_scheme(&name)
+                                            .map_err(|e| {
+                                                errors::failed(format!(
+                                                    "Could not commit color scheme: {}",
+                                                    e
+                                                ))
+                                            })?;
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("name")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("RollbackColorScheme", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let name: String = m.msg.read1()?;
+
+                                        let s = parameters_util::rollback_color_scheme(&name)
+                                            .map_err(|e| {
+                                                errors::failed(format!(
+                                                    "Could not roll back color scheme: {}",
+                                                    e
+                                                ))
+                                            })?;
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("name")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("SetPointerPosition", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (x, y): (f64, f64) = m.msg.read2()?;
+
+                                        if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+                                            return Err(errors::invalid_argument("position"));
+                                        }
+
+                                        plugins::pointer::set_absolute_pointer_position(x, y);
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<f64, _>("x")
+                                .inarg::<f64, _>("y")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetKeyboardIndicators", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let lock_state = indicators::get_lock_state();
+                                        let layout = indicators::get_keyboard_layout();
+
+                                        Ok(vec![m.msg.method_return().append3(
+                                            (
+                                                lock_state.caps_lock,
+                                                lock_state.num_lock,
+                                                lock_state.scroll_lock,
+                                            ),
+                                            layout.group,
+                                            layout.name,
+                                        )])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<(bool, bool, bool), _>("lock_state")
+                                .outarg::<u32, _>("group")
+                                .outarg::<String, _>("layout"),
+                            )
+                            .add_m(
+                                f.method("SetKeyboardIndicators", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let ((caps_lock, num_lock, scroll_lock), group, name): (
+                                            (bool, bool, bool),
+                                            u32,
+                                            String,
+                                        ) = m.msg.read3()?;
+
+                                        indicators::report_state(
+                                            indicators::LockState {
+                                                caps_lock,
+                                                num_lock,
+                                                scroll_lock,
+                                            },
+                                            indicators::KeyboardLayout { group, name },
+                                        );
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<(bool, bool, bool), _>("lock_state")
+                                .inarg::<u32, _>("group")
+                                .inarg::<String, _>("layout")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetHardwareState", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let state = plugins::laptop::get_hardware_state();
+
+                                        Ok(vec![m
+                                            .msg
+                                            .method_return()
+                                            .append2(state.lid_closed, state.external_displays)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<bool, _>("lid_closed")
+                                .outarg::<Vec<String>, _>("displays"),
+                            )
+                            .add_m(
+                                f.method("SetHardwareState", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (lid_closed, external_displays): (bool, Vec<String>) =
+                                            m.msg.read2()?;
+
+                                        plugins::laptop::set_hardware_state(
+                                            plugins::laptop::HardwareState {
+                                                lid_closed,
+                                                external_displays,
+                                            },
+                                        );
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<bool, _>("lid_closed")
+                                .inarg::<Vec<String>, _>("displays")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("SetActiveWindow", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (class, title, app_id): (String, String, String) =
+                                            m.msg.read3()?;
+
+                                        plugins::window::set_active_window(
+                                            plugins::window::ActiveWindow {
+                                                class,
+                                                title,
+                                                app_id,
+                                            },
+                                        );
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("class")
+                                .inarg::<String, _>("title")
+                                .inarg::<String, _>("app_id")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("ListQuickActions", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let s = quick_actions::list_actions()
+                                            .iter()
+                                            .map(|action| {
+                                                (
+                                                    action.id(),
+                                                    action.label(),
+                                                    action.category().to_string(),
+                                                )
+                                            })
+                                            .collect::<Vec<(String, String, String)>>();
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<Vec<(String, String, String)>, _>("actions"),
+                            )
+                            .add_m(
+                                f.method("InvokeQuickAction", (), move |m| {
+                                    if perms::has_settings_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        let device: u64 = m.msg.read1()?;
+                                        let id: String = m.msg.read1()?;
 
-                                        trace!("Querying device [{}] status", device);
-
-                                        let result = query_device_specific_status(device)
-                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+                                        quick_actions::invoke(&id).map_err(errors::from_error)?;
 
-                                        Ok(vec![m.msg.method_return().append1(result)])
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
-                                .inarg::<u64, _>("device")
-                                .outarg::<String, _>("status"),
+                                .inarg::<String, _>("id")
+                                .outarg::<bool, _>("status"),
                             )
                             .add_m(
-                                f.method("GetManagedDevices", (), move |m| {
+                                f.method("GetPrivacyMode", (), move |m| {
                                     if perms::has_monitor_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        if crate::QUIT.load(Ordering::SeqCst) {
-                                            return Err(MethodErr::failed(
-                                                "Eruption is shutting down",
-                                            ));
-                                        }
-
-                                        let keyboards = {
-                                            let keyboards = crate::KEYBOARD_DEVICES.read();
-
-                                            let keyboards: Vec<(u16, u16)> = keyboards
-                                                .iter()
-                                                .map(|device| {
-                                                    (
-                                                        device.read().get_usb_vid(),
-                                                        device.read().get_usb_pid(),
-                                                    )
-                                                })
-                                                .collect();
-
-                                            keyboards
-                                        };
-
-                                        let mice = {
-                                            let mice = crate::MOUSE_DEVICES.read();
-
-                                            let mice: Vec<(u16, u16)> = mice
-                                                .iter()
-                                                .map(|device| {
-                                                    (
-                                                        device.read().get_usb_vid(),
-                                                        device.read().get_usb_pid(),
-                                                    )
-                                                })
-                                                .collect();
-
-                                            mice
-                                        };
-
-                                        let misc = {
-                                            let misc = crate::MISC_DEVICES.read();
-
-                                            let misc: Vec<(u16, u16)> = misc
-                                                .iter()
-                                                .map(|device| {
-                                                    (
-                                                        device.read().get_usb_vid(),
-                                                        device.read().get_usb_pid(),
-                                                    )
-                                                })
-                                                .collect();
-
-                                            misc
-                                        };
+                                        let zones = privacy::get_zones();
 
                                         Ok(vec![m
                                             .msg
                                             .method_return()
-                                            .append1((keyboards, mice, misc))])
+                                            .append2(zones.is_some(), zones.unwrap_or_default())])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
-                                .outarg::<(
-                                    Vec<(u16, u16)>,
-                                    Vec<(u16, u16)>,
-                                    Vec<(u16, u16)>,
-                                ), _>(
-                                    "values"
-                                ),
+                                .outarg::<bool, _>("enabled")
+                                .outarg::<Vec<String>, _>("zones"),
                             )
-                            .add_p(device_status_property_clone),
-                    ),
-            )
-            .add(
-                f.object_path("/org/eruption/config", ())
-                    .introspectable()
-                    .add(
-                        f.interface("org.eruption.Config", ())
-                            .add_s(brightness_changed_signal_clone)
-                            .add_p(enable_sfx_property_clone)
-                            .add_p(brightness_property_clone)
                             .add_m(
-                                f.method("WriteFile", (), move |m| {
-                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
-                                        .unwrap_or(false)
+                                f.method("SetPrivacyMode", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
                                     {
-                                        let (filename, data): (String, String) = m.msg.read2()?;
+                                        let (enable, zones): (bool, Vec<String>) = m.msg.read2()?;
 
-                                        crate::util::write_file(&PathBuf::from(filename), &data)
-                                            .map_err(|e| {
-                                                MethodErr::failed(&format!(
-                                                    "Error writing file: {}",
-                                                    e
-                                                ))
-                                            })?;
+                                        if enable {
+                                            privacy::enable(&zones).map_err(errors::from_error)?;
+                                        } else {
+                                            privacy::disable();
+                                        }
 
                                         let s = true;
                                         Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
-                                .inarg::<String, _>("filename")
-                                .inarg::<String, _>("data")
+                                .inarg::<bool, _>("enable")
+                                .inarg::<Vec<String>, _>("zones")
                                 .outarg::<bool, _>("status"),
                             )
                             .add_m(
-                                f.method("Ping", (), move |m| {
-                                    if perms::has_monitor_permission_cached(
+                                f.method("SetCanvasLayer", (), move |m| {
+                                    if perms::has_settings_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
+                                        let (layer, colors, alpha, timeout_millis): (
+                                            String,
+                                            Vec<u32>,
+                                            f64,
+                                            u64,
+                                        ) = m.msg.read4()?;
+
+                                        let priority = layer
+                                            .parse::<compositor::Priority>()
+                                            .map_err(|_e| errors::invalid_argument("layer"))?;
+
+                                        // colors are specified as ARGB, a single color
+                                        // fills the whole canvas
+                                        let colors = if colors.len() == 1 {
+                                            vec![colors[0]; constants::CANVAS_SIZE]
+                                        } else {
+                                            colors
+                                        };
+
+                                        let led_map = colors
+                                            .iter()
+                                            .map(|color| hwdevices::RGBA {
+                                                r: ((color >> 16) & 0xff) as u8,
+                                                g: ((color >> 8) & 0xff) as u8,
+                                                b: (color & 0xff) as u8,
+                                                a: ((color >> 24) & 0xff) as u8,
+                                            })
+                                            .collect();
+
+                                        let timeout = if timeout_millis > 0 {
+                                            Some(Duration::from_millis(timeout_millis))
+                                        } else {
+                                            None
+                                        };
+
+                                        compositor::set_layer(priority, led_map, alpha, timeout)
+                                            .map_err(errors::from_error)?;
+
                                         let s = true;
                                         Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
+                                .inarg::<String, _>("layer")
+                                .inarg::<Vec<u32>, _>("colors")
+                                .inarg::<f64, _>("alpha")
+                                .inarg::<u64, _>("timeout_millis")
                                 .outarg::<bool, _>("status"),
                             )
                             .add_m(
-                                f.method("PingPrivileged", (), move |m| {
-                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
-                                        .unwrap_or(false)
+                                f.method("ClearCanvasLayer", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
                                     {
-                                        let s = true;
+                                        let layer: String = m.msg.read1()?;
+
+                                        let priority = layer
+                                            .parse::<compositor::Priority>()
+                                            .map_err(|_e| errors::invalid_argument("layer"))?;
+
+                                        let s = compositor::clear_layer(priority);
                                         Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
+                                .inarg::<String, _>("layer")
                                 .outarg::<bool, _>("status"),
                             )
                             .add_m(
-                                f.method("GetColorSchemes", (), move |m| {
-                                    if perms::has_monitor_permission_cached(
+                                f.method("SetAfk", (), move |m| {
+                                    if perms::has_settings_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        let color_schemes: Vec<String> = crate::NAMED_COLOR_SCHEMES
-                                            .read()
-                                            .keys()
-                                            .cloned()
-                                            .collect();
+                                        let enable: bool = m.msg.read1()?;
 
-                                        Ok(vec![m.msg.method_return().append1(color_schemes)])
+                                        let s = afk::set_active(enable);
+                                        Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
-                                .outarg::<Vec<String>, _>("color_schemes"),
+                                .inarg::<bool, _>("enable")
+                                .outarg::<bool, _>("status"),
                             )
                             .add_m(
-                                f.method("SetColorScheme", (), move |m| {
+                                f.method("Standby", (), move |m| {
                                     if perms::has_settings_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        let (name, data): (String, Vec<u8>) = m.msg.read2()?;
-
-                                        if name.chars().take(1).all(char::is_numeric)
-                                            || !name.chars().all(|c| {
-                                                c == '_' || char::is_ascii_alphanumeric(&c)
-                                            })
-                                        {
-                                            Err(MethodErr::failed("Invalid identifier name"))
-                                        } else {
-                                            let mut color_schemes =
-                                                crate::NAMED_COLOR_SCHEMES.write();
-                                            let mut colors = Vec::new();
-
-                                            for chunk in data.chunks(4) {
-                                                let r = chunk[0];
-                                                let g = chunk[1];
-                                                let b = chunk[2];
-                                                let a = chunk[3];
-
-                                                let color = Color::from_linear_rgba8(r, g, b, a);
-
-                                                colors.push(color);
-                                            }
-
-                                            color_schemes.insert(name, ColorScheme { colors });
-
-                                            crate::REQUEST_PROFILE_RELOAD
-                                                .store(true, Ordering::SeqCst);
-
-                                            let s = true;
-                                            Ok(vec![m.msg.method_return().append1(s)])
-                                        }
+                                        let s = standby::set_active(true);
+                                        Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
-                                .inarg::<String, _>("name")
-                                .inarg::<Vec<u8>, _>("data")
                                 .outarg::<bool, _>("status"),
                             )
                             .add_m(
-                                f.method("RemoveColorScheme", (), move |m| {
+                                f.method("Resume", (), move |m| {
                                     if perms::has_settings_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        let name: String = m.msg.read1()?;
-
-                                        let s = crate::NAMED_COLOR_SCHEMES
-                                            .write()
-                                            .remove(&name)
-                                            .is_some();
-
-                                        if s {
-                                            crate::REQUEST_PROFILE_RELOAD
-                                                .store(true, Ordering::SeqCst);
-                                        }
-
+                                        let s = standby::set_active(false);
                                         Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
-                                .inarg::<String, _>("name")
                                 .outarg::<bool, _>("status"),
                             ),
                     ),
@@ -727,6 +2101,7 @@ impl DbusApi {
                     .add(
                         f.interface("org.eruption.Slot", ())
                             .add_s(active_slot_changed_signal_clone)
+                            .add_s(profile_assigned_signal_clone)
                             .add_p(active_slot_property_clone.clone())
                             .add_m(
                                 f.method("SwitchSlot", (), move |m| {
@@ -738,7 +2113,7 @@ impl DbusApi {
                                         let n: u64 = m.msg.read1()?;
 
                                         if n as usize >= constants::NUM_SLOTS {
-                                            Err(MethodErr::failed("Slot index out of bounds"))
+                                            Err(errors::invalid_argument("slot"))
                                         } else {
                                             dbus_tx
                                                 .send(Message::SwitchSlot(n as usize))
@@ -769,7 +2144,7 @@ impl DbusApi {
                                             Ok(vec![m.msg.method_return().append1(s)])
                                         }
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
                                 .inarg::<u64, _>("slot")
@@ -792,11 +2167,98 @@ impl DbusApi {
 
                                         Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
                                 .outarg::<Vec<String>, _>("values"),
                             )
+                            .add_m(
+                                f.method("GetSlotBlend", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let slot: u64 = m.msg.read1()?;
+
+                                        let (profile_file, weight) =
+                                            match blending::get_slot_blend(slot as usize) {
+                                                Some(blend) => (
+                                                    blend.profile.to_string_lossy().to_string(),
+                                                    blend.weight,
+                                                ),
+
+                                                None => (String::new(), 0.0),
+                                            };
+
+                                        Ok(vec![m
+                                            .msg
+                                            .method_return()
+                                            .append2(profile_file, weight)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("slot")
+                                .outarg::<String, _>("profile_file")
+                                .outarg::<f64, _>("weight"),
+                            )
+                            .add_m(
+                                f.method("SetSlotBlend", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (slot, profile_file, weight): (u64, &str, f64) =
+                                            m.msg.read3()?;
+
+                                        // an empty profile file removes the blend of the slot
+                                        let profile_file = Some(Path::new(profile_file))
+                                            .filter(|p| !p.as_os_str().is_empty());
+
+                                        blending::set_slot_blend(
+                                            slot as usize,
+                                            profile_file,
+                                            weight,
+                                        )
+                                        .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("slot")
+                                .inarg::<&str, _>("profile_file")
+                                .inarg::<f64, _>("weight")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("FadeBlendWeight", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (weight, duration_millis): (f64, u64) =
+                                            m.msg.read2()?;
+
+                                        blending::fade_to(
+                                            weight,
+                                            Duration::from_millis(duration_millis),
+                                        )
+                                        .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<f64, _>("weight")
+                                .inarg::<u64, _>("duration_millis")
+                                .outarg::<bool, _>("status"),
+                            )
                             .add_p(
                                 f.property::<Vec<String>, _>("SlotNames", ())
                                     .access(Access::ReadWrite)
@@ -813,7 +2275,7 @@ impl DbusApi {
 
                                             Ok(())
                                         } else {
-                                            Err(MethodErr::failed("Authentication failed"))
+                                            Err(errors::not_authorized())
                                         }
                                     })
                                     .on_set(|i, m| {
@@ -829,10 +2291,10 @@ impl DbusApi {
 
                                                 Ok(())
                                             } else {
-                                                Err(MethodErr::failed("Invalid number of elements"))
+                                                Err(errors::invalid_argument("slot names"))
                                             }
                                         } else {
-                                            Err(MethodErr::failed("Authentication failed"))
+                                            Err(errors::not_authorized())
                                         }
                                     }),
                             ),
@@ -845,6 +2307,8 @@ impl DbusApi {
                         f.interface("org.eruption.Profile", ())
                             .add_s(profiles_changed_signal_clone)
                             .add_s(active_profile_changed_signal_clone)
+                            .add_s(profile_switch_started_signal_clone)
+                            .add_s(profile_switch_completed_signal_clone)
                             .add_p(active_profile_property_clone.clone())
                             .add_m(
                                 f.method("SwitchProfile", (), move |m| {
@@ -855,8 +2319,14 @@ impl DbusApi {
                                     {
                                         let n: &str = m.msg.read1()?;
 
+                                        profiles::Profile::load_file_only(Path::new(n))
+                                            .map_err(errors::from_error)?;
+
                                         dbus_tx_clone
-                                            .send(Message::SwitchProfile(PathBuf::from(n)))
+                                            .send(Message::SwitchProfile(
+                                                PathBuf::from(n),
+                                                next_profile_switch_id(),
+                                            ))
                                             .unwrap_or_else(|e| {
                                                 error!(
                                                     "Could not send a pending D-Bus event: {}",
@@ -874,22 +2344,80 @@ impl DbusApi {
                                             || Box::new(n.to_owned()),
                                         );
 
-                                        if !changed_properties.is_empty() {
-                                            let msg = changed_properties
-                                                .first()
-                                                .unwrap()
-                                                .to_emit_message(&"/org/eruption/profile".into());
-                                            c_clone3.clone().send(msg).unwrap();
-                                        }
+                                        if !changed_properties.is_empty() {
+                                            let msg = changed_properties
+                                                .first()
+                                                .unwrap()
+                                                .to_emit_message(&"/org/eruption/profile".into());
+                                            c_clone3.clone().send(msg).unwrap();
+                                        }
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<&str, _>("filename")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("SwitchProfileAndWait", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let n: &str = m.msg.read1()?;
+
+                                        profiles::Profile::load_file_only(Path::new(n))
+                                            .map_err(errors::from_error)?;
+
+                                        let id = next_profile_switch_id();
+
+                                        dbus_tx_clone2
+                                            .send(Message::SwitchProfile(PathBuf::from(n), id))
+                                            .map_err(errors::failed)?;
 
-                                        let s = true;
-                                        Ok(vec![m.msg.method_return().append1(s)])
+                                        // reset the audio backend, it will be enabled again if needed
+                                        plugins::audio::reset_audio_backend();
+
+                                        // wait for the switch on a separate thread, so that the
+                                        // D-Bus thread stays responsive; the reply is sent later on
+                                        let reply = m.msg.method_return();
+                                        let timeout_reply =
+                                            errors::failed("Timeout while switching profiles")
+                                                .to_message(m.msg);
+
+                                        let reply_tx = reply_tx.clone();
+
+                                        thread::Builder::new()
+                                            .name("dbus-switch".into())
+                                            .spawn(move || {
+                                                let reply = match wait_for_profile_switch(
+                                                    id,
+                                                    Duration::from_millis(
+                                                        constants::PROFILE_SWITCH_TIMEOUT_MILLIS,
+                                                    ),
+                                                ) {
+                                                    Some(succeeded) => reply.append2(id, succeeded),
+                                                    None => timeout_reply,
+                                                };
+
+                                                reply_tx.send(reply).unwrap_or_else(|e| {
+                                                    error!("Could not send a D-Bus reply: {}", e)
+                                                });
+                                            })
+                                            .map_err(errors::failed)?;
+
+                                        Ok(vec![])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
                                 .inarg::<&str, _>("filename")
-                                .outarg::<bool, _>("status"),
+                                .outarg::<u64, _>("id")
+                                .outarg::<bool, _>("succeeded"),
                             )
                             .add_m(
                                 f.method("EnumProfiles", (), move |m| {
@@ -916,7 +2444,7 @@ impl DbusApi {
 
                                         Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
                                 .outarg::<Vec<(String, String)>, _>("profiles"),
@@ -950,11 +2478,13 @@ impl DbusApi {
                                             Ok(()) => Ok(vec![m.msg.method_return().append1(true)]),
                                             Err(err) => {
                                                 debug!("Could not set parameter: {}", err);
-                                                Err(MethodErr::invalid_arg(&value))
+                                                Err(errors::from_error_or_invalid_argument(
+                                                    err, value,
+                                                ))
                                             }
                                         }
                                     } else {
-                                        Err(MethodErr::failed("Authentication failed"))
+                                        Err(errors::not_authorized())
                                     }
                                 })
                                 .inarg::<&str, _>("profile_file")
@@ -962,6 +2492,206 @@ impl DbusApi {
                                 .inarg::<&str, _>("param_name")
                                 .inarg::<&str, _>("value")
                                 .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetParameterMetadata", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let script_file: &str = m.msg.read1()?;
+
+                                        trace!("Querying parameter metadata of {}", &script_file);
+
+                                        let result = query_parameter_metadata(script_file)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<&str, _>("script_file")
+                                .outarg::<String, _>("metadata"),
+                            )
+                            .add_m(
+                                f.method("GetScriptMetadata", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let script_file: &str = m.msg.read1()?;
+
+                                        trace!("Querying script metadata of {}", &script_file);
+
+                                        let result = query_script_metadata(script_file)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<&str, _>("script_file")
+                                .outarg::<String, _>("metadata"),
+                            )
+                            .add_m(
+                                f.method("InspectScript", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let index: u64 = m.msg.read1()?;
+
+                                        trace!("Inspecting script [{}]", index);
+
+                                        let result = query_script_inspection(index)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<u64, _>("index")
+                                .outarg::<String, _>("inspection"),
+                            )
+                            .add_m(
+                                f.method("LintScript", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (file_name, script, manifest): (&str, &str, &str) =
+                                            m.msg.read3()?;
+
+                                        debug!("Linting script {}", file_name);
+
+                                        let result = query_script_lint(file_name, script, manifest)
+                                            .map_err(errors::from_error)?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<&str, _>("file_name")
+                                .inarg::<&str, _>("script")
+                                .inarg::<&str, _>("manifest")
+                                .outarg::<String, _>("diagnostics"),
+                            )
+                            .add_m(
+                                f.method("ApplyNamedColorScheme", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (profile_file, scheme_name, persist): (
+                                            &str,
+                                            &str,
+                                            bool,
+                                        ) = m.msg.read3()?;
+
+                                        debug!(
+                                            "Applying color scheme '{}' to {} (persist: {})",
+                                            &scheme_name, &profile_file, persist
+                                        );
+
+                                        let applied = parameters_util::apply_color_scheme(
+                                            profile_file,
+                                            scheme_name,
+                                            persist,
+                                        );
+                                        match applied {
+                                            Ok(()) => Ok(vec![m.msg.method_return().append1(true)]),
+                                            Err(err) => {
+                                                debug!("Could not apply color scheme: {}", err);
+                                                Err(errors::failed(format!(
+                                                    "Could not apply color scheme: {}",
+                                                    err
+                                                )))
+                                            }
+                                        }
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<&str, _>("profile_file")
+                                .inarg::<&str, _>("scheme_name")
+                                .inarg::<bool, _>("persist")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetZoneControls", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let controls = zone_controls::get_zone_controls()
+                                            .into_iter()
+                                            .map(|(name, control)| {
+                                                (name, control.brightness, control.speed)
+                                            })
+                                            .collect::<Vec<(String, i32, f64)>>();
+
+                                        Ok(vec![m.msg.method_return().append1(controls)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .outarg::<Vec<(String, i32, f64)>, _>("controls"),
+                            )
+                            .add_m(
+                                f.method("SetZoneControl", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (zone, brightness, speed): (String, i32, f64) =
+                                            m.msg.read3()?;
+
+                                        zone_controls::set_zone_control(
+                                            &zone,
+                                            profiles::ZoneControl { brightness, speed },
+                                        )
+                                        .map_err(errors::from_error)?;
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("zone")
+                                .inarg::<i32, _>("brightness")
+                                .inarg::<f64, _>("speed")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("ResetZoneControl", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let zone: String = m.msg.read1()?;
+
+                                        let s = zone_controls::reset_zone_control(&zone)
+                                            .map_err(errors::from_error)?;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(errors::not_authorized())
+                                    }
+                                })
+                                .inarg::<String, _>("zone")
+                                .outarg::<bool, _>("status"),
                             ),
                     ),
             );
@@ -972,12 +2702,22 @@ impl DbusApi {
 
         Ok(Self {
             connection: Some(c_clone),
+            pending_replies,
             active_slot_changed: active_slot_changed_signal,
             active_profile_changed: active_profile_changed_signal,
+            profile_switch_started: profile_switch_started_signal,
+            profile_switch_completed: profile_switch_completed_signal,
             profiles_changed: profiles_changed_signal,
+            profile_assigned: profile_assigned_signal,
             brightness_changed: brightness_changed_signal,
+            game_mode_changed: game_mode_changed_signal,
+            hardware_mode_changed: hardware_mode_changed_signal,
+            afk_changed: afk_changed_signal,
             device_status_changed: device_status_changed_signal,
             device_hotplug: device_hotplug_signal,
+            lock_state_changed: lock_state_changed_signal,
+            layout_changed: layout_changed_signal,
+            frame_rate_changed: frame_rate_changed_signal,
         })
     }
 
@@ -998,8 +2738,7 @@ impl DbusApi {
             })
             .collect::<Vec<DeviceStatus>>();
 
-        let result = serde_json::to_string_pretty(&device_status)
-            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+        let result = serde_json::to_string_pretty(&device_status).map_err(errors::from_error)?;
 
         let _ = self
             .connection
@@ -1030,6 +2769,61 @@ impl DbusApi {
         Ok(())
     }
 
+    pub fn notify_lock_state_changed(&self) -> Result<()> {
+        let lock_state = indicators::get_lock_state();
+
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(self.lock_state_changed.emit(
+                &"/org/eruption/config".into(),
+                &"org.eruption.Config".into(),
+                &[(
+                    lock_state.caps_lock,
+                    lock_state.num_lock,
+                    lock_state.scroll_lock,
+                )],
+            ))
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
+    pub fn notify_layout_changed(&self) -> Result<()> {
+        let layout = indicators::get_keyboard_layout();
+
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(self.layout_changed.emit(
+                &"/org/eruption/config".into(),
+                &"org.eruption.Config".into(),
+                &[(layout.group, layout.name)],
+            ))
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
+    pub fn notify_frame_rate_changed(&self) -> Result<()> {
+        let frame_rate = (frame_budget::get_target_fps(), frame_budget::is_degraded());
+
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(self.frame_rate_changed.emit(
+                &"/org/eruption/status".into(),
+                &"org.eruption.Status".into(),
+                &[frame_rate],
+            ))
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
     pub fn notify_brightness_changed(&self) -> Result<()> {
         let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
 
@@ -1047,6 +2841,57 @@ impl DbusApi {
         Ok(())
     }
 
+    pub fn notify_game_mode_changed(&self) -> Result<()> {
+        let enabled = game_mode::is_active();
+
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(self.game_mode_changed.emit(
+                &"/org/eruption/config".into(),
+                &"org.eruption.Config".into(),
+                &[enabled],
+            ))
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
+    pub fn notify_hardware_mode_changed(&self) -> Result<()> {
+        let enabled = hardware_mode::is_active();
+
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(self.hardware_mode_changed.emit(
+                &"/org/eruption/config".into(),
+                &"org.eruption.Config".into(),
+                &[enabled],
+            ))
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
+    pub fn notify_afk_changed(&self) -> Result<()> {
+        let enabled = afk::is_active();
+
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(self.afk_changed.emit(
+                &"/org/eruption/config".into(),
+                &"org.eruption.Config".into(),
+                &[enabled],
+            ))
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
     pub fn notify_active_slot_changed(&self) -> Result<()> {
         let active_slot = crate::ACTIVE_SLOT.load(Ordering::SeqCst);
 
@@ -1064,6 +2909,21 @@ impl DbusApi {
         Ok(())
     }
 
+    pub fn notify_profile_assigned(&self, slot: usize, profile_file: &Path) -> Result<()> {
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(
+                self.profile_assigned
+                    .msg(&"/org/eruption/slot".into(), &"org.eruption.Slot".into())
+                    .append2(slot as u64, profile_file.to_string_lossy().to_string()),
+            )
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
     pub fn notify_active_profile_changed(&self) -> Result<()> {
         let active_profile = crate::ACTIVE_PROFILE.lock();
 
@@ -1088,6 +2948,47 @@ impl DbusApi {
         Ok(())
     }
 
+    pub fn notify_profile_switch_started(&self, id: u64, profile_file: &Path) -> Result<()> {
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(
+                self.profile_switch_started
+                    .msg(
+                        &"/org/eruption/profile".into(),
+                        &"org.eruption.Profile".into(),
+                    )
+                    .append2(id, profile_file.to_string_lossy().to_string()),
+            )
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
+    pub fn notify_profile_switch_completed(
+        &self,
+        id: u64,
+        profile_file: &Path,
+        succeeded: bool,
+    ) -> Result<()> {
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(
+                self.profile_switch_completed
+                    .msg(
+                        &"/org/eruption/profile".into(),
+                        &"org.eruption.Profile".into(),
+                    )
+                    .append3(id, profile_file.to_string_lossy().to_string(), succeeded),
+            )
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
     pub fn notify_profiles_changed(&self) -> Result<()> {
         let _ = self
             .connection
@@ -1136,8 +3037,24 @@ impl DbusApi {
                 }
             }
 
-            None => Err(DbusApiError::BusNotConnected {}.into()),
+            None => Err(DbusApiError::BusNotConnected {}.into()),
+        }
+    }
+
+    /// Send the replies to method calls that completed on other threads
+    pub fn send_pending_replies(&self) -> Result<()> {
+        let connection = self
+            .connection
+            .as_ref()
+            .ok_or(DbusApiError::BusNotConnected {})?;
+
+        for reply in self.pending_replies.try_iter() {
+            let _ = connection
+                .send(reply)
+                .map_err(|_| error!("D-Bus error during send call"));
         }
+
+        Ok(())
     }
 
     pub fn get_next_event_timeout(&self, timeout_ms: u32) -> Result<bool> {
@@ -1166,6 +3083,46 @@ pub fn initialize(dbus_tx: Sender<Message>) -> Result<DbusApi> {
     DbusApi::new(dbus_tx)
 }
 
+/// Returns the correlation id of the next profile switch
+pub fn next_profile_switch_id() -> u64 {
+    NEXT_PROFILE_SWITCH_ID.fetch_add(1, Ordering::SeqCst)
+}
+
+/// Record the result of the profile switch `id`, and wake up the threads that wait for it
+pub fn signal_profile_switch_completed(id: u64, succeeded: bool) {
+    let mut completed = crate::PROFILE_SWITCHING_COMPLETED_CONDITION.0.lock();
+
+    completed.push_back((id, succeeded));
+
+    while completed.len() > constants::PROFILE_SWITCH_HISTORY {
+        completed.pop_front();
+    }
+
+    crate::PROFILE_SWITCHING_COMPLETED_CONDITION.1.notify_all();
+}
+
+/// Wait until the profile switch `id` has completed. Returns whether it succeeded, or `None`
+/// on timeout
+fn wait_for_profile_switch(id: u64, timeout: Duration) -> Option<bool> {
+    let deadline = Instant::now() + timeout;
+
+    let mut completed = crate::PROFILE_SWITCHING_COMPLETED_CONDITION.0.lock();
+
+    loop {
+        if let Some((_, succeeded)) = completed.iter().find(|(i, _)| *i == id) {
+            return Some(*succeeded);
+        }
+
+        if crate::PROFILE_SWITCHING_COMPLETED_CONDITION
+            .1
+            .wait_until(&mut completed, deadline)
+            .timed_out()
+        {
+            return None;
+        }
+    }
+}
+
 fn apply_parameter(
     profile_file: &str,
     script_file: &str,
@@ -1182,14 +3139,338 @@ fn apply_parameter(
     )
 }
 
-/// Query the device specific status from the global status store
+/// Returns `true` if `name` may be used as the name of a color scheme
+fn is_valid_color_scheme_name(name: &str) -> bool {
+    !name.chars().take(1).all(char::is_numeric)
+        && name
+            .chars()
+            .all(|c| c == '_' || char::is_ascii_alphanumeric(&c))
+}
+
+/// Parse a color scheme from a sequence of RGBA components
+fn parse_color_scheme(data: &[u8]) -> ColorScheme {
+    let colors = data
+        .chunks_exact(4)
+        .map(|c| Color::from_linear_rgba8(c[0], c[1], c[2], c[3]))
+        .collect();
+
+    ColorScheme { colors }
+}
+
+/// Query the colors of a named color scheme or a stock gradient, returns the
+/// colors as a sequence of RGBA components
+fn query_color_scheme(name: &str) -> Result<Vec<u8>> {
+    if let Some(color_scheme) = crate::NAMED_COLOR_SCHEMES.read().get(name) {
+        return Ok(color_scheme
+            .colors
+            .iter()
+            .flat_map(|c| {
+                let (r, g, b, a) = c.to_linear_rgba_u8();
+                [r, g, b, a]
+            })
+            .collect());
+    }
+
+    let gradient = match name {
+        "rainbow-smooth" => colorgrad::rainbow(),
+        "sinebow-smooth" => colorgrad::sinebow(),
+        "spectral-smooth" => colorgrad::spectral(),
+        "rainbow-sharp" => colorgrad::rainbow().sharp(5, 0.15),
+        "sinebow-sharp" => colorgrad::sinebow().sharp(5, 0.15),
+        "spectral-sharp" => colorgrad::spectral().sharp(5, 0.15),
+
+        _ => return Err(DbusApiError::InvalidParameter {}.into()),
+    };
+
+    Ok(gradient
+        .colors(constants::GRADIENT_SAMPLE_COUNT)
+        .iter()
+        .flat_map(|c| {
+            let (r, g, b, a) = c.to_linear_rgba_u8();
+            [r, g, b, a]
+        })
+        .collect())
+}
+
+/// Query the declared parameters of a script, including their types and constraints,
+/// returns the metadata in JSON format
+fn query_parameter_metadata(script_file: &str) -> Result<String> {
+    let script_file = util::resolve_script_path(&script_file)?;
+    let manifest = Manifest::load(&script_file)?;
+
+    Ok(serde_json::to_string_pretty(&manifest.config)?)
+}
+
+/// Query the metadata of a script, like e.g. its author, license and preview hints,
+/// returns the metadata in JSON format
+fn query_script_metadata(script_file: &str) -> Result<String> {
+    let script_file = util::resolve_script_path(&script_file)?;
+    let manifest = Manifest::load(&script_file)?;
+
+    Ok(serde_json::to_string_pretty(&manifest.get_metadata())?)
+}
+
+/// Query the debug information of the script `index` of the active profile,
+/// returns the information in JSON format
+fn query_script_inspection(index: u64) -> Result<String> {
+    // the entries of scripts of previously active profiles may linger around
+    if index as usize >= crate::LUA_TXS.read().len() {
+        return Err(DbusApiError::InvalidScript {}.into());
+    }
+
+    let inspection =
+        inspect::get_inspection(index as usize).ok_or(DbusApiError::InvalidScript {})?;
+
+    Ok(serde_json::to_string_pretty(&inspection)?)
+}
+
+/// Lint a Lua script and its manifest, an empty `manifest` denotes a script without one
+fn query_script_lint(file_name: &str, script: &str, manifest: &str) -> Result<String> {
+    let manifest = Some(manifest).filter(|m| !m.is_empty());
+    let diagnostics = lint::lint_script(file_name, script, manifest)?;
+
+    Ok(serde_json::to_string_pretty(&diagnostics)?)
+}
+
+/// Query the device specific status from the global status store, the
+/// frame pacing statistics of the device are merged into the result
 fn query_device_specific_status(device: u64) -> Result<String> {
     let device_status = crate::DEVICE_STATUS.as_ref().lock();
+    let pacing_stats = pacing::get_stats(device);
+
+    let mut status = match (device_status.get(&device), pacing_stats) {
+        (Some(status), _) => status.0.clone(),
+        (None, Some(_)) => HashMap::new(),
+        (None, None) => return Err(DbusApiError::InvalidDevice {}.into()),
+    };
+
+    if let Some((max_update_rate, stats)) = pacing_stats {
+        if let Some(max_update_rate) = max_update_rate {
+            status.insert(
+                "max-update-rate".to_string(),
+                format!("{}", max_update_rate),
+            );
+        }
+
+        status.insert("frames-sent".to_string(), format!("{}", stats.frames_sent));
+        status.insert(
+            "frames-deferred".to_string(),
+            format!("{}", stats.frames_deferred),
+        );
+        status.insert(
+            "frames-dropped".to_string(),
+            format!("{}", stats.frames_dropped),
+        );
+    }
+
+    Ok(serde_json::to_string_pretty(&status)?)
+}
+
+/// Failure statistics of a device, as returned by `GetDeviceHealth`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeviceHealthStatus {
+    /// The stable identifier of the device, see `device_id::device_id()`
+    pub device: String,
+
+    /// The index of the device, if it is currently connected
+    pub index: Option<u64>,
+
+    pub health: health::DeviceHealth,
+
+    /// The thresholds that the device exceeded
+    pub warnings: Vec<String>,
+
+    /// Set if the device has been quarantined, because it failed too often
+    pub quarantine: Option<quarantine::QuarantineStatus>,
+}
+
+/// Query the failure statistics of all devices that have been seen so far,
+/// returns the statistics in JSON format
+fn query_device_health() -> Result<String> {
+    let mut indices = HashMap::new();
+    let mut index = 0;
 
-    match device_status.get(&device) {
-        Some(status) => Ok(serde_json::to_string_pretty(&status.0)?),
-        None => Err(DbusApiError::InvalidDevice {}.into()),
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        indices.insert(device_id::device_id(device.read().as_device()), index);
+        index += 1;
     }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        indices.insert(device_id::device_id(device.read().as_device()), index);
+        index += 1;
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        indices.insert(device_id::device_id(device.read().as_device()), index);
+        index += 1;
+    }
+
+    let result = health::DEVICE_HEALTH
+        .read()
+        .iter()
+        .map(|(key, health)| DeviceHealthStatus {
+            device: key.clone(),
+            index: indices.get(key).copied(),
+            health: health.clone(),
+            warnings: health.warnings(),
+            quarantine: quarantine::get_status(key),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::to_string_pretty(&result)?)
+}
+
+/// Initialization status of a connected device, as returned by `GetDeviceInitStatus`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeviceInitStatus {
+    /// The stable identifier of the device, see `device_id::device_id()`
+    pub device: String,
+
+    pub index: u64,
+
+    /// `None` if the device has not been initialized yet
+    pub status: Option<device_init::InitStatus>,
+}
+
+/// Query the initialization status of all connected devices, returns the status in JSON format
+fn query_device_init_status() -> Result<String> {
+    let mut ids = vec![];
+
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        ids.push(device_id::device_id(device.read().as_device()));
+    }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        ids.push(device_id::device_id(device.read().as_device()));
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        ids.push(device_id::device_id(device.read().as_device()));
+    }
+
+    let result = ids
+        .into_iter()
+        .enumerate()
+        .map(|(index, id)| DeviceInitStatus {
+            status: device_init::get_status(&id),
+            device: id,
+            index: index as u64,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::to_string_pretty(&result)?)
+}
+
+/// Returns the arrangement of the keyboards on the shared canvas, in JSON format
+fn query_canvas_layout() -> Result<String> {
+    let layout = canvas_layout::get_layout(&crate::KEYBOARD_DEVICES.read());
+
+    Ok(serde_json::to_string_pretty(&layout)?)
+}
+
+/// Move the keyboard with the index `device` to `placement` on the shared canvas
+fn set_canvas_placement(device: u64, placement: Placement) -> Result<()> {
+    let keyboards = crate::KEYBOARD_DEVICES.read();
+
+    let device = keyboards
+        .get(device as usize)
+        .ok_or(DbusApiError::InvalidDevice {})?;
+
+    let id = device_id::device_id(device.read().as_device());
+
+    canvas_layout::set_placement(&id, Some(placement))
+}
+
+/// Validate the device index `device`, and suspend rendering for the self-test of the
+/// device. Only a single self-test may run at a time
+fn claim_device_self_test(device: u64) -> Result<()> {
+    let num_devices = crate::KEYBOARD_DEVICES.read().len()
+        + crate::MOUSE_DEVICES.read().len()
+        + crate::MISC_DEVICES.read().len();
+
+    if device as usize >= num_devices {
+        return Err(DbusApiError::InvalidDevice {}.into());
+    }
+
+    if standby::is_active()
+        || crate::DEVICE_SELF_TEST_ACTIVE
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+    {
+        return Err(DbusApiError::DeviceBusy {}.into());
+    }
+
+    Ok(())
+}
+
+/// Run the self-test of a device that has been claimed by `claim_device_self_test`,
+/// returns the report in JSON format. Rendering is resumed afterwards
+fn run_device_self_test(device: u64) -> Result<String> {
+    fn driver_self_test(
+        device: &mut dyn hwdevices::DeviceTrait,
+        report: &mut hwdevices::SelfTestReport,
+    ) {
+        if let Err(e) = device.self_test(report) {
+            match e.downcast_ref::<hwdevices::HwDeviceError>() {
+                Some(hwdevices::HwDeviceError::OpNotSupported {}) => {
+                    debug!("The device driver does not provide a self-test")
+                }
+
+                _ => report.errors.push(format!("Driver self-test failed: {e}")),
+            }
+        }
+    }
+
+    fn self_test(device: usize) -> Result<hwdevices::SelfTestReport> {
+        let mut report = hwdevices::SelfTestReport::default();
+
+        // give a frame that may currently be in flight the chance to complete
+        thread::sleep(Duration::from_millis(constants::DEVICE_SETTLE_MILLIS));
+
+        let num_keyboards = crate::KEYBOARD_DEVICES.read().len();
+        let num_mice = crate::MOUSE_DEVICES.read().len();
+
+        // devices may have been unplugged in the meantime
+        if device < num_keyboards {
+            let device = crate::KEYBOARD_DEVICES
+                .read()
+                .get(device)
+                .cloned()
+                .ok_or(DbusApiError::InvalidDevice {})?;
+
+            hwdevices::self_test_leds(&mut report, |led_map| device.write().send_led_map(led_map));
+            driver_self_test(device.write().as_device_mut(), &mut report);
+        } else if device < num_keyboards + num_mice {
+            let device = crate::MOUSE_DEVICES
+                .read()
+                .get(device - num_keyboards)
+                .cloned()
+                .ok_or(DbusApiError::InvalidDevice {})?;
+
+            hwdevices::self_test_leds(&mut report, |led_map| device.write().send_led_map(led_map));
+            driver_self_test(device.write().as_device_mut(), &mut report);
+        } else {
+            let device = crate::MISC_DEVICES
+                .read()
+                .get(device - (num_keyboards + num_mice))
+                .cloned()
+                .ok_or(DbusApiError::InvalidDevice {})?;
+
+            hwdevices::self_test_leds(&mut report, |led_map| device.write().send_led_map(led_map));
+            driver_self_test(device.write().as_device_mut(), &mut report);
+        }
+
+        Ok(report)
+    }
+
+    let result = self_test(device as usize);
+
+    crate::DEVICE_SELF_TEST_ACTIVE.store(false, Ordering::SeqCst);
+
+    // force a re-render of the current frame
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    Ok(serde_json::to_string_pretty(&result?)?)
 }
 
 /// Query the device driver for status information
@@ -1232,7 +3513,83 @@ fn query_device_specific_status(device: u64) -> Result<String> {
 //     Ok(json)
 // }
 
+/// Returns `true` if the devices may not be accessed right now, e.g. in standby
+/// or while a self-test is running
+fn is_device_io_suspended() -> bool {
+    standby::is_active() || crate::DEVICE_SELF_TEST_ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Run `f` on the device with the index `device`
+fn with_device<T>(
+    device: u64,
+    f: impl FnOnce(&mut dyn hwdevices::DeviceTrait) -> Result<T>,
+) -> Result<T> {
+    if is_device_io_suspended() {
+        return Err(DbusApiError::DeviceBusy {}.into());
+    }
+
+    let num_keyboards = crate::KEYBOARD_DEVICES.read().len();
+    let num_mice = crate::MOUSE_DEVICES.read().len();
+    let num_misc = crate::MISC_DEVICES.read().len();
+
+    let device = device as usize;
+
+    // the devices may have been removed in the meantime, so the indices are checked again
+    if device < num_keyboards {
+        let keyboards = crate::KEYBOARD_DEVICES.read();
+        let keyboard = keyboards
+            .get(device)
+            .ok_or(DbusApiError::InvalidDevice {})?;
+
+        f(keyboard.write().as_device_mut())
+    } else if device < num_keyboards + num_mice {
+        let mice = crate::MOUSE_DEVICES.read();
+        let mouse = mice
+            .get(device - num_keyboards)
+            .ok_or(DbusApiError::InvalidDevice {})?;
+
+        f(mouse.write().as_device_mut())
+    } else if device < num_keyboards + num_mice + num_misc {
+        let misc_devices = crate::MISC_DEVICES.read();
+        let misc_device = misc_devices
+            .get(device - (num_keyboards + num_mice))
+            .ok_or(DbusApiError::InvalidDevice {})?;
+
+        f(misc_device.write().as_device_mut())
+    } else {
+        Err(DbusApiError::InvalidDevice {}.into())
+    }
+}
+
+/// Query the auxiliary LEDs of a device, and the colors that have been set
+fn query_aux_leds(device: u64) -> Result<String> {
+    let aux_leds = with_device(device, |device| Ok(aux_leds::get_aux_leds(device)))?;
+
+    Ok(serde_json::to_string_pretty(&aux_leds)?)
+}
+
+/// Set the color of an auxiliary LED of a device
+fn apply_aux_led(device: u64, name: &str, color: u32) -> Result<()> {
+    with_device(device, |device| aux_leds::set_aux_led(device, name, color))
+}
+
+/// Query the USB polling rate in Hz of the mouse with the index `device`
+fn query_poll_rate(device: u64) -> Result<i32> {
+    let rate = query_device_specific_configuration(device, "rate")?.parse::<i32>()?;
+
+    Ok(rate)
+}
+
+/// Set the USB polling rate in Hz of the mouse with the index `device`
+fn apply_poll_rate(device: u64, rate: i32) -> Result<()> {
+    apply_device_specific_configuration(device, "rate", &format!("{}", rate))
+}
+
 fn apply_device_specific_configuration(device: u64, param: &str, value: &str) -> Result<()> {
+    if is_device_io_suspended() {
+        return Err(DbusApiError::DeviceBusy {}.into());
+    }
+
     if (device as usize) < crate::KEYBOARD_DEVICES.read().len() {
         let device = &crate::KEYBOARD_DEVICES.read()[device as usize];
 
@@ -1246,6 +3603,30 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
                 Ok(())
             }
 
+            "transform" => {
+                let transform = value.parse::<CanvasTransform>()?;
+                transform::set_transform(device.read().as_device(), transform)
+            }
+
+            "brightness-curve" => {
+                let curve = match value {
+                    "default" => None,
+                    _ => Some(value.parse::<BrightnessCurve>()?),
+                };
+
+                brightness_curve::set_curve(device.read().as_device(), curve)
+            }
+
+            "key-filter" => {
+                let config = value.parse::<KeyFilterConfig>()?;
+                key_filter::configure_key_filter(device.read().as_device(), config)
+            }
+
+            "key-filter-enabled" => {
+                let enabled = value.parse::<bool>()?;
+                key_filter::enable_key_filter(device.read().as_device(), enabled)
+            }
+
             _ => Err(DbusApiError::InvalidParameter {}.into()),
         }
     } else if (device as usize)
@@ -1271,6 +3652,10 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
 
             "rate" => {
                 let rate = value.parse::<i32>()?;
+
+                // reject unsupported rates before they reach the device
+                hwdevices::poll_rate_to_index(rate)?;
+
                 device.write().set_rate(rate)?;
 
                 Ok(())
@@ -1278,11 +3663,27 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
 
             "dcu" => {
                 let dcu_config = value.parse::<i32>()?;
+
+                // reject unsupported settings before they reach the device
+                hwdevices::dcu_config_to_byte(dcu_config)?;
+
                 device.write().set_dcu_config(dcu_config)?;
 
                 Ok(())
             }
 
+            "surface-calibration" => {
+                let step = match value {
+                    "start" => hwdevices::SurfaceCalibration::Start,
+                    "accept" => hwdevices::SurfaceCalibration::Accept,
+                    "cancel" => hwdevices::SurfaceCalibration::Cancel,
+
+                    _ => return Err(DbusApiError::InvalidParameter {}.into()),
+                };
+
+                device.write().calibrate_surface(step)
+            }
+
             "angle-snapping" => {
                 let angle_snapping = value.parse::<bool>()?;
                 device.write().set_angle_snapping(angle_snapping)?;
@@ -1306,6 +3707,20 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
                 Ok(())
             }
 
+            "transform" => {
+                let transform = value.parse::<CanvasTransform>()?;
+                transform::set_transform(device.read().as_device(), transform)
+            }
+
+            "brightness-curve" => {
+                let curve = match value {
+                    "default" => None,
+                    _ => Some(value.parse::<BrightnessCurve>()?),
+                };
+
+                brightness_curve::set_curve(device.read().as_device(), curve)
+            }
+
             _ => Err(DbusApiError::InvalidParameter {}.into()),
         }
     } else if (device as usize)
@@ -1327,6 +3742,34 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
                 Ok(())
             }
 
+            "led-ring" => {
+                let enabled = value.parse::<bool>()?;
+
+                device
+                    .write()
+                    .as_headset_device_mut()
+                    .ok_or(DbusApiError::InvalidParameter {})?
+                    .set_led_ring_enabled(enabled)?;
+
+                script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+                Ok(())
+            }
+
+            "transform" => {
+                let transform = value.parse::<CanvasTransform>()?;
+                transform::set_transform(device.read().as_device(), transform)
+            }
+
+            "brightness-curve" => {
+                let curve = match value {
+                    "default" => None,
+                    _ => Some(value.parse::<BrightnessCurve>()?),
+                };
+
+                brightness_curve::set_curve(device.read().as_device(), curve)
+            }
+
             _ => Err(DbusApiError::InvalidParameter {}.into()),
         }
     } else {
@@ -1335,6 +3778,10 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
 }
 
 fn query_device_specific_configuration(device: u64, param: &str) -> Result<String> {
+    if is_device_io_suspended() {
+        return Err(DbusApiError::DeviceBusy {}.into());
+    }
+
     if (device as usize) < crate::KEYBOARD_DEVICES.read().len() {
         let device = &crate::KEYBOARD_DEVICES.read()[device as usize];
 
@@ -1367,6 +3814,21 @@ fn query_device_specific_configuration(device: u64, param: &str) -> Result<Strin
                 Ok(format!("{}", brightness))
             }
 
+            "transform" => Ok(transform::get_transform(device.read().as_device()).to_string()),
+
+            "brightness-curve" => Ok(
+                brightness_curve::get_device_curve(device.read().as_device())
+                    .map(|curve| curve.to_string())
+                    .unwrap_or_else(|| "default".to_string()),
+            ),
+
+            "key-filter" => Ok(key_filter::get_key_filter(device.read().as_device()).to_string()),
+
+            "key-filter-enabled" => Ok(format!(
+                "{}",
+                key_filter::get_key_filter(device.read().as_device()).enabled
+            )),
+
             _ => Err(DbusApiError::InvalidParameter {}.into()),
         }
     } else if (device as usize)
@@ -1440,6 +3902,14 @@ fn query_device_specific_configuration(device: u64, param: &str) -> Result<Strin
                 Ok(format!("{}", brightness))
             }
 
+            "transform" => Ok(transform::get_transform(device.read().as_device()).to_string()),
+
+            "brightness-curve" => Ok(
+                brightness_curve::get_device_curve(device.read().as_device())
+                    .map(|curve| curve.to_string())
+                    .unwrap_or_else(|| "default".to_string()),
+            ),
+
             _ => Err(DbusApiError::InvalidParameter {}.into()),
         }
     } else if (device as usize)
@@ -1480,6 +3950,24 @@ fn query_device_specific_configuration(device: u64, param: &str) -> Result<Strin
                 Ok(format!("{}", brightness))
             }
 
+            "led-ring" => {
+                let enabled = device
+                    .read()
+                    .as_headset_device()
+                    .ok_or(DbusApiError::InvalidParameter {})?
+                    .is_led_ring_enabled();
+
+                Ok(format!("{}", enabled))
+            }
+
+            "transform" => Ok(transform::get_transform(device.read().as_device()).to_string()),
+
+            "brightness-curve" => Ok(
+                brightness_curve::get_device_curve(device.read().as_device())
+                    .map(|curve| curve.to_string())
+                    .unwrap_or_else(|| "default".to_string()),
+            ),
+
             _ => Err(DbusApiError::InvalidParameter {}.into()),
         }
     } else {
@@ -1523,7 +4011,180 @@ fn get_device_specific_ids(device: u64) -> Result<(u16, u16)> {
     }
 }
 
-mod perms {
+/// Structured errors of the D-Bus API. The name of an error identifies its kind, so that
+/// clients may react to it programmatically, and translate the message
+pub mod errors {
+    use dbus_tree::MethodErr;
+    use std::fmt::Display;
+
+    use super::DbusApiError;
+    use crate::batch::BatchError;
+    use crate::hwdevices::HwDeviceError;
+    use crate::profiles::ProfileError;
+    use crate::scripting::manifest::ManifestError;
+    use crate::scripting::parameters::ParameterError;
+    use crate::scripting::parameters_util::ParametersUtilError;
+    use crate::scripting::script::ScriptingError;
+    use crate::zone_controls::ZoneControlError;
+
+    /// The caller lacks the permission to perform the operation
+    pub const NOT_AUTHORIZED: &str = "org.eruption.Error.NotAuthorized";
+
+    /// The profile does not exist, could not be loaded, or is not active
+    pub const INVALID_PROFILE: &str = "org.eruption.Error.InvalidProfile";
+
+    /// There is no device with the specified index or ID
+    pub const INVALID_DEVICE: &str = "org.eruption.Error.InvalidDevice";
+
+    /// The device is currently not available, e.g. in standby or while a self-test is running
+    pub const DEVICE_BUSY: &str = "org.eruption.Error.DeviceBusy";
+
+    /// A Lua script or its manifest could not be loaded or executed
+    pub const SCRIPT_FAILURE: &str = "org.eruption.Error.ScriptFailure";
+
+    /// An argument is invalid or out of range
+    pub const INVALID_ARGUMENT: &str = "org.eruption.Error.InvalidArgument";
+
+    /// Any other error
+    pub const FAILED: &str = "org.eruption.Error.Failed";
+
+    pub fn not_authorized() -> MethodErr {
+        (NOT_AUTHORIZED, "Authentication failed").into()
+    }
+
+    pub fn invalid_profile(msg: impl Display) -> MethodErr {
+        (INVALID_PROFILE, msg.to_string()).into()
+    }
+
+    pub fn invalid_device(msg: impl Display) -> MethodErr {
+        (INVALID_DEVICE, msg.to_string()).into()
+    }
+
+    pub fn invalid_argument(arg: &str) -> MethodErr {
+        (INVALID_ARGUMENT, format!("Invalid argument: {}", arg)).into()
+    }
+
+    pub fn failed(msg: impl Display) -> MethodErr {
+        (FAILED, msg.to_string()).into()
+    }
+
+    /// Returns the name of an error of the daemon, if its kind is known to the API
+    fn error_name(e: &eyre::Error) -> Option<&'static str> {
+        if let Some(e) = e.downcast_ref::<DbusApiError>() {
+            match e {
+                DbusApiError::InvalidDevice {} => Some(INVALID_DEVICE),
+                DbusApiError::DeviceBusy {} => Some(DEVICE_BUSY),
+                DbusApiError::InvalidParameter {} => Some(INVALID_ARGUMENT),
+                DbusApiError::InvalidScript {} => Some(INVALID_ARGUMENT),
+                DbusApiError::BusNotConnected {} => None,
+            }
+        } else if let Some(e) = e.downcast_ref::<HwDeviceError>() {
+            match e {
+                HwDeviceError::DeviceNotBound {}
+                | HwDeviceError::DeviceNotOpened {}
+                | HwDeviceError::DeviceNotInitialized {} => Some(DEVICE_BUSY),
+
+                HwDeviceError::InvalidPollRate { .. } | HwDeviceError::InvalidDcuConfig { .. } => {
+                    Some(INVALID_ARGUMENT)
+                }
+
+                _ => None,
+            }
+        } else if let Some(e) = e.downcast_ref::<ParametersUtilError>() {
+            match e {
+                ParametersUtilError::ProfileNotActive {}
+                | ParametersUtilError::NoActiveProfile {} => Some(INVALID_PROFILE),
+
+                ParametersUtilError::ParseParameterError {}
+                | ParametersUtilError::NoSuchParameter {}
+                | ParametersUtilError::NoSuchColorScheme {} => Some(INVALID_ARGUMENT),
+
+                ParametersUtilError::OpenError {} => None,
+            }
+        } else if let Some(e) = e.downcast_ref::<BatchError>() {
+            match e {
+                // classify the error of the operation that failed
+                BatchError::OperationFailed { source, .. } => error_name(source),
+
+                BatchError::NoProfile {} => Some(INVALID_PROFILE),
+
+                BatchError::ParseError { .. }
+                | BatchError::InvalidSlot { .. }
+                | BatchError::InvalidBrightness { .. }
+                | BatchError::NoSuchScript { .. } => Some(INVALID_ARGUMENT),
+            }
+        } else if let Some(e) = e.downcast_ref::<ZoneControlError>() {
+            match e {
+                ZoneControlError::NoActiveProfile => Some(INVALID_PROFILE),
+
+                ZoneControlError::UnknownZone { .. }
+                | ZoneControlError::InvalidZoneControl { .. } => Some(INVALID_ARGUMENT),
+            }
+        } else if e.is::<ProfileError>() {
+            Some(INVALID_PROFILE)
+        } else if e.is::<ParameterError>() {
+            Some(INVALID_ARGUMENT)
+        } else if e.is::<ScriptingError>() || e.is::<ManifestError>() {
+            Some(SCRIPT_FAILURE)
+        } else {
+            None
+        }
+    }
+
+    /// Convert an error of the daemon to a D-Bus error, errors of an unknown kind are
+    /// reported as `org.eruption.Error.Failed`
+    pub fn from_error(e: impl Into<eyre::Error>) -> MethodErr {
+        let e = e.into();
+
+        (error_name(&e).unwrap_or(FAILED), e.to_string()).into()
+    }
+
+    /// Like `from_error`, but errors of an unknown kind are reported as an invalid argument `arg`
+    pub fn from_error_or_invalid_argument(e: eyre::Error, arg: &str) -> MethodErr {
+        match error_name(&e) {
+            Some(name) => (name, e.to_string()).into(),
+            None => invalid_argument(arg),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn errors_are_classified() {
+            let e = from_error(eyre::Error::from(DbusApiError::InvalidDevice {}));
+            assert_eq!(&**e.errorname(), INVALID_DEVICE);
+
+            let e = from_error(eyre::Error::from(ProfileError::ParseError {}));
+            assert_eq!(&**e.errorname(), INVALID_PROFILE);
+
+            let e = from_error(crate::hwdevices::dcu_config_to_byte(4).unwrap_err());
+            assert_eq!(&**e.errorname(), INVALID_ARGUMENT);
+
+            let e = from_error(BatchError::OperationFailed {
+                index: 1,
+                source: BatchError::NoProfile {}.into(),
+            });
+            assert_eq!(&**e.errorname(), INVALID_PROFILE);
+
+            let e = from_error(BatchError::OperationFailed {
+                index: 2,
+                source: BatchError::InvalidSlot { slot: 0 }.into(),
+            });
+            assert_eq!(&**e.errorname(), INVALID_ARGUMENT);
+
+            let e = from_error_or_invalid_argument(eyre::eyre!("unknown"), "rate");
+            assert_eq!(&**e.errorname(), INVALID_ARGUMENT);
+
+            let e = from_error(eyre::eyre!("unknown"));
+            assert_eq!(&**e.errorname(), FAILED);
+            assert_eq!(e.description(), "unknown");
+        }
+    }
+}
+
+pub(crate) mod perms {
     use dbus::{arg::RefArg, arg::Variant, blocking::Connection};
     use lazy_static::lazy_static;
     use parking_lot::RwLock;
@@ -1760,6 +4421,34 @@ mod perms {
         Ok((result.0 .0, false))
     }
 
+    /// Check whether the process `pid` of the user `uid`, e.g. a client of the SDK socket, is
+    /// authorized to perform the action `action`. Unlike the checks for D-Bus senders the
+    /// check is performed only once, so that a denied request won't be retried
+    pub fn has_process_permission(pid: u32, uid: u32, action: &str) -> Result<bool> {
+        use polkit::OrgFreedesktopPolicyKit1Authority;
+
+        let conn = Connection::new_system()?;
+
+        let polkit_proxy = conn.with_proxy(
+            "org.freedesktop.PolicyKit1",
+            "/org/freedesktop/PolicyKit1/Authority",
+            Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS_INTERACTIVE as u64),
+        );
+
+        let mut map = HashMap::new();
+        map.insert("pid", Variant(Box::new(pid) as Box<dyn RefArg>));
+        map.insert("start-time", Variant(Box::new(0_u64) as Box<dyn RefArg>));
+        map.insert("uid", Variant(Box::new(uid) as Box<dyn RefArg>));
+
+        let mut details = HashMap::new();
+        details.insert("AllowUserInteraction", "true");
+
+        let result =
+            polkit_proxy.check_authorization(("unix-process", map), action, details, 1, "")?;
+
+        Ok(result.0)
+    }
+
     mod bus {
         // This code was autogenerated with `dbus-codegen-rust -s -d org.freedesktop.DBus -p /org/freedesktop/DBus/Bus -m None`, see https://github.com/diwic/dbus-rs
 
@@ -0,0 +1,220 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::{Mutex, RwLock};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::constants;
+use crate::device_id;
+use crate::hotkeys;
+use crate::hwdevices::DeviceTrait;
+use crate::state::{self, DeviceMakeModelSerial};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeviceDefaultsError {
+    #[error("Could not parse the device defaults file {file}: {description}")]
+    ParseError { file: String, description: String },
+}
+
+lazy_static! {
+    /// Default profiles of the supported device models, indexed by their USB IDs
+    static ref DEFAULT_PROFILES: Arc<RwLock<HashMap<(u16, u16), PathBuf>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+
+    /// Devices that have been attached since the daemon has been started
+    static ref SEEN_DEVICES: Arc<RwLock<HashSet<DeviceMakeModelSerial>>> =
+        Arc::new(RwLock::new(HashSet::new()));
+
+    /// The default profile of a freshly connected device, it is activated as soon as
+    /// the main loop is re-entered
+    static ref PENDING_PROFILE: Arc<Mutex<Option<PathBuf>>> = Arc::new(Mutex::new(None));
+}
+
+/// The contents of a file in the device defaults directory
+#[derive(Debug, Default, Deserialize)]
+struct DefaultsFile {
+    #[serde(default)]
+    devices: Vec<DeviceDefaults>,
+}
+
+/// The default profile of a device model
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DeviceDefaults {
+    pub usb_vid: u16,
+    pub usb_pid: u16,
+
+    /// The profile file, relative paths are looked up in the profile directories
+    pub profile: PathBuf,
+}
+
+/// Load the default profiles of all device models, from the `*.toml` files in the
+/// device defaults directory. Entries of later files (in lexical order) replace
+/// entries of earlier files for the same device model
+pub fn load_defaults() -> Result<()> {
+    let defaults = load_defaults_from(Path::new(constants::DEFAULT_DEVICE_DEFAULTS_DIR))?;
+
+    info!(
+        "Loaded {} device specific default profile(s)",
+        defaults.len()
+    );
+
+    *DEFAULT_PROFILES.write() = defaults;
+
+    Ok(())
+}
+
+fn load_defaults_from(dir: &Path) -> Result<HashMap<(u16, u16), PathBuf>> {
+    let mut result = HashMap::new();
+
+    if !dir.is_dir() {
+        debug!(
+            "The device defaults directory {} does not exist",
+            dir.display()
+        );
+
+        return Ok(result);
+    }
+
+    let mut files = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "toml"))
+        .collect::<Vec<_>>();
+
+    files.sort();
+
+    for file in files {
+        match fs::read_to_string(&file)
+            .map_err(eyre::Error::from)
+            .and_then(|data| parse_defaults(&file, &data))
+        {
+            Ok(defaults) => {
+                for entry in defaults {
+                    result.insert((entry.usb_vid, entry.usb_pid), entry.profile);
+                }
+            }
+
+            Err(e) => error!("Skipping device defaults file: {}", e),
+        }
+    }
+
+    Ok(result)
+}
+
+fn parse_defaults(file: &Path, data: &str) -> Result<Vec<DeviceDefaults>> {
+    let defaults: DefaultsFile =
+        toml::from_str(data).map_err(|e| DeviceDefaultsError::ParseError {
+            file: file.display().to_string(),
+            description: format!("{}", e),
+        })?;
+
+    Ok(defaults.devices)
+}
+
+/// Returns the default profile of the device model with the USB IDs `usb_vid`:`usb_pid`
+pub fn get_default_profile(usb_vid: u16, usb_pid: u16) -> Option<PathBuf> {
+    DEFAULT_PROFILES
+        .read()
+        .get(&(usb_vid, usb_pid))
+        .map(|profile| hotkeys::resolve_profile_path(profile))
+}
+
+/// Remember `device` as known, e.g. for devices that are present at startup, so that
+/// re-connecting it later on will not switch profiles
+pub fn mark_seen(device: &dyn DeviceTrait) {
+    SEEN_DEVICES.write().insert(device_id::device_id(device));
+}
+
+/// Called when `device` has been hotplugged. If it has never been connected before
+/// and a default profile is registered for its model, that profile is scheduled
+/// for activation
+pub fn device_attached(device: &dyn DeviceTrait) {
+    let id = device_id::device_id(device);
+
+    let is_fresh = SEEN_DEVICES.write().insert(id.clone()) && !state::is_known_device(device);
+
+    if !is_fresh {
+        return;
+    }
+
+    if let Some(profile) = get_default_profile(device.get_usb_vid(), device.get_usb_pid()) {
+        if profile.exists() {
+            info!(
+                "Device {} has been connected for the first time, activating its default profile: {}",
+                id,
+                profile.display()
+            );
+
+            *PENDING_PROFILE.lock() = Some(profile);
+        } else {
+            warn!(
+                "The default profile of device {} does not exist: {}",
+                id,
+                profile.display()
+            );
+        }
+    }
+}
+
+/// Returns the default profile of a freshly connected device that should be activated
+pub fn take_pending_profile() -> Option<PathBuf> {
+    PENDING_PROFILE.lock().take()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_device_defaults() {
+        let data = r#"
+            [[devices]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x2e27
+            profile = "batique-mouse.profile"
+
+            [[devices]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x311a
+            profile = "/var/lib/eruption/profiles/default.profile"
+        "#;
+
+        let defaults = parse_defaults(Path::new("test.toml"), data).unwrap();
+
+        assert_eq!(defaults.len(), 2);
+        assert_eq!(defaults[0].usb_vid, 0x1e7d);
+        assert_eq!(defaults[0].usb_pid, 0x2e27);
+        assert_eq!(defaults[0].profile, PathBuf::from("batique-mouse.profile"));
+
+        assert!(parse_defaults(Path::new("empty.toml"), "")
+            .unwrap()
+            .is_empty());
+
+        assert!(parse_defaults(Path::new("invalid.toml"), "[[devices]]\nusb_vid = 1").is_err());
+    }
+}
@@ -0,0 +1,219 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::hwdevices::DeviceTrait;
+use crate::state::DeviceMakeModelSerial;
+
+lazy_static! {
+    /// Identifiers of the devices that have been seen so far, indexed by their USB path;
+    /// resolving the USB port requires a walk of sysfs, so we only do that once per device
+    static ref DEVICE_IDS: Arc<Mutex<HashMap<String, DeviceMakeModelSerial>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Returns the stable identifier of a device, that does not depend on the order in which
+/// the devices have been enumerated. The identifier consists of the USB vendor and product
+/// IDs and the serial number of the device, like e.g. "0x1e7d:0x3098:0123456789". Devices
+/// that do not report a serial number are identified by the USB port they are plugged into,
+/// like e.g. "0x1e7d:0x2dcb:usb-1-2.3"
+pub fn device_id(device: &dyn DeviceTrait) -> DeviceMakeModelSerial {
+    let usb_path = device.get_usb_path();
+
+    if let Some(id) = DEVICE_IDS.lock().get(&usb_path) {
+        return id.clone();
+    }
+
+    let make = format!("0x{:x}", device.get_usb_vid());
+    let model = format!("0x{:x}", device.get_usb_pid());
+
+    let id = match device.get_serial().filter(|serial| !serial.is_empty()) {
+        Some(serial) => format!("{}:{}:{}", make, model, serial),
+
+        None => match usb_port_path(&usb_path) {
+            Some(port) => format!("{}:{}:usb-{}", make, model, port),
+
+            None => {
+                warn!(
+                    "Could not determine the USB port of device {}:{}, settings of identical devices may be mixed up",
+                    make, model
+                );
+
+                format!("{}:{}:", make, model)
+            }
+        },
+    };
+
+    DEVICE_IDS.lock().insert(usb_path, id.clone());
+
+    id
+}
+
+/// Discard the cached identifiers, e.g. after devices have been removed,
+/// since their USB paths may be re-used by other devices
+pub fn reset_cache() {
+    DEVICE_IDS.lock().clear();
+}
+
+/// Returns the key that has been used by previous versions of Eruption, consisting of
+/// the USB IDs and the (possibly empty) serial number of the device
+fn legacy_device_key(device: &dyn DeviceTrait) -> DeviceMakeModelSerial {
+    let make = format!("0x{:x}", device.get_usb_vid());
+    let model = format!("0x{:x}", device.get_usb_pid());
+    let serial = device.get_serial().unwrap_or("");
+
+    format!("{}:{}:{}", make, model, serial)
+}
+
+/// Returns the persisted state of `device`, falling back to state that has
+/// been saved by previous versions of Eruption
+pub fn lookup<'a, V>(
+    map: &'a BTreeMap<DeviceMakeModelSerial, V>,
+    device: &dyn DeviceTrait,
+) -> Option<&'a V> {
+    map.get(&device_id(device))
+        .or_else(|| map.get(&legacy_device_key(device)))
+}
+
+/// Move the persisted state of `device` that has been saved by previous versions of
+/// Eruption over to the stable identifier of the device. Returns the identifier
+pub fn migrate<V>(
+    map: &mut BTreeMap<DeviceMakeModelSerial, V>,
+    device: &dyn DeviceTrait,
+) -> DeviceMakeModelSerial {
+    let id = device_id(device);
+    let legacy_key = legacy_device_key(device);
+
+    if legacy_key != id && !map.contains_key(&id) {
+        if let Some(value) = map.remove(&legacy_key) {
+            info!("Migrating saved state of device {} to {}", legacy_key, id);
+
+            map.insert(id.clone(), value);
+        }
+    }
+
+    id
+}
+
+/// Returns the stable identifiers of all connected devices, ordered by their device index
+pub fn get_device_ids() -> Vec<DeviceMakeModelSerial> {
+    let mut result = vec![];
+
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        result.push(device_id(device.read().as_device()));
+    }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        result.push(device_id(device.read().as_device()));
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        result.push(device_id(device.read().as_device()));
+    }
+
+    result
+}
+
+/// Returns the current index of the device with the stable identifier `id`
+pub fn find_device_index(id: &str) -> Option<u64> {
+    get_device_ids()
+        .iter()
+        .position(|device_id| device_id == id)
+        .map(|index| index as u64)
+}
+
+/// Returns the physical USB port that a device is plugged into, like e.g. "1-2.3".
+/// `usb_path` is the path reported by hidapi, either the path of a hidraw device node
+/// or a "bus:address:interface" triple when using the libusb backend
+pub fn usb_port_path(usb_path: &str) -> Option<String> {
+    if let Some(name) = usb_path.strip_prefix("/dev/") {
+        // the sysfs path of the hidraw device contains all the USB ports up to the root hub
+        let device_path =
+            fs::canonicalize(Path::new("/sys/class/hidraw").join(name).join("device")).ok()?;
+
+        device_path
+            .iter()
+            .filter_map(|component| component.to_str())
+            .rfind(|component| is_usb_port(component))
+            .map(|component| component.to_string())
+    } else {
+        let mut parts = usb_path.split(':');
+
+        let bus = u32::from_str_radix(parts.next()?, 16).ok()?;
+        let address = u32::from_str_radix(parts.next()?, 16).ok()?;
+
+        fs::read_dir("/sys/bus/usb/devices")
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str().map(|s| s.to_string()))
+            .filter(|name| is_usb_port(name))
+            .find(|name| {
+                let path = PathBuf::from("/sys/bus/usb/devices").join(name);
+
+                read_sysfs_number(&path.join("busnum")) == Some(bus)
+                    && read_sysfs_number(&path.join("devnum")) == Some(address)
+            })
+    }
+}
+
+/// Returns `true` if `name` denotes a USB port, like e.g. "1-2" or "3-1.4.2"
+fn is_usb_port(name: &str) -> bool {
+    match name.split_once('-') {
+        Some((bus, ports)) => {
+            !bus.is_empty()
+                && bus.chars().all(|c| c.is_ascii_digit())
+                && !ports.is_empty()
+                && ports
+                    .split('.')
+                    .all(|port| !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()))
+        }
+
+        None => false,
+    }
+}
+
+fn read_sysfs_number(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usb_port_names() {
+        assert!(is_usb_port("1-2"));
+        assert!(is_usb_port("3-1.4.2"));
+
+        assert!(!is_usb_port("usb1"));
+        assert!(!is_usb_port("1-2:1.0"));
+        assert!(!is_usb_port("0003:1E7D:2E2C.0005"));
+        assert!(!is_usb_port("1-"));
+        assert!(!is_usb_port("1-2..3"));
+    }
+}
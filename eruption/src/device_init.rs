@@ -0,0 +1,178 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::constants;
+use crate::state::DeviceMakeModelSerial;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Initialization status of all devices that have been initialized since startup
+    static ref INIT_STATUS: Arc<RwLock<BTreeMap<DeviceMakeModelSerial, InitStatus>>> =
+        Arc::new(RwLock::new(BTreeMap::new()));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InitState {
+    /// The device is being initialized, or waits for the next attempt
+    Initializing,
+
+    Initialized,
+
+    /// All attempts failed, the device has been marked as failed
+    Failed,
+}
+
+/// Initialization status of a single device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InitStatus {
+    pub state: InitState,
+
+    /// Number of attempts so far, including the successful one
+    pub attempts: u32,
+
+    /// The error of the most recent failed attempt
+    pub last_error: Option<String>,
+}
+
+/// Returns the initialization status of a device, or `None` if it has not been initialized yet
+pub fn get_status(id: &DeviceMakeModelSerial) -> Option<InitStatus> {
+    INIT_STATUS.read().get(id).cloned()
+}
+
+fn update_status(id: &DeviceMakeModelSerial, status: InitStatus) {
+    INIT_STATUS.write().insert(id.clone(), status);
+}
+
+/// Returns the delay before the attempt `attempt` (counting from 1), the delay is doubled
+/// after each failed attempt
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(constants::DEVICE_INIT_INITIAL_BACKOFF_MILLIS)
+        * 2_u32.saturating_pow(attempt.saturating_sub(2))
+}
+
+/// Run `init` until it succeeds, e.g. to send the init sequence to a device that has not
+/// settled yet after power on. Failed attempts are retried with exponential backoff, up to
+/// `DEVICE_INIT_MAX_ATTEMPTS` times. Returns `false` if all attempts failed
+pub fn initialize_with_retries<F>(id: &DeviceMakeModelSerial, mut init: F) -> bool
+where
+    F: FnMut() -> Result<()>,
+{
+    let mut status = InitStatus {
+        state: InitState::Initializing,
+        attempts: 0,
+        last_error: None,
+    };
+
+    for attempt in 1..=constants::DEVICE_INIT_MAX_ATTEMPTS {
+        if attempt > 1 {
+            thread::sleep(backoff(attempt));
+        }
+
+        status.attempts = attempt;
+        update_status(id, status.clone());
+
+        match init() {
+            Ok(()) => {
+                if attempt > 1 {
+                    info!(
+                        "Device {} has been initialized after {} attempts",
+                        id, attempt
+                    );
+                }
+
+                status.state = InitState::Initialized;
+                update_status(id, status);
+
+                return true;
+            }
+
+            Err(e) => {
+                warn!(
+                    "Could not initialize the device {} (attempt {} of {}): {}",
+                    id,
+                    attempt,
+                    constants::DEVICE_INIT_MAX_ATTEMPTS,
+                    e
+                );
+
+                status.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    error!(
+        "Giving up on the initialization of the device {}, the device has been marked as failed",
+        id
+    );
+
+    status.state = InitState::Failed;
+    update_status(id, status);
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles() {
+        let initial = Duration::from_millis(constants::DEVICE_INIT_INITIAL_BACKOFF_MILLIS);
+
+        assert_eq!(backoff(2), initial);
+        assert_eq!(backoff(3), initial * 2);
+        assert_eq!(backoff(4), initial * 4);
+    }
+
+    #[test]
+    fn retries_until_success() {
+        let id = "0x0000:0x0000:test-retries".to_string();
+        let mut calls = 0;
+
+        let initialized = initialize_with_retries(&id, || {
+            calls += 1;
+
+            if calls < 2 {
+                Err(eyre::eyre!("not settled"))
+            } else {
+                Ok(())
+            }
+        });
+
+        assert!(initialized);
+
+        let status = get_status(&id).unwrap();
+        assert_eq!(status.state, InitState::Initialized);
+        assert_eq!(status.attempts, 2);
+        assert_eq!(status.last_error.as_deref(), Some("not settled"));
+    }
+}
@@ -19,21 +19,25 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+use crate::hwdevices::DeviceClass;
+use crate::profiles::RouteEvent;
 use crate::{
-    constants, dbus_interface, events, macros, script, switch_profile, DbusApiEvent,
-    FileSystemEvent, KeyboardDevice, KeyboardHidEvent, MouseDevice, MouseHidEvent, ACTIVE_SLOT,
-    DEVICE_STATUS, FAILED_TXS, KEY_STATES, LUA_TXS, MOUSE_MOTION_BUF,
-    MOUSE_MOVE_EVENT_LAST_DISPATCHED, REQUEST_FAILSAFE_MODE, REQUEST_PROFILE_RELOAD,
-    UPCALL_COMPLETED_ON_KEYBOARD_HID_EVENT, UPCALL_COMPLETED_ON_KEY_DOWN,
+    constants, dbus_interface, device_id, events, game_mode, hotkeys, input_filter, low_latency,
+    macros, mouse_bindings, profile_slots, script, scripting::bytecode_cache,
+    switch_profile_announced, DbusApiEvent, EventAction, FileSystemEvent, KeyboardDevice,
+    KeyboardHidEvent, MouseDevice, MouseHidEvent, ACTIVE_SLOT, DEVICE_STATUS, FAILED_TXS,
+    KEY_STATES, LUA_TXS, MOUSE_MOTION_BUF, MOUSE_MOVE_EVENT_LAST_DISPATCHED, REQUEST_FAILSAFE_MODE,
+    REQUEST_PROFILE_RELOAD, UPCALL_COMPLETED_ON_KEYBOARD_HID_EVENT, UPCALL_COMPLETED_ON_KEY_DOWN,
     UPCALL_COMPLETED_ON_KEY_UP, UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN,
     UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP, UPCALL_COMPLETED_ON_MOUSE_EVENT,
     UPCALL_COMPLETED_ON_MOUSE_HID_EVENT, UPCALL_COMPLETED_ON_MOUSE_MOVE,
 };
-use flume::Sender;
+use flume::{bounded, unbounded, Receiver, Sender};
 use lazy_static::lazy_static;
-use log::{error, info, trace, warn};
+use log::{debug, error, info, trace, warn};
 use parking_lot::Mutex;
 
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -45,6 +49,9 @@ pub enum Event {
     DaemonStartup,
     DaemonShutdown,
 
+    ProfileChanged,
+    DeviceDisconnected,
+
     FileSystemEvent(crate::FileSystemEvent),
 
     KeyboardHidEvent(crate::hwdevices::KeyboardHidEvent),
@@ -67,6 +74,34 @@ pub type Callback = dyn Fn(&Event) -> Result<bool> + Sync + Send + 'static;
 lazy_static! {
     static ref INTERNAL_EVENT_OBSERVERS: Arc<Mutex<Vec<Box<Callback>>>> =
         Arc::new(Mutex::new(vec![]));
+
+    /// Tracks the keys that are currently held down, for the detection of key chords
+    static ref CHORD_STATE: Arc<Mutex<ChordState>> = Arc::new(Mutex::new(ChordState::default()));
+
+    /// Synthetic input events that are pending, processed by the main loop
+    pub static ref INJECTED_EVENTS: (Sender<InjectedEventRequest>, Receiver<InjectedEventRequest>) =
+        unbounded();
+}
+
+/// A synthetic input event, injected by an SDK client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InjectedEvent {
+    KeyDown(u8),
+    KeyUp(u8),
+    MouseButtonDown(u8),
+    MouseButtonUp(u8),
+}
+
+/// An injected event, along with the channel that is notified once it has been processed
+pub type InjectedEventRequest = (InjectedEvent, Sender<()>);
+
+#[derive(Debug, Default)]
+struct ChordState {
+    /// Key indices of all keys currently held down, along with the time they were pressed
+    pressed_keys: HashMap<u8, Instant>,
+
+    /// Names of the chords that already triggered, and that have not been released yet
+    triggered_chords: HashSet<String>,
 }
 
 pub fn register_observer<C>(callback: C)
@@ -90,7 +125,7 @@ pub fn process_filesystem_event(
     dbus_api_tx: &Sender<DbusApiEvent>,
 ) -> Result<()> {
     match fsevent {
-        FileSystemEvent::ProfileChanged { action: _, path: _ } => {
+        FileSystemEvent::ProfileChanged { action, path } => {
             events::notify_observers(events::Event::FileSystemEvent(fsevent.clone()))
                 .unwrap_or_else(|e| error!("Error during notification of observers: {}", e));
 
@@ -98,11 +133,28 @@ pub fn process_filesystem_event(
                 .send(DbusApiEvent::ProfilesChanged)
                 .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
 
+            if let EventAction::Created = action {
+                match profile_slots::auto_assign(path) {
+                    Ok(Some(slot)) => dbus_api_tx
+                        .send(DbusApiEvent::ProfileAssigned(slot, path.clone()))
+                        .unwrap_or_else(|e| {
+                            error!("Could not send a pending dbus API event: {}", e)
+                        }),
+
+                    Ok(None) => (),
+
+                    Err(e) => error!("Could not assign the new profile to a slot: {}", e),
+                }
+            }
+
             // TODO: maybe make this more fine grained
             REQUEST_PROFILE_RELOAD.store(true, Ordering::SeqCst);
         }
 
-        FileSystemEvent::ScriptChanged => {
+        FileSystemEvent::ScriptChanged { path } => {
+            // the precompiled chunk of the script is stale now
+            bytecode_cache::invalidate(path);
+
             events::notify_observers(events::Event::FileSystemEvent(fsevent.clone()))
                 .unwrap_or_else(|e| error!("Error during notification of observers: {}", e));
 
@@ -130,10 +182,10 @@ pub fn process_dbus_event(
             ACTIVE_SLOT.store(*slot, Ordering::SeqCst);
         }
 
-        dbus_interface::Message::SwitchProfile(profile_path) => {
+        dbus_interface::Message::SwitchProfile(profile_path, id) => {
             info!("Loading profile: {}", profile_path.display());
 
-            if let Err(e) = switch_profile(Some(profile_path), dbus_api_tx, true) {
+            if let Err(e) = switch_profile_announced(profile_path, *id, dbus_api_tx) {
                 error!("Could not switch profiles: {}", e);
             }
         }
@@ -177,6 +229,172 @@ pub fn process_timer_event() -> Result<()> {
     Ok(())
 }
 
+/// Record a key press or release, used for the detection of key chords and global hotkeys.
+/// Key events may be reported by multiple sources, so a repeated key press
+/// does not reset the point in time when the key has been pressed initially
+fn update_chord_state(index: u8, is_pressed: bool) {
+    let mut chord_state = CHORD_STATE.lock();
+
+    if is_pressed {
+        if let Entry::Vacant(entry) = chord_state.pressed_keys.entry(index) {
+            entry.insert(Instant::now());
+
+            // global hotkeys take effect immediately, without a hold time
+            hotkeys::process_key_down(index, &chord_state.pressed_keys);
+        }
+    } else {
+        chord_state.pressed_keys.remove(&index);
+    }
+}
+
+/// Detect key chords of the active profile that are held down for long enough,
+/// and notify the Lua VMs. This is called on every iteration of the main loop,
+/// so that chords are detected even if the individual key events arrive across
+/// multiple frames
+pub fn process_chords() -> Result<()> {
+    let mut chord_state = CHORD_STATE.lock();
+
+    if chord_state.pressed_keys.is_empty() {
+        chord_state.triggered_chords.clear();
+
+        return Ok(());
+    }
+
+    let chords = match crate::ACTIVE_PROFILE.lock().as_ref() {
+        Some(profile) => profile.chords.clone(),
+        None => return Ok(()),
+    };
+
+    for chord in chords.iter() {
+        if chord.keys.is_empty() {
+            continue;
+        }
+
+        // the chord is held since the last one of its keys has been pressed
+        let held_since = chord
+            .keys
+            .iter()
+            .map(|key| chord_state.pressed_keys.get(key))
+            .collect::<Option<Vec<&Instant>>>()
+            .and_then(|instants| instants.into_iter().max().copied());
+
+        match held_since {
+            Some(held_since) => {
+                if held_since.elapsed() >= Duration::from_millis(chord.hold_millis)
+                    && chord_state.triggered_chords.insert(chord.name.clone())
+                {
+                    info!("Key chord triggered: {}", chord.name);
+
+                    for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+                        if !FAILED_TXS.read().contains(&idx) {
+                            lua_tx
+                                .send(script::Message::Chord(chord.name.clone()))
+                                .unwrap_or_else(|e| {
+                                    error!(
+                                        "Could not send a pending chord event to a Lua VM: {}",
+                                        e
+                                    )
+                                });
+                        } else {
+                            warn!("Not sending a message to a failed tx");
+                        }
+                    }
+                }
+            }
+
+            None => {
+                // at least one key of the chord has been released, so re-arm it
+                chord_state.triggered_chords.remove(&chord.name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Route an input event of a device to effects on another device, as configured by the
+/// routing table of the active profile. Matching routes are sent to the Lua VMs, along
+/// with the index of the target device
+fn process_routes(source_class: DeviceClass, source_id: &str, event: RouteEvent, code: u8) {
+    let routes = match crate::ACTIVE_PROFILE.lock().as_ref() {
+        Some(profile) => profile
+            .routes
+            .iter()
+            .filter(|route| {
+                route.event == event
+                    && (route.code.is_none() || route.code == Some(code))
+                    && is_route_device(&route.source, source_class, source_id)
+            })
+            .cloned()
+            .collect::<Vec<_>>(),
+
+        None => return,
+    };
+
+    for route in routes.iter() {
+        let device = match resolve_route_target(&route.target) {
+            Some(device) => device,
+
+            None => {
+                debug!(
+                    "Target device '{}' of route '{}' is not connected",
+                    route.target, route.name
+                );
+
+                continue;
+            }
+        };
+
+        trace!(
+            "Routing event {:?} to device {}: {}",
+            event,
+            device,
+            route.name
+        );
+
+        for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+            if !FAILED_TXS.read().contains(&idx) {
+                lua_tx
+                    .send(script::Message::Route(route.name.clone(), device, code))
+                    .unwrap_or_else(|e| {
+                        error!("Could not send a pending route event to a Lua VM: {}", e)
+                    });
+            } else {
+                warn!("Not sending a message to a failed tx");
+            }
+        }
+    }
+}
+
+/// Returns `true` if the device specification `spec` of a route, either a device class
+/// or a stable device identifier, denotes the device `class`/`id`
+fn is_route_device(spec: &str, class: DeviceClass, id: &str) -> bool {
+    match spec.to_lowercase().as_str() {
+        "keyboard" => matches!(class, DeviceClass::Keyboard),
+        "mouse" => matches!(class, DeviceClass::Mouse),
+        "misc" => matches!(class, DeviceClass::Misc),
+
+        _ => spec == id,
+    }
+}
+
+/// Returns the index of the device denoted by the device specification `spec` of a route.
+/// A device class denotes the first connected device of that class
+fn resolve_route_target(spec: &str) -> Option<u64> {
+    let num_keyboards = crate::KEYBOARD_DEVICES.read().len();
+    let num_mice = crate::MOUSE_DEVICES.read().len();
+    let num_misc = crate::MISC_DEVICES.read().len();
+
+    match spec.to_lowercase().as_str() {
+        "keyboard" if num_keyboards > 0 => Some(0),
+        "mouse" if num_mice > 0 => Some(num_keyboards as u64),
+        "misc" if num_misc > 0 => Some((num_keyboards + num_mice) as u64),
+        "keyboard" | "mouse" | "misc" => None,
+
+        _ => device_id::find_device_index(spec),
+    }
+}
+
 /// Process HID events
 pub fn process_keyboard_hid_events(keyboard_device: &KeyboardDevice) -> Result<()> {
     // limit the number of messages that will be processed during this iteration
@@ -245,6 +463,8 @@ pub fn process_keyboard_hid_events(keyboard_device: &KeyboardDevice) -> Result<(
                                 }
                             }
 
+                            update_chord_state(index, true);
+
                             *UPCALL_COMPLETED_ON_KEY_DOWN.0.lock() =
                                 LUA_TXS.read().len() - FAILED_TXS.read().len();
 
@@ -298,6 +518,8 @@ pub fn process_keyboard_hid_events(keyboard_device: &KeyboardDevice) -> Result<(
                                 }
                             }
 
+                            update_chord_state(index, false);
+
                             *UPCALL_COMPLETED_ON_KEY_UP.0.lock() =
                                 LUA_TXS.read().len() - FAILED_TXS.read().len();
 
@@ -343,6 +565,21 @@ pub fn process_keyboard_hid_events(keyboard_device: &KeyboardDevice) -> Result<(
                         }
                     }
 
+                    KeyboardHidEvent::VolumeUp
+                    | KeyboardHidEvent::VolumeDown
+                    | KeyboardHidEvent::BrightnessUp
+                    | KeyboardHidEvent::BrightnessDown => {
+                        let event = match result {
+                            KeyboardHidEvent::VolumeUp => RouteEvent::VolumeUp,
+                            KeyboardHidEvent::VolumeDown => RouteEvent::VolumeDown,
+                            KeyboardHidEvent::BrightnessUp => RouteEvent::BrightnessUp,
+                            _ => RouteEvent::BrightnessDown,
+                        };
+
+                        let source_id = device_id::device_id(keyboard_device.read().as_device());
+                        process_routes(DeviceClass::Keyboard, &source_id, event, 0);
+                    }
+
                     _ => { /* ignore other events */ }
                 }
             }
@@ -442,6 +679,105 @@ pub fn process_mouse_hid_events(mouse_device: &MouseDevice) -> Result<()> {
     Ok(())
 }
 
+/// Report a mouse button event to the Lua VMs, the observers and the event routes. Returns
+/// `false` if the button is bound by the active profile, and shall not be mirrored
+fn dispatch_mouse_button_event(index: u8, is_pressed: bool, source_id: &str) -> bool {
+    if mouse_bindings::process_button_event(index, is_pressed) {
+        // the button is bound to a key sequence or an action by the active profile
+        return false;
+    }
+
+    if is_pressed {
+        *UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.0.lock() =
+            LUA_TXS.read().len() - FAILED_TXS.read().len();
+
+        for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+            if !FAILED_TXS.read().contains(&idx) {
+                lua_tx
+                    .send(script::Message::MouseButtonDown(index))
+                    .unwrap_or_else(|e| {
+                        error!("Could not send a pending mouse event to a Lua VM: {}", e)
+                    });
+            } else {
+                warn!("Not sending a message to a failed tx");
+            }
+        }
+
+        // wait until all Lua VMs completed the event handler
+        loop {
+            if REQUEST_FAILSAFE_MODE.load(Ordering::SeqCst) {
+                *UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.0.lock() = 0;
+                break;
+            }
+
+            let mut pending = UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.0.lock();
+
+            UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.1.wait_for(
+                &mut pending,
+                Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
+            );
+
+            if *pending == 0 {
+                break;
+            }
+        }
+
+        events::notify_observers(events::Event::MouseButtonDown(index)).unwrap_or_else(|e| {
+            error!(
+                "Error during notification of observers [mouse_event]: {}",
+                e
+            )
+        });
+
+        process_routes(DeviceClass::Mouse, source_id, RouteEvent::ButtonDown, index);
+    } else {
+        *UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.0.lock() =
+            LUA_TXS.read().len() - FAILED_TXS.read().len();
+
+        for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+            if !FAILED_TXS.read().contains(&idx) {
+                lua_tx
+                    .send(script::Message::MouseButtonUp(index))
+                    .unwrap_or_else(|e| {
+                        error!("Could not send a pending mouse event to a Lua VM: {}", e)
+                    });
+            } else {
+                warn!("Not sending a message to a failed tx");
+            }
+        }
+
+        // wait until all Lua VMs completed the event handler
+        loop {
+            if REQUEST_FAILSAFE_MODE.load(Ordering::SeqCst) {
+                *UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.0.lock() = 0;
+                break;
+            }
+
+            let mut pending = UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.0.lock();
+
+            UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.1.wait_for(
+                &mut pending,
+                Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
+            );
+
+            if *pending == 0 {
+                break;
+            }
+        }
+
+        events::notify_observers(events::Event::MouseButtonUp(index)).unwrap_or_else(|e| {
+            error!(
+                "Error during notification of observers [mouse_event]: {}",
+                e
+            )
+        });
+
+        process_routes(DeviceClass::Mouse, source_id, RouteEvent::ButtonUp, index);
+    }
+
+    true
+}
+
 /// Process mouse events
 pub fn process_mouse_event(
     raw_event: &evdev_rs::InputEvent,
@@ -481,7 +817,9 @@ pub fn process_mouse_event(
                     4
                 };
 
+                // static profiles do not react to mouse movement
                 if *MOUSE_MOTION_BUF.read() != (0, 0, 0)
+                    && !low_latency::is_suspended()
                     && MOUSE_MOVE_EVENT_LAST_DISPATCHED
                         .read()
                         .elapsed()
@@ -616,6 +954,9 @@ pub fn process_mouse_event(
                         )
                     },
                 );
+
+                let source_id = device_id::device_id(mouse_device.read().as_device());
+                process_routes(DeviceClass::Mouse, &source_id, RouteEvent::Wheel, direction);
             }
 
             _ => (), // ignore other events
@@ -626,88 +967,10 @@ pub fn process_mouse_event(
         let is_pressed = raw_event.value > 0;
         let index = mouse_device.read().ev_key_to_button_index(code).unwrap();
 
-        if is_pressed {
-            *UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.0.lock() =
-                LUA_TXS.read().len() - FAILED_TXS.read().len();
-
-            for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
-                if !FAILED_TXS.read().contains(&idx) {
-                    lua_tx
-                        .send(script::Message::MouseButtonDown(index))
-                        .unwrap_or_else(|e| {
-                            error!("Could not send a pending mouse event to a Lua VM: {}", e)
-                        });
-                } else {
-                    warn!("Not sending a message to a failed tx");
-                }
-            }
-
-            // wait until all Lua VMs completed the event handler
-            loop {
-                if REQUEST_FAILSAFE_MODE.load(Ordering::SeqCst) {
-                    *UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.0.lock() = 0;
-                    break;
-                }
-
-                let mut pending = UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.0.lock();
-
-                UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.1.wait_for(
-                    &mut pending,
-                    Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
-                );
+        let source_id = device_id::device_id(mouse_device.read().as_device());
 
-                if *pending == 0 {
-                    break;
-                }
-            }
-
-            events::notify_observers(events::Event::MouseButtonDown(index)).unwrap_or_else(|e| {
-                error!(
-                    "Error during notification of observers [mouse_event]: {}",
-                    e
-                )
-            });
-        } else {
-            *UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.0.lock() =
-                LUA_TXS.read().len() - FAILED_TXS.read().len();
-
-            for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
-                if !FAILED_TXS.read().contains(&idx) {
-                    lua_tx
-                        .send(script::Message::MouseButtonUp(index))
-                        .unwrap_or_else(|e| {
-                            error!("Could not send a pending mouse event to a Lua VM: {}", e)
-                        });
-                } else {
-                    warn!("Not sending a message to a failed tx");
-                }
-            }
-
-            // wait until all Lua VMs completed the event handler
-            loop {
-                if REQUEST_FAILSAFE_MODE.load(Ordering::SeqCst) {
-                    *UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.0.lock() = 0;
-                    break;
-                }
-
-                let mut pending = UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.0.lock();
-
-                UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.1.wait_for(
-                    &mut pending,
-                    Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
-                );
-
-                if *pending == 0 {
-                    break;
-                }
-            }
-
-            events::notify_observers(events::Event::MouseButtonUp(index)).unwrap_or_else(|e| {
-                error!(
-                    "Error during notification of observers [mouse_event]: {}",
-                    e
-                )
-            });
+        if !dispatch_mouse_button_event(index, is_pressed, &source_id) {
+            mirror_event = false;
         }
     }
 
@@ -851,115 +1114,197 @@ pub fn process_mouse_event(
 //     Ok(())
 // }
 
-/// Process keyboard events
-pub fn process_keyboard_event(
-    raw_event: &evdev_rs::InputEvent,
-    keyboard_device: &KeyboardDevice,
-) -> Result<()> {
-    // notify all observers of raw events
-    events::notify_observers(events::Event::RawKeyboardEvent(raw_event.clone())).ok();
+/// Report a key event to the Lua VMs, the observers and the event routes. Returns `false`
+/// if the key has been inhibited, and shall not be mirrored to the virtual keyboard
+fn dispatch_key_event(index: u8, is_pressed: bool, source_id: &str) -> bool {
+    update_chord_state(index, is_pressed);
 
-    if let evdev_rs::enums::EventCode::EV_KEY(ref code) = raw_event.event_code {
-        let is_pressed = raw_event.value > 0;
-        let index = keyboard_device.read().ev_key_to_key_index(*code);
+    // keys that are inhibited by game mode are neither reported to the Lua VMs, nor
+    // mirrored to the virtual keyboard. Key releases are always passed through, so
+    // that keys that have been held down while game mode was enabled won't get stuck
+    if is_pressed && game_mode::is_key_inhibited(index) {
+        debug!("Key has been inhibited by game mode: {}", index);
 
-        trace!("Key index: {:#x}", index);
+        return false;
+    }
 
-        if is_pressed {
-            *UPCALL_COMPLETED_ON_KEY_DOWN.0.lock() = LUA_TXS.read().len() - FAILED_TXS.read().len();
+    if is_pressed {
+        *UPCALL_COMPLETED_ON_KEY_DOWN.0.lock() = LUA_TXS.read().len() - FAILED_TXS.read().len();
 
-            for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
-                if !FAILED_TXS.read().contains(&idx) {
-                    lua_tx
-                        .send(script::Message::KeyDown(index))
-                        .unwrap_or_else(|e| {
-                            error!("Could not send a pending keyboard event to a Lua VM: {}", e)
-                        });
-                } else {
-                    warn!("Not sending a message to a failed tx");
-                }
+        for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+            if !FAILED_TXS.read().contains(&idx) {
+                lua_tx
+                    .send(script::Message::KeyDown(index))
+                    .unwrap_or_else(|e| {
+                        error!("Could not send a pending keyboard event to a Lua VM: {}", e)
+                    });
+            } else {
+                warn!("Not sending a message to a failed tx");
             }
+        }
 
-            // wait until all Lua VMs completed the event handler
-            loop {
-                // this is required to avoid a deadlock when a Lua script fails
-                // and a key event is pending
-                if REQUEST_FAILSAFE_MODE.load(Ordering::SeqCst) {
-                    *UPCALL_COMPLETED_ON_KEY_DOWN.0.lock() = 0;
-                    break;
-                }
+        // wait until all Lua VMs completed the event handler
+        loop {
+            // this is required to avoid a deadlock when a Lua script fails
+            // and a key event is pending
+            if REQUEST_FAILSAFE_MODE.load(Ordering::SeqCst) {
+                *UPCALL_COMPLETED_ON_KEY_DOWN.0.lock() = 0;
+                break;
+            }
 
-                let mut pending = UPCALL_COMPLETED_ON_KEY_DOWN.0.lock();
+            let mut pending = UPCALL_COMPLETED_ON_KEY_DOWN.0.lock();
 
-                UPCALL_COMPLETED_ON_KEY_DOWN.1.wait_for(
-                    &mut pending,
-                    Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
-                );
+            UPCALL_COMPLETED_ON_KEY_DOWN.1.wait_for(
+                &mut pending,
+                Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
+            );
 
-                if *pending == 0 {
-                    break;
-                }
+            if *pending == 0 {
+                break;
             }
+        }
 
-            events::notify_observers(events::Event::KeyDown(index)).unwrap_or_else(|e| {
-                error!(
-                    "Error during notification of observers [keyboard_event]: {}",
-                    e
-                )
-            });
-        } else {
-            *UPCALL_COMPLETED_ON_KEY_UP.0.lock() = LUA_TXS.read().len() - FAILED_TXS.read().len();
-
-            for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
-                if !FAILED_TXS.read().contains(&idx) {
-                    lua_tx
-                        .send(script::Message::KeyUp(index))
-                        .unwrap_or_else(|e| {
-                            error!("Could not send a pending keyboard event to a Lua VM: {}", e)
-                        });
-                } else {
-                    warn!("Not sending a message to a failed tx");
-                }
+        events::notify_observers(events::Event::KeyDown(index)).unwrap_or_else(|e| {
+            error!(
+                "Error during notification of observers [keyboard_event]: {}",
+                e
+            )
+        });
+
+        process_routes(DeviceClass::Keyboard, source_id, RouteEvent::KeyDown, index);
+    } else {
+        *UPCALL_COMPLETED_ON_KEY_UP.0.lock() = LUA_TXS.read().len() - FAILED_TXS.read().len();
+
+        for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+            if !FAILED_TXS.read().contains(&idx) {
+                lua_tx
+                    .send(script::Message::KeyUp(index))
+                    .unwrap_or_else(|e| {
+                        error!("Could not send a pending keyboard event to a Lua VM: {}", e)
+                    });
+            } else {
+                warn!("Not sending a message to a failed tx");
             }
+        }
 
-            // wait until all Lua VMs completed the event handler
-            loop {
-                // this is required to avoid a deadlock when a Lua script fails
-                // and a key event is pending
-                if REQUEST_FAILSAFE_MODE.load(Ordering::SeqCst) {
-                    *UPCALL_COMPLETED_ON_KEY_UP.0.lock() = 0;
-                    break;
-                }
+        // wait until all Lua VMs completed the event handler
+        loop {
+            // this is required to avoid a deadlock when a Lua script fails
+            // and a key event is pending
+            if REQUEST_FAILSAFE_MODE.load(Ordering::SeqCst) {
+                *UPCALL_COMPLETED_ON_KEY_UP.0.lock() = 0;
+                break;
+            }
 
-                let mut pending = UPCALL_COMPLETED_ON_KEY_UP.0.lock();
+            let mut pending = UPCALL_COMPLETED_ON_KEY_UP.0.lock();
 
-                UPCALL_COMPLETED_ON_KEY_UP.1.wait_for(
-                    &mut pending,
-                    Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
-                );
+            UPCALL_COMPLETED_ON_KEY_UP.1.wait_for(
+                &mut pending,
+                Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
+            );
 
-                if *pending == 0 {
-                    break;
-                }
+            if *pending == 0 {
+                break;
             }
+        }
 
-            events::notify_observers(events::Event::KeyUp(index)).unwrap_or_else(|e| {
-                error!(
-                    "Error during notification of observers [keyboard_event]: {}",
-                    e
-                )
-            });
+        events::notify_observers(events::Event::KeyUp(index)).unwrap_or_else(|e| {
+            error!(
+                "Error during notification of observers [keyboard_event]: {}",
+                e
+            )
+        });
+
+        process_routes(DeviceClass::Keyboard, source_id, RouteEvent::KeyUp, index);
+    }
+
+    true
+}
+
+/// Process keyboard events
+pub fn process_keyboard_event(
+    raw_event: &evdev_rs::InputEvent,
+    keyboard_device: &KeyboardDevice,
+) -> Result<()> {
+    // notify all observers of raw events
+    events::notify_observers(events::Event::RawKeyboardEvent(raw_event.clone())).ok();
+
+    if let evdev_rs::enums::EventCode::EV_KEY(ref code) = raw_event.event_code {
+        let is_pressed = raw_event.value > 0;
+        let index = keyboard_device.read().ev_key_to_key_index(*code);
+
+        trace!("Key index: {:#x}", index);
+
+        let source_id = device_id::device_id(keyboard_device.read().as_device());
+
+        if !dispatch_key_event(index, is_pressed, &source_id) {
+            return Ok(());
         }
     }
 
+    // the input filters of the Lua scripts may drop the key, or replace the key code
+    let raw_event = match input_filter::filter(raw_event) {
+        Some(raw_event) => raw_event,
+        None => return Ok(()),
+    };
+
     // handler for Message::MirrorKey will drop the key if a Lua VM
     // called inject_key(..), so that the key won't be reported twice
     macros::UINPUT_TX
         .read()
         .as_ref()
         .unwrap()
-        .send(macros::Message::MirrorKey(raw_event.clone()))
+        .send(macros::Message::MirrorKey(raw_event))
         .unwrap_or_else(|e| error!("Could not send a pending keyboard event: {}", e));
 
     Ok(())
 }
+
+/// Inject a synthetic input event into the event pipeline, as if it had been reported by
+/// the first keyboard or mouse. Blocks until the event handlers of all Lua VMs completed
+pub fn inject_event(event: InjectedEvent) -> Result<()> {
+    let (done_tx, done_rx) = bounded(1);
+
+    INJECTED_EVENTS.0.send((event, done_tx))?;
+    done_rx.recv_timeout(Duration::from_millis(
+        constants::INJECTED_EVENT_TIMEOUT_MILLIS,
+    ))?;
+
+    Ok(())
+}
+
+/// Process a synthetic input event, must be called by the main loop. Injected events are
+/// not mirrored to the virtual input devices
+pub fn process_injected_event(request: &InjectedEventRequest) {
+    let (event, done_tx) = request;
+
+    debug!("Processing injected event: {:?}", event);
+
+    match *event {
+        InjectedEvent::KeyDown(index) | InjectedEvent::KeyUp(index) => {
+            let source_id = crate::KEYBOARD_DEVICES
+                .read()
+                .first()
+                .map(|device| device_id::device_id(device.read().as_device()))
+                .unwrap_or_default();
+
+            let is_pressed = matches!(event, InjectedEvent::KeyDown(_));
+
+            dispatch_key_event(index, is_pressed, &source_id);
+        }
+
+        InjectedEvent::MouseButtonDown(index) | InjectedEvent::MouseButtonUp(index) => {
+            let source_id = crate::MOUSE_DEVICES
+                .read()
+                .first()
+                .map(|device| device_id::device_id(device.read().as_device()))
+                .unwrap_or_default();
+
+            let is_pressed = matches!(event, InjectedEvent::MouseButtonDown(_));
+
+            dispatch_mouse_button_event(index, is_pressed, &source_id);
+        }
+    }
+
+    done_tx.send(()).ok();
+}
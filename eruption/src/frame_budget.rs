@@ -0,0 +1,207 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{constants, power};
+
+lazy_static! {
+    /// Set if the frame rate shall be halved while the system is under load
+    pub static ref ENABLE_ADAPTIVE_FRAME_RATE: AtomicBool = AtomicBool::new(true);
+
+    /// Frame overruns of the most recently rendered frames
+    static ref FRAME_BUDGET: Arc<RwLock<FrameBudget>> = Arc::new(RwLock::new(FrameBudget::new()));
+}
+
+/// Keeps track of the frames that have been rendered too late. If the number of
+/// overruns within the window exceeds the error budget, the frame rate is halved.
+/// The full frame rate is restored after no overruns occurred for a while
+#[derive(Debug, Clone)]
+struct FrameBudget {
+    /// `true` for each frame of the window that has been rendered too late
+    window: VecDeque<bool>,
+
+    /// Set while the frame rate is halved
+    degraded: bool,
+
+    /// Point in time of the most recent overrun
+    last_overrun: Option<Instant>,
+}
+
+impl FrameBudget {
+    fn new() -> Self {
+        Self {
+            window: VecDeque::with_capacity(constants::FRAME_BUDGET_WINDOW),
+            degraded: false,
+            last_overrun: None,
+        }
+    }
+
+    fn overruns(&self) -> usize {
+        self.window.iter().filter(|overrun| **overrun).count()
+    }
+
+    /// Record a rendered frame, returns `true` if the frame rate changed
+    fn record(&mut self, overrun: bool, now: Instant) -> bool {
+        if self.window.len() >= constants::FRAME_BUDGET_WINDOW {
+            self.window.pop_front();
+        }
+
+        self.window.push_back(overrun);
+
+        if overrun {
+            self.last_overrun = Some(now);
+        }
+
+        if !self.degraded && self.overruns() > constants::FRAME_BUDGET_MAX_OVERRUNS {
+            self.degraded = true;
+            self.window.clear();
+
+            true
+        } else if self.degraded
+            && self.last_overrun.map_or(true, |last| {
+                now.duration_since(last)
+                    >= Duration::from_secs(constants::FRAME_BUDGET_RESTORE_SECS)
+            })
+        {
+            self.degraded = false;
+            self.window.clear();
+
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Record a rendered frame that has been due after `interval`, but has been rendered
+/// `elapsed` after the previous frame. Returns `true` if the frame rate changed
+pub fn record_frame(elapsed: Duration, interval: Duration) -> bool {
+    if !ENABLE_ADAPTIVE_FRAME_RATE.load(Ordering::SeqCst) {
+        return false;
+    }
+
+    // we dropped at least one frame
+    let overrun = elapsed >= interval * 2;
+
+    let changed = FRAME_BUDGET.write().record(overrun, Instant::now());
+
+    if changed {
+        if is_degraded() {
+            warn!(
+                "Sustained frame overruns detected, reducing the frame rate to {} fps",
+                get_target_fps()
+            );
+        } else {
+            info!(
+                "The system load decreased, restoring the frame rate to {} fps",
+                get_target_fps()
+            );
+        }
+    }
+
+    changed
+}
+
+/// Returns `true` while the frame rate is halved, due to sustained frame overruns
+pub fn is_degraded() -> bool {
+    FRAME_BUDGET.read().degraded
+}
+
+/// Returns the frame rate that the LED maps shall be rendered with, taking power
+/// saving and the system load into account
+pub fn get_target_fps() -> u64 {
+    let fps = power::get_target_fps();
+
+    if is_degraded() {
+        (fps / 2).max(1)
+    } else {
+        fps
+    }
+}
+
+/// Load the settings from the global configuration
+pub fn load_settings() {
+    let enabled = crate::CONFIG
+        .lock()
+        .as_ref()
+        .unwrap()
+        .get_bool("global.adaptive_frame_rate")
+        .unwrap_or(true);
+
+    ENABLE_ADAPTIVE_FRAME_RATE.store(enabled, Ordering::SeqCst);
+
+    if !enabled {
+        *FRAME_BUDGET.write() = FrameBudget::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degrade_on_sustained_overruns() {
+        let now = Instant::now();
+        let mut budget = FrameBudget::new();
+
+        // isolated overruns stay within the budget
+        for i in 0..constants::FRAME_BUDGET_WINDOW * 2 {
+            assert!(!budget.record(i % 8 == 0, now));
+        }
+
+        assert!(!budget.degraded);
+
+        let mut changed = false;
+        for _ in 0..=constants::FRAME_BUDGET_MAX_OVERRUNS {
+            changed |= budget.record(true, now);
+        }
+
+        assert!(changed);
+        assert!(budget.degraded);
+    }
+
+    #[test]
+    fn restore_after_headroom_returns() {
+        let now = Instant::now();
+        let mut budget = FrameBudget::new();
+
+        for _ in 0..=constants::FRAME_BUDGET_MAX_OVERRUNS {
+            budget.record(true, now);
+        }
+
+        assert!(budget.degraded);
+
+        // not restored while the overruns are recent
+        assert!(!budget.record(false, now + Duration::from_secs(1)));
+        assert!(budget.degraded);
+
+        let later = now + Duration::from_secs(constants::FRAME_BUDGET_RESTORE_SECS);
+        assert!(budget.record(false, later));
+        assert!(!budget.degraded);
+    }
+}
@@ -0,0 +1,92 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::profiles::GameMode;
+use crate::scripting::script;
+
+lazy_static! {
+    /// Global "game mode enabled" flag
+    pub static ref GAME_MODE_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+    /// Game mode settings of the active profile
+    static ref GAME_MODE: Arc<RwLock<Option<GameMode>>> = Arc::new(RwLock::new(None));
+}
+
+/// Assign the game mode settings of a newly activated profile
+pub fn set_game_mode(game_mode: Option<&GameMode>) {
+    *GAME_MODE.write() = game_mode.cloned();
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+}
+
+pub fn is_active() -> bool {
+    GAME_MODE_ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Enable or disable game mode
+pub fn set_active(enable: bool) {
+    if GAME_MODE_ACTIVE.swap(enable, Ordering::SeqCst) != enable {
+        if enable {
+            if GAME_MODE.read().is_none() {
+                warn!("Game mode enabled, but the active profile does not inhibit any keys");
+            } else {
+                info!("Game mode enabled");
+            }
+        } else {
+            info!("Game mode disabled");
+        }
+
+        script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Enable game mode if it is disabled, and vice versa
+pub fn toggle() {
+    set_active(!is_active());
+}
+
+/// Returns `true` if the key `index` shall not be forwarded to the system
+pub fn is_key_inhibited(index: u8) -> bool {
+    is_active()
+        && GAME_MODE
+            .read()
+            .as_ref()
+            .is_some_and(|game_mode| game_mode.keys.contains(&index))
+}
+
+/// Returns the key indices of the inhibited keys along with the color (ARGB)
+/// that they shall be rendered with, or `None` if game mode is disabled
+pub fn get_inhibited_keys() -> Option<(Vec<u8>, u32)> {
+    if !is_active() {
+        return None;
+    }
+
+    GAME_MODE
+        .read()
+        .as_ref()
+        .map(|game_mode| (game_mode.keys.clone(), game_mode.color))
+}
@@ -0,0 +1,115 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::hwdevices::{DeviceTrait, HwDeviceError};
+use crate::scripting::script;
+
+lazy_static! {
+    /// Set if the devices shall be handed back to their firmware
+    static ref HARDWARE_MODE_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+    /// Set if the devices have been handed back to their firmware, only
+    /// modified by the device I/O thread
+    static ref HARDWARE_MODE_ACTIVE: AtomicBool = AtomicBool::new(false);
+}
+
+/// Returns `true` if streaming of LED maps is suspended
+pub fn is_active() -> bool {
+    HARDWARE_MODE_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Suspend streaming of LED maps and return the devices to their firmware
+/// effects, or resume streaming. The transition is performed by the device
+/// I/O thread, before the next frame is rendered
+pub fn set_active(enable: bool) {
+    if HARDWARE_MODE_REQUESTED.swap(enable, Ordering::SeqCst) != enable {
+        if enable {
+            info!("Suspending streaming, handing the devices back to their firmware");
+        } else {
+            info!("Resuming streaming");
+        }
+    }
+}
+
+/// Suspend streaming if it is active, and vice versa
+pub fn toggle() {
+    set_active(!is_active());
+}
+
+/// Perform a pending transition from or to hardware mode. Must be called by
+/// the device I/O thread. Returns `true` if streaming is suspended
+pub fn apply() -> bool {
+    let requested = HARDWARE_MODE_REQUESTED.load(Ordering::SeqCst);
+
+    if HARDWARE_MODE_ACTIVE.load(Ordering::SeqCst) != requested {
+        if requested {
+            for_each_device(enter_hardware_mode);
+        } else {
+            for_each_device(leave_hardware_mode);
+
+            // force a re-render of the LED maps
+            script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+        }
+
+        HARDWARE_MODE_ACTIVE.store(requested, Ordering::SeqCst);
+    }
+
+    requested
+}
+
+fn for_each_device(f: fn(&mut dyn DeviceTrait)) {
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        f(device.write().as_device_mut());
+    }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        f(device.write().as_device_mut());
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        f(device.write().as_device_mut());
+    }
+}
+
+fn enter_hardware_mode(device: &mut dyn DeviceTrait) {
+    if let Err(e) = device.set_hardware_mode() {
+        match e.downcast_ref::<HwDeviceError>() {
+            Some(HwDeviceError::OpNotSupported {}) => info!(
+                "The device 0x{:04x}:0x{:04x} does not support returning to its firmware effects, the LEDs will keep their current state",
+                device.get_usb_vid(),
+                device.get_usb_pid()
+            ),
+
+            _ => error!("Could not return the device to its firmware effects: {}", e),
+        }
+    }
+}
+
+fn leave_hardware_mode(device: &mut dyn DeviceTrait) {
+    // bring the device back into a known good state, suitable for streaming
+    device
+        .send_init_sequence()
+        .unwrap_or_else(|e| error!("Could not initialize the device: {}", e));
+}
@@ -0,0 +1,137 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::hwdevices::HeadsetEvent;
+use crate::scripting::script;
+use crate::{FAILED_TXS, LUA_TXS};
+
+/// Event types of the `on_headset_event` upcall
+pub const EVENT_CONNECTION_CHANGED: u8 = 1;
+pub const EVENT_BATTERY_LEVEL_CHANGED: u8 = 2;
+pub const EVENT_CHAT_MIX_CHANGED: u8 = 3;
+
+/// State of a headset, as returned by `GetHeadsets`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadsetState {
+    /// Index of the device
+    pub device: u64,
+
+    /// Battery level in percent, `None` if unknown
+    pub battery_level: Option<u8>,
+
+    /// Balance between chat and game audio in percent, `None` if the headset has no chat mixer
+    pub chat_mix: Option<u8>,
+
+    pub led_ring_enabled: bool,
+}
+
+impl HeadsetEvent {
+    /// Returns the event type and the payload, as passed to the `on_headset_event` upcall
+    fn to_upcall_args(self) -> (u8, u32) {
+        match self {
+            HeadsetEvent::ConnectionChanged { connected } => {
+                (EVENT_CONNECTION_CHANGED, connected as u32)
+            }
+
+            HeadsetEvent::BatteryLevelChanged { level } => {
+                (EVENT_BATTERY_LEVEL_CHANGED, level as u32)
+            }
+
+            HeadsetEvent::ChatMixChanged { mix } => (EVENT_CHAT_MIX_CHANGED, mix as u32),
+        }
+    }
+}
+
+/// Returns the index of the first misc device, headsets are managed as misc devices
+fn misc_device_offset() -> usize {
+    crate::KEYBOARD_DEVICES.read().len() + crate::MOUSE_DEVICES.read().len()
+}
+
+/// Returns the state of all connected headsets
+pub fn get_headsets() -> Vec<HeadsetState> {
+    let offset = misc_device_offset();
+
+    crate::MISC_DEVICES
+        .read()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, device)| {
+            let device = device.read();
+            let headset = device.as_headset_device()?;
+
+            Some(HeadsetState {
+                device: (index + offset) as u64,
+                battery_level: headset.get_battery_level(),
+                chat_mix: headset.get_chat_mix(),
+                led_ring_enabled: headset.is_led_ring_enabled(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the battery level of the first connected headset that reports it
+pub fn get_battery_level() -> Option<u8> {
+    get_headsets().iter().find_map(|h| h.battery_level)
+}
+
+/// Returns the chat mix of the first connected headset that reports it
+pub fn get_chat_mix() -> Option<u8> {
+    get_headsets().iter().find_map(|h| h.chat_mix)
+}
+
+/// Collect the events reported by the connected headsets, and forward them to the Lua VMs
+pub fn process_events() {
+    let offset = misc_device_offset();
+
+    let mut events = vec![];
+
+    for (index, device) in crate::MISC_DEVICES.read().iter().enumerate() {
+        if let Some(headset) = device.write().as_headset_device_mut() {
+            events.extend(
+                headset
+                    .take_events()
+                    .into_iter()
+                    .map(|event| ((index + offset) as u64, event)),
+            );
+        }
+    }
+
+    for (device, event) in events {
+        debug!("Headset event on device {}: {:?}", device, event);
+
+        let (event_type, value) = event.to_upcall_args();
+
+        for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+            if !FAILED_TXS.read().contains(&idx) {
+                lua_tx
+                    .send(script::Message::HeadsetEvent(device, event_type, value))
+                    .unwrap_or_else(|e| {
+                        error!("Could not send a pending headset event to a Lua VM: {}", e)
+                    });
+            } else {
+                warn!("Not sending a message to a failed tx");
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::hwdevices::DeviceTrait;
+use crate::state::DeviceMakeModelSerial;
+use crate::util::ratelimited;
+use crate::{constants, device_id, quarantine, state_file};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Failure statistics of all devices that have been seen so far, persisted across restarts
+    pub static ref DEVICE_HEALTH: Arc<RwLock<BTreeMap<DeviceMakeModelSerial, DeviceHealth>>> =
+        Arc::new(RwLock::new(BTreeMap::new()));
+
+    /// Thresholds above which a device is considered unhealthy
+    static ref THRESHOLDS: Arc<RwLock<HealthThresholds>> =
+        Arc::new(RwLock::new(HealthThresholds::default()));
+
+    /// Backoff of the devices that are being re-initialized, indexed by their stable identifier
+    static ref REINIT_BACKOFF: Arc<Mutex<HashMap<DeviceMakeModelSerial, ReinitBackoff>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Kinds of failures that are tracked per device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthEvent {
+    /// The device has been disconnected or has failed, and has been unplugged
+    Disconnect,
+
+    /// A LED map could not be sent to the device
+    FailedWrite,
+
+    /// The device lost its initialization and has been re-initialized
+    ReinitAttempt,
+
+    /// The device could not be initialized, even after retrying
+    FailedInit,
+}
+
+/// Failure statistics of a single device
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceHealth {
+    pub disconnects: u64,
+    pub failed_writes: u64,
+    pub reinit_attempts: u64,
+
+    #[serde(default)]
+    pub failed_inits: u64,
+
+    /// Point in time of the most recent failure, in seconds since the UNIX epoch
+    pub last_failure: Option<u64>,
+}
+
+impl DeviceHealth {
+    /// Returns a description of each of the thresholds that have been exceeded
+    pub fn warnings(&self) -> Vec<String> {
+        let thresholds = THRESHOLDS.read();
+        let mut result = vec![];
+
+        if self.disconnects >= thresholds.max_disconnects {
+            result.push(format!(
+                "Disconnected {} times, please check the cable or the wireless receiver",
+                self.disconnects
+            ));
+        }
+
+        if self.failed_writes >= thresholds.max_failed_writes {
+            result.push(format!(
+                "{} failed writes, please check the cable or the USB port",
+                self.failed_writes
+            ));
+        }
+
+        if self.reinit_attempts >= thresholds.max_reinit_attempts {
+            result.push(format!(
+                "Re-initialized {} times, the device may be losing power",
+                self.reinit_attempts
+            ));
+        }
+
+        if self.failed_inits >= thresholds.max_failed_inits {
+            result.push(format!(
+                "Could not be initialized {} times, the device may need more time to settle after power on",
+                self.failed_inits
+            ));
+        }
+
+        result
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HealthThresholds {
+    max_disconnects: u64,
+    max_failed_writes: u64,
+    max_reinit_attempts: u64,
+    max_failed_inits: u64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            max_disconnects: constants::HEALTH_MAX_DISCONNECTS,
+            max_failed_writes: constants::HEALTH_MAX_FAILED_WRITES,
+            max_reinit_attempts: constants::HEALTH_MAX_REINIT_ATTEMPTS,
+            max_failed_inits: constants::HEALTH_MAX_FAILED_INITS,
+        }
+    }
+}
+
+/// Spaces out the attempts to re-initialize a device that lost its initialization
+#[derive(Debug)]
+struct ReinitBackoff {
+    next_attempt: Instant,
+    backoff: Duration,
+}
+
+impl ReinitBackoff {
+    fn new(now: Instant) -> Self {
+        Self {
+            next_attempt: now,
+            backoff: Duration::from_millis(constants::REINIT_INITIAL_BACKOFF_MILLIS),
+        }
+    }
+
+    /// Returns `true` if an attempt is due at `now`, the delay until the next attempt is doubled
+    fn attempt(&mut self, now: Instant) -> bool {
+        if now < self.next_attempt {
+            return false;
+        }
+
+        self.next_attempt = now + self.backoff;
+        self.backoff =
+            (self.backoff * 2).min(Duration::from_millis(constants::REINIT_MAX_BACKOFF_MILLIS));
+
+        true
+    }
+}
+
+/// Returns `true` if the uninitialized `device` shall be re-initialized now, and records
+/// the attempt. Otherwise the device would be re-initialized, and the attempt recorded,
+/// on every frame until it recovers
+pub fn begin_reinit_attempt(device: &dyn DeviceTrait) -> bool {
+    let now = Instant::now();
+
+    let due = REINIT_BACKOFF
+        .lock()
+        .entry(device_id::device_id(device))
+        .or_insert_with(|| ReinitBackoff::new(now))
+        .attempt(now);
+
+    if due {
+        record_event(device, HealthEvent::ReinitAttempt);
+    }
+
+    due
+}
+
+/// Record that a frame has been sent to `device` successfully, so that the next loss of
+/// its initialization is retried right away
+pub fn record_success(device: &dyn DeviceTrait) {
+    let mut reinit_backoff = REINIT_BACKOFF.lock();
+
+    if !reinit_backoff.is_empty() {
+        reinit_backoff.remove(&device_id::device_id(device));
+    }
+}
+
+/// Record a failure of a device, and warn if the device exceeded one of the thresholds
+pub fn record_event(device: &dyn DeviceTrait, event: HealthEvent) {
+    let thresholds = *THRESHOLDS.read();
+
+    let mut device_health = DEVICE_HEALTH.write();
+    let key = device_id::migrate(&mut device_health, device);
+    let health = device_health.entry(key.clone()).or_default();
+
+    let (count, threshold) = match event {
+        HealthEvent::Disconnect => {
+            health.disconnects += 1;
+            (health.disconnects, thresholds.max_disconnects)
+        }
+
+        HealthEvent::FailedWrite => {
+            health.failed_writes += 1;
+            (health.failed_writes, thresholds.max_failed_writes)
+        }
+
+        HealthEvent::ReinitAttempt => {
+            health.reinit_attempts += 1;
+            (health.reinit_attempts, thresholds.max_reinit_attempts)
+        }
+
+        HealthEvent::FailedInit => {
+            health.failed_inits += 1;
+            (health.failed_inits, thresholds.max_failed_inits)
+        }
+    };
+
+    health.last_failure = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs());
+
+    // warn each time the count reaches another multiple of the threshold,
+    // so that a persistent problem does not go unnoticed
+    if threshold > 0 && count % threshold == 0 {
+        let description = health.warnings().join("; ");
+
+        ratelimited::warn!("Device {} seems to be unhealthy: {}", key, description);
+    }
+
+    quarantine::record_failure(&key, event);
+}
+
+/// Reset the failure statistics of all devices
+pub fn reset_device_health() {
+    DEVICE_HEALTH.write().clear();
+}
+
+/// Load the thresholds from the global configuration
+pub fn load_thresholds() -> Result<()> {
+    let config = crate::CONFIG.lock();
+    let config = config.as_ref().unwrap();
+
+    let defaults = HealthThresholds::default();
+
+    let thresholds = HealthThresholds {
+        max_disconnects: config
+            .get::<u64>("health.max_disconnects")
+            .unwrap_or(defaults.max_disconnects),
+        max_failed_writes: config
+            .get::<u64>("health.max_failed_writes")
+            .unwrap_or(defaults.max_failed_writes),
+        max_reinit_attempts: config
+            .get::<u64>("health.max_reinit_attempts")
+            .unwrap_or(defaults.max_reinit_attempts),
+        max_failed_inits: config
+            .get::<u64>("health.max_failed_inits")
+            .unwrap_or(defaults.max_failed_inits),
+    };
+
+    *THRESHOLDS.write() = thresholds;
+
+    Ok(())
+}
+
+pub fn save_device_health() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("device-health.state");
+
+    let data = toml::to_string_pretty(&*DEVICE_HEALTH.read())?;
+    state_file::write(&file_name, &data)?;
+
+    Ok(())
+}
+
+pub fn load_device_health() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("device-health.state");
+
+    let device_health: BTreeMap<DeviceMakeModelSerial, DeviceHealth> =
+        state_file::load(&file_name, |data| Ok(toml::from_str(data)?))?;
+
+    for (key, health) in device_health.iter() {
+        let warnings = health.warnings();
+
+        if !warnings.is_empty() {
+            warn!(
+                "Device {} seems to be unhealthy: {}",
+                key,
+                warnings.join("; ")
+            );
+        }
+    }
+
+    *DEVICE_HEALTH.write() = device_health;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reinit_attempts_back_off() {
+        let start = Instant::now();
+        let mut backoff = ReinitBackoff::new(start);
+
+        // the first attempt is made right away, but only once per cycle
+        assert!(backoff.attempt(start));
+        assert!(!backoff.attempt(start + Duration::from_millis(100)));
+
+        let initial = Duration::from_millis(constants::REINIT_INITIAL_BACKOFF_MILLIS);
+
+        let second = start + initial;
+        assert!(backoff.attempt(second));
+
+        // the delay has been doubled
+        assert!(!backoff.attempt(second + initial));
+        assert!(backoff.attempt(second + initial * 2));
+
+        // and is capped
+        let mut now = second + initial * 2;
+
+        for _ in 0..32 {
+            now += Duration::from_millis(constants::REINIT_MAX_BACKOFF_MILLIS);
+            assert!(backoff.attempt(now));
+        }
+    }
+}
@@ -0,0 +1,218 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::plugins::ProfilesPlugin;
+use crate::{constants, game_mode, hardware_mode, profiles, SDK_SUPPORT_ACTIVE};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HotkeyError {
+    #[error("Invalid hotkey binding: {msg}")]
+    InvalidBinding { msg: String },
+}
+
+lazy_static! {
+    /// Global hotkeys, as configured in the [hotkeys] section of eruption.conf
+    static ref HOTKEYS: Arc<RwLock<Vec<Hotkey>>> = Arc::new(RwLock::new(vec![]));
+
+    /// Global "hotkeys enabled" flag
+    pub static ref HOTKEYS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+    /// Do not process hotkeys while an SDK client is in control of the devices
+    static ref SUSPEND_WHILE_SDK_ACTIVE: AtomicBool = AtomicBool::new(true);
+}
+
+/// A global hotkey, switches to a slot or to a profile, toggles game mode, or suspends
+/// streaming when all of its keys are pressed
+#[derive(Debug, Clone, Deserialize)]
+pub struct Hotkey {
+    /// Key indices of the keys that make up the hotkey
+    pub keys: Vec<u8>,
+
+    /// Slot number to switch to, starting at 1
+    #[serde(default)]
+    pub slot: Option<usize>,
+
+    /// Profile to switch to, relative paths are looked up in the profile directories
+    #[serde(default)]
+    pub profile: Option<PathBuf>,
+
+    /// Enable or disable game mode
+    #[serde(default)]
+    pub toggle_game_mode: bool,
+
+    /// Suspend streaming and return the devices to their firmware effects, or resume streaming
+    #[serde(default)]
+    pub toggle_hardware_mode: bool,
+}
+
+impl Hotkey {
+    fn validate(&self) -> Result<()> {
+        if self.keys.is_empty() {
+            return Err(HotkeyError::InvalidBinding {
+                msg: "No keys specified".to_owned(),
+            }
+            .into());
+        }
+
+        let num_actions = [
+            self.slot.is_some(),
+            self.profile.is_some(),
+            self.toggle_game_mode,
+            self.toggle_hardware_mode,
+        ]
+        .iter()
+        .filter(|action| **action)
+        .count();
+
+        if num_actions != 1 {
+            return Err(HotkeyError::InvalidBinding {
+                msg: "Exactly one of 'slot', 'profile', 'toggle_game_mode' or 'toggle_hardware_mode' needs to be specified".to_owned(),
+            }
+            .into());
+        }
+
+        match self.slot {
+            Some(slot) if !(1..=constants::NUM_SLOTS).contains(&slot) => {
+                Err(HotkeyError::InvalidBinding {
+                    msg: format!("Slot number out of range: {slot}"),
+                }
+                .into())
+            }
+
+            _ => Ok(()),
+        }
+    }
+
+    fn trigger(&self) {
+        if self.toggle_game_mode {
+            info!("Hotkey triggered: Toggling game mode");
+
+            game_mode::toggle();
+        } else if self.toggle_hardware_mode {
+            info!("Hotkey triggered: Toggling hardware mode");
+
+            hardware_mode::toggle();
+        } else if let Some(slot) = self.slot {
+            info!("Hotkey triggered: Switching to slot #{}", slot);
+
+            ProfilesPlugin::switch_to_slot(slot - 1);
+        } else if let Some(profile) = &self.profile {
+            let profile_file = resolve_profile_path(profile);
+
+            info!(
+                "Hotkey triggered: Switching to profile: {}",
+                profile_file.display()
+            );
+
+            ProfilesPlugin::switch_to_profile(profile_file.to_string_lossy().to_string());
+        }
+    }
+}
+
+/// Load the global hotkeys from the configuration file
+pub fn load_hotkeys() -> Result<()> {
+    let config = crate::CONFIG.lock();
+    let config = config.as_ref().unwrap();
+
+    let enabled = config.get::<bool>("hotkeys.enabled").unwrap_or(true);
+    HOTKEYS_ENABLED.store(enabled, Ordering::SeqCst);
+
+    let suspend_while_sdk_active = config
+        .get::<bool>("hotkeys.suspend_while_sdk_active")
+        .unwrap_or(true);
+    SUSPEND_WHILE_SDK_ACTIVE.store(suspend_while_sdk_active, Ordering::SeqCst);
+
+    let bindings = config
+        .get::<Vec<Hotkey>>("hotkeys.bindings")
+        .unwrap_or_else(|_| vec![]);
+
+    let mut hotkeys = vec![];
+
+    for hotkey in bindings {
+        match hotkey.validate() {
+            Ok(()) => hotkeys.push(hotkey),
+            Err(e) => error!("Skipping hotkey {:?}: {}", hotkey.keys, e),
+        }
+    }
+
+    info!("Loaded {} global hotkey(s)", hotkeys.len());
+
+    *HOTKEYS.write() = hotkeys;
+
+    Ok(())
+}
+
+/// Check whether a key press completed one of the global hotkeys, and if so, trigger it.
+/// Returns `true` if a hotkey has been triggered
+pub fn process_key_down(index: u8, pressed_keys: &HashMap<u8, Instant>) -> bool {
+    if !HOTKEYS_ENABLED.load(Ordering::SeqCst)
+        || (SUSPEND_WHILE_SDK_ACTIVE.load(Ordering::SeqCst)
+            && SDK_SUPPORT_ACTIVE.load(Ordering::SeqCst))
+    {
+        return false;
+    }
+
+    // only the key that completes a hotkey may trigger it, so that
+    // a hotkey is not triggered repeatedly while it is being held
+    let hotkey = HOTKEYS
+        .read()
+        .iter()
+        .find(|hotkey| {
+            hotkey.keys.contains(&index)
+                && hotkey.keys.iter().all(|key| pressed_keys.contains_key(key))
+        })
+        .cloned();
+
+    match hotkey {
+        Some(hotkey) => {
+            hotkey.trigger();
+
+            true
+        }
+
+        None => false,
+    }
+}
+
+/// Look up a relative profile path in the profile directories
+pub fn resolve_profile_path(profile: &Path) -> PathBuf {
+    if profile.is_absolute() {
+        return profile.to_path_buf();
+    }
+
+    profiles::get_profile_dirs()
+        .iter()
+        .map(|dir| dir.join(profile))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| profile.to_path_buf())
+}
@@ -0,0 +1,389 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use flume::Sender;
+use lazy_static::lazy_static;
+use log::*;
+use nix::poll::{poll, PollFd, PollFlags};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use udev::{EventType, MonitorBuilder};
+
+use crate::hwdevices::{self, DeviceTrait};
+use crate::plugins::sdk_support::{self, HotplugInfo};
+use crate::scripting::script;
+use crate::{
+    constants, device_defaults, device_id, key_geometry, standby, FAILED_TXS, LUA_TXS, QUIT,
+    REENTER_MAIN_LOOP,
+};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Set if supported devices have been plugged in, they will be attached
+    /// the next time the main loop is re-entered
+    static ref ATTACH_PENDING: AtomicBool = AtomicBool::new(false);
+}
+
+/// A device has been plugged in or removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotplugEvent {
+    Added {
+        usb_vid: u16,
+        usb_pid: u16,
+    },
+
+    /// `serial` is the serial number as reported by udev, `None` if the device has none
+    Removed {
+        usb_vid: u16,
+        usb_pid: u16,
+        serial: Option<String>,
+    },
+}
+
+impl HotplugEvent {
+    /// Returns `true` if this is the removal of the device with the specified USB IDs and
+    /// serial number. Devices without a serial number can not be told apart, so in that
+    /// case all devices with matching USB IDs are considered to be removed
+    fn is_removal_of(&self, usb_vid: u16, usb_pid: u16, serial: Option<&str>) -> bool {
+        match self {
+            HotplugEvent::Removed {
+                usb_vid: removed_vid,
+                usb_pid: removed_pid,
+                serial: removed_serial,
+            } if *removed_vid == usb_vid && *removed_pid == usb_pid => {
+                match (
+                    removed_serial.as_deref().filter(|s| !s.is_empty()),
+                    serial.filter(|s| !s.is_empty()),
+                ) {
+                    (Some(removed_serial), Some(serial)) => {
+                        normalize_serial(removed_serial) == normalize_serial(serial)
+                    }
+
+                    _ => true,
+                }
+            }
+
+            _ => false,
+        }
+    }
+}
+
+/// udev replaces whitespace and special characters in serial numbers, so both
+/// representations are brought into the same form before comparing them
+fn normalize_serial(serial: &str) -> String {
+    serial
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "#+-.:=@_".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Spawn a thread that monitors udev for supported USB devices being plugged in
+/// or removed. Bursts of events, e.g. from a device that registers multiple
+/// interfaces, are debounced and sent as a single batch to the main loop
+pub fn spawn_udev_monitor_thread(hotplug_tx: Sender<Vec<HotplugEvent>>) -> Result<()> {
+    let socket = MonitorBuilder::new()?
+        .match_subsystem_devtype("usb", "usb_device")?
+        .listen()?;
+
+    thread::Builder::new()
+        .name("udev-monitor".into())
+        .spawn(move || -> Result<()> {
+            #[cfg(feature = "profiling")]
+            coz::thread_init();
+
+            let mut pending: Vec<HotplugEvent> = Vec::new();
+            let mut last_event = Instant::now();
+
+            loop {
+                if QUIT.load(Ordering::SeqCst) {
+                    break Ok(());
+                }
+
+                let mut fds = [PollFd::new(socket.as_raw_fd(), PollFlags::POLLIN)];
+
+                if poll(&mut fds, constants::HOTPLUG_POLL_TIMEOUT_MILLIS)? > 0 {
+                    for event in socket.iter() {
+                        if let Some(event) = parse_event(&event) {
+                            debug!("udev: {:?}", event);
+
+                            if !pending.contains(&event) {
+                                pending.push(event);
+                            }
+
+                            last_event = Instant::now();
+                        }
+                    }
+                }
+
+                // wait until the devices have settled
+                if !pending.is_empty()
+                    && last_event.elapsed()
+                        >= Duration::from_millis(constants::HOTPLUG_DEBOUNCE_MILLIS)
+                {
+                    hotplug_tx.send(std::mem::take(&mut pending))?;
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Returns the hotplug event for a supported device, or `None` if the
+/// event is irrelevant to us
+fn parse_event(event: &udev::Event) -> Option<HotplugEvent> {
+    // the PRODUCT property is available on add as well as on remove events,
+    // e.g.: "1e7d/307a/100"
+    let product = event
+        .device()
+        .property_value("PRODUCT")?
+        .to_str()?
+        .to_owned();
+
+    let mut parts = product.split('/');
+    let usb_vid = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let usb_pid = u16::from_str_radix(parts.next()?, 16).ok()?;
+
+    let is_supported = hwdevices::DRIVERS
+        .lock()
+        .iter()
+        .any(|d| d.get_usb_vid() == usb_vid && d.get_usb_pid() == usb_pid);
+
+    if !is_supported {
+        return None;
+    }
+
+    match event.event_type() {
+        EventType::Add => Some(HotplugEvent::Added { usb_vid, usb_pid }),
+
+        EventType::Remove => {
+            let serial = event
+                .device()
+                .property_value("ID_SERIAL_SHORT")
+                .and_then(|serial| serial.to_str())
+                .map(str::to_owned);
+
+            Some(HotplugEvent::Removed {
+                usb_vid,
+                usb_pid,
+                serial,
+            })
+        }
+
+        _ => None,
+    }
+}
+
+/// Process a batch of debounced hotplug events, this is called from the main loop.
+/// Removed devices are marked as failed and added devices are scheduled for
+/// attachment; both take effect when the main loop is re-entered
+pub fn process_hotplug_events(events: &[HotplugEvent]) {
+    for event in events {
+        match *event {
+            HotplugEvent::Added { usb_vid, usb_pid } => {
+                info!(
+                    "A supported device has been plugged in: 0x{:x}:0x{:x}",
+                    usb_vid, usb_pid
+                );
+
+                ATTACH_PENDING.store(true, Ordering::SeqCst);
+            }
+
+            HotplugEvent::Removed {
+                usb_vid,
+                usb_pid,
+                ref serial,
+            } => {
+                info!(
+                    "A supported device has been removed: 0x{:x}:0x{:x} (serial: {})",
+                    usb_vid,
+                    usb_pid,
+                    serial.as_deref().unwrap_or("<none>")
+                );
+
+                fail_devices(event);
+            }
+        }
+    }
+
+    REENTER_MAIN_LOOP.store(true, Ordering::SeqCst);
+}
+
+/// Mark the devices that have been removed according to `event` as failed, so that they
+/// will be unplugged
+fn fail_devices(event: &HotplugEvent) {
+    fn fail(device: &mut dyn DeviceTrait) {
+        device
+            .fail()
+            .unwrap_or_else(|e| error!("Could not mark the device as failed: {}", e));
+    }
+
+    let matches = |device: &dyn DeviceTrait| {
+        event.is_removal_of(
+            device.get_usb_vid(),
+            device.get_usb_pid(),
+            device.get_serial(),
+        )
+    };
+
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        let mut device = device.write();
+
+        if matches(device.as_device()) {
+            fail(device.as_device_mut());
+        }
+    }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        let mut device = device.write();
+
+        if matches(device.as_device()) {
+            fail(device.as_device_mut());
+        }
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        let mut device = device.write();
+
+        if matches(device.as_device()) {
+            fail(device.as_device_mut());
+        }
+    }
+}
+
+/// Attach the devices that have been plugged in while in standby, after resuming
+pub fn attach_deferred_devices() {
+    if ATTACH_PENDING.load(Ordering::SeqCst) {
+        REENTER_MAIN_LOOP.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Returns the class of a misc device, as passed to the Lua hotplug event handlers
+pub fn misc_device_class(device: &dyn DeviceTrait) -> &'static str {
+    if device.as_headset_device().is_some() {
+        "headset"
+    } else {
+        "misc"
+    }
+}
+
+/// Notify the Lua VMs that `device` of the device class `class` has been attached
+pub fn notify_device_attached(class: &str, device: &dyn DeviceTrait) {
+    device_defaults::device_attached(device);
+    key_geometry::invalidate();
+
+    broadcast(script::Message::DeviceAttached(
+        class.to_string(),
+        device_id::device_id(device),
+    ));
+}
+
+/// Notify the Lua VMs that `device` of the device class `class` has been removed
+pub fn notify_device_removed(class: &str, device: &dyn DeviceTrait) {
+    key_geometry::invalidate();
+
+    broadcast(script::Message::DeviceRemoved(
+        class.to_string(),
+        device_id::device_id(device),
+    ));
+}
+
+fn broadcast(message: script::Message) {
+    for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+        if !FAILED_TXS.read().contains(&idx) {
+            lua_tx.send(message.clone()).unwrap_or_else(|e| {
+                error!("Could not send a pending hotplug event to a Lua VM: {}", e)
+            });
+        } else {
+            warn!("Not sending a message to a failed tx");
+        }
+    }
+}
+
+/// Attach devices that have been plugged in since the last call. This has to be
+/// called while the main loop is not running, since it modifies the device lists.
+/// Devices that are plugged in while in standby are attached after resuming
+pub fn attach_pending_devices() -> Result<()> {
+    if standby::is_active() {
+        return Ok(());
+    }
+
+    if ATTACH_PENDING.swap(false, Ordering::SeqCst) {
+        sdk_support::claim_hotplugged_devices(&HotplugInfo::default())?;
+
+        // the main loop is not running, so there are no locks that would need to be dropped
+        REENTER_MAIN_LOOP.store(false, Ordering::SeqCst);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn removed(serial: Option<&str>) -> HotplugEvent {
+        HotplugEvent::Removed {
+            usb_vid: 0x1e7d,
+            usb_pid: 0x307a,
+            serial: serial.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn only_the_removed_device_is_failed() {
+        let event = removed(Some("AB12"));
+
+        assert!(event.is_removal_of(0x1e7d, 0x307a, Some("AB12")));
+
+        // a second device of the same model stays attached
+        assert!(!event.is_removal_of(0x1e7d, 0x307a, Some("CD34")));
+        assert!(!event.is_removal_of(0x1e7d, 0x3098, Some("AB12")));
+
+        assert!(!HotplugEvent::Added {
+            usb_vid: 0x1e7d,
+            usb_pid: 0x307a
+        }
+        .is_removal_of(0x1e7d, 0x307a, Some("AB12")));
+    }
+
+    #[test]
+    fn devices_without_serial_are_matched_by_usb_ids() {
+        assert!(removed(None).is_removal_of(0x1e7d, 0x307a, Some("AB12")));
+        assert!(removed(Some("AB12")).is_removal_of(0x1e7d, 0x307a, None));
+        assert!(removed(Some("")).is_removal_of(0x1e7d, 0x307a, Some("CD34")));
+    }
+
+    #[test]
+    fn serials_are_compared_like_udev_reports_them() {
+        assert!(removed(Some("AB_12")).is_removal_of(0x1e7d, 0x307a, Some("AB 12")));
+        assert!(!removed(Some("AB_12")).is_removal_of(0x1e7d, 0x307a, Some("AB 13")));
+    }
+}
@@ -31,9 +31,9 @@ use std::{sync::Arc, thread};
 use crate::constants;
 
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
-    KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind,
-    MouseDeviceTrait, RGBA,
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, DisplayDeviceTrait,
+    HwDeviceError, KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode,
+    LedKind, MouseDeviceTrait, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -57,14 +57,14 @@ pub fn bind_hiddev(
     //     device.vendor_id() == usb_vid
     //         && device.product_id() == usb_pid
     //         && device.serial_number().unwrap_or_else(|| "") == serial
-    //         && device.interface_number() == CTRL_INTERFACE
+    //         && super::get_interface_number(device) == CTRL_INTERFACE
     // });
 
     let led_dev = hidapi.device_list().find(|&device| {
         device.vendor_id() == usb_vid
             && device.product_id() == usb_pid
             && device.serial_number().unwrap_or("") == serial
-            && device.interface_number() == LED_INTERFACE
+            && super::get_interface_number(device) == LED_INTERFACE
     });
 
     if
@@ -587,6 +587,14 @@ impl DeviceTrait for CorsairStrafe {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         None
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
 }
 
 impl KeyboardDeviceTrait for CorsairStrafe {
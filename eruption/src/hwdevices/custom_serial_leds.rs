@@ -29,8 +29,8 @@ use std::time::Duration;
 use crate::hwdevices::DeviceStatus;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, MiscDeviceTrait,
-    MouseDeviceTrait, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceTrait, DisplayDeviceTrait, HwDeviceError,
+    MiscDeviceTrait, MouseDeviceTrait, RGBA,
 };
 
 const BAUD_RATE: u32 = 460800;
@@ -200,6 +200,14 @@ impl DeviceTrait for CustomSerialLeds {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         None
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
 }
 
 impl MiscDeviceTrait for CustomSerialLeds {
@@ -29,8 +29,9 @@ use std::{any::Any, collections::HashMap};
 use crate::hwdevices::DeviceStatus;
 
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, KeyboardDevice,
-    KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind, MouseDeviceTrait, RGBA,
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, DisplayDeviceTrait,
+    HwDeviceError, KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode,
+    LedKind, MouseDeviceTrait, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -198,6 +199,14 @@ impl DeviceTrait for GenericKeyboard {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         None
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
 }
 
 impl KeyboardDeviceTrait for GenericKeyboard {
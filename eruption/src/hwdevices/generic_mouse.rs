@@ -29,8 +29,8 @@ use parking_lot::RwLock;
 use crate::hwdevices::DeviceStatus;
 
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, MouseDevice,
-    MouseDeviceTrait, MouseHidEvent, RGBA,
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, DisplayDeviceTrait,
+    HwDeviceError, MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -192,6 +192,14 @@ impl DeviceTrait for GenericMouse {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         Some(self as &mut dyn MouseDeviceTrait)
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
 }
 
 impl MouseDeviceTrait for GenericMouse {
@@ -26,11 +26,20 @@ use log::*;
 use parking_lot::{Mutex, RwLock};
 use serde::{self, Deserialize};
 use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
 use std::u8;
 use std::{any::Any, sync::Arc, thread};
-use std::{path::PathBuf, time::Duration};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 use udev::Enumerator;
 
+use crate::constants;
+use crate::glyphs;
+use crate::key_geometry;
+use crate::zones;
+
 mod corsair_strafe;
 mod custom_serial_leds;
 mod generic_keyboard;
@@ -139,7 +148,7 @@ lazy_static! {
         // Supported miscellaneous devices
 
         // ROCCAT/Turtle Beach
-        MiscDriver::register("ROCCAT/Turtle Beach", "Elo 7.1 Air", 0x1e7d, 0x3a37, &roccat_elo_71_air::bind_hiddev, MaturityLevel::Testing),
+        MiscDriver::register_headset("ROCCAT/Turtle Beach", "Elo 7.1 Air", 0x1e7d, 0x3a37, &roccat_elo_71_air::bind_hiddev, MaturityLevel::Testing),
 
         MiscDriver::register("ROCCAT", "Aimo Pad Wide", 0x1e7d, 0x343b, &roccat_aimo_pad::bind_hiddev, MaturityLevel::Stable),
 
@@ -192,8 +201,17 @@ pub enum HwDeviceError {
     #[error("LED map has an invalid size")]
     LedMapError {},
 
+    #[error("Framebuffer has an invalid size")]
+    FramebufferError {},
+
     #[error("Could not map an evdev event code to a key or button")]
     MappingError {},
+
+    #[error("Unsupported polling rate: {rate} Hz")]
+    InvalidPollRate { rate: i32 },
+
+    #[error("Unsupported lift-off distance: {dcu}")]
+    InvalidDcuConfig { dcu: i32 },
 }
 
 pub trait DriverMetadata {
@@ -351,6 +369,29 @@ impl MiscDriver<'static> {
     }
 }
 
+impl MiscDriver<'static> {
+    /// Register a driver for a headset. Headsets are managed as misc devices, and
+    /// additionally implement the `HeadsetDeviceTrait`
+    pub fn register_headset(
+        device_make: &'static str,
+        device_name: &'static str,
+        usb_vid: u16,
+        usb_pid: u16,
+        bind_fn: &'static (dyn Fn(&HidApi, u16, u16, &str) -> Result<MiscDevice> + Sync + Send),
+        status: MaturityLevel,
+    ) -> Box<(dyn DriverMetadata + Sync + Send + 'static)> {
+        Box::new(MiscDriver {
+            device_make,
+            device_name,
+            device_class: DeviceClass::Headset,
+            usb_vid,
+            usb_pid,
+            bind_fn,
+            status,
+        })
+    }
+}
+
 impl DriverMetadata for MiscDriver<'static> {
     fn get_device_class(&self) -> DeviceClass {
         self.device_class
@@ -431,6 +472,25 @@ pub enum DeviceClass {
     Keyboard,
     Mouse,
     Misc,
+    Headset,
+}
+
+/// A LED of a device that is not part of the canvas, like e.g. a logo LED
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AuxLed {
+    pub name: String,
+
+    /// Set if the LED may only be switched on or off, any color other than black switches it on
+    pub monochrome: bool,
+}
+
+impl AuxLed {
+    pub fn new(name: &str, monochrome: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            monochrome,
+        }
+    }
 }
 
 /// Represents an RGBA color value
@@ -586,6 +646,109 @@ impl Default for DeviceStatus {
     }
 }
 
+/// Results of a device self-test
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct SelfTestReport {
+    /// Result of the driver provided HID report round-trip check, `None` if unsupported
+    pub hid_roundtrip_ok: Option<bool>,
+
+    /// Number of LED map writes that have been performed
+    pub writes: usize,
+
+    pub write_latency_avg_micros: u64,
+    pub write_latency_max_micros: u64,
+
+    /// Indices of the LEDs whose LED map write failed. The LEDs themselves can not be
+    /// observed, so an LED that is broken but accepted its write is not listed here
+    pub failed_writes: Vec<usize>,
+
+    /// Errors that occurred during the self-test
+    pub errors: Vec<String>,
+}
+
+/// Run the generic part of a device self-test: Cycle the RGB primaries on all LEDs,
+/// then light up each LED individually while measuring the write latency
+pub fn self_test_leds<F>(report: &mut SelfTestReport, mut send_led_map: F)
+where
+    F: FnMut(&[RGBA]) -> Result<()>,
+{
+    let mut latencies = vec![];
+
+    let off = RGBA {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 0,
+    };
+
+    let primaries = [
+        RGBA {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        },
+        RGBA {
+            r: 0,
+            g: 255,
+            b: 0,
+            a: 255,
+        },
+        RGBA {
+            r: 0,
+            g: 0,
+            b: 255,
+            a: 255,
+        },
+    ];
+
+    for color in primaries.iter() {
+        let led_map = [*color; constants::CANVAS_SIZE];
+
+        let start = Instant::now();
+
+        if let Err(e) = send_led_map(&led_map) {
+            report.errors.push(format!("Could not set all LEDs: {e}"));
+        }
+
+        latencies.push(start.elapsed());
+
+        thread::sleep(Duration::from_millis(constants::SELF_TEST_PRIMARY_MILLIS));
+    }
+
+    for index in 0..constants::CANVAS_SIZE {
+        let mut led_map = [off; constants::CANVAS_SIZE];
+        led_map[index] = primaries[index % primaries.len()];
+
+        let start = Instant::now();
+
+        if send_led_map(&led_map).is_err() {
+            report.failed_writes.push(index);
+        }
+
+        latencies.push(start.elapsed());
+    }
+
+    send_led_map(&[off; constants::CANVAS_SIZE]).unwrap_or_else(|e| {
+        report
+            .errors
+            .push(format!("Could not switch off the LEDs: {e}"))
+    });
+
+    report.writes = latencies.len();
+
+    if !latencies.is_empty() {
+        let total: Duration = latencies.iter().sum();
+
+        report.write_latency_avg_micros = (total / latencies.len() as u32).as_micros() as u64;
+        report.write_latency_max_micros = latencies
+            .iter()
+            .max()
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+    }
+}
+
 /// Non 'Plug and Play' device, may be declared in .config file
 #[derive(Debug, Clone)]
 pub struct NonPnPDevice {
@@ -623,6 +786,8 @@ pub enum Capability {
     Debounce,
     DebounceTimeSelection,
     AngleSnapping,
+
+    Display,
 }
 
 /// Information about a generic device
@@ -705,6 +870,53 @@ pub trait DeviceTrait: DeviceInfoTrait {
 
     fn as_mouse_device(&self) -> Option<&dyn MouseDeviceTrait>;
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait>;
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait>;
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait>;
+
+    /// Returns the named zones of the device, like e.g. the WASD keys or the numpad
+    fn get_zones(&self) -> Vec<zones::Zone> {
+        vec![]
+    }
+
+    /// Returns the maximum rate in frames per second at which the device is able
+    /// to accept LED map updates, or `None` if the device keeps up with the frame rate
+    fn get_max_update_rate(&self) -> Option<u32> {
+        None
+    }
+
+    /// Run a driver provided diagnostic, e.g. verify that HID reports round-trip
+    /// through the device, and record the results in `report`
+    fn self_test(&mut self, _report: &mut SelfTestReport) -> Result<()> {
+        Err(HwDeviceError::OpNotSupported {}.into())
+    }
+
+    /// Hand control of the LEDs back to the firmware of the device, so that its
+    /// on-board effects are shown while Eruption does not stream any LED maps.
+    /// The device is brought back into streaming mode by `send_init_sequence()`
+    fn set_hardware_mode(&mut self) -> Result<()> {
+        Err(HwDeviceError::OpNotSupported {}.into())
+    }
+
+    /// Returns the LEDs of the device that are not part of the canvas, like e.g. a logo
+    /// LED, or the LEDs that indicate the active profile or DPI level
+    fn get_aux_leds(&self) -> Vec<AuxLed> {
+        vec![]
+    }
+
+    /// Set the color of the auxiliary LED `name`, see `get_aux_leds()`
+    fn set_aux_led(&mut self, _name: &str, _color: RGBA) -> Result<()> {
+        Err(HwDeviceError::OpNotSupported {}.into())
+    }
+
+    /// Returns the headset specific functions, if the device is a headset
+    fn as_headset_device(&self) -> Option<&dyn HeadsetDeviceTrait> {
+        None
+    }
+
+    fn as_headset_device_mut(&mut self) -> Option<&mut dyn HeadsetDeviceTrait> {
+        None
+    }
 }
 
 // Generic device trait
@@ -783,6 +995,61 @@ pub trait KeyboardDeviceTrait: DeviceTrait {
 
     /// Returns the indices of the keys in column `col`
     fn get_col_topology(&self, col: usize) -> &'static [u8];
+
+    /// Returns the physical positions of the keys. Drivers that don't ship a table return
+    /// an empty vector, the positions are approximated from the row topology then
+    fn get_key_positions(&self) -> Vec<key_geometry::KeyPosition> {
+        vec![]
+    }
+
+    /// Show the rasterized label `label` on the display of the key `key_index`,
+    /// only supported by keyboards that feature per-key displays
+    fn send_key_label(&mut self, _key_index: usize, _label: &glyphs::Bitmap) -> Result<()> {
+        Err(HwDeviceError::OpNotSupported {}.into())
+    }
+}
+
+/// The USB polling rates of mice in Hz
+pub const POLL_RATES: [i32; 4] = [125, 250, 500, 1000];
+
+/// Returns the index of the polling rate `rate` in `POLL_RATES`, as used by the device protocols
+pub fn poll_rate_to_index(rate: i32) -> Result<u8> {
+    POLL_RATES
+        .iter()
+        .position(|r| *r == rate)
+        .map(|index| index as u8)
+        .ok_or_else(|| HwDeviceError::InvalidPollRate { rate }.into())
+}
+
+/// The settings of the distance control unit (DCU) of mouse sensors, i.e. the lift-off
+/// distance, from the lowest to the highest distance
+pub const DCU_CONFIGS: RangeInclusive<i32> = 0..=3;
+
+/// Returns the DCU setting `dcu` as used by the device protocols, one of `DCU_CONFIGS`
+pub fn dcu_config_to_byte(dcu: i32) -> Result<u8> {
+    if DCU_CONFIGS.contains(&dcu) {
+        Ok(dcu as u8)
+    } else {
+        Err(HwDeviceError::InvalidDcuConfig { dcu }.into())
+    }
+}
+
+/// A step of the surface calibration of a mouse sensor, also known as the tracking control
+/// unit (TCU). The sensor measures the surface while the mouse is being moved across it,
+/// until the measurement is either accepted or cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceCalibration {
+    Start,
+    Accept,
+    Cancel,
+}
+
+/// Returns the polling rate in Hz of the index `index` in `POLL_RATES`
+pub fn poll_rate_from_index(index: u8) -> Result<i32> {
+    POLL_RATES
+        .get(index as usize)
+        .copied()
+        .ok_or_else(|| HwDeviceError::InvalidResult {}.into())
 }
 
 /// Device like e.g. a supported mouse
@@ -795,8 +1062,10 @@ pub trait MouseDeviceTrait: DeviceTrait {
 
     fn set_dpi(&mut self, dpi: i32) -> Result<()>;
 
+    /// Get the USB polling rate in Hz, one of `POLL_RATES`
     fn get_rate(&self) -> Result<i32>;
 
+    /// Set the USB polling rate in Hz, one of `POLL_RATES`
     fn set_rate(&mut self, rate: i32) -> Result<()>;
 
     fn get_dcu_config(&self) -> Result<i32>;
@@ -811,6 +1080,11 @@ pub trait MouseDeviceTrait: DeviceTrait {
 
     fn set_debounce(&mut self, debounce: bool) -> Result<()>;
 
+    /// Run the step `step` of the surface calibration of the sensor
+    fn calibrate_surface(&mut self, _step: SurfaceCalibration) -> Result<()> {
+        Err(HwDeviceError::OpNotSupported {}.into())
+    }
+
     /// Set the device specific brightness
     fn set_local_brightness(&mut self, brightness: i32) -> Result<()>;
 
@@ -867,6 +1141,51 @@ pub trait MiscDeviceTrait: DeviceTrait {
     fn set_led_off_pattern(&mut self) -> Result<()>;
 }
 
+/// Devices that feature an LCD/OLED screen
+pub trait DisplayDeviceTrait {
+    /// Returns the resolution (width, height) of the display in pixels
+    fn get_display_resolution(&self) -> (usize, usize);
+
+    /// Send a framebuffer to the display. The framebuffer is expected to
+    /// match the resolution of the display, pixels are stored row by row
+    fn send_framebuffer(&mut self, framebuffer: &[RGBA]) -> Result<()>;
+}
+
+/// State changes reported by a headset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadsetEvent {
+    /// The headset has been connected to, or disconnected from its transceiver
+    ConnectionChanged { connected: bool },
+
+    /// The battery level changed, in percent
+    BatteryLevelChanged { level: u8 },
+
+    /// The balance between chat and game audio changed, 0 = game audio only, 100 = chat only
+    ChatMixChanged { mix: u8 },
+}
+
+/// Headsets, in addition to the functions of misc devices
+pub trait HeadsetDeviceTrait {
+    /// Returns the battery level in percent, or `None` if it is unknown, e.g. because
+    /// the headset is not connected to its transceiver
+    fn get_battery_level(&self) -> Option<u8>;
+
+    /// Returns the balance between chat and game audio in percent (0 = game audio only,
+    /// 100 = chat only), or `None` if the headset does not feature a chat mixer
+    fn get_chat_mix(&self) -> Option<u8> {
+        None
+    }
+
+    /// Returns `true` if the LED ring shows the canvas, otherwise it is switched off
+    fn is_led_ring_enabled(&self) -> bool;
+
+    /// Switch the LED ring on or off
+    fn set_led_ring_enabled(&mut self, enabled: bool) -> Result<()>;
+
+    /// Returns the events that have been reported by the headset since the last call
+    fn take_events(&mut self) -> Vec<HeadsetEvent>;
+}
+
 /// Misc Serial Devices
 pub trait MiscSerialDeviceTrait: SerialDeviceTrait {
     /// Send RGBA LED map to the device
@@ -881,6 +1200,70 @@ pub trait MiscSerialDeviceTrait: SerialDeviceTrait {
     fn set_led_off_pattern(&mut self) -> Result<()>;
 }
 
+/// Scale a framebuffer of size `src` to the resolution `dst` of a display (nearest neighbor)
+pub fn scale_framebuffer(
+    framebuffer: &[RGBA],
+    src: (usize, usize),
+    dst: (usize, usize),
+) -> Result<Vec<RGBA>> {
+    let (src_width, src_height) = src;
+    let (dst_width, dst_height) = dst;
+
+    if src_width == 0 || src_height == 0 || framebuffer.len() != src_width * src_height {
+        return Err(HwDeviceError::FramebufferError {}.into());
+    }
+
+    if src == dst {
+        return Ok(framebuffer.to_vec());
+    }
+
+    let mut result = Vec::with_capacity(dst_width * dst_height);
+
+    for y in 0..dst_height {
+        let sy = y * src_height / dst_height;
+
+        for x in 0..dst_width {
+            let sx = x * src_width / dst_width;
+
+            result.push(framebuffer[sy * src_width + sx]);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Returns the USB interface number of a HID device. Virtual devices, like e.g. the
+/// uhid devices created by the integration tests, do not have a USB parent device,
+/// so we fall back to the interface number encoded in the physical path of the
+/// device (e.g. "usb-0000:00:14.0-2/input1"), like it is set by the usbhid driver
+pub fn get_interface_number(device_info: &hidapi::DeviceInfo) -> i32 {
+    let interface_number = device_info.interface_number();
+
+    if interface_number >= 0 {
+        interface_number
+    } else {
+        get_interface_number_from_sysfs(device_info).unwrap_or(interface_number)
+    }
+}
+
+fn get_interface_number_from_sysfs(device_info: &hidapi::DeviceInfo) -> Option<i32> {
+    let path = PathBuf::from(device_info.path().to_string_lossy().to_string());
+    let name = path.file_name()?;
+
+    let uevent = std::fs::read_to_string(
+        PathBuf::from("/sys/class/hidraw/")
+            .join(name)
+            .join("device/uevent"),
+    )
+    .ok()?;
+
+    uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("HID_PHYS="))
+        .and_then(|phys| phys.rsplit_once("/input"))
+        .and_then(|(_, number)| number.parse::<i32>().ok())
+}
+
 /// Returns true if the USB device is blacklisted in the global configuration
 pub fn is_device_blacklisted(vid: u16, pid: u16) -> Result<bool> {
     let config = crate::CONFIG.lock();
@@ -901,6 +1284,8 @@ pub fn is_device_blacklisted(vid: u16, pid: u16) -> Result<bool> {
                 }
             } else if table["entry_type"].clone().into_string()? == "device" {
                 /* skip device declarations */
+            } else if table["entry_type"].clone().into_string()? == "quirks" {
+                /* skip device quirks */
             } else {
                 error!("Invalid 'entry_type' specified in the configuration file");
             }
@@ -913,6 +1298,51 @@ pub fn is_device_blacklisted(vid: u16, pid: u16) -> Result<bool> {
         Ok(false)
     }
 }
+
+/// Returns the maximum LED map update rate of a device. A `max_update_rate` declared in
+/// a "quirks" entry in eruption.conf takes precedence over the rate supplied by the driver,
+/// a rate of `0` lifts the limit
+pub fn get_max_update_rate(device: &dyn DeviceTrait) -> Option<u32> {
+    let vid = device.get_usb_vid();
+    let pid = device.get_usb_pid();
+
+    let config = crate::CONFIG.lock();
+
+    if let Some(config) = config.as_ref() {
+        let devices = config.get_array("devices").unwrap_or_else(|_e| vec![]);
+
+        for entry in devices.iter() {
+            let table = match entry.clone().into_table() {
+                Ok(table) => table,
+                Err(_) => continue,
+            };
+
+            let is_quirks = table
+                .get("entry_type")
+                .and_then(|v| v.clone().into_string().ok())
+                .is_some_and(|v| v == "quirks");
+
+            let usb_vid = table
+                .get("vendor_id")
+                .and_then(|v| v.clone().into_int().ok());
+            let usb_pid = table
+                .get("product_id")
+                .and_then(|v| v.clone().into_int().ok());
+
+            if is_quirks && usb_vid == Some(vid as i64) && usb_pid == Some(pid as i64) {
+                if let Some(rate) = table
+                    .get("max_update_rate")
+                    .and_then(|v| v.clone().into_int().ok())
+                {
+                    return u32::try_from(rate).ok().filter(|rate| *rate > 0);
+                }
+            }
+        }
+    }
+
+    device.get_max_update_rate()
+}
+
 /// Returns a Vec of non plug and play devices declared in eruption.conf
 pub fn get_non_pnp_devices() -> Result<Vec<NonPnPDevice>> {
     let mut result = vec![];
@@ -939,6 +1369,8 @@ pub fn get_non_pnp_devices() -> Result<Vec<NonPnPDevice>> {
                 result.push(device);
             } else if table["entry_type"].clone().into_string()? == "blacklist" {
                 /* skip blacklist entries */
+            } else if table["entry_type"].clone().into_string()? == "quirks" {
+                /* skip device quirks */
             } else {
                 error!("Invalid 'entry_type' specified in the configuration file");
             }
@@ -1099,7 +1531,7 @@ pub fn probe_devices() -> Result<(Vec<KeyboardDevice>, Vec<MouseDevice>, Vec<Mis
                             }
                         }
 
-                        DeviceClass::Misc => {
+                        DeviceClass::Misc | DeviceClass::Headset => {
                             info!(
                                 "Found supported misc device: 0x{:x}:0x{:x} ({}) - {} {}",
                                 device_info.vendor_id(),
@@ -1237,7 +1669,9 @@ pub fn probe_devices() -> Result<(Vec<KeyboardDevice>, Vec<MouseDevice>, Vec<Mis
                             }
                         }
 
-                        Ok(DeviceClass::Unknown) | Ok(DeviceClass::Misc) => { /* unknown device class, ignore the device */
+                        Ok(DeviceClass::Unknown)
+                        | Ok(DeviceClass::Misc)
+                        | Ok(DeviceClass::Headset) => { /* unknown device class, ignore the device */
                         }
 
                         Err(e) => {
@@ -1394,7 +1828,7 @@ pub fn probe_devices_hotplug() -> Result<(Vec<KeyboardDevice>, Vec<MouseDevice>,
                             }
                         }
 
-                        DeviceClass::Misc => {
+                        DeviceClass::Misc | DeviceClass::Headset => {
                             info!(
                                 "Found supported misc device: 0x{:x}:0x{:x} ({}) - {} {}",
                                 device_info.vendor_id(),
@@ -1532,7 +1966,9 @@ pub fn probe_devices_hotplug() -> Result<(Vec<KeyboardDevice>, Vec<MouseDevice>,
                             }
                         }
 
-                        Ok(DeviceClass::Unknown) | Ok(DeviceClass::Misc) => { /* unknown device class, ignore the device */
+                        Ok(DeviceClass::Unknown)
+                        | Ok(DeviceClass::Misc)
+                        | Ok(DeviceClass::Headset) => { /* unknown device class, ignore the device */
                         }
 
                         Err(e) => {
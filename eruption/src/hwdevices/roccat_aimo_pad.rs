@@ -31,8 +31,8 @@ use std::{mem::size_of, sync::Arc};
 use crate::constants;
 
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
-    MiscDevice, MiscDeviceTrait, MouseDeviceTrait, RGBA,
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, DisplayDeviceTrait,
+    HwDeviceError, MiscDevice, MiscDeviceTrait, MouseDeviceTrait, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -57,14 +57,14 @@ pub fn bind_hiddev(
         device.vendor_id() == usb_vid
             && device.product_id() == usb_pid
             && device.serial_number().unwrap_or("") == serial
-            && device.interface_number() == LED_INTERFACE // CTRL_INTERFACE
+            && super::get_interface_number(device) == LED_INTERFACE // CTRL_INTERFACE
     });
 
     // let led_dev = hidapi.device_list().find(|&device| {
     //     device.vendor_id() == usb_vid
     //         && device.product_id() == usb_pid
     //         && device.serial_number().unwrap_or("") == serial
-    //         && device.interface_number() == LED_INTERFACE
+    //         && super::get_interface_number(device) == LED_INTERFACE
     // });
 
     if ctrl_dev.is_none()
@@ -560,6 +560,14 @@ impl DeviceTrait for RoccatAimoPad {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         None
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
 }
 
 impl MiscDeviceTrait for RoccatAimoPad {
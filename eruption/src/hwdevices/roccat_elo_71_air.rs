@@ -30,9 +30,12 @@ use std::{mem::size_of, sync::Arc};
 
 use crate::constants::{self, DEVICE_SETTLE_MILLIS};
 
+use crate::zones::Zone;
+
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
-    MiscDevice, MiscDeviceTrait, MouseDeviceTrait, RGBA,
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, DisplayDeviceTrait,
+    HeadsetDeviceTrait, HeadsetEvent, HwDeviceError, MiscDevice, MiscDeviceTrait, MouseDeviceTrait,
+    RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -56,14 +59,14 @@ pub fn bind_hiddev(
         device.vendor_id() == usb_vid
             && device.product_id() == usb_pid
             && device.serial_number().unwrap_or("") == serial
-            && device.interface_number() == LED_INTERFACE // CTRL_INTERFACE
+            && super::get_interface_number(device) == LED_INTERFACE // CTRL_INTERFACE
     });
 
     // let led_dev = hidapi.device_list().find(|&device| {
     //     device.vendor_id() == usb_vid
     //         && device.product_id() == usb_pid
     //         && device.serial_number().unwrap_or("") == serial
-    //         && device.interface_number() == LED_INTERFACE
+    //         && super::get_interface_number(device) == LED_INTERFACE
     // });
 
     if ctrl_dev.is_none()
@@ -116,6 +119,11 @@ pub struct RoccatElo71Air {
 
     // device status
     pub device_status: DeviceStatus,
+
+    // headset state
+    pub battery_level: Option<u8>,
+    pub led_ring_enabled: bool,
+    pub events: Vec<HeadsetEvent>,
 }
 
 impl RoccatElo71Air {
@@ -137,6 +145,10 @@ impl RoccatElo71Air {
             has_failed: false,
 
             device_status: DeviceStatus(HashMap::new()),
+
+            battery_level: None,
+            led_ring_enabled: true,
+            events: Vec::new(),
         }
     }
 
@@ -366,6 +378,12 @@ impl RoccatElo71Air {
                     hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
 
                     if let Ok(status) = self.parse_device_status(&buf) {
+                        let level = status
+                            .get("battery-level-percent")
+                            .and_then(|level| level.parse::<u8>().ok());
+
+                        update_battery_level(&mut self.battery_level, &mut self.events, level);
+
                         self.device_status = status;
                     }
 
@@ -419,6 +437,32 @@ impl RoccatElo71Air {
     }
 }
 
+/// Record the battery level of a status report, and the events caused by a change
+fn update_battery_level(
+    battery_level: &mut Option<u8>,
+    events: &mut Vec<HeadsetEvent>,
+    level: Option<u8>,
+) {
+    if *battery_level == level {
+        return;
+    }
+
+    // the headset only sends status reports while it is connected to the transceiver
+    if battery_level.is_none() != level.is_none() {
+        events.push(HeadsetEvent::ConnectionChanged {
+            connected: level.is_some(),
+        });
+    }
+
+    if let Some(level) = level {
+        debug!("Battery level of the headset: {}%", level);
+
+        events.push(HeadsetEvent::BatteryLevelChanged { level });
+    }
+
+    *battery_level = level;
+}
+
 impl DeviceInfoTrait for RoccatElo71Air {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
         DeviceCapabilities::from([
@@ -650,7 +694,14 @@ impl DeviceTrait for RoccatElo71Air {
     }
 
     fn device_status(&self) -> Result<DeviceStatus> {
-        Ok(self.device_status.clone())
+        let mut status = self.device_status.clone();
+
+        status.0.insert(
+            "led-ring-enabled".to_string(),
+            format!("{}", self.led_ring_enabled),
+        );
+
+        Ok(status)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -676,6 +727,52 @@ impl DeviceTrait for RoccatElo71Air {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         None
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn get_zones(&self) -> Vec<Zone> {
+        // zones use 1-based key indices
+        vec![Zone::new("headset", &[LED_0 + 1])]
+    }
+
+    fn get_max_update_rate(&self) -> Option<u32> {
+        // the wireless headset drops frames at the full frame rate
+        Some(constants::WIRELESS_MAX_UPDATE_RATE)
+    }
+
+    fn as_headset_device(&self) -> Option<&dyn HeadsetDeviceTrait> {
+        Some(self)
+    }
+
+    fn as_headset_device_mut(&mut self) -> Option<&mut dyn HeadsetDeviceTrait> {
+        Some(self)
+    }
+}
+
+impl HeadsetDeviceTrait for RoccatElo71Air {
+    fn get_battery_level(&self) -> Option<u8> {
+        self.battery_level
+    }
+
+    fn is_led_ring_enabled(&self) -> bool {
+        self.led_ring_enabled
+    }
+
+    fn set_led_ring_enabled(&mut self, enabled: bool) -> Result<()> {
+        self.led_ring_enabled = enabled;
+
+        Ok(())
+    }
+
+    fn take_events(&mut self) -> Vec<HeadsetEvent> {
+        std::mem::take(&mut self.events)
+    }
 }
 
 impl MiscDeviceTrait for RoccatElo71Air {
@@ -721,6 +818,13 @@ impl MiscDeviceTrait for RoccatElo71Air {
                 _ => { /* do nothing */ }
             }
 
+            // a switched off LED ring is set to black
+            let brightness = if self.led_ring_enabled {
+                self.brightness as f32 / 100.0
+            } else {
+                0.0
+            };
+
             let ctrl_dev = self.ctrl_hiddev.as_ref().lock();
             let ctrl_dev = ctrl_dev.as_ref().unwrap();
 
@@ -729,9 +833,9 @@ impl MiscDeviceTrait for RoccatElo71Air {
                 0x04,
                 0x00,
                 0x00,
-                (led_map[LED_0].r as f32 * (self.brightness as f32 / 100.0)).floor() as u8,
-                (led_map[LED_0].g as f32 * (self.brightness as f32 / 100.0)).floor() as u8,
-                (led_map[LED_0].b as f32 * (self.brightness as f32 / 100.0)).floor() as u8,
+                (led_map[LED_0].r as f32 * brightness).floor() as u8,
+                (led_map[LED_0].g as f32 * brightness).floor() as u8,
+                (led_map[LED_0].b as f32 * brightness).floor() as u8,
                 0x00,
                 0x00,
                 0x00,
@@ -34,8 +34,8 @@ use std::{mem::size_of, sync::Arc};
 use crate::constants;
 
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
-    MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, DisplayDeviceTrait,
+    HwDeviceError, MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -64,14 +64,14 @@ pub fn bind_hiddev(
         device.vendor_id() == usb_vid
             && device.product_id() == usb_pid
             && device.serial_number().unwrap_or("") == serial
-            && device.interface_number() == CTRL_INTERFACE
+            && super::get_interface_number(device) == CTRL_INTERFACE
     });
 
     let led_dev = hidapi.device_list().find(|&device| {
         device.vendor_id() == usb_vid
             && device.product_id() == usb_pid
             && device.serial_number().unwrap_or("") == serial
-            && device.interface_number() == LED_INTERFACE
+            && super::get_interface_number(device) == LED_INTERFACE
     });
 
     if ctrl_dev.is_none() || led_dev.is_none() {
@@ -654,6 +654,19 @@ impl DeviceTrait for RoccatKain2xx {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         Some(self as &mut dyn MouseDeviceTrait)
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn get_max_update_rate(&self) -> Option<u32> {
+        // the wireless receiver drops frames at the full frame rate
+        Some(constants::WIRELESS_MAX_UPDATE_RATE)
+    }
 }
 
 impl MouseDeviceTrait for RoccatKain2xx {
@@ -29,8 +29,8 @@ use std::{any::Any, collections::HashMap, mem::size_of, sync::Arc};
 use crate::{constants, hwdevices::DeviceStatus};
 
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, MouseDevice,
-    MouseDeviceTrait, MouseHidEvent, RGBA,
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, DisplayDeviceTrait,
+    HwDeviceError, MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -61,7 +61,7 @@ pub fn bind_hiddev(
         device.vendor_id() == usb_vid
             && device.product_id() == usb_pid
             && device.serial_number().unwrap_or("") == serial
-            && device.interface_number() == SUB_DEVICE
+            && super::get_interface_number(device) == SUB_DEVICE
     });
 
     if ctrl_dev.is_none() {
@@ -505,6 +505,14 @@ impl DeviceTrait for RoccatKoneAimo {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         Some(self as &mut dyn MouseDeviceTrait)
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
 }
 
 impl MouseDeviceTrait for RoccatKoneAimo {
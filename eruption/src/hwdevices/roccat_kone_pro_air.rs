@@ -33,8 +33,8 @@ use std::{any::Any, thread};
 use crate::constants::{self, DEVICE_SETTLE_MILLIS};
 
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
-    MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, DisplayDeviceTrait,
+    HwDeviceError, MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -61,14 +61,14 @@ pub fn bind_hiddev(
             device.vendor_id() == usb_vid
                 && device.product_id() == usb_pid
                 && device.serial_number().unwrap_or("") == serial
-                && device.interface_number() == 1
+                && super::get_interface_number(device) == 1
         });
 
         led_dev = hidapi.device_list().find(|&device| {
             device.vendor_id() == usb_vid
                 && device.product_id() == usb_pid
                 && device.serial_number().unwrap_or("") == serial
-                && device.interface_number() == 2
+                && super::get_interface_number(device) == 2
         });
     } else {
         // cable mode
@@ -76,14 +76,14 @@ pub fn bind_hiddev(
             device.vendor_id() == usb_vid
                 && device.product_id() == usb_pid
                 && device.serial_number().unwrap_or("") == serial
-                && device.interface_number() == 2
+                && super::get_interface_number(device) == 2
         });
 
         led_dev = hidapi.device_list().find(|&device| {
             device.vendor_id() == usb_vid
                 && device.product_id() == usb_pid
                 && device.serial_number().unwrap_or("") == serial
-                && device.interface_number() == 1
+                && super::get_interface_number(device) == 1
         });
     }
 
@@ -733,6 +733,22 @@ impl DeviceTrait for RoccatKoneProAir {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         Some(self as &mut dyn MouseDeviceTrait)
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn get_max_update_rate(&self) -> Option<u32> {
+        // only the wireless dongle is rate limited, the wired connection keeps up
+        match self.ctrl_hiddev_info.as_ref().map(|info| info.product_id()) {
+            Some(0x2c8e) => Some(constants::WIRELESS_MAX_UPDATE_RATE),
+            _ => None,
+        }
+    }
 }
 
 impl MouseDeviceTrait for RoccatKoneProAir {
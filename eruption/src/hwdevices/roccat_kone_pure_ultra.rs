@@ -32,8 +32,9 @@ use std::{mem::size_of, sync::Arc};
 use crate::constants;
 
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
-    MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
+    dcu_config_to_byte, poll_rate_from_index, poll_rate_to_index, AuxLed, Capability,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, DisplayDeviceTrait,
+    HwDeviceError, MouseDevice, MouseDeviceTrait, MouseHidEvent, SurfaceCalibration, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -45,6 +46,17 @@ pub const SUB_DEVICE: i32 = 2; // USB HID sub-device to bind to
 // canvas to LED index mapping
 pub const LED_0: usize = constants::CANVAS_SIZE - 36;
 
+// control unit report of the Owl-Eye sensor: report id, size, DCU (lift-off distance),
+// TCU (surface calibration), median of the surface measurement and action
+const CONTROL_UNIT_REPORT: u8 = 0x0e;
+const CONTROL_UNIT_REPORT_SIZE: usize = 6;
+
+const TCU_ON: u8 = 0x01;
+
+const CONTROL_UNIT_ACTION_CANCEL: u8 = 0x00;
+const CONTROL_UNIT_ACTION_ACCEPT: u8 = 0x01;
+const CONTROL_UNIT_ACTION_UNDEFINED: u8 = 0xff;
+
 /// Binds the driver to a device
 pub fn bind_hiddev(
     hidapi: &HidApi,
@@ -56,7 +68,7 @@ pub fn bind_hiddev(
         device.vendor_id() == usb_vid
             && device.product_id() == usb_pid
             && device.serial_number().unwrap_or("") == serial
-            && device.interface_number() == SUB_DEVICE
+            && super::get_interface_number(device) == SUB_DEVICE
     });
 
     if ctrl_dev.is_none() {
@@ -97,6 +109,9 @@ pub struct RoccatKonePureUltra {
 
     // device specific configuration options
     pub brightness: i32,
+
+    /// Color of the logo LED if it has been set via `set_aux_led()`, overrides the canvas
+    pub logo_color: Option<RGBA>,
 }
 
 impl RoccatKonePureUltra {
@@ -118,6 +133,8 @@ impl RoccatKonePureUltra {
             has_failed: false,
 
             brightness: 100,
+
+            logo_color: None,
         }
     }
 
@@ -231,6 +248,64 @@ impl RoccatKonePureUltra {
         }
     }
 
+    fn read_control_unit(&self) -> Result<[u8; CONTROL_UNIT_REPORT_SIZE]> {
+        trace!("Querying the control unit of the sensor");
+
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            let ctrl_dev = self.ctrl_hiddev.as_ref().lock();
+            let ctrl_dev = ctrl_dev.as_ref().unwrap();
+
+            let mut buf = [0x00_u8; CONTROL_UNIT_REPORT_SIZE];
+            buf[0] = CONTROL_UNIT_REPORT;
+
+            match ctrl_dev.get_feature_report(&mut buf) {
+                Ok(_result) => {
+                    hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
+
+                    Ok(buf)
+                }
+
+                Err(_) => Err(HwDeviceError::InvalidResult {}.into()),
+            }
+        }
+    }
+
+    fn write_control_unit(&self, dcu: u8, median: u8, action: u8) -> Result<()> {
+        trace!("Setting the control unit of the sensor");
+
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            let ctrl_dev = self.ctrl_hiddev.as_ref().lock();
+            let ctrl_dev = ctrl_dev.as_ref().unwrap();
+
+            let buf: [u8; CONTROL_UNIT_REPORT_SIZE] = [
+                CONTROL_UNIT_REPORT,
+                CONTROL_UNIT_REPORT_SIZE as u8,
+                dcu,
+                TCU_ON,
+                median,
+                action,
+            ];
+
+            match ctrl_dev.send_feature_report(&buf) {
+                Ok(_result) => {
+                    hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
+
+                    Ok(())
+                }
+
+                Err(_) => Err(HwDeviceError::InvalidResult {}.into()),
+            }
+        }
+    }
+
     fn wait_for_ctrl_dev(&mut self) -> Result<()> {
         trace!("Waiting for control device to respond...");
 
@@ -434,6 +509,25 @@ impl DeviceTrait for RoccatKonePureUltra {
         Ok(())
     }
 
+    fn get_aux_leds(&self) -> Vec<AuxLed> {
+        vec![AuxLed::new("logo", false)]
+    }
+
+    fn set_aux_led(&mut self, name: &str, color: RGBA) -> Result<()> {
+        if name != "logo" {
+            return Err(HwDeviceError::OpNotSupported {}.into());
+        }
+
+        self.logo_color = Some(color);
+
+        // show the color right away, instead of waiting for the next frame
+        if self.is_initialized {
+            self.send_led_map(&[color; constants::CANVAS_SIZE])?;
+        }
+
+        Ok(())
+    }
+
     fn write_data_raw(&self, buf: &[u8]) -> Result<()> {
         if !self.is_bound {
             Err(HwDeviceError::DeviceNotBound {}.into())
@@ -514,6 +608,14 @@ impl DeviceTrait for RoccatKonePureUltra {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         Some(self as &mut dyn MouseDeviceTrait)
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
 }
 
 impl MouseDeviceTrait for RoccatKonePureUltra {
@@ -677,17 +779,7 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
                 Err(_) => Err(HwDeviceError::InvalidResult {}),
             }?;
 
-            match buf[29] {
-                0 => Ok(125),
-
-                1 => Ok(250),
-
-                2 => Ok(500),
-
-                3 => Ok(1000),
-
-                _ => Err(HwDeviceError::InvalidResult {}.into()),
-            }
+            poll_rate_from_index(buf[29])
         }
     }
 
@@ -715,7 +807,7 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
                 Err(_) => Err(HwDeviceError::InvalidResult {}),
             }?;
 
-            buf[29] = rate as u8;
+            buf[29] = poll_rate_to_index(rate)?;
 
             match ctrl_dev.send_feature_report(&buf) {
                 Ok(_result) => {
@@ -734,13 +826,19 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
     fn get_dcu_config(&self) -> Result<i32> {
         trace!("Querying device DCU config");
 
-        Err(HwDeviceError::OpNotSupported {}.into())
+        let control_unit = self.read_control_unit()?;
+
+        Ok(control_unit[2] as i32)
     }
 
-    fn set_dcu_config(&mut self, _dcu: i32) -> Result<()> {
+    fn set_dcu_config(&mut self, dcu: i32) -> Result<()> {
         trace!("Setting device DCU config");
 
-        Err(HwDeviceError::OpNotSupported {}.into())
+        let dcu = dcu_config_to_byte(dcu)?;
+        let control_unit = self.read_control_unit()?;
+
+        // keep the result of the last surface calibration
+        self.write_control_unit(dcu, control_unit[4], CONTROL_UNIT_ACTION_ACCEPT)
     }
 
     fn get_angle_snapping(&self) -> Result<bool> {
@@ -889,6 +987,28 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
         }
     }
 
+    fn calibrate_surface(&mut self, step: SurfaceCalibration) -> Result<()> {
+        trace!("Calibrating the sensor: {:?}", step);
+
+        let control_unit = self.read_control_unit()?;
+        let dcu = control_unit[2];
+
+        match step {
+            // the sensor measures the surface, until the median of the measurement is accepted
+            SurfaceCalibration::Start => {
+                self.write_control_unit(dcu, 0x00, CONTROL_UNIT_ACTION_UNDEFINED)
+            }
+
+            SurfaceCalibration::Accept => {
+                self.write_control_unit(dcu, control_unit[4], CONTROL_UNIT_ACTION_ACCEPT)
+            }
+
+            SurfaceCalibration::Cancel => {
+                self.write_control_unit(dcu, 0x00, CONTROL_UNIT_ACTION_CANCEL)
+            }
+        }
+    }
+
     fn get_local_brightness(&self) -> Result<i32> {
         trace!("Querying device specific brightness");
 
@@ -1108,12 +1228,14 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
             let ctrl_dev = self.ctrl_hiddev.as_ref().lock();
             let ctrl_dev = ctrl_dev.as_ref().unwrap();
 
+            let color = self.logo_color.unwrap_or(led_map[LED_0]);
+
             let buf: [u8; 11] = [
                 0x0d,
                 0x0b,
-                (led_map[LED_0].r as f32 * (self.brightness as f32 / 100.0)).floor() as u8,
-                (led_map[LED_0].g as f32 * (self.brightness as f32 / 100.0)).floor() as u8,
-                (led_map[LED_0].b as f32 * (self.brightness as f32 / 100.0)).floor() as u8,
+                (color.r as f32 * (self.brightness as f32 / 100.0)).floor() as u8,
+                (color.g as f32 * (self.brightness as f32 / 100.0)).floor() as u8,
+                (color.b as f32 * (self.brightness as f32 / 100.0)).floor() as u8,
                 0x00,
                 0x00,
                 0x00,
@@ -30,11 +30,13 @@ use std::time::Duration;
 use std::{any::Any, mem::size_of};
 
 use crate::constants::{self, DEVICE_SETTLE_MILLIS};
+use crate::key_geometry::KeyPosition;
+use crate::zones::Zone;
 
 use super::{
-    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
-    KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind,
-    MouseDeviceTrait, RGBA,
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, DisplayDeviceTrait,
+    HwDeviceError, KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode,
+    LedKind, MouseDeviceTrait, SelfTestReport, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -58,14 +60,14 @@ pub fn bind_hiddev(
         device.vendor_id() == usb_vid
             && device.product_id() == usb_pid
             && device.serial_number().unwrap_or("") == serial
-            && device.interface_number() == CTRL_INTERFACE
+            && super::get_interface_number(device) == CTRL_INTERFACE
     });
 
     let led_dev = hidapi.device_list().find(|&device| {
         device.vendor_id() == usb_vid
             && device.product_id() == usb_pid
             && device.serial_number().unwrap_or("") == serial
-            && device.interface_number() == LED_INTERFACE
+            && super::get_interface_number(device) == LED_INTERFACE
     });
 
     if ctrl_dev.is_none() || led_dev.is_none() {
@@ -761,6 +763,107 @@ impl DeviceTrait for RoccatVulcan1xx {
     fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
         None
     }
+
+    fn as_display_device(&self) -> Option<&dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn as_display_device_mut(&mut self) -> Option<&mut dyn DisplayDeviceTrait> {
+        None
+    }
+
+    fn get_zones(&self) -> Vec<Zone> {
+        vec![
+            Zone::new("wasd", &[14, 9, 15, 21]),
+            Zone::new(
+                "function-row",
+                &[12, 18, 24, 29, 49, 54, 60, 66, 79, 85, 86, 87],
+            ),
+            Zone::new(
+                "numpad",
+                &[
+                    114, 120, 125, 130, 115, 121, 126, 131, 116, 122, 127, 117, 123, 128, 132, 118,
+                    129,
+                ],
+            ),
+        ]
+    }
+
+    fn set_hardware_mode(&mut self) -> Result<()> {
+        trace!("Handing the device back to its firmware...");
+
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            {
+                let ctrl_dev = self.ctrl_hiddev.as_ref().lock();
+                let ctrl_dev = ctrl_dev.as_ref().unwrap();
+
+                // the inverse of step 1 of the init sequence, disables software control
+                let buf: [u8; 3] = [0x15, 0x00, 0x00];
+
+                match ctrl_dev.send_feature_report(&buf) {
+                    Ok(_result) => {
+                        hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
+
+                        Ok(())
+                    }
+
+                    Err(_) => Err(HwDeviceError::InvalidResult {}),
+                }?;
+            }
+
+            self.wait_for_ctrl_dev()
+        }
+    }
+
+    fn self_test(&mut self, report: &mut SelfTestReport) -> Result<()> {
+        trace!("Running self-test...");
+
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            // query the status report of the control device, the firmware
+            // signals readiness by responding with 0x01 in the second byte
+            let ctrl_dev = self.ctrl_hiddev.as_ref().lock();
+            let ctrl_dev = ctrl_dev.as_ref().unwrap();
+
+            let mut roundtrip_ok = false;
+
+            for _ in 0..10 {
+                let mut buf: [u8; 4] = [0; 4];
+                buf[0] = 0x04;
+
+                match ctrl_dev.get_feature_report(&mut buf) {
+                    Ok(_result) => {
+                        hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
+
+                        if buf[1] == 0x01 {
+                            roundtrip_ok = true;
+                            break;
+                        }
+                    }
+
+                    Err(e) => {
+                        report
+                            .errors
+                            .push(format!("Could not read the status report: {e}"));
+                        break;
+                    }
+                }
+
+                thread::sleep(Duration::from_millis(DEVICE_SETTLE_MILLIS));
+            }
+
+            report.hid_roundtrip_ok = Some(roundtrip_ok);
+
+            Ok(())
+        }
+    }
 }
 
 impl KeyboardDeviceTrait for RoccatVulcan1xx {
@@ -1085,6 +1188,13 @@ impl KeyboardDeviceTrait for RoccatVulcan1xx {
         let idx = col * NUM_ROWS;
         &COLS_TOPOLOGY[idx..(idx + NUM_ROWS + 1)]
     }
+
+    fn get_key_positions(&self) -> Vec<KeyPosition> {
+        KEY_POSITIONS
+            .iter()
+            .map(|(key, x, y)| KeyPosition::from_units(*key, *x, *y))
+            .collect()
+    }
 }
 
 fn keyboard_hid_event_code_from_report(report: u8, code: u8) -> KeyboardHidEventCode {
@@ -1389,3 +1499,114 @@ pub const COLS_TOPOLOGY: [u8; 252] = [
 	0x7c, 0x7d, 0x7e, 0x7f, 0x80, 0xff,
 	0x81, 0x82, 0x83, 0xff, 0xff, 0xff
 ];
+
+/// The centers of the keys of the ISO model, in key units, measured from the top left
+/// corner of the escape key
+#[rustfmt::skip]
+const KEY_POSITIONS: [(usize, f32, f32); 105] = [
+    (1, 0.5, 0.5),
+    (2, 0.5, 2.0),
+    (3, 0.75, 3.0),
+    (4, 0.875, 4.0),
+    (5, 0.625, 5.0),
+    (6, 0.625, 6.0),
+    (7, 1.5, 2.0),
+    (8, 2.0, 3.0),
+    (9, 2.25, 4.0),
+    (10, 1.75, 5.0),
+    (11, 1.875, 6.0),
+    (12, 2.5, 0.5),
+    (13, 2.5, 2.0),
+    (14, 3.0, 3.0),
+    (15, 3.25, 4.0),
+    (16, 2.75, 5.0),
+    (17, 3.125, 6.0),
+    (18, 3.5, 0.5),
+    (19, 3.5, 2.0),
+    (20, 4.0, 3.0),
+    (21, 4.25, 4.0),
+    (22, 3.75, 5.0),
+    (24, 4.5, 0.5),
+    (25, 4.5, 2.0),
+    (26, 5.0, 3.0),
+    (27, 5.25, 4.0),
+    (28, 4.75, 5.0),
+    (29, 5.5, 0.5),
+    (30, 5.5, 2.0),
+    (31, 6.0, 3.0),
+    (32, 6.25, 4.0),
+    (33, 5.75, 5.0),
+    (34, 6.5, 2.0),
+    (35, 7.0, 3.0),
+    (36, 7.25, 4.0),
+    (37, 6.75, 5.0),
+    (38, 6.875, 6.0),
+    (49, 7.0, 0.5),
+    (50, 7.5, 2.0),
+    (51, 8.0, 3.0),
+    (52, 8.25, 4.0),
+    (53, 7.75, 5.0),
+    (54, 8.0, 0.5),
+    (55, 8.5, 2.0),
+    (56, 9.0, 3.0),
+    (57, 9.25, 4.0),
+    (58, 8.75, 5.0),
+    (60, 9.0, 0.5),
+    (61, 9.5, 2.0),
+    (62, 10.0, 3.0),
+    (63, 10.25, 4.0),
+    (64, 9.75, 5.0),
+    (66, 10.0, 0.5),
+    (67, 10.5, 2.0),
+    (68, 11.0, 3.0),
+    (69, 11.25, 4.0),
+    (70, 10.75, 5.0),
+    (71, 10.625, 6.0),
+    (73, 11.5, 2.0),
+    (74, 12.0, 3.0),
+    (75, 12.25, 4.0),
+    (76, 11.75, 5.0),
+    (77, 11.875, 6.0),
+    (79, 11.5, 0.5),
+    (80, 12.5, 2.0),
+    (81, 13.0, 3.0),
+    (83, 13.625, 5.0),
+    (84, 13.125, 6.0),
+    (85, 12.5, 0.5),
+    (86, 13.5, 0.5),
+    (87, 14.5, 0.5),
+    (88, 14.0, 2.0),
+    (89, 14.375, 3.5),
+    (90, 14.375, 6.0),
+    (97, 13.25, 4.0),
+    (100, 15.75, 0.5),
+    (101, 15.75, 2.0),
+    (102, 15.75, 3.0),
+    (103, 15.75, 6.0),
+    (104, 16.75, 0.5),
+    (105, 16.75, 2.0),
+    (106, 16.75, 3.0),
+    (107, 16.75, 5.0),
+    (108, 16.75, 6.0),
+    (109, 17.75, 0.5),
+    (110, 17.75, 2.0),
+    (111, 17.75, 3.0),
+    (112, 17.75, 6.0),
+    (114, 19.0, 2.0),
+    (115, 19.0, 3.0),
+    (116, 19.0, 4.0),
+    (117, 19.0, 5.0),
+    (118, 19.5, 6.0),
+    (120, 20.0, 2.0),
+    (121, 20.0, 3.0),
+    (122, 20.0, 4.0),
+    (123, 20.0, 5.0),
+    (125, 21.0, 2.0),
+    (126, 21.0, 3.0),
+    (127, 21.0, 4.0),
+    (128, 21.0, 5.0),
+    (129, 21.0, 6.0),
+    (130, 22.0, 2.0),
+    (131, 22.0, 3.5),
+    (132, 22.0, 5.5),
+];
@@ -0,0 +1,260 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use evdev_rs::enums::EV_KEY;
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::scripting::script;
+use crate::{constants, DbusApiEvent, FAILED_TXS, KEYBOARD_DEVICES, LUA_TXS};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Settings of the lock indicator overlay, as configured in the [indicators] section of eruption.conf
+    static ref SETTINGS: Arc<RwLock<IndicatorSettings>> = Arc::new(RwLock::new(IndicatorSettings::default()));
+
+    /// The current state of the Caps Lock, Num Lock and Scroll Lock indicators
+    static ref LOCK_STATE: Arc<RwLock<LockState>> = Arc::new(RwLock::new(LockState::default()));
+
+    /// The currently active keyboard layout (xkb group)
+    static ref KEYBOARD_LAYOUT: Arc<RwLock<KeyboardLayout>> = Arc::new(RwLock::new(KeyboardLayout::default()));
+
+    /// Point in time when the lock state has been reported by a session sensor most recently
+    static ref LAST_REPORT: Arc<RwLock<Option<Instant>>> = Arc::new(RwLock::new(None));
+}
+
+/// The state of the lock indicators of the keyboard
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LockState {
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+/// A keyboard layout, as selected via the xkb group
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    /// Index of the xkb group
+    pub group: u32,
+
+    /// Name of the layout, like e.g. "English (US)", may be empty if unknown
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+struct IndicatorSettings {
+    /// Tint the keys of the active lock indicators
+    overlay: bool,
+
+    /// Color (ARGB) of the tint
+    color: u32,
+}
+
+impl Default for IndicatorSettings {
+    fn default() -> Self {
+        Self {
+            overlay: true,
+            color: constants::DEFAULT_INDICATOR_OVERLAY_COLOR,
+        }
+    }
+}
+
+/// Load the settings from the global configuration
+pub fn load_settings() -> Result<()> {
+    let config = crate::CONFIG.lock();
+    let config = config.as_ref().unwrap();
+
+    let defaults = IndicatorSettings::default();
+
+    let settings = IndicatorSettings {
+        overlay: config
+            .get::<bool>("indicators.overlay")
+            .unwrap_or(defaults.overlay),
+        color: config
+            .get::<u32>("indicators.overlay_color")
+            .unwrap_or(defaults.color),
+    };
+
+    *SETTINGS.write() = settings;
+
+    Ok(())
+}
+
+pub fn get_lock_state() -> LockState {
+    *LOCK_STATE.read()
+}
+
+pub fn get_keyboard_layout() -> KeyboardLayout {
+    KEYBOARD_LAYOUT.read().clone()
+}
+
+/// Read the state of the lock indicators from the keyboard LEDs in sysfs. An indicator
+/// is considered active if the respective LED of any of the keyboards is lit. Returns
+/// `None` if no keyboard LEDs have been found
+pub fn read_lock_state() -> Option<LockState> {
+    let entries = fs::read_dir(constants::LEDS_SYSFS_PATH).ok()?;
+
+    let is_lit = |path: &Path| {
+        fs::read_to_string(path.join("brightness"))
+            .ok()
+            .and_then(|s| s.trim().parse::<u32>().ok())
+            .is_some_and(|brightness| brightness > 0)
+    };
+
+    let mut found = false;
+    let mut result = LockState::default();
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+
+        if name.ends_with("::capslock") {
+            found = true;
+            result.caps_lock |= is_lit(&path);
+        } else if name.ends_with("::numlock") {
+            found = true;
+            result.num_lock |= is_lit(&path);
+        } else if name.ends_with("::scrolllock") {
+            found = true;
+            result.scroll_lock |= is_lit(&path);
+        }
+    }
+
+    found.then_some(result)
+}
+
+/// Poll the keyboard LEDs, unless a session sensor reported the lock state recently.
+/// The xkb state reported by a session sensor takes precedence over the LEDs, since
+/// it is updated immediately, and since the LEDs may be remapped by the user
+pub fn update_lock_state() {
+    let reported_recently = LAST_REPORT.read().is_some_and(|t| {
+        t.elapsed() < Duration::from_millis(constants::INDICATORS_REPORT_TIMEOUT_MILLIS)
+    });
+
+    if !reported_recently {
+        if let Some(lock_state) = read_lock_state() {
+            set_lock_state(lock_state);
+        }
+    }
+}
+
+/// Assign the lock state and the keyboard layout reported by a session sensor
+pub fn report_state(lock_state: LockState, layout: KeyboardLayout) {
+    *LAST_REPORT.write() = Some(Instant::now());
+
+    set_lock_state(lock_state);
+    set_keyboard_layout(layout);
+}
+
+fn set_lock_state(lock_state: LockState) {
+    let previous = std::mem::replace(&mut *LOCK_STATE.write(), lock_state);
+
+    if previous != lock_state {
+        debug!("Lock state changed: {:?}", lock_state);
+
+        broadcast(script::Message::LockStateChanged(lock_state));
+        notify_dbus(DbusApiEvent::LockStateChanged);
+
+        if SETTINGS.read().overlay {
+            script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+fn set_keyboard_layout(layout: KeyboardLayout) {
+    let previous = std::mem::replace(&mut *KEYBOARD_LAYOUT.write(), layout.clone());
+
+    if previous != layout {
+        info!(
+            "Keyboard layout changed: {} ({})",
+            layout.name, layout.group
+        );
+
+        broadcast(script::Message::LayoutChanged(layout.group, layout.name));
+        notify_dbus(DbusApiEvent::LayoutChanged);
+    }
+}
+
+/// Send a message to all Lua VMs
+fn broadcast(message: script::Message) {
+    for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+        if !FAILED_TXS.read().contains(&idx) {
+            lua_tx.send(message.clone()).unwrap_or_else(|e| {
+                error!(
+                    "Could not send a pending indicator event to a Lua VM: {}",
+                    e
+                )
+            });
+        } else {
+            warn!("Not sending a message to a failed tx");
+        }
+    }
+}
+
+fn notify_dbus(event: DbusApiEvent) {
+    if let Some(dbus_api_tx) = crate::DBUS_API_TX.lock().as_ref() {
+        dbus_api_tx
+            .send(event)
+            .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
+    }
+}
+
+/// Returns the key indices of the keys of all active lock indicators along with the
+/// color (ARGB) that they shall be tinted with, or `None` if the overlay is disabled
+pub fn get_indicator_keys() -> Option<(Vec<u8>, u32)> {
+    let settings = SETTINGS.read();
+
+    if !settings.overlay {
+        return None;
+    }
+
+    let lock_state = get_lock_state();
+
+    let keys = [
+        (lock_state.caps_lock, EV_KEY::KEY_CAPSLOCK),
+        (lock_state.num_lock, EV_KEY::KEY_NUMLOCK),
+        (lock_state.scroll_lock, EV_KEY::KEY_SCROLLLOCK),
+    ];
+
+    let keyboard_devices = KEYBOARD_DEVICES.read();
+    let keyboard_device = keyboard_devices.first()?.read();
+
+    let result = keys
+        .into_iter()
+        .filter(|(active, _)| *active)
+        .map(|(_, key)| keyboard_device.ev_key_to_key_index(key))
+        // keys that do not exist on this keyboard map to either 0 or 0xff
+        .filter(|index| *index > 0 && *index < u8::MAX)
+        .collect::<Vec<u8>>();
+
+    if result.is_empty() {
+        None
+    } else {
+        Some((result, settings.color))
+    }
+}
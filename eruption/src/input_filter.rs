@@ -0,0 +1,317 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use evdev_rs::enums::EventCode;
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::constants;
+use crate::scripting::script;
+use crate::util::ratelimited;
+
+/// The result of the `on_input_filter` handler of a Lua script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Mirror the event unchanged
+    Pass,
+
+    /// Suppress the event, it won't be mirrored to the virtual keyboard
+    Drop,
+
+    /// Mirror the event with the key code replaced
+    Replace(u32),
+}
+
+impl Verdict {
+    /// Convert the value returned by the Lua handler: `nil` or `true` passes the event,
+    /// `false` drops it and an integer replaces the key code
+    pub fn from_lua_value(value: &mlua::Value) -> Option<Self> {
+        match value {
+            mlua::Value::Nil | mlua::Value::Boolean(true) => Some(Verdict::Pass),
+            mlua::Value::Boolean(false) => Some(Verdict::Drop),
+            mlua::Value::Integer(code) => u32::try_from(*code).ok().map(Verdict::Replace),
+
+            _ => None,
+        }
+    }
+}
+
+lazy_static! {
+    /// Set if input filters are enabled in the configuration
+    static ref ENABLED: AtomicBool = AtomicBool::new(false);
+
+    /// Set after a filter exceeded its time budget, filters are bypassed until this point in time
+    static ref BYPASSED_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+    /// The key codes that have been emitted for the keys that are currently held down
+    static ref HELD_KEYS: Mutex<HeldKeys> = Mutex::new(HeldKeys::default());
+
+    /// Indices of the Lua VMs whose scripts define an `on_input_filter` handler
+    static ref FILTER_VMS: Arc<RwLock<BTreeSet<usize>>> = Arc::new(RwLock::new(BTreeSet::new()));
+}
+
+/// Enable input filters, if they are enabled in the configuration
+pub fn initialize() {
+    let enabled = crate::CONFIG
+        .lock()
+        .as_ref()
+        .and_then(|config| config.get::<bool>("global.enable_input_filters").ok())
+        .unwrap_or(false);
+
+    if enabled {
+        info!("Lua input filters are enabled");
+    }
+
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns `true` if input filters are enabled and are not currently bypassed
+pub fn is_active() -> bool {
+    if !ENABLED.load(Ordering::SeqCst) {
+        return false;
+    }
+
+    let mut bypassed_until = BYPASSED_UNTIL.lock();
+
+    match *bypassed_until {
+        Some(until) if Instant::now() < until => false,
+
+        Some(_) => {
+            info!("Resuming the Lua input filters");
+
+            *bypassed_until = None;
+
+            true
+        }
+
+        None => true,
+    }
+}
+
+/// Register the Lua VM `thread_idx` as an input filter
+pub fn register(thread_idx: usize) {
+    FILTER_VMS.write().insert(thread_idx);
+}
+
+/// Forget all registered filters and lift the bypass, this is called on every profile switch
+pub fn reset() {
+    FILTER_VMS.write().clear();
+    *BYPASSED_UNTIL.lock() = None;
+}
+
+/// Keeps track of the key codes that have been emitted for the keys that are held down, so
+/// that the key-up of a key is always paired with its key-down, even if the filters changed
+/// in between, e.g. after a profile switch or while the filters are bypassed
+#[derive(Debug, Default)]
+struct HeldKeys(HashMap<u32, Option<u32>>);
+
+impl HeldKeys {
+    /// Returns the key code that shall be emitted for the event `value` of the key `code`, or
+    /// `None` if the event shall be dropped. `filtered` is the verdict of the filters
+    fn resolve(&mut self, code: u32, value: i32, filtered: Option<u32>) -> Option<u32> {
+        match value {
+            // key-down
+            1 => {
+                self.0.insert(code, filtered);
+
+                filtered
+            }
+
+            // key-up
+            0 => self.0.remove(&code).unwrap_or(filtered),
+
+            // auto-repeat
+            _ => self.0.get(&code).copied().unwrap_or(filtered),
+        }
+    }
+
+    fn is_held(&self, code: u32) -> bool {
+        self.0.contains_key(&code)
+    }
+}
+
+/// Run the key event `event` through the `on_input_filter` handlers of the Lua VMs, in order.
+/// Returns the event that shall be mirrored to the virtual keyboard, or `None` if it has been
+/// dropped. Key-ups and auto-repeats always follow the verdict for the key-down of the key
+pub fn filter(event: &evdev_rs::InputEvent) -> Option<evdev_rs::InputEvent> {
+    let code = match event.event_code {
+        EventCode::EV_KEY(key) => key as u32,
+        _ => return Some(event.clone()),
+    };
+
+    // auto-repeats of held keys are not run through the filters
+    let filtered = if event.value == 2 && HELD_KEYS.lock().is_held(code) {
+        None
+    } else {
+        run_filters(code, event.value)
+    };
+
+    let emitted = HELD_KEYS.lock().resolve(code, event.value, filtered)?;
+
+    match evdev_rs::enums::int_to_ev_key(emitted) {
+        Some(key) => Some(evdev_rs::InputEvent::new(
+            &event.time,
+            &EventCode::EV_KEY(key),
+            event.value,
+        )),
+
+        None => Some(event.clone()),
+    }
+}
+
+/// Returns the key code chosen by the filters for the key event `value` of the key `code`,
+/// or `None` if it has been dropped. If the handlers exceed their time budget, filtering is
+/// bypassed for a while
+fn run_filters(code: u32, value: i32) -> Option<u32> {
+    if !is_active() {
+        return Some(code);
+    }
+
+    let filter_vms = FILTER_VMS.read().clone();
+
+    if filter_vms.is_empty() {
+        return Some(code);
+    }
+
+    let deadline = Instant::now() + Duration::from_millis(constants::INPUT_FILTER_BUDGET_MILLIS);
+    let mut result = code;
+
+    for thread_idx in filter_vms {
+        if crate::FAILED_TXS.read().contains(&thread_idx) {
+            continue;
+        }
+
+        let (reply_tx, reply_rx) = flume::bounded(1);
+
+        let sent = crate::LUA_TXS
+            .read()
+            .get(thread_idx)
+            .filter(|lua_tx| !lua_tx.is_failed)
+            .map(|lua_tx| {
+                lua_tx
+                    .send(script::Message::InputFilter(result, value, reply_tx))
+                    .is_ok()
+            })
+            .unwrap_or(false);
+
+        if !sent {
+            continue;
+        }
+
+        match reply_rx.recv_deadline(deadline) {
+            Ok(Verdict::Pass) => (),
+
+            Ok(Verdict::Drop) => {
+                debug!("Key event has been dropped by an input filter: {}", result);
+
+                return None;
+            }
+
+            Ok(Verdict::Replace(replacement)) => {
+                if evdev_rs::enums::int_to_ev_key(replacement).is_some() {
+                    result = replacement;
+                } else {
+                    ratelimited::warn!(
+                        "An input filter returned an invalid key code: {}",
+                        replacement
+                    );
+                }
+            }
+
+            Err(flume::RecvTimeoutError::Timeout) => {
+                let script_file = crate::LUA_TXS
+                    .read()
+                    .get(thread_idx)
+                    .map(|lua_tx| lua_tx.script_file.clone())
+                    .unwrap_or_else(PathBuf::new);
+
+                warn!(
+                    "The input filter of {} exceeded its time budget of {} ms, bypassing all input filters for {} ms",
+                    script_file.display(),
+                    constants::INPUT_FILTER_BUDGET_MILLIS,
+                    constants::INPUT_FILTER_BYPASS_MILLIS
+                );
+
+                *BYPASSED_UNTIL.lock() = Some(
+                    Instant::now() + Duration::from_millis(constants::INPUT_FILTER_BYPASS_MILLIS),
+                );
+
+                return Some(code);
+            }
+
+            // the VM terminated before it replied
+            Err(flume::RecvTimeoutError::Disconnected) => (),
+        }
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verdict_from_lua_value() {
+        assert_eq!(
+            Verdict::from_lua_value(&mlua::Value::Nil),
+            Some(Verdict::Pass)
+        );
+        assert_eq!(
+            Verdict::from_lua_value(&mlua::Value::Boolean(true)),
+            Some(Verdict::Pass)
+        );
+        assert_eq!(
+            Verdict::from_lua_value(&mlua::Value::Boolean(false)),
+            Some(Verdict::Drop)
+        );
+        assert_eq!(
+            Verdict::from_lua_value(&mlua::Value::Integer(30)),
+            Some(Verdict::Replace(30))
+        );
+        assert_eq!(Verdict::from_lua_value(&mlua::Value::Integer(-1)), None);
+        assert_eq!(Verdict::from_lua_value(&mlua::Value::Number(1.5)), None);
+    }
+
+    #[test]
+    fn key_up_follows_key_down() {
+        let mut held_keys = HeldKeys::default();
+
+        // the key-down has been remapped, the filters changed before the key-up
+        assert_eq!(held_keys.resolve(30, 1, Some(48)), Some(48));
+        assert_eq!(held_keys.resolve(30, 2, Some(30)), Some(48));
+        assert_eq!(held_keys.resolve(30, 0, Some(30)), Some(48));
+        assert!(!held_keys.is_held(30));
+
+        // the key-down has been dropped, so is the key-up
+        assert_eq!(held_keys.resolve(31, 1, None), None);
+        assert_eq!(held_keys.resolve(31, 0, Some(31)), None);
+
+        // the key has been pressed before it was tracked
+        assert_eq!(held_keys.resolve(32, 0, Some(32)), Some(32));
+    }
+}
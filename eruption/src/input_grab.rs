@@ -0,0 +1,156 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::device_id;
+use crate::hwdevices::DeviceTrait;
+use crate::plugins::macros;
+use crate::state::DeviceMakeModelSerial;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InputGrabError {
+    #[error("The device does not feature an input device that is read by Eruption")]
+    NoInputDevice {},
+
+    #[error("Could not change the grab of the input device: {description}")]
+    IoctlError { description: String },
+}
+
+// EVIOCGRAB, the argument is passed by value
+nix::ioctl_write_int!(eviocgrab, b'E', 0x90);
+
+lazy_static! {
+    /// Handles of the evdev devices that are read by the input threads, indexed by the stable
+    /// identifier of their device. They share the open file description with the handles of
+    /// the input threads, so that the grab can be changed without interrupting the threads
+    static ref HANDLES: Arc<RwLock<HashMap<DeviceMakeModelSerial, File>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+
+    /// Devices that have been released at runtime, they stay released when they are re-opened
+    static ref RELEASED: Arc<RwLock<HashSet<DeviceMakeModelSerial>>> =
+        Arc::new(RwLock::new(HashSet::new()));
+
+    /// Set while all input devices are released temporarily, e.g. in standby
+    static ref SUSPENDED: AtomicBool = AtomicBool::new(false);
+}
+
+/// Register the evdev handle `file` of the device `id`, after it has been (re-)opened by
+/// an input thread. Returns `true` if the device shall be grabbed
+pub fn register_handle(id: &DeviceMakeModelSerial, file: &File) -> bool {
+    match file.try_clone() {
+        Ok(file) => {
+            HANDLES.write().insert(id.clone(), file);
+        }
+
+        Err(e) => error!("Could not duplicate the handle of the input device: {}", e),
+    }
+
+    is_grabbed(id)
+}
+
+/// Forget the evdev handle of the device `device`, after it has been unplugged
+pub fn unregister_device(device: &dyn DeviceTrait) {
+    HANDLES.write().remove(&device_id::device_id(device));
+}
+
+/// Returns `true` if the input device `id` is grabbed exclusively by Eruption. Events of
+/// released devices are delivered by the OS directly, so they must not be mirrored
+pub fn is_grabbed(id: &DeviceMakeModelSerial) -> bool {
+    !SUSPENDED.load(Ordering::SeqCst) && !RELEASED.read().contains(id)
+}
+
+/// Returns `true` if the input device of `device` is grabbed exclusively by Eruption
+pub fn is_device_grabbed(device: &dyn DeviceTrait) -> bool {
+    is_grabbed(&device_id::device_id(device))
+}
+
+/// Grab the input device of `device` exclusively, or hand it back to the OS
+pub fn set_device_grabbed(device: &dyn DeviceTrait, grab: bool) -> Result<()> {
+    let id = device_id::device_id(device);
+
+    let handles = HANDLES.read();
+    let file = handles.get(&id).ok_or(InputGrabError::NoInputDevice {})?;
+
+    // the releases of keys that are held down would be delivered by the OS directly,
+    // so release them on the virtual devices before they would get stuck
+    if !grab && is_grabbed(&id) {
+        macros::release_held_keys();
+    }
+
+    unsafe { eviocgrab(file.as_raw_fd(), grab as u64) }.map_err(|e| {
+        InputGrabError::IoctlError {
+            description: format!("{}", e),
+        }
+    })?;
+
+    if grab {
+        info!("Grabbed the input device of {} exclusively", id);
+
+        RELEASED.write().remove(&id);
+    } else {
+        info!("Released the input device of {}", id);
+
+        RELEASED.write().insert(id);
+    }
+
+    Ok(())
+}
+
+/// Release all grabbed input devices temporarily, or grab them again. Devices that have
+/// been released via `set_device_grabbed()` stay released
+pub fn set_suspended(suspend: bool) {
+    // stop mirroring the events before the devices are released, and resume mirroring
+    // only after they have been grabbed again, so that no event is reported twice
+    if suspend && !SUSPENDED.swap(true, Ordering::SeqCst) {
+        // the releases of keys that are held down, like e.g. the Enter key that has been
+        // used to enter standby, would be delivered by the OS directly
+        macros::release_held_keys();
+    }
+
+    let released = RELEASED.read();
+
+    for (id, file) in HANDLES.read().iter() {
+        if released.contains(id) {
+            continue;
+        }
+
+        if let Err(e) = unsafe { eviocgrab(file.as_raw_fd(), !suspend as u64) } {
+            error!(
+                "Could not change the grab of the input device of {}: {}",
+                id, e
+            );
+        }
+    }
+
+    if !suspend {
+        SUSPENDED.store(false, Ordering::SeqCst);
+    }
+}
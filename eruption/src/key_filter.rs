@@ -0,0 +1,485 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Key debounce filtering and custom key repeat handling of keyboards, applied to the
+//! events of the evdev device before they are processed. The debounce filter ignores
+//! repeated make/break events of a key within a short period of time, to compensate for
+//! chattering switches. A key that settles in a different state than the one that has
+//! been reported is corrected as soon as the debounce period expires
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::hwdevices::DeviceTrait;
+use crate::state::DeviceMakeModelSerial;
+use crate::{constants, device_id, state_file};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyFilterError {
+    #[error("Invalid key filter: {description}")]
+    InvalidKeyFilter { description: String },
+}
+
+lazy_static! {
+    /// Key filters of all devices that have been configured so far, persisted across restarts
+    pub static ref DEVICE_KEY_FILTERS: Arc<RwLock<BTreeMap<DeviceMakeModelSerial, KeyFilterConfig>>> =
+        Arc::new(RwLock::new(BTreeMap::new()));
+}
+
+/// The key filter settings of a keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyFilterConfig {
+    /// Allows to switch the filter off temporarily, without losing its settings
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// Make/break events of a key within this period after its last change are ignored, 0 disables debouncing
+    #[serde(default)]
+    pub debounce_millis: u64,
+
+    /// Delay before a held key starts to repeat
+    #[serde(default = "default_repeat_delay")]
+    pub repeat_delay_millis: u64,
+
+    /// Key repeats per second, 0 passes the key repeat events of the device through unaltered
+    #[serde(default)]
+    pub repeat_rate: u32,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_repeat_delay() -> u64 {
+    constants::DEFAULT_KEY_REPEAT_DELAY_MILLIS
+}
+
+impl Default for KeyFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            debounce_millis: 0,
+            repeat_delay_millis: default_repeat_delay(),
+            repeat_rate: 0,
+        }
+    }
+}
+
+impl KeyFilterConfig {
+    /// Returns true if neither debouncing nor a custom key repeat rate are configured
+    pub fn is_pass_through(&self) -> bool {
+        !self.enabled || (self.debounce_millis == 0 && self.repeat_rate == 0)
+    }
+
+    fn debounce_period(&self) -> Duration {
+        Duration::from_millis(self.debounce_millis)
+    }
+
+    fn repeat_period(&self) -> Duration {
+        Duration::from_secs(1) / self.repeat_rate.max(1)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.debounce_millis > constants::MAX_KEY_DEBOUNCE_MILLIS {
+            return Err(KeyFilterError::InvalidKeyFilter {
+                description: format!(
+                    "The debounce period may not exceed {} milliseconds",
+                    constants::MAX_KEY_DEBOUNCE_MILLIS
+                ),
+            }
+            .into());
+        }
+
+        if self.repeat_rate > constants::MAX_KEY_REPEAT_RATE {
+            return Err(KeyFilterError::InvalidKeyFilter {
+                description: format!(
+                    "The key repeat rate may not exceed {} per second",
+                    constants::MAX_KEY_REPEAT_RATE
+                ),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for KeyFilterConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+
+        if self.debounce_millis > 0 {
+            parts.push(format!("debounce={}", self.debounce_millis));
+        }
+
+        if self.repeat_rate > 0 {
+            parts.push(format!(
+                "repeat={}:{}",
+                self.repeat_delay_millis, self.repeat_rate
+            ));
+        }
+
+        if parts.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", parts.join(","))
+        }
+    }
+}
+
+impl FromStr for KeyFilterConfig {
+    type Err = eyre::Error;
+
+    /// Parse a key filter like e.g. "debounce=8,repeat=300:30", or "none"
+    fn from_str(s: &str) -> Result<Self> {
+        let mut result = Self::default();
+
+        let invalid = |part: &str| KeyFilterError::InvalidKeyFilter {
+            description: part.to_string(),
+        };
+
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some(("debounce", value)) => {
+                    result.debounce_millis = value.parse().map_err(|_| invalid(part))?;
+                }
+
+                Some(("repeat", value)) => {
+                    let (delay, rate) = value.split_once(':').ok_or_else(|| invalid(part))?;
+
+                    result.repeat_delay_millis = delay.parse().map_err(|_| invalid(part))?;
+                    result.repeat_rate = rate.parse().map_err(|_| invalid(part))?;
+                }
+
+                None if part == "none" => result = Self::default(),
+
+                _ => return Err(invalid(part).into()),
+            }
+        }
+
+        result.validate()?;
+
+        Ok(result)
+    }
+}
+
+/// The state of a key, as seen by the debounce filter
+#[derive(Debug, Clone, Copy)]
+struct KeyState {
+    /// The most recent state reported by the device
+    raw: bool,
+
+    /// The state that has been passed on
+    reported: bool,
+
+    /// Point in time of the last change of the reported state
+    changed_at: Option<Instant>,
+}
+
+impl KeyState {
+    /// Returns true if the debounce period after the last change expired at `now`
+    fn is_settled(&self, config: &KeyFilterConfig, now: Instant) -> bool {
+        self.changed_at
+            .map_or(true, |t| now.duration_since(t) >= config.debounce_period())
+    }
+}
+
+/// Filters the key events of a single keyboard, lives in the input thread of the device.
+/// Keys are identified by their evdev key codes
+#[derive(Debug, Default)]
+pub struct KeyFilter {
+    keys: HashMap<u32, KeyState>,
+
+    /// The key that is currently repeated, along with the point in time of its next repeat
+    repeat: Option<(u32, Instant)>,
+}
+
+impl KeyFilter {
+    /// Filter a key event of the device, `value` is 0 for a release, 1 for a press and 2
+    /// for a key repeat. Returns true if the event shall be passed on
+    pub fn filter(
+        &mut self,
+        config: &KeyFilterConfig,
+        code: u32,
+        value: i32,
+        now: Instant,
+    ) -> bool {
+        if config.is_pass_through() {
+            self.keys.clear();
+            self.repeat = None;
+
+            return true;
+        }
+
+        if value == 2 {
+            // key repeats of the device are replaced by our own, if a repeat rate has been set
+            let is_reported = self.keys.get(&code).map_or(true, |key| key.reported);
+
+            return config.repeat_rate == 0 && is_reported;
+        }
+
+        let pressed = value != 0;
+
+        let key = self.keys.entry(code).or_insert(KeyState {
+            raw: !pressed,
+            reported: !pressed,
+            changed_at: None,
+        });
+
+        key.raw = pressed;
+
+        if key.reported == pressed || !key.is_settled(config, now) {
+            // either no change at all, or the key is chattering. A key that settles in a
+            // different state will be corrected by `poll()` once the period expired
+            return false;
+        }
+
+        key.reported = pressed;
+        key.changed_at = Some(now);
+
+        self.update_repeat(config, code, pressed, now);
+
+        true
+    }
+
+    /// Returns the point in time at which `poll()` has to be called next, if any
+    pub fn next_deadline(&self, config: &KeyFilterConfig) -> Option<Instant> {
+        let settle = self
+            .keys
+            .values()
+            .filter(|key| key.raw != key.reported)
+            .filter_map(|key| key.changed_at)
+            .map(|t| t + config.debounce_period());
+
+        settle.chain(self.repeat.map(|(_, at)| at)).min()
+    }
+
+    /// Returns the events that are due at `now`: corrections of keys that settled in a
+    /// different state than the one that has been reported, and key repeats
+    pub fn poll(&mut self, config: &KeyFilterConfig, now: Instant) -> Vec<(u32, i32)> {
+        let mut result = vec![];
+
+        if config.is_pass_through() {
+            return result;
+        }
+
+        let mut settled = self
+            .keys
+            .iter_mut()
+            .filter(|(_, key)| key.raw != key.reported && key.is_settled(config, now))
+            .map(|(code, key)| {
+                key.reported = key.raw;
+                key.changed_at = Some(now);
+
+                (*code, key.raw)
+            })
+            .collect::<Vec<_>>();
+
+        settled.sort_unstable();
+
+        for (code, pressed) in settled {
+            self.update_repeat(config, code, pressed, now);
+
+            result.push((code, pressed as i32));
+        }
+
+        if let Some((code, at)) = self.repeat.as_mut() {
+            if now >= *at {
+                result.push((*code, 2));
+
+                // skip repeats that are overdue, instead of emitting them in a burst
+                *at = (*at + config.repeat_period()).max(now);
+            }
+        }
+
+        result
+    }
+
+    fn update_repeat(&mut self, config: &KeyFilterConfig, code: u32, pressed: bool, now: Instant) {
+        if pressed {
+            if config.repeat_rate > 0 {
+                self.repeat = Some((
+                    code,
+                    now + Duration::from_millis(config.repeat_delay_millis),
+                ));
+            }
+        } else if self.repeat.map_or(false, |(repeated, _)| repeated == code) {
+            self.repeat = None;
+        }
+    }
+}
+
+/// Returns the key filter settings of a device
+pub fn get_key_filter(device: &dyn DeviceTrait) -> KeyFilterConfig {
+    device_id::lookup(&DEVICE_KEY_FILTERS.read(), device)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Assign the key filter settings of a device, and persist them
+pub fn set_key_filter(device: &dyn DeviceTrait, config: KeyFilterConfig) -> Result<()> {
+    config.validate()?;
+
+    {
+        let mut device_key_filters = DEVICE_KEY_FILTERS.write();
+        let key = device_id::migrate(&mut device_key_filters, device);
+
+        if config == KeyFilterConfig::default() {
+            device_key_filters.remove(&key);
+        } else {
+            device_key_filters.insert(key, config);
+        }
+    }
+
+    save_device_key_filters()
+}
+
+/// Update the settings of the key filter of a device, but keep its enabled state
+pub fn configure_key_filter(device: &dyn DeviceTrait, config: KeyFilterConfig) -> Result<()> {
+    let enabled = get_key_filter(device).enabled;
+
+    set_key_filter(device, KeyFilterConfig { enabled, ..config })
+}
+
+/// Switch the key filter of a device on or off, without losing its settings
+pub fn enable_key_filter(device: &dyn DeviceTrait, enabled: bool) -> Result<()> {
+    let config = get_key_filter(device);
+
+    set_key_filter(device, KeyFilterConfig { enabled, ..config })
+}
+
+pub fn save_device_key_filters() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("device-key-filters.state");
+
+    let data = toml::to_string_pretty(&*DEVICE_KEY_FILTERS.read())?;
+    state_file::write(&file_name, &data)?;
+
+    Ok(())
+}
+
+pub fn load_device_key_filters() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("device-key-filters.state");
+
+    let device_key_filters: BTreeMap<DeviceMakeModelSerial, KeyFilterConfig> =
+        state_file::load(&file_name, |data| Ok(toml::from_str(data)?))?;
+
+    *DEVICE_KEY_FILTERS.write() = device_key_filters
+        .into_iter()
+        .filter(|(_, config)| config.validate().is_ok())
+        .collect();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_A: u32 = 30;
+
+    fn millis(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn parse_and_format() {
+        let config = "debounce=8,repeat=300:30"
+            .parse::<KeyFilterConfig>()
+            .unwrap();
+
+        assert_eq!(config.debounce_millis, 8);
+        assert_eq!((config.repeat_delay_millis, config.repeat_rate), (300, 30));
+
+        assert_eq!(
+            config.to_string().parse::<KeyFilterConfig>().unwrap(),
+            config
+        );
+
+        assert!("none".parse::<KeyFilterConfig>().unwrap().is_pass_through());
+        assert!("repeat=300".parse::<KeyFilterConfig>().is_err());
+        assert!("debounce=100000".parse::<KeyFilterConfig>().is_err());
+        assert!("bounce=8".parse::<KeyFilterConfig>().is_err());
+    }
+
+    #[test]
+    fn ignore_chattering_keys() {
+        let config = "debounce=10".parse::<KeyFilterConfig>().unwrap();
+        let mut filter = KeyFilter::default();
+
+        let start = Instant::now();
+
+        assert!(filter.filter(&config, KEY_A, 1, start));
+
+        // the switch chatters while it is being pressed
+        assert!(!filter.filter(&config, KEY_A, 0, millis(start, 2)));
+        assert!(!filter.filter(&config, KEY_A, 1, millis(start, 3)));
+        assert!(filter.poll(&config, millis(start, 20)).is_empty());
+
+        assert!(filter.filter(&config, KEY_A, 0, millis(start, 100)));
+        assert!(!filter.filter(&config, KEY_A, 1, millis(start, 105)));
+    }
+
+    #[test]
+    fn correct_keys_that_settled() {
+        let config = "debounce=10".parse::<KeyFilterConfig>().unwrap();
+        let mut filter = KeyFilter::default();
+
+        let start = Instant::now();
+
+        // a very short tap is released within the debounce period
+        assert!(filter.filter(&config, KEY_A, 1, start));
+        assert!(!filter.filter(&config, KEY_A, 0, millis(start, 5)));
+
+        assert_eq!(filter.next_deadline(&config), Some(millis(start, 10)));
+        assert!(filter.poll(&config, millis(start, 8)).is_empty());
+        assert_eq!(filter.poll(&config, millis(start, 10)), vec![(KEY_A, 0)]);
+        assert_eq!(filter.next_deadline(&config), None);
+    }
+
+    #[test]
+    fn repeat_held_keys() {
+        let config = "repeat=300:10".parse::<KeyFilterConfig>().unwrap();
+        let mut filter = KeyFilter::default();
+
+        let start = Instant::now();
+
+        assert!(filter.filter(&config, KEY_A, 1, start));
+
+        // key repeats of the device are replaced
+        assert!(!filter.filter(&config, KEY_A, 2, millis(start, 250)));
+
+        assert!(filter.poll(&config, millis(start, 299)).is_empty());
+        assert_eq!(filter.poll(&config, millis(start, 300)), vec![(KEY_A, 2)]);
+        assert_eq!(filter.next_deadline(&config), Some(millis(start, 400)));
+
+        assert!(filter.filter(&config, KEY_A, 0, millis(start, 450)));
+        assert_eq!(filter.next_deadline(&config), None);
+    }
+}
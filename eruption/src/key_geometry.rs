@@ -0,0 +1,195 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Physical positions of the keys of the connected keyboards.
+//!
+//! Currently only the ROCCAT Vulcan 1xx series ships a table of measured key positions.
+//! The positions of the keys of all other keyboards are approximated from their row
+//! topology, on an unstaggered grid of one unit wide keys
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::hwdevices::KeyboardDeviceTrait;
+
+/// The distance between the centers of two adjacent keys, one key "unit"
+pub const KEY_PITCH_MM: f32 = 19.05;
+
+lazy_static! {
+    /// The resolved positions of the keys of all connected keyboards, `None` if they have to
+    /// be recomputed, e.g. after a keyboard has been hotplugged
+    static ref KEY_POSITIONS: Arc<RwLock<Option<Vec<KeyPosition>>>> = Arc::new(RwLock::new(None));
+}
+
+/// Incremented each time the cached key positions are invalidated
+static KEY_POSITIONS_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The physical position of the center of a key, in millimeters, relative to the top left
+/// corner of the key area of the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KeyPosition {
+    /// Key index of the key, as used by Lua scripts
+    pub key: usize,
+
+    pub x: f32,
+    pub y: f32,
+}
+
+impl KeyPosition {
+    /// Create a key position from coordinates in key units
+    pub fn from_units(key: usize, x: f32, y: f32) -> Self {
+        Self {
+            key,
+            x: x * KEY_PITCH_MM,
+            y: y * KEY_PITCH_MM,
+        }
+    }
+}
+
+/// Approximate the positions of the keys from the row topology of a keyboard, for drivers
+/// that don't ship a table of their own. Each key is assumed to be one unit wide, so wide
+/// keys and the stagger of the rows are not accounted for
+pub fn from_topology(device: &dyn KeyboardDeviceTrait) -> Vec<KeyPosition> {
+    from_rows(device.get_num_rows(), |row| device.get_row_topology(row))
+}
+
+fn from_rows<'a, F>(num_rows: usize, row_topology: F) -> Vec<KeyPosition>
+where
+    F: Fn(usize) -> &'a [u8],
+{
+    let mut result = vec![];
+
+    for row in 0..num_rows {
+        for (col, index) in row_topology(row).iter().enumerate() {
+            if *index == 0xff {
+                continue;
+            }
+
+            // keys that span multiple columns are listed once per column, keep the first one
+            if result
+                .iter()
+                .any(|p: &KeyPosition| p.key == *index as usize + 1)
+            {
+                continue;
+            }
+
+            result.push(KeyPosition::from_units(
+                *index as usize + 1,
+                col as f32 + 0.5,
+                row as f32 + 0.5,
+            ));
+        }
+    }
+
+    result
+}
+
+/// Discard the cached key positions, they are recomputed on next use. This has to be
+/// called whenever the set of connected keyboards changes
+pub fn invalidate() {
+    let mut key_positions = KEY_POSITIONS.write();
+
+    KEY_POSITIONS_GENERATION.fetch_add(1, Ordering::SeqCst);
+    *key_positions = None;
+}
+
+/// Run `f` on the cached key positions, resolve them first if required
+fn with_key_positions<T>(f: impl FnOnce(&[KeyPosition]) -> T) -> T {
+    if let Some(positions) = KEY_POSITIONS.read().as_ref() {
+        return f(positions);
+    }
+
+    // resolve the positions without holding the lock, this acquires the device locks
+    let generation = KEY_POSITIONS_GENERATION.load(Ordering::SeqCst);
+    let positions = resolve_key_positions();
+
+    let result = f(&positions);
+
+    let mut key_positions = KEY_POSITIONS.write();
+
+    // do not cache the positions, if the keyboards changed in the meantime
+    if KEY_POSITIONS_GENERATION.load(Ordering::SeqCst) == generation {
+        *key_positions = Some(positions);
+    }
+
+    result
+}
+
+/// Returns the positions of the keys of the connected keyboards. The keyboards share the
+/// key area of the canvas, the first keyboard that knows the position of a key wins
+pub fn get_key_positions() -> Vec<KeyPosition> {
+    with_key_positions(|positions| positions.to_vec())
+}
+
+fn resolve_key_positions() -> Vec<KeyPosition> {
+    let mut result: Vec<KeyPosition> = vec![];
+
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        let device = device.read();
+
+        let mut positions = device.get_key_positions();
+
+        if positions.is_empty() {
+            positions = from_topology(&**device);
+        }
+
+        for position in positions {
+            if !result.iter().any(|p| p.key == position.key) {
+                result.push(position);
+            }
+        }
+    }
+
+    result.sort_by_key(|p| p.key);
+
+    result
+}
+
+/// Returns the position of the key `key`, in millimeters
+pub fn get_key_position(key: usize) -> Option<(f32, f32)> {
+    with_key_positions(|positions| {
+        positions
+            .binary_search_by_key(&key, |p| p.key)
+            .ok()
+            .map(|index| (positions[index].x, positions[index].y))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_from_topology() {
+        // two rows, the second one starts with a key that spans two columns
+        let rows: [&[u8]; 2] = [&[0x00, 0x01, 0xff], &[0x02, 0x02, 0x03]];
+
+        let positions = from_rows(2, |row| rows[row]);
+
+        assert_eq!(positions.len(), 4);
+        assert_eq!(positions[1], KeyPosition::from_units(2, 1.5, 0.5));
+        assert_eq!(positions[2], KeyPosition::from_units(3, 0.5, 1.5));
+        assert_eq!(positions[3].x, 2.5 * KEY_PITCH_MM);
+    }
+}
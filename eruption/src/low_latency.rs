@@ -0,0 +1,113 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::constants;
+
+lazy_static! {
+    /// Set if the active profile declares itself as static
+    static ref STATIC_PROFILE: AtomicBool = AtomicBool::new(false);
+
+    /// Number of frames that have been rendered since the fade-in of the profile completed
+    static ref SETTLED_FRAMES: AtomicUsize = AtomicUsize::new(0);
+}
+
+/// Enter low-latency mode if the new profile is static, or leave it otherwise.
+/// This is called on every profile switch
+pub fn set_profile(is_static: bool) {
+    SETTLED_FRAMES.store(0, Ordering::SeqCst);
+
+    if STATIC_PROFILE.swap(is_static, Ordering::SeqCst) != is_static {
+        if is_static {
+            info!("The profile is static, entering low-latency mode");
+        } else {
+            info!("Leaving low-latency mode");
+        }
+    }
+}
+
+/// Returns `true` if the LED map of the static profile has been uploaded, and
+/// rendering is suspended until the next profile switch
+pub fn is_suspended() -> bool {
+    STATIC_PROFILE.load(Ordering::SeqCst)
+        && SETTLED_FRAMES.load(Ordering::SeqCst) >= constants::STATIC_PROFILE_SETTLE_FRAMES
+}
+
+/// Returns `true` if the next frame shall be rendered. Static profiles are rendered
+/// until they have faded in and their scripts have settled, afterwards the scripts
+/// do not receive timer ticks anymore. The LED map is only uploaded again if
+/// something else changed, like e.g. the brightness
+pub fn should_render_frame(fading: bool) -> bool {
+    if !STATIC_PROFILE.load(Ordering::SeqCst) {
+        return true;
+    }
+
+    if fading {
+        SETTLED_FRAMES.store(0, Ordering::SeqCst);
+
+        return true;
+    }
+
+    let frames = SETTLED_FRAMES
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |frames| {
+            Some(
+                frames
+                    .saturating_add(1)
+                    .min(constants::STATIC_PROFILE_SETTLE_FRAMES),
+            )
+        })
+        .unwrap_or(0);
+
+    if frames + 1 == constants::STATIC_PROFILE_SETTLE_FRAMES {
+        debug!("The static profile has been uploaded, suspending rendering");
+    }
+
+    frames < constants::STATIC_PROFILE_SETTLE_FRAMES
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_profiles_settle() {
+        set_profile(true);
+
+        // keep rendering while the profile fades in
+        assert!(should_render_frame(true));
+        assert!(!is_suspended());
+
+        let rendered = (0..constants::STATIC_PROFILE_SETTLE_FRAMES * 2)
+            .filter(|_| should_render_frame(false))
+            .count();
+
+        assert_eq!(rendered, constants::STATIC_PROFILE_SETTLE_FRAMES);
+        assert!(is_suspended());
+
+        // rendering resumes on the next profile switch
+        set_profile(false);
+        assert!(!is_suspended());
+        assert!(should_render_frame(false));
+    }
+}
@@ -19,7 +19,7 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 use config::Config;
 use flume::{select::SelectError, unbounded, Receiver, Selector, Sender};
 use hotwatch::{
@@ -37,8 +37,11 @@ use rust_embed::RustEmbed;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::u64;
-use std::{collections::HashMap, env};
 use std::{collections::HashSet, thread};
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+};
 use std::{
     fs,
     path::{Path, PathBuf},
@@ -51,6 +54,8 @@ use tokio::join;
 use util::ratelimited;
 
 mod logger;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod threads;
 use threads::*;
 
@@ -59,15 +64,61 @@ mod util;
 mod hwdevices;
 use hwdevices::{KeyboardDevice, KeyboardHidEvent, MiscDevice, MouseDevice, MouseHidEvent};
 
+mod afk;
+mod animation;
+mod aux_leds;
+mod backlight;
+mod batch;
+mod blending;
+mod brightness_curve;
+mod canvas_layout;
 mod color_scheme;
+mod color_transitions;
+mod compositor;
 mod constants;
 mod dbus_interface;
+mod device_defaults;
+mod device_id;
+mod device_init;
 mod events;
+mod frame_budget;
+mod game_mode;
+mod glyphs;
+mod hardware_mode;
+mod headsets;
+mod health;
+mod hotkeys;
+mod hotplug;
+mod indicators;
+mod input_filter;
+mod input_grab;
+mod key_filter;
+mod key_geometry;
+mod low_latency;
+mod mouse_bindings;
+mod pacing;
 mod plugin_manager;
 mod plugins;
+mod poll_rate;
+mod power;
+mod privacy;
+mod profile_conditions;
+mod profile_slots;
+mod profiler;
 mod profiles;
+mod quarantine;
+mod quick_actions;
+mod safe_mode;
 mod scripting;
+mod sdk_clients;
+mod standby;
+mod startup;
 mod state;
+mod state_file;
+mod text_entry;
+mod transform;
+mod zone_controls;
+mod zones;
 
 use crate::{
     color_scheme::ColorScheme,
@@ -169,10 +220,18 @@ lazy_static! {
     /// The profile that was active before we entered AFK mode
     pub static ref ACTIVE_PROFILE_NAME_BEFORE_AFK: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
 
+    /// The profile that was active before we entered power saving mode
+    pub static ref ACTIVE_PROFILE_NAME_BEFORE_POWER_SAVING: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+
     /// Named color schemes, for use in e.g. gradients
     pub static ref NAMED_COLOR_SCHEMES: Arc<RwLock<HashMap<String, ColorScheme>>> =
         Arc::new(RwLock::new(HashMap::new()));
 
+    /// Original state of the named color schemes that are currently being previewed,
+    /// `None` if the color scheme did not exist before the preview has been started
+    pub static ref COLOR_SCHEME_PREVIEWS: Arc<RwLock<HashMap<String, Option<ColorScheme>>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+
     /// Global configuration
     pub static ref CONFIG: Arc<Mutex<Option<config::Config>>> = Arc::new(Mutex::new(None));
 
@@ -206,6 +265,9 @@ lazy_static! {
     /// Global "enable Linux Userspace LEDs support" flag
     pub static ref ULEDS_SUPPORT_ACTIVE: AtomicBool = AtomicBool::new(false);
 
+    /// Set while a device self-test is running, rendering is suspended in the meantime
+    pub static ref DEVICE_SELF_TEST_ACTIVE: AtomicBool = AtomicBool::new(false);
+
 
     // Other state
 
@@ -257,6 +319,11 @@ lazy_static! {
     pub static ref COLOR_MAPS_READY_CONDITION: Arc<(Mutex<usize>, Condvar)> =
         Arc::new((Mutex::new(0), Condvar::new()));
 
+    // Profile switches completed? Holds the correlation ids of the most recently completed
+    // switches that have been requested via D-Bus, along with whether they succeeded
+    pub static ref PROFILE_SWITCHING_COMPLETED_CONDITION: Arc<(Mutex<VecDeque<(u64, bool)>>, Condvar)> =
+        Arc::new((Mutex::new(VecDeque::new()), Condvar::new()));
+
     // All upcalls (event handlers) in Lua VM completed?
     pub static ref UPCALL_COMPLETED_ON_KEY_DOWN: Arc<(Mutex<usize>, Condvar)> =
         Arc::new((Mutex::new(0), Condvar::new()));
@@ -330,6 +397,9 @@ pub struct LuaTx {
     pub script_file: PathBuf,
     pub sender: Sender<script::Message>,
     pub is_failed: bool,
+
+    /// Set if the script belongs to the profile that is blended into the active slot
+    pub is_secondary: bool,
 }
 
 impl LuaTx {
@@ -338,6 +408,7 @@ impl LuaTx {
             script_file,
             sender,
             is_failed: false,
+            is_secondary: false,
         }
     }
 }
@@ -360,7 +431,7 @@ pub enum EventAction {
 #[derive(Debug, Clone)]
 pub enum FileSystemEvent {
     ProfileChanged { action: EventAction, path: PathBuf },
-    ScriptChanged,
+    ScriptChanged { path: PathBuf },
 }
 
 #[derive(Debug, Clone)]
@@ -401,6 +472,12 @@ fn parse_commandline() -> clap::ArgMatches {
                 .value_name("FILE")
                 .help("Sets the configuration file to use"),
         )
+        .arg(
+            Arg::new("safe-mode")
+                .long("safe-mode")
+                .action(ArgAction::SetTrue)
+                .help("Boot into the failsafe profile, with user scripts disabled"),
+        )
         // .arg(
         //     Arg::new("completions")
         //         .long("completions")
@@ -417,6 +494,36 @@ pub fn switch_profile_please(profile_file: Option<&Path>) -> Result<SwitchProfil
     switch_profile(profile_file, dbus_api_tx, true)
 }
 
+/// Switch to the profile `profile_file`, and announce the switch `id` via the D-Bus signals
+/// `ProfileSwitchStarted` and `ProfileSwitchCompleted`
+pub fn switch_profile_announced(
+    profile_file: &Path,
+    id: u64,
+    dbus_api_tx: &Sender<DbusApiEvent>,
+) -> Result<SwitchProfileResult> {
+    dbus_api_tx
+        .send(DbusApiEvent::ProfileSwitchStarted(
+            id,
+            profile_file.to_path_buf(),
+        ))
+        .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
+
+    let result = switch_profile(Some(profile_file), dbus_api_tx, true);
+    let succeeded = matches!(result, Ok(SwitchProfileResult::Switched));
+
+    dbus_api_tx
+        .send(DbusApiEvent::ProfileSwitchCompleted(
+            id,
+            profile_file.to_path_buf(),
+            succeeded,
+        ))
+        .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
+
+    dbus_interface::signal_profile_switch_completed(id, succeeded);
+
+    result
+}
+
 #[derive(PartialEq, Eq)]
 pub enum SwitchProfileResult {
     Switched,
@@ -424,6 +531,62 @@ pub enum SwitchProfileResult {
     FallbackToFailsafe,
 }
 
+/// Spawns the Lua VMs of the profile that is blended into the active slot, if any.
+/// Errors are not fatal, the primary profile keeps running on its own
+fn spawn_blended_profile() {
+    let blend = match blending::get_active_blend() {
+        Some(blend) => blend,
+        None => return,
+    };
+
+    match profiles::Profile::load_fully(&blend.profile) {
+        Ok(profile) => {
+            info!(
+                "Blending profile: {} ({:.0}%)",
+                blend.profile.display(),
+                blend.weight * 100.0
+            );
+
+            let skipped = profile_conditions::skipped_scripts(&profile);
+
+            for manifest in profile.manifests.values() {
+                if skipped.contains(&manifest.script_file) {
+                    continue;
+                }
+
+                let thread_idx = LUA_TXS.read().len();
+
+                let (lua_tx, lua_rx) = unbounded();
+                match threads::spawn_lua_thread(
+                    thread_idx,
+                    lua_rx,
+                    &manifest.script_file,
+                    manifest.get_shader_file(),
+                    manifest.get_native_effect(),
+                    &manifest.get_merged_parameters(&profile),
+                ) {
+                    Ok(()) => {
+                        let mut tx = LuaTx::new(manifest.script_file.to_owned(), lua_tx);
+                        tx.is_secondary = true;
+
+                        LUA_TXS.write().push(tx);
+                    }
+
+                    Err(e) => error!("Could not spawn a thread: {}", e),
+                }
+            }
+
+            blending::reset_weight(blend.weight);
+        }
+
+        Err(e) => error!(
+            "Could not load the blended profile {}: {}",
+            blend.profile.display(),
+            e
+        ),
+    }
+}
+
 /// Switches the currently active profile to the profile file `profile_file`
 /// Returns Ok(Switched) if the new profile has been activated, Ok(InvalidProfile)
 /// if the old profile was kept, or else Ok(FallbackToFailsafe) when we entered
@@ -439,16 +602,35 @@ pub fn switch_profile(
 
         let profile = Profile::new_fail_safe();
 
+        compositor::set_failsafe(true);
+        input_filter::reset();
+        scripting::blackboard::clear();
+        glyphs::set_key_labels(&profile.labels);
+        game_mode::set_game_mode(profile.game_mode.as_ref());
+        low_latency::set_profile(false);
+        mouse_bindings::set_mouse_bindings(&profile.mouse_bindings);
+        poll_rate::apply_profile(&profile.poll_rates);
+        plugins::audio::set_profile_sfx(profile.sfx.as_ref());
+        profile_conditions::set_skipped_scripts(vec![]);
+        zone_controls::set_profile(None);
+
         // spawn a new set of Lua VMs, with scripts from the failsafe profile
         for (thread_idx, manifest) in profile.manifests.values().enumerate() {
             let (lua_tx, lua_rx) = unbounded();
             let parameters = &manifest.get_merged_parameters(&profile);
-            threads::spawn_lua_thread(thread_idx, lua_rx, &manifest.script_file, parameters)
-                .unwrap_or_else(|e| {
-                    errors_present = true;
+            threads::spawn_lua_thread(
+                thread_idx,
+                lua_rx,
+                &manifest.script_file,
+                manifest.get_shader_file(),
+                manifest.get_native_effect(),
+                parameters,
+            )
+            .unwrap_or_else(|e| {
+                errors_present = true;
 
-                    error!("Could not spawn a thread: {}", e);
-                });
+                error!("Could not spawn a thread: {}", e);
+            });
 
             let mut tx = LuaTx::new(manifest.script_file.to_owned(), lua_tx);
 
@@ -505,6 +687,10 @@ pub fn switch_profile(
         debug!("Successfully entered failsafe mode");
 
         Ok(SwitchProfileResult::FallbackToFailsafe)
+    } else if safe_mode::is_active() {
+        warn!("Running in safe mode, refusing to switch profiles");
+
+        Ok(SwitchProfileResult::InvalidProfile)
     } else {
         // we require profile_file to be set in this branch
         let profile_file = if let Some(profile_file) = profile_file {
@@ -541,13 +727,34 @@ pub fn switch_profile(
 
                 let mut num_vms = 0; // only valid if no errors occurred
 
+                // scripts that depend on devices which are not connected are not run at all
+                let skipped = profile_conditions::skipped_scripts(&profile);
+
+                compositor::set_failsafe(false);
+                input_filter::reset();
+                scripting::blackboard::clear();
+                glyphs::set_key_labels(&profile.labels);
+                game_mode::set_game_mode(profile.game_mode.as_ref());
+                mouse_bindings::set_mouse_bindings(&profile.mouse_bindings);
+                poll_rate::apply_profile(&profile.poll_rates);
+                plugins::audio::set_profile_sfx(profile.sfx.as_ref());
+                zone_controls::set_profile(Some(&profile));
+
                 // now spawn a new set of Lua VMs, with scripts from the new profile
-                for (thread_idx, manifest) in profile.manifests.values().enumerate() {
+                for manifest in profile.manifests.values() {
+                    if skipped.contains(&manifest.script_file) {
+                        continue;
+                    }
+
+                    let thread_idx = LUA_TXS.read().len();
+
                     let (lua_tx, lua_rx) = unbounded();
                     if let Err(e) = threads::spawn_lua_thread(
                         thread_idx,
                         lua_rx,
                         &manifest.script_file,
+                        manifest.get_shader_file(),
+                        manifest.get_native_effect(),
                         &manifest.get_merged_parameters(&profile),
                     ) {
                         errors_present = true;
@@ -567,8 +774,9 @@ pub fn switch_profile(
                 }
 
                 // it seems that at least one Lua VM failed during loading of the new profile,
-                // so we have to switch to failsafe mode to be safe
-                if errors_present || num_vms == 0 {
+                // so we have to switch to failsafe mode to be safe. A profile whose scripts
+                // all depend on missing devices is valid though
+                if errors_present || (num_vms == 0 && skipped.is_empty()) {
                     error!(
                         "An error occurred during switching of profiles, loading failsafe profile now"
                     );
@@ -579,6 +787,15 @@ pub fn switch_profile(
                     // everything is fine, finally assign the globally active profile
                     debug!("Switch successful");
 
+                    profile_conditions::set_skipped_scripts(skipped);
+
+                    spawn_blended_profile();
+
+                    // blends may fade, so only unblended static profiles are rendered once
+                    low_latency::set_profile(
+                        profile.is_static && blending::get_active_blend().is_none(),
+                    );
+
                     let fade_millis = crate::CONFIG
                         .lock()
                         .as_ref()
@@ -591,6 +808,9 @@ pub fn switch_profile(
 
                     *ACTIVE_PROFILE.lock() = Some(profile);
 
+                    events::notify_observers(events::Event::ProfileChanged)
+                        .unwrap_or_else(|e| error!("Could not notify observers: {}", e));
+
                     if notify {
                         dbus_api_tx
                             .send(DbusApiEvent::ActiveProfileChanged)
@@ -637,18 +857,12 @@ fn run_main_loop(
     ctrl_c_rx: &Receiver<bool>,
     dbus_rx: &Receiver<dbus_interface::Message>,
     fsevents_rx: &Receiver<FileSystemEvent>,
+    hotplug_rx: &Receiver<Vec<hotplug::HotplugEvent>>,
 ) -> Result<()> {
     trace!("Entering main loop...");
 
     events::notify_observers(events::Event::DaemonStartup).unwrap();
 
-    let afk_timeout_secs = crate::CONFIG
-        .lock()
-        .as_ref()
-        .unwrap()
-        .get_int("global.afk_timeout_secs")
-        .unwrap_or(constants::AFK_TIMEOUT_SECS as i64) as u64;
-
     // main loop iterations, monotonic counter
     let mut ticks = 0;
     let mut start_time;
@@ -661,9 +875,15 @@ fn run_main_loop(
     let mut saved_slot = 0;
 
     let mut saved_brightness = BRIGHTNESS.load(Ordering::SeqCst);
+    let mut saved_game_mode = game_mode::is_active();
+    let mut saved_hardware_mode = hardware_mode::is_active();
+    let mut saved_afk = afk::is_active();
 
-    // used to detect changes to the AFK state
-    let mut saved_afk_mode = false;
+    // used to detect changes to the power saving state
+    let mut saved_power_saving = false;
+    let mut last_power_source_poll = Instant::now();
+    let mut last_backlight_poll = Instant::now();
+    let mut last_indicators_poll = Instant::now();
 
     'MAIN_LOOP: loop {
         #[cfg(feature = "profiling")]
@@ -708,6 +928,21 @@ fn run_main_loop(
 
                     QUIT.store(true, Ordering::SeqCst);
                 }
+            })
+            .recv(&events::INJECTED_EVENTS.1, |event| {
+                if let Ok(request) = event {
+                    events::process_injected_event(&request);
+                }
+            })
+            .recv(hotplug_rx, |event| {
+                if let Ok(events) = event {
+                    hotplug::process_hotplug_events(&events);
+                } else {
+                    ratelimited::error!(
+                        "Could not process a hotplug event: {}",
+                        event.as_ref().unwrap_err()
+                    );
+                }
             });
 
         let failed_kbd_rxs = Arc::new(Mutex::new(HashSet::new()));
@@ -858,7 +1093,11 @@ fn run_main_loop(
                     slot_profiles.as_ref().unwrap()[active_slot].clone()
                 };
 
-                switch_profile(Some(&profile_path), dbus_api_tx, true)?;
+                switch_profile_announced(
+                    &profile_path,
+                    dbus_interface::next_profile_switch_id(),
+                    dbus_api_tx,
+                )?;
 
                 saved_slot = active_slot;
                 FAILED_TXS.write().clear();
@@ -875,39 +1114,92 @@ fn run_main_loop(
             saved_brightness = current_brightness;
         }
 
-        // user is AFK?
-        let afk_mode = AFK.load(Ordering::SeqCst);
-        if afk_mode != saved_afk_mode {
-            if afk_mode {
-                info!("Entering AFK mode now...");
+        // game mode toggled?
+        let current_game_mode = game_mode::is_active();
+        if current_game_mode != saved_game_mode {
+            dbus_api_tx
+                .send(DbusApiEvent::GameModeChanged)
+                .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
 
-                let afk_profile = crate::CONFIG
-                    .lock()
-                    .as_ref()
-                    .unwrap()
-                    .get::<String>("global.afk_profile")
-                    .unwrap_or_else(|_| constants::DEFAULT_AFK_PROFILE.to_owned());
+            saved_game_mode = current_game_mode;
+        }
 
-                let active_profile = &*ACTIVE_PROFILE.lock();
-                let before_afk = &active_profile.as_ref().unwrap().profile_file;
+        // streaming suspended or resumed?
+        let current_hardware_mode = hardware_mode::is_active();
+        if current_hardware_mode != saved_hardware_mode {
+            dbus_api_tx
+                .send(DbusApiEvent::HardwareModeChanged)
+                .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
 
-                *ACTIVE_PROFILE_NAME_BEFORE_AFK.lock() =
-                    Some(before_afk.to_string_lossy().to_string());
+            saved_hardware_mode = current_hardware_mode;
+        }
 
-                ACTIVE_PROFILE_NAME.lock().replace(afk_profile);
-            } else {
-                info!("Leaving AFK mode now...");
+        // user is AFK? take or revert the configured AFK actions
+        let current_afk = afk::is_active();
+        afk::update(current_afk);
 
-                ACTIVE_PROFILE_NAME.lock().replace(
-                    ACTIVE_PROFILE_NAME_BEFORE_AFK
-                        .lock()
+        if current_afk != saved_afk {
+            dbus_api_tx
+                .send(DbusApiEvent::AfkChanged)
+                .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
+
+            saved_afk = current_afk;
+        }
+
+        // reset the crash-loop counter once we are running stable
+        safe_mode::check_stable();
+
+        // running on battery?
+        if last_power_source_poll.elapsed()
+            >= Duration::from_millis(constants::POWER_SOURCE_POLL_MILLIS)
+        {
+            power::update_power_source();
+
+            last_power_source_poll = Instant::now();
+        }
+
+        // display backlight dimmed?
+        if last_backlight_poll.elapsed() >= Duration::from_millis(constants::BACKLIGHT_POLL_MILLIS)
+        {
+            backlight::update_display_level();
+
+            last_backlight_poll = Instant::now();
+        }
+
+        // lock indicators changed?
+        if last_indicators_poll.elapsed()
+            >= Duration::from_millis(constants::INDICATORS_POLL_MILLIS)
+        {
+            indicators::update_lock_state();
+
+            last_indicators_poll = Instant::now();
+        }
+
+        let power_saving = power::is_active();
+        if power_saving != saved_power_saving {
+            if power_saving {
+                info!("Entering power saving mode now...");
+
+                if let Some(profile) = power::get_power_saving_profile() {
+                    let active_profile = &*ACTIVE_PROFILE.lock();
+
+                    *ACTIVE_PROFILE_NAME_BEFORE_POWER_SAVING.lock() = active_profile
                         .as_ref()
-                        .unwrap()
-                        .clone(),
-                );
+                        .map(|p| p.profile_file.to_string_lossy().to_string());
+
+                    ACTIVE_PROFILE_NAME
+                        .lock()
+                        .replace(profile.to_string_lossy().to_string());
+                }
+            } else {
+                info!("Leaving power saving mode now...");
+
+                if let Some(profile) = ACTIVE_PROFILE_NAME_BEFORE_POWER_SAVING.lock().take() {
+                    ACTIVE_PROFILE_NAME.lock().replace(profile);
+                }
             }
 
-            saved_afk_mode = afk_mode;
+            saved_power_saving = power_saving;
         }
 
         {
@@ -922,7 +1214,11 @@ fn run_main_loop(
 
                 let profile_path = Path::new(active_profile);
 
-                if let Err(e) = switch_profile(Some(profile_path), dbus_api_tx, true) {
+                if let Err(e) = switch_profile_announced(
+                    profile_path,
+                    dbus_interface::next_profile_switch_id(),
+                    dbus_api_tx,
+                ) {
                     error!("Could not switch profiles: {}", e);
                 }
 
@@ -960,6 +1256,9 @@ fn run_main_loop(
             }
         }
 
+        color_transitions::update();
+        compositor::expire_layers();
+
         {
             #[cfg(feature = "profiling")]
             coz::scope!("main loop hooks");
@@ -991,18 +1290,23 @@ fn run_main_loop(
 
             let saved_status = crate::DEVICE_STATUS.as_ref().lock().clone();
 
-            if let Err(_e) = events::process_timer_event() {
-                /* do nothing  */
+            // the devices are closed while in standby
+            if !standby::is_active() {
+                if let Err(_e) = events::process_timer_event() {
+                    /* do nothing  */
 
-                // if e.type_id() == (HwDeviceError::NoOpResult {}).type_id() {
-                //     error!("Could not process a timer event: {}", e);
-                // } else {
-                //     trace!("Result is a NoOp");
-                // }
+                    // if e.type_id() == (HwDeviceError::NoOpResult {}).type_id() {
+                    //     error!("Could not process a timer event: {}", e);
+                    // } else {
+                    //     trace!("Result is a NoOp");
+                    // }
+                }
             }
 
             last_status_poll = Instant::now();
 
+            headsets::process_events();
+
             let current_status = crate::DEVICE_STATUS.lock().clone();
 
             if current_status != saved_status {
@@ -1100,27 +1404,50 @@ fn run_main_loop(
             }
         }
 
-        if !device_has_failed
-            && delay_time_render.elapsed() >= Duration::from_millis(1000 / constants::TARGET_FPS)
-        {
+        // detect key chords that are held down long enough
+        events::process_chords()
+            .unwrap_or_else(|e| ratelimited::error!("Could not process key chords: {}", e));
+
+        let frame_interval = Duration::from_millis(1000 / frame_budget::get_target_fps());
+
+        if !device_has_failed && delay_time_render.elapsed() >= frame_interval {
             #[cfg(feature = "profiling")]
             coz::scope!("render code");
 
+            let _span = tracing::trace_span!("render").entered();
+
+            #[cfg(feature = "metrics")]
+            metrics::record_frame_time(delay_time_render.elapsed());
+
+            // halve the frame rate while the system is under load
+            if frame_budget::record_frame(delay_time_render.elapsed(), frame_interval) {
+                dbus_api_tx
+                    .send(DbusApiEvent::FrameRateChanged)
+                    .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
+            }
+
             let delta =
                 (delay_time_render.elapsed().as_millis() as u64 / constants::TARGET_FPS) as u32;
 
             delay_time_render = Instant::now();
 
+            // static profiles do not receive timer ticks once they have been uploaded
+            let render_frame = low_latency::should_render_frame(
+                crate::BRIGHTNESS_FADER.load(Ordering::SeqCst) > 0,
+            );
+
             // send timer tick events to the Lua VMs
-            for (index, lua_tx) in LUA_TXS.read().iter().enumerate() {
-                // if this tx failed previously, then skip it completely
-                if !FAILED_TXS.read().contains(&index) {
-                    lua_tx
-                        .send(script::Message::Tick(delta))
-                        .unwrap_or_else(|e| {
-                            error!("Send error during timer tick event: {}", e);
-                            FAILED_TXS.write().insert(index);
-                        });
+            if render_frame {
+                for (index, lua_tx) in LUA_TXS.read().iter().enumerate() {
+                    // if this tx failed previously, then skip it completely
+                    if !FAILED_TXS.read().contains(&index) {
+                        lua_tx
+                            .send(script::Message::Tick(delta))
+                            .unwrap_or_else(|e| {
+                                error!("Send error during timer tick event: {}", e);
+                                FAILED_TXS.write().insert(index);
+                            });
+                    }
                 }
             }
 
@@ -1140,9 +1467,16 @@ fn run_main_loop(
             crate::BRIGHTNESS_FADER.store(fader - 1, Ordering::SeqCst);
         }
 
-        // compute AFK time
-        if afk_timeout_secs > 0 {
-            let afk = LAST_INPUT_TIME.lock().elapsed() >= Duration::from_secs(afk_timeout_secs);
+        // compute AFK time, the timeout may be learned from the idle periods of the user
+        let last_input = *LAST_INPUT_TIME.lock();
+        let idle = last_input.elapsed();
+        afk::observe_idle(idle);
+
+        let afk_timeout_secs = afk::get_timeout_secs();
+        if let Some(afk) = afk::get_forced_state(last_input) {
+            AFK.store(afk, Ordering::SeqCst);
+        } else if afk_timeout_secs > 0 {
+            let afk = idle >= Duration::from_secs(afk_timeout_secs);
             AFK.store(afk, Ordering::SeqCst);
         }
 
@@ -1206,6 +1540,14 @@ fn remove_failed_devices() -> Result<bool> {
     {
         info!("Unplugging a failed keyboard device...");
 
+        health::record_event(
+            keyboard_devices[index].read().as_device(),
+            health::HealthEvent::Disconnect,
+        );
+
+        hotplug::notify_device_removed("keyboard", keyboard_devices[index].read().as_device());
+        input_grab::unregister_device(keyboard_devices[index].read().as_device());
+
         let mut devices_rx = crate::KEYBOARD_DEVICES_RX.write();
         assert!(devices_rx.len() > index);
         devices_rx.remove(index);
@@ -1232,6 +1574,14 @@ fn remove_failed_devices() -> Result<bool> {
     {
         info!("Unplugging a failed mouse device...");
 
+        health::record_event(
+            mouse_devices[index].read().as_device(),
+            health::HealthEvent::Disconnect,
+        );
+
+        hotplug::notify_device_removed("mouse", mouse_devices[index].read().as_device());
+        input_grab::unregister_device(mouse_devices[index].read().as_device());
+
         let mut devices_rx = crate::MOUSE_DEVICES_RX.write();
         assert!(devices_rx.len() > index);
         devices_rx.remove(index);
@@ -1258,6 +1608,20 @@ fn remove_failed_devices() -> Result<bool> {
     {
         info!("Unplugging a failed misc device...");
 
+        health::record_event(
+            misc_devices[index].read().as_device(),
+            health::HealthEvent::Disconnect,
+        );
+
+        {
+            let device = misc_devices[index].read();
+            hotplug::notify_device_removed(
+                hotplug::misc_device_class(device.as_device()),
+                device.as_device(),
+            );
+            input_grab::unregister_device(device.as_device());
+        }
+
         let mut devices_rx = crate::MISC_DEVICES_RX.write();
         assert!(devices_rx.len() > index);
         devices_rx.remove(index);
@@ -1277,6 +1641,14 @@ fn remove_failed_devices() -> Result<bool> {
             .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
     }
 
+    if result {
+        events::notify_observers(events::Event::DeviceDisconnected)
+            .unwrap_or_else(|e| error!("Could not notify observers: {}", e));
+
+        health::save_device_health()
+            .unwrap_or_else(|e| error!("Could not save device health statistics: {}", e));
+    }
+
     Ok(result)
 }
 
@@ -1336,7 +1708,14 @@ pub fn register_filesystem_watcher(
                                         if to.extension().unwrap_or_default().to_string_lossy() == "profile" {
                                             info!("Profile file renamed: {:?}", (&from, &to));
 
-                                            fsevents_tx_c.send(FileSystemEvent::ProfileChanged { action: EventAction::Modified, path: to }).unwrap();
+                                            // a file that has been moved into place, e.g. by an installer, is a new profile
+                                            let action = if from.extension().unwrap_or_default().to_string_lossy() == "profile" {
+                                                EventAction::Modified
+                                            } else {
+                                                EventAction::Created
+                                            };
+
+                                            fsevents_tx_c.send(FileSystemEvent::ProfileChanged { action, path: to }).unwrap();
                                         }
                                     } else if let Event::Remove(event) = event {
                                         if event.extension().unwrap_or_default().to_string_lossy() == "state" {
@@ -1366,7 +1745,7 @@ pub fn register_filesystem_watcher(
                                            event.extension().unwrap_or_default().to_string_lossy() == "manifest" {
                                             info!("Script file, manifest or keymap changed: {:?}", event);
 
-                                            fsevents_tx_c.send(FileSystemEvent::ScriptChanged).unwrap();
+                                            fsevents_tx_c.send(FileSystemEvent::ScriptChanged { path: event }).unwrap();
                                         }
                                     }
 
@@ -1421,6 +1800,28 @@ mod thread_util {
     }
 }
 
+/// Initialize all devices in parallel, since opening a device and sending
+/// the initialization handshake may take a few hundred milliseconds each
+pub fn init_devices(
+    keyboard_devices: &[KeyboardDevice],
+    mouse_devices: &[MouseDevice],
+    misc_devices: &[MiscDevice],
+) {
+    thread::scope(|s| {
+        for device in keyboard_devices {
+            s.spawn(move || init_keyboard_device(device));
+        }
+
+        for device in mouse_devices {
+            s.spawn(move || init_mouse_device(device));
+        }
+
+        for device in misc_devices {
+            s.spawn(move || init_misc_device(device));
+        }
+    });
+}
+
 /// open the control and LED devices of the keyboard
 pub fn init_keyboard_device(keyboard_device: &KeyboardDevice) {
     info!("Opening keyboard device...");
@@ -1435,19 +1836,31 @@ pub fn init_keyboard_device(keyboard_device: &KeyboardDevice) {
         );
     });
 
-    // send initialization handshake
+    // send initialization handshake and set LEDs to a known good initial state,
+    // devices may need some time to settle after power on so failures are retried
     info!("Initializing keyboard device...");
-    keyboard_device
-        .write()
-        .send_init_sequence()
-        .unwrap_or_else(|e| error!("Could not initialize the device: {}", e));
-
-    // set LEDs to a known good initial state
-    info!("Configuring keyboard LEDs...");
-    keyboard_device
-        .write()
-        .set_led_init_pattern()
-        .unwrap_or_else(|e| error!("Could not initialize LEDs: {}", e));
+    let id = device_id::device_id(keyboard_device.read().as_device());
+
+    let initialized = device_init::initialize_with_retries(&id, || {
+        let mut device = keyboard_device.write();
+
+        device.send_init_sequence()?;
+
+        info!("Configuring keyboard LEDs...");
+        device.set_led_init_pattern()
+    });
+
+    if !initialized {
+        let mut device = keyboard_device.write();
+
+        health::record_event(device.as_device(), health::HealthEvent::FailedInit);
+        device
+            .fail()
+            .unwrap_or_else(|e| error!("Could not mark the device as failed: {}", e));
+    }
+
+    // apply the colors of the auxiliary LEDs, if any have been set before
+    aux_leds::restore_aux_leds(keyboard_device.write().as_device_mut());
 
     info!(
         "Firmware revision: {}",
@@ -1469,19 +1882,31 @@ pub fn init_mouse_device(mouse_device: &MouseDevice) {
         );
     });
 
-    // send initialization handshake
+    // send initialization handshake and set LEDs to a known good initial state,
+    // devices may need some time to settle after power on so failures are retried
     info!("Initializing mouse device...");
-    mouse_device
-        .write()
-        .send_init_sequence()
-        .unwrap_or_else(|e| error!("Could not initialize the device: {}", e));
-
-    // set LEDs to a known good initial state
-    info!("Configuring mouse LEDs...");
-    mouse_device
-        .write()
-        .set_led_init_pattern()
-        .unwrap_or_else(|e| error!("Could not initialize LEDs: {}", e));
+    let id = device_id::device_id(mouse_device.read().as_device());
+
+    let initialized = device_init::initialize_with_retries(&id, || {
+        let mut device = mouse_device.write();
+
+        device.send_init_sequence()?;
+
+        info!("Configuring mouse LEDs...");
+        device.set_led_init_pattern()
+    });
+
+    if !initialized {
+        let mut device = mouse_device.write();
+
+        health::record_event(device.as_device(), health::HealthEvent::FailedInit);
+        device
+            .fail()
+            .unwrap_or_else(|e| error!("Could not mark the device as failed: {}", e));
+    }
+
+    // apply the colors of the auxiliary LEDs, if any have been set before
+    aux_leds::restore_aux_leds(mouse_device.write().as_device_mut());
 
     info!(
         "Firmware revision: {}",
@@ -1503,19 +1928,31 @@ pub fn init_misc_device(misc_device: &MiscDevice) {
         );
     });
 
-    // send initialization handshake
+    // send initialization handshake and set LEDs to a known good initial state,
+    // devices may need some time to settle after power on so failures are retried
     info!("Initializing misc device...");
-    misc_device
-        .write()
-        .send_init_sequence()
-        .unwrap_or_else(|e| error!("Could not initialize the device: {}", e));
-
-    // set LEDs to a known good initial state
-    info!("Configuring misc device LEDs...");
-    misc_device
-        .write()
-        .set_led_init_pattern()
-        .unwrap_or_else(|e| error!("Could not initialize LEDs: {}", e));
+    let id = device_id::device_id(misc_device.read().as_device());
+
+    let initialized = device_init::initialize_with_retries(&id, || {
+        let mut device = misc_device.write();
+
+        device.send_init_sequence()?;
+
+        info!("Configuring misc device LEDs...");
+        device.set_led_init_pattern()
+    });
+
+    if !initialized {
+        let mut device = misc_device.write();
+
+        health::record_event(device.as_device(), health::HealthEvent::FailedInit);
+        device
+            .fail()
+            .unwrap_or_else(|e| error!("Could not mark the device as failed: {}", e));
+    }
+
+    // apply the colors of the auxiliary LEDs, if any have been set before
+    aux_leds::restore_aux_leds(misc_device.write().as_device_mut());
 
     info!(
         "Firmware revision: {}",
@@ -1527,6 +1964,8 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
     #[cfg(feature = "profiling")]
     coz::thread_init();
 
+    startup::begin();
+
     cfg_if::cfg_if! {
         if #[cfg(debug_assertions)] {
             color_eyre::config::HookBuilder::default()
@@ -1663,15 +2102,101 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
         }
     }
 
+    // count this startup, and boot into safe mode if requested or if we are caught in a crash loop
+    safe_mode::begin_startup(matches.get_flag("safe-mode"))
+        .unwrap_or_else(|e| warn!("Could not update the crash-loop counter: {}", e));
+
+    profiler::initialize();
+    input_filter::initialize();
+    profile_slots::initialize();
+
+    if safe_mode::is_active() {
+        REQUEST_FAILSAFE_MODE.store(true, Ordering::SeqCst);
+    }
+
     // load and initialize global runtime state
     info!("Loading saved state...");
+    let span = startup::span("loading saved state");
+
     state::init_global_runtime_state()
         .unwrap_or_else(|e| warn!("Could not parse state file: {}", e));
 
+    // compile the scripts of the active profile, while the devices are being initialized
+    let active_profile_file = SLOT_PROFILES.lock().as_ref().and_then(|slot_profiles| {
+        slot_profiles
+            .get(ACTIVE_SLOT.load(Ordering::SeqCst))
+            .cloned()
+    });
+
+    if let Some(profile_file) = active_profile_file.filter(|_| !safe_mode::is_active()) {
+        startup::spawn_profile_preload_thread(profile_file)
+            .unwrap_or_else(|e| warn!("Could not spawn a thread: {}", e));
+    }
+
     // restore saved color-schemes
     state::load_color_schemes()
         .unwrap_or_else(|e| warn!("Could not restore previously saved color-schemes: {}", e));
 
+    // restore device health statistics
+    health::load_thresholds()
+        .unwrap_or_else(|e| warn!("Could not load device health thresholds: {}", e));
+    quarantine::load_policy()
+        .unwrap_or_else(|e| warn!("Could not load the device quarantine policy: {}", e));
+    health::load_device_health()
+        .unwrap_or_else(|e| warn!("Could not restore device health statistics: {}", e));
+
+    // load the access policy of the SDK socket
+    sdk_clients::load_policy()
+        .unwrap_or_else(|e| warn!("Could not load the SDK access policy: {}", e));
+
+    // restore canvas transforms
+    transform::load_device_transforms()
+        .unwrap_or_else(|e| warn!("Could not restore canvas transforms: {}", e));
+
+    // restore the placements of the keyboards on the shared canvas
+    canvas_layout::load_canvas_layout()
+        .unwrap_or_else(|e| warn!("Could not restore the canvas layout: {}", e));
+
+    // restore brightness curves
+    brightness_curve::initialize();
+    brightness_curve::load_device_curves()
+        .unwrap_or_else(|e| warn!("Could not restore brightness curves: {}", e));
+
+    // restore key filter settings
+    key_filter::load_device_key_filters()
+        .unwrap_or_else(|e| warn!("Could not restore key filter settings: {}", e));
+
+    // load power saving settings
+    power::load_settings().unwrap_or_else(|e| warn!("Could not load power saving settings: {}", e));
+    power::update_power_source();
+
+    // load the settings of the display backlight sync
+    backlight::load_settings()
+        .unwrap_or_else(|e| warn!("Could not load display backlight settings: {}", e));
+    backlight::update_display_level();
+
+    // load the settings of the adaptive frame rate
+    frame_budget::load_settings();
+
+    // load AFK actions
+    afk::load_settings().unwrap_or_else(|e| warn!("Could not load AFK actions: {}", e));
+
+    // load lock indicator settings
+    indicators::load_settings()
+        .unwrap_or_else(|e| warn!("Could not load lock indicator settings: {}", e));
+
+    // load global hotkeys
+    hotkeys::load_hotkeys().unwrap_or_else(|e| warn!("Could not load global hotkeys: {}", e));
+
+    // load user defined zones
+    zones::load_zones().unwrap_or_else(|e| warn!("Could not load zones: {}", e));
+
+    // load the default profiles of the supported device models
+    device_defaults::load_defaults()
+        .unwrap_or_else(|e| warn!("Could not load device specific default profiles: {}", e));
+
+    span.end();
+
     // enable the mouse
     let enable_mouse = config.get::<bool>("global.enable_mouse").unwrap_or(true);
 
@@ -1684,23 +2209,42 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
 
             // initialize plugins
             info!("Registering plugins...");
+            let span = startup::span("registering plugins");
+
             plugins::register_plugins()
                 .unwrap_or_else(|_e| error!("Could not register one or more plugins"));
 
-            // load plugin state from disk
-            plugins::PersistencePlugin::load_persistent_data()
-                .unwrap_or_else(|e| warn!("Could not load persisted state: {}", e));
+            span.end();
+
+            // load plugin state from disk, it is not required until the first profile is activated
+            let persistence_loader = startup::spawn_persistence_loader_thread()
+                .map_err(|e| warn!("Could not spawn a thread: {}", e))
+                .ok();
 
             info!("Plugins loaded and initialized successfully");
 
             // enumerate devices
             info!("Enumerating connected devices...");
+            let span = startup::span("device enumeration");
+
+            let devices = hwdevices::probe_devices();
+
+            span.end();
+
+            if let Ok(devices) = devices {
+                // initialize all devices in parallel, the input threads are spawned in order below
+                let span = startup::span("device initialization");
+
+                init_devices(
+                    &devices.0,
+                    if enable_mouse { &devices.1 } else { &[] },
+                    &devices.2,
+                );
+
+                span.end();
 
-            if let Ok(devices) = hwdevices::probe_devices() {
                 // initialize keyboard devices
                 for (index, device) in devices.0.iter().enumerate() {
-                    init_keyboard_device(device);
-
                     let usb_vid = device.read().get_usb_vid();
                     let usb_pid = device.read().get_usb_pid();
 
@@ -1728,8 +2272,6 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 for (index, device) in devices.1.iter().enumerate() {
                     // enable mouse input
                     if enable_mouse {
-                        init_mouse_device(device);
-
                         let usb_vid = device.read().get_usb_vid();
                         let usb_pid = device.read().get_usb_pid();
 
@@ -1778,8 +2320,6 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
 
                 // initialize misc devices
                 for (index, device) in devices.2.iter().enumerate() {
-                    init_misc_device(device);
-
                     if device.read().has_input_device() {
                         let usb_vid = device.read().get_usb_vid();
                         let usb_pid = device.read().get_usb_pid();
@@ -1810,6 +2350,8 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     crate::MISC_DEVICES.write().push(device.clone());
                 }
 
+                key_geometry::invalidate();
+
                 info!("Device enumeration completed");
 
                 if crate::KEYBOARD_DEVICES.read().is_empty()
@@ -1820,13 +2362,27 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 }
 
                 info!("Performing late initializations...");
+                let span = startup::span("late initializations");
 
                 // load and initialize global runtime state (late init)
                 info!("Loading saved device state...");
                 state::init_global_runtime_state_late()
                     .unwrap_or_else(|e| warn!("Could not parse state file: {}", e));
 
-                // initialize the Linux uleds interface
+                // devices that are present at startup keep the active profile when they are re-connected
+                for device in KEYBOARD_DEVICES.read().iter() {
+                    device_defaults::mark_seen(device.read().as_device());
+                }
+
+                for device in MOUSE_DEVICES.read().iter() {
+                    device_defaults::mark_seen(device.read().as_device());
+                }
+
+                for device in MISC_DEVICES.read().iter() {
+                    device_defaults::mark_seen(device.read().as_device());
+                }
+
+                // initialize the Linux uleds interface, the device is set up lazily by the uleds thread
                 info!("Initializing Linux Userspace LEDs interface...");
                 plugins::UledsPlugin::spawn_uleds_thread().unwrap_or_else(|e| {
                     warn!("Could not spawn a thread: {}", e);
@@ -1857,9 +2413,29 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
 
                 *DEV_IO_TX.lock() = Some(dev_io_tx.clone());
 
+                // monitor udev for devices being plugged in or removed
+                info!("Initializing udev monitor...");
+                let (hotplug_tx, hotplug_rx) = unbounded();
+                hotplug::spawn_udev_monitor_thread(hotplug_tx)
+                    .unwrap_or_else(|e| error!("Could not spawn the udev monitor thread: {}", e));
+
+                // serve the metrics endpoint, if it has been configured
+                #[cfg(feature = "metrics")]
+                metrics::spawn_metrics_thread()
+                    .unwrap_or_else(|e| error!("Could not start the metrics endpoint: {}", e));
+
+                // the scripts of the first profile may access the persisted state
+                if let Some(persistence_loader) = persistence_loader {
+                    persistence_loader
+                        .join()
+                        .unwrap_or_else(|_| error!("Could not load persisted state"));
+                }
+
+                span.end();
+
                 info!("Late initializations completed");
 
-                info!("Startup completed");
+                startup::log_summary();
 
                 'OUTER_LOOP: loop {
                     info!("Entering the main loop now...");
@@ -1867,12 +2443,17 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     let mut errors_present = false;
 
                     // enter the main loop
-                    run_main_loop(&dbus_api_tx, &ctrl_c_rx, &dbus_rx, &fsevents_rx).unwrap_or_else(
-                        |e| {
-                            warn!("Left the main loop due to an irrecoverable error: {}", e);
-                            errors_present = true;
-                        },
-                    );
+                    run_main_loop(
+                        &dbus_api_tx,
+                        &ctrl_c_rx,
+                        &dbus_rx,
+                        &fsevents_rx,
+                        &hotplug_rx,
+                    )
+                    .unwrap_or_else(|e| {
+                        warn!("Left the main loop due to an irrecoverable error: {}", e);
+                        errors_present = true;
+                    });
 
                     if !errors_present {
                         info!("Main loop terminated gracefully");
@@ -1887,6 +2468,41 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
 
                     // remove disconnected or failed devices
                     remove_failed_devices()?;
+
+                    // the USB paths of removed devices may be re-used by other devices
+                    device_id::reset_cache();
+
+                    // attach devices that have been plugged in
+                    hotplug::attach_pending_devices()
+                        .unwrap_or_else(|e| error!("Could not attach hotplugged devices: {}", e));
+
+                    // the zones of the removed or attached devices changed
+                    zone_controls::update_key_controls();
+
+                    // activate the default profile of a device that has been connected for the first time
+                    if let Some(profile_file) = device_defaults::take_pending_profile() {
+                        if let Err(e) = switch_profile_announced(
+                            &profile_file,
+                            dbus_interface::next_profile_switch_id(),
+                            &dbus_api_tx,
+                        ) {
+                            error!("Could not switch profiles: {}", e);
+                        }
+                    } else if profile_conditions::needs_reload() {
+                        // start or stop the scripts that depend on the hotplugged devices
+                        let profile_file = ACTIVE_PROFILE
+                            .lock()
+                            .as_ref()
+                            .map(|profile| profile.profile_file.clone());
+
+                        if let Err(e) = switch_profile(profile_file.as_deref(), &dbus_api_tx, true)
+                        {
+                            error!("Could not switch profiles: {}", e);
+                        }
+                    }
+
+                    // device indices may have been re-assigned
+                    pacing::reset();
                 }
 
                 events::notify_observers(events::Event::DaemonShutdown)?;
@@ -1933,6 +2549,16 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 state::save_color_schemes()
                     .unwrap_or_else(|e| error!("Could not save color-schemes: {}", e));
 
+                // a clean shutdown is not a failed startup
+                safe_mode::reset_counter();
+
+                // the polling rates are stored on the mice, so restore them before we exit
+                poll_rate::restore_all();
+
+                // save device health statistics
+                health::save_device_health()
+                    .unwrap_or_else(|e| error!("Could not save device health statistics: {}", e));
+
                 // close all managed devices
                 info!("Closing all devices now...");
 
@@ -1991,13 +2617,6 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
         }
     }
 
-    if util::file_exists("/run/lock/eruption-hotplug-helper.lock") {
-        debug!("Removing stale eruption-hotplug-helper.lock file...");
-
-        fs::remove_file("/run/lock/eruption-hotplug-helper.lock")
-            .unwrap_or_else(|e| warn!("Could not remove lock file: {}", e));
-    }
-
     info!("Exiting now");
 
     Ok(())
@@ -0,0 +1,480 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Exports metrics of the daemon in the Prometheus text exposition format
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::scripting::inspect;
+use crate::{frame_budget, health, pacing};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetricsError {
+    #[error("Invalid bind address of the metrics endpoint: {address}")]
+    InvalidBindAddress { address: String },
+}
+
+/// Upper bounds of the buckets of the frame time histogram, in seconds
+const FRAME_TIME_BUCKETS: &[f64] = &[0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 1.0];
+
+/// Upper bounds of the buckets of the USB write latency histogram, in seconds
+const USB_WRITE_BUCKETS: &[f64] = &[0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1];
+
+/// Max. number of bytes of a request that are read, including its headers
+const MAX_REQUEST_SIZE: u64 = 8192;
+
+/// Max. number of clients that are served concurrently, further connections are dropped
+const MAX_CLIENTS: usize = 4;
+
+/// Number of clients that are currently being served
+static ACTIVE_CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// Time between two consecutive rendered frames
+    static ref FRAME_TIMES: Arc<Mutex<Histogram>> =
+        Arc::new(Mutex::new(Histogram::new(FRAME_TIME_BUCKETS)));
+
+    /// Duration of the LED map transfers, indexed by the device index
+    static ref USB_WRITE_LATENCIES: Arc<Mutex<BTreeMap<u64, Histogram>>> =
+        Arc::new(Mutex::new(BTreeMap::new()));
+}
+
+/// A cumulative histogram, as defined by Prometheus
+#[derive(Debug, Clone)]
+struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(buckets: &'static [f64]) -> Self {
+        Self {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.buckets.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &[(&str, String)]) {
+        for (bound, count) in self.buckets.iter().zip(self.counts.iter()) {
+            let mut labels = labels.to_vec();
+            labels.push(("le", format!("{}", bound)));
+
+            sample(out, &format!("{}_bucket", name), &labels, *count as f64);
+        }
+
+        let mut inf_labels = labels.to_vec();
+        inf_labels.push(("le", "+Inf".to_string()));
+
+        sample(
+            out,
+            &format!("{}_bucket", name),
+            &inf_labels,
+            self.count as f64,
+        );
+        sample(out, &format!("{}_sum", name), labels, self.sum);
+        sample(out, &format!("{}_count", name), labels, self.count as f64);
+    }
+}
+
+/// Record the time that elapsed since the previous frame has been rendered
+pub fn record_frame_time(elapsed: Duration) {
+    FRAME_TIMES.lock().observe(elapsed.as_secs_f64());
+}
+
+/// Record the duration of a transfer of an LED map to the device `index`
+pub fn record_usb_write(index: u64, elapsed: Duration) {
+    USB_WRITE_LATENCIES
+        .lock()
+        .entry(index)
+        .or_insert_with(|| Histogram::new(USB_WRITE_BUCKETS))
+        .observe(elapsed.as_secs_f64());
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, String)], value: f64) {
+    if labels.is_empty() {
+        let _ = writeln!(out, "{} {}", name, value);
+    } else {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label_value(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+    }
+}
+
+/// Render all metrics in the Prometheus text exposition format
+pub fn render() -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "eruption_frame_time_seconds",
+        "histogram",
+        "Time between two consecutive rendered frames",
+    );
+    FRAME_TIMES
+        .lock()
+        .render(&mut out, "eruption_frame_time_seconds", &[]);
+
+    header(
+        &mut out,
+        "eruption_target_fps",
+        "gauge",
+        "The frame rate that the LED maps are currently rendered with",
+    );
+    sample(
+        &mut out,
+        "eruption_target_fps",
+        &[],
+        frame_budget::get_target_fps() as f64,
+    );
+
+    let frame_pacing = pacing::FRAME_PACING
+        .lock()
+        .iter()
+        .map(|(index, pacing)| (*index, pacing.stats))
+        .collect::<BTreeMap<_, _>>();
+
+    header(
+        &mut out,
+        "eruption_frames_sent_total",
+        "counter",
+        "Frames that have been transferred to a device",
+    );
+    for (index, stats) in frame_pacing.iter() {
+        sample(
+            &mut out,
+            "eruption_frames_sent_total",
+            &[("device", index.to_string())],
+            stats.frames_sent as f64,
+        );
+    }
+
+    header(
+        &mut out,
+        "eruption_frames_dropped_total",
+        "counter",
+        "Frames that could not be transferred to a device",
+    );
+    for (index, stats) in frame_pacing.iter() {
+        sample(
+            &mut out,
+            "eruption_frames_dropped_total",
+            &[("device", index.to_string())],
+            stats.frames_dropped as f64,
+        );
+    }
+
+    header(
+        &mut out,
+        "eruption_usb_write_seconds",
+        "histogram",
+        "Duration of the transfers of LED maps to a device",
+    );
+    for (index, histogram) in USB_WRITE_LATENCIES.lock().iter() {
+        histogram.render(
+            &mut out,
+            "eruption_usb_write_seconds",
+            &[("device", index.to_string())],
+        );
+    }
+
+    let device_health = health::DEVICE_HEALTH.read().clone();
+
+    header(
+        &mut out,
+        "eruption_device_failures_total",
+        "counter",
+        "Failures of a device, like disconnects, failed writes, re-initializations or failed initializations",
+    );
+    for (id, health) in device_health.iter() {
+        for (kind, count) in [
+            ("disconnect", health.disconnects),
+            ("failed_write", health.failed_writes),
+            ("reinit_attempt", health.reinit_attempts),
+            ("failed_init", health.failed_inits),
+        ] {
+            sample(
+                &mut out,
+                "eruption_device_failures_total",
+                &[("device_id", id.clone()), ("kind", kind.to_string())],
+                count as f64,
+            );
+        }
+    }
+
+    let inspections = inspect::get_inspections();
+
+    header(
+        &mut out,
+        "eruption_script_frame_cost_seconds",
+        "gauge",
+        "Average time spent in the event handlers of a script per rendered frame",
+    );
+    for inspection in inspections.iter() {
+        sample(
+            &mut out,
+            "eruption_script_frame_cost_seconds",
+            &[("script", inspection.script_file.display().to_string())],
+            inspection.frame_cost_micros / 1_000_000.0,
+        );
+    }
+
+    header(
+        &mut out,
+        "eruption_script_handler_seconds_total",
+        "counter",
+        "Time spent in an event handler of a script",
+    );
+    for inspection in inspections.iter() {
+        for (handler, stats) in inspection.handler_stats.iter() {
+            sample(
+                &mut out,
+                "eruption_script_handler_seconds_total",
+                &[
+                    ("script", inspection.script_file.display().to_string()),
+                    ("handler", handler.clone()),
+                ],
+                stats.total_micros as f64 / 1_000_000.0,
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "eruption_script_handler_calls_total",
+        "counter",
+        "Calls of an event handler of a script",
+    );
+    for inspection in inspections.iter() {
+        for (handler, stats) in inspection.handler_stats.iter() {
+            sample(
+                &mut out,
+                "eruption_script_handler_calls_total",
+                &[
+                    ("script", inspection.script_file.display().to_string()),
+                    ("handler", handler.clone()),
+                ],
+                stats.calls as f64,
+            );
+        }
+    }
+
+    out
+}
+
+/// Read the request line of a request, and skip its headers. At most `MAX_REQUEST_SIZE`
+/// bytes are read
+fn read_request_line(stream: impl Read) -> Result<String> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_SIZE));
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // skip the request headers
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    Ok(request_line)
+}
+
+fn handle_connection(mut stream: TcpStream) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+
+    let request_line = read_request_line(stream.try_clone()?)?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let (status, body) = if method == "GET" && (path == "/metrics" || path == "/") {
+        ("200 OK", render())
+    } else {
+        ("404 Not Found", "Not found\n".to_string())
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+
+    Ok(())
+}
+
+/// Serve the client `stream` on a thread of its own, unless too many clients are connected
+fn serve_client(stream: TcpStream) {
+    if ACTIVE_CLIENTS.fetch_add(1, Ordering::SeqCst) >= MAX_CLIENTS {
+        ACTIVE_CLIENTS.fetch_sub(1, Ordering::SeqCst);
+
+        debug!("Too many metrics clients, dropping the connection");
+
+        return;
+    }
+
+    let result = thread::Builder::new()
+        .name("metrics-client".to_owned())
+        .spawn(move || {
+            handle_connection(stream)
+                .unwrap_or_else(|e| debug!("Could not serve a metrics request: {}", e));
+
+            ACTIVE_CLIENTS.fetch_sub(1, Ordering::SeqCst);
+        });
+
+    if let Err(e) = result {
+        ACTIVE_CLIENTS.fetch_sub(1, Ordering::SeqCst);
+
+        error!("Could not spawn a thread for a metrics client: {}", e);
+    }
+}
+
+/// Spawn the thread that serves the metrics endpoint, if a bind address has been
+/// configured in the section [metrics] of eruption.conf
+pub fn spawn_metrics_thread() -> Result<()> {
+    let address = crate::CONFIG
+        .lock()
+        .as_ref()
+        .unwrap()
+        .get_string("metrics.bind_address")
+        .ok();
+
+    let address = match address {
+        Some(address) => address,
+        None => {
+            debug!("The metrics endpoint is disabled, no bind address has been configured");
+
+            return Ok(());
+        }
+    };
+
+    let bind_address = address
+        .parse::<SocketAddr>()
+        .map_err(|_e| MetricsError::InvalidBindAddress { address })?;
+
+    let listener = TcpListener::bind(bind_address)?;
+
+    info!("Serving metrics on http://{}/metrics", bind_address);
+
+    thread::Builder::new()
+        .name("metrics".to_owned())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => serve_client(stream),
+
+                    Err(e) => error!("Could not accept a metrics connection: {}", e),
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_histogram() {
+        let mut histogram = Histogram::new(&[0.1, 1.0]);
+
+        histogram.observe(0.25);
+        histogram.observe(0.5);
+        histogram.observe(2.0);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "test_seconds", &[("device", "0".to_string())]);
+
+        let expected = "test_seconds_bucket{device=\"0\",le=\"0.1\"} 0\n\
+                        test_seconds_bucket{device=\"0\",le=\"1\"} 2\n\
+                        test_seconds_bucket{device=\"0\",le=\"+Inf\"} 3\n\
+                        test_seconds_sum{device=\"0\"} 2.75\n\
+                        test_seconds_count{device=\"0\"} 3\n";
+
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn oversized_requests_are_truncated() -> Result<()> {
+        let mut request = b"GET /metrics HTTP/1.1\r\n".to_vec();
+        request.extend(std::iter::repeat(b'x').take(MAX_REQUEST_SIZE as usize * 4));
+
+        let mut stream = std::io::Cursor::new(request);
+
+        assert_eq!(read_request_line(&mut stream)?, "GET /metrics HTTP/1.1\r\n");
+        assert_eq!(stream.position(), MAX_REQUEST_SIZE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn escape_label_values() {
+        assert_eq!(escape_label_value("a \"b\"\\c\nd"), "a \\\"b\\\"\\\\c\\nd");
+    }
+}
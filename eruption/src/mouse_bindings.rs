@@ -0,0 +1,143 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::plugins::{macros, ProfilesPlugin};
+use crate::profiles::{MouseBinding, MouseButtonAction};
+use crate::{constants, game_mode};
+
+lazy_static! {
+    /// Mouse button bindings of the active profile
+    static ref MOUSE_BINDINGS: Arc<RwLock<Vec<MouseBinding>>> = Arc::new(RwLock::new(vec![]));
+
+    /// Bindings of the buttons that are currently held down, indexed by their button index.
+    /// Used to release the bound keys, even if the profile has been switched in the meantime
+    static ref HELD_BINDINGS: Arc<RwLock<HashMap<u8, MouseBinding>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+}
+
+/// Assign the mouse button bindings of a newly activated profile
+pub fn set_mouse_bindings(bindings: &[MouseBinding]) {
+    *MOUSE_BINDINGS.write() = bindings.to_vec();
+}
+
+/// Process a mouse button event. Returns `true` if the button is bound by the active
+/// profile, in which case the event shall not be passed through to the system
+pub fn process_button_event(index: u8, is_pressed: bool) -> bool {
+    if is_pressed {
+        let binding = MOUSE_BINDINGS
+            .read()
+            .iter()
+            .find(|binding| binding.button == index)
+            .cloned();
+
+        match binding {
+            Some(binding) => {
+                press(&binding);
+
+                HELD_BINDINGS.write().insert(index, binding);
+
+                true
+            }
+
+            None => false,
+        }
+    } else {
+        match HELD_BINDINGS.write().remove(&index) {
+            Some(binding) => {
+                release(&binding);
+
+                true
+            }
+
+            None => false,
+        }
+    }
+}
+
+fn press(binding: &MouseBinding) {
+    for key in binding.keys.iter() {
+        inject(macros::Message::InjectKey {
+            key: *key,
+            down: true,
+        });
+    }
+
+    if let Some(button) = binding.remap {
+        inject(macros::Message::InjectButtonEvent {
+            button: button as u32,
+            down: true,
+        });
+    }
+
+    if let Some(action) = binding.action {
+        debug!(
+            "Mouse button {} triggered action: {:?}",
+            binding.button, action
+        );
+
+        match action {
+            MouseButtonAction::ToggleGameMode => game_mode::toggle(),
+
+            MouseButtonAction::NextSlot => {
+                let slot = ProfilesPlugin::get_current_slot();
+                ProfilesPlugin::switch_to_slot((slot + 1) % constants::NUM_SLOTS);
+            }
+
+            MouseButtonAction::PreviousSlot => {
+                let slot = ProfilesPlugin::get_current_slot();
+                ProfilesPlugin::switch_to_slot(
+                    (slot + constants::NUM_SLOTS - 1) % constants::NUM_SLOTS,
+                );
+            }
+        }
+    }
+}
+
+fn release(binding: &MouseBinding) {
+    if let Some(button) = binding.remap {
+        inject(macros::Message::InjectButtonEvent {
+            button: button as u32,
+            down: false,
+        });
+    }
+
+    // release the keys in reverse order
+    for key in binding.keys.iter().rev() {
+        inject(macros::Message::InjectKey {
+            key: *key,
+            down: false,
+        });
+    }
+}
+
+fn inject(message: macros::Message) {
+    if let Some(uinput_tx) = macros::UINPUT_TX.read().as_ref() {
+        uinput_tx
+            .send(message)
+            .unwrap_or_else(|e| error!("Could not inject a bound mouse button event: {}", e));
+    }
+}
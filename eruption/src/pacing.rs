@@ -0,0 +1,243 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::hwdevices::{self, DeviceTrait};
+
+lazy_static! {
+    /// Frame pacing state of each device, indexed by the device index
+    pub static ref FRAME_PACING: Arc<Mutex<HashMap<u64, FramePacing>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Counters of the frames that have been rendered for a device
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FramePacingStats {
+    /// Frames that have been transferred to the device
+    pub frames_sent: u64,
+
+    /// Frames that have been held back to honor the maximum update rate of the
+    /// device; deferred frames are coalesced into the next frame that is sent
+    pub frames_deferred: u64,
+
+    /// Frames that could not be transferred, e.g. because the device was busy
+    /// or reported an error
+    pub frames_dropped: u64,
+}
+
+/// Limits the rate of LED map updates that are sent to a device. Devices like e.g.
+/// wireless receivers are unable to keep up with the rate at which we render frames,
+/// so we defer a frame if the previous one has been sent too recently. Since the
+/// render thread always sends the latest LED map, a deferred frame is implicitly
+/// coalesced with all the frames that have been rendered in the meantime
+#[derive(Debug, Clone)]
+pub struct FramePacing {
+    /// Maximum update rate of the device in frames per second, or `None` if unlimited
+    max_update_rate: Option<u32>,
+
+    /// Point in time when the last frame has been sent to the device
+    last_sent: Option<Instant>,
+
+    /// Set if the device did not receive the latest frame yet
+    pending: bool,
+
+    pub stats: FramePacingStats,
+}
+
+impl FramePacing {
+    pub fn new(max_update_rate: Option<u32>) -> Self {
+        Self {
+            max_update_rate: max_update_rate.filter(|rate| *rate > 0),
+            last_sent: None,
+            pending: false,
+            stats: FramePacingStats::default(),
+        }
+    }
+
+    pub fn max_update_rate(&self) -> Option<u32> {
+        self.max_update_rate
+    }
+
+    fn is_due(&self) -> bool {
+        match (self.max_update_rate, self.last_sent) {
+            (Some(rate), Some(last_sent)) => {
+                last_sent.elapsed() >= Duration::from_secs_f64(1.0 / rate as f64)
+            }
+
+            _ => true,
+        }
+    }
+
+    /// Returns `true` if a new frame may be sent to the device now,
+    /// otherwise the frame is deferred
+    pub fn submit(&mut self) -> bool {
+        if self.is_due() {
+            true
+        } else {
+            self.stats.frames_deferred += 1;
+            self.pending = true;
+
+            false
+        }
+    }
+
+    /// Returns `true` if a deferred frame is waiting to be sent, and the
+    /// device is ready to accept it
+    pub fn is_flush_due(&self) -> bool {
+        self.pending && self.is_due()
+    }
+
+    /// Record that the current frame has been transferred to the device
+    pub fn sent(&mut self) {
+        self.last_sent = Some(Instant::now());
+        self.pending = false;
+
+        self.stats.frames_sent += 1;
+    }
+
+    /// Record that the current frame could not be transferred to the device.
+    /// The frame will be re-sent on the next opportunity
+    pub fn dropped(&mut self) {
+        self.pending = true;
+
+        self.stats.frames_dropped += 1;
+    }
+}
+
+/// Submit a new frame for the device `index`, returns `true` if the frame may be
+/// sent to the device now, or `false` if it has been deferred
+pub fn submit_frame(index: u64, device: &dyn DeviceTrait) -> bool {
+    FRAME_PACING
+        .lock()
+        .entry(index)
+        .or_insert_with(|| FramePacing::new(hwdevices::get_max_update_rate(device)))
+        .submit()
+}
+
+/// Returns `true` if a deferred frame of the device `index` is ready to be sent
+pub fn is_flush_due(index: u64) -> bool {
+    FRAME_PACING
+        .lock()
+        .get(&index)
+        .is_some_and(|pacing| pacing.is_flush_due())
+}
+
+/// Returns `true` if a deferred frame of any of the devices is ready to be sent
+pub fn is_any_flush_due() -> bool {
+    FRAME_PACING
+        .lock()
+        .values()
+        .any(|pacing| pacing.is_flush_due())
+}
+
+/// Record that a frame has been transferred to the device `index`
+pub fn frame_sent(index: u64) {
+    if let Some(pacing) = FRAME_PACING.lock().get_mut(&index) {
+        pacing.sent();
+    }
+}
+
+/// Record that a frame could not be transferred to the device `index`
+pub fn frame_dropped(index: u64) {
+    if let Some(pacing) = FRAME_PACING.lock().get_mut(&index) {
+        pacing.dropped();
+    }
+}
+
+/// Returns the frame pacing statistics of the device `index`, or `None` if no
+/// frames have been rendered for the device yet
+pub fn get_stats(index: u64) -> Option<(Option<u32>, FramePacingStats)> {
+    FRAME_PACING
+        .lock()
+        .get(&index)
+        .map(|pacing| (pacing.max_update_rate(), pacing.stats))
+}
+
+/// Discard the frame pacing state of all devices, e.g. after the set of
+/// connected devices changed, since the device indices may be re-assigned
+pub fn reset() {
+    FRAME_PACING.lock().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_deferred_and_flushed() {
+        let mut pacing = FramePacing::new(Some(10));
+
+        assert!(pacing.submit());
+        pacing.sent();
+
+        // the device is still busy with the previous frame
+        assert!(!pacing.submit());
+        assert!(!pacing.submit());
+        assert!(!pacing.is_flush_due());
+
+        pacing.last_sent = Some(Instant::now() - Duration::from_millis(100));
+
+        // the deferred frames are coalesced into a single frame
+        assert!(pacing.is_flush_due());
+        pacing.sent();
+        assert!(!pacing.is_flush_due());
+
+        assert_eq!(
+            pacing.stats,
+            FramePacingStats {
+                frames_sent: 2,
+                frames_deferred: 2,
+                frames_dropped: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn dropped_frames_are_resent() {
+        let mut pacing = FramePacing::new(Some(10));
+
+        assert!(pacing.submit());
+        pacing.dropped();
+
+        pacing.last_sent = Some(Instant::now() - Duration::from_millis(100));
+
+        assert!(pacing.is_flush_due());
+        assert_eq!(pacing.stats.frames_dropped, 1);
+    }
+
+    #[test]
+    fn unlimited_devices_are_never_deferred() {
+        for mut pacing in [FramePacing::new(None), FramePacing::new(Some(0))] {
+            for _ in 0..10 {
+                assert!(pacing.submit());
+                pacing.sent();
+            }
+
+            assert_eq!(pacing.max_update_rate(), None);
+            assert_eq!(pacing.stats.frames_deferred, 0);
+        }
+    }
+}
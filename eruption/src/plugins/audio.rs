@@ -23,15 +23,20 @@ use lazy_static::lazy_static;
 use log::*;
 use mlua::prelude::*;
 use parking_lot::{Mutex, RwLock};
-use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicIsize, Ordering};
 use std::sync::Arc;
 use std::{
     any::Any,
+    fmt,
     time::{Duration, Instant},
 };
 
-use crate::events;
 use crate::plugins::{self, Plugin};
+use crate::profiles::SoundFx;
+use crate::util::ratelimited;
+use crate::{constants, events};
 
 pub mod protocol {
     include!(concat!(env!("OUT_DIR"), "/audio_proxy.rs"));
@@ -43,6 +48,12 @@ pub type Result<T> = std::result::Result<T, eyre::Error>;
 pub enum AudioPluginError {
     #[error("Audio grabber error: {description}")]
     GrabberError { description: String },
+
+    #[error("Invalid audio source: {source_name}")]
+    InvalidAudioSource { source_name: String },
+
+    #[error("Invalid sound effect sample: {file}")]
+    InvalidSample { file: String },
 }
 
 /// The allocated size of the audio grabber buffer
@@ -72,8 +83,62 @@ lazy_static! {
 
     /// Global "sound effects enabled" flag
     pub static ref ENABLE_SFX: AtomicBool = AtomicBool::new(false);
+
+    /// Global volume of sound effects, in percent
+    pub static ref SFX_VOLUME: AtomicI64 = AtomicI64::new(constants::DEFAULT_SFX_VOLUME);
+
+    /// The audio stream that is recorded by the audio proxy
+    static ref AUDIO_SOURCE: Arc<RwLock<AudioSource>> = Arc::new(RwLock::new(AudioSource::Output));
+
+    /// Threshold of the noise gate of the microphone input in dBFS
+    pub static ref NOISE_GATE_THRESHOLD: AtomicI64 = AtomicI64::new(constants::DEFAULT_NOISE_GATE_THRESHOLD);
+
+    /// Sound effects defined by the active profile
+    static ref PROFILE_SFX: Arc<RwLock<Option<SoundFx>>> = Arc::new(RwLock::new(None));
+
+    /// Tempo and beat phase, as estimated by the audio proxy
+    static ref BEAT_STATE: Arc<RwLock<Option<BeatState>>> = Arc::new(RwLock::new(None));
+}
+
+/// Beat information is considered stale after this amount of time
+const BEAT_STATE_TIMEOUT_MILLIS: u64 = 1000;
+
+/// Tempo and beat phase of the recorded audio stream
+#[derive(Debug, Clone, Copy)]
+struct BeatState {
+    bpm: f32,
+
+    /// Phase at the time of reception, already shifted ahead by the latency compensation
+    phase: f32,
+
+    confidence: f32,
+
+    received_at: Instant,
+}
+
+impl BeatState {
+    fn current() -> Option<Self> {
+        let state = (*BEAT_STATE.read())?;
+
+        if state.received_at.elapsed() < Duration::from_millis(BEAT_STATE_TIMEOUT_MILLIS) {
+            Some(state)
+        } else {
+            None
+        }
+    }
+
+    /// Extrapolate the phase to the current point in time
+    fn phase_now(&self) -> f32 {
+        let beats = self.received_at.elapsed().as_secs_f32() * self.bpm / 60.0;
+
+        (self.phase + beats).rem_euclid(1.0)
+    }
 }
 
+/// Set when the audio source or the noise gate have been changed, and the audio proxy
+/// has not been notified yet
+static AUDIO_SOURCE_CHANGED: AtomicBool = AtomicBool::new(true);
+
 // Record audio?
 pub static AUDIO_GRABBER_RECORD_AUDIO: AtomicBool = AtomicBool::new(false);
 static AUDIO_GRABBER_RECORDING: AtomicBool = AtomicBool::new(false);
@@ -82,6 +147,151 @@ static AUDIO_GRABBER_RECORDING: AtomicBool = AtomicBool::new(false);
 static AUDIO_GRABBER_PERFORM_RMS_COMPUTATION: AtomicBool = AtomicBool::new(false);
 static AUDIO_GRABBER_PERFORM_FFT_COMPUTATION: AtomicBool = AtomicBool::new(false);
 
+/// The audio stream that is visualized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioSource {
+    /// The audio output, i.e. the monitor of the default sink
+    Output,
+
+    /// The default audio input, with a noise gate applied
+    Microphone,
+}
+
+impl fmt::Display for AudioSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioSource::Output => write!(f, "output"),
+            AudioSource::Microphone => write!(f, "microphone"),
+        }
+    }
+}
+
+impl FromStr for AudioSource {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "output" => Ok(AudioSource::Output),
+            "microphone" => Ok(AudioSource::Microphone),
+
+            _ => Err(AudioPluginError::InvalidAudioSource {
+                source_name: s.to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+/// Returns the audio stream that is visualized
+pub fn get_audio_source() -> AudioSource {
+    *AUDIO_SOURCE.read()
+}
+
+/// Select the audio stream that is visualized
+pub fn set_audio_source(source: AudioSource) {
+    *AUDIO_SOURCE.write() = source;
+
+    AUDIO_SOURCE_CHANGED.store(true, Ordering::SeqCst);
+}
+
+/// Set the threshold of the noise gate of the microphone input in dBFS
+pub fn set_noise_gate_threshold(threshold: i64) {
+    NOISE_GATE_THRESHOLD.store(threshold, Ordering::SeqCst);
+
+    AUDIO_SOURCE_CHANGED.store(true, Ordering::SeqCst);
+}
+
+/// Events that may trigger the playback of a sound effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SfxEvent {
+    KeyDown,
+    KeyUp,
+    ProfileSwitch,
+    DeviceDisconnect,
+}
+
+/// Set the sound effects of the active profile
+pub fn set_profile_sfx(sfx: Option<&SoundFx>) {
+    *PROFILE_SFX.write() = sfx.cloned();
+}
+
+/// Returns the sample that shall be played back on `event`, along with its volume.
+/// Key presses fall back to the stock sound effects, if the profile does not define them.
+/// Samples must be plain file names, they are always loaded from the sound effects directory
+fn get_sfx_sample(event: SfxEvent) -> Result<Option<(PathBuf, f32)>> {
+    let profile_sfx = PROFILE_SFX.read();
+
+    let (sample, volume) = match profile_sfx.as_ref() {
+        Some(sfx) => {
+            let sample = match event {
+                SfxEvent::KeyDown => sfx.key_down.clone(),
+                SfxEvent::KeyUp => sfx.key_up.clone(),
+                SfxEvent::ProfileSwitch => sfx.profile_switch.clone(),
+                SfxEvent::DeviceDisconnect => sfx.device_disconnect.clone(),
+            };
+
+            (sample, sfx.volume.clamp(0.0, 1.0))
+        }
+
+        None => (None, 1.0),
+    };
+
+    let sample = match sample.or_else(|| match event {
+        SfxEvent::KeyDown => Some(PathBuf::from("key-down.wav")),
+        SfxEvent::KeyUp => Some(PathBuf::from("key-up.wav")),
+
+        _ => None,
+    }) {
+        Some(sample) => sample,
+        None => return Ok(None),
+    };
+
+    if !is_plain_file_name(&sample) {
+        return Err(AudioPluginError::InvalidSample {
+            file: sample.to_string_lossy().to_string(),
+        }
+        .into());
+    }
+
+    let volume = volume * SFX_VOLUME.load(Ordering::SeqCst).clamp(0, 100) as f32 / 100.0;
+
+    Ok(Some((
+        Path::new(constants::DEFAULT_SFX_DIR).join(sample),
+        volume,
+    )))
+}
+
+/// Returns `true` if `file` is a file name without any directory components, so that it
+/// can not escape the directory it is joined to
+fn is_plain_file_name(file: &Path) -> bool {
+    let mut components = file.components();
+
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+/// Play back the sound effect that is associated with `event`, if sound effects are enabled
+pub fn play_event_sfx(event: SfxEvent) -> Result<()> {
+    if ENABLE_SFX.load(Ordering::SeqCst) {
+        match get_sfx_sample(event) {
+            Ok(Some((sample, volume))) => {
+                if let Some(backend) = AUDIO_BACKEND.lock().as_ref() {
+                    backend.play_sfx(&sample, volume)?;
+                }
+            }
+
+            Ok(None) => {}
+
+            // an invalid sample of the profile must not stop the other event observers
+            Err(e) => ratelimited::warn!("Could not play back a sound effect: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
 pub fn reset_audio_backend() {
     AUDIO_GRABBER_RECORD_AUDIO.store(false, Ordering::SeqCst);
 
@@ -151,6 +361,28 @@ impl AudioPlugin {
         AUDIO_GRABBER_BUFFER.read().to_vec()
     }
 
+    pub fn get_audio_bpm() -> f32 {
+        AUDIO_GRABBER_RECORD_AUDIO.store(true, Ordering::SeqCst);
+
+        BeatState::current().map(|state| state.bpm).unwrap_or(0.0)
+    }
+
+    pub fn get_audio_beat_phase() -> f32 {
+        AUDIO_GRABBER_RECORD_AUDIO.store(true, Ordering::SeqCst);
+
+        BeatState::current()
+            .map(|state| state.phase_now())
+            .unwrap_or(0.0)
+    }
+
+    pub fn get_audio_beat_confidence() -> f32 {
+        AUDIO_GRABBER_RECORD_AUDIO.store(true, Ordering::SeqCst);
+
+        BeatState::current()
+            .map(|state| state.confidence)
+            .unwrap_or(0.0)
+    }
+
     pub fn get_audio_volume() -> isize {
         if let Some(backend) = &*AUDIO_BACKEND.lock() {
             backend.get_master_volume().unwrap_or(0) * 100 / u16::MAX as isize
@@ -183,21 +415,11 @@ impl Plugin for AudioPlugin {
 
         events::register_observer(|event: &events::Event| {
             match event {
-                events::Event::KeyDown(_index) => {
-                    if ENABLE_SFX.load(Ordering::SeqCst) {
-                        if let Some(backend) = AUDIO_BACKEND.lock().as_ref() {
-                            backend.play_sfx(0)?;
-                        }
-                    }
-                }
+                events::Event::KeyDown(_index) => play_event_sfx(SfxEvent::KeyDown)?,
+                events::Event::KeyUp(_index) => play_event_sfx(SfxEvent::KeyUp)?,
 
-                events::Event::KeyUp(_index) => {
-                    if ENABLE_SFX.load(Ordering::SeqCst) {
-                        if let Some(backend) = AUDIO_BACKEND.lock().as_ref() {
-                            backend.play_sfx(1)?;
-                        }
-                    }
-                }
+                events::Event::ProfileChanged => play_event_sfx(SfxEvent::ProfileSwitch)?,
+                events::Event::DeviceDisconnected => play_event_sfx(SfxEvent::DeviceDisconnect)?,
 
                 _ => (),
             };
@@ -223,6 +445,18 @@ impl Plugin for AudioPlugin {
             lua_ctx.create_function(move |_, ()| Ok(AudioPlugin::get_audio_raw_data()))?;
         globals.set("get_audio_raw_data", get_audio_raw_data)?;
 
+        let get_audio_bpm =
+            lua_ctx.create_function(move |_, ()| Ok(AudioPlugin::get_audio_bpm()))?;
+        globals.set("get_audio_bpm", get_audio_bpm)?;
+
+        let get_audio_beat_phase =
+            lua_ctx.create_function(move |_, ()| Ok(AudioPlugin::get_audio_beat_phase()))?;
+        globals.set("get_audio_beat_phase", get_audio_beat_phase)?;
+
+        let get_audio_beat_confidence =
+            lua_ctx.create_function(move |_, ()| Ok(AudioPlugin::get_audio_beat_confidence()))?;
+        globals.set("get_audio_beat_confidence", get_audio_beat_confidence)?;
+
         let is_audio_muted =
             lua_ctx.create_function(move |_, ()| Ok(AudioPlugin::is_audio_muted()))?;
         globals.set("is_audio_muted", is_audio_muted)?;
@@ -258,6 +492,8 @@ mod backends {
     use super::AUDIO_SPECTRUM;
     use super::CURRENT_RMS;
     use super::FFT_SIZE;
+    use super::{AudioSource, AUDIO_SOURCE_CHANGED, NOISE_GATE_THRESHOLD};
+    use super::{BeatState, BEAT_STATE};
 
     use flume::{self, unbounded, Receiver, Sender};
     use lazy_static::lazy_static;
@@ -268,6 +504,7 @@ mod backends {
     use std::fs;
     use std::io::Cursor;
     use std::os::unix::prelude::PermissionsExt;
+    use std::path::{Path, PathBuf};
     use std::sync::atomic::AtomicBool;
     use std::sync::atomic::AtomicI32;
     use std::sync::Arc;
@@ -281,15 +518,15 @@ mod backends {
     use std::f32::consts::PI;
     use std::mem::MaybeUninit;
     use std::os::unix::io::AsRawFd;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
     use protocol::response::Payload;
 
     lazy_static! {
         pub static ref LISTENER: Arc<Mutex<Option<Socket>>> = Arc::new(Mutex::new(None));
 
-        pub static ref SFX_TX: Arc<Mutex<Option<Sender<u32>>>> = Arc::new(Mutex::new(None));
-        pub static ref SFX_RX: Arc<Mutex<Option<Receiver<u32>>>> = Arc::new(Mutex::new(None));
+        pub static ref SFX_TX: Arc<Mutex<Option<Sender<SfxRequest>>>> = Arc::new(Mutex::new(None));
+        pub static ref SFX_RX: Arc<Mutex<Option<Receiver<SfxRequest>>>> = Arc::new(Mutex::new(None));
 
         /// Audio device master volume
         static ref MASTER_VOLUME: AtomicI32 = AtomicI32::new(0);
@@ -298,10 +535,19 @@ mod backends {
         static ref AUDIO_MUTED: AtomicBool = AtomicBool::new(false);
     }
 
+    /// A request to play back a waveform audio file
+    #[derive(Debug, Clone)]
+    pub struct SfxRequest {
+        pub file: PathBuf,
+
+        /// Volume in the range `0.0..=1.0`
+        pub volume: f32,
+    }
+
     /// Audio backend trait, defines an interface to the player and
     /// grabber functionality
     pub trait AudioBackend {
-        fn play_sfx(&self, id: u32) -> Result<()>;
+        fn play_sfx(&self, file: &Path, volume: f32) -> Result<()>;
 
         fn start_audio_grabber(&self) -> Result<()>;
         fn stop_audio_grabber(&self) -> Result<()>;
@@ -315,7 +561,7 @@ mod backends {
     pub struct NullBackend {}
 
     impl AudioBackend for NullBackend {
-        fn play_sfx(&self, _id: u32) -> Result<()> {
+        fn play_sfx(&self, _file: &Path, _volume: f32) -> Result<()> {
             Ok(())
         }
 
@@ -357,7 +603,7 @@ mod backends {
 
             LISTENER.lock().replace(listener);
 
-            let (tx, rx): (Sender<u32>, Receiver<u32>) = unbounded();
+            let (tx, rx): (Sender<SfxRequest>, Receiver<SfxRequest>) = unbounded();
 
             *SFX_TX.lock() = Some(tx);
             *SFX_RX.lock() = Some(rx);
@@ -411,18 +657,21 @@ mod backends {
                                 }
                             }
 
+                            // the newly connected proxy has to be told which audio stream to record
+                            AUDIO_SOURCE_CHANGED.store(true, Ordering::SeqCst);
+
                             // connection successful, enter event loop now
                             'EVENT_LOOP: loop {
                                 if crate::QUIT.load(Ordering::SeqCst) {
                                     break 'EVENT_LOOP;
                                 }
 
-                                let pending_sfx_id = if let Some(ref rx) = *SFX_RX.lock() {
+                                let pending_sfx = if let Some(ref rx) = *SFX_RX.lock() {
                                     // do we have any requests to play a sound effect?
                                     match rx.recv_timeout(Duration::from_millis(1)) {
-                                        Ok(idx) => {
-                                            trace!("Play back SFX with ID: {}", idx);
-                                            Some(idx)
+                                        Ok(request) => {
+                                            trace!("Play back SFX: {}", request.file.display());
+                                            Some(request)
                                         }
 
                                         Err(_e) => {
@@ -617,6 +866,29 @@ mod backends {
                                                             };
                                                         }
 
+                                                        protocol::CommandType::BeatInfo => {
+                                                            if let Some(Payload::BeatInfo(val)) =
+                                                                response.payload
+                                                            {
+                                                                trace!(
+                                                                    "Beat info: {} bpm, phase: {}, latency: {} ms",
+                                                                    val.bpm,
+                                                                    val.phase,
+                                                                    val.latency_millis
+                                                                );
+
+                                                                *BEAT_STATE.write() =
+                                                                    Some(BeatState {
+                                                                        bpm: val.bpm,
+                                                                        phase: val.phase,
+                                                                        confidence: val.confidence,
+                                                                        received_at: Instant::now(),
+                                                                    });
+                                                            } else {
+                                                                error!("Invalid payload received");
+                                                            };
+                                                        }
+
                                                         protocol::CommandType::Noop => {
                                                             /* Do nothing */
 
@@ -646,18 +918,78 @@ mod backends {
 
                                     if poll_fds[0].revents().unwrap().contains(PollFlags::POLLOUT) {
                                         // pending sound effect?
-                                        if let Some(sfx_id) = pending_sfx_id {
+                                        if let Some(request) = pending_sfx {
                                             debug!(
-                                                "Notifying audio proxy to play SFX with ID: {}",
-                                                sfx_id
+                                                "Notifying audio proxy to play SFX: {}",
+                                                request.file.display()
                                             );
 
                                             let mut command = protocol::Command::default();
-                                            command
-                                                .set_command_type(protocol::CommandType::PlaySfx);
+                                            command.set_command_type(
+                                                protocol::CommandType::PlaySample,
+                                            );
 
                                             command.payload =
-                                                Some(protocol::command::Payload::Id(sfx_id));
+                                                Some(protocol::command::Payload::Sample(
+                                                    protocol::Sample {
+                                                        file: request
+                                                            .file
+                                                            .to_string_lossy()
+                                                            .to_string(),
+                                                        volume: request.volume,
+                                                    },
+                                                ));
+
+                                            let mut buf = Vec::new();
+                                            command.encode_length_delimited(&mut buf)?;
+
+                                            // send data
+                                            match socket.send(&buf) {
+                                                Ok(_n) => {}
+
+                                                Err(_e) => {
+                                                    return Err(AudioPluginError::GrabberError {
+                                                        description: "Lost connection to proxy"
+                                                            .to_owned(),
+                                                    }
+                                                    .into());
+                                                }
+                                            }
+                                        }
+
+                                        // pending change of the audio source?
+                                        if AUDIO_SOURCE_CHANGED.swap(false, Ordering::SeqCst) {
+                                            let source = super::get_audio_source();
+
+                                            debug!(
+                                                "Notifying audio proxy to record from: {}",
+                                                source
+                                            );
+
+                                            let mut command = protocol::Command::default();
+                                            command.set_command_type(
+                                                protocol::CommandType::SetAudioSource,
+                                            );
+
+                                            let mut config = protocol::AudioSourceConfig {
+                                                noise_gate_threshold: NOISE_GATE_THRESHOLD
+                                                    .load(Ordering::SeqCst)
+                                                    as f32,
+                                                ..Default::default()
+                                            };
+
+                                            config.set_source(match source {
+                                                AudioSource::Output => {
+                                                    protocol::AudioSource::Output
+                                                }
+                                                AudioSource::Microphone => {
+                                                    protocol::AudioSource::Microphone
+                                                }
+                                            });
+
+                                            command.payload = Some(
+                                                protocol::command::Payload::AudioSource(config),
+                                            );
 
                                             let mut buf = Vec::new();
                                             command.encode_length_delimited(&mut buf)?;
@@ -759,9 +1091,14 @@ mod backends {
     }
 
     impl AudioBackend for ProxyBackend {
-        fn play_sfx(&self, id: u32) -> Result<()> {
+        fn play_sfx(&self, file: &Path, volume: f32) -> Result<()> {
             if let Some(ref tx) = *SFX_TX.lock() {
-                tx.send_timeout(id, Duration::from_millis(1))?;
+                let request = SfxRequest {
+                    file: file.to_path_buf(),
+                    volume,
+                };
+
+                tx.send_timeout(request, Duration::from_millis(1))?;
             }
 
             Ok(())
@@ -800,3 +1137,19 @@ mod backends {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_must_be_plain_file_names() {
+        assert!(is_plain_file_name(Path::new("key-down.wav")));
+
+        assert!(!is_plain_file_name(Path::new("")));
+        assert!(!is_plain_file_name(Path::new("/etc/shadow")));
+        assert!(!is_plain_file_name(Path::new("../key-down.wav")));
+        assert!(!is_plain_file_name(Path::new("..")));
+        assert!(!is_plain_file_name(Path::new("sfx/key-down.wav")));
+    }
+}
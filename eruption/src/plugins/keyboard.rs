@@ -23,6 +23,7 @@
 use mlua::prelude::*;
 use std::any::Any;
 
+use crate::indicators;
 use crate::plugins::{self, Plugin};
 
 // pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -62,6 +63,24 @@ impl Plugin for KeyboardPlugin {
             .create_function(|_, key_index: usize| Ok(KeyboardPlugin::get_key_state(key_index)))?;
         globals.set("get_key_state", get_key_state)?;
 
+        let get_lock_state = lua_ctx.create_function(|_, ()| {
+            let lock_state = indicators::get_lock_state();
+
+            Ok((
+                lock_state.caps_lock,
+                lock_state.num_lock,
+                lock_state.scroll_lock,
+            ))
+        })?;
+        globals.set("get_lock_state", get_lock_state)?;
+
+        let get_keyboard_layout = lua_ctx.create_function(|_, ()| {
+            let layout = indicators::get_keyboard_layout();
+
+            Ok((layout.group, layout.name))
+        })?;
+        globals.set("get_keyboard_layout", get_keyboard_layout)?;
+
         Ok(())
     }
 
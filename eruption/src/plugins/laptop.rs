@@ -0,0 +1,133 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use mlua::prelude::*;
+use parking_lot::RwLock;
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::plugins::{self, Plugin};
+use crate::scripting::script;
+use crate::{FAILED_TXS, LUA_TXS};
+
+// pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// State of the lid and of the external displays, as reported by the process monitor
+    static ref HARDWARE_STATE: Arc<RwLock<HardwareState>> = Arc::new(RwLock::new(HardwareState::default()));
+}
+
+/// State of the lid and of the external displays, as reported by the hardware sensor
+/// of the process monitor
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HardwareState {
+    pub lid_closed: bool,
+
+    /// DRM connectors of the connected external displays, e.g. `HDMI-A-1`
+    pub external_displays: Vec<String>,
+}
+
+/// Returns the state of the lid and of the external displays
+pub fn get_hardware_state() -> HardwareState {
+    HARDWARE_STATE.read().clone()
+}
+
+/// Set the state of the lid and of the external displays, and notify the Lua VMs of changes
+pub fn set_hardware_state(state: HardwareState) {
+    let previous = std::mem::replace(&mut *HARDWARE_STATE.write(), state.clone());
+
+    let mut messages = vec![];
+
+    if previous.lid_closed != state.lid_closed {
+        debug!("Lid closed: {}", state.lid_closed);
+
+        messages.push(script::Message::LidChanged(state.lid_closed));
+    }
+
+    if previous.external_displays != state.external_displays {
+        debug!("External displays changed: {:?}", state.external_displays);
+
+        messages.push(script::Message::DisplaysChanged(state.external_displays));
+    }
+
+    for message in messages {
+        for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+            if !FAILED_TXS.read().contains(&idx) {
+                lua_tx.send(message.clone()).unwrap_or_else(|e| {
+                    error!("Could not send a pending hardware event to a Lua VM: {}", e)
+                });
+            } else {
+                warn!("Not sending a message to a failed tx");
+            }
+        }
+    }
+}
+
+/// A plugin that exposes the state of the lid of laptops and of the external displays to Lua scripts
+pub struct LaptopPlugin {}
+
+impl LaptopPlugin {
+    pub fn new() -> Self {
+        LaptopPlugin {}
+    }
+}
+
+#[async_trait::async_trait]
+impl Plugin for LaptopPlugin {
+    fn get_name(&self) -> String {
+        "Laptop".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Exposes the state of the lid and the connected external displays".to_string()
+    }
+
+    fn initialize(&mut self) -> plugins::Result<()> {
+        Ok(())
+    }
+
+    fn register_lua_funcs(&self, lua_ctx: &Lua) -> mlua::Result<()> {
+        let globals = lua_ctx.globals();
+
+        let is_lid_closed = lua_ctx.create_function(|_, ()| Ok(get_hardware_state().lid_closed))?;
+        globals.set("is_lid_closed", is_lid_closed)?;
+
+        let get_external_displays =
+            lua_ctx.create_function(|_, ()| Ok(get_hardware_state().external_displays))?;
+        globals.set("get_external_displays", get_external_displays)?;
+
+        Ok(())
+    }
+
+    async fn main_loop_hook(&self, _ticks: u64) {}
+
+    fn sync_main_loop_hook(&self, _ticks: u64) {}
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -20,17 +20,20 @@
 */
 
 use evdev_rs::{enums::*, DeviceWrapper, InputEvent, TimeVal, UInputDevice, UninitDevice};
-use flume::{unbounded, Sender};
+use flume::{bounded, unbounded, Sender};
 use lazy_static::lazy_static;
 use log::*;
 use mlua::prelude::*;
 use parking_lot::RwLock;
 use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{any::Any, thread};
 
+use crate::constants;
 use crate::plugins::{self, Plugin};
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -38,13 +41,24 @@ pub type Result<T> = std::result::Result<T, eyre::Error>;
 pub enum Message {
     // keyboard related
     MirrorKey(evdev_rs::InputEvent),
-    InjectKey { key: u32, down: bool },
+    InjectKey {
+        key: u32,
+        down: bool,
+    },
 
     // mouse related
     MirrorMouseEvent(evdev_rs::InputEvent),
     MirrorMouseEventImmediate(evdev_rs::InputEvent),
-    InjectButtonEvent { button: u32, down: bool },
-    InjectMouseWheelEvent { direction: u32 },
+    InjectButtonEvent {
+        button: u32,
+        down: bool,
+    },
+    InjectMouseWheelEvent {
+        direction: u32,
+    },
+
+    /// Release all keys and buttons that are held down on the virtual devices
+    ReleaseHeldKeys(Sender<()>),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -65,6 +79,50 @@ lazy_static! {
 thread_local! {
     static KEYBOARD_DEVICE: RefCell<Option<UInputDevice>> = RefCell::new(None);
     static MOUSE_DEVICE: RefCell<Option<UInputDevice>> = RefCell::new(None);
+
+    /// Key codes of the keys that are held down on the virtual keyboard
+    static HELD_KEYS: RefCell<BTreeSet<u32>> = RefCell::new(BTreeSet::new());
+
+    /// Key codes of the buttons that are held down on the virtual mouse
+    static HELD_BUTTONS: RefCell<BTreeSet<u32>> = RefCell::new(BTreeSet::new());
+}
+
+/// Release all keys and buttons that are held down on the virtual devices, e.g. before the
+/// input devices are handed back to the OS. Their releases would not be mirrored otherwise,
+/// so the keys would be stuck. Blocks until the releases have been injected
+pub fn release_held_keys() {
+    let (done_tx, done_rx) = bounded(1);
+
+    let sent = UINPUT_TX
+        .read()
+        .as_ref()
+        .map(|uinput_tx| uinput_tx.send(Message::ReleaseHeldKeys(done_tx)).is_ok())
+        .unwrap_or(false);
+
+    if sent {
+        done_rx
+            .recv_timeout(Duration::from_millis(
+                constants::RELEASE_HELD_KEYS_TIMEOUT_MILLIS,
+            ))
+            .unwrap_or_else(|e| error!("Could not release the held keys: {}", e));
+    }
+}
+
+/// Keep track of the keys or buttons `held` that are held down on a virtual device
+fn track_held_key(held: &RefCell<BTreeSet<u32>>, event_code: &EventCode, value: i32) {
+    if let EventCode::EV_KEY(key) = event_code {
+        match value {
+            0 => {
+                held.borrow_mut().remove(&(*key as u32));
+            }
+
+            1 => {
+                held.borrow_mut().insert(*key as u32);
+            }
+
+            _ => (),
+        }
+    }
 }
 
 /// Implements support for macros by registering a virtual keyboard and a
@@ -419,6 +477,8 @@ impl MacrosPlugin {
     fn inject_single_key(key: EV_KEY, value: i32, time: &TimeVal) -> Result<()> {
         // let mut do_initialize = false;
 
+        HELD_KEYS.with(|held| track_held_key(held, &EventCode::EV_KEY(key), value));
+
         KEYBOARD_DEVICE.with(|dev| {
             let device = dev.borrow();
 
@@ -455,6 +515,8 @@ impl MacrosPlugin {
     fn inject_single_mouse_event(button: EV_KEY, value: i32, time: &TimeVal) -> Result<()> {
         // let mut do_initialize = false;
 
+        HELD_BUTTONS.with(|held| track_held_key(held, &EventCode::EV_KEY(button), value));
+
         MOUSE_DEVICE.with(|dev| {
             let device = dev.borrow();
 
@@ -491,6 +553,8 @@ impl MacrosPlugin {
     fn inject_key_event(event: evdev_rs::InputEvent) -> Result<()> {
         // let mut do_initialize = false;
 
+        HELD_KEYS.with(|held| track_held_key(held, &event.event_code, event.value));
+
         KEYBOARD_DEVICE.with(|dev| {
             trace!("Injecting: {:?}", event);
 
@@ -513,6 +577,8 @@ impl MacrosPlugin {
     fn inject_mouse_event(event: evdev_rs::InputEvent) -> Result<()> {
         // let mut do_initialize = false;
 
+        HELD_BUTTONS.with(|held| track_held_key(held, &event.event_code, event.value));
+
         MOUSE_DEVICE.with(|dev| {
             trace!("Injecting: {:?}", event);
 
@@ -536,6 +602,8 @@ impl MacrosPlugin {
     fn inject_mouse_event_immediate(event: evdev_rs::InputEvent) -> Result<()> {
         // let mut do_initialize = false;
 
+        HELD_BUTTONS.with(|held| track_held_key(held, &event.event_code, event.value));
+
         MOUSE_DEVICE.with(|dev| {
             // trace!("Injecting: {:?}", event);
 
@@ -566,6 +634,32 @@ impl MacrosPlugin {
         Ok(())
     }
 
+    /// Inject a release of all keys and buttons that are held down on the virtual devices
+    fn release_held_keys() -> Result<()> {
+        let time = evdev_rs::TimeVal {
+            tv_sec: 0,
+            tv_usec: 0,
+        };
+
+        let held_keys = HELD_KEYS.with(|held| held.borrow().clone());
+
+        for key in held_keys.into_iter().filter_map(int_to_ev_key) {
+            debug!("Releasing the held key: {:?}", key);
+
+            Self::inject_single_key(key, 0, &time)?;
+        }
+
+        let held_buttons = HELD_BUTTONS.with(|held| held.borrow().clone());
+
+        for button in held_buttons.into_iter().filter_map(int_to_ev_key) {
+            debug!("Releasing the held button: {:?}", button);
+
+            Self::inject_single_mouse_event(button, 0, &time)?;
+        }
+
+        Ok(())
+    }
+
     fn spawn_uinput_thread() -> Result<()> {
         let (uinput_tx, uinput_rx) = unbounded();
 
@@ -628,6 +722,12 @@ impl MacrosPlugin {
                         Message::InjectMouseWheelEvent { direction: _ } => {
                             // REL_RESERVED
                         }
+
+                        Message::ReleaseHeldKeys(done_tx) => {
+                            Self::release_held_keys()?;
+
+                            done_tx.send(()).ok();
+                        }
                     }
                 }
             })?;
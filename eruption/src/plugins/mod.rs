@@ -23,29 +23,35 @@ pub mod animal;
 pub mod audio;
 pub mod introspection;
 pub mod keyboard;
+pub mod laptop;
 pub mod macros;
 pub mod mouse;
 pub mod persistence;
 pub mod plugin;
+pub mod pointer;
 pub mod profiles;
 pub mod sdk_support;
 pub mod sensors;
 pub mod system;
 pub mod uleds;
+pub mod window;
 
 pub use animal::AnimalPlugin;
 pub use audio::AudioPlugin;
 pub use introspection::IntrospectionPlugin;
 pub use keyboard::KeyboardPlugin;
+pub use laptop::LaptopPlugin;
 pub use macros::MacrosPlugin;
 pub use mouse::MousePlugin;
 pub use persistence::PersistencePlugin;
 pub use plugin::Plugin;
+pub use pointer::PointerPlugin;
 pub use profiles::ProfilesPlugin;
 pub use sdk_support::SdkSupportPlugin;
 pub use sensors::SensorsPlugin;
 pub use system::SystemPlugin;
 pub use uleds::UledsPlugin;
+pub use window::WindowPlugin;
 
 use log::*;
 
@@ -77,6 +83,18 @@ pub fn register_plugins() -> Result<()> {
         .register_plugin(Box::new(MousePlugin::new()))
         .map_err(|_e| error!("An error occurred during initialization of the plugin"));
 
+    let _ = plugin_manager
+        .register_plugin(Box::new(PointerPlugin::new()))
+        .map_err(|_e| error!("An error occurred during initialization of the plugin"));
+
+    let _ = plugin_manager
+        .register_plugin(Box::new(WindowPlugin::new()))
+        .map_err(|_e| error!("An error occurred during initialization of the plugin"));
+
+    let _ = plugin_manager
+        .register_plugin(Box::new(LaptopPlugin::new()))
+        .map_err(|_e| error!("An error occurred during initialization of the plugin"));
+
     let _ = plugin_manager
         .register_plugin(Box::new(MacrosPlugin::new()))
         .map_err(|_e| error!("An error occurred during initialization of the plugin"));
@@ -0,0 +1,215 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+// use log::*;
+use mlua::prelude::*;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::constants;
+use crate::events;
+use crate::plugins::{self, Plugin};
+
+// pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// The estimated position of the mouse pointer
+    static ref POINTER_STATE: Arc<Mutex<PointerState>> = Arc::new(Mutex::new(PointerState::new()));
+}
+
+/// Estimates the position of the mouse pointer on the screen. Relative motion events of
+/// the mouse are integrated into a normalized position in the range `0.0..=1.0`, which is
+/// pulled back to the center of the screen over time, since we can not know about
+/// pointer acceleration or the size of the screen. If a compositor sensor reports
+/// the absolute position of the pointer, the estimate is re-anchored to it
+#[derive(Debug, Clone)]
+struct PointerState {
+    x: f64,
+    y: f64,
+
+    /// Point in time of the last absolute position reported by a compositor sensor
+    last_absolute: Option<Instant>,
+
+    /// Point in time of the last relative motion event
+    last_motion: Instant,
+
+    /// Point in time when decay has been applied last
+    last_decay: Instant,
+}
+
+impl PointerState {
+    fn new() -> Self {
+        Self {
+            x: 0.5,
+            y: 0.5,
+            last_absolute: None,
+            last_motion: Instant::now(),
+            last_decay: Instant::now(),
+        }
+    }
+
+    /// Returns `true` if a compositor sensor reported the position of the pointer recently
+    fn has_absolute_position(&self) -> bool {
+        self.last_absolute.is_some_and(|t| {
+            t.elapsed() < Duration::from_millis(constants::POINTER_ABSOLUTE_TIMEOUT_MILLIS)
+        })
+    }
+
+    fn move_relative(&mut self, direction: u8, value: i32) {
+        let delta = value as f64 * constants::POINTER_SENSITIVITY;
+
+        match direction {
+            1 => self.x = (self.x + delta).clamp(0.0, 1.0),
+            2 => self.y = (self.y + delta).clamp(0.0, 1.0),
+
+            _ => return,
+        }
+
+        self.last_motion = Instant::now();
+    }
+
+    fn set_absolute(&mut self, x: f64, y: f64) {
+        self.x = x.clamp(0.0, 1.0);
+        self.y = y.clamp(0.0, 1.0);
+
+        self.last_absolute = Some(Instant::now());
+    }
+
+    /// Pull the estimated position towards the center of the screen, once the
+    /// pointer has been idle for a while. Does nothing while we know the
+    /// absolute position of the pointer
+    fn decay(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last_decay).as_secs_f64();
+
+        self.last_decay = now;
+
+        if self.has_absolute_position()
+            || self.last_motion.elapsed()
+                < Duration::from_millis(constants::POINTER_DECAY_DELAY_MILLIS)
+        {
+            return;
+        }
+
+        let factor = 0.5_f64.powf(elapsed / constants::POINTER_DECAY_HALF_LIFE_SECS);
+
+        self.x = 0.5 + (self.x - 0.5) * factor;
+        self.y = 0.5 + (self.y - 0.5) * factor;
+    }
+}
+
+/// Set the absolute position of the mouse pointer, as reported by a compositor sensor.
+/// The coordinates are normalized to the range `0.0..=1.0`
+pub fn set_absolute_pointer_position(x: f64, y: f64) {
+    POINTER_STATE.lock().set_absolute(x, y);
+}
+
+/// A plugin that estimates the position of the mouse pointer
+pub struct PointerPlugin {}
+
+impl PointerPlugin {
+    pub fn new() -> Self {
+        PointerPlugin {}
+    }
+
+    /// Returns the normalized position of the pointer
+    pub(crate) fn get_pointer_position() -> (f64, f64) {
+        let state = POINTER_STATE.lock();
+
+        (state.x, state.y)
+    }
+
+    /// Returns the position of the pointer, mapped to the coordinates of the canvas
+    pub(crate) fn get_pointer_canvas_position() -> (usize, usize) {
+        let (x, y) = Self::get_pointer_position();
+
+        let x = (x * (constants::CANVAS_WIDTH - 1) as f64).round() as usize;
+        let y = (y * (constants::CANVAS_HEIGHT - 1) as f64).round() as usize;
+
+        (x, y)
+    }
+
+    /// Returns `true` if the position has been reported by a compositor sensor,
+    /// `false` if it is an estimate based on the relative motion of the mouse
+    pub(crate) fn is_pointer_position_absolute() -> bool {
+        POINTER_STATE.lock().has_absolute_position()
+    }
+}
+
+#[async_trait::async_trait]
+impl Plugin for PointerPlugin {
+    fn get_name(&self) -> String {
+        "Pointer".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Estimates the position of the mouse pointer".to_string()
+    }
+
+    fn initialize(&mut self) -> plugins::Result<()> {
+        events::register_observer(|event: &events::Event| {
+            if let events::Event::MouseMove(direction, value) = event {
+                // relative motion is integrated even if a compositor sensor is available,
+                // since the sensor is polled at a much lower rate than the mouse reports
+                POINTER_STATE.lock().move_relative(*direction, *value);
+            }
+
+            Ok(true) // event has been processed
+        });
+
+        Ok(())
+    }
+
+    fn register_lua_funcs(&self, lua_ctx: &Lua) -> mlua::Result<()> {
+        let globals = lua_ctx.globals();
+
+        let get_pointer_position =
+            lua_ctx.create_function(|_, ()| Ok(PointerPlugin::get_pointer_position()))?;
+        globals.set("get_pointer_position", get_pointer_position)?;
+
+        let get_pointer_canvas_position =
+            lua_ctx.create_function(|_, ()| Ok(PointerPlugin::get_pointer_canvas_position()))?;
+        globals.set("get_pointer_canvas_position", get_pointer_canvas_position)?;
+
+        let is_pointer_position_absolute =
+            lua_ctx.create_function(|_, ()| Ok(PointerPlugin::is_pointer_position_absolute()))?;
+        globals.set("is_pointer_position_absolute", is_pointer_position_absolute)?;
+
+        Ok(())
+    }
+
+    async fn main_loop_hook(&self, _ticks: u64) {}
+
+    fn sync_main_loop_hook(&self, _ticks: u64) {
+        POINTER_STATE.lock().decay();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
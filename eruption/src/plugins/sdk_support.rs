@@ -20,35 +20,40 @@
 */
 
 use crate::{
-    constants, hwdevices, init_keyboard_device, init_misc_device, init_mouse_device, script,
-    spawn_keyboard_input_thread, spawn_misc_input_thread, spawn_mouse_input_thread, DbusApiEvent,
-    SwitchProfileResult, SDK_SUPPORT_ACTIVE,
+    constants, hotplug, hwdevices, init_keyboard_device, init_misc_device, init_mouse_device,
+    script, spawn_keyboard_input_thread, spawn_misc_input_thread, spawn_mouse_input_thread,
+    DbusApiEvent, SwitchProfileResult, SDK_SUPPORT_ACTIVE,
 };
 use flume::unbounded;
 use lazy_static::lazy_static;
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use mlua::prelude::*;
 use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
 use nix::unistd::unlink;
 use parking_lot::{Mutex, RwLock};
 use prost::Message;
 use socket2::{Domain, SockAddr, Socket, Type};
 use std::any::Any;
+use std::collections::{HashMap, VecDeque};
 use std::io::Cursor;
 use std::mem::MaybeUninit;
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{fs, thread};
 
 use crate::{
+    device_id, events,
     hwdevices::RGBA,
+    key_geometry,
     plugins::{self, Plugin},
     scripting::parameters,
     scripting::parameters_util,
+    sdk_clients, state, zones,
 };
 
 pub mod protocol {
@@ -71,12 +76,271 @@ lazy_static! {
         b: 0x00,
         a: 0x00,
     }; constants::CANVAS_SIZE]));
+
+    /// A temporary overlay, blended on top of the canvas
+    pub static ref OVERLAY: Arc<RwLock<Option<Overlay>>> = Arc::new(RwLock::new(None));
+
+    /// Indices of the devices that show the canvas, or `None` for all devices
+    pub static ref TARGET_DEVICES: Arc<RwLock<Option<Vec<u64>>>> = Arc::new(RwLock::new(None));
+
+    /// Indices of the slots in which the canvas is shown, or `None` for all slots
+    pub static ref TARGET_SLOTS: Arc<RwLock<Option<Vec<u32>>>> = Arc::new(RwLock::new(None));
+
+    /// Snapshots that have been captured by SDK clients, oldest first
+    static ref SNAPSHOTS: Arc<Mutex<VecDeque<(u32, CanvasSnapshot)>>> =
+        Arc::new(Mutex::new(VecDeque::new()));
+
+    /// Identifier of the next canvas snapshot
+    static ref NEXT_SNAPSHOT_ID: AtomicU32 = AtomicU32::new(1);
+
+    /// Named parameters that have been published by the connected SDK client
+    static ref NAMED_PARAMETERS: Arc<RwLock<HashMap<String, NamedParameter>>> =
+        Arc::new(RwLock::new(HashMap::new()));
 }
 
+/// Capabilities of the wire protocol that are supported by the SDK support plugin
+const SUPPORTED_CAPABILITIES: u32 = protocol::Capability::CompressionLz4 as u32
+    | protocol::Capability::PartialUpdates as u32
+    | protocol::Capability::DeviceTargeting as u32
+    | protocol::Capability::CanvasSnapshots as u32
+    | protocol::Capability::SlotTargeting as u32
+    | protocol::Capability::EventInjection as u32;
+
 lazy_static! {
     pub static ref LISTENER: Arc<Mutex<Option<Socket>>> = Arc::new(Mutex::new(None));
 }
 
+/// A LED map that has been submitted by e.g. `eruptionctl canvas`, it
+/// is shown until it expires or is replaced by another overlay
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    pub led_map: Vec<RGBA>,
+
+    /// Point in time when the overlay will be removed, or `None` to keep it indefinitely
+    pub expires_at: Option<Instant>,
+}
+
+impl Overlay {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|t| Instant::now() >= t)
+    }
+}
+
+/// The state of the SDK support plugin at the time a snapshot has been captured. The
+/// profile keeps running, so restoring this state returns to exactly what has been
+/// shown before, even if the scripts of the active profile are animated
+#[derive(Debug, Clone)]
+struct CanvasSnapshot {
+    led_map: Vec<RGBA>,
+    sdk_support_active: bool,
+    target_devices: Option<Vec<u64>>,
+    target_slots: Option<Vec<u32>>,
+    overlay: Option<Overlay>,
+}
+
+/// A value that has been published by an SDK client, e.g. the health of the player
+/// of a game, scripts of the active profile may read it via `get_sdk_parameter()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NamedParameter {
+    pub min: f64,
+    pub max: f64,
+    pub value: f64,
+}
+
+/// Register the named parameter `name`, or replace it if it already exists. Returns
+/// `false` if the parameter is invalid, or if too many parameters have been registered
+fn register_named_parameter(name: &str, min: f64, max: f64, value: f64) -> bool {
+    if name.is_empty() || !min.is_finite() || !max.is_finite() || min > max || value.is_nan() {
+        return false;
+    }
+
+    let mut parameters = NAMED_PARAMETERS.write();
+
+    if !parameters.contains_key(name) && parameters.len() >= constants::MAX_SDK_NAMED_PARAMETERS {
+        return false;
+    }
+
+    parameters.insert(
+        name.to_owned(),
+        NamedParameter {
+            min,
+            max,
+            value: value.clamp(min, max),
+        },
+    );
+
+    true
+}
+
+/// Update the value of the named parameter `name`. Returns `false` if the parameter
+/// has not been registered
+fn set_named_parameter(name: &str, value: f64) -> bool {
+    if value.is_nan() {
+        return false;
+    }
+
+    match NAMED_PARAMETERS.write().get_mut(name) {
+        Some(parameter) => {
+            parameter.value = value.clamp(parameter.min, parameter.max);
+
+            true
+        }
+
+        None => false,
+    }
+}
+
+/// Returns the named parameter `name`, if it has been registered by an SDK client
+pub fn get_named_parameter(name: &str) -> Option<NamedParameter> {
+    NAMED_PARAMETERS.read().get(name).copied()
+}
+
+/// Capture the current state of the SDK support plugin. Returns the identifier of
+/// the snapshot, and the canvas as it is currently shown on the devices
+fn snapshot_canvas() -> (u32, Vec<RGBA>) {
+    let snapshot = CanvasSnapshot {
+        led_map: LED_MAP.read().clone(),
+        sdk_support_active: SDK_SUPPORT_ACTIVE.load(Ordering::SeqCst),
+        target_devices: TARGET_DEVICES.read().clone(),
+        target_slots: TARGET_SLOTS.read().clone(),
+        overlay: OVERLAY.read().clone(),
+    };
+
+    let id = NEXT_SNAPSHOT_ID.fetch_add(1, Ordering::SeqCst);
+
+    let mut snapshots = SNAPSHOTS.lock();
+    if snapshots.len() >= constants::MAX_CANVAS_SNAPSHOTS {
+        snapshots.pop_front();
+    }
+
+    snapshots.push_back((id, snapshot));
+
+    (id, script::LAST_RENDERED_LED_MAP.read().clone())
+}
+
+/// Restore the state that has been captured by `snapshot_canvas()`, the snapshot is
+/// discarded afterwards. Returns `false` if there is no snapshot with the identifier `id`
+fn restore_canvas(id: u32) -> bool {
+    let snapshot = {
+        let mut snapshots = SNAPSHOTS.lock();
+
+        snapshots
+            .iter()
+            .position(|(i, _)| *i == id)
+            .and_then(|index| snapshots.remove(index))
+    };
+
+    match snapshot {
+        Some((_id, snapshot)) => {
+            LED_MAP.write().copy_from_slice(&snapshot.led_map);
+            *TARGET_DEVICES.write() = snapshot.target_devices;
+            *TARGET_SLOTS.write() = snapshot.target_slots;
+            *OVERLAY.write() = snapshot.overlay.filter(|overlay| !overlay.is_expired());
+
+            SDK_SUPPORT_ACTIVE.store(snapshot.sdk_support_active, Ordering::SeqCst);
+
+            script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+            true
+        }
+
+        None => false,
+    }
+}
+
+/// Returns `true` if `request` may only be issued by authenticated clients
+fn requires_authentication(request: &protocol::Request) -> bool {
+    use protocol::request::RequestMessage;
+
+    !matches!(
+        request.request_message,
+        Some(RequestMessage::Noop(_))
+            | Some(RequestMessage::Status(_))
+            | Some(RequestMessage::Hello(_))
+    )
+}
+
+/// Returns `true` if the canvas shall be shown on the device `index`
+pub fn is_target_device(index: u64) -> bool {
+    match TARGET_DEVICES.read().as_ref() {
+        Some(devices) => devices.contains(&index),
+        None => true,
+    }
+}
+
+/// Returns `true` if the canvas of the SDK client shall currently be shown. While a
+/// slot is active that has not been targeted by the client, the canvas is suppressed
+pub fn is_canvas_visible() -> bool {
+    if !SDK_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
+        return false;
+    }
+
+    match TARGET_SLOTS.read().as_ref() {
+        Some(slots) => {
+            let active_slot = crate::ACTIVE_SLOT.load(Ordering::SeqCst) as u32;
+
+            slots.contains(&active_slot)
+        }
+
+        None => true,
+    }
+}
+
+/// Decode the payload of a canvas, decompressing it if required
+fn decode_canvas(payload: Vec<u8>, compression: i32) -> Result<Vec<u8>> {
+    match protocol::Compression::try_from(compression) {
+        Ok(protocol::Compression::None) => Ok(payload),
+
+        Ok(protocol::Compression::Lz4) => {
+            // the uncompressed size is prepended to the payload, refuse to
+            // allocate more than the size of a canvas
+            let size = payload
+                .get(..4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                .unwrap_or(0);
+
+            if size > constants::CANVAS_SIZE * 4 {
+                return Err(SdkPluginError::PluginError {
+                    description: format!("Invalid size of the canvas: {}", size),
+                }
+                .into());
+            }
+
+            Ok(lz4_flex::decompress_size_prepended(&payload)?)
+        }
+
+        Err(_e) => Err(SdkPluginError::PluginError {
+            description: format!("Unsupported compression: {}", compression),
+        }
+        .into()),
+    }
+}
+
+/// Decode a synthetic input event, injected by an SDK client
+fn decode_injected_event(event_type: i32, index: u32) -> Result<events::InjectedEvent> {
+    let index = u8::try_from(index).map_err(|_e| SdkPluginError::PluginError {
+        description: format!("Invalid key or button index: {}", index),
+    })?;
+
+    match protocol::InputEventType::try_from(event_type) {
+        Ok(protocol::InputEventType::KeyDown) => Ok(events::InjectedEvent::KeyDown(index)),
+        Ok(protocol::InputEventType::KeyUp) => Ok(events::InjectedEvent::KeyUp(index)),
+
+        Ok(protocol::InputEventType::MouseButtonDown) => {
+            Ok(events::InjectedEvent::MouseButtonDown(index))
+        }
+
+        Ok(protocol::InputEventType::MouseButtonUp) => {
+            Ok(events::InjectedEvent::MouseButtonUp(index))
+        }
+
+        Err(_e) => Err(SdkPluginError::PluginError {
+            description: format!("Unsupported input event type: {}", event_type),
+        }
+        .into()),
+    }
+}
+
 use bincode::{Decode, Encode};
 
 #[derive(Debug, Default, Clone, Encode, Decode)]
@@ -96,8 +360,8 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
             // initialize keyboard devices
             for (index, device) in devices.0.iter().enumerate() {
                 if !crate::KEYBOARD_DEVICES.read().iter().any(|d| {
-                    d.read().get_usb_vid() == device.read().get_usb_vid()
-                        && d.read().get_usb_pid() == device.read().get_usb_pid()
+                    device_id::device_id(d.read().as_device())
+                        == device_id::device_id(device.read().as_device())
                 }) {
                     info!("Initializing the hotplugged keyboard device...");
 
@@ -129,6 +393,17 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
                     crate::KEYBOARD_DEVICES_RX.write().push(kbd_rx);
                     crate::KEYBOARD_DEVICES.write().push(device.clone());
 
+                    hotplug::notify_device_attached("keyboard", device.read().as_device());
+
+                    // restore the saved brightness of the device
+                    let brightness = state::get_saved_device_brightness(device.read().as_device());
+                    device
+                        .write()
+                        .set_local_brightness(brightness)
+                        .unwrap_or_else(|e| {
+                            error!("Could not restore the device brightness: {}", e)
+                        });
+
                     debug!("Sending device hotplug notification...");
 
                     let dbus_api_tx = crate::DBUS_API_TX.lock();
@@ -153,8 +428,8 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
                 // enable mouse input
                 if enable_mouse {
                     if !crate::MOUSE_DEVICES.read().iter().any(|d| {
-                        d.read().get_usb_vid() == device.read().get_usb_vid()
-                            && d.read().get_usb_pid() == device.read().get_usb_pid()
+                        device_id::device_id(d.read().as_device())
+                            == device_id::device_id(device.read().as_device())
                     }) {
                         info!("Initializing the hotplugged mouse device...");
 
@@ -206,6 +481,18 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
                         crate::MOUSE_DEVICES_RX.write().push(mouse_rx);
                         crate::MOUSE_DEVICES.write().push(device.clone());
 
+                        hotplug::notify_device_attached("mouse", device.read().as_device());
+
+                        // restore the saved brightness of the device
+                        let brightness =
+                            state::get_saved_device_brightness(device.read().as_device());
+                        device
+                            .write()
+                            .set_local_brightness(brightness)
+                            .unwrap_or_else(|e| {
+                                error!("Could not restore the device brightness: {}", e)
+                            });
+
                         debug!("Sending device hotplug notification...");
 
                         let dbus_api_tx = crate::DBUS_API_TX.lock();
@@ -225,8 +512,8 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
             // initialize misc devices
             for (index, device) in devices.2.iter().enumerate() {
                 if !crate::MISC_DEVICES.read().iter().any(|d| {
-                    d.read().get_usb_vid() == device.read().get_usb_vid()
-                        && d.read().get_usb_pid() == device.read().get_usb_pid()
+                    device_id::device_id(d.read().as_device())
+                        == device_id::device_id(device.read().as_device())
                 }) {
                     info!("Initializing the hotplugged misc device...");
 
@@ -286,6 +573,25 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
                     }
 
                     crate::MISC_DEVICES.write().push(device.clone());
+
+                    {
+                        let device = device.read();
+                        hotplug::notify_device_attached(
+                            hotplug::misc_device_class(device.as_device()),
+                            device.as_device(),
+                        );
+                    }
+
+                    // restore the saved brightness of the device
+
+                    let brightness = state::get_saved_device_brightness(device.read().as_device());
+
+                    device
+                        .write()
+                        .set_local_brightness(brightness)
+                        .unwrap_or_else(|e| {
+                            error!("Could not restore the device brightness: {}", e)
+                        });
                 }
             }
 
@@ -360,18 +666,44 @@ impl SdkSupportPlugin {
 
                 match listener.accept() {
                     Ok((socket, _sockaddr)) => {
-                        debug!("Eruption SDK client connected");
+                        // identify the client by the credentials of its socket
+                        let client = match getsockopt(socket.as_raw_fd(), PeerCredentials)
+                            .map_err(eyre::Error::from)
+                            .and_then(|cred| sdk_clients::register_client(cred.pid(), cred.uid()))
+                        {
+                            Ok(client) => client,
+
+                            Err(e) => {
+                                warn!("Refused Eruption SDK client: {}", e);
+
+                                continue 'IO_LOOP;
+                            }
+                        };
+
+                        debug!("Eruption SDK client connected (id: {})", client.id);
 
                         // socket.set_nodelay(true)?; // not supported on AF_UNIX on Linux
                         socket.set_send_buffer_size(constants::NET_BUFFER_CAPACITY * 2)?;
                         socket.set_recv_buffer_size(constants::NET_BUFFER_CAPACITY * 2)?;
 
+                        // clients that do not perform the handshake are legacy clients
+                        let mut capabilities = protocol::Capability::None as u32;
+                        *TARGET_DEVICES.write() = None;
+                        *TARGET_SLOTS.write() = None;
+                        NAMED_PARAMETERS.write().clear();
+
                         // connection successful, enter event loop now
                         'EVENT_LOOP: loop {
                             if crate::QUIT.load(Ordering::SeqCst) {
                                 break 'EVENT_LOOP;
                             }
 
+                            if client.is_kicked() {
+                                info!("Eruption SDK client {} has been disconnected", client.id);
+
+                                break 'EVENT_LOOP;
+                            }
+
                             // wait for socket to be ready
                             let mut poll_fds = [PollFd::new(
                                 socket.as_raw_fd(),
@@ -435,6 +767,19 @@ impl SdkSupportPlugin {
                                             }
                                         };
 
+                                        // clients have to present the access token during the
+                                        // handshake before they may issue any other requests
+                                        if !client.is_authenticated()
+                                            && requires_authentication(&request)
+                                        {
+                                            warn!(
+                                                "Eruption SDK client {} did not authenticate, disconnecting",
+                                                client.id
+                                            );
+
+                                            break 'EVENT_LOOP;
+                                        }
+
                                         match request.request_message {
                                             Some(protocol::request::RequestMessage::Noop(
                                                 _message,
@@ -444,6 +789,59 @@ impl SdkSupportPlugin {
                                                 trace!("NOOP");
                                             }
 
+                                            Some(protocol::request::RequestMessage::Hello(
+                                                message,
+                                            )) => {
+                                                trace!("Hello");
+
+                                                if let Err(e) = client
+                                                    .hello(message.protocol_version, &message.token)
+                                                {
+                                                    warn!(
+                                                        "Eruption SDK client {}: {}",
+                                                        client.id, e
+                                                    );
+
+                                                    break 'EVENT_LOOP;
+                                                }
+
+                                                capabilities =
+                                                    message.capabilities & SUPPORTED_CAPABILITIES;
+
+                                                debug!(
+                                                    "Eruption SDK client protocol version: {}, capabilities: {:#x}",
+                                                    message.protocol_version, capabilities
+                                                );
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::Hello(
+                                                            protocol::HelloResponse {
+                                                                protocol_version:
+                                                                    protocol::ProtocolVersion::Current
+                                                                        as u32,
+                                                                capabilities,
+                                                            },
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
                                             Some(protocol::request::RequestMessage::Status(
                                                 _message,
                                             )) => {
@@ -454,6 +852,9 @@ impl SdkSupportPlugin {
                                                         protocol::response::ResponseMessage::Status(
                                                             protocol::StatusResponse {
                                                                 description: "Eruption".to_string(),
+                                                                protocol_version:
+                                                                    protocol::ProtocolVersion::Current
+                                                                        as u32,
                                                             },
                                                         ),
                                                     ),
@@ -520,6 +921,64 @@ impl SdkSupportPlugin {
                                                 }
                                             }
 
+                                            Some(
+                                                protocol::request::RequestMessage::ActiveSlot(
+                                                    _message,
+                                                ),
+                                            ) => {
+                                                trace!("Get Active Slot");
+
+                                                let slot =
+                                                    crate::ACTIVE_SLOT.load(Ordering::SeqCst);
+
+                                                let slot_name = crate::SLOT_NAMES
+                                                    .lock()
+                                                    .get(slot)
+                                                    .cloned()
+                                                    .unwrap_or_else(|| {
+                                                        format!("Profile Slot {}", slot + 1)
+                                                    });
+
+                                                let profile_file = {
+                                                    let active_profile =
+                                                        &*crate::ACTIVE_PROFILE.lock();
+                                                    match active_profile {
+                                                        Some(active_profile) => active_profile
+                                                            .profile_file
+                                                            .to_string_lossy()
+                                                            .to_string(),
+                                                        None => "Unknown".to_string(),
+                                                    }
+                                                };
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::ActiveSlot(
+                                                            protocol::ActiveSlotResponse {
+                                                                slot: slot as u32,
+                                                                slot_name,
+                                                                profile_file,
+                                                            },
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
                                             Some(
                                                 protocol::request::RequestMessage::SwitchProfile(
                                                     message,
@@ -609,43 +1068,86 @@ impl SdkSupportPlugin {
                                             )) => {
                                                 trace!("Set canvas");
 
-                                                let payload_map = message.canvas;
-
-                                                let mut led_map = [RGBA {
-                                                    r: 0,
-                                                    g: 0,
-                                                    b: 0,
-                                                    a: 0,
+                                                // partial updates and device targeting require
+                                                // the respective capabilities
+                                                let partial = message.partial
+                                                    && capabilities
+                                                        & protocol::Capability::PartialUpdates
+                                                            as u32
+                                                        != 0;
+
+                                                let offset = if partial {
+                                                    message.offset as usize
+                                                } else {
+                                                    0
                                                 };
-                                                    constants::CANVAS_SIZE];
-
-                                                let mut i = 0;
-                                                let mut cntr = 0;
-
-                                                loop {
-                                                    led_map[cntr] = RGBA {
-                                                        r: payload_map[i],
-                                                        g: payload_map[i + 1],
-                                                        b: payload_map[i + 2],
-                                                        a: payload_map[i + 3],
-                                                    };
-
-                                                    i += 4;
-                                                    cntr += 1;
-
-                                                    if cntr >= led_map.len()
-                                                        || i >= payload_map.len()
-                                                    {
-                                                        break;
-                                                    }
-                                                }
 
-                                                LED_MAP.write().copy_from_slice(&led_map);
+                                                match decode_canvas(
+                                                    message.canvas,
+                                                    message.compression,
+                                                ) {
+                                                    Ok(payload_map) => {
+                                                        let mut led_map = LED_MAP.write();
+
+                                                        if !partial {
+                                                            led_map.fill(RGBA {
+                                                                r: 0,
+                                                                g: 0,
+                                                                b: 0,
+                                                                a: 0,
+                                                            });
+                                                        }
 
-                                                SDK_SUPPORT_ACTIVE.store(true, Ordering::SeqCst);
+                                                        for (color, chunk) in led_map
+                                                            .iter_mut()
+                                                            .skip(offset)
+                                                            .zip(payload_map.chunks_exact(4))
+                                                        {
+                                                            *color = RGBA {
+                                                                r: chunk[0],
+                                                                g: chunk[1],
+                                                                b: chunk[2],
+                                                                a: chunk[3],
+                                                            };
+                                                        }
 
-                                                script::FRAME_GENERATION_COUNTER
-                                                    .fetch_add(1, Ordering::SeqCst);
+                                                        if capabilities
+                                                            & protocol::Capability::DeviceTargeting
+                                                                as u32
+                                                            != 0
+                                                        {
+                                                            *TARGET_DEVICES.write() =
+                                                                if message.devices.is_empty() {
+                                                                    None
+                                                                } else {
+                                                                    Some(message.devices)
+                                                                };
+                                                        }
+
+                                                        if capabilities
+                                                            & protocol::Capability::SlotTargeting
+                                                                as u32
+                                                            != 0
+                                                        {
+                                                            *TARGET_SLOTS.write() =
+                                                                if message.slots.is_empty() {
+                                                                    None
+                                                                } else {
+                                                                    Some(message.slots)
+                                                                };
+                                                        }
+
+                                                        SDK_SUPPORT_ACTIVE
+                                                            .store(true, Ordering::SeqCst);
+
+                                                        script::FRAME_GENERATION_COUNTER
+                                                            .fetch_add(1, Ordering::SeqCst);
+                                                    }
+
+                                                    Err(e) => {
+                                                        error!("Could not decode the canvas: {}", e)
+                                                    }
+                                                }
 
                                                 let response = protocol::Response {
                                                     response_message: Some(
@@ -721,6 +1223,428 @@ impl SdkSupportPlugin {
                                                 }
                                             }
 
+                                            Some(protocol::request::RequestMessage::GetZones(
+                                                _message,
+                                            )) => {
+                                                trace!("Get zones");
+
+                                                let zones = zones::get_zones()
+                                                    .into_iter()
+                                                    .map(|zone| protocol::Zone {
+                                                        name: zone.name,
+                                                        keys: zone
+                                                            .keys
+                                                            .iter()
+                                                            .map(|k| *k as u32)
+                                                            .collect(),
+                                                    })
+                                                    .collect();
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::GetZones(
+                                                            protocol::GetZonesResponse { zones },
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
+                                            Some(
+                                                protocol::request::RequestMessage::GetKeyPositions(
+                                                    _message,
+                                                ),
+                                            ) => {
+                                                trace!("Get key positions");
+
+                                                let positions = key_geometry::get_key_positions()
+                                                    .into_iter()
+                                                    .map(|p| protocol::KeyPosition {
+                                                        key: p.key as u32,
+                                                        x: p.x,
+                                                        y: p.y,
+                                                    })
+                                                    .collect();
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::GetKeyPositions(
+                                                            protocol::GetKeyPositionsResponse { positions },
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
+                                            Some(
+                                                protocol::request::RequestMessage::SetOverlay(
+                                                    message,
+                                                ),
+                                            ) => {
+                                                trace!("Set overlay");
+
+                                                match decode_canvas(
+                                                    message.canvas,
+                                                    message.compression,
+                                                ) {
+                                                    Ok(payload_map) => {
+                                                        let mut led_map = vec![
+                                                            RGBA {
+                                                                r: 0,
+                                                                g: 0,
+                                                                b: 0,
+                                                                a: 0,
+                                                            };
+                                                            constants::CANVAS_SIZE
+                                                        ];
+
+                                                        for (color, chunk) in led_map
+                                                            .iter_mut()
+                                                            .zip(payload_map.chunks_exact(4))
+                                                        {
+                                                            *color = RGBA {
+                                                                r: chunk[0],
+                                                                g: chunk[1],
+                                                                b: chunk[2],
+                                                                a: chunk[3],
+                                                            };
+                                                        }
+
+                                                        // a fully transparent overlay removes the current overlay
+                                                        *OVERLAY.write() =
+                                                            if led_map.iter().any(|c| c.a > 0) {
+                                                                let expires_at =
+                                                                    if message.timeout_millis > 0 {
+                                                                        Some(
+                                                                    Instant::now()
+                                                                        + Duration::from_millis(
+                                                                            message.timeout_millis,
+                                                                        ),
+                                                                )
+                                                                    } else {
+                                                                        None
+                                                                    };
+
+                                                                Some(Overlay {
+                                                                    led_map,
+                                                                    expires_at,
+                                                                })
+                                                            } else {
+                                                                None
+                                                            };
+
+                                                        script::FRAME_GENERATION_COUNTER
+                                                            .fetch_add(1, Ordering::SeqCst);
+                                                    }
+
+                                                    Err(e) => {
+                                                        error!(
+                                                            "Could not decode the overlay: {}",
+                                                            e
+                                                        )
+                                                    }
+                                                }
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::SetOverlay(
+                                                            protocol::SetOverlayResponse {},
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
+                                            Some(
+                                                protocol::request::RequestMessage::SnapshotCanvas(
+                                                    _message,
+                                                ),
+                                            ) => {
+                                                trace!("Snapshot canvas");
+
+                                                let (snapshot_id, led_map) = snapshot_canvas();
+
+                                                debug!("Captured canvas snapshot {}", snapshot_id);
+
+                                                let canvas = led_map
+                                                    .iter()
+                                                    .flat_map(|c| [c.r, c.g, c.b, c.a])
+                                                    .collect();
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::SnapshotCanvas(
+                                                            protocol::SnapshotCanvasResponse {
+                                                                snapshot_id,
+                                                                canvas,
+                                                            },
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
+                                            Some(
+                                                protocol::request::RequestMessage::RestoreCanvas(
+                                                    message,
+                                                ),
+                                            ) => {
+                                                trace!("Restore canvas");
+
+                                                let restored = restore_canvas(message.snapshot_id);
+
+                                                if restored {
+                                                    debug!(
+                                                        "Restored canvas snapshot {}",
+                                                        message.snapshot_id
+                                                    );
+                                                } else {
+                                                    warn!(
+                                                        "Unknown canvas snapshot: {}",
+                                                        message.snapshot_id
+                                                    );
+                                                }
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::RestoreCanvas(
+                                                            protocol::RestoreCanvasResponse {
+                                                                restored,
+                                                            },
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
+                                            Some(
+                                                protocol::request::RequestMessage::InjectEvent(
+                                                    message,
+                                                ),
+                                            ) => {
+                                                trace!("Inject event");
+
+                                                // injecting events is a privileged operation
+                                                let injected = if capabilities
+                                                    & protocol::Capability::EventInjection as u32
+                                                    == 0
+                                                    || !client.may_inject_events()
+                                                {
+                                                    warn!(
+                                                        "Eruption SDK client {} is not allowed to inject input events",
+                                                        client.id
+                                                    );
+
+                                                    false
+                                                } else {
+                                                    match decode_injected_event(
+                                                        message.event_type,
+                                                        message.index,
+                                                    )
+                                                    .and_then(|event| {
+                                                        events::inject_event(event)?;
+
+                                                        Ok(event)
+                                                    }) {
+                                                        Ok(event) => {
+                                                            debug!(
+                                                                "Eruption SDK client {} injected an event: {:?}",
+                                                                client.id, event
+                                                            );
+
+                                                            true
+                                                        }
+
+                                                        Err(e) => {
+                                                            warn!(
+                                                                "Could not inject an input event: {}",
+                                                                e
+                                                            );
+
+                                                            false
+                                                        }
+                                                    }
+                                                };
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::InjectEvent(
+                                                            protocol::InjectEventResponse {
+                                                                injected,
+                                                            },
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
+                                            Some(
+                                                protocol::request::RequestMessage::RegisterNamedParameter(
+                                                    message,
+                                                ),
+                                            ) => {
+                                                trace!("Register named parameter");
+
+                                                let registered = register_named_parameter(
+                                                    &message.name,
+                                                    message.min,
+                                                    message.max,
+                                                    message.value,
+                                                );
+
+                                                if !registered {
+                                                    warn!(
+                                                        "Eruption SDK client {} could not register the named parameter: {}",
+                                                        client.id, message.name
+                                                    );
+                                                }
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::RegisterNamedParameter(
+                                                            protocol::RegisterNamedParameterResponse {
+                                                                registered,
+                                                            },
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
+                                            Some(
+                                                protocol::request::RequestMessage::SetNamedParameter(
+                                                    message,
+                                                ),
+                                            ) => {
+                                                trace!("Set named parameter");
+
+                                                let accepted =
+                                                    set_named_parameter(&message.name, message.value);
+
+                                                let response = protocol::Response {
+                                                    response_message: Some(
+                                                        protocol::response::ResponseMessage::SetNamedParameter(
+                                                            protocol::SetNamedParameterResponse {
+                                                                accepted,
+                                                            },
+                                                        ),
+                                                    ),
+                                                };
+
+                                                let mut buf = Vec::new();
+                                                response.encode_length_delimited(&mut buf)?;
+
+                                                // send data
+                                                match socket.send(&buf) {
+                                                    Ok(_n) => {}
+
+                                                    Err(_e) => {
+                                                        return Err(SdkPluginError::PluginError {
+                                                            description: "Lost connection to Eruption SDK client".to_owned(),
+                                                        }
+                                                            .into());
+                                                    }
+                                                }
+                                            }
+
                                             None => {
                                                 // not sure how this can happen
                                                 error!(
@@ -743,6 +1667,9 @@ impl SdkSupportPlugin {
                                 thread::sleep(Duration::from_millis(15));
                             }
                         }
+
+                        // the parameters of the client vanish along with it
+                        NAMED_PARAMETERS.write().clear();
                     }
 
                     Err(_e) => {
@@ -789,18 +1716,36 @@ impl Plugin for SdkSupportPlugin {
     }
 
     fn register_lua_funcs(&self, lua_ctx: &Lua) -> mlua::Result<()> {
-        let _globals = lua_ctx.globals();
+        let globals = lua_ctx.globals();
 
         // let get_current_slot =
         //     lua_ctx.create_function(move |_, ()| Ok(SdkSupportPlugin::get_current_slot()))?;
         // globals.set("get_current_slot", get_current_slot)?;
 
+        let get_sdk_parameter = lua_ctx.create_function(|_, name: String| {
+            Ok(match get_named_parameter(&name) {
+                Some(p) => (Some(p.value), Some(p.min), Some(p.max)),
+                None => (None, None, None),
+            })
+        })?;
+        globals.set("get_sdk_parameter", get_sdk_parameter)?;
+
         Ok(())
     }
 
     async fn main_loop_hook(&self, _ticks: u64) {}
 
-    fn sync_main_loop_hook(&self, _ticks: u64) {}
+    fn sync_main_loop_hook(&self, _ticks: u64) {
+        let expired = OVERLAY.read().as_ref().is_some_and(|o| o.is_expired());
+
+        if expired {
+            debug!("The overlay expired");
+
+            *OVERLAY.write() = None;
+
+            script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+        }
+    }
 
     fn as_any(&self) -> &dyn Any {
         self
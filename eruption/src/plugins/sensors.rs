@@ -22,14 +22,20 @@
 use lazy_static::lazy_static;
 // use log::*;
 use mlua::prelude::*;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use std::any::Any;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use sysinfo::{ComponentExt, RefreshKind, SystemExt};
 
+use crate::constants;
 use crate::plugins;
 use crate::plugins::Plugin;
+use crate::util::ratelimited;
 
 // pub type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -45,6 +51,106 @@ lazy_static! {
 
     /// System state and sensor information
     static ref SYSTEM: Arc<Mutex<sysinfo::System>> = Arc::new(Mutex::new(sysinfo::System::new_with_specifics(RefreshKind::default().with_components().with_memory())));
+
+    /// Cached values of the hwmon sensors
+    static ref HWMON_SENSORS: Arc<Mutex<HwmonSensors>> = Arc::new(Mutex::new(HwmonSensors::default()));
+
+    /// The hwmon sensors that may be queried by Lua scripts, `None` means all sensors are allowed
+    static ref ALLOWED_SENSORS: Arc<RwLock<Option<HashSet<String>>>> = Arc::new(RwLock::new(None));
+}
+
+#[derive(Debug, Default)]
+struct HwmonSensors {
+    /// Sensor values, indexed by "<chip name>/<sensor label>"
+    values: BTreeMap<String, f64>,
+
+    last_refresh: Option<Instant>,
+}
+
+impl HwmonSensors {
+    /// Re-read all hwmon sensors, but not more often than every HWMON_REFRESH_INTERVAL_MILLIS
+    fn refresh_if_stale(&mut self) {
+        let is_stale = match self.last_refresh {
+            Some(last_refresh) => {
+                last_refresh.elapsed()
+                    >= Duration::from_millis(constants::HWMON_REFRESH_INTERVAL_MILLIS)
+            }
+
+            None => true,
+        };
+
+        if is_stale {
+            self.values = read_hwmon_sensors();
+            self.last_refresh = Some(Instant::now());
+        }
+    }
+}
+
+/// Read the current values of all hwmon sensors. Temperatures are converted to
+/// degrees Celsius, voltages to Volts and power to Watts, fan speeds are in RPM
+fn read_hwmon_sensors() -> BTreeMap<String, f64> {
+    let mut result = BTreeMap::new();
+
+    let entries = match fs::read_dir(constants::HWMON_SYSFS_PATH) {
+        Ok(entries) => entries,
+        Err(e) => {
+            ratelimited::warn!("Could not enumerate hwmon devices: {}", e);
+
+            return result;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        let chip_name = match fs::read_to_string(path.join("name")) {
+            Ok(name) => name.trim().to_string(),
+            Err(_) => continue,
+        };
+
+        let files = match fs::read_dir(&path) {
+            Ok(files) => files,
+            Err(_) => continue,
+        };
+
+        for file in files.flatten() {
+            let file_name = file.file_name().to_string_lossy().to_string();
+
+            let prefix = match file_name.strip_suffix("_input") {
+                Some(prefix) => prefix,
+                None => continue,
+            };
+
+            let scale = if prefix.starts_with("temp") || prefix.starts_with("in") {
+                1000.0
+            } else if prefix.starts_with("power") {
+                1_000_000.0
+            } else if prefix.starts_with("fan") {
+                1.0
+            } else {
+                continue;
+            };
+
+            let value = match read_sysfs_value(&file.path()) {
+                Some(value) => value / scale,
+                None => continue,
+            };
+
+            let label = fs::read_to_string(path.join(format!("{prefix}_label")))
+                .map(|label| label.trim().to_string())
+                .unwrap_or_else(|_| prefix.to_string());
+
+            result
+                .entry(format!("{chip_name}/{label}"))
+                .or_insert(value);
+        }
+    }
+
+    result
+}
+
+fn read_sysfs_value(path: &Path) -> Option<f64> {
+    fs::read_to_string(path).ok()?.trim().parse::<f64>().ok()
 }
 
 /// A plugin that gives Lua scripts access to the systems sensor data
@@ -105,6 +211,39 @@ impl SensorsPlugin {
         }
     }
 
+    /// Get the value of the hwmon sensor `name`, e.g. "k10temp/Tctl" or "amdgpu/fan1".
+    /// Returns `None` if the sensor does not exist, or if it is not allowed to be queried
+    pub fn get_sensor(name: &str) -> Option<f64> {
+        if let Some(allowed_sensors) = ALLOWED_SENSORS.read().as_ref() {
+            if !allowed_sensors.contains(name) {
+                return None;
+            }
+        }
+
+        let mut sensors = HWMON_SENSORS.lock();
+        sensors.refresh_if_stale();
+
+        sensors.values.get(name).copied()
+    }
+
+    /// Get the names of all available hwmon sensors that may be queried
+    pub fn get_sensor_names() -> Vec<String> {
+        let mut sensors = HWMON_SENSORS.lock();
+        sensors.refresh_if_stale();
+
+        let allowed_sensors = ALLOWED_SENSORS.read();
+
+        sensors
+            .values
+            .keys()
+            .filter(|name| match allowed_sensors.as_ref() {
+                Some(allowed_sensors) => allowed_sensors.contains(*name),
+                None => true,
+            })
+            .cloned()
+            .collect()
+    }
+
     /// Get the total installed memory size
     pub fn get_mem_total_kb() -> u64 {
         DO_REFRESH.store(true, Ordering::SeqCst);
@@ -149,6 +288,16 @@ impl Plugin for SensorsPlugin {
     }
 
     fn initialize(&mut self) -> plugins::Result<()> {
+        let allowed_sensors = crate::CONFIG
+            .lock()
+            .as_ref()
+            .unwrap()
+            .get::<Vec<String>>("sensors.allowed_sensors")
+            .ok();
+
+        *ALLOWED_SENSORS.write() =
+            allowed_sensors.map(|sensors| sensors.into_iter().collect::<HashSet<String>>());
+
         Ok(())
     }
 
@@ -179,6 +328,14 @@ impl Plugin for SensorsPlugin {
             lua_ctx.create_function(move |_, ()| Ok(SensorsPlugin::get_swap_used_kb()))?;
         globals.set("get_swap_used_kb", get_swap_used_kb)?;
 
+        let get_sensor =
+            lua_ctx.create_function(move |_, name: String| Ok(SensorsPlugin::get_sensor(&name)))?;
+        globals.set("get_sensor", get_sensor)?;
+
+        let get_sensor_names =
+            lua_ctx.create_function(move |_, ()| Ok(SensorsPlugin::get_sensor_names()))?;
+        globals.set("get_sensor_names", get_sensor_names)?;
+
         Ok(())
     }
 
@@ -19,15 +19,25 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::Connection;
+use flume::{Receiver, RecvTimeoutError, Sender};
+use lazy_static::lazy_static;
 use log::*;
 use mlua::prelude::*;
+use parking_lot::Mutex;
 use std::any::Any;
-
+use std::collections::BTreeMap;
 use std::process::Command;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::constants;
 use crate::plugins;
 use crate::plugins::Plugin;
+use crate::util::ratelimited;
 
 // pub type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -37,6 +47,144 @@ use crate::plugins::Plugin;
 //     UnknownError { description: String },
 // }
 
+lazy_static! {
+    /// States of the systemd units that are watched by the Lua scripts, they are
+    /// refreshed by a background thread, so that scripts never block on D-Bus
+    static ref UNIT_STATES: Arc<Mutex<BTreeMap<String, UnitState>>> =
+        Arc::new(Mutex::new(BTreeMap::new()));
+
+    /// Wakes up the thread that refreshes the unit states, `None` until it has been spawned
+    static ref UNIT_STATES_TX: Arc<Mutex<Option<Sender<()>>>> = Arc::new(Mutex::new(None));
+}
+
+#[derive(Debug, Clone)]
+struct UnitState {
+    state: String,
+
+    /// Point in time when a script read the state the last time
+    last_read: Instant,
+}
+
+/// Query the `ActiveState` of the systemd unit `name` over D-Bus. Units that are not
+/// loaded are reported as "inactive"
+fn query_unit_state(conn: &Connection, name: &str) -> Result<String, dbus::Error> {
+    let timeout = Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS as u64);
+
+    let manager = conn.with_proxy(
+        "org.freedesktop.systemd1",
+        "/org/freedesktop/systemd1",
+        timeout,
+    );
+
+    let result: Result<(dbus::Path,), _> =
+        manager.method_call("org.freedesktop.systemd1.Manager", "GetUnit", (name,));
+
+    let path = match result {
+        Ok((path,)) => path,
+
+        Err(e) if e.name() == Some("org.freedesktop.systemd1.NoSuchUnit") => {
+            return Ok("inactive".to_string())
+        }
+
+        Err(e) => return Err(e),
+    };
+
+    let unit = conn.with_proxy("org.freedesktop.systemd1", path, timeout);
+
+    unit.get("org.freedesktop.systemd1.Unit", "ActiveState")
+}
+
+/// Wake up the thread that refreshes the unit states, spawn it if it is not running yet
+fn wake_unit_states_thread() {
+    let mut unit_states_tx = UNIT_STATES_TX.lock();
+
+    if unit_states_tx.is_none() {
+        let (tx, rx) = flume::unbounded();
+
+        match thread::Builder::new()
+            .name("unit-states".into())
+            .spawn(move || run_unit_states_thread(rx))
+        {
+            Ok(_) => *unit_states_tx = Some(tx),
+
+            Err(e) => {
+                ratelimited::error!("Could not spawn a thread: {}", e);
+
+                return;
+            }
+        }
+    }
+
+    if let Some(tx) = unit_states_tx.as_ref() {
+        tx.send(()).ok();
+    }
+}
+
+fn run_unit_states_thread(wakeup_rx: Receiver<()>) {
+    let mut system_bus = None;
+
+    loop {
+        if crate::QUIT.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let names = {
+            let mut unit_states = UNIT_STATES.lock();
+
+            // forget the units that are no longer watched by any script
+            unit_states.retain(|_, unit| {
+                unit.last_read.elapsed() < Duration::from_secs(constants::UNIT_STATE_EXPIRY_SECS)
+            });
+
+            unit_states.keys().cloned().collect::<Vec<_>>()
+        };
+
+        for name in names {
+            let state = refresh_unit_state(&mut system_bus, &name);
+
+            if let Some(unit) = UNIT_STATES.lock().get_mut(&name) {
+                unit.state = state;
+            }
+        }
+
+        // wait for the next refresh, or until a unit has been queried for the first time
+        if let Err(RecvTimeoutError::Disconnected) = wakeup_rx.recv_timeout(Duration::from_millis(
+            constants::UNIT_STATE_REFRESH_INTERVAL_MILLIS,
+        )) {
+            break;
+        }
+
+        // coalesce multiple wake-ups
+        while wakeup_rx.try_recv().is_ok() {}
+    }
+}
+
+/// Query the state of the unit `name`, returns "unknown" if the state could not be queried
+fn refresh_unit_state(system_bus: &mut Option<Connection>, name: &str) -> String {
+    if system_bus.is_none() {
+        match Connection::new_system() {
+            Ok(conn) => *system_bus = Some(conn),
+
+            Err(e) => ratelimited::error!("Could not connect to the system bus: {}", e),
+        }
+    }
+
+    match system_bus.as_ref().map(|conn| query_unit_state(conn, name)) {
+        Some(Ok(state)) => state,
+
+        Some(Err(e)) => {
+            ratelimited::warn!("Could not query the state of the unit {}: {}", name, e);
+
+            // re-connect on the next query
+            *system_bus = None;
+
+            "unknown".to_string()
+        }
+
+        None => "unknown".to_string(),
+    }
+}
+
 /// A plugin that gives Lua scripts access to the systems state like e.g.
 /// the number of runnable processes or the load average
 pub struct SystemPlugin {}
@@ -106,6 +254,33 @@ impl SystemPlugin {
             .unwrap()
     }
 
+    /// Get the state of the systemd unit `name`, like e.g. "active", "failed" or "inactive".
+    /// The states are refreshed in the background every UNIT_STATE_REFRESH_INTERVAL_MILLIS,
+    /// returns "unknown" until the state of a unit has been queried for the first time
+    pub(crate) fn get_unit_state(name: &str) -> String {
+        let mut unit_states = UNIT_STATES.lock();
+
+        if let Some(unit) = unit_states.get_mut(name) {
+            unit.last_read = Instant::now();
+
+            return unit.state.clone();
+        }
+
+        unit_states.insert(
+            name.to_string(),
+            UnitState {
+                state: "unknown".to_string(),
+                last_read: Instant::now(),
+            },
+        );
+
+        drop(unit_states);
+
+        wake_unit_states_thread();
+
+        "unknown".to_string()
+    }
+
     /// Terminate the Eruption daemon
     pub(crate) fn exit() {
         crate::QUIT.store(true, Ordering::SeqCst);
@@ -154,6 +329,10 @@ impl Plugin for SystemPlugin {
         })?;
         globals.set("system", system)?;
 
+        let get_unit_state =
+            lua_ctx.create_function(|_, name: String| Ok(SystemPlugin::get_unit_state(&name)))?;
+        globals.set("get_unit_state", get_unit_state)?;
+
         let exit = lua_ctx.create_function(|_, (): ()| {
             SystemPlugin::exit();
             Ok(())
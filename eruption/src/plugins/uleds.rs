@@ -74,6 +74,42 @@ impl UledsPlugin {
         UledsPlugin {}
     }
 
+    /// Register our LED with the Linux ULEDs subsystem, if it is available
+    fn open_uleds_device() -> Result<()> {
+        let filename = PathBuf::from("/dev/uleds");
+
+        if util::file_exists(&filename) {
+            let fd = fcntl::open(&filename, OFlag::O_RDWR, Mode::from_bits(0o660).unwrap())?;
+            debug!("Successfully opened the ULEDs device");
+
+            //
+            let mut dev = UledsUserDev {
+                ..Default::default()
+            };
+
+            let name = CString::new("eruption::all")?;
+            let name = name.as_bytes_with_nul();
+            dev.name[0..name.len()]
+                .copy_from_slice(&name.iter().map(|&c| c as i8).collect::<Vec<i8>>());
+
+            dev.max_brightness = 255;
+
+            //
+            let bytes = unsafe { any_as_u8_slice(&dev) };
+            let _result = nix::unistd::write(fd, bytes)?;
+
+            ULEDS_FDS.write().push(fd);
+
+            debug!("Successfully initialized the ULEDs subsystem");
+
+            Ok(())
+        } else {
+            info!("The ULEDs subsystem is not available on this kernel");
+
+            Ok(())
+        }
+    }
+
     pub fn spawn_uleds_thread() -> Result<()> {
         // let (uleds_tx, uleds_rx) = unbounded();
 
@@ -85,6 +121,9 @@ impl UledsPlugin {
 
                 // Self::initialize_thread_locals()?;
 
+                Self::open_uleds_device()
+                    .unwrap_or_else(|e| error!("Could not initialize the ULEDs subsystem: {}", e));
+
                 if ULEDS_FDS.read().len() > 0 {
                     ULEDS_SUPPORT_ACTIVE.store(true, Ordering::SeqCst);
 
@@ -132,38 +171,9 @@ impl Plugin for UledsPlugin {
     }
 
     fn initialize(&mut self) -> plugins::Result<()> {
-        let filename = PathBuf::from("/dev/uleds");
-
-        if util::file_exists(&filename) {
-            let fd = fcntl::open(&filename, OFlag::O_RDWR, Mode::from_bits(0o660).unwrap())?;
-            debug!("Successfully opened the ULEDs device");
-
-            //
-            let mut dev = UledsUserDev {
-                ..Default::default()
-            };
-
-            let name = CString::new("eruption::all")?;
-            let name = name.as_bytes_with_nul();
-            dev.name[0..name.len()]
-                .copy_from_slice(&name.iter().map(|&c| c as i8).collect::<Vec<i8>>());
-
-            dev.max_brightness = 255;
-
-            //
-            let bytes = unsafe { any_as_u8_slice(&dev) };
-            let _result = nix::unistd::write(fd, bytes)?;
-
-            ULEDS_FDS.write().push(fd);
-
-            debug!("Successfully initialized the ULEDs subsystem");
-
-            Ok(())
-        } else {
-            info!("The ULEDs subsystem is not available on this kernel");
-
-            Ok(())
-        }
+        // the ULEDs device is set up lazily by the uleds thread, so that
+        // it does not delay the startup of the daemon
+        Ok(())
     }
 
     fn register_lua_funcs(&self, _lua_ctx: &Lua) -> mlua::Result<()> {
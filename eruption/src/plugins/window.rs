@@ -0,0 +1,149 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use mlua::prelude::*;
+use parking_lot::RwLock;
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::plugins::{self, Plugin};
+use crate::scripting::script;
+use crate::{FAILED_TXS, LUA_TXS};
+
+// pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Metadata of the currently focused window, as reported by the process monitor
+    static ref ACTIVE_WINDOW: Arc<RwLock<Option<ActiveWindow>>> = Arc::new(RwLock::new(None));
+}
+
+/// Metadata of a window, as reported by the window sensors of the process monitor.
+/// On Wayland the `app_id` is reported, the window class holds the same value there
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActiveWindow {
+    pub class: String,
+    pub title: String,
+    pub app_id: String,
+}
+
+/// Set the metadata of the currently focused window, and notify the Lua VMs if it changed
+pub fn set_active_window(window: ActiveWindow) {
+    let previous = ACTIVE_WINDOW.write().replace(window.clone());
+
+    if previous.as_ref() != Some(&window) {
+        debug!("Active window changed: {:?}", window);
+
+        for (idx, lua_tx) in LUA_TXS.read().iter().enumerate() {
+            if !FAILED_TXS.read().contains(&idx) {
+                lua_tx
+                    .send(script::Message::WindowFocused(
+                        window.class.clone(),
+                        window.title.clone(),
+                        window.app_id.clone(),
+                    ))
+                    .unwrap_or_else(|e| {
+                        error!("Could not send a pending window event to a Lua VM: {}", e)
+                    });
+            } else {
+                warn!("Not sending a message to a failed tx");
+            }
+        }
+    }
+}
+
+/// A plugin that exposes the metadata of the focused window to Lua scripts
+pub struct WindowPlugin {}
+
+impl WindowPlugin {
+    pub fn new() -> Self {
+        WindowPlugin {}
+    }
+
+    /// Returns the class, the title and the app_id of the focused window, or `None`
+    /// if the process monitor did not report a window yet
+    pub(crate) fn get_active_window() -> Option<(String, String, String)> {
+        ACTIVE_WINDOW.read().as_ref().map(|window| {
+            (
+                window.class.clone(),
+                window.title.clone(),
+                window.app_id.clone(),
+            )
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Plugin for WindowPlugin {
+    fn get_name(&self) -> String {
+        "Window".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Exposes the metadata of the focused window".to_string()
+    }
+
+    fn initialize(&mut self) -> plugins::Result<()> {
+        Ok(())
+    }
+
+    fn register_lua_funcs(&self, lua_ctx: &Lua) -> mlua::Result<()> {
+        let globals = lua_ctx.globals();
+
+        let get_active_window = lua_ctx.create_function(|_, ()| {
+            Ok(match WindowPlugin::get_active_window() {
+                Some((class, title, app_id)) => (Some(class), Some(title), Some(app_id)),
+                None => (None, None, None),
+            })
+        })?;
+        globals.set("get_active_window", get_active_window)?;
+
+        let get_active_window_class = lua_ctx.create_function(|_, ()| {
+            Ok(WindowPlugin::get_active_window().map(|(class, _, _)| class))
+        })?;
+        globals.set("get_active_window_class", get_active_window_class)?;
+
+        let get_active_window_title = lua_ctx.create_function(|_, ()| {
+            Ok(WindowPlugin::get_active_window().map(|(_, title, _)| title))
+        })?;
+        globals.set("get_active_window_title", get_active_window_title)?;
+
+        let get_active_window_app_id = lua_ctx.create_function(|_, ()| {
+            Ok(WindowPlugin::get_active_window().map(|(_, _, app_id)| app_id))
+        })?;
+        globals.set("get_active_window_app_id", get_active_window_app_id)?;
+
+        Ok(())
+    }
+
+    async fn main_loop_hook(&self, _ticks: u64) {}
+
+    fn sync_main_loop_hook(&self, _ticks: u64) {}
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,133 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Per-profile overrides of the USB polling rate of mice. The polling rate that a mouse
+//! used before it has been overridden is restored, as soon as a profile without an
+//! override for that mouse is activated
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::device_id;
+use crate::profiles::PollRate;
+use crate::state::DeviceMakeModelSerial;
+
+lazy_static! {
+    /// Polling rates of the mice before they have been overridden by a profile,
+    /// indexed by their stable device identifier
+    static ref SAVED_RATES: Arc<Mutex<HashMap<DeviceMakeModelSerial, i32>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Returns the polling rate that the profile assigns to the mouse `id`,
+/// an override for that specific mouse takes precedence over an override for all mice
+fn find_override(overrides: &[PollRate], id: &str) -> Option<i32> {
+    overrides
+        .iter()
+        .find(|o| o.device.as_deref() == Some(id))
+        .or_else(|| overrides.iter().find(|o| o.device.is_none()))
+        .map(|o| o.rate)
+}
+
+/// Apply the polling rate overrides of a newly activated profile
+pub fn apply_profile(overrides: &[PollRate]) {
+    let mut saved_rates = SAVED_RATES.lock();
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        let id = device_id::device_id(device.read().as_device());
+
+        match find_override(overrides, &id) {
+            Some(rate) => {
+                if !saved_rates.contains_key(&id) {
+                    match device.read().get_rate() {
+                        Ok(current) => {
+                            saved_rates.insert(id.clone(), current);
+                        }
+
+                        Err(e) => {
+                            debug!("Not overriding the polling rate of {}: {}", id, e);
+                            continue;
+                        }
+                    }
+                }
+
+                info!("Setting the polling rate of {} to {} Hz", id, rate);
+
+                device
+                    .write()
+                    .set_rate(rate)
+                    .unwrap_or_else(|e| warn!("Could not set the polling rate: {}", e));
+            }
+
+            None => {
+                if let Some(rate) = saved_rates.remove(&id) {
+                    info!("Restoring the polling rate of {} to {} Hz", id, rate);
+
+                    device
+                        .write()
+                        .set_rate(rate)
+                        .unwrap_or_else(|e| warn!("Could not restore the polling rate: {}", e));
+                }
+            }
+        }
+    }
+}
+
+/// Restore the polling rates of all mice that have been overridden, should be
+/// called before the devices are closed
+pub fn restore_all() {
+    apply_profile(&[]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_override_takes_precedence() {
+        let overrides = vec![
+            PollRate {
+                device: None,
+                rate: 500,
+            },
+            PollRate {
+                device: Some("0x1e7d:0x2dd2:usb-1-2".to_string()),
+                rate: 1000,
+            },
+        ];
+
+        assert_eq!(
+            find_override(&overrides, "0x1e7d:0x2dd2:usb-1-2"),
+            Some(1000)
+        );
+        assert_eq!(
+            find_override(&overrides, "0x1e7d:0x2e27:usb-1-3"),
+            Some(500)
+        );
+        assert_eq!(
+            find_override(&overrides[1..], "0x1e7d:0x2e27:usb-1-3"),
+            None
+        );
+    }
+}
@@ -0,0 +1,265 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::{constants, hotkeys};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PowerError {
+    #[error("Invalid power saving mode: {mode}")]
+    InvalidMode { mode: String },
+}
+
+lazy_static! {
+    /// Power saving settings, as configured in the [power] section of eruption.conf
+    static ref SETTINGS: Arc<RwLock<PowerSettings>> = Arc::new(RwLock::new(PowerSettings::default()));
+
+    /// The power source that has been detected most recently
+    static ref POWER_SOURCE: Arc<RwLock<PowerSource>> = Arc::new(RwLock::new(PowerSource::Unknown));
+
+    /// Power saving mode, may be overridden via D-Bus
+    static ref POWER_SAVING_MODE: Arc<RwLock<PowerSavingMode>> = Arc::new(RwLock::new(PowerSavingMode::Auto));
+}
+
+/// The source the system is currently powered from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+
+    /// No power supplies have been found, e.g. on most desktop systems
+    Unknown,
+}
+
+impl fmt::Display for PowerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerSource::Ac => write!(f, "ac"),
+            PowerSource::Battery => write!(f, "battery"),
+            PowerSource::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Whether power saving is enabled automatically when running on battery,
+/// or has been forced on or off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSavingMode {
+    Auto,
+    On,
+    Off,
+}
+
+impl fmt::Display for PowerSavingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerSavingMode::Auto => write!(f, "auto"),
+            PowerSavingMode::On => write!(f, "on"),
+            PowerSavingMode::Off => write!(f, "off"),
+        }
+    }
+}
+
+impl FromStr for PowerSavingMode {
+    type Err = PowerError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(PowerSavingMode::Auto),
+            "on" => Ok(PowerSavingMode::On),
+            "off" => Ok(PowerSavingMode::Off),
+
+            _ => Err(PowerError::InvalidMode {
+                mode: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// The measures that are taken while power saving is active
+#[derive(Debug, Clone, Default)]
+struct PowerSettings {
+    /// Enter power saving automatically when running on battery
+    enabled: bool,
+
+    /// Frame rate while power saving is active
+    fps: Option<u64>,
+
+    /// Brightness in percent, relative to the global brightness
+    brightness: Option<u64>,
+
+    /// Profile to switch to while power saving is active, relative paths
+    /// are looked up in the profile directories
+    profile: Option<PathBuf>,
+}
+
+/// Load the power saving settings from the global configuration
+pub fn load_settings() -> Result<()> {
+    let config = crate::CONFIG.lock();
+    let config = config.as_ref().unwrap();
+
+    let settings = PowerSettings {
+        enabled: config.get::<bool>("power.enabled").unwrap_or(false),
+        fps: config
+            .get::<u64>("power.battery_fps")
+            .ok()
+            .filter(|fps| (1..=constants::TARGET_FPS).contains(fps)),
+        brightness: config
+            .get::<u64>("power.battery_brightness")
+            .ok()
+            .map(|brightness| brightness.min(100)),
+        profile: config
+            .get::<String>("power.battery_profile")
+            .ok()
+            .map(PathBuf::from),
+    };
+
+    *SETTINGS.write() = settings;
+
+    Ok(())
+}
+
+/// Determine the current power source from the power supplies in sysfs. We are
+/// running on battery if none of the mains power supplies is online, and at least
+/// one battery is present
+pub fn detect_power_source() -> PowerSource {
+    let read = |path: &Path, attribute: &str| {
+        fs::read_to_string(path.join(attribute))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let mut has_mains = false;
+    let mut has_battery = false;
+
+    if let Ok(entries) = fs::read_dir(constants::POWER_SUPPLY_SYSFS_PATH) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            match read(&path, "type").as_str() {
+                "Mains" | "USB" => {
+                    if read(&path, "online") == "1" {
+                        return PowerSource::Ac;
+                    }
+
+                    has_mains = true;
+                }
+
+                "Battery" => {
+                    // skip the batteries of peripheral devices, like e.g. wireless mice
+                    if read(&path, "scope") != "Device" {
+                        has_battery = true;
+                    }
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    if has_battery {
+        PowerSource::Battery
+    } else if has_mains {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+/// Re-detect the power source, returns `true` if it changed
+pub fn update_power_source() -> bool {
+    let power_source = detect_power_source();
+    let previous = std::mem::replace(&mut *POWER_SOURCE.write(), power_source);
+
+    if previous != power_source {
+        info!("Power source changed: {}", power_source);
+
+        true
+    } else {
+        false
+    }
+}
+
+pub fn get_power_source() -> PowerSource {
+    *POWER_SOURCE.read()
+}
+
+pub fn get_power_saving_mode() -> PowerSavingMode {
+    *POWER_SAVING_MODE.read()
+}
+
+/// Override automatic power saving, or return to automatic mode
+pub fn set_power_saving_mode(mode: PowerSavingMode) {
+    info!("Power saving mode: {}", mode);
+
+    *POWER_SAVING_MODE.write() = mode;
+}
+
+/// Returns `true` if power saving measures should currently be in effect
+pub fn is_active() -> bool {
+    match get_power_saving_mode() {
+        PowerSavingMode::On => true,
+        PowerSavingMode::Off => false,
+
+        PowerSavingMode::Auto => {
+            SETTINGS.read().enabled && get_power_source() == PowerSource::Battery
+        }
+    }
+}
+
+/// Returns the frame rate that the LED maps shall be rendered with
+pub fn get_target_fps() -> u64 {
+    if is_active() {
+        SETTINGS.read().fps.unwrap_or(constants::TARGET_FPS)
+    } else {
+        constants::TARGET_FPS
+    }
+}
+
+/// Returns the factor that the LED maps shall be dimmed by, or `None` if they
+/// shall be sent to the devices unaltered
+pub fn get_brightness_factor() -> Option<f32> {
+    if is_active() {
+        SETTINGS
+            .read()
+            .brightness
+            .map(|brightness| brightness as f32 / 100.0)
+    } else {
+        None
+    }
+}
+
+/// Returns the profile to switch to while power saving is active
+pub fn get_power_saving_profile() -> Option<PathBuf> {
+    let profile = SETTINGS.read().profile.clone();
+
+    profile.map(|profile| hotkeys::resolve_profile_path(&profile))
+}
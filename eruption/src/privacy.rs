@@ -0,0 +1,110 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::constants;
+use crate::scripting::script;
+use crate::zones;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PrivacyError {
+    #[error("Unknown zone: {name}")]
+    UnknownZone { name: String },
+}
+
+lazy_static! {
+    /// The zones that are blacked out while privacy mode is enabled, along with their keys
+    static ref PRIVACY_MODE: Arc<RwLock<Option<PrivacyMode>>> = Arc::new(RwLock::new(None));
+}
+
+#[derive(Debug, Clone)]
+struct PrivacyMode {
+    /// Names of the blacked out zones, empty if the whole canvas is blacked out
+    zones: Vec<String>,
+
+    /// Key indices of the blacked out zones
+    keys: Vec<usize>,
+}
+
+/// Enable privacy mode, blacking out the zones `zones`, or the whole canvas if
+/// `zones` is empty
+pub fn enable(zones: &[String]) -> Result<()> {
+    let keys = if zones.is_empty() {
+        (1..=constants::CANVAS_SIZE).collect()
+    } else {
+        let mut keys = Vec::new();
+
+        for name in zones {
+            keys.extend(
+                zones::get_zone(name).ok_or_else(|| PrivacyError::UnknownZone {
+                    name: name.to_owned(),
+                })?,
+            );
+        }
+
+        keys
+    };
+
+    if zones.is_empty() {
+        info!("Privacy mode enabled, blacking out the whole canvas");
+    } else {
+        info!(
+            "Privacy mode enabled, blacking out zones: {}",
+            zones.join(", ")
+        );
+    }
+
+    *PRIVACY_MODE.write() = Some(PrivacyMode {
+        zones: zones.to_vec(),
+        keys,
+    });
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    Ok(())
+}
+
+/// Disable privacy mode
+pub fn disable() {
+    if PRIVACY_MODE.write().take().is_some() {
+        info!("Privacy mode disabled");
+
+        script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Returns the names of the blacked out zones if privacy mode is enabled, an empty
+/// list denotes the whole canvas
+pub fn get_zones() -> Option<Vec<String>> {
+    PRIVACY_MODE.read().as_ref().map(|mode| mode.zones.clone())
+}
+
+/// Returns the key indices that shall be blacked out, or `None` if privacy mode is disabled
+pub fn get_blacked_out_keys() -> Option<Vec<usize>> {
+    PRIVACY_MODE.read().as_ref().map(|mode| mode.keys.clone())
+}
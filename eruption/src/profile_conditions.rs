@@ -0,0 +1,90 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::Mutex;
+use std::path::PathBuf;
+
+use crate::profiles::{DeviceClass, Profile};
+
+lazy_static! {
+    /// Scripts of the active profile that are not running, since their conditions are not met
+    static ref SKIPPED_SCRIPTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+}
+
+/// Returns the classes of the devices that are currently managed by Eruption
+pub fn connected_device_classes() -> Vec<DeviceClass> {
+    let mut result = Vec::new();
+
+    if !crate::KEYBOARD_DEVICES.read().is_empty() {
+        result.push(DeviceClass::Keyboard);
+    }
+
+    if !crate::MOUSE_DEVICES.read().is_empty() {
+        result.push(DeviceClass::Mouse);
+    }
+
+    if !crate::MISC_DEVICES.read().is_empty() {
+        result.push(DeviceClass::Misc);
+    }
+
+    result
+}
+
+/// Returns the scripts of `profile` whose conditions are not met by the connected devices
+pub fn skipped_scripts(profile: &Profile) -> Vec<PathBuf> {
+    let connected = connected_device_classes();
+
+    profile
+        .manifests
+        .values()
+        .filter(|manifest| !profile.is_script_enabled(&manifest.script_file, &connected))
+        .map(|manifest| manifest.script_file.to_owned())
+        .collect()
+}
+
+/// Remember the scripts that have been skipped while loading the active profile.
+/// This is called on every profile switch
+pub fn set_skipped_scripts(skipped: Vec<PathBuf>) {
+    for script_file in skipped.iter() {
+        info!(
+            "Not running the script {}, the required devices are not connected",
+            script_file.display()
+        );
+    }
+
+    *SKIPPED_SCRIPTS.lock() = skipped;
+}
+
+/// Returns `true` if the active profile has to be reloaded, since the set of scripts whose
+/// conditions are met changed. This is called after devices have been hotplugged
+pub fn needs_reload() -> bool {
+    let active_profile = crate::ACTIVE_PROFILE.lock();
+
+    match active_profile.as_ref() {
+        Some(profile) if !profile.conditions.is_empty() => {
+            *SKIPPED_SCRIPTS.lock() != skipped_scripts(profile)
+        }
+
+        _ => false,
+    }
+}
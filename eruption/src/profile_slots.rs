@@ -0,0 +1,132 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{profiles, state};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Set if new profiles shall be assigned to empty slots
+    static ref AUTO_ASSIGN: AtomicBool = AtomicBool::new(false);
+}
+
+/// Enable the automatic assignment of new profiles, if it is enabled in the configuration
+pub fn initialize() {
+    let enabled = crate::CONFIG
+        .lock()
+        .as_ref()
+        .and_then(|config| config.get::<bool>("global.auto_assign_profiles").ok())
+        .unwrap_or(false);
+
+    AUTO_ASSIGN.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns the index of the first slot that has no profile, or whose profile
+/// file does not exist anymore
+fn find_empty_slot(slot_profiles: &[PathBuf], exists: impl Fn(&Path) -> bool) -> Option<usize> {
+    slot_profiles
+        .iter()
+        .position(|profile_file| profile_file.as_os_str().is_empty() || !exists(profile_file))
+}
+
+/// Assign the newly created profile `profile_file` to the first empty slot, if enabled.
+/// Profiles that are invalid, or that are already assigned to a slot, are skipped.
+/// Returns the index of the slot, or `None` if the profile has not been assigned
+pub fn auto_assign(profile_file: &Path) -> Result<Option<usize>> {
+    if !AUTO_ASSIGN.load(Ordering::SeqCst) {
+        return Ok(None);
+    }
+
+    if let Err(e) = profiles::Profile::load_file_only(profile_file) {
+        debug!(
+            "Not assigning the profile {} to a slot: {}",
+            profile_file.display(),
+            e
+        );
+
+        return Ok(None);
+    }
+
+    let slot = {
+        let mut slot_profiles = crate::SLOT_PROFILES.lock();
+
+        let slot_profiles = match slot_profiles.as_mut() {
+            Some(slot_profiles) => slot_profiles,
+            None => return Ok(None),
+        };
+
+        if slot_profiles.iter().any(|p| p == profile_file) {
+            return Ok(None);
+        }
+
+        match find_empty_slot(slot_profiles, Path::is_file) {
+            Some(slot) => {
+                slot_profiles[slot] = profile_file.to_path_buf();
+
+                slot
+            }
+
+            None => {
+                debug!(
+                    "Not assigning the profile {} to a slot: All slots are in use",
+                    profile_file.display()
+                );
+
+                return Ok(None);
+            }
+        }
+    };
+
+    info!(
+        "Assigned the new profile {} to slot {}",
+        profile_file.display(),
+        slot + 1
+    );
+
+    state::save_runtime_state()?;
+
+    Ok(Some(slot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_empty_slot() {
+        let slot_profiles = vec![
+            PathBuf::from("/var/lib/eruption/profiles/a.profile"),
+            PathBuf::from("/var/lib/eruption/profiles/removed.profile"),
+            PathBuf::new(),
+        ];
+
+        let exists = |p: &Path| !p.ends_with("removed.profile");
+
+        assert_eq!(find_empty_slot(&slot_profiles, exists), Some(1));
+        assert_eq!(find_empty_slot(&slot_profiles[2..], exists), Some(0));
+        assert_eq!(find_empty_slot(&slot_profiles[..1], exists), None);
+    }
+}
@@ -0,0 +1,272 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Span profiler. Structured `tracing` spans around rendering, compositing, HID writes and
+//! Lua upcalls are aggregated per second into a bounded history. The history may be dumped
+//! as folded stacks (one `frame;frame;frame <microseconds>` line per stack) via D-Bus, and
+//! turned into a flamegraph with e.g. `inferno-flamegraph` or `flamegraph.pl`
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::{constants, util};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Set if the span profiler has been installed
+    static ref ENABLED: AtomicBool = AtomicBool::new(false);
+
+    /// Self-time of the recorded stacks, aggregated per second
+    static ref HISTORY: Arc<Mutex<ProfileHistory>> = Arc::new(Mutex::new(ProfileHistory::default()));
+
+    /// Point in time when the span profiler has been installed
+    static ref STARTED_AT: Instant = Instant::now();
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfilerError {
+    #[error("The span profiler is disabled, set 'enable_span_profiler = true' in section [global] of eruption.conf")]
+    Disabled {},
+}
+
+/// Self-time of folded stacks in microseconds, in buckets of one second each
+#[derive(Debug, Default)]
+struct ProfileHistory {
+    buckets: VecDeque<(u64, HashMap<String, u64>)>,
+}
+
+impl ProfileHistory {
+    fn record(&mut self, second: u64, stack: String, micros: u64) {
+        if self.buckets.back().map(|(s, _)| *s) != Some(second) {
+            self.buckets.push_back((second, HashMap::new()));
+
+            while self.buckets.front().map_or(false, |(s, _)| {
+                s + constants::MAX_PROFILE_HISTORY_SECS <= second
+            }) {
+                self.buckets.pop_front();
+            }
+        }
+
+        if let Some((_, stacks)) = self.buckets.back_mut() {
+            *stacks.entry(stack).or_insert(0) += micros;
+        }
+    }
+
+    /// Merge the buckets of the seconds `since` and later
+    fn folded(&self, since: u64) -> BTreeMap<String, u64> {
+        let mut result = BTreeMap::new();
+
+        for (_, stacks) in self.buckets.iter().filter(|(s, _)| *s >= since) {
+            for (stack, micros) in stacks {
+                *result.entry(stack.clone()).or_insert(0) += micros;
+            }
+        }
+
+        result
+    }
+}
+
+/// Timing of a span, stored in the extensions of the span
+struct SpanTiming {
+    label: String,
+    entered: Option<Instant>,
+
+    /// Time spent in child spans, subtracted from the time of this span
+    children: Duration,
+}
+
+/// Records the value of the optional `label` field of a span, e.g. the name of a Lua function
+struct LabelVisitor(Option<String>);
+
+impl Visit for LabelVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "label" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "label" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+/// A `tracing` layer that aggregates the self-time of spans into the profile history
+struct SpanProfilerLayer;
+
+impl<S> Layer<S> for SpanProfilerLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut visitor = LabelVisitor(None);
+            attrs.record(&mut visitor);
+
+            let label = match visitor.0 {
+                Some(label) => format!("{}:{}", attrs.metadata().name(), label),
+                None => attrs.metadata().name().to_string(),
+            };
+
+            span.extensions_mut().insert(SpanTiming {
+                label,
+                entered: None,
+                children: Duration::ZERO,
+            });
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                timing.entered = Some(Instant::now());
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let (elapsed, children) = match span.extensions_mut().get_mut::<SpanTiming>() {
+            Some(timing) => match timing.entered.take() {
+                Some(entered) => (entered.elapsed(), std::mem::take(&mut timing.children)),
+                None => return,
+            },
+
+            None => return,
+        };
+
+        if let Some(parent) = span.parent() {
+            if let Some(timing) = parent.extensions_mut().get_mut::<SpanTiming>() {
+                timing.children += elapsed;
+            }
+        }
+
+        let mut frames = vec![util::get_thread_name()];
+
+        frames.extend(span.scope().from_root().map(|span| {
+            span.extensions()
+                .get::<SpanTiming>()
+                .map(|timing| timing.label.clone())
+                .unwrap_or_else(|| span.name().to_string())
+        }));
+
+        let micros = elapsed.saturating_sub(children).as_micros() as u64;
+
+        HISTORY
+            .lock()
+            .record(STARTED_AT.elapsed().as_secs(), frames.join(";"), micros);
+    }
+}
+
+/// Install the span profiler, if it is enabled in the configuration
+pub fn initialize() {
+    let enabled = crate::CONFIG
+        .lock()
+        .as_ref()
+        .and_then(|config| config.get::<bool>("global.enable_span_profiler").ok())
+        .unwrap_or(false);
+
+    if !enabled {
+        return;
+    }
+
+    lazy_static::initialize(&STARTED_AT);
+
+    let subscriber = tracing_subscriber::registry().with(SpanProfilerLayer);
+
+    match tracing::subscriber::set_global_default(subscriber) {
+        Ok(()) => {
+            info!("The span profiler is enabled");
+
+            ENABLED.store(true, Ordering::SeqCst);
+        }
+
+        Err(e) => warn!("Could not install the span profiler: {}", e),
+    }
+}
+
+/// Returns the profile of the last `seconds` seconds as folded stacks, one stack per line
+pub fn dump_profile(seconds: u64) -> Result<String> {
+    if !ENABLED.load(Ordering::SeqCst) {
+        return Err(ProfilerError::Disabled {}.into());
+    }
+
+    let since = STARTED_AT.elapsed().as_secs().saturating_sub(seconds);
+    let stacks = HISTORY.lock().folded(since);
+
+    let data = stacks
+        .iter()
+        .map(|(stack, micros)| format!("{} {}\n", stack, micros))
+        .collect::<String>();
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate_stacks_per_second() {
+        let mut history = ProfileHistory::default();
+
+        history.record(1, "dev-io/all;composite".to_string(), 100);
+        history.record(1, "dev-io/all;composite".to_string(), 50);
+        history.record(2, "dev-io/all;hid write".to_string(), 20);
+        history.record(3, "dev-io/all;composite".to_string(), 10);
+
+        let folded = history.folded(0);
+
+        assert_eq!(folded["dev-io/all;composite"], 160);
+        assert_eq!(folded["dev-io/all;hid write"], 20);
+
+        assert_eq!(history.folded(2)["dev-io/all;composite"], 10);
+    }
+
+    #[test]
+    fn discard_old_buckets() {
+        let mut history = ProfileHistory::default();
+
+        history.record(0, "render".to_string(), 100);
+        history.record(constants::MAX_PROFILE_HISTORY_SECS, "render".to_string(), 1);
+
+        assert_eq!(history.buckets.len(), 1);
+        assert_eq!(history.folded(0)["render"], 1);
+    }
+}
@@ -21,22 +21,20 @@
 
 #![allow(dead_code)]
 
-use crate::constants;
+use crate::{constants, state_file};
 use indexmap::IndexMap;
 use log::*;
 
 use serde::{Deserialize, Serialize};
 use std::default::Default;
-use std::os::unix::prelude::{MetadataExt, OpenOptionsExt};
+use std::fs;
+use std::os::unix::prelude::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::{collections::BTreeMap, ffi::OsStr};
-use std::{fs, io};
 use uuid::Uuid;
 
 use crate::scripting::manifest::Manifest;
-use crate::scripting::parameters::{
-    ProfileConfiguration, ProfileParameter, ProfileScriptParameters, TypedValue,
-};
+use crate::scripting::parameters::{ProfileConfiguration, ProfileParameter, TypedValue};
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -76,6 +74,179 @@ fn default_script_file() -> Vec<PathBuf> {
     vec![constants::DEFAULT_EFFECT_SCRIPT.into()]
 }
 
+fn default_chord_hold_millis() -> u64 {
+    constants::DEFAULT_CHORD_HOLD_MILLIS
+}
+
+fn default_game_mode_color() -> u32 {
+    constants::DEFAULT_GAME_MODE_COLOR
+}
+
+fn default_sfx_volume() -> f32 {
+    1.0
+}
+
+/// A key chord, triggers when all of its keys are held down simultaneously
+/// for at least `hold_millis` milliseconds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub name: String,
+
+    /// Key indices of the keys that make up the chord
+    pub keys: Vec<u8>,
+
+    #[serde(default = "default_chord_hold_millis")]
+    pub hold_millis: u64,
+}
+
+/// Input events that may be routed from one device to another
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteEvent {
+    KeyDown,
+    KeyUp,
+    ButtonDown,
+    ButtonUp,
+    Wheel,
+    VolumeUp,
+    VolumeDown,
+    BrightnessUp,
+    BrightnessDown,
+}
+
+/// An entry of the event routing table, routes input events of the `source` device to
+/// effects on the `target` device. Devices are either specified by their class
+/// ("keyboard", "mouse" or "misc"), or by their stable device identifier
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub name: String,
+
+    pub source: String,
+    pub event: RouteEvent,
+
+    /// Key index, button index or wheel direction of the event; matches all events if unset
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u8>,
+
+    pub target: String,
+}
+
+/// Actions that may be bound to a mouse button
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButtonAction {
+    ToggleGameMode,
+    NextSlot,
+    PreviousSlot,
+}
+
+/// Binds a mouse button to a key sequence, to another button or to an action, while
+/// the profile is active. The bound button is no longer passed through to the system
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MouseBinding {
+    /// Button index of the bound mouse button
+    pub button: u8,
+
+    /// EV_KEY codes of the keys that are pressed in order while the button is held,
+    /// like e.g. `[29, 46]` for Ctrl+C
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<u32>,
+
+    /// Button index of the button that is emitted instead
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remap: Option<u8>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<MouseButtonAction>,
+}
+
+/// A label of a key, rendered on keyboards with per-key displays and
+/// made available to Lua scripts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KeyLabel {
+    /// Key index of the labeled key
+    pub key: u8,
+
+    pub label: String,
+
+    /// Optional color (ARGB) that scripts may use to highlight the key
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<u32>,
+}
+
+/// Keys that are inhibited while game mode is enabled, like e.g. the Super or Menu keys
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GameMode {
+    /// Key indices of the inhibited keys
+    pub keys: Vec<u8>,
+
+    /// Color (ARGB) that the inhibited keys are rendered with
+    #[serde(default = "default_game_mode_color")]
+    pub color: u32,
+}
+
+/// Samples that are played back on certain events while sound effects are enabled.
+/// Relative paths are resolved against the directory of the stock sound effects
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SoundFx {
+    /// Volume of the samples of this profile in the range `0.0..=1.0`,
+    /// scaled by the global sound effects volume
+    #[serde(default = "default_sfx_volume")]
+    pub volume: f32,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_down: Option<PathBuf>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_up: Option<PathBuf>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile_switch: Option<PathBuf>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_disconnect: Option<PathBuf>,
+}
+
+/// Overrides the USB polling rate of mice while the profile is active
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PollRate {
+    /// Stable identifier of the mouse, like e.g. "0x1e7d:0x2dd2:usb-1-2",
+    /// the override applies to all mice if not set
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+
+    /// Polling rate in Hz, one of 125, 250, 500 or 1000
+    pub rate: i32,
+}
+
+/// Classes of devices that scripts may depend on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceClass {
+    Keyboard,
+    Mouse,
+    Misc,
+}
+
+/// Runs the script `script` only while devices of all of the classes `requires` are
+/// connected, e.g. a mouse effect is only run if a mouse is managed by Eruption
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScriptCondition {
+    pub script: PathBuf,
+
+    pub requires: Vec<DeviceClass>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Profile {
     #[serde(default = "default_id")]
@@ -93,10 +264,96 @@ pub struct Profile {
     #[serde(default)]
     pub config: ProfileConfiguration,
 
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chords: Vec<Chord>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<Route>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mouse_bindings: Vec<MouseBinding>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<KeyLabel>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_mode: Option<GameMode>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sfx: Option<SoundFx>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub poll_rates: Vec<PollRate>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<ScriptCondition>,
+
+    /// Set if the profile shows a static LED map, it is uploaded once and rendering is
+    /// suspended until the next profile switch
+    #[serde(rename = "static")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_static: bool,
+
+    /// Brightness and animation speed of the zones, as adjusted at runtime. They are
+    /// persisted in the profile state, along with the values of the script parameters
+    #[serde(skip)]
+    pub zone_controls: BTreeMap<String, ZoneControl>,
+
     #[serde(skip)]
     pub manifests: IndexMap<String, Manifest>,
 }
 
+/// The brightness and animation speed of a zone
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ZoneControl {
+    /// Brightness of the zone in percent, relative to the global brightness
+    #[serde(default = "default_zone_brightness")]
+    pub brightness: i32,
+
+    /// Factor that scales the speed of the animations of the zone
+    #[serde(default = "default_zone_speed")]
+    pub speed: f64,
+}
+
+fn default_zone_brightness() -> i32 {
+    100
+}
+
+fn default_zone_speed() -> f64 {
+    1.0
+}
+
+impl Default for ZoneControl {
+    fn default() -> Self {
+        Self {
+            brightness: default_zone_brightness(),
+            speed: default_zone_speed(),
+        }
+    }
+}
+
+/// Contents of the `.profile.state` file of a profile
+#[derive(Serialize, Deserialize)]
+struct ProfileState {
+    /// The prefix keeps the key apart from the names of the scripts
+    #[serde(rename = "$zone-controls")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    zone_controls: BTreeMap<String, ZoneControl>,
+
+    #[serde(flatten)]
+    config: ProfileConfiguration,
+}
+
 macro_rules! get_default_value {
     ($t:ident, $tval:ty, $rval:ty) => {
         paste::item! {
@@ -168,6 +425,16 @@ impl Profile {
                 "/usr/share/eruption/scripts/lib/failsafe.lua",
             )],
             config: ProfileConfiguration::new(),
+            chords: vec![],
+            routes: vec![],
+            mouse_bindings: vec![],
+            labels: vec![],
+            game_mode: None,
+            sfx: None,
+            poll_rates: vec![],
+            conditions: vec![],
+            is_static: false,
+            zone_controls: BTreeMap::new(),
             manifests: IndexMap::new(),
         };
 
@@ -253,6 +520,21 @@ impl Profile {
                 let profile_parameter =
                     profile_script_parameters.get_parameter_mut(&manifest_parameter.name);
                 if let Some(profile_parameter) = profile_parameter {
+                    if let Err(e) = manifest_parameter
+                        .manifest
+                        .validate(&profile_parameter.value)
+                    {
+                        warn!(
+                            "Invalid value of parameter {} of script {} in profile {}: {}. Using the default value",
+                            manifest_parameter.name,
+                            manifest.name,
+                            self.profile_file.display(),
+                            e
+                        );
+
+                        profile_parameter.value = manifest_parameter.get_default();
+                    }
+
                     profile_parameter.manifest = Some(manifest_parameter.manifest.to_owned())
                 }
             }
@@ -298,30 +580,29 @@ impl Profile {
 
     pub fn load_params(&mut self) -> Result<()> {
         let path = self.profile_file.with_extension("profile.state");
-        let file = fs::File::open(path)?;
 
-        let map: BTreeMap<String, ProfileScriptParameters> =
-            serde_json::from_reader(io::BufReader::new(file))?;
+        // a corrupted state file falls back to one of its previous generations
+        let state: ProfileState = state_file::load(&path, |data| Ok(serde_json::from_str(data)?))?;
 
-        self.config = ProfileConfiguration::from(map);
+        self.config = state.config;
+        self.zone_controls = state.zone_controls;
 
         Ok(())
     }
 
     pub fn save_params(&self) -> Result<()> {
-        if !self.config.is_empty() {
-            let state_path = self.profile_file.with_extension("profile.state");
-            let profile_metadata = fs::metadata(&self.profile_file);
+        let state_path = self.profile_file.with_extension("profile.state");
 
-            let mut open_options = fs::OpenOptions::new();
-            open_options.create(true).write(true);
-            if let Ok(profile_metadata) = &profile_metadata {
-                open_options.mode(profile_metadata.mode()); // (only takes effect if the file is new)
-            }
+        // an existing state file has to be updated, e.g. after the last zone control has been reset
+        if !self.config.is_empty() || !self.zone_controls.is_empty() || state_path.exists() {
+            let profile_metadata = fs::metadata(&self.profile_file);
 
-            let file = open_options.open(&state_path)?;
+            let state = ProfileState {
+                zone_controls: self.zone_controls.clone(),
+                config: self.config.clone(),
+            };
 
-            serde_json::to_writer_pretty(file, &self.config)?;
+            state_file::write(&state_path, &serde_json::to_string_pretty(&state)?)?;
 
             // Try to give the state file the same permissions and ownership as the profile file.
             // This can be useful if the profile file is sitting under the user's home directory.
@@ -371,6 +652,20 @@ impl Profile {
     get_default_value!(color, TypedValue::Color, u32);
     get_config_value!(color, TypedValue::Color, u32);
     set_config_value!(color, TypedValue::Color, u32);
+
+    /// Returns `true` if the conditions of the script `script_file` are met, while devices
+    /// of the classes `connected` are present. Scripts without conditions are always enabled
+    pub fn is_script_enabled(&self, script_file: &Path, connected: &[DeviceClass]) -> bool {
+        self.conditions
+            .iter()
+            .filter(|condition| script_file.file_name() == condition.script.file_name())
+            .all(|condition| {
+                condition
+                    .requires
+                    .iter()
+                    .all(|class| connected.contains(class))
+            })
+    }
 }
 
 impl Default for Profile {
@@ -385,6 +680,16 @@ impl Default for Profile {
             description: "Auto-generated profile".into(),
             active_scripts: vec![PathBuf::from(constants::DEFAULT_EFFECT_SCRIPT)],
             config: ProfileConfiguration::new(),
+            chords: vec![],
+            routes: vec![],
+            mouse_bindings: vec![],
+            labels: vec![],
+            game_mode: None,
+            sfx: None,
+            poll_rates: vec![],
+            conditions: vec![],
+            is_static: false,
+            zone_controls: BTreeMap::new(),
             manifests: IndexMap::new(),
         }
     }
@@ -526,14 +831,14 @@ pub fn get_fail_safe_profile() -> Profile {
 
 #[cfg(test)]
 mod tests {
-    use std::{path::PathBuf, str::FromStr};
+    use std::{collections::BTreeMap, fs, path::PathBuf, str::FromStr};
 
     use indexmap::IndexMap;
     use uuid::Uuid;
 
     use crate::scripting::parameters::{ManifestValue, ProfileParameter, TypedValue};
 
-    use super::Profile;
+    use super::{MouseButtonAction, Profile, RouteEvent, ZoneControl};
 
     #[test]
     fn enum_profile_files() -> super::Result<()> {
@@ -636,6 +941,257 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn load_profile_with_chords() -> super::Result<()> {
+        let lit_toml = r#"
+id = "5dc62fa6-e965-45cb-a0da-e87d29713093"
+name = "Chords"
+description = "Profile with key chords"
+active_scripts = ["macros.lua"]
+
+[[chords]]
+name = "ctrl-alt-k"
+keys = [5, 6, 42]
+hold_millis = 500
+
+[[chords]]
+name = "default-hold"
+keys = [1, 2]
+        "#;
+
+        let profile = toml::de::from_str::<Profile>(lit_toml)?;
+
+        assert_eq!(profile.chords.len(), 2);
+
+        assert_eq!(profile.chords[0].name, "ctrl-alt-k");
+        assert_eq!(profile.chords[0].keys, vec![5, 6, 42]);
+        assert_eq!(profile.chords[0].hold_millis, 500);
+
+        assert_eq!(
+            profile.chords[1].hold_millis,
+            crate::constants::DEFAULT_CHORD_HOLD_MILLIS
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_profile_with_routes() -> super::Result<()> {
+        let lit_toml = r#"
+id = "9b7e3c1a-52d4-4e0b-b1f6-3c8d2a7e4f10"
+name = "Routes"
+description = "Profile with an event routing table"
+active_scripts = ["impact.lua"]
+
+[[routes]]
+name = "click-ripple"
+source = "mouse"
+event = "button_down"
+code = 1
+target = "keyboard"
+
+[[routes]]
+name = "volume-ring"
+source = "0x1e7d:0x3098:0123456789"
+event = "volume_up"
+target = "mouse"
+        "#;
+
+        let profile = toml::de::from_str::<Profile>(lit_toml)?;
+
+        assert_eq!(profile.routes.len(), 2);
+
+        assert_eq!(profile.routes[0].name, "click-ripple");
+        assert_eq!(profile.routes[0].event, RouteEvent::ButtonDown);
+        assert_eq!(profile.routes[0].code, Some(1));
+        assert_eq!(profile.routes[0].target, "keyboard");
+
+        assert_eq!(profile.routes[1].source, "0x1e7d:0x3098:0123456789");
+        assert_eq!(profile.routes[1].event, RouteEvent::VolumeUp);
+        assert_eq!(profile.routes[1].code, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_profile_with_mouse_bindings() -> super::Result<()> {
+        let lit_toml = r#"
+id = "0f6a1d2e-7c3b-4a59-9e84-1b2c3d4e5f60"
+name = "Mouse bindings"
+description = "Profile with mouse side-button bindings"
+active_scripts = ["organic.lua"]
+
+[[mouse_bindings]]
+button = 4
+keys = [29, 46]
+
+[[mouse_bindings]]
+button = 5
+remap = 2
+
+[[mouse_bindings]]
+button = 6
+action = "next_slot"
+        "#;
+
+        let profile = toml::de::from_str::<Profile>(lit_toml)?;
+
+        assert_eq!(profile.mouse_bindings.len(), 3);
+
+        assert_eq!(profile.mouse_bindings[0].button, 4);
+        assert_eq!(profile.mouse_bindings[0].keys, vec![29, 46]);
+        assert_eq!(profile.mouse_bindings[0].action, None);
+
+        assert_eq!(profile.mouse_bindings[1].remap, Some(2));
+        assert!(profile.mouse_bindings[1].keys.is_empty());
+
+        assert_eq!(
+            profile.mouse_bindings[2].action,
+            Some(MouseButtonAction::NextSlot)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_profile_with_labels() -> super::Result<()> {
+        let lit_toml = r#"
+id = "5dc62fa6-e965-45cb-a0da-e87d29713093"
+name = "Labels"
+description = "Profile with key labels"
+active_scripts = ["key-labels.lua"]
+
+[[labels]]
+key = 2
+label = "M1"
+color = 0xffff0000
+
+[[labels]]
+key = 3
+label = "M2"
+        "#;
+
+        let profile = toml::de::from_str::<Profile>(lit_toml)?;
+
+        assert_eq!(profile.labels.len(), 2);
+
+        assert_eq!(profile.labels[0].key, 2);
+        assert_eq!(profile.labels[0].label, "M1");
+        assert_eq!(profile.labels[0].color, Some(0xffff0000));
+
+        assert_eq!(profile.labels[1].color, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_profile_with_game_mode() -> super::Result<()> {
+        let lit_toml = r#"
+id = "0d3a6a6e-7c1f-4f43-9a4b-7e1bb1c9d0a5"
+name = "Game mode"
+description = "Profile with inhibited keys"
+active_scripts = ["organic.lua"]
+
+[game_mode]
+keys = [83, 87]
+        "#;
+
+        let profile = toml::de::from_str::<Profile>(lit_toml)?;
+        let game_mode = profile.game_mode.unwrap();
+
+        assert_eq!(game_mode.keys, vec![83, 87]);
+        assert_eq!(game_mode.color, crate::constants::DEFAULT_GAME_MODE_COLOR);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_profile_with_sfx() -> super::Result<()> {
+        let lit_toml = r#"
+id = "5b0f2a43-2f4b-4f6e-8d52-0a1c9a4b3e11"
+name = "Sound effects"
+description = "Profile with sound effects"
+active_scripts = ["organic.lua"]
+
+[sfx]
+key_down = "typewriter-down.wav"
+profile_switch = "/home/user/chime.wav"
+        "#;
+
+        let profile = toml::de::from_str::<Profile>(lit_toml)?;
+        let sfx = profile.sfx.unwrap();
+
+        assert_eq!(sfx.volume, 1.0);
+        assert_eq!(sfx.key_down, Some(PathBuf::from("typewriter-down.wav")));
+        assert_eq!(sfx.key_up, None);
+        assert_eq!(
+            sfx.profile_switch,
+            Some(PathBuf::from("/home/user/chime.wav"))
+        );
+        assert_eq!(sfx.device_disconnect, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_profile_with_poll_rates() -> super::Result<()> {
+        let lit_toml = r#"
+id = "8f6d3b1e-4c2a-4e7b-9a1d-2b5c7e9f0a13"
+name = "Poll rates"
+description = "Profile with polling rate overrides"
+active_scripts = ["organic.lua"]
+
+[[poll_rates]]
+rate = 500
+
+[[poll_rates]]
+device = "0x1e7d:0x2dd2:usb-1-2"
+rate = 1000
+        "#;
+
+        let profile = toml::de::from_str::<Profile>(lit_toml)?;
+
+        assert_eq!(profile.poll_rates.len(), 2);
+
+        assert_eq!(profile.poll_rates[0].device, None);
+        assert_eq!(profile.poll_rates[0].rate, 500);
+
+        assert_eq!(
+            profile.poll_rates[1].device.as_deref(),
+            Some("0x1e7d:0x2dd2:usb-1-2")
+        );
+        assert_eq!(profile.poll_rates[1].rate, 1000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_profile_with_conditions() -> super::Result<()> {
+        let lit_toml = r#"
+id = "3b0e6c2d-7f41-4d8a-b5e2-6a9c1f4d8e27"
+name = "Conditions"
+description = "Profile with conditional scripts"
+active_scripts = ["organic.lua", "impact.lua"]
+
+[[conditions]]
+script = "impact.lua"
+requires = ["keyboard", "mouse"]
+        "#;
+
+        let profile = toml::de::from_str::<Profile>(lit_toml)?;
+
+        assert_eq!(profile.conditions.len(), 1);
+
+        let impact = Path::new("/usr/share/eruption/scripts/impact.lua");
+        let organic = Path::new("/usr/share/eruption/scripts/organic.lua");
+
+        assert!(profile.is_script_enabled(impact, &[DeviceClass::Keyboard, DeviceClass::Mouse]));
+        assert!(!profile.is_script_enabled(impact, &[DeviceClass::Keyboard]));
+        assert!(profile.is_script_enabled(organic, &[]));
+
+        Ok(())
+    }
+
     #[test]
     fn load_profile_with_state() -> super::Result<()> {
         let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
@@ -725,6 +1281,7 @@ mod tests {
                 default: 7,
                 min: Some(-1),
                 max: Some(9999),
+                step: None,
             } => {}
             _ => assert!(false, "Wrong manifest_value: {:?}", manifest_value),
         }
@@ -824,6 +1381,16 @@ mod tests {
                 ),
             ]
             .into(),
+            chords: vec![],
+            routes: vec![],
+            mouse_bindings: vec![],
+            labels: vec![],
+            game_mode: None,
+            sfx: None,
+            poll_rates: vec![],
+            conditions: vec![],
+            is_static: false,
+            zone_controls: BTreeMap::new(),
             manifests: IndexMap::new(),
         };
 
@@ -871,4 +1438,112 @@ value = true
 
         Ok(())
     }
+
+    #[test]
+    fn zone_controls_are_kept_in_the_profile_state() -> super::Result<()> {
+        let dir = std::env::temp_dir().join(format!("eruption-profile-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let profile_file = dir.join("test.profile");
+        fs::write(&profile_file, "")?;
+
+        let mut profile = Profile {
+            profile_file: profile_file.clone(),
+            ..Default::default()
+        };
+
+        profile.config.set_parameter(
+            "Perlin noise",
+            ProfileParameter {
+                name: "opacity".to_string(),
+                value: TypedValue::Float(0.5),
+                manifest: None,
+            },
+        );
+
+        profile.zone_controls = BTreeMap::from([(
+            "numpad".to_string(),
+            ZoneControl {
+                brightness: 25,
+                speed: 2.0,
+            },
+        )]);
+
+        profile.save_params()?;
+
+        let mut loaded = Profile {
+            profile_file: profile_file.clone(),
+            ..Default::default()
+        };
+
+        loaded.load_params()?;
+
+        assert_eq!(loaded.config, profile.config);
+        assert_eq!(loaded.zone_controls, profile.zone_controls);
+
+        // resetting the last zone control updates the existing state file
+        profile.zone_controls.clear();
+        profile.save_params()?;
+
+        loaded.load_params()?;
+
+        assert!(loaded.zone_controls.is_empty());
+        assert_eq!(loaded.config, profile.config);
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_profile_state_falls_back_to_the_previous_generation() -> super::Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("eruption-profile-state-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let profile_file = dir.join("test.profile");
+        fs::write(&profile_file, "")?;
+
+        let mut profile = Profile {
+            profile_file: profile_file.clone(),
+            ..Default::default()
+        };
+
+        for opacity in [0.5, 0.75] {
+            profile.config.set_parameter(
+                "Perlin noise",
+                ProfileParameter {
+                    name: "opacity".to_string(),
+                    value: TypedValue::Float(opacity),
+                    manifest: None,
+                },
+            );
+
+            profile.save_params()?;
+        }
+
+        // a crash while writing the state file leaves a truncated file behind
+        let state_path = profile_file.with_extension("profile.state");
+        let contents = fs::read(&state_path)?;
+        fs::write(&state_path, &contents[..contents.len() / 2])?;
+
+        let mut loaded = Profile {
+            profile_file: profile_file.clone(),
+            ..Default::default()
+        };
+
+        loaded.load_params()?;
+
+        assert_eq!(
+            loaded
+                .config
+                .get_parameter("Perlin noise", "opacity")
+                .map(|p| p.value.clone()),
+            Some(TypedValue::Float(0.5))
+        );
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
 }
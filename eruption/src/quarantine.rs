@@ -0,0 +1,262 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::health::HealthEvent;
+use crate::hwdevices::DeviceTrait;
+use crate::state::DeviceMakeModelSerial;
+use crate::{constants, device_id};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Quarantine state of the devices that failed recently, indexed by their stable identifier
+    static ref QUARANTINE: Arc<Mutex<HashMap<DeviceMakeModelSerial, QuarantineState>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+
+    static ref POLICY: Arc<RwLock<QuarantinePolicy>> =
+        Arc::new(RwLock::new(QuarantinePolicy::default()));
+}
+
+/// Quarantine state of a device, as returned by `GetDeviceHealth`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantineStatus {
+    /// Number of retries that failed so far
+    pub failed_retries: u32,
+
+    /// Seconds until the device is retried, zero if a retry is in progress
+    pub retry_in_secs: u64,
+}
+
+/// Decides when a device is parked, and how often it is retried
+#[derive(Debug, Clone, Copy)]
+struct QuarantinePolicy {
+    /// Number of failures within `window` after which a device is parked, 0 disables the quarantine
+    max_failures: u64,
+    window: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for QuarantinePolicy {
+    fn default() -> Self {
+        Self {
+            max_failures: constants::QUARANTINE_MAX_FAILURES,
+            window: Duration::from_secs(constants::QUARANTINE_WINDOW_SECS),
+            initial_backoff: Duration::from_secs(constants::QUARANTINE_INITIAL_BACKOFF_SECS),
+            max_backoff: Duration::from_secs(constants::QUARANTINE_MAX_BACKOFF_SECS),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct QuarantineState {
+    /// Points in time of the recent failures, within the time window of the policy
+    failures: VecDeque<Instant>,
+
+    /// Set while the device is parked
+    parked: Option<Parked>,
+}
+
+#[derive(Debug)]
+struct Parked {
+    retry_at: Instant,
+    backoff: Duration,
+    failed_retries: u32,
+
+    /// Set while the device is being retried
+    retrying: bool,
+}
+
+/// Record a failure of the device `id`. The device is parked if it failed too often within
+/// the time window of the policy. A failed retry parks the device again, with twice the delay
+pub fn record_failure(id: &DeviceMakeModelSerial, event: HealthEvent) {
+    let policy = *POLICY.read();
+
+    if policy.max_failures == 0 {
+        return;
+    }
+
+    let now = Instant::now();
+
+    let mut quarantine = QUARANTINE.lock();
+    let state = quarantine.entry(id.clone()).or_default();
+
+    match &mut state.parked {
+        // the re-initialization of the device is part of the retry itself
+        Some(parked) if parked.retrying && event != HealthEvent::ReinitAttempt => {
+            parked.retrying = false;
+            parked.failed_retries += 1;
+            parked.backoff = (parked.backoff * 2).min(policy.max_backoff);
+            parked.retry_at = now + parked.backoff;
+
+            warn!(
+                "Retry of quarantined device {} failed, retrying again in {} seconds",
+                id,
+                parked.backoff.as_secs()
+            );
+        }
+
+        Some(_parked) => {}
+
+        None => {
+            state.failures.push_back(now);
+
+            while state
+                .failures
+                .front()
+                .is_some_and(|t| now.duration_since(*t) > policy.window)
+            {
+                state.failures.pop_front();
+            }
+
+            if state.failures.len() as u64 >= policy.max_failures {
+                warn!(
+                    "Device {} failed {} times within {} seconds, it has been quarantined, retrying in {} seconds",
+                    id,
+                    state.failures.len(),
+                    policy.window.as_secs(),
+                    policy.initial_backoff.as_secs()
+                );
+
+                state.failures.clear();
+                state.parked = Some(Parked {
+                    retry_at: now + policy.initial_backoff,
+                    backoff: policy.initial_backoff,
+                    failed_retries: 0,
+                    retrying: false,
+                });
+            }
+        }
+    }
+}
+
+/// Returns `true` if `device` is parked and shall be skipped. Once the delay has
+/// expired, the device is let through once, to retry it
+pub fn is_parked(device: &dyn DeviceTrait) -> bool {
+    let mut quarantine = QUARANTINE.lock();
+
+    if quarantine.is_empty() {
+        return false;
+    }
+
+    let id = device_id::device_id(device);
+
+    match quarantine
+        .get_mut(&id)
+        .and_then(|state| state.parked.as_mut())
+    {
+        Some(parked) if parked.retrying => false,
+
+        Some(parked) if Instant::now() >= parked.retry_at => {
+            info!("Retrying quarantined device {}", id);
+
+            parked.retrying = true;
+
+            false
+        }
+
+        Some(_parked) => true,
+
+        None => false,
+    }
+}
+
+/// Record that a frame has been sent to `device` successfully, a device that is
+/// being retried is released from quarantine
+pub fn record_success(device: &dyn DeviceTrait) {
+    let mut quarantine = QUARANTINE.lock();
+
+    if quarantine.is_empty() {
+        return;
+    }
+
+    let id = device_id::device_id(device);
+
+    if let Some(state) = quarantine.get_mut(&id) {
+        if state.parked.as_ref().is_some_and(|parked| parked.retrying) {
+            info!(
+                "Device {} recovered, it has been released from quarantine",
+                id
+            );
+
+            state.parked = None;
+        }
+    }
+}
+
+/// Returns the quarantine state of the device `id`, or `None` if it is not parked
+pub fn get_status(id: &DeviceMakeModelSerial) -> Option<QuarantineStatus> {
+    let now = Instant::now();
+
+    QUARANTINE
+        .lock()
+        .get(id)
+        .and_then(|state| state.parked.as_ref())
+        .map(|parked| QuarantineStatus {
+            failed_retries: parked.failed_retries,
+            retry_in_secs: if parked.retrying {
+                0
+            } else {
+                parked.retry_at.saturating_duration_since(now).as_secs()
+            },
+        })
+}
+
+/// Release all devices from quarantine, and forget their recent failures
+pub fn release_all() {
+    QUARANTINE.lock().clear();
+}
+
+/// Load the quarantine policy from the global configuration
+pub fn load_policy() -> Result<()> {
+    let config = crate::CONFIG.lock();
+    let config = config.as_ref().unwrap();
+
+    let defaults = QuarantinePolicy::default();
+
+    let secs = |key: &str, default: Duration| {
+        config
+            .get::<u64>(key)
+            .map(Duration::from_secs)
+            .unwrap_or(default)
+    };
+
+    let policy = QuarantinePolicy {
+        max_failures: config
+            .get::<u64>("quarantine.max_failures")
+            .unwrap_or(defaults.max_failures),
+        window: secs("quarantine.window_secs", defaults.window),
+        initial_backoff: secs("quarantine.initial_backoff_secs", defaults.initial_backoff),
+        max_backoff: secs("quarantine.max_backoff_secs", defaults.max_backoff),
+    };
+
+    *POLICY.write() = policy;
+
+    Ok(())
+}
@@ -0,0 +1,244 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Quick actions, common parameterized actions with human readable labels, that are
+//! exposed via D-Bus so that GUIs and launchers may offer a command palette
+
+use log::*;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+
+use crate::plugins::audio;
+use crate::scripting::parameters_util;
+use crate::{afk, constants};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum QuickActionError {
+    #[error("Unknown quick action: {id}")]
+    UnknownAction { id: String },
+
+    #[error("No profile is active")]
+    NoActiveProfile {},
+}
+
+/// An action that may be invoked via `InvokeQuickAction`, identified by a stable id
+/// like `switch-slot/2` or `apply-color-scheme/wallpaper`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuickAction {
+    /// Switch to the slot with the given index, starting at 0
+    SwitchSlot(usize),
+
+    /// Enter or leave AFK mode
+    ToggleAfk,
+
+    /// Apply the named color scheme to the active profile
+    ApplyColorScheme(String),
+
+    /// Reload the active profile, which restarts all of its scripts
+    RestartFailedScripts,
+}
+
+impl QuickAction {
+    pub fn id(&self) -> String {
+        format!("{}", self)
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            QuickAction::SwitchSlot(index) => match crate::SLOT_NAMES.lock().get(*index) {
+                Some(name) => format!("Switch to slot {}: {}", index + 1, name),
+                None => format!("Switch to slot {}", index + 1),
+            },
+
+            QuickAction::ToggleAfk => {
+                if crate::AFK.load(Ordering::SeqCst) {
+                    "Leave away from keyboard mode".to_string()
+                } else {
+                    "Enter away from keyboard mode".to_string()
+                }
+            }
+
+            QuickAction::ApplyColorScheme(name) => format!("Apply color scheme: {}", name),
+
+            QuickAction::RestartFailedScripts => "Restart failed scripts".to_string(),
+        }
+    }
+
+    /// The category of the action, so that GUIs may group them
+    pub fn category(&self) -> &'static str {
+        match self {
+            QuickAction::SwitchSlot(_) => "slots",
+            QuickAction::ToggleAfk => "afk",
+            QuickAction::ApplyColorScheme(_) => "color-schemes",
+            QuickAction::RestartFailedScripts => "scripts",
+        }
+    }
+
+    /// Invoke the action
+    pub fn invoke(&self) -> Result<()> {
+        info!("Invoking quick action: {}", self);
+
+        match self {
+            QuickAction::SwitchSlot(index) => {
+                // reset the audio backend, it will be enabled again if needed
+                audio::reset_audio_backend();
+
+                crate::ACTIVE_SLOT.store(*index, Ordering::SeqCst);
+            }
+
+            QuickAction::ToggleAfk => {
+                afk::toggle();
+            }
+
+            QuickAction::ApplyColorScheme(name) => {
+                let profile_file = crate::ACTIVE_PROFILE
+                    .lock()
+                    .as_ref()
+                    .map(|profile| profile.profile_file.to_string_lossy().to_string())
+                    .ok_or(QuickActionError::NoActiveProfile {})?;
+
+                parameters_util::apply_color_scheme(&profile_file, name, false)?;
+            }
+
+            QuickAction::RestartFailedScripts => {
+                crate::FAILED_TXS.write().clear();
+                crate::REQUEST_PROFILE_RELOAD.store(true, Ordering::SeqCst);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for QuickAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuickAction::SwitchSlot(index) => write!(f, "switch-slot/{}", index + 1),
+            QuickAction::ToggleAfk => write!(f, "toggle-afk"),
+            QuickAction::ApplyColorScheme(name) => write!(f, "apply-color-scheme/{}", name),
+            QuickAction::RestartFailedScripts => write!(f, "restart-failed-scripts"),
+        }
+    }
+}
+
+impl FromStr for QuickAction {
+    type Err = QuickActionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let unknown = || QuickActionError::UnknownAction { id: s.to_string() };
+
+        let (action, arg) = match s.split_once('/') {
+            Some((action, arg)) => (action, Some(arg)),
+            None => (s, None),
+        };
+
+        match (action, arg) {
+            ("switch-slot", Some(slot)) => match slot.parse::<usize>() {
+                Ok(slot) if (1..=constants::NUM_SLOTS).contains(&slot) => {
+                    Ok(QuickAction::SwitchSlot(slot - 1))
+                }
+
+                _ => Err(unknown()),
+            },
+
+            ("toggle-afk", None) => Ok(QuickAction::ToggleAfk),
+
+            ("apply-color-scheme", Some(name)) if !name.is_empty() => {
+                Ok(QuickAction::ApplyColorScheme(name.to_string()))
+            }
+
+            ("restart-failed-scripts", None) => Ok(QuickAction::RestartFailedScripts),
+
+            _ => Err(unknown()),
+        }
+    }
+}
+
+/// Returns all quick actions that are currently available
+pub fn list_actions() -> Vec<QuickAction> {
+    let mut result = (0..constants::NUM_SLOTS)
+        .map(QuickAction::SwitchSlot)
+        .collect::<Vec<_>>();
+
+    result.push(QuickAction::ToggleAfk);
+
+    let mut color_schemes = crate::NAMED_COLOR_SCHEMES
+        .read()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    color_schemes.sort();
+
+    result.extend(color_schemes.into_iter().map(QuickAction::ApplyColorScheme));
+
+    result.push(QuickAction::RestartFailedScripts);
+
+    result
+}
+
+/// Parse and invoke the quick action with the id `id`
+pub fn invoke(id: &str) -> Result<()> {
+    let action = id.parse::<QuickAction>()?;
+
+    action.invoke()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_action_ids() {
+        for action in [
+            QuickAction::SwitchSlot(0),
+            QuickAction::SwitchSlot(3),
+            QuickAction::ToggleAfk,
+            QuickAction::ApplyColorScheme("wallpaper".to_string()),
+            QuickAction::RestartFailedScripts,
+        ] {
+            assert_eq!(action.id().parse::<QuickAction>().unwrap(), action);
+        }
+
+        assert_eq!(
+            "switch-slot/2".parse::<QuickAction>().unwrap(),
+            QuickAction::SwitchSlot(1)
+        );
+    }
+
+    #[test]
+    fn reject_unknown_actions() {
+        for id in [
+            "",
+            "switch-slot",
+            "switch-slot/0",
+            "switch-slot/5",
+            "toggle-afk/1",
+            "apply-color-scheme/",
+            "reboot",
+        ] {
+            assert!(id.parse::<QuickAction>().is_err(), "{}", id);
+        }
+    }
+}
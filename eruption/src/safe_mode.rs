@@ -0,0 +1,246 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Crash-loop detection. Each startup increments a persistent counter, that is reset
+//! after the daemon ran for a while or has been shut down cleanly. If the counter exceeds
+//! the threshold, the daemon boots into safe mode: the failsafe profile is activated and
+//! switching to profiles with user scripts is refused, until safe mode is left via D-Bus
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::constants;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Set while the daemon runs in safe mode
+    static ref ACTIVE: AtomicBool = AtomicBool::new(false);
+
+    /// Set as soon as the daemon ran long enough to be considered stable
+    static ref STABLE: AtomicBool = AtomicBool::new(false);
+
+    static ref STATUS: Arc<RwLock<SafeModeStatus>> =
+        Arc::new(RwLock::new(SafeModeStatus::default()));
+
+    /// Point in time when the crash-loop detection has been started
+    static ref STARTED_AT: Instant = Instant::now();
+}
+
+/// The reason why the daemon runs in safe mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SafeModeReason {
+    /// Requested via the `--safe-mode` command line flag
+    Requested,
+
+    /// The daemon failed to start up too often in a row
+    CrashLoop,
+}
+
+/// Safe mode state, as returned by `GetSafeMode`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafeModeStatus {
+    pub active: bool,
+    pub reason: Option<SafeModeReason>,
+
+    /// Number of consecutive startups that did not complete, including the current one
+    pub failed_startups: u32,
+
+    /// Number of failed startups after which safe mode is entered, 0 disables the detection
+    pub threshold: u32,
+}
+
+fn counter_file() -> PathBuf {
+    PathBuf::from(constants::STATE_DIR).join("startup-failures")
+}
+
+fn read_counter() -> u32 {
+    fs::read_to_string(counter_file())
+        .ok()
+        .and_then(|s| parse_counter(&s))
+        .unwrap_or(0)
+}
+
+fn write_counter(value: u32) -> Result<()> {
+    fs::write(counter_file(), format!("{}\n", value))?;
+
+    Ok(())
+}
+
+fn parse_counter(s: &str) -> Option<u32> {
+    s.trim().parse::<u32>().ok()
+}
+
+/// Returns the reason to enter safe mode, if any
+fn evaluate(requested: bool, failed_startups: u32, threshold: u32) -> Option<SafeModeReason> {
+    if requested {
+        Some(SafeModeReason::Requested)
+    } else if threshold > 0 && failed_startups > threshold {
+        Some(SafeModeReason::CrashLoop)
+    } else {
+        None
+    }
+}
+
+/// Count the current startup, and decide whether the daemon boots into safe mode
+pub fn begin_startup(requested: bool) -> Result<()> {
+    lazy_static::initialize(&STARTED_AT);
+
+    let threshold = crate::CONFIG
+        .lock()
+        .as_ref()
+        .unwrap()
+        .get::<u32>("global.safe_mode_threshold")
+        .unwrap_or(constants::SAFE_MODE_THRESHOLD);
+
+    // the current startup counts as failed until the daemon has been running for a while
+    let failed_startups = read_counter().saturating_add(1);
+
+    let reason = evaluate(requested, failed_startups, threshold);
+
+    match reason {
+        Some(SafeModeReason::Requested) => {
+            warn!("Safe mode has been requested, user scripts are disabled")
+        }
+
+        Some(SafeModeReason::CrashLoop) => warn!(
+            "The daemon failed to start up {} times in a row, entering safe mode now. User scripts are disabled",
+            failed_startups - 1
+        ),
+
+        None => {}
+    }
+
+    ACTIVE.store(reason.is_some(), Ordering::SeqCst);
+
+    *STATUS.write() = SafeModeStatus {
+        active: reason.is_some(),
+        reason,
+        failed_startups,
+        threshold,
+    };
+
+    write_counter(failed_startups)?;
+
+    Ok(())
+}
+
+/// Returns true if the daemon runs in safe mode
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Returns the current safe mode state
+pub fn get_status() -> SafeModeStatus {
+    STATUS.read().clone()
+}
+
+/// Should be called periodically from the main loop, resets the crash-loop counter once the
+/// daemon ran long enough. In safe mode the counter is kept, since the profile that is
+/// running is not the one that failed
+pub fn check_stable() {
+    if is_active() || STABLE.load(Ordering::Relaxed) {
+        return;
+    }
+
+    if STARTED_AT.elapsed().as_secs() >= constants::SAFE_MODE_STABLE_SECS {
+        STABLE.store(true, Ordering::Relaxed);
+
+        debug!("The daemon is running stable, resetting the crash-loop counter");
+
+        reset_counter();
+    }
+}
+
+/// Reset the crash-loop counter, should be called on a clean shutdown
+pub fn reset_counter() {
+    STATUS.write().failed_startups = 0;
+
+    write_counter(0).unwrap_or_else(|e| warn!("Could not reset the crash-loop counter: {}", e));
+}
+
+/// Leave safe mode and switch back to the profile of the active slot.
+/// Returns false if the daemon did not run in safe mode
+pub fn exit() -> bool {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return false;
+    }
+
+    info!("Leaving safe mode now");
+
+    {
+        let mut status = STATUS.write();
+
+        status.active = false;
+        status.reason = None;
+    }
+
+    reset_counter();
+
+    // the main loop switches to the profile
+    let profile_file = crate::SLOT_PROFILES
+        .lock()
+        .as_ref()
+        .and_then(|slot_profiles| {
+            slot_profiles
+                .get(crate::ACTIVE_SLOT.load(Ordering::SeqCst))
+                .cloned()
+        });
+
+    if let Some(profile_file) = profile_file {
+        crate::ACTIVE_PROFILE_NAME
+            .lock()
+            .replace(profile_file.to_string_lossy().to_string());
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enter_safe_mode_after_threshold() {
+        assert_eq!(evaluate(false, 1, 3), None);
+        assert_eq!(evaluate(false, 3, 3), None);
+        assert_eq!(evaluate(false, 4, 3), Some(SafeModeReason::CrashLoop));
+
+        // the detection is disabled, but safe mode may still be requested
+        assert_eq!(evaluate(false, 100, 0), None);
+        assert_eq!(evaluate(true, 1, 0), Some(SafeModeReason::Requested));
+    }
+
+    #[test]
+    fn parse_counter_file() {
+        assert_eq!(parse_counter("2\n"), Some(2));
+        assert_eq!(parse_counter(""), None);
+        assert_eq!(parse_counter("garbage"), None);
+    }
+}
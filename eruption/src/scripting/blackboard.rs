@@ -0,0 +1,408 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! The blackboard, a key-value store that is shared by the scripts of the active profile,
+//! so that they may pass data to each other, e.g. one script detects the beat of the music
+//! and another one renders it. Values are copied on write and on read, so the scripts never
+//! share mutable state. Changes made by other scripts are announced to a script via its
+//! `on_blackboard_changed(key)` event handler
+
+use lazy_static::lazy_static;
+use mlua::prelude::*;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread::{self, ThreadId};
+
+use crate::constants;
+
+lazy_static! {
+    /// The blackboard of the active profile
+    static ref BLACKBOARD: Arc<RwLock<Blackboard>> = Arc::new(RwLock::new(Blackboard::default()));
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum BlackboardError {
+    #[error("Values of type '{type_name}' can not be stored on the blackboard")]
+    UnsupportedType { type_name: String },
+
+    #[error("Tables on the blackboard may not be nested deeper than {max} levels")]
+    NestingTooDeep { max: usize },
+
+    #[error("The blackboard is full, it holds at most {max} keys")]
+    TooManyEntries { max: usize },
+}
+
+/// A value on the blackboard, a copy of a Lua value
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlackboardValue {
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+    Table(Vec<(BlackboardValue, BlackboardValue)>),
+}
+
+impl BlackboardValue {
+    /// Copy the Lua value `value`, returns `None` for `nil`
+    pub fn from_lua(value: LuaValue, depth: usize) -> Result<Option<Self>, BlackboardError> {
+        let result = match value {
+            LuaValue::Nil => None,
+            LuaValue::Boolean(value) => Some(BlackboardValue::Boolean(value)),
+            LuaValue::Integer(value) => Some(BlackboardValue::Integer(value)),
+            LuaValue::Number(value) => Some(BlackboardValue::Number(value)),
+
+            LuaValue::String(value) => {
+                Some(BlackboardValue::String(value.to_string_lossy().to_string()))
+            }
+
+            LuaValue::Table(table) => {
+                if depth >= constants::MAX_BLACKBOARD_TABLE_DEPTH {
+                    return Err(BlackboardError::NestingTooDeep {
+                        max: constants::MAX_BLACKBOARD_TABLE_DEPTH,
+                    });
+                }
+
+                let mut entries = vec![];
+
+                for pair in table.pairs::<LuaValue, LuaValue>() {
+                    let (key, value) = pair.map_err(|_e| BlackboardError::UnsupportedType {
+                        type_name: "table".to_string(),
+                    })?;
+
+                    if let (Some(key), Some(value)) = (
+                        Self::from_lua(key, depth + 1)?,
+                        Self::from_lua(value, depth + 1)?,
+                    ) {
+                        entries.push((key, value));
+                    }
+                }
+
+                Some(BlackboardValue::Table(entries))
+            }
+
+            value => {
+                return Err(BlackboardError::UnsupportedType {
+                    type_name: value.type_name().to_string(),
+                })
+            }
+        };
+
+        Ok(result)
+    }
+
+    /// Create a new Lua value from this value
+    pub fn to_lua<'lua>(&self, lua_ctx: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        let result = match self {
+            BlackboardValue::Boolean(value) => LuaValue::Boolean(*value),
+            BlackboardValue::Integer(value) => LuaValue::Integer(*value),
+            BlackboardValue::Number(value) => LuaValue::Number(*value),
+            BlackboardValue::String(value) => LuaValue::String(lua_ctx.create_string(value)?),
+
+            BlackboardValue::Table(entries) => {
+                let table = lua_ctx.create_table()?;
+
+                for (key, value) in entries {
+                    table.raw_set(key.to_lua(lua_ctx)?, value.to_lua(lua_ctx)?)?;
+                }
+
+                LuaValue::Table(table)
+            }
+        };
+
+        Ok(result)
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// The current value, `None` if the key has been removed
+    value: Option<BlackboardValue>,
+
+    /// The generation of the blackboard when the value has been written
+    generation: u64,
+
+    /// The thread of the Lua VM that has written the value
+    writer: ThreadId,
+}
+
+#[derive(Debug, Default)]
+struct Blackboard {
+    entries: HashMap<String, Entry>,
+
+    /// Incremented on each change, never reset so that the Lua VMs of the
+    /// next profile do not miss any changes
+    generation: u64,
+}
+
+impl Blackboard {
+    fn set(
+        &mut self,
+        key: &str,
+        value: Option<BlackboardValue>,
+        writer: ThreadId,
+    ) -> Result<(), BlackboardError> {
+        let current = self.entries.get(key).and_then(|entry| entry.value.as_ref());
+
+        if current == value.as_ref() {
+            return Ok(());
+        }
+
+        if current.is_none() && self.len() >= constants::MAX_BLACKBOARD_ENTRIES {
+            return Err(BlackboardError::TooManyEntries {
+                max: constants::MAX_BLACKBOARD_ENTRIES,
+            });
+        }
+
+        self.generation += 1;
+
+        let removed = value.is_none();
+
+        // removed keys are kept, so that the other scripts are notified of the removal
+        self.entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                generation: self.generation,
+                writer,
+            },
+        );
+
+        if removed {
+            self.prune_removed();
+        }
+
+        Ok(())
+    }
+
+    /// Forget the oldest removed keys, once more than `MAX_BLACKBOARD_REMOVED_ENTRIES` of them
+    /// are remembered. The scripts have been notified of their removal long ago
+    fn prune_removed(&mut self) {
+        let mut removed = self
+            .entries
+            .iter()
+            .filter(|(_key, entry)| entry.value.is_none())
+            .map(|(key, entry)| (entry.generation, key.clone()))
+            .collect::<Vec<_>>();
+
+        if removed.len() <= constants::MAX_BLACKBOARD_REMOVED_ENTRIES {
+            return;
+        }
+
+        removed.sort();
+
+        let excess = removed.len() - constants::MAX_BLACKBOARD_REMOVED_ENTRIES;
+
+        for (_generation, key) in removed.into_iter().take(excess) {
+            self.entries.remove(&key);
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<BlackboardValue> {
+        self.entries.get(key).and_then(|entry| entry.value.clone())
+    }
+
+    fn len(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| entry.value.is_some())
+            .count()
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let mut result = self
+            .entries
+            .iter()
+            .filter(|(_key, entry)| entry.value.is_some())
+            .map(|(key, _entry)| key.clone())
+            .collect::<Vec<_>>();
+
+        result.sort();
+
+        result
+    }
+
+    /// Returns the keys that have been changed by other threads than `reader`
+    /// after `generation`, in the order of their changes
+    fn changes_since(&self, generation: u64, reader: ThreadId) -> Vec<String> {
+        let mut changes = self
+            .entries
+            .iter()
+            .filter(|(_key, entry)| entry.generation > generation && entry.writer != reader)
+            .map(|(key, entry)| (entry.generation, key.clone()))
+            .collect::<Vec<_>>();
+
+        changes.sort();
+
+        changes.into_iter().map(|(_generation, key)| key).collect()
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Store a copy of `value` under `key`, `None` removes the key
+pub fn set(key: &str, value: Option<BlackboardValue>) -> Result<(), BlackboardError> {
+    BLACKBOARD.write().set(key, value, thread::current().id())
+}
+
+/// Returns a copy of the value stored under `key`
+pub fn get(key: &str) -> Option<BlackboardValue> {
+    BLACKBOARD.read().get(key)
+}
+
+/// Returns the keys that are currently stored on the blackboard, in alphabetical order
+pub fn keys() -> Vec<String> {
+    BLACKBOARD.read().keys()
+}
+
+/// Returns the current generation of the blackboard
+pub fn generation() -> u64 {
+    BLACKBOARD.read().generation
+}
+
+/// Returns the keys that have been changed by the Lua VMs of other threads after
+/// `generation`, along with the current generation
+pub fn changes_since(generation: u64) -> (Vec<String>, u64) {
+    let blackboard = BLACKBOARD.read();
+
+    if blackboard.generation == generation {
+        return (vec![], generation);
+    }
+
+    (
+        blackboard.changes_since(generation, thread::current().id()),
+        blackboard.generation,
+    )
+}
+
+/// Discard all values, should be called when the profile is switched
+pub fn clear() {
+    BLACKBOARD.write().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn writer() -> ThreadId {
+        thread::spawn(|| thread::current().id()).join().unwrap()
+    }
+
+    #[test]
+    fn notify_other_scripts_of_changes() {
+        let mut blackboard = Blackboard::default();
+
+        let reader = thread::current().id();
+        let other = writer();
+
+        blackboard
+            .set("beat", Some(BlackboardValue::Number(0.5)), other)
+            .unwrap();
+        blackboard
+            .set("own", Some(BlackboardValue::Boolean(true)), reader)
+            .unwrap();
+        blackboard
+            .set("hue", Some(BlackboardValue::Integer(120)), other)
+            .unwrap();
+
+        assert_eq!(blackboard.changes_since(0, reader), vec!["beat", "hue"]);
+        assert_eq!(blackboard.changes_since(2, reader), vec!["hue"]);
+
+        // writing the same value again is not a change
+        blackboard
+            .set("hue", Some(BlackboardValue::Integer(120)), other)
+            .unwrap();
+        assert_eq!(blackboard.generation, 3);
+
+        // removals are changes too
+        blackboard.set("beat", None, other).unwrap();
+
+        assert_eq!(blackboard.changes_since(3, reader), vec!["beat"]);
+        assert_eq!(blackboard.get("beat"), None);
+        assert_eq!(blackboard.keys(), vec!["hue", "own"]);
+    }
+
+    #[test]
+    fn removed_keys_are_pruned() {
+        let mut blackboard = Blackboard::default();
+
+        let reader = thread::current().id();
+        let other = writer();
+
+        for i in 0..constants::MAX_BLACKBOARD_REMOVED_ENTRIES + 2 {
+            let key = format!("key-{}", i);
+
+            blackboard
+                .set(&key, Some(BlackboardValue::Integer(i as i64)), other)
+                .unwrap();
+            blackboard.set(&key, None, other).unwrap();
+        }
+
+        assert_eq!(
+            blackboard.entries.len(),
+            constants::MAX_BLACKBOARD_REMOVED_ENTRIES
+        );
+
+        // the oldest removals are forgotten, the most recent ones are still announced
+        assert!(!blackboard.entries.contains_key("key-0"));
+        assert!(!blackboard.entries.contains_key("key-1"));
+        assert_eq!(
+            blackboard.changes_since(blackboard.generation - 1, reader),
+            vec![format!(
+                "key-{}",
+                constants::MAX_BLACKBOARD_REMOVED_ENTRIES + 1
+            )]
+        );
+    }
+
+    #[test]
+    fn copy_lua_values() -> LuaResult<()> {
+        let lua_ctx = Lua::new();
+
+        let value = lua_ctx
+            .load(r#"{ 1, 2.5, "three", nested = { flag = true } }"#)
+            .eval::<LuaValue>()?;
+
+        let copy = BlackboardValue::from_lua(value, 0).unwrap().unwrap();
+
+        lua_ctx.globals().set("copy", copy.to_lua(&lua_ctx)?)?;
+
+        assert!(lua_ctx
+            .load(
+                r#"copy[1] == 1 and copy[2] == 2.5 and copy[3] == "three" and copy.nested.flag == true"#
+            )
+            .eval::<bool>()?);
+
+        let function = lua_ctx.create_function(|_, ()| Ok(()))?;
+
+        assert!(BlackboardValue::from_lua(LuaValue::Function(function), 0).is_err());
+
+        let cyclic = lua_ctx
+            .load("local t = {} t.t = t return t")
+            .eval::<LuaValue>()?;
+
+        assert!(BlackboardValue::from_lua(cyclic, 0).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use mlua::prelude::*;
+use mlua::{ChunkMode, Function};
+use parking_lot::RwLock;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Precompiled Lua chunks, indexed by the hash of the script source
+    static ref BYTECODE_CACHE: Arc<RwLock<HashMap<u64, Arc<Vec<u8>>>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+
+    /// Hash of the cached source of each script file, used for the invalidation of stale chunks
+    static ref CACHED_FILES: Arc<RwLock<HashMap<PathBuf, u64>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+}
+
+fn hash_source(script: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    script.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// Load the script `script_file` with the source `script` into the Lua VM `lua_ctx`.
+/// Uses the precompiled chunk if the source has been compiled before, otherwise
+/// the source is compiled and the resulting bytecode is added to the cache
+pub fn load_script<'lua>(
+    lua_ctx: &'lua Lua,
+    script_file: &Path,
+    script: &str,
+) -> LuaResult<Function<'lua>> {
+    let hash = hash_source(script);
+
+    let bytecode = BYTECODE_CACHE.read().get(&hash).cloned();
+
+    if let Some(bytecode) = bytecode {
+        trace!("Using precompiled chunk of {}", script_file.display());
+
+        match lua_ctx
+            .load(bytecode.as_slice())
+            .set_mode(ChunkMode::Binary)
+            .into_function()
+        {
+            Ok(function) => return Ok(function),
+
+            // fall back to compiling the source
+            Err(e) => warn!(
+                "Could not load the precompiled chunk of {}: {}",
+                script_file.display(),
+                e
+            ),
+        }
+    }
+
+    let function = lua_ctx
+        .load(script)
+        .set_mode(ChunkMode::Text)
+        .into_function()?;
+
+    BYTECODE_CACHE
+        .write()
+        .insert(hash, Arc::new(function.dump(false)));
+
+    // evict the chunk of the previous revision of the script
+    if let Some(previous) = CACHED_FILES.write().insert(script_file.to_path_buf(), hash) {
+        if previous != hash {
+            BYTECODE_CACHE.write().remove(&previous);
+        }
+    }
+
+    Ok(function)
+}
+
+/// Evict the precompiled chunk of the script `script_file`, e.g. because the file
+/// has been modified or removed
+pub fn invalidate(script_file: &Path) {
+    if let Some(hash) = CACHED_FILES.write().remove(script_file) {
+        debug!(
+            "Evicting the precompiled chunk of {}",
+            script_file.display()
+        );
+
+        BYTECODE_CACHE.write().remove(&hash);
+    }
+}
+
+/// Compile the script `script_file` ahead of time and add the resulting bytecode to
+/// the cache, so that the Lua VMs do not have to compile it when they are started
+pub fn precompile(script_file: &Path) -> Result<()> {
+    let script = fs::read_to_string(script_file)?;
+
+    // a bare Lua VM suffices, since the chunk is only compiled but not run
+    let lua_ctx = Lua::new();
+    load_script(&lua_ctx, script_file, &script)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_chunks_are_evicted() {
+        let lua_ctx = Lua::new();
+        let script_file = Path::new("/tmp/eruption-bytecode-cache-test.lua");
+
+        let source = "return 42 -- revision 1";
+        let function = load_script(&lua_ctx, script_file, source).unwrap();
+        assert_eq!(function.call::<_, i32>(()).unwrap(), 42);
+        assert!(BYTECODE_CACHE.read().contains_key(&hash_source(source)));
+
+        // the precompiled chunk is used the second time
+        let function = load_script(&lua_ctx, script_file, source).unwrap();
+        assert_eq!(function.call::<_, i32>(()).unwrap(), 42);
+
+        // a new revision of the script replaces the chunk of the previous one
+        let revision = "return 43 -- revision 2";
+        let function = load_script(&lua_ctx, script_file, revision).unwrap();
+        assert_eq!(function.call::<_, i32>(()).unwrap(), 43);
+        assert!(!BYTECODE_CACHE.read().contains_key(&hash_source(source)));
+        assert!(BYTECODE_CACHE.read().contains_key(&hash_source(revision)));
+
+        invalidate(script_file);
+        assert!(!BYTECODE_CACHE.read().contains_key(&hash_source(revision)));
+        assert!(!CACHED_FILES.read().contains_key(script_file));
+    }
+}
@@ -27,25 +27,30 @@ use noise::NoiseFn;
 use palette::convert::FromColor;
 use palette::{Hsl, Srgb};
 use rand::Rng;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt::Write;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::vec::Vec;
 use std::{cell::RefCell, thread};
 
 use crate::{
-    constants,
+    animation::{self, Animation, Easing, Keyframe, Playback, Timeline, Tween},
+    aux_leds, constants, glyphs, headsets,
     hwdevices::RGBA,
-    plugin_manager,
+    key_geometry, plugin_manager,
     plugins::macros,
     script::ScriptingError,
     script::{
-        ALLOCATED_GRADIENTS, FRAME_GENERATION_COUNTER, LED_MAP, LOCAL_LED_MAP,
+        ALLOCATED_GRADIENTS, ANIMATIONS, BOUND_GRADIENTS, DISPLAY_FRAMEBUFFER,
+        DISPLAY_FRAMEBUFFER_MODIFIED, FRAME_GENERATION_COUNTER, LED_MAP, LOCAL_LED_MAP,
         LOCAL_LED_MAP_MODIFIED,
     },
+    scripting::blackboard::{self, BlackboardValue},
     scripting::callbacks,
+    text_entry, zone_controls, zones,
 };
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -57,6 +62,9 @@ pub enum CallbacksError {
 
     #[error("Could not parse param value")]
     ParseParamError {},
+
+    #[error("Invalid animation: {description}")]
+    InvalidAnimation { description: String },
 }
 
 fn seed() -> u32 {
@@ -179,7 +187,7 @@ pub(crate) fn stringify(mut string: &mut String, value: mlua::Value) -> Option<(
 
 /// Returns the target framerate
 pub(crate) fn get_target_fps() -> u64 {
-    constants::TARGET_FPS
+    crate::frame_budget::get_target_fps()
 }
 
 /// Returns the Lua support scripts for all connected devices
@@ -264,6 +272,16 @@ pub(crate) fn inject_mouse_wheel(direction: u32) {
         .unwrap();
 }
 
+/// Type the UTF-8 string `text` on the eruption virtual keyboard.
+pub(crate) fn type_text(text: &str) {
+    // calling type_text(..) from Lua will drop the current input;
+    // the original key event from the hardware keyboard will not be
+    // mirrored on the virtual keyboard.
+    macros::DROP_CURRENT_KEY.store(true, Ordering::SeqCst);
+
+    text_entry::type_text(text);
+}
+
 /// Inject a key on the eruption virtual keyboard after sleeping for `millis` milliseconds.
 pub(crate) fn inject_key_with_delay(ev_key: u32, down: bool, millis: u64) {
     // calling inject_key(..) from Lua will drop the current input;
@@ -447,6 +465,8 @@ pub(crate) fn gradient_from_name(val: &str) -> Result<usize> {
             let idx = m.len() + 1;
 
             let gradient = if let Some(color_scheme) = crate::NAMED_COLOR_SCHEMES.read().get(val) {
+                bind_gradient(idx, val);
+
                 colorgrad::CustomGradient::new()
                     // start at index 1, ignore the darkest/black part of the palette
                     .colors(&color_scheme.colors)
@@ -473,6 +493,7 @@ pub(crate) fn gradient_from_name(val: &str) -> Result<usize> {
                         .build()?;
 
                     m.insert(idx, gradient);
+                    bind_gradient(idx, val);
 
                     Ok(idx)
                 })
@@ -491,6 +512,8 @@ pub(crate) fn gradient_destroy(handle: usize) -> Result<()> {
         let mut m = f.borrow_mut();
 
         if m.remove(&handle).is_some() {
+            BOUND_GRADIENTS.with(|f| f.borrow_mut().remove(&handle));
+
             Ok(())
         } else {
             Err(CallbacksError::InvalidHandle {}.into())
@@ -498,8 +521,44 @@ pub(crate) fn gradient_destroy(handle: usize) -> Result<()> {
     })
 }
 
+/// Remember that the gradient `handle` has been created from the named color scheme
+/// `name`, so that it follows subsequent changes of the color scheme
+fn bind_gradient(handle: usize, name: &str) {
+    let generation = color_transitions::GENERATION.load(Ordering::SeqCst);
+
+    BOUND_GRADIENTS.with(|f| f.borrow_mut().insert(handle, (name.to_owned(), generation)));
+}
+
+/// Rebuild the gradient `handle` if the named color scheme that it has been created
+/// from changed in the meantime, e.g. while the color scheme is transitioning
+fn refresh_gradient(handle: usize) -> Result<()> {
+    let generation = color_transitions::GENERATION.load(Ordering::SeqCst);
+
+    BOUND_GRADIENTS.with(|f| {
+        let mut bindings = f.borrow_mut();
+
+        if let Some((name, bound_generation)) = bindings.get_mut(&handle) {
+            if *bound_generation != generation {
+                if let Some(color_scheme) = crate::NAMED_COLOR_SCHEMES.read().get(name) {
+                    let gradient = colorgrad::CustomGradient::new()
+                        .colors(&color_scheme.colors)
+                        .build()?;
+
+                    ALLOCATED_GRADIENTS.with(|f| f.borrow_mut().insert(handle, gradient));
+                }
+
+                *bound_generation = generation;
+            }
+        }
+
+        Ok(())
+    })
+}
+
 /// Returns the color at the position `pos`
 pub(crate) fn gradient_color_at(handle: usize, pos: f64) -> Result<u32> {
+    refresh_gradient(handle)?;
+
     ALLOCATED_GRADIENTS.with(|f| {
         let m = f.borrow();
 
@@ -749,6 +808,350 @@ pub(crate) fn set_brightness(val: isize) {
     FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
 }
 
+/// Returns the width of the auxiliary display surface
+pub(crate) fn get_display_width() -> usize {
+    constants::DISPLAY_WIDTH
+}
+
+/// Returns the height of the auxiliary display surface
+pub(crate) fn get_display_height() -> usize {
+    constants::DISPLAY_HEIGHT
+}
+
+/// Returns `true` if at least one of the connected devices features an LCD/OLED screen
+pub(crate) fn has_display() -> bool {
+    crate::KEYBOARD_DEVICES
+        .read()
+        .iter()
+        .any(|device| device.read().as_display_device().is_some())
+        || crate::MOUSE_DEVICES
+            .read()
+            .iter()
+            .any(|device| device.read().as_display_device().is_some())
+        || crate::MISC_DEVICES
+            .read()
+            .iter()
+            .any(|device| device.read().as_display_device().is_some())
+}
+
+fn display_color(c: u32) -> RGBA {
+    let (r, g, b, a) = color_to_rgba(c);
+
+    RGBA { r, g, b, a }
+}
+
+fn display_put_pixel(framebuffer: &mut [RGBA], x: i64, y: i64, color: RGBA) {
+    if (0..constants::DISPLAY_WIDTH as i64).contains(&x)
+        && (0..constants::DISPLAY_HEIGHT as i64).contains(&y)
+    {
+        framebuffer[y as usize * constants::DISPLAY_WIDTH + x as usize] = color;
+    }
+}
+
+/// Fill the whole display surface with the color `color`
+pub(crate) fn display_clear(color: u32) {
+    DISPLAY_FRAMEBUFFER.write().fill(display_color(color));
+}
+
+/// Set the pixel at `x`, `y` of the display surface to the color `color`
+pub(crate) fn display_set_pixel(x: i64, y: i64, color: u32) {
+    display_put_pixel(&mut DISPLAY_FRAMEBUFFER.write(), x, y, display_color(color));
+}
+
+/// Fill the rectangle at `x`, `y` with the size `width` x `height` on the display surface
+pub(crate) fn display_fill_rect(x: i64, y: i64, width: i64, height: i64, color: u32) {
+    let color = display_color(color);
+    let mut framebuffer = DISPLAY_FRAMEBUFFER.write();
+
+    // only the visible part of the rectangle is filled
+    let (x0, x1) = (
+        x.clamp(0, constants::DISPLAY_WIDTH as i64),
+        x.saturating_add(width.max(0))
+            .clamp(0, constants::DISPLAY_WIDTH as i64),
+    );
+    let (y0, y1) = (
+        y.clamp(0, constants::DISPLAY_HEIGHT as i64),
+        y.saturating_add(height.max(0))
+            .clamp(0, constants::DISPLAY_HEIGHT as i64),
+    );
+
+    for py in y0..y1 {
+        for px in x0..x1 {
+            display_put_pixel(&mut framebuffer, px, py, color);
+        }
+    }
+}
+
+/// Clip the line from `p0` to `p1` to the display surface (Liang-Barsky), returns `None`
+/// if the line is not visible at all. Coordinates are limited to the range of an `i32`,
+/// so that the intersections can be computed precisely
+fn clip_line(p0: (i64, i64), p1: (i64, i64)) -> Option<((i64, i64), (i64, i64))> {
+    let range = i32::MIN as i64..=i32::MAX as i64;
+
+    if ![p0.0, p0.1, p1.0, p1.1].iter().all(|c| range.contains(c)) {
+        return None;
+    }
+
+    let (x0, y0) = (p0.0 as f64, p0.1 as f64);
+    let (dx, dy) = (p1.0 as f64 - x0, p1.1 as f64 - y0);
+
+    let (x_max, y_max) = (
+        (constants::DISPLAY_WIDTH - 1) as f64,
+        (constants::DISPLAY_HEIGHT - 1) as f64,
+    );
+
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+
+    for (p, q) in [(-dx, x0), (dx, x_max - x0), (-dy, y0), (dy, y_max - y0)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+
+    if t0 > t1 {
+        return None;
+    }
+
+    let point = |t: f64| ((x0 + t * dx).round() as i64, (y0 + t * dy).round() as i64);
+
+    Some((point(t0), point(t1)))
+}
+
+/// Draw a line from `x0`, `y0` to `x1`, `y1` on the display surface
+pub(crate) fn display_draw_line(x0: i64, y0: i64, x1: i64, y1: i64, color: u32) {
+    let color = display_color(color);
+    let mut framebuffer = DISPLAY_FRAMEBUFFER.write();
+
+    // only the visible part of the line is drawn
+    let ((x0, y0), (x1, y1)) = match clip_line((x0, y0), (x1, y1)) {
+        Some(line) => line,
+        None => return,
+    };
+
+    // Bresenham's line algorithm
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+
+    let (mut x, mut y) = (x0, y0);
+    let mut err = dx + dy;
+
+    loop {
+        display_put_pixel(&mut framebuffer, x, y, color);
+
+        if x == x1 && y == y1 {
+            break;
+        }
+
+        let e2 = 2 * err;
+
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Draw the text `text` at `x`, `y` on the display surface, using the built-in font
+pub(crate) fn display_draw_text(x: i64, y: i64, text: &str, color: u32) {
+    let color = display_color(color);
+    let bitmap = glyphs::rasterize(text);
+    let mut framebuffer = DISPLAY_FRAMEBUFFER.write();
+
+    for py in 0..bitmap.height {
+        for px in 0..bitmap.width {
+            if bitmap.get(px, py) {
+                display_put_pixel(&mut framebuffer, x + px as i64, y + py as i64, color);
+            }
+        }
+    }
+}
+
+/// Submit the display surface for later realization, as soon as the
+/// next frame is rendered
+pub(crate) fn display_submit() {
+    DISPLAY_FRAMEBUFFER_MODIFIED.store(true, Ordering::SeqCst);
+    FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Returns the width in pixels of the text `text`, when rendered with the built-in font
+pub(crate) fn text_width(text: &str) -> usize {
+    glyphs::text_width(text)
+}
+
+/// Rasterize the text `text` using the built-in font. Returns the width and the height
+/// of the resulting bitmap, and the pixels stored row by row
+pub(crate) fn rasterize_text(text: &str) -> (usize, usize, Vec<bool>) {
+    let bitmap = glyphs::rasterize(text);
+
+    (bitmap.width, bitmap.height, bitmap.pixels)
+}
+
+/// Returns the label of the key `key_index`, as defined by the active profile
+pub(crate) fn get_key_label(key_index: u8) -> Option<String> {
+    glyphs::get_key_label(key_index).map(|label| label.label)
+}
+
+/// Returns the color of the label of the key `key_index`, as defined by the active profile
+pub(crate) fn get_key_label_color(key_index: u8) -> Option<u32> {
+    glyphs::get_key_label(key_index).and_then(|label| label.color)
+}
+
+/// Returns all key labels of the active profile, indexed by key index
+pub(crate) fn get_key_labels() -> BTreeMap<u8, String> {
+    glyphs::KEY_LABELS
+        .read()
+        .iter()
+        .map(|(key, label)| (*key, label.label.clone()))
+        .collect()
+}
+
+/// Returns the key indices of the zone `name`, like e.g. "wasd" or "numpad"
+pub(crate) fn get_zone(name: &str) -> Option<Vec<usize>> {
+    zones::get_zone(name)
+}
+
+/// Returns the names of all zones that are available for the connected devices
+pub(crate) fn get_zone_names() -> Vec<String> {
+    zones::get_zones()
+        .into_iter()
+        .map(|zone| zone.name)
+        .collect()
+}
+
+/// Returns the factor that scales the speed of the animations on the key `key_index`
+pub(crate) fn get_key_speed(key_index: usize) -> f64 {
+    zone_controls::get_key_speed(key_index)
+}
+
+/// Returns the physical position of the center of the key `key_index` in millimeters
+pub(crate) fn get_key_position(key_index: usize) -> Option<(f32, f32)> {
+    key_geometry::get_key_position(key_index)
+}
+
+/// Store a copy of `value` on the blackboard of the active profile, `nil` removes the key
+pub(crate) fn blackboard_set(key: &str, value: LuaValue) -> mlua::Result<()> {
+    let value =
+        BlackboardValue::from_lua(value, 0).map_err(|e| LuaError::ExternalError(Arc::new(e)))?;
+
+    blackboard::set(key, value).map_err(|e| LuaError::ExternalError(Arc::new(e)))
+}
+
+/// Returns a copy of the value stored on the blackboard of the active profile under `key`
+pub(crate) fn blackboard_get<'lua>(lua_ctx: &'lua Lua, key: &str) -> mlua::Result<LuaValue<'lua>> {
+    match blackboard::get(key) {
+        Some(value) => value.to_lua(lua_ctx),
+        None => Ok(LuaValue::Nil),
+    }
+}
+
+/// Returns the names of the auxiliary LEDs of all connected devices, like e.g. "logo"
+pub(crate) fn get_aux_led_names() -> Vec<String> {
+    aux_leds::get_aux_led_names()
+}
+
+/// Set the auxiliary LED `name` of all devices that feature it, `color` is given as 0xRRGGBB
+pub(crate) fn set_aux_led(name: &str, color: u32) -> bool {
+    match aux_leds::set_aux_led_all(name, color) {
+        Ok(_) => true,
+
+        Err(e) => {
+            debug!("Could not set the auxiliary LED '{}': {}", name, e);
+            false
+        }
+    }
+}
+
+/// Returns the battery level of the first connected headset in percent, or `None` if unknown
+pub(crate) fn get_headset_battery_level() -> Option<u8> {
+    headsets::get_battery_level()
+}
+
+/// Returns the chat mix of the first connected headset in percent, or `None` if unknown
+pub(crate) fn get_headset_chat_mix() -> Option<u8> {
+    headsets::get_chat_mix()
+}
+
+fn invalid_animation(e: eyre::Error) -> LuaError {
+    LuaError::ExternalError(Arc::new(CallbacksError::InvalidAnimation {
+        description: format!("{}", e),
+    }))
+}
+
+/// Parse the optional names of an easing curve and of a playback mode
+fn parse_animation_opts(
+    easing: Option<String>,
+    playback: Option<String>,
+) -> animation::Result<(Easing, Playback)> {
+    let easing = easing.map(|e| e.parse()).transpose()?.unwrap_or_default();
+    let playback = playback.map(|p| p.parse()).transpose()?.unwrap_or_default();
+
+    Ok((easing, playback))
+}
+
+/// Start animating the global variable `param`, replacing a running animation of it
+pub(crate) fn animate(lua_ctx: &Lua, param: String, animation: Animation) -> mlua::Result<()> {
+    lua_ctx
+        .globals()
+        .set(param.as_str(), animation.value_at(Duration::ZERO))?;
+
+    ANIMATIONS.with(|animations| {
+        animations
+            .borrow_mut()
+            .insert(param, (animation, Instant::now()))
+    });
+
+    Ok(())
+}
+
+/// Stop the animation of the global variable `param`, its current value is kept
+pub(crate) fn stop_animation(param: &str) -> bool {
+    ANIMATIONS.with(|animations| animations.borrow_mut().remove(param).is_some())
+}
+
+/// Returns `true` if the global variable `param` is currently being animated
+pub(crate) fn is_animating(param: &str) -> bool {
+    ANIMATIONS.with(|animations| animations.borrow().contains_key(param))
+}
+
+/// Assign the current values of all running animations to the global variables they
+/// animate. Finished animations are removed, after their final value has been assigned
+pub(crate) fn update_animations(lua_ctx: &Lua) -> mlua::Result<()> {
+    ANIMATIONS.with(|animations| {
+        let mut animations = animations.borrow_mut();
+
+        if animations.is_empty() {
+            return Ok(());
+        }
+
+        let globals = lua_ctx.globals();
+
+        for (param, (animation, start)) in animations.iter() {
+            globals.set(param.as_str(), animation.value_at(start.elapsed()))?;
+        }
+
+        animations.retain(|_param, (animation, start)| !animation.is_finished(start.elapsed()));
+
+        Ok(())
+    })
+}
+
 pub fn register_support_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
     let globals = lua_ctx.globals();
 
@@ -881,6 +1284,68 @@ pub fn register_support_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
     })?;
     globals.set("range", range)?;
 
+    // animations
+    let ease = lua_ctx.create_function(|_, (easing, t): (String, f64)| {
+        let easing = easing.parse::<Easing>().map_err(invalid_animation)?;
+
+        Ok(easing.apply(t))
+    })?;
+    globals.set("ease", ease)?;
+
+    let animate = lua_ctx.create_function(
+        |lua_ctx,
+         (param, from, to, duration, easing, playback): (
+            String,
+            f64,
+            f64,
+            u64,
+            Option<String>,
+            Option<String>,
+        )| {
+            let (easing, playback) =
+                parse_animation_opts(easing, playback).map_err(invalid_animation)?;
+
+            let tween = Tween::new(from, to, Duration::from_millis(duration), easing)
+                .with_playback(playback);
+
+            callbacks::animate(lua_ctx, param, Animation::Tween(tween))
+        },
+    )?;
+    globals.set("animate", animate)?;
+
+    let animate_keyframes = lua_ctx.create_function(
+        |lua_ctx, (param, keyframes, playback): (String, Vec<LuaTable>, Option<String>)| {
+            let (_, playback) = parse_animation_opts(None, playback).map_err(invalid_animation)?;
+
+            let keyframes = keyframes
+                .iter()
+                .map(|keyframe| {
+                    let (easing, _) = parse_animation_opts(keyframe.get("easing")?, None)
+                        .map_err(invalid_animation)?;
+
+                    Ok(Keyframe {
+                        offset: Duration::from_millis(keyframe.get("time")?),
+                        value: keyframe.get("value")?,
+                        easing,
+                    })
+                })
+                .collect::<mlua::Result<Vec<_>>>()?;
+
+            let timeline = Timeline::new(keyframes, playback).map_err(invalid_animation)?;
+
+            callbacks::animate(lua_ctx, param, Animation::Timeline(timeline))
+        },
+    )?;
+    globals.set("animate_keyframes", animate_keyframes)?;
+
+    let stop_animation =
+        lua_ctx.create_function(|_, param: String| Ok(callbacks::stop_animation(&param)))?;
+    globals.set("stop_animation", stop_animation)?;
+
+    let is_animating =
+        lua_ctx.create_function(|_, param: String| Ok(callbacks::is_animating(&param)))?;
+    globals.set("is_animating", is_animating)?;
+
     // keyboard state and macros
     let inject_key = lua_ctx.create_function(|_, (ev_key, down): (u32, bool)| {
         callbacks::inject_key(ev_key, down);
@@ -895,6 +1360,12 @@ pub fn register_support_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
         })?;
     globals.set("inject_key_with_delay", inject_key_with_delay)?;
 
+    let type_text = lua_ctx.create_function(|_, text: String| {
+        callbacks::type_text(&text);
+        Ok(())
+    })?;
+    globals.set("type_text", type_text)?;
+
     // mouse state and macros
     let inject_mouse_button = lua_ctx.create_function(|_, (button_index, down): (u32, bool)| {
         callbacks::inject_mouse_button(button_index, down);
@@ -1066,6 +1537,124 @@ pub fn register_support_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
     })?;
     globals.set("set_brightness", set_brightness)?;
 
+    // display related
+    let get_display_width = lua_ctx.create_function(|_, ()| Ok(callbacks::get_display_width()))?;
+    globals.set("get_display_width", get_display_width)?;
+
+    let get_display_height =
+        lua_ctx.create_function(|_, ()| Ok(callbacks::get_display_height()))?;
+    globals.set("get_display_height", get_display_height)?;
+
+    let has_display = lua_ctx.create_function(|_, ()| Ok(callbacks::has_display()))?;
+    globals.set("has_display", has_display)?;
+
+    let display_clear = lua_ctx.create_function(|_, color: u32| {
+        callbacks::display_clear(color);
+        Ok(())
+    })?;
+    globals.set("display_clear", display_clear)?;
+
+    let display_set_pixel = lua_ctx.create_function(|_, (x, y, color): (i64, i64, u32)| {
+        callbacks::display_set_pixel(x, y, color);
+        Ok(())
+    })?;
+    globals.set("display_set_pixel", display_set_pixel)?;
+
+    let display_fill_rect = lua_ctx.create_function(
+        |_, (x, y, width, height, color): (i64, i64, i64, i64, u32)| {
+            callbacks::display_fill_rect(x, y, width, height, color);
+            Ok(())
+        },
+    )?;
+    globals.set("display_fill_rect", display_fill_rect)?;
+
+    let display_draw_line =
+        lua_ctx.create_function(|_, (x0, y0, x1, y1, color): (i64, i64, i64, i64, u32)| {
+            callbacks::display_draw_line(x0, y0, x1, y1, color);
+            Ok(())
+        })?;
+    globals.set("display_draw_line", display_draw_line)?;
+
+    let display_draw_text =
+        lua_ctx.create_function(|_, (x, y, text, color): (i64, i64, String, u32)| {
+            callbacks::display_draw_text(x, y, &text, color);
+            Ok(())
+        })?;
+    globals.set("display_draw_text", display_draw_text)?;
+
+    let display_submit = lua_ctx.create_function(|_, ()| {
+        callbacks::display_submit();
+        Ok(())
+    })?;
+    globals.set("display_submit", display_submit)?;
+
+    // text rendering and key labels
+    let text_width = lua_ctx.create_function(|_, text: String| Ok(callbacks::text_width(&text)))?;
+    globals.set("text_width", text_width)?;
+
+    let rasterize_text =
+        lua_ctx.create_function(|_, text: String| Ok(callbacks::rasterize_text(&text)))?;
+    globals.set("rasterize_text", rasterize_text)?;
+
+    let get_key_label =
+        lua_ctx.create_function(|_, key_index: u8| Ok(callbacks::get_key_label(key_index)))?;
+    globals.set("get_key_label", get_key_label)?;
+
+    let get_key_label_color = lua_ctx
+        .create_function(|_, key_index: u8| Ok(callbacks::get_key_label_color(key_index)))?;
+    globals.set("get_key_label_color", get_key_label_color)?;
+
+    let get_key_labels = lua_ctx.create_function(|_, ()| Ok(callbacks::get_key_labels()))?;
+    globals.set("get_key_labels", get_key_labels)?;
+
+    // zones
+    let get_zone = lua_ctx.create_function(|_, name: String| Ok(callbacks::get_zone(&name)))?;
+    globals.set("get_zone", get_zone)?;
+
+    let get_zone_names = lua_ctx.create_function(|_, ()| Ok(callbacks::get_zone_names()))?;
+    globals.set("get_zone_names", get_zone_names)?;
+
+    let get_key_speed =
+        lua_ctx.create_function(|_, key_index: usize| Ok(callbacks::get_key_speed(key_index)))?;
+    globals.set("get_key_speed", get_key_speed)?;
+
+    // physical key geometry
+    let get_key_position = lua_ctx.create_function(|_, key_index: usize| {
+        Ok(callbacks::get_key_position(key_index).unzip())
+    })?;
+    globals.set("get_key_position", get_key_position)?;
+
+    // auxiliary LEDs
+    let get_aux_led_names = lua_ctx.create_function(|_, ()| Ok(callbacks::get_aux_led_names()))?;
+    globals.set("get_aux_led_names", get_aux_led_names)?;
+
+    let set_aux_led = lua_ctx.create_function(|_, (name, color): (String, u32)| {
+        Ok(callbacks::set_aux_led(&name, color))
+    })?;
+    globals.set("set_aux_led", set_aux_led)?;
+
+    // headsets
+    let get_headset_battery_level =
+        lua_ctx.create_function(|_, ()| Ok(callbacks::get_headset_battery_level()))?;
+    globals.set("get_headset_battery_level", get_headset_battery_level)?;
+
+    let get_headset_chat_mix =
+        lua_ctx.create_function(|_, ()| Ok(callbacks::get_headset_chat_mix()))?;
+    globals.set("get_headset_chat_mix", get_headset_chat_mix)?;
+
+    // blackboard
+    let blackboard_set = lua_ctx.create_function(|_, (key, value): (String, LuaValue)| {
+        callbacks::blackboard_set(&key, value)
+    })?;
+    globals.set("blackboard_set", blackboard_set)?;
+
+    let blackboard_get =
+        lua_ctx.create_function(|lua_ctx, key: String| callbacks::blackboard_get(lua_ctx, &key))?;
+    globals.set("blackboard_get", blackboard_get)?;
+
+    let blackboard_keys = lua_ctx.create_function(|_, ()| Ok(blackboard::keys()))?;
+    globals.set("blackboard_keys", blackboard_keys)?;
+
     // finally, register Lua functions supplied by eruption plugins
     let plugin_manager = plugin_manager::PLUGIN_MANAGER.read();
     let plugins = plugin_manager.get_plugins();
@@ -1076,3 +1665,41 @@ pub fn register_support_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_clipped_to_the_display() {
+        let (w, h) = (
+            constants::DISPLAY_WIDTH as i64,
+            constants::DISPLAY_HEIGHT as i64,
+        );
+
+        // visible lines are not modified
+        assert_eq!(
+            clip_line((0, 0), (w - 1, h - 1)),
+            Some(((0, 0), (w - 1, h - 1)))
+        );
+        assert_eq!(clip_line((3, 2), (3, 2)), Some(((3, 2), (3, 2))));
+
+        assert_eq!(
+            clip_line((-10, 1), (i32::MAX as i64, 1)),
+            Some(((0, 1), (w - 1, 1)))
+        );
+        assert_eq!(
+            clip_line((1, i32::MIN as i64), (1, 1)),
+            Some(((1, 0), (1, 1)))
+        );
+        assert_eq!(
+            clip_line((-1_000_000, -1_000_000), (1_000_000, 1_000_000)),
+            Some(((0, 0), (h - 1, h - 1)))
+        );
+
+        assert_eq!(clip_line((0, 0), (i64::MAX, 0)), None);
+
+        assert_eq!(clip_line((-10, -10), (-1, -1)), None);
+        assert_eq!(clip_line((w, 0), (w + 10, h)), None);
+    }
+}
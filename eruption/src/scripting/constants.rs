@@ -28,9 +28,49 @@ pub const FUNCTION_ON_TICK: &str = "on_tick";
 pub const FUNCTION_ON_APPLY_PARAMETER: &str = "on_apply_parameter";
 pub const FUNCTION_ON_KEY_DOWN: &str = "on_key_down";
 pub const FUNCTION_ON_KEY_UP: &str = "on_key_up";
+pub const FUNCTION_ON_INPUT_FILTER: &str = "on_input_filter";
+pub const FUNCTION_ON_CHORD: &str = "on_chord";
+pub const FUNCTION_ON_ROUTE: &str = "on_route";
+pub const FUNCTION_ON_LOCK_STATE_CHANGED: &str = "on_lock_state_changed";
+pub const FUNCTION_ON_LAYOUT_CHANGED: &str = "on_layout_changed";
 pub const FUNCTION_ON_MOUSE_BUTTON_DOWN: &str = "on_mouse_button_down";
 pub const FUNCTION_ON_MOUSE_BUTTON_UP: &str = "on_mouse_button_up";
 pub const FUNCTION_ON_MOUSE_WHEEL: &str = "on_mouse_wheel";
 pub const FUNCTION_ON_MOUSE_MOVE: &str = "on_mouse_move";
 pub const FUNCTION_ON_HID_EVENT: &str = "on_hid_event";
 pub const FUNCTION_ON_MOUSE_HID_EVENT: &str = "on_mouse_hid_event";
+pub const FUNCTION_ON_HEADSET_EVENT: &str = "on_headset_event";
+pub const FUNCTION_ON_DEVICE_ATTACHED: &str = "on_device_attached";
+pub const FUNCTION_ON_DEVICE_REMOVED: &str = "on_device_removed";
+pub const FUNCTION_ON_WINDOW_FOCUSED: &str = "on_window_focused";
+pub const FUNCTION_ON_LID_CHANGED: &str = "on_lid_changed";
+pub const FUNCTION_ON_DISPLAYS_CHANGED: &str = "on_displays_changed";
+pub const FUNCTION_ON_BLACKBOARD_CHANGED: &str = "on_blackboard_changed";
+
+/// All event handler functions, that a script may define
+pub const HANDLER_FUNCTIONS: &[&str] = &[
+    FUNCTION_ON_STARTUP,
+    FUNCTION_ON_QUIT,
+    FUNCTION_ON_TICK,
+    FUNCTION_ON_APPLY_PARAMETER,
+    FUNCTION_ON_KEY_DOWN,
+    FUNCTION_ON_KEY_UP,
+    FUNCTION_ON_INPUT_FILTER,
+    FUNCTION_ON_CHORD,
+    FUNCTION_ON_ROUTE,
+    FUNCTION_ON_LOCK_STATE_CHANGED,
+    FUNCTION_ON_LAYOUT_CHANGED,
+    FUNCTION_ON_MOUSE_BUTTON_DOWN,
+    FUNCTION_ON_MOUSE_BUTTON_UP,
+    FUNCTION_ON_MOUSE_WHEEL,
+    FUNCTION_ON_MOUSE_MOVE,
+    FUNCTION_ON_HID_EVENT,
+    FUNCTION_ON_MOUSE_HID_EVENT,
+    FUNCTION_ON_HEADSET_EVENT,
+    FUNCTION_ON_DEVICE_ATTACHED,
+    FUNCTION_ON_DEVICE_REMOVED,
+    FUNCTION_ON_WINDOW_FOCUSED,
+    FUNCTION_ON_LID_CHANGED,
+    FUNCTION_ON_DISPLAYS_CHANGED,
+    FUNCTION_ON_BLACKBOARD_CHANGED,
+];
@@ -0,0 +1,524 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Headless rendering of profiles, for visual regression tests of the shipped effects.
+//!
+//! The scripts of a profile are run on Lua VMs of their own, without any devices attached.
+//! A timeline of synthetic events is fed to the VMs, one tick at a time, and the layers that
+//! the VMs submit are composed just like the device I/O thread does. The frames of selected
+//! ticks are captured, and compared against golden images in the PAM format.
+//!
+//! Set the environment variable `ERUPTION_UPDATE_GOLDEN_FRAMES` to (re-)write the golden
+//! images from the captured frames instead of comparing them.
+
+use flume::{unbounded, Sender};
+use lazy_static::lazy_static;
+use parking_lot::{Mutex, MutexGuard};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::constants;
+use crate::hwdevices::RGBA;
+use crate::profiles::Profile;
+use crate::scripting::manifest::Manifest;
+use crate::scripting::native;
+use crate::scripting::script::{self, Message, RunScriptResult};
+use crate::util;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Time to wait for the VMs to submit their layers, this includes the startup of the scripts
+const REALIZE_TIMEOUT_MILLIS: u64 = 5000;
+
+/// The environment variable that requests the golden images to be (re-)written
+const UPDATE_GOLDEN_FRAMES_VAR: &str = "ERUPTION_UPDATE_GOLDEN_FRAMES";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FrameCaptureError {
+    #[error("Script not found in the script directories: {script}")]
+    ScriptNotFound { script: String },
+
+    #[error("The script {script} terminated unexpectedly")]
+    ScriptTerminated { script: String },
+
+    #[error("Timeout while waiting for the layers of tick {tick}")]
+    Timeout { tick: u32 },
+
+    #[error("Invalid image: {description}")]
+    InvalidImage { description: String },
+
+    #[error("The frame does not match the golden image {path}: {mismatched} LEDs differ, the first one at index {first}")]
+    Mismatch {
+        path: String,
+        mismatched: usize,
+        first: usize,
+    },
+}
+
+lazy_static! {
+    /// The layers and the readiness counter are global, so frames may only be captured
+    /// from a single profile at a time
+    static ref HARNESS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// A sequence of synthetic events, keyed by the tick that they are sent before
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    events: BTreeMap<u32, Vec<Message>>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the event `message` to all VMs, right before the tick `tick`
+    pub fn at(mut self, tick: u32, message: Message) -> Self {
+        self.events.entry(tick).or_default().push(message);
+        self
+    }
+}
+
+/// The allowed difference between a captured frame and its golden image
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Maximum absolute difference of each of the channels of an LED
+    pub max_channel_delta: u8,
+
+    /// Number of LEDs that may exceed `max_channel_delta`
+    pub max_mismatched_leds: usize,
+}
+
+impl Tolerance {
+    pub const EXACT: Self = Self {
+        max_channel_delta: 0,
+        max_mismatched_leds: 0,
+    };
+}
+
+/// A composited canvas
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub led_map: Vec<RGBA>,
+}
+
+impl Frame {
+    /// Encode the canvas as a PAM image of `CANVAS_WIDTH` x `CANVAS_HEIGHT` pixels
+    pub fn to_pam(&self) -> Vec<u8> {
+        let mut result = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            constants::CANVAS_WIDTH,
+            constants::CANVAS_HEIGHT
+        )
+        .into_bytes();
+
+        for color in self.led_map.iter() {
+            result.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+
+        result
+    }
+
+    pub fn from_pam(data: &[u8]) -> Result<Self> {
+        let invalid = |description: &str| FrameCaptureError::InvalidImage {
+            description: description.to_string(),
+        };
+
+        const END_OF_HEADER: &[u8] = b"ENDHDR\n";
+
+        let header_len = data
+            .windows(END_OF_HEADER.len())
+            .position(|w| w == END_OF_HEADER)
+            .ok_or_else(|| invalid("missing header"))?
+            + END_OF_HEADER.len();
+
+        let header = std::str::from_utf8(&data[..header_len])?;
+
+        let mut width = 0;
+        let mut height = 0;
+        let mut depth = 0;
+
+        for line in header.lines() {
+            match line.split_once(' ') {
+                Some(("WIDTH", value)) => width = value.trim().parse::<usize>()?,
+                Some(("HEIGHT", value)) => height = value.trim().parse::<usize>()?,
+                Some(("DEPTH", value)) => depth = value.trim().parse::<usize>()?,
+                Some(("MAXVAL", value)) if value.trim() != "255" => {
+                    return Err(invalid("unsupported MAXVAL").into())
+                }
+                _ => (),
+            }
+        }
+
+        if width * height != constants::CANVAS_SIZE || depth != 4 {
+            return Err(invalid("the image does not match the canvas").into());
+        }
+
+        let pixels = &data[header_len..];
+
+        if pixels.len() != constants::CANVAS_SIZE * 4 {
+            return Err(invalid("truncated image").into());
+        }
+
+        let led_map = pixels
+            .chunks_exact(4)
+            .map(|p| RGBA {
+                r: p[0],
+                g: p[1],
+                b: p[2],
+                a: p[3],
+            })
+            .collect();
+
+        Ok(Self { led_map })
+    }
+
+    /// Returns the indices of the LEDs that differ from `other` by more than
+    /// `max_channel_delta` in any of their channels
+    pub fn diff(&self, other: &Frame, max_channel_delta: u8) -> Vec<usize> {
+        self.led_map
+            .iter()
+            .zip(other.led_map.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| {
+                [(a.r, b.r), (a.g, b.g), (a.b, b.b), (a.a, b.a)]
+                    .iter()
+                    .any(|(a, b)| a.abs_diff(*b) > max_channel_delta)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Compare the frame against the golden image `path`. On a mismatch, the frame is
+    /// written next to the golden image with the suffix `.actual`, for inspection
+    pub fn assert_matches_golden<P: AsRef<Path>>(
+        &self,
+        path: &P,
+        tolerance: Tolerance,
+    ) -> Result<()> {
+        let path = path.as_ref();
+
+        if std::env::var_os(UPDATE_GOLDEN_FRAMES_VAR).is_some() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+
+            fs::write(path, self.to_pam())?;
+
+            return Ok(());
+        }
+
+        let golden = Self::from_pam(&fs::read(path)?)?;
+        let mismatched = self.diff(&golden, tolerance.max_channel_delta);
+
+        if mismatched.len() > tolerance.max_mismatched_leds {
+            fs::write(path.with_extension("actual.pam"), self.to_pam())?;
+
+            return Err(FrameCaptureError::Mismatch {
+                path: path.display().to_string(),
+                mismatched: mismatched.len(),
+                first: mismatched[0],
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// The scripts of a profile, running on Lua VMs of their own
+pub struct FrameCapture {
+    lua_txs: Vec<(PathBuf, Sender<Message>)>,
+    threads: Vec<JoinHandle<Result<RunScriptResult>>>,
+
+    /// The number of ticks that have been rendered
+    tick: u32,
+
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl FrameCapture {
+    /// Load the profile `profile_file`, and start its scripts. The scripts and their libraries
+    /// are looked up in `script_dirs`. The state of the profile is ignored, so that the
+    /// frames only depend on the profile and the manifests
+    pub fn new<P: AsRef<Path>>(profile_file: &P, script_dirs: &[PathBuf]) -> Result<Self> {
+        let guard = HARNESS_LOCK.lock();
+
+        // the Lua VMs expect a configuration, the script directories are set up below
+        crate::CONFIG
+            .lock()
+            .get_or_insert_with(config::Config::default);
+
+        let profile = Profile::load_file_only(profile_file.as_ref())?;
+
+        let mut lua_txs = vec![];
+        let mut threads = vec![];
+
+        for (thread_idx, script) in profile.active_scripts.iter().enumerate() {
+            let script_file = script_dirs
+                .iter()
+                .map(|dir| dir.join(script))
+                .find(|path| util::get_manifest_for(path).is_file())
+                .ok_or_else(|| FrameCaptureError::ScriptNotFound {
+                    script: script.display().to_string(),
+                })?;
+
+            let manifest = Manifest::load(&script_file)?;
+            let mut parameter_values = manifest
+                .get_merged_parameters(&profile)
+                .into_iter()
+                .map(|pv| (pv.name.clone(), pv))
+                .collect::<BTreeMap<_, _>>();
+
+            let package_path = script_dirs
+                .iter()
+                .map(|dir| format!(";{0}/lib/?;{0}/lib/?.lua", dir.display()))
+                .collect::<String>();
+
+            let (lua_tx, lua_rx) = unbounded();
+            let native_effect = manifest.get_native_effect();
+            let script_file = manifest.script_file;
+
+            lua_txs.push((script_file.clone(), lua_tx));
+
+            threads.push(
+                thread::Builder::new()
+                    .name(format!("frame-capture:{}", thread_idx))
+                    .spawn(move || -> Result<RunScriptResult> {
+                        if let Some(kind) = native_effect {
+                            return native::run_native(
+                                thread_idx,
+                                &script_file,
+                                kind,
+                                &mut parameter_values,
+                                &lua_rx,
+                            );
+                        }
+
+                        let lua_ctx = script::new_vm()?;
+
+                        lua_ctx
+                            .load(&format!(
+                                "package.path = package.path .. '{}'",
+                                package_path
+                            ))
+                            .exec()?;
+
+                        script::run_script(
+                            thread_idx,
+                            &script_file,
+                            &mut parameter_values,
+                            &lua_rx,
+                            Some(lua_ctx),
+                        )
+                    })?,
+            );
+        }
+
+        Ok(Self {
+            lua_txs,
+            threads,
+            tick: 0,
+            _guard: guard,
+        })
+    }
+
+    /// Render the ticks up to the last one of `capture_ticks`, sending the events of
+    /// `timeline` along the way. Returns the frames of the ticks in `capture_ticks`
+    pub fn render(
+        &mut self,
+        timeline: &Timeline,
+        capture_ticks: &[u32],
+    ) -> Result<BTreeMap<u32, Frame>> {
+        let capture_ticks = capture_ticks.iter().copied().collect::<BTreeSet<u32>>();
+        let last_tick = capture_ticks
+            .iter()
+            .next_back()
+            .copied()
+            .unwrap_or(self.tick);
+
+        let mut result = BTreeMap::new();
+
+        while self.tick < last_tick {
+            self.tick += 1;
+
+            for message in timeline.events.get(&self.tick).into_iter().flatten() {
+                self.send(message.clone())?;
+            }
+
+            // a fixed delta, so that the frames do not depend on the speed of the host
+            self.send(Message::Tick(1))?;
+
+            let frame = self.realize()?;
+
+            if capture_ticks.contains(&self.tick) {
+                result.insert(self.tick, frame);
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn send(&self, message: Message) -> Result<()> {
+        for (script_file, lua_tx) in self.lua_txs.iter() {
+            lua_tx
+                .send(message.clone())
+                .map_err(|_| FrameCaptureError::ScriptTerminated {
+                    script: script_file.display().to_string(),
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Let the VMs submit their layers, and compose them in the order of the profile
+    fn realize(&self) -> Result<Frame> {
+        script::prepare_layers(self.lua_txs.len());
+
+        {
+            let mut pending = crate::COLOR_MAPS_READY_CONDITION.0.lock();
+            *pending = self.lua_txs.len();
+
+            for (index, (script_file, lua_tx)) in self.lua_txs.iter().enumerate() {
+                lua_tx.send(Message::RealizeColorMap(index)).map_err(|_| {
+                    FrameCaptureError::ScriptTerminated {
+                        script: script_file.display().to_string(),
+                    }
+                })?;
+            }
+
+            let deadline = Instant::now() + Duration::from_millis(REALIZE_TIMEOUT_MILLIS);
+
+            while *pending > 0 {
+                if crate::COLOR_MAPS_READY_CONDITION
+                    .1
+                    .wait_until(&mut pending, deadline)
+                    .timed_out()
+                {
+                    *pending = 0;
+
+                    return Err(FrameCaptureError::Timeout { tick: self.tick }.into());
+                }
+            }
+        }
+
+        let mut led_map = vec![
+            RGBA {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            };
+            constants::CANVAS_SIZE
+        ];
+
+        // compose at full brightness, so that the frames do not depend on the settings
+        for layer in script::LAYERS.lock().iter().filter(|layer| layer.submitted) {
+            script::blend_layer(&mut led_map, &layer.led_map, 100.0);
+        }
+
+        Ok(Frame { led_map })
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        // ignore errors, the scripts may have terminated already
+        let _ = self.send(Message::Unload);
+
+        self.lua_txs.clear();
+
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assets_path() -> PathBuf {
+        PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("../support/tests/assets")
+    }
+
+    fn script_dirs() -> Vec<PathBuf> {
+        let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+
+        vec![path.join("src/scripts"), assets_path()]
+    }
+
+    #[test]
+    fn frames_match_golden_images() -> Result<()> {
+        let mut harness =
+            FrameCapture::new(&assets_path().join("frame_capture.profile"), &script_dirs())?;
+
+        let timeline = Timeline::new().at(3, Message::KeyDown(5));
+        let frames = harness.render(&timeline, &[1, 3, 5])?;
+
+        assert_eq!(frames.len(), 3);
+
+        for (tick, frame) in frames.iter() {
+            frame.assert_matches_golden(
+                &assets_path().join(format!("frames/frame_capture-{}.pam", tick)),
+                Tolerance::EXACT,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn shipped_script_matches_golden_images() -> Result<()> {
+        let mut harness =
+            FrameCapture::new(&assets_path().join("solid_color.profile"), &script_dirs())?;
+
+        let frames = harness.render(&Timeline::new(), &[1, 10])?;
+
+        for (tick, frame) in frames.iter() {
+            frame.assert_matches_golden(
+                &assets_path().join(format!("frames/solid_color-{}.pam", tick)),
+                Tolerance {
+                    max_channel_delta: 1,
+                    max_mismatched_leds: 0,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn tolerance_is_applied() -> Result<()> {
+        let golden = Frame::from_pam(&fs::read(assets_path().join("frames/frame_capture-3.pam"))?)?;
+
+        let mut frame = golden.clone();
+        frame.led_map[7].g = frame.led_map[7].g.wrapping_add(2);
+
+        assert_eq!(frame.diff(&golden, 0), vec![7]);
+        assert!(frame.diff(&golden, 2).is_empty());
+
+        Ok(())
+    }
+}
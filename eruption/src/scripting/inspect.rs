@@ -0,0 +1,235 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::constants;
+
+use super::parameters::PlainParameter;
+
+lazy_static! {
+    /// Debug information of the scripts of the active profile, indexed by the thread index of their Lua VM
+    static ref SCRIPT_INSPECTION: Arc<RwLock<HashMap<usize, ScriptInspection>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+}
+
+/// An error that terminated a script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptError {
+    pub message: String,
+
+    /// The Lua stack traceback, if available
+    pub traceback: Option<String>,
+
+    /// Point in time of the error, in seconds since the UNIX epoch
+    pub timestamp: u64,
+}
+
+/// Timing statistics of a single handler function
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct HandlerStats {
+    pub calls: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+}
+
+/// Debug information of a running script, as returned by `InspectScript`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptInspection {
+    pub index: usize,
+    pub script_file: PathBuf,
+
+    /// Set if the Lua VM is running, scripts that failed are not restarted
+    /// until the profile is switched
+    pub running: bool,
+
+    /// The event handler functions that are defined by the script
+    pub handlers: Vec<String>,
+
+    /// Current values of the parameters of the script
+    pub parameters: BTreeMap<String, String>,
+
+    pub handler_stats: BTreeMap<String, HandlerStats>,
+
+    /// Average time spent in the handlers of the script per rendered frame
+    pub frame_cost_micros: f64,
+
+    /// Share of the frame time budget at the target frame rate, in percent
+    pub frame_budget_percent: f64,
+
+    pub last_error: Option<ScriptError>,
+}
+
+/// Collects the timing statistics of a script on its Lua thread, and publishes
+/// them once per frame, so that event handlers do not contend for a global lock
+#[derive(Debug, Default, Clone)]
+pub struct ScriptProfiler {
+    handler_stats: BTreeMap<String, HandlerStats>,
+
+    /// Time spent in handlers since the start of the current frame
+    frame_micros: u64,
+
+    /// Moving average of the time spent in handlers per frame
+    frame_cost_micros: Option<f64>,
+}
+
+impl ScriptProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a call of the handler function `handler` that took `elapsed`
+    pub fn record_call(&mut self, handler: &str, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+
+        let stats = self.handler_stats.entry(handler.to_string()).or_default();
+
+        stats.calls += 1;
+        stats.total_micros += micros;
+        stats.max_micros = stats.max_micros.max(micros);
+
+        self.frame_micros += micros;
+    }
+
+    /// Close the current frame, and publish the statistics of the script `thread_idx`
+    pub fn end_frame(&mut self, thread_idx: usize) {
+        let frame_micros = self.frame_micros as f64;
+
+        let frame_cost_micros = match self.frame_cost_micros {
+            Some(average) => average * 0.9 + frame_micros * 0.1,
+            None => frame_micros,
+        };
+
+        self.frame_cost_micros = Some(frame_cost_micros);
+        self.frame_micros = 0;
+
+        if let Some(inspection) = SCRIPT_INSPECTION.write().get_mut(&thread_idx) {
+            let frame_budget_micros = 1_000_000.0 / constants::TARGET_FPS as f64;
+
+            inspection.handler_stats = self.handler_stats.clone();
+            inspection.frame_cost_micros = frame_cost_micros;
+            inspection.frame_budget_percent = frame_cost_micros / frame_budget_micros * 100.0;
+        }
+    }
+}
+
+/// Register a script that is about to be started by the Lua VM `thread_idx`. The last
+/// error is retained if the same script has been running on the VM before
+pub fn begin<'a, I>(thread_idx: usize, script_file: &Path, parameter_values: I)
+where
+    I: Iterator<Item = &'a PlainParameter>,
+{
+    let mut inspections = SCRIPT_INSPECTION.write();
+
+    let last_error = inspections
+        .get(&thread_idx)
+        .filter(|inspection| inspection.script_file == script_file)
+        .and_then(|inspection| inspection.last_error.clone());
+
+    inspections.insert(
+        thread_idx,
+        ScriptInspection {
+            index: thread_idx,
+            script_file: script_file.to_path_buf(),
+            running: true,
+            handlers: vec![],
+            parameters: parameter_values
+                .map(|pv| (pv.name.clone(), pv.value.to_string()))
+                .collect(),
+            handler_stats: BTreeMap::new(),
+            frame_cost_micros: 0.0,
+            frame_budget_percent: 0.0,
+            last_error,
+        },
+    );
+}
+
+/// Record the event handler functions that are defined by the script `thread_idx`
+pub fn set_handlers(thread_idx: usize, handlers: Vec<String>) {
+    if let Some(inspection) = SCRIPT_INSPECTION.write().get_mut(&thread_idx) {
+        inspection.handlers = handlers;
+    }
+}
+
+/// Record updated parameter values of the script `thread_idx`
+pub fn update_parameters(thread_idx: usize, parameter_values: &[PlainParameter]) {
+    if let Some(inspection) = SCRIPT_INSPECTION.write().get_mut(&thread_idx) {
+        for pv in parameter_values {
+            inspection
+                .parameters
+                .insert(pv.name.clone(), pv.value.to_string());
+        }
+    }
+}
+
+/// Record an error that terminated the script `thread_idx`. Errors that occurred
+/// in Lua code carry a stack traceback, which is split off the message
+pub fn record_error(thread_idx: usize, error: &dyn fmt::Display) {
+    let error = error.to_string();
+
+    let (message, traceback) = match error.split_once("stack traceback:") {
+        Some((message, traceback)) => (
+            message.trim_end().to_string(),
+            Some(traceback.trim().to_string()),
+        ),
+
+        None => (error, None),
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    if let Some(inspection) = SCRIPT_INSPECTION.write().get_mut(&thread_idx) {
+        inspection.running = false;
+        inspection.last_error = Some(ScriptError {
+            message,
+            traceback,
+            timestamp,
+        });
+    }
+}
+
+/// Record that the script `thread_idx` terminated gracefully
+pub fn stopped(thread_idx: usize) {
+    if let Some(inspection) = SCRIPT_INSPECTION.write().get_mut(&thread_idx) {
+        inspection.running = false;
+    }
+}
+
+/// Returns the debug information of the script `thread_idx`
+pub fn get_inspection(thread_idx: usize) -> Option<ScriptInspection> {
+    SCRIPT_INSPECTION.read().get(&thread_idx).cloned()
+}
+
+/// Returns the debug information of all scripts of the active profile
+#[cfg(feature = "metrics")]
+pub fn get_inspections() -> Vec<ScriptInspection> {
+    SCRIPT_INSPECTION.read().values().cloned().collect()
+}
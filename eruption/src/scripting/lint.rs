@@ -0,0 +1,837 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, PathBuf};
+
+use crate::scripting::constants::HANDLER_FUNCTIONS;
+use crate::scripting::manifest::Manifest;
+use crate::{script, util};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Maximum edit distance of a misspelled event handler name
+const MAX_HANDLER_DISTANCE: usize = 2;
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Style,
+}
+
+/// A single finding of the linter, as returned by `LintScript`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// The line of the script the finding refers to, if any
+    pub line: Option<usize>,
+
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Lint the Lua script `source`, and validate its `manifest` if one is supplied.
+/// `file_name` is only used to name the chunk in messages of the Lua parser
+pub fn lint_script(
+    file_name: &str,
+    source: &str,
+    manifest: Option<&str>,
+) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+
+    // the globals of a fresh VM are exactly the API that is available to the script
+    let lua = script::new_vm()?;
+
+    let mut known_globals = HashSet::new();
+    for pair in lua.globals().pairs::<mlua::Value, mlua::Value>() {
+        if let (mlua::Value::String(name), _) = pair? {
+            known_globals.insert(name.to_str()?.to_string());
+        }
+    }
+
+    match manifest {
+        Some(manifest) => {
+            diagnostics.extend(check_manifest(manifest, &mut known_globals));
+        }
+
+        None => diagnostics.push(Diagnostic::new(
+            Severity::Warning,
+            None,
+            "No manifest found, the script can not be used in a profile",
+        )),
+    }
+
+    if let Err(e) = lua
+        .load(source)
+        .set_name(&format!("@{}", file_name))?
+        .into_function()
+    {
+        let message = e.to_string();
+        diagnostics.push(Diagnostic::new(
+            Severity::Error,
+            parse_error_line(&message),
+            message,
+        ));
+    } else {
+        diagnostics.extend(check_source(
+            source,
+            &known_globals,
+            &util::get_script_dirs(),
+        ));
+    }
+
+    diagnostics.sort_by_key(|d| (d.line, d.severity));
+
+    Ok(diagnostics)
+}
+
+/// Extract the line number from a message of the Lua parser, like "file.lua:12: ..."
+fn parse_error_line(message: &str) -> Option<usize> {
+    message.split(':').skip(1).find_map(|s| s.parse().ok())
+}
+
+/// Validate the manifest `source`; the names of the parameters declared by the
+/// manifest are added to `known_globals`
+fn check_manifest(source: &str, known_globals: &mut HashSet<String>) -> Vec<Diagnostic> {
+    let mut result = Vec::new();
+
+    match toml::de::from_str::<Manifest>(source) {
+        Ok(manifest) => {
+            for parameter in manifest.config.iter() {
+                known_globals.insert(parameter.name.clone());
+
+                if let Err(e) = parameter.manifest.validate(&parameter.get_default()) {
+                    result.push(Diagnostic::new(
+                        Severity::Error,
+                        None,
+                        format!(
+                            "Manifest: Invalid default value of parameter '{}': {}",
+                            parameter.name, e
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Err(e) => result.push(Diagnostic::new(
+            Severity::Error,
+            None,
+            format!("Manifest: {}", e.message()),
+        )),
+    }
+
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Name,
+    Keyword,
+    Symbol,
+    Literal,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    line: usize,
+}
+
+impl Token<'_> {
+    fn is(&self, kind: TokenKind, text: &str) -> bool {
+        self.kind == kind && self.text == text
+    }
+}
+
+/// Returns the length of the opening long bracket at the start of `bytes`, like `[==[`
+fn long_bracket_level(bytes: &[u8]) -> Option<usize> {
+    if bytes.first() != Some(&b'[') {
+        return None;
+    }
+
+    let level = bytes[1..].iter().take_while(|b| **b == b'=').count();
+
+    (bytes.get(level + 1) == Some(&b'[')).then_some(level)
+}
+
+/// Split `source` into tokens, skipping whitespace and comments. The text of string
+/// literals includes their delimiters
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    const SYMBOLS: &[&str] = &["...", "..", "==", "~=", "<=", ">=", "::", "//", "<<", ">>"];
+
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    // skip to the end of the long bracket of `level` that starts at `start`
+    let skip_long = |start: usize, level: usize, line: &mut usize| -> usize {
+        let close = format!("]{}]", "=".repeat(level));
+        let body = start + level + 2;
+
+        let end = source[body..]
+            .find(&close)
+            .map_or(source.len(), |p| body + p + close.len());
+
+        *line += source[start..end].matches('\n').count();
+
+        end
+    };
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        let start = i;
+        let start_line = line;
+
+        if b == b'\n' {
+            line += 1;
+            i += 1;
+        } else if b.is_ascii_whitespace() {
+            i += 1;
+        } else if bytes[i..].starts_with(b"--") {
+            match long_bracket_level(&bytes[i + 2..]) {
+                Some(level) => i = skip_long(i + 2, level, &mut line),
+                None => {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                }
+            }
+        } else if let Some(level) = long_bracket_level(&bytes[i..]) {
+            i = skip_long(i, level, &mut line);
+
+            tokens.push(Token {
+                kind: TokenKind::Literal,
+                text: &source[start..i],
+                line: start_line,
+            });
+        } else if b == b'"' || b == b'\'' {
+            i += 1;
+
+            while i < bytes.len() && bytes[i] != b {
+                match bytes[i] {
+                    b'\\' => {
+                        if bytes.get(i + 1) == Some(&b'\n') {
+                            line += 1;
+                        }
+                        i += 2;
+                    }
+
+                    b'\n' => break,
+
+                    _ => i += 1,
+                }
+            }
+
+            i = (i + 1).min(bytes.len());
+
+            tokens.push(Token {
+                kind: TokenKind::Literal,
+                text: &source[start..i],
+                line: start_line,
+            });
+        } else if b.is_ascii_alphabetic() || b == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+
+            let text = &source[start..i];
+            let kind = if KEYWORDS.contains(&text) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Name
+            };
+
+            tokens.push(Token {
+                kind,
+                text,
+                line: start_line,
+            });
+        } else if b.is_ascii_digit()
+            || (b == b'.' && bytes.get(i + 1).is_some_and(|b| b.is_ascii_digit()))
+        {
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric()
+                    || bytes[i] == b'.'
+                    || (matches!(bytes[i], b'+' | b'-')
+                        && matches!(bytes[i - 1], b'e' | b'E' | b'p' | b'P')))
+            {
+                i += 1;
+            }
+
+            tokens.push(Token {
+                kind: TokenKind::Literal,
+                text: &source[start..i],
+                line: start_line,
+            });
+        } else if b.is_ascii() {
+            let len = SYMBOLS
+                .iter()
+                .find(|s| bytes[i..].starts_with(s.as_bytes()))
+                .map_or(1, |s| s.len());
+
+            i += len;
+
+            tokens.push(Token {
+                kind: TokenKind::Symbol,
+                text: &source[start..i],
+                line: start_line,
+            });
+        } else {
+            // stray non-ASCII characters, they are reported by the Lua parser
+            i += 1;
+
+            while i < bytes.len() && !source.is_char_boundary(i) {
+                i += 1;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Names that are defined by a script, either globally or locally
+#[derive(Debug, Default)]
+struct Definitions {
+    names: HashSet<String>,
+
+    /// Global functions with a plain name, and the line of their definition
+    functions: Vec<(String, usize)>,
+
+    /// Libraries that are loaded via `require`, and the line of the call
+    requires: Vec<(String, usize)>,
+
+    /// Set if a library name is only known at runtime, like in `pcall(require, name)`
+    dynamic_requires: bool,
+}
+
+fn collect_definitions(tokens: &[Token], definitions: &mut Definitions) {
+    let symbol = |i: usize, text: &str| {
+        tokens
+            .get(i)
+            .is_some_and(|t: &Token| t.is(TokenKind::Symbol, text))
+    };
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::Keyword if token.text == "function" => {
+                let mut j = i + 1;
+
+                if let Some(name) = tokens.get(j).filter(|t| t.kind == TokenKind::Name) {
+                    if !symbol(j + 1, ".") && !symbol(j + 1, ":") {
+                        definitions.names.insert(name.text.to_string());
+
+                        let is_local = i > 0 && tokens[i - 1].is(TokenKind::Keyword, "local");
+                        if !is_local {
+                            definitions
+                                .functions
+                                .push((name.text.to_string(), name.line));
+                        }
+                    }
+                }
+
+                // the parameter list
+                while j < tokens.len() && !symbol(j, "(") {
+                    j += 1;
+                }
+
+                while j < tokens.len() && !symbol(j, ")") {
+                    if tokens[j].kind == TokenKind::Name {
+                        definitions.names.insert(tokens[j].text.to_string());
+                    }
+
+                    j += 1;
+                }
+            }
+
+            TokenKind::Keyword if token.text == "local" || token.text == "for" => {
+                let mut j = i + 1;
+
+                while let Some(t) = tokens.get(j) {
+                    match t.kind {
+                        TokenKind::Name => {
+                            definitions.names.insert(t.text.to_string());
+                        }
+
+                        // attributes like <const>
+                        TokenKind::Symbol if matches!(t.text, "," | "<" | ">") => {}
+
+                        _ => break,
+                    }
+
+                    j += 1;
+                }
+            }
+
+            TokenKind::Name => {
+                let is_field = i > 0 && (symbol(i - 1, ".") || symbol(i - 1, ":"));
+
+                if !is_field && symbol(i + 1, "=") {
+                    definitions.names.insert(token.text.to_string());
+                }
+
+                if token.text == "require" && !is_field {
+                    if i > 0 && (symbol(i - 1, "(") || symbol(i - 1, ",")) {
+                        definitions.dynamic_requires = true;
+                    }
+
+                    let argument = if symbol(i + 1, "(") {
+                        tokens.get(i + 2)
+                    } else {
+                        tokens.get(i + 1)
+                    };
+
+                    match argument.filter(|t| t.kind == TokenKind::Literal) {
+                        Some(argument) => {
+                            let name = argument.text.trim_matches(|c| c == '"' || c == '\'');
+                            definitions.requires.push((name.to_string(), token.line));
+                        }
+
+                        None => definitions.dynamic_requires = true,
+                    }
+                }
+            }
+
+            _ => {}
+        }
+    }
+}
+
+/// Find the library `name` in the `lib` directories of the script directories. Names
+/// that would escape the `lib` directories, like absolute paths or `..`, are not found
+fn find_library(name: &str, script_dirs: &[PathBuf]) -> Option<PathBuf> {
+    let name = PathBuf::from(name.replace('.', "/"));
+
+    if !name
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    script_dirs.iter().find_map(|dir| {
+        [
+            dir.join("lib").join(&name),
+            dir.join("lib").join(format!("{}.lua", name.display())),
+        ]
+        .into_iter()
+        .find(|path| path.is_file())
+    })
+}
+
+/// Collect the definitions of the libraries required by the script, and of their
+/// dependencies. Returns the diagnostics for libraries that could not be found
+fn collect_library_definitions(
+    definitions: &mut Definitions,
+    script_dirs: &[PathBuf],
+) -> Vec<Diagnostic> {
+    let mut result = Vec::new();
+
+    let mut visited = HashSet::new();
+    let mut pending = definitions.requires.clone();
+
+    while let Some((name, line)) = pending.pop() {
+        if !visited.insert(name.clone()) {
+            continue;
+        }
+
+        match find_library(&name, script_dirs).and_then(|path| fs::read_to_string(path).ok()) {
+            Some(source) => {
+                let mut library = Definitions::default();
+                collect_definitions(&tokenize(&source), &mut library);
+
+                definitions.names.extend(library.names);
+                definitions
+                    .names
+                    .extend(library.functions.into_iter().map(|(name, _)| name));
+
+                // report missing dependencies of libraries at the outermost `require`
+                pending.extend(library.requires.into_iter().map(|(name, _)| (name, line)));
+            }
+
+            None => result.push(Diagnostic::new(
+                Severity::Error,
+                Some(line),
+                format!("Required library '{}' could not be found", name),
+            )),
+        }
+    }
+
+    result
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(row[j + 1])
+            };
+
+            previous = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Run the Eruption specific checks on the syntactically valid script `source`
+fn check_source(
+    source: &str,
+    known_globals: &HashSet<String>,
+    script_dirs: &[PathBuf],
+) -> Vec<Diagnostic> {
+    let mut result = Vec::new();
+
+    let tokens = tokenize(source);
+
+    let mut definitions = Definitions::default();
+    collect_definitions(&tokens, &mut definitions);
+
+    result.extend(collect_library_definitions(&mut definitions, script_dirs));
+
+    // event handlers
+    let handlers = definitions
+        .functions
+        .iter()
+        .filter(|(name, _)| HANDLER_FUNCTIONS.contains(&name.as_str()))
+        .count();
+
+    if handlers == 0 {
+        result.push(Diagnostic::new(
+            Severity::Warning,
+            None,
+            "The script does not define any event handlers",
+        ));
+    }
+
+    for (name, line) in definitions.functions.iter() {
+        if !name.starts_with("on_") || HANDLER_FUNCTIONS.contains(&name.as_str()) {
+            continue;
+        }
+
+        if let Some(handler) = HANDLER_FUNCTIONS
+            .iter()
+            .find(|handler| edit_distance(name, handler) <= MAX_HANDLER_DISTANCE)
+        {
+            result.push(Diagnostic::new(
+                Severity::Warning,
+                Some(*line),
+                format!(
+                    "Function '{}' is not an event handler, did you mean '{}'?",
+                    name, handler
+                ),
+            ));
+        }
+    }
+
+    // calls of undefined functions, optional functions are compared against nil before the call
+    let optional = tokens
+        .windows(3)
+        .filter(|w| {
+            w[0].kind == TokenKind::Name
+                && (w[1].is(TokenKind::Symbol, "~=") || w[1].is(TokenKind::Symbol, "=="))
+                && w[2].is(TokenKind::Keyword, "nil")
+        })
+        .map(|w| w[0].text)
+        .collect::<HashSet<_>>();
+
+    let mut undefined: HashMap<&str, usize> = HashMap::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Name {
+            continue;
+        }
+
+        let is_field = i > 0
+            && (tokens[i - 1].is(TokenKind::Symbol, ".")
+                || tokens[i - 1].is(TokenKind::Symbol, ":")
+                || tokens[i - 1].is(TokenKind::Keyword, "function"));
+
+        let is_call = tokens.get(i + 1).is_some_and(|next| {
+            next.is(TokenKind::Symbol, "(")
+                || next.is(TokenKind::Symbol, "{")
+                || (next.kind == TokenKind::Literal && next.text.starts_with(['"', '\'', '[']))
+        });
+
+        if is_call
+            && !is_field
+            && !definitions.names.contains(token.text)
+            && !known_globals.contains(token.text)
+            && !optional.contains(token.text)
+        {
+            undefined.entry(token.text).or_insert(token.line);
+        }
+    }
+
+    // the function may be defined by a library that is loaded at runtime
+    let severity = if definitions.dynamic_requires {
+        Severity::Warning
+    } else {
+        Severity::Error
+    };
+
+    for (name, line) in undefined {
+        result.push(Diagnostic::new(
+            severity,
+            Some(line),
+            format!("Call of undefined function '{}'", name),
+        ));
+    }
+
+    result.extend(check_on_tick(&tokens));
+    result.extend(check_style(source));
+
+    result
+}
+
+/// Find allocations in the body of `on_tick`, they are performed on every frame
+fn check_on_tick(tokens: &[Token]) -> Vec<Diagnostic> {
+    let mut result = Vec::new();
+
+    let start = tokens.windows(2).position(|w| {
+        w[0].is(TokenKind::Keyword, "function") && w[1].is(TokenKind::Name, "on_tick")
+    });
+
+    let start = match start {
+        Some(start) => start + 2,
+        None => return result,
+    };
+
+    let mut depth = 1;
+
+    for token in tokens[start..].iter() {
+        match token.kind {
+            TokenKind::Keyword => match token.text {
+                "function" => {
+                    result.push(Diagnostic::new(
+                        Severity::Warning,
+                        Some(token.line),
+                        "A closure is created in on_tick(), on every frame",
+                    ));
+
+                    depth += 1;
+                }
+
+                "if" | "do" | "repeat" => depth += 1,
+
+                "end" | "until" => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        break;
+                    }
+                }
+
+                _ => {}
+            },
+
+            TokenKind::Symbol if token.text == "{" => result.push(Diagnostic::new(
+                Severity::Warning,
+                Some(token.line),
+                "A table is allocated in on_tick(), on every frame",
+            )),
+
+            TokenKind::Name if token.text == "gradient_create" => {
+                result.push(Diagnostic::new(
+                    Severity::Warning,
+                    Some(token.line),
+                    "A gradient is allocated in on_tick(), on every frame, consider creating it in on_startup()",
+                ))
+            }
+
+            _ => {}
+        }
+    }
+
+    result
+}
+
+fn check_style(source: &str) -> Vec<Diagnostic> {
+    let mut result = Vec::new();
+
+    let mut tab_indent = None;
+    let mut space_indent = None;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+
+        if text.ends_with([' ', '\t']) {
+            result.push(Diagnostic::new(
+                Severity::Style,
+                Some(line),
+                "Trailing whitespace",
+            ));
+        }
+
+        if text.starts_with('\t') {
+            tab_indent.get_or_insert(line);
+        } else if text.starts_with(' ') && !text.trim().is_empty() {
+            space_indent.get_or_insert(line);
+        }
+    }
+
+    if let (Some(tab), Some(space)) = (tab_indent, space_indent) {
+        result.push(Diagnostic::new(
+            Severity::Style,
+            Some(tab.max(space)),
+            "Indentation mixes tabs and spaces",
+        ));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(source: &str) -> Vec<Diagnostic> {
+        let known_globals = ["get_num_keys", "gradient_create", "ipairs"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        check_source(source, &known_globals, &[])
+    }
+
+    #[test]
+    fn clean_script() {
+        let source = "-- comment with call() and { table }\n\
+                      local colors = {}\n\
+                      function on_startup(config)\n\
+                      \tfor i = 1, get_num_keys() do\n\
+                      \t\tcolors[i] = 0\n\
+                      \tend\n\
+                      end\n\
+                      function on_tick(delta)\n\
+                      \tlocal s = [[ { not a table } ]]\n\
+                      \tfor i, c in ipairs(colors) do\n\
+                      \t\tcolors[i] = c + delta\n\
+                      \tend\n\
+                      end\n";
+
+        assert_eq!(lint(source), vec![]);
+    }
+
+    #[test]
+    fn findings() {
+        let source = "function on_tik(delta)\n\
+                      \tundefined_call(1)\n\
+                      end\n\
+                      function on_tick(delta)\n\
+                      \tlocal t = { 1, 2 }\n\
+                      \tif delta > 0 then\n\
+                      \t\tlocal g = gradient_create({})\n\
+                      \tend\n\
+                      end\n\
+                      local x = 1 \n";
+
+        let result = lint(source);
+
+        let lines = |severity: Severity| -> Vec<Option<usize>> {
+            result
+                .iter()
+                .filter(|d| d.severity == severity)
+                .map(|d| d.line)
+                .collect()
+        };
+
+        assert_eq!(lines(Severity::Error), vec![Some(2)]);
+        assert_eq!(
+            lines(Severity::Warning),
+            vec![Some(1), Some(5), Some(7), Some(7)]
+        );
+        assert_eq!(lines(Severity::Style), vec![Some(10)]);
+    }
+
+    #[test]
+    fn missing_handlers() {
+        let result = lint("local x = 1\n");
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn error_line() {
+        assert_eq!(
+            parse_error_line("test.lua:12: 'end' expected near <eof>"),
+            Some(12)
+        );
+    }
+
+    #[test]
+    fn libraries_are_confined_to_the_script_dirs() {
+        let dir = std::env::temp_dir().join(format!("eruption-lint-{}", std::process::id()));
+        let lib = dir.join("lib");
+
+        fs::create_dir_all(lib.join("macros")).unwrap();
+        fs::write(lib.join("macros").join("modifiers.lua"), "").unwrap();
+        fs::write(dir.join("secret.lua"), "").unwrap();
+
+        let script_dirs = [dir.clone()];
+
+        assert_eq!(
+            find_library("macros.modifiers", &script_dirs),
+            Some(lib.join("macros").join("modifiers.lua"))
+        );
+
+        // "..secret" maps to "//secret", which would replace the base path
+        assert_eq!(find_library("..secret", &script_dirs), None);
+        assert_eq!(find_library("../secret", &script_dirs), None);
+        assert_eq!(
+            find_library(&dir.join("secret").to_string_lossy(), &script_dirs),
+            None
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
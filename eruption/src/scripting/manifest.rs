@@ -24,6 +24,7 @@
 use log::*;
 use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -47,12 +48,22 @@ pub enum ManifestError {
 
     #[error("Could not parse a param value")]
     ParseParamError,
+
+    #[error("Invalid shader file: {msg}")]
+    ShaderFileError { msg: String },
+
+    #[error("Native effect of script {name} is unspecified")]
+    NativeEffectError { name: String },
 }
 
 fn default_script_file() -> PathBuf {
     "".into()
 }
 
+fn default_author() -> String {
+    "Unknown".into()
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Manifest {
     #[serde(default = "default_script_file")]
@@ -61,13 +72,135 @@ pub struct Manifest {
     pub name: String,
     pub description: String,
     pub version: String,
+    #[serde(default = "default_author")]
     pub author: String,
     pub min_supported_version: String,
     pub tags: Option<Vec<ScriptTag>>,
+
+    /// SPDX license identifier of the script, like e.g. "GPL-3.0-or-later"
+    #[serde(default)]
+    pub license: Option<String>,
+
+    /// URL of the project page of the script
+    #[serde(default)]
+    pub homepage: Option<String>,
+
+    /// Hints that allow a GUI to render a preview of the effect, without running the script
+    #[serde(default)]
+    pub preview: Option<ScriptPreview>,
+
+    /// The kind of effect, either a Lua script, a WGSL compute shader or a native effect
+    #[serde(rename = "type", default)]
+    pub script_type: ScriptType,
+
+    /// The WGSL source of a `shader` effect, relative to the directory of the script.
+    /// The Lua script serves as a fallback, if the GPU backend is unavailable
+    #[serde(default)]
+    pub shader_file: Option<PathBuf>,
+
+    /// The built-in effect of a `native` effect, these effects do not require a Lua script
+    #[serde(default)]
+    pub native_effect: Option<NativeEffectKind>,
+
     #[serde(default)]
     pub config: ManifestConfiguration,
 }
 
+/// The kind of an effect
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptType {
+    /// The effect is implemented by the Lua script
+    #[default]
+    Lua,
+
+    /// The effect is implemented by a WGSL compute shader, that is evaluated onto the canvas
+    Shader,
+
+    /// The effect is built into Eruption, the manifest does not need to be accompanied by a
+    /// Lua script
+    Native,
+}
+
+/// The built-in effects
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NativeEffectKind {
+    /// Stars that twinkle in and out of existence
+    Starfield,
+
+    /// Overlapping sine waves, cycling through the hues
+    Plasma,
+
+    /// Columns of glyphs that rain down the canvas
+    Matrix,
+
+    /// Flames rising from the bottom of the canvas
+    Fire,
+}
+
+impl fmt::Display for NativeEffectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Starfield => write!(f, "starfield"),
+            Self::Plasma => write!(f, "plasma"),
+            Self::Matrix => write!(f, "matrix"),
+            Self::Fire => write!(f, "fire"),
+        }
+    }
+}
+
+/// Hints that describe what a script looks like
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ScriptPreview {
+    /// Dominant colors of the effect, in the form "#rrggbb" or "#rrggbbaa"
+    #[serde(default)]
+    pub colors: Vec<String>,
+
+    #[serde(default)]
+    pub animation: PreviewAnimation,
+}
+
+/// The kind of animation that a preview should use
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PreviewAnimation {
+    /// The colors do not change over time
+    #[default]
+    Static,
+
+    /// The brightness of the colors rises and falls periodically
+    Pulse,
+
+    /// The colors travel across the keyboard
+    Wave,
+
+    /// The colors change randomly
+    Random,
+
+    /// The effect reacts to user input, e.g. key presses
+    Reactive,
+
+    /// The effect visualizes audio or other external data
+    Visualization,
+    /// An animation that is not known to this version of Eruption, replaced by `Static`
+    #[serde(other)]
+    Unknown,
+}
+
+/// The metadata of a script, as presented by GUIs and the profile repository.
+/// Unspecified fields of legacy manifests are filled in with defaults
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScriptMetadata {
+    pub name: String,
+    pub description: String,
+    pub version: String,
+    pub author: String,
+    pub license: Option<String>,
+    pub homepage: Option<String>,
+    pub tags: Vec<ScriptTag>,
+    pub preview: ScriptPreview,
+}
+
 impl std::cmp::PartialOrd for Manifest {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.name.cmp(&other.name))
@@ -86,6 +219,45 @@ impl Manifest {
                         // fill in required fields, after parsing
                         result.script_file = script_path;
 
+                        result.validate_metadata();
+
+                        if result.script_type == ScriptType::Native {
+                            if result.native_effect.is_none() {
+                                return Err(
+                                    ManifestError::NativeEffectError { name: result.name }.into()
+                                );
+                            }
+                        } else if let Err(err) =
+                            util::demand_file_is_accessible(&result.script_file)
+                        {
+                            error!(
+                                "Script file {} is not accessible: {}",
+                                result.script_file.display(),
+                                err
+                            );
+
+                            return Err(ManifestError::OpenError {}.into());
+                        }
+
+                        if let Err(e) = result.resolve_shader_file() {
+                            warn!(
+                                "Manifest of script {} specifies an invalid shader, using the Lua script instead: {}",
+                                result.name, e
+                            );
+
+                            result.script_type = ScriptType::Lua;
+                            result.shader_file = None;
+                        }
+
+                        for parameter in result.config.iter() {
+                            if let Err(e) = parameter.manifest.validate(&parameter.get_default()) {
+                                warn!(
+                                    "Invalid default value of parameter {} in manifest of script {}: {}",
+                                    parameter.name, result.name, e
+                                );
+                            }
+                        }
+
                         Ok(result)
                     }
 
@@ -101,6 +273,127 @@ impl Manifest {
         }
     }
 
+    /// Check the optional metadata fields, invalid values are discarded with a warning
+    fn validate_metadata(&mut self) {
+        if self.author.trim().is_empty() {
+            self.author = default_author();
+        }
+
+        if let Some(license) = &self.license {
+            if license.is_empty() || license.contains(char::is_whitespace) {
+                warn!(
+                    "Invalid license identifier '{}' in manifest of script {}, expected an SPDX identifier",
+                    license, self.name
+                );
+
+                self.license = None;
+            }
+        }
+
+        if let Some(homepage) = &self.homepage {
+            if !(homepage.starts_with("https://") || homepage.starts_with("http://")) {
+                warn!(
+                    "Invalid homepage URL '{}' in manifest of script {}",
+                    homepage, self.name
+                );
+
+                self.homepage = None;
+            }
+        }
+
+        if let Some(preview) = &mut self.preview {
+            let name = &self.name;
+
+            preview.colors.retain(|color| {
+                let valid = is_valid_preview_color(color);
+
+                if !valid {
+                    warn!(
+                        "Invalid preview color '{}' in manifest of script {}",
+                        color, name
+                    );
+                }
+
+                valid
+            });
+
+            if preview.animation == PreviewAnimation::Unknown {
+                warn!(
+                    "Unknown preview animation in manifest of script {}, using a static preview",
+                    name
+                );
+
+                preview.animation = PreviewAnimation::Static;
+            }
+        }
+    }
+
+    /// Resolve the shader file of a `shader` effect, relative to the directory of the script
+    fn resolve_shader_file(&mut self) -> Result<()> {
+        if self.script_type != ScriptType::Shader {
+            return Ok(());
+        }
+
+        let shader_file =
+            self.shader_file
+                .as_ref()
+                .ok_or_else(|| ManifestError::ShaderFileError {
+                    msg: "No shader_file specified".to_owned(),
+                })?;
+
+        let shader_file = match self.script_file.parent() {
+            Some(script_dir) => script_dir.join(shader_file),
+            None => shader_file.to_owned(),
+        };
+
+        if shader_file.extension().unwrap_or_default() != "wgsl" {
+            return Err(ManifestError::ShaderFileError {
+                msg: format!("{} is not a WGSL file", shader_file.display()),
+            }
+            .into());
+        }
+
+        util::demand_file_is_accessible(&shader_file).map_err(|e| {
+            ManifestError::ShaderFileError {
+                msg: format!("{}: {}", shader_file.display(), e),
+            }
+        })?;
+
+        self.shader_file = Some(shader_file);
+
+        Ok(())
+    }
+
+    /// Returns the shader file, if this is a `shader` effect
+    pub fn get_shader_file(&self) -> Option<&Path> {
+        match self.script_type {
+            ScriptType::Shader => self.shader_file.as_deref(),
+            ScriptType::Lua | ScriptType::Native => None,
+        }
+    }
+
+    /// Returns the built-in effect, if this is a `native` effect
+    pub fn get_native_effect(&self) -> Option<NativeEffectKind> {
+        match self.script_type {
+            ScriptType::Native => self.native_effect,
+            ScriptType::Lua | ScriptType::Shader => None,
+        }
+    }
+
+    /// Returns the metadata of the script, with defaults for unspecified fields
+    pub fn get_metadata(&self) -> ScriptMetadata {
+        ScriptMetadata {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            version: self.version.clone(),
+            author: self.author.clone(),
+            license: self.license.clone(),
+            homepage: self.homepage.clone(),
+            tags: self.tags.clone().unwrap_or_default(),
+            preview: self.preview.clone().unwrap_or_default(),
+        }
+    }
+
     pub fn get_merged_parameters(&self, profile: &Profile) -> Vec<PlainParameter> {
         let profile_script_parameters = profile.config.get_parameters(&self.name);
         if let Some(profile_script_parameters) = profile_script_parameters {
@@ -126,15 +419,29 @@ impl Manifest {
     }
 }
 
+/// Returns `true` if `color` is of the form "#rrggbb" or "#rrggbbaa"
+fn is_valid_preview_color(color: &str) -> bool {
+    match color.strip_prefix('#') {
+        Some(hex) => {
+            (hex.len() == 6 || hex.len() == 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
+/// Locate the script and its manifest. The script file itself is optional, since the
+/// manifest of a `native` effect may stand on its own, this is checked after parsing
 fn verify_script_and_manifest_paths(script_path: &Path) -> Result<(PathBuf, PathBuf)> {
-    let script_path = if script_path.exists() {
-        script_path.to_owned()
+    let manifest_path = util::get_manifest_for(script_path);
+
+    let manifest_path = if manifest_path.exists() {
+        manifest_path
     } else {
-        match util::match_script_path(&script_path) {
-            Ok(script_path) => script_path,
+        match util::match_script_path(&manifest_path) {
+            Ok(manifest_path) => manifest_path,
             Err(error) => {
                 error!(
-                    "Script file {} cannot be found: {}",
+                    "Manifest file for script {} cannot be found: {}",
                     script_path.display(),
                     error
                 );
@@ -143,20 +450,6 @@ fn verify_script_and_manifest_paths(script_path: &Path) -> Result<(PathBuf, Path
         }
     };
 
-    let manifest_path = util::get_manifest_for(&script_path);
-
-    let script_path = match script_path.canonicalize() {
-        Ok(script_path) => script_path,
-        Err(err) => {
-            error!(
-                "Script file path {} could not be canonicalized: {}",
-                script_path.display(),
-                err
-            );
-            return Err(ManifestError::OpenError {}.into());
-        }
-    };
-
     let manifest_path = match manifest_path.canonicalize() {
         Ok(manifest_path) => manifest_path,
         Err(err) => {
@@ -169,16 +462,6 @@ fn verify_script_and_manifest_paths(script_path: &Path) -> Result<(PathBuf, Path
         }
     };
 
-    if let Err(err) = util::demand_file_is_accessible(&script_path) {
-        error!(
-            "Script file {} is not accessible: {}",
-            script_path.display(),
-            err
-        );
-
-        return Err(ManifestError::OpenError {}.into());
-    }
-
     if let Err(err) = util::demand_file_is_accessible(&manifest_path) {
         error!(
             "Manifest file for script {} is not accessible: {}",
@@ -189,6 +472,17 @@ fn verify_script_and_manifest_paths(script_path: &Path) -> Result<(PathBuf, Path
         return Err(ManifestError::OpenError {}.into());
     }
 
+    // the script file lives next to its manifest
+    let script_path = match script_path.file_name() {
+        Some(file_name) => manifest_path.with_file_name(file_name),
+        None => {
+            error!("Invalid script file name: {}", script_path.display());
+            return Err(ManifestError::OpenError {}.into());
+        }
+    };
+
+    let script_path = script_path.canonicalize().unwrap_or(script_path);
+
     Ok((script_path, manifest_path))
 }
 
@@ -206,12 +500,21 @@ pub fn get_script_files_from(script_dirs: &[PathBuf]) -> Result<Vec<PathBuf>> {
         if let Ok(paths) = fs::read_dir(script_path) {
             let mut script_paths = paths
                 .map(|p| p.unwrap().path())
-                .filter(|p| {
-                    if p.extension().is_some() {
-                        return p.extension().unwrap_or_else(|| OsStr::new("")) == "lua";
+                .filter_map(|p| {
+                    if p.extension().unwrap_or_else(|| OsStr::new("")) == "lua" {
+                        return Some(p);
                     }
 
-                    false
+                    // the manifest of a `native` effect may come without a script file
+                    let file_name = p.file_name()?.to_str()?;
+                    let script_path = p.with_file_name(file_name.strip_suffix(".manifest")?);
+
+                    if script_path.extension().unwrap_or_default() == "lua" && !script_path.exists()
+                    {
+                        Some(script_path)
+                    } else {
+                        None
+                    }
                 })
                 .collect::<Vec<PathBuf>>();
 
@@ -364,3 +667,129 @@ impl ScriptTag {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Manifest, NativeEffectKind, PreviewAnimation, ScriptPreview, ScriptType};
+
+    #[test]
+    fn legacy_manifest_defaults() {
+        let toml = r#"
+            name = "Legacy"
+            description = "A manifest without metadata"
+            version = "0.0.1"
+            min_supported_version = "0.0.12"
+            tags = ['Background']
+        "#;
+
+        let mut manifest = toml::de::from_str::<Manifest>(toml).unwrap();
+        manifest.validate_metadata();
+
+        let metadata = manifest.get_metadata();
+
+        assert_eq!(metadata.author, "Unknown");
+        assert_eq!(metadata.license, None);
+        assert_eq!(metadata.homepage, None);
+        assert_eq!(metadata.preview, ScriptPreview::default());
+    }
+
+    #[test]
+    fn invalid_metadata_is_discarded() {
+        let toml = r##"
+            name = "Metadata"
+            description = "A manifest with metadata"
+            version = "0.0.1"
+            author = "The Eruption Development Team"
+            min_supported_version = "0.0.12"
+            tags = ['Effect']
+            license = "not a license"
+            homepage = "example.org"
+
+            [preview]
+            colors = ['#ff0000', 'red', '#00ff00ff', '#12345']
+            animation = 'Wave'
+        "##;
+
+        let mut manifest = toml::de::from_str::<Manifest>(toml).unwrap();
+        manifest.validate_metadata();
+
+        let metadata = manifest.get_metadata();
+
+        assert_eq!(metadata.license, None);
+        assert_eq!(metadata.homepage, None);
+        assert_eq!(metadata.preview.colors, vec!["#ff0000", "#00ff00ff"]);
+        assert_eq!(metadata.preview.animation, PreviewAnimation::Wave);
+    }
+
+    #[test]
+    fn unknown_preview_animations_are_accepted() {
+        let toml = r#"
+            name = "Preview"
+            description = "A manifest with a preview of a future version"
+            version = "0.0.1"
+            min_supported_version = "0.0.12"
+            tags = ['Effect']
+
+            [preview]
+            animation = 'Kaleidoscope'
+        "#;
+
+        let mut manifest = toml::de::from_str::<Manifest>(toml).unwrap();
+        manifest.validate_metadata();
+
+        assert_eq!(
+            manifest.get_metadata().preview.animation,
+            PreviewAnimation::Static
+        );
+    }
+
+    #[test]
+    fn shader_manifest() {
+        let toml = r#"
+            name = "Shader"
+            description = "A shader effect"
+            version = "0.0.1"
+            min_supported_version = "0.5.0"
+            tags = ['Background']
+            type = 'shader'
+            shader_file = 'plasma.wgsl'
+        "#;
+
+        let manifest = toml::de::from_str::<Manifest>(toml).unwrap();
+
+        assert_eq!(manifest.script_type, ScriptType::Shader);
+        assert_eq!(
+            manifest.get_shader_file(),
+            Some(std::path::Path::new("plasma.wgsl"))
+        );
+
+        let legacy = toml::de::from_str::<Manifest>(&toml.replace("type = 'shader'", "")).unwrap();
+
+        assert_eq!(legacy.script_type, ScriptType::Lua);
+        assert_eq!(legacy.get_shader_file(), None);
+    }
+
+    #[test]
+    fn native_manifest() {
+        let toml = r#"
+            name = "Native"
+            description = "A native effect"
+            version = "0.0.1"
+            min_supported_version = "0.5.0"
+            tags = ['Background']
+            type = 'native'
+            native_effect = 'starfield'
+        "#;
+
+        let manifest = toml::de::from_str::<Manifest>(toml).unwrap();
+
+        assert_eq!(manifest.script_type, ScriptType::Native);
+        assert_eq!(
+            manifest.get_native_effect(),
+            Some(NativeEffectKind::Starfield)
+        );
+        assert_eq!(manifest.get_shader_file(), None);
+
+        assert!(toml::de::from_str::<Manifest>(&toml.replace("'starfield'", "'unknown'")).is_err());
+    }
+}
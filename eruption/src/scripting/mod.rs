@@ -19,9 +19,18 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+pub mod blackboard;
+pub mod bytecode_cache;
 pub mod callbacks;
 pub mod constants;
+#[cfg(test)]
+pub mod frame_capture;
+pub mod inspect;
+pub mod lint;
 pub mod manifest;
+pub mod native;
 pub mod parameters;
 pub mod parameters_util;
 pub mod script;
+pub mod shader;
+pub mod vm_pool;
@@ -0,0 +1,109 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Flames rising from the bottom of the canvas. The heat of each cell is propagated
+//! upwards and cools down on the way, the heat is then mapped onto a color palette
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{mix, NativeEffect, Parameters};
+use crate::constants;
+use crate::hwdevices::RGBA;
+use crate::util::canvas_index;
+
+/// The palette that the heat of a cell is mapped onto, from cold to hot
+const PALETTE: [u32; 5] = [0xff000000, 0xff800000, 0xffff3000, 0xffffa000, 0xffffff80];
+
+pub struct Fire {
+    rng: StdRng,
+
+    /// Heat of each cell in the range 0.0 .. 1.0, with an extra row below the canvas,
+    /// that feeds the flames. Indexed by `heat_index`
+    heat: Vec<f64>,
+
+    cooling: f64,
+    intensity: f64,
+}
+
+impl Fire {
+    pub fn new() -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(0),
+            heat: vec![0.0; (constants::CANVAS_HEIGHT + 1) * constants::CANVAS_WIDTH],
+
+            cooling: 0.2,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl NativeEffect for Fire {
+    fn set_parameters(&mut self, parameters: &Parameters) {
+        self.cooling = parameters
+            .get_float("cooling")
+            .unwrap_or(self.cooling)
+            .clamp(0.0, 1.0);
+        self.intensity = parameters
+            .get_float("intensity")
+            .unwrap_or(self.intensity)
+            .clamp(0.0, 1.0);
+    }
+
+    fn render(&mut self, _ticks: u32, canvas: &mut [RGBA]) {
+        let width = constants::CANVAS_WIDTH;
+        let height = constants::CANVAS_HEIGHT;
+
+        // feed the flames
+        for x in 0..width {
+            self.heat[heat_index(x, height)] = self.rng.gen_range(0.5..1.0) * self.intensity;
+        }
+
+        // let the heat rise, drifting sideways at random
+        for y in 0..height {
+            for x in 0..width {
+                let drift = self.rng.gen_range(-1..=1);
+                let source = (x as isize + drift).clamp(0, width as isize - 1) as usize;
+
+                let cooling = self.rng.gen_range(0.0..self.cooling.max(f64::EPSILON));
+
+                self.heat[heat_index(x, y)] =
+                    (self.heat[heat_index(source, y + 1)] - cooling).max(0.0);
+            }
+        }
+
+        for x in 0..width {
+            for y in 0..height {
+                let position = self.heat[heat_index(x, y)] * (PALETTE.len() - 1) as f64;
+                let index = (position as usize).min(PALETTE.len() - 2);
+
+                canvas[canvas_index(x, y)] =
+                    mix(PALETTE[index], PALETTE[index + 1], position - index as f64);
+            }
+        }
+    }
+}
+
+/// Returns the index of the cell at (`x`, `y`) of the heat buffer, which holds one more
+/// row than the canvas
+fn heat_index(x: usize, y: usize) -> usize {
+    x * (constants::CANVAS_HEIGHT + 1) + y
+}
@@ -0,0 +1,127 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Drops of light, that rain down the columns of the canvas and leave a fading trail
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{mix, to_rgba, NativeEffect, Parameters};
+use crate::constants;
+use crate::hwdevices::RGBA;
+use crate::util::canvas_index;
+
+/// The number of rows that a drop falls per second, at a speed of 1.0
+const ROWS_PER_SEC: f64 = 4.0;
+
+struct Drop {
+    /// Row of the head of the drop, negative values are above the canvas
+    y: f64,
+
+    /// Individual speed factor of the drop
+    velocity: f64,
+}
+
+pub struct Matrix {
+    rng: StdRng,
+    drops: Vec<Drop>,
+
+    color_background: u32,
+    color_head: u32,
+    color_trail: u32,
+    trail_length: f64,
+    speed: f64,
+}
+
+impl Matrix {
+    pub fn new() -> Self {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let drops = (0..constants::CANVAS_WIDTH)
+            .map(|_| Self::new_drop(&mut rng))
+            .collect();
+
+        Self {
+            rng,
+            drops,
+
+            color_background: 0xff000000,
+            color_head: 0xffb0ffb0,
+            color_trail: 0xff00c020,
+            trail_length: 4.0,
+            speed: 1.0,
+        }
+    }
+
+    fn new_drop(rng: &mut StdRng) -> Drop {
+        Drop {
+            y: -rng.gen_range(0.0..constants::CANVAS_HEIGHT as f64 * 2.0),
+            velocity: rng.gen_range(0.5..1.5),
+        }
+    }
+}
+
+impl NativeEffect for Matrix {
+    fn set_parameters(&mut self, parameters: &Parameters) {
+        self.color_background = parameters
+            .get_color("color_background")
+            .unwrap_or(self.color_background);
+        self.color_head = parameters
+            .get_color("color_head")
+            .unwrap_or(self.color_head);
+        self.color_trail = parameters
+            .get_color("color_trail")
+            .unwrap_or(self.color_trail);
+        self.trail_length = parameters
+            .get_float("trail_length")
+            .unwrap_or(self.trail_length)
+            .max(1.0);
+        self.speed = parameters.get_float("speed").unwrap_or(self.speed).max(0.0);
+    }
+
+    fn render(&mut self, _ticks: u32, canvas: &mut [RGBA]) {
+        let step = self.speed * ROWS_PER_SEC / constants::TARGET_FPS as f64;
+
+        for (x, drop) in self.drops.iter_mut().enumerate() {
+            drop.y += step * drop.velocity;
+
+            if drop.y - self.trail_length > constants::CANVAS_HEIGHT as f64 {
+                *drop = Self::new_drop(&mut self.rng);
+            }
+
+            for y in 0..constants::CANVAS_HEIGHT {
+                let distance = drop.y - y as f64;
+
+                canvas[canvas_index(x, y)] = if (0.0..1.0).contains(&distance) {
+                    mix(self.color_trail, self.color_head, 1.0 - distance)
+                } else if (1.0..self.trail_length).contains(&distance) {
+                    mix(
+                        self.color_trail,
+                        self.color_background,
+                        distance / self.trail_length,
+                    )
+                } else {
+                    to_rgba(self.color_background)
+                };
+            }
+        }
+    }
+}
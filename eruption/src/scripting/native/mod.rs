@@ -0,0 +1,251 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Evaluation of `native` effects. These effects are implemented in Rust and need no
+//! Lua script, they are selected by the `native_effect` field of a manifest, e.g.:
+//!
+//! ```toml
+//! type = 'native'
+//! native_effect = 'starfield'
+//! ```
+//!
+//! Native effects render directly onto the canvas of their thread, input events are
+//! acknowledged but otherwise ignored
+
+use flume::Receiver;
+use log::*;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::hwdevices::RGBA;
+use crate::scripting::callbacks;
+use crate::scripting::inspect;
+use crate::scripting::manifest::NativeEffectKind;
+use crate::scripting::parameters::{PlainParameter, TypedValue};
+use crate::scripting::script::{
+    self, Message, RunScriptResult, LOCAL_LED_MAP, LOCAL_LED_MAP_MODIFIED,
+};
+
+mod fire;
+mod matrix;
+mod plasma;
+mod starfield;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// An effect that is implemented in Rust
+pub trait NativeEffect: Send {
+    /// Apply the parameters of the effect, parameters that the effect does not know
+    /// about are ignored
+    fn set_parameters(&mut self, parameters: &Parameters);
+
+    /// Render the frame `ticks` onto `canvas`, which holds `constants::CANVAS_SIZE` cells
+    fn render(&mut self, ticks: u32, canvas: &mut [RGBA]);
+}
+
+/// Instantiate the effect `kind`, with its default parameters
+pub fn new_effect(kind: NativeEffectKind) -> Box<dyn NativeEffect> {
+    match kind {
+        NativeEffectKind::Starfield => Box::new(starfield::Starfield::new()),
+        NativeEffectKind::Plasma => Box::new(plasma::Plasma::new()),
+        NativeEffectKind::Matrix => Box::new(matrix::Matrix::new()),
+        NativeEffectKind::Fire => Box::new(fire::Fire::new()),
+    }
+}
+
+/// Runs the `native` effect `kind` of `script_file` until it is unloaded
+pub fn run_native(
+    thread_idx: usize,
+    script_file: &Path,
+    kind: NativeEffectKind,
+    parameter_values: &mut BTreeMap<String, PlainParameter>,
+    rx: &Receiver<Message>,
+) -> Result<RunScriptResult> {
+    info!("Running native effect: {}", kind);
+
+    inspect::begin(thread_idx, script_file, parameter_values.values());
+
+    let mut effect = new_effect(kind);
+    effect.set_parameters(&Parameters(parameter_values));
+
+    loop {
+        let Ok(msg) = rx.recv() else {
+            return Ok(RunScriptResult::TerminatedGracefully);
+        };
+
+        match msg {
+            Message::Tick(ticks) => {
+                LOCAL_LED_MAP.with(|local_map| effect.render(ticks, &mut local_map.borrow_mut()));
+                LOCAL_LED_MAP_MODIFIED.with(|f| *f.borrow_mut() = true);
+            }
+
+            Message::RealizeColorMap(index) => {
+                script::realize_color_map(index)?;
+            }
+
+            Message::Quit(_) => script::signal_quit_completed(),
+
+            Message::Unload => {
+                inspect::stopped(thread_idx);
+
+                return Ok(RunScriptResult::TerminatedGracefully);
+            }
+
+            Message::SetParameters {
+                parameter_values: new_parameter_values,
+            } => {
+                new_parameter_values.iter().for_each(|pv| {
+                    parameter_values.insert(pv.name.clone(), pv.clone());
+                });
+
+                inspect::update_parameters(thread_idx, &new_parameter_values);
+
+                effect.set_parameters(&Parameters(parameter_values));
+            }
+
+            // native effects do not react to input events, but the event dispatch waits
+            // until all VMs have processed them
+            msg => script::acknowledge_input_event(msg),
+        }
+    }
+}
+
+/// Typed access to the parameters of a native effect
+pub struct Parameters<'a>(pub &'a BTreeMap<String, PlainParameter>);
+
+impl Parameters<'_> {
+    pub fn get_float(&self, name: &str) -> Option<f64> {
+        match self.0.get(name)?.value {
+            TypedValue::Float(value) => Some(value),
+            TypedValue::Int(value) => Some(value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn get_color(&self, name: &str) -> Option<u32> {
+        match self.0.get(name)?.value {
+            TypedValue::Color(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Convert a 32 bits color value to an `RGBA` cell of the canvas
+fn to_rgba(color: u32) -> RGBA {
+    let (r, g, b, a) = callbacks::color_to_rgba(color);
+
+    RGBA { r, g, b, a }
+}
+
+/// Linearly interpolate between the colors `from` and `to`, `t` is in the range 0.0 .. 1.0
+fn mix(from: u32, to: u32, t: f64) -> RGBA {
+    let (r1, g1, b1, a1) = callbacks::color_to_rgba(from);
+    let (r2, g2, b2, a2) = callbacks::color_to_rgba(to);
+    let t = t.clamp(0.0, 1.0);
+
+    let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+
+    RGBA {
+        r: lerp(r1, r2),
+        g: lerp(g1, g2),
+        b: lerp(b1, b2),
+        a: lerp(a1, a2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{new_effect, run_native, NativeEffectKind, Parameters};
+    use crate::constants;
+    use crate::hwdevices::RGBA;
+    use crate::scripting::script::Message;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn input_events_are_acknowledged() {
+        let (tx, rx) = flume::unbounded();
+
+        *crate::UPCALL_COMPLETED_ON_KEY_DOWN.0.lock() = 1;
+
+        let handle = std::thread::spawn(move || {
+            run_native(
+                usize::MAX,
+                Path::new("native-test.lua"),
+                NativeEffectKind::Plasma,
+                &mut BTreeMap::new(),
+                &rx,
+            )
+        });
+
+        tx.send(Message::KeyDown(1)).unwrap();
+
+        let (reply_tx, reply_rx) = flume::bounded(1);
+        tx.send(Message::InputFilter(30, 1, reply_tx)).unwrap();
+
+        assert_eq!(
+            reply_rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            crate::input_filter::Verdict::Pass
+        );
+
+        tx.send(Message::Unload).unwrap();
+        handle.join().unwrap().unwrap();
+
+        assert_eq!(*crate::UPCALL_COMPLETED_ON_KEY_DOWN.0.lock(), 0);
+    }
+
+    #[test]
+    fn effects_render_the_whole_canvas() {
+        for kind in [
+            NativeEffectKind::Starfield,
+            NativeEffectKind::Plasma,
+            NativeEffectKind::Matrix,
+            NativeEffectKind::Fire,
+        ] {
+            let mut effect = new_effect(kind);
+            effect.set_parameters(&Parameters(&BTreeMap::new()));
+
+            let mut canvas = vec![
+                RGBA {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: 0,
+                };
+                constants::CANVAS_SIZE
+            ];
+
+            for ticks in 0..(constants::TARGET_FPS as u32 * 4) {
+                effect.render(ticks, &mut canvas);
+            }
+
+            assert!(
+                canvas.iter().all(|c| c.a > 0),
+                "{kind} left cells of the canvas transparent"
+            );
+            assert!(
+                canvas.iter().any(|c| c.r > 0 || c.g > 0 || c.b > 0),
+                "{kind} rendered a black canvas"
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Overlapping sine waves, mapped onto a slowly rotating range of hues
+
+use super::{to_rgba, NativeEffect, Parameters};
+use crate::constants;
+use crate::hwdevices::RGBA;
+use crate::scripting::callbacks;
+use crate::util::canvas_index;
+
+pub struct Plasma {
+    speed: f64,
+    scale: f64,
+    saturation: f64,
+    lightness: f64,
+}
+
+impl Plasma {
+    pub fn new() -> Self {
+        Self {
+            speed: 1.0,
+            scale: 4.0,
+            saturation: 1.0,
+            lightness: 0.5,
+        }
+    }
+}
+
+impl NativeEffect for Plasma {
+    fn set_parameters(&mut self, parameters: &Parameters) {
+        self.speed = parameters.get_float("speed").unwrap_or(self.speed);
+        self.scale = parameters
+            .get_float("scale")
+            .unwrap_or(self.scale)
+            .max(f64::EPSILON);
+        self.saturation = parameters
+            .get_float("saturation")
+            .unwrap_or(self.saturation)
+            .clamp(0.0, 1.0);
+        self.lightness = parameters
+            .get_float("lightness")
+            .unwrap_or(self.lightness)
+            .clamp(0.0, 1.0);
+    }
+
+    fn render(&mut self, ticks: u32, canvas: &mut [RGBA]) {
+        let time = ticks as f64 / constants::TARGET_FPS as f64 * self.speed;
+
+        for y in 0..constants::CANVAS_HEIGHT {
+            for x in 0..constants::CANVAS_WIDTH {
+                let (fx, fy) = (x as f64 / self.scale, y as f64 / self.scale);
+
+                let value = (fx + time).sin()
+                    + ((fy + time) * 0.5).sin()
+                    + ((fx + fy + time) * 0.5).sin()
+                    + ((fx * fx + fy * fy).sqrt() + time).sin();
+
+                // value is in the range -4.0 .. 4.0, map it onto the hue circle
+                let hue = ((value + 4.0) * 45.0 + time * 10.0) % 360.0;
+
+                canvas[canvas_index(x, y)] = to_rgba(callbacks::hsl_to_color(
+                    hue,
+                    self.saturation,
+                    self.lightness,
+                ));
+            }
+        }
+    }
+}
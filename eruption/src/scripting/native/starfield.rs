@@ -0,0 +1,106 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Stars that twinkle in and out of existence, on a dark background
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{mix, to_rgba, NativeEffect, Parameters};
+use crate::constants;
+use crate::hwdevices::RGBA;
+
+/// The time it takes for a star to fade in and out again, at a speed of 1.0
+const STAR_LIFETIME_SECS: f64 = 2.0;
+
+struct Star {
+    index: usize,
+
+    /// Progress through the lifetime of the star, in the range 0.0 .. 1.0
+    age: f64,
+}
+
+pub struct Starfield {
+    rng: StdRng,
+    stars: Vec<Star>,
+
+    color_background: u32,
+    color_star: u32,
+    density: f64,
+    speed: f64,
+}
+
+impl Starfield {
+    pub fn new() -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(0),
+            stars: vec![],
+
+            color_background: 0xff00000a,
+            color_star: 0xffffffff,
+            density: 0.1,
+            speed: 1.0,
+        }
+    }
+}
+
+impl NativeEffect for Starfield {
+    fn set_parameters(&mut self, parameters: &Parameters) {
+        self.color_background = parameters
+            .get_color("color_background")
+            .unwrap_or(self.color_background);
+        self.color_star = parameters
+            .get_color("color_star")
+            .unwrap_or(self.color_star);
+        self.density = parameters
+            .get_float("density")
+            .unwrap_or(self.density)
+            .clamp(0.0, 1.0);
+        self.speed = parameters.get_float("speed").unwrap_or(self.speed).max(0.0);
+    }
+
+    fn render(&mut self, _ticks: u32, canvas: &mut [RGBA]) {
+        let step = self.speed / (STAR_LIFETIME_SECS * constants::TARGET_FPS as f64);
+
+        self.stars.iter_mut().for_each(|star| star.age += step);
+        self.stars.retain(|star| star.age < 1.0);
+
+        // spawn new stars at a rate that keeps the desired number of stars alive
+        let target = self.density * canvas.len() as f64;
+        let rate = target * step;
+
+        if (self.stars.len() as f64) < target && self.rng.gen_bool(rate.min(1.0)) {
+            let index = self.rng.gen_range(0..canvas.len());
+
+            if !self.stars.iter().any(|star| star.index == index) {
+                self.stars.push(Star { index, age: 0.0 });
+            }
+        }
+
+        canvas.fill(to_rgba(self.color_background));
+
+        for star in self.stars.iter() {
+            let brightness = (star.age * std::f64::consts::PI).sin();
+
+            canvas[star.index] = mix(self.color_background, self.color_star, brightness);
+        }
+    }
+}
@@ -54,11 +54,13 @@ pub enum ManifestValue {
         default: i64,
         min: Option<i64>,
         max: Option<i64>,
+        step: Option<i64>,
     },
     Float {
         default: f64,
         min: Option<f64>,
         max: Option<f64>,
+        step: Option<f64>,
     },
     Bool {
         default: bool,
@@ -71,6 +73,26 @@ pub enum ManifestValue {
         min: Option<u32>,
         max: Option<u32>,
     },
+    /// A string that is restricted to one of the listed variants
+    Enum {
+        default: String,
+        variants: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ParameterError {
+    #[error("Type mismatch, expected a value of type '{expected}'")]
+    TypeMismatch { expected: String },
+
+    #[error("Value {value} is out of range")]
+    OutOfRange { value: String },
+
+    #[error("Value {value} is not a multiple of the step size {step}")]
+    InvalidStep { value: String, step: String },
+
+    #[error("Invalid value '{value}', expected one of: {variants}")]
+    InvalidVariant { value: String, variants: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd)]
@@ -112,6 +134,118 @@ impl ManifestValue {
             Self::Bool { default, .. } => TypedValue::Bool(default.to_owned()),
             Self::String { default, .. } => TypedValue::String(default.to_owned()),
             Self::Color { default, .. } => TypedValue::Color(default.to_owned()),
+            Self::Enum { default, .. } => TypedValue::String(default.to_owned()),
+        }
+    }
+
+    pub fn get_type_name(&self) -> &'static str {
+        match &self {
+            Self::Int { .. } => "int",
+            Self::Float { .. } => "float",
+            Self::Bool { .. } => "bool",
+            Self::String { .. } => "string",
+            Self::Color { .. } => "color",
+            Self::Enum { .. } => "enum",
+        }
+    }
+
+    /// Returns a human readable description of the constraints of the parameter, if any
+    #[allow(dead_code)] // only used by eruptionctl, which shares this module
+    pub fn get_constraints(&self) -> Option<String> {
+        fn range<T: fmt::Display>(min: &Option<T>, max: &Option<T>, step: &Option<T>) -> String {
+            let mut result = format!(
+                "{}..{}",
+                min.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+                max.as_ref().map(|v| v.to_string()).unwrap_or_default()
+            );
+
+            if let Some(step) = step {
+                result.push_str(&format!(", step {}", step));
+            }
+
+            result
+        }
+
+        match &self {
+            Self::Int { min, max, step, .. }
+                if min.is_some() || max.is_some() || step.is_some() =>
+            {
+                Some(range(min, max, step))
+            }
+
+            Self::Float { min, max, step, .. }
+                if min.is_some() || max.is_some() || step.is_some() =>
+            {
+                Some(range(min, max, step))
+            }
+
+            Self::Enum { variants, .. } => Some(variants.join(", ")),
+
+            _ => None,
+        }
+    }
+
+    /// Verify that `value` is of the declared type and satisfies the declared constraints
+    pub fn validate(&self, value: &TypedValue) -> Result<(), ParameterError> {
+        let out_of_range = || ParameterError::OutOfRange {
+            value: value.to_string(),
+        };
+
+        match (&self, value) {
+            (Self::Int { min, max, step, .. }, TypedValue::Int(v)) => {
+                if min.is_some_and(|min| *v < min) || max.is_some_and(|max| *v > max) {
+                    return Err(out_of_range());
+                }
+
+                if let Some(step) = step.filter(|step| *step > 0) {
+                    if (*v - min.unwrap_or(0)) % step != 0 {
+                        return Err(ParameterError::InvalidStep {
+                            value: v.to_string(),
+                            step: step.to_string(),
+                        });
+                    }
+                }
+
+                Ok(())
+            }
+
+            (Self::Float { min, max, step, .. }, TypedValue::Float(v)) => {
+                if min.is_some_and(|min| *v < min) || max.is_some_and(|max| *v > max) {
+                    return Err(out_of_range());
+                }
+
+                if let Some(step) = step.filter(|step| *step > 0.0) {
+                    let steps = (*v - min.unwrap_or(0.0)) / step;
+
+                    if (steps - steps.round()).abs() > 1e-6 {
+                        return Err(ParameterError::InvalidStep {
+                            value: v.to_string(),
+                            step: step.to_string(),
+                        });
+                    }
+                }
+
+                Ok(())
+            }
+
+            (Self::Enum { variants, .. }, TypedValue::String(v)) => {
+                if variants.contains(v) {
+                    Ok(())
+                } else {
+                    Err(ParameterError::InvalidVariant {
+                        value: v.to_owned(),
+                        variants: variants.join(", "),
+                    })
+                }
+            }
+
+            (Self::Bool { .. }, TypedValue::Bool(_))
+            | (Self::String { .. }, TypedValue::String(_))
+            | (Self::Color { .. }, TypedValue::Color(_)) => Ok(()),
+
+            _ => Err(ParameterError::TypeMismatch {
+                expected: self.get_type_name().to_string(),
+            }),
         }
     }
 }
@@ -431,8 +565,8 @@ mod tests {
     use std::error::Error;
 
     use super::{
-        ManifestConfiguration, ManifestParameter, ManifestValue, ProfileConfiguration,
-        ProfileParameter, TypedValue,
+        ManifestConfiguration, ManifestParameter, ManifestValue, ParameterError,
+        ProfileConfiguration, ProfileParameter, TypedValue,
     };
 
     #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -522,6 +656,7 @@ value = 64
                         default: 1.23,
                         min: Some(0.0),
                         max: None,
+                        step: None,
                     },
                 },
             ]
@@ -551,6 +686,58 @@ min = 0.0
         let de_test_container = toml::de::from_str::<ManifestTestContainer>(&toml)?;
         assert_eq!(test_container, de_test_container);
 
+        Ok(())
+    }
+    #[test]
+    fn typed_manifest_parameters() -> Result<(), Box<dyn Error>> {
+        let toml = r#"
+[[config]]
+name = "mode"
+description = "an enum parameter"
+type = "enum"
+default = "wave"
+variants = ["wave", "pulse", "static"]
+
+[[config]]
+name = "speed"
+description = "an int range parameter"
+type = "int"
+default = 10
+min = 0
+max = 100
+step = 5
+        "#;
+
+        let test_container = toml::de::from_str::<ManifestTestContainer>(toml)?;
+
+        let mode = test_container.config.get_parameter("mode").unwrap();
+        assert_eq!(mode.get_default(), TypedValue::String("wave".to_string()));
+        assert_eq!(
+            mode.manifest
+                .validate(&TypedValue::String("pulse".to_string())),
+            Ok(())
+        );
+        assert!(matches!(
+            mode.manifest
+                .validate(&TypedValue::String("sparkle".to_string())),
+            Err(ParameterError::InvalidVariant { .. })
+        ));
+
+        let speed = test_container.config.get_parameter("speed").unwrap();
+        assert_eq!(speed.manifest.validate(&TypedValue::Int(25)), Ok(()));
+        assert!(matches!(
+            speed.manifest.validate(&TypedValue::Int(27)),
+            Err(ParameterError::InvalidStep { .. })
+        ));
+        assert!(matches!(
+            speed.manifest.validate(&TypedValue::Int(105)),
+            Err(ParameterError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            speed.manifest.validate(&TypedValue::Bool(true)),
+            Err(ParameterError::TypeMismatch { .. })
+        ));
+
         Ok(())
     }
 }
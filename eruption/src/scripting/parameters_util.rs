@@ -26,6 +26,8 @@ use std::str::FromStr;
 use std::sync::atomic::Ordering;
 
 use crate::{
+    color_scheme::ColorScheme,
+    color_transitions, constants,
     profiles::Profile,
     script,
     scripting::manifest::Manifest,
@@ -45,6 +47,15 @@ pub enum ParametersUtilError {
 
     #[error("Script manifest does not reference the parameter")]
     NoSuchParameter {},
+
+    #[error("No such color scheme")]
+    NoSuchColorScheme {},
+
+    #[error("Profile is not active")]
+    ProfileNotActive {},
+
+    #[error("No profile is active")]
+    NoActiveProfile {},
 }
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -103,6 +114,18 @@ fn update_profile_and_state_file(
     profile: &mut Profile,
     script_path: &Path,
     parameter_values: &[UntypedParameter],
+) -> Result<Vec<PlainParameter>> {
+    let new_parameters = update_profile_parameters(profile, script_path, parameter_values)?;
+
+    profile.save_params()?;
+
+    Ok(new_parameters)
+}
+
+fn update_profile_parameters(
+    profile: &mut Profile,
+    script_path: &Path,
+    parameter_values: &[UntypedParameter],
 ) -> Result<Vec<PlainParameter>> {
     let manifest = profile
         .manifests
@@ -146,9 +169,195 @@ fn update_profile_and_state_file(
         }
     }
 
+    Ok(new_parameters)
+}
+
+/// Rebind the gradient and color parameters of all scripts of a profile to the
+/// named color scheme `scheme_name`. If the profile is the active profile, the new
+/// values are sent to the running Lua VMs immediately. The profile state is only
+/// written to disk if `persist` is set, so an inactive profile requires `persist`
+pub fn apply_color_scheme(profile_file: &str, scheme_name: &str, persist: bool) -> Result<()> {
+    let colors = crate::NAMED_COLOR_SCHEMES
+        .read()
+        .get(scheme_name)
+        .map(|color_scheme| color_scheme.colors.clone())
+        .ok_or(ParametersUtilError::NoSuchColorScheme {})?;
+
+    let profile_path = PathBuf::from(&profile_file);
+
+    // If the specified profile_file is for the active profile, update that directly.
+    {
+        let active_profile = &mut *crate::ACTIVE_PROFILE.lock();
+        if let Some(active_profile) = active_profile.as_mut() {
+            if is_same_file(&active_profile.profile_file, &profile_path) {
+                let scripts = color_scheme_parameters(active_profile, scheme_name, &colors);
+
+                for (script_path, parameter_values) in scripts {
+                    let new_parameters =
+                        update_profile_parameters(active_profile, &script_path, &parameter_values)?;
+                    update_parameters_on_active_profile(&script_path, new_parameters)?;
+                }
+
+                if persist {
+                    active_profile.save_params()?;
+                }
+
+                return Ok(());
+            }
+        }
+    }
+
+    if !persist {
+        return Err(ParametersUtilError::ProfileNotActive {}.into());
+    }
+
+    // Otherwise, load the profile including all manifests and modify that
+    let mut profile = match Profile::load_fully(&profile_path) {
+        Ok(profile) => profile,
+        Err(e) => {
+            error!("Could not open profile file: {}", e);
+            return Err(ParametersUtilError::OpenError {}.into());
+        }
+    };
+
+    let scripts = color_scheme_parameters(&profile, scheme_name, &colors);
+
+    for (script_path, parameter_values) in scripts {
+        update_profile_parameters(&mut profile, &script_path, &parameter_values)?;
+    }
+
     profile.save_params()?;
 
-    Ok(new_parameters)
+    Ok(())
+}
+
+/// Replace the colors of the named color scheme `scheme_name` and rebind the active
+/// profile to it, without saving anything, so that the changes are shown on the
+/// hardware immediately. The original color scheme is retained until the preview
+/// is either committed or rolled back
+pub fn preview_color_scheme(scheme_name: &str, color_scheme: ColorScheme) -> Result<()> {
+    color_transitions::cancel(scheme_name);
+
+    {
+        let mut previews = crate::COLOR_SCHEME_PREVIEWS.write();
+        let original = crate::NAMED_COLOR_SCHEMES
+            .write()
+            .insert(scheme_name.to_owned(), color_scheme);
+
+        // only the state before the first preview is of interest
+        previews.entry(scheme_name.to_owned()).or_insert(original);
+    }
+
+    let profile_file = active_profile_file()?;
+
+    apply_color_scheme(&profile_file.to_string_lossy(), scheme_name, false)
+}
+
+/// Make the previewed color scheme `scheme_name` permanent, and persist the parameters
+/// of the active profile. Returns `false` if no preview of the color scheme is pending
+pub fn commit_color_scheme(scheme_name: &str) -> Result<bool> {
+    if crate::COLOR_SCHEME_PREVIEWS
+        .write()
+        .remove(scheme_name)
+        .is_none()
+    {
+        return Ok(false);
+    }
+
+    crate::state::save_color_schemes()?;
+
+    let profile_file = active_profile_file()?;
+    apply_color_scheme(&profile_file.to_string_lossy(), scheme_name, true)?;
+
+    Ok(true)
+}
+
+/// Discard the preview of the color scheme `scheme_name` and restore the original
+/// color scheme. The active profile is reloaded, so that the parameters revert to
+/// their saved values. Returns `false` if no preview of the color scheme is pending
+pub fn rollback_color_scheme(scheme_name: &str) -> Result<bool> {
+    let original = match crate::COLOR_SCHEME_PREVIEWS.write().remove(scheme_name) {
+        Some(original) => original,
+        None => return Ok(false),
+    };
+
+    color_transitions::cancel(scheme_name);
+
+    {
+        let mut color_schemes = crate::NAMED_COLOR_SCHEMES.write();
+
+        match original {
+            Some(color_scheme) => {
+                color_schemes.insert(scheme_name.to_owned(), color_scheme);
+            }
+
+            None => {
+                color_schemes.remove(scheme_name);
+            }
+        }
+    }
+
+    crate::REQUEST_PROFILE_RELOAD.store(true, Ordering::SeqCst);
+
+    Ok(true)
+}
+
+fn active_profile_file() -> Result<PathBuf> {
+    crate::ACTIVE_PROFILE
+        .lock()
+        .as_ref()
+        .map(|profile| profile.profile_file.clone())
+        .ok_or_else(|| ParametersUtilError::NoActiveProfile {}.into())
+}
+
+/// Collect the parameters of each script of the profile that can be rebound to a color
+/// scheme: String parameters that select a gradient receive the name of the color
+/// scheme, Color parameters receive the colors of the scheme in order
+fn color_scheme_parameters(
+    profile: &Profile,
+    scheme_name: &str,
+    colors: &[csscolorparser::Color],
+) -> Vec<(PathBuf, Vec<UntypedParameter>)> {
+    let mut result = Vec::new();
+
+    for manifest in profile.manifests.values() {
+        let mut parameter_values = Vec::new();
+        let mut color_index = 0;
+
+        for manifest_param in manifest.config.iter() {
+            match &manifest_param.manifest {
+                ManifestValue::String { .. }
+                    if constants::GRADIENT_PARAMETER_NAMES
+                        .contains(&manifest_param.name.as_str()) =>
+                {
+                    parameter_values.push(UntypedParameter {
+                        name: manifest_param.name.to_owned(),
+                        value: scheme_name.to_owned(),
+                    });
+                }
+
+                ManifestValue::Color { .. } if !colors.is_empty() => {
+                    let (r, g, b, a) = colors[color_index % colors.len()].to_linear_rgba_u8();
+                    color_index += 1;
+
+                    let value = u32::from_be_bytes([a, r, g, b]);
+
+                    parameter_values.push(UntypedParameter {
+                        name: manifest_param.name.to_owned(),
+                        value: format!("{}", value),
+                    });
+                }
+
+                _ => (),
+            }
+        }
+
+        if !parameter_values.is_empty() {
+            result.push((manifest.script_file.clone(), parameter_values));
+        }
+    }
+
+    result
 }
 
 fn update_parameters_on_active_profile(
@@ -181,7 +390,9 @@ fn parse_new_profile_parameter(
         ManifestValue::Bool { .. } => {
             TypedValue::Bool(bool::from_str(&val.to_string().to_lowercase())?)
         }
-        ManifestValue::String { .. } => TypedValue::String(val.to_owned()),
+        ManifestValue::String { .. } | ManifestValue::Enum { .. } => {
+            TypedValue::String(val.to_owned())
+        }
         ManifestValue::Color { .. } => {
             if &val[0..1] == "#" {
                 TypedValue::Color(u32::from_str_radix(&val[1..], 16)?)
@@ -191,6 +402,8 @@ fn parse_new_profile_parameter(
         }
     };
 
+    manifest_parameter.manifest.validate(&typed_value)?;
+
     Ok(ProfileParameter {
         name: manifest_parameter.name.to_owned(),
         value: typed_value,
@@ -19,26 +19,30 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
-use flume::Receiver;
+use flume::{Receiver, Sender};
 use lazy_static::lazy_static;
 use log::*;
 use mlua::prelude::*;
+use mlua::FromLuaMulti;
 use mlua::Function;
 use mlua::ToLuaMulti;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::path::Path;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use std::vec::Vec;
 
 use crate::{
-    constants, hwdevices::KeyboardHidEvent, hwdevices::MouseHidEvent, hwdevices::RGBA,
-    scripting::callbacks, scripting::constants::*,
+    animation::Animation, constants, hwdevices::KeyboardHidEvent, hwdevices::MouseHidEvent,
+    hwdevices::RGBA, indicators::LockState, input_filter, scripting::blackboard,
+    scripting::bytecode_cache, scripting::callbacks, scripting::constants::*, scripting::inspect,
+    scripting::inspect::ScriptProfiler, util::ratelimited,
 };
 
 use super::parameters::PlainParameter;
@@ -55,6 +59,17 @@ pub enum Message {
     // Keyboard events
     KeyDown(u8),
     KeyUp(u8),
+    Chord(String),
+
+    // Input filters: evdev key code, value and the channel to send the verdict to
+    InputFilter(u32, i32, Sender<input_filter::Verdict>),
+
+    // Routed input events
+    Route(String, u64, u8),
+
+    // Lock indicators and keyboard layout
+    LockStateChanged(LockState),
+    LayoutChanged(u32, String),
 
     // HID events
     KeyboardHidEvent(KeyboardHidEvent),
@@ -66,12 +81,27 @@ pub enum Message {
     MouseMove(i32, i32, i32),
     MouseWheelEvent(u8),
 
+    // Headset events: device, event type, payload
+    HeadsetEvent(u64, u8, u32),
+
+    // Hotplug events: device class, stable device identifier
+    DeviceAttached(String, String),
+    DeviceRemoved(String, String),
+
+    // Session events: class, title and app_id of the focused window
+    WindowFocused(String, String, String),
+
+    // Laptop events: state of the lid, DRM connectors of the external displays
+    LidChanged(bool),
+    DisplaysChanged(Vec<String>),
+
     //LoadScript(PathBuf),
     // Abort,
     Unload,
 
-    /// blend LOCAL_LED_MAP with LED_MAP ("realize" the color map)
-    RealizeColorMap,
+    /// submit LOCAL_LED_MAP as the layer with the given index, the layers are
+    /// composed onto LED_MAP by the device I/O thread ("realize" the color map)
+    RealizeColorMap(usize),
 
     SetParameters {
         parameter_values: Vec<PlainParameter>,
@@ -95,8 +125,49 @@ lazy_static! {
         a: 0x00,
     }; constants::CANVAS_SIZE]));
 
+    /// The color maps that have been submitted by the Lua VMs for the current frame, indexed by
+    /// the position of the VM in LUA_TXS. All VMs submit their layers in parallel, the layers are
+    /// then composed in order, so the result does not depend on the order of completion
+    pub static ref LAYERS: Arc<Mutex<Vec<Layer>>> = Arc::new(Mutex::new(vec![]));
+
     /// Frame generation counter, used to detect if we need to submit the LED_MAP to the hardware
     pub static ref FRAME_GENERATION_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    /// Auxiliary surface, that is sent to all devices that feature an LCD/OLED screen
+    pub static ref DISPLAY_FRAMEBUFFER: Arc<RwLock<Vec<RGBA>>> = Arc::new(RwLock::new(vec![RGBA {
+        r: 0x00,
+        g: 0x00,
+        b: 0x00,
+        a: 0x00,
+    }; constants::DISPLAY_WIDTH * constants::DISPLAY_HEIGHT]));
+
+    /// Set to true, if the display surface has been submitted and needs to be sent to the hardware
+    pub static ref DISPLAY_FRAMEBUFFER_MODIFIED: AtomicBool = AtomicBool::new(false);
+}
+
+/// The color map of a single Lua VM
+#[derive(Debug, Clone)]
+pub struct Layer {
+    /// Set if the VM submitted its color map for the current frame
+    pub submitted: bool,
+    pub led_map: Vec<RGBA>,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            submitted: false,
+            led_map: vec![
+                RGBA {
+                    r: 0x00,
+                    g: 0x00,
+                    b: 0x00,
+                    a: 0x00,
+                };
+                constants::CANVAS_SIZE
+            ],
+        }
+    }
 }
 
 thread_local! {
@@ -113,6 +184,14 @@ thread_local! {
 
     /// Vec of allocated gradient objects
     pub static ALLOCATED_GRADIENTS: RefCell<HashMap<usize, colorgrad::Gradient>> = RefCell::new(HashMap::new());
+
+    /// Gradients that have been created from a named color scheme, along with the
+    /// generation of the color schemes they have been built from
+    pub static BOUND_GRADIENTS: RefCell<HashMap<usize, (String, u64)>> = RefCell::new(HashMap::new());
+
+    /// Running animations, indexed by the name of the global variable they animate,
+    /// along with the point in time when they have been started
+    pub static ANIMATIONS: RefCell<HashMap<String, (Animation, Instant)>> = RefCell::new(HashMap::new());
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -159,12 +238,17 @@ pub enum RunningScriptResult {
 }
 
 struct RunningScriptCallHelper<'lua> {
+    thread_idx: usize,
     file_name: String,
     lua_ctx: &'lua Lua,
     lua_functions: HashMap<String, Option<Function<'lua>>>,
     skip_on_tick: bool,
     skip_on_mouse_move: bool,
     skip_on_hid_event: bool,
+    profiler: ScriptProfiler,
+
+    /// The generation of the blackboard that the script has been notified of
+    blackboard_generation: u64,
 }
 
 enum RunningScriptCallHelperResult {
@@ -173,14 +257,17 @@ enum RunningScriptCallHelperResult {
 }
 
 impl<'lua> RunningScriptCallHelper<'lua> {
-    fn new(file: &Path, lua_ctx: &'lua Lua) -> RunningScriptCallHelper<'lua> {
+    fn new(thread_idx: usize, file: &Path, lua_ctx: &'lua Lua) -> RunningScriptCallHelper<'lua> {
         RunningScriptCallHelper {
+            thread_idx,
             file_name: file.to_string_lossy().to_string(),
             lua_ctx,
             lua_functions: HashMap::new(),
             skip_on_tick: false,
             skip_on_mouse_move: false,
             skip_on_hid_event: false,
+            profiler: ScriptProfiler::new(),
+            blackboard_generation: blackboard::generation(),
         }
     }
 
@@ -189,19 +276,55 @@ impl<'lua> RunningScriptCallHelper<'lua> {
         function_name: &str,
         args: Args,
     ) -> Result<RunningScriptCallHelperResult> {
-        match self.find_handler(function_name) {
-            Some(handler) => match handler.call::<Args, ()>(args) {
-                Ok(()) => Ok(RunningScriptCallHelperResult::Successful),
-                Err(e) => {
-                    let error = e.source().unwrap_or(&UnknownError {});
-                    error!("Lua error in file {}: {}\n\t{:?}", self.file_name, e, error);
-                    Err(ScriptingError::HandlerError {}.into())
-                }
-            },
+        match self.call_returning::<Args, ()>(function_name, args)? {
+            Some(()) => Ok(RunningScriptCallHelperResult::Successful),
             None => Ok(RunningScriptCallHelperResult::NoHandler),
         }
     }
 
+    /// Call the handler `function_name` and return its result, or `None` if the script
+    /// does not define the handler
+    fn call_returning<Args: ToLuaMulti<'lua>, R: FromLuaMulti<'lua>>(
+        &mut self,
+        function_name: &str,
+        args: Args,
+    ) -> Result<Option<R>> {
+        let handler = match self.find_handler(function_name) {
+            Some(handler) => handler.clone(),
+            None => return Ok(None),
+        };
+
+        let _span = tracing::trace_span!(
+            "lua upcall",
+            label = %format_args!(
+                "{}/{}",
+                Path::new(&self.file_name)
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+                function_name
+            )
+        )
+        .entered();
+
+        let start = Instant::now();
+        let result = handler.call::<Args, R>(args);
+
+        self.profiler.record_call(function_name, start.elapsed());
+
+        match result {
+            Ok(result) => Ok(Some(result)),
+            Err(e) => {
+                let error = e.source().unwrap_or(&UnknownError {});
+                error!("Lua error in file {}: {}\n\t{:?}", self.file_name, e, error);
+
+                inspect::record_error(self.thread_idx, &e);
+
+                Err(ScriptingError::HandlerError {}.into())
+            }
+        }
+    }
+
     fn verify_handler_exists(&mut self, function_name: &str) -> bool {
         self.find_handler(function_name).is_some()
     }
@@ -231,23 +354,65 @@ impl<'lua> RunningScriptCallHelper<'lua> {
     }
 }
 
+/// Create a new Lua VM, with the support globals and functions registered
+pub fn new_vm() -> mlua::Result<Lua> {
+    let lua_ctx = unsafe { Lua::unsafe_new_with(mlua::StdLib::ALL, mlua::LuaOptions::default()) };
+
+    register_support_globals(&lua_ctx)?;
+    register_support_funcs(&lua_ctx)?;
+
+    Ok(lua_ctx)
+}
+
+/// Reset the state of the scripting engine that is local to the current thread,
+/// so that the thread may be re-used to run another script
+pub fn reset_thread_locals() {
+    LOCAL_LED_MAP.with(|local_map| {
+        local_map.borrow_mut().fill(RGBA {
+            r: 0x00,
+            g: 0x00,
+            b: 0x00,
+            a: 0x00,
+        })
+    });
+
+    LOCAL_LED_MAP_MODIFIED.with(|f| *f.borrow_mut() = false);
+    ALLOCATED_GRADIENTS.with(|gradients| gradients.borrow_mut().clear());
+    BOUND_GRADIENTS.with(|gradients| gradients.borrow_mut().clear());
+    ANIMATIONS.with(|animations| animations.borrow_mut().clear());
+}
+
 /// Loads and runs a lua script.
-/// Initializes a lua environment, loads the script and executes it
+/// Uses the pre-initialized Lua VM `lua_ctx` if one is supplied, otherwise a
+/// new lua environment is initialized. Then loads the script and executes it
 pub fn run_script(
+    thread_idx: usize,
     script_file: &Path,
     parameter_values: &mut BTreeMap<String, PlainParameter>,
     rx: &Receiver<Message>,
+    lua_ctx: Option<Lua>,
 ) -> Result<RunScriptResult> {
     match fs::read_to_string(script_file) {
         Ok(script) => {
-            let lua_ctx =
-                unsafe { Lua::unsafe_new_with(mlua::StdLib::ALL, mlua::LuaOptions::default()) };
+            inspect::begin(thread_idx, script_file, parameter_values.values());
+
+            let lua_ctx = match lua_ctx.map_or_else(new_vm, Ok) {
+                Ok(lua_ctx) => lua_ctx,
+
+                Err(e) => {
+                    error!(
+                        "Could not initialize the Lua VM for {}: {}",
+                        script_file.to_string_lossy(),
+                        e
+                    );
+                    return Ok(RunScriptResult::TerminatedWithErrors);
+                }
+            };
 
             // Prepare the Lua environment and eval the script
-            let prepared = register_support_globals(&lua_ctx)
-                .and_then(|()| register_support_funcs(&lua_ctx))
-                .and_then(|()| set_parameter_values(&lua_ctx, parameter_values.values()))
-                .and_then(|()| lua_ctx.load(&script).eval::<()>());
+            let prepared = set_parameter_values(&lua_ctx, parameter_values.values())
+                .and_then(|()| bytecode_cache::load_script(&lua_ctx, script_file, &script))
+                .and_then(|chunk| chunk.call::<_, ()>(()));
 
             if let Err(e) = prepared {
                 error!(
@@ -256,10 +421,25 @@ pub fn run_script(
                     e,
                     e.source().unwrap_or(&UnknownError {})
                 );
+
+                inspect::record_error(thread_idx, &e);
+
                 return Ok(RunScriptResult::TerminatedWithErrors);
             }
 
-            let mut call_helper = RunningScriptCallHelper::new(script_file, &lua_ctx);
+            let mut call_helper = RunningScriptCallHelper::new(thread_idx, script_file, &lua_ctx);
+
+            let handlers = HANDLER_FUNCTIONS
+                .iter()
+                .filter(|name| call_helper.verify_handler_exists(name))
+                .map(|name| name.to_string())
+                .collect();
+
+            inspect::set_handlers(thread_idx, handlers);
+
+            if call_helper.verify_handler_exists(FUNCTION_ON_INPUT_FILTER) {
+                input_filter::register(thread_idx);
+            }
 
             if call_helper.call(FUNCTION_ON_STARTUP, ()).is_err() {
                 return Ok(RunScriptResult::TerminatedWithErrors);
@@ -275,12 +455,16 @@ pub fn run_script(
                         new_parameter_values.iter().for_each(|pv| {
                             parameter_values.insert(pv.name.clone(), pv.clone());
                         });
+
+                        inspect::update_parameters(thread_idx, new_parameter_values);
                     }
 
                     match process_message(&mut call_helper, msg) {
                         Ok(RunningScriptResult::Continue) => (),
                         Ok(RunningScriptResult::TerminateGracefully) => {
-                            return Ok(RunScriptResult::TerminatedGracefully)
+                            inspect::stopped(thread_idx);
+
+                            return Ok(RunScriptResult::TerminatedGracefully);
                         }
                         Ok(RunningScriptResult::TerminateWithErrors) => {
                             return Ok(RunScriptResult::TerminatedWithErrors)
@@ -294,6 +478,9 @@ pub fn run_script(
                                 "Unexpected lua error in file {}: {}\n\t{:?}",
                                 call_helper.file_name, e, error
                             );
+
+                            inspect::record_error(thread_idx, &e);
+
                             return Ok(RunScriptResult::TerminatedWithErrors);
                         }
                     }
@@ -372,15 +559,32 @@ fn process_message(
     match msg {
         Message::Quit(param) => on_quit(call_helper, param),
         Message::Tick(param) => on_tick(call_helper, param),
-        Message::RealizeColorMap => realize_color_map(),
+        Message::RealizeColorMap(index) => realize_color_map(index),
         Message::KeyDown(param) => on_key_down(call_helper, param),
         Message::KeyUp(param) => on_key_up(call_helper, param),
+        Message::Chord(name) => on_chord(call_helper, name),
+        Message::InputFilter(code, value, reply_tx) => {
+            on_input_filter(call_helper, code, value, reply_tx)
+        }
+        Message::Route(name, device, code) => on_route(call_helper, name, device, code),
+        Message::LockStateChanged(lock_state) => on_lock_state_changed(call_helper, lock_state),
+        Message::LayoutChanged(group, name) => on_layout_changed(call_helper, group, name),
         Message::KeyboardHidEvent(param) => on_keyboard_hid_event(call_helper, param),
         Message::MouseHidEvent(param) => on_mouse_hid_event(call_helper, param),
         Message::MouseButtonDown(param) => on_mouse_button_down(call_helper, param),
         Message::MouseButtonUp(param) => on_mouse_button_up(call_helper, param),
         Message::MouseMove(rel_x, rel_y, rel_z) => on_mouse_move(call_helper, rel_x, rel_y, rel_z),
         Message::MouseWheelEvent(param) => on_mouse_wheel_event(call_helper, param),
+        Message::HeadsetEvent(device, event_type, value) => {
+            on_headset_event(call_helper, device, event_type, value)
+        }
+        Message::DeviceAttached(class, id) => on_device_attached(call_helper, class, id),
+        Message::DeviceRemoved(class, id) => on_device_removed(call_helper, class, id),
+        Message::WindowFocused(class, title, app_id) => {
+            on_window_focused(call_helper, class, title, app_id)
+        }
+        Message::LidChanged(closed) => on_lid_changed(call_helper, closed),
+        Message::DisplaysChanged(displays) => on_displays_changed(call_helper, displays),
         Message::Unload => on_unload(call_helper),
         Message::SetParameters { parameter_values } => {
             on_apply_parameters(call_helper, parameter_values)
@@ -400,66 +604,158 @@ fn continue_if_ok(
 fn on_quit(call_helper: &mut RunningScriptCallHelper, param: u32) -> Result<RunningScriptResult> {
     let called = call_helper.call(FUNCTION_ON_QUIT, param);
 
+    signal_quit_completed();
+
+    continue_if_ok(called)
+}
+
+/// Notify the main thread that this thread has processed the `Quit` message
+pub fn signal_quit_completed() {
     let mut val = crate::UPCALL_COMPLETED_ON_QUIT.0.lock();
     *val = val.saturating_sub(1);
 
     crate::UPCALL_COMPLETED_ON_QUIT.1.notify_all();
+}
 
-    continue_if_ok(called)
+/// Acknowledge the input event `msg` without handling it. This is used by the VMs that do not
+/// react to input events, so that the event dispatch does not wait for them forever. Input
+/// filter requests are answered with a pass-through verdict
+pub fn acknowledge_input_event(msg: Message) {
+    let upcall_completed: &(Mutex<usize>, parking_lot::Condvar) = match msg {
+        Message::KeyDown(_) => &crate::UPCALL_COMPLETED_ON_KEY_DOWN,
+        Message::KeyUp(_) => &crate::UPCALL_COMPLETED_ON_KEY_UP,
+        Message::KeyboardHidEvent(_) => &crate::UPCALL_COMPLETED_ON_KEYBOARD_HID_EVENT,
+        Message::MouseHidEvent(_) => &crate::UPCALL_COMPLETED_ON_MOUSE_HID_EVENT,
+        Message::MouseButtonDown(_) => &crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN,
+        Message::MouseButtonUp(_) => &crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP,
+        Message::MouseMove(..) => &crate::UPCALL_COMPLETED_ON_MOUSE_MOVE,
+        Message::MouseWheelEvent(_) => &crate::UPCALL_COMPLETED_ON_MOUSE_EVENT,
+
+        Message::InputFilter(_, _, reply_tx) => {
+            reply_tx.send(input_filter::Verdict::Pass).ok();
+
+            return;
+        }
+
+        _ => return,
+    };
+
+    let mut val = upcall_completed.0.lock();
+    *val = val.saturating_sub(1);
+
+    upcall_completed.1.notify_all();
 }
 
 fn on_tick(call_helper: &mut RunningScriptCallHelper, param: u32) -> Result<RunningScriptResult> {
+    // announce the changes that the other scripts made to the blackboard since the last tick
+    if on_blackboard_changed(call_helper).is_err() {
+        return Ok(RunningScriptResult::TerminateWithErrors);
+    }
+
+    // advance the animations before the handler runs, so that it sees the current values
+    callbacks::update_animations(call_helper.lua_ctx).unwrap_or_else(|e| {
+        error!(
+            "Could not update the animations of {}: {}",
+            call_helper.file_name, e
+        )
+    });
+
     let called = if call_helper.skip_on_tick {
         Ok(RunningScriptCallHelperResult::NoHandler)
     } else {
         call_helper.call(FUNCTION_ON_TICK, param)
     };
 
+    // ticks are sent once per frame
+    let thread_idx = call_helper.thread_idx;
+    call_helper.profiler.end_frame(thread_idx);
+
     continue_if_ok(called)
 }
 
-fn realize_color_map() -> Result<RunningScriptResult> {
-    if LOCAL_LED_MAP_MODIFIED.with(|f| *f.borrow()) {
-        LOCAL_LED_MAP.with(|foreground| {
-            let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+fn on_blackboard_changed(
+    call_helper: &mut RunningScriptCallHelper,
+) -> Result<RunningScriptCallHelperResult> {
+    let (changes, generation) = blackboard::changes_since(call_helper.blackboard_generation);
 
-            let fader = crate::BRIGHTNESS_FADER.load(Ordering::SeqCst);
-            let fader_base = crate::BRIGHTNESS_FADER_BASE.load(Ordering::SeqCst);
+    call_helper.blackboard_generation = generation;
 
-            let brightness = if fader_base > 0 && fader > 0 {
-                (1.0 - (fader as f32 / fader_base as f32)) * brightness as f32
-            } else {
-                brightness as f32
-            };
+    if changes.is_empty() || !call_helper.verify_handler_exists(FUNCTION_ON_BLACKBOARD_CHANGED) {
+        return Ok(RunningScriptCallHelperResult::NoHandler);
+    }
 
-            for chunks in LED_MAP.write().chunks_exact_mut(constants::CANVAS_SIZE) {
-                for (idx, background) in chunks.iter_mut().enumerate() {
-                    let bg = &background;
-                    let fg = foreground.borrow()[idx];
+    for key in changes {
+        call_helper.call(FUNCTION_ON_BLACKBOARD_CHANGED, key)?;
+    }
 
-                    #[rustfmt::skip]
-                    let color = RGBA {
-                        r: ((((fg.a as f32) * fg.r as f32 + (255 - fg.a) as f32 * bg.r as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
-                        g: ((((fg.a as f32) * fg.g as f32 + (255 - fg.a) as f32 * bg.g as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
-                        b: ((((fg.a as f32) * fg.b as f32 + (255 - fg.a) as f32 * bg.b as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
-                        a: fg.a,
-                    };
+    Ok(RunningScriptCallHelperResult::Successful)
+}
 
-                    *background = color;
-                }
+/// Prepare the layers of the next frame for `count` Lua VMs
+pub fn prepare_layers(count: usize) {
+    let mut layers = LAYERS.lock();
+
+    layers.resize_with(count, Layer::default);
+    layers.iter_mut().for_each(|layer| layer.submitted = false);
+}
+
+/// Returns the brightness that the layers are composed with, including the fade-in after a
+/// profile switch
+pub fn layer_brightness() -> f32 {
+    let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+
+    let fader = crate::BRIGHTNESS_FADER.load(Ordering::SeqCst);
+    let fader_base = crate::BRIGHTNESS_FADER_BASE.load(Ordering::SeqCst);
+
+    if fader_base > 0 && fader > 0 {
+        (1.0 - (fader as f32 / fader_base as f32)) * brightness as f32
+    } else {
+        brightness as f32
+    }
+}
+
+/// Alpha blend the layer `foreground` onto the canvas
+pub fn blend_layer(canvas: &mut [RGBA], foreground: &[RGBA], brightness: f32) {
+    for chunks in canvas.chunks_exact_mut(constants::CANVAS_SIZE) {
+        for (idx, background) in chunks.iter_mut().enumerate() {
+            let bg = &background;
+            let fg = foreground[idx];
+
+            #[rustfmt::skip]
+            let color = RGBA {
+                r: ((((fg.a as f32) * fg.r as f32 + (255 - fg.a) as f32 * bg.r as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
+                g: ((((fg.a as f32) * fg.g as f32 + (255 - fg.a) as f32 * bg.g as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
+                b: ((((fg.a as f32) * fg.b as f32 + (255 - fg.a) as f32 * bg.b as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
+                a: fg.a,
+            };
+
+            *background = color;
+        }
+    }
+}
+
+/// Submit the LED map of this thread as the layer `index` of the current frame, and signal
+/// readiness to the device I/O thread
+pub fn realize_color_map(index: usize) -> Result<RunningScriptResult> {
+    if LOCAL_LED_MAP_MODIFIED.with(|f| *f.borrow()) {
+        LOCAL_LED_MAP.with(|foreground| {
+            if let Some(layer) = LAYERS.lock().get_mut(index) {
+                layer.led_map.copy_from_slice(&foreground.borrow());
+                layer.submitted = true;
             }
         });
     }
 
-    // signal readiness / notify the main thread that we are done
-    let val = { *crate::COLOR_MAPS_READY_CONDITION.0.lock() };
+    // signal readiness / notify the device I/O thread that we are done. The VMs realize
+    // their color maps in parallel, so the counter has to be decremented atomically
+    {
+        let mut pending = crate::COLOR_MAPS_READY_CONDITION.0.lock();
 
-    let val = val.checked_sub(1).unwrap_or_else(|| {
-        warn!("Incorrect state in locking code detected");
-        0
-    });
-
-    *crate::COLOR_MAPS_READY_CONDITION.0.lock() = val;
+        *pending = pending.checked_sub(1).unwrap_or_else(|| {
+            warn!("Incorrect state in locking code detected");
+            0
+        });
+    }
 
     crate::COLOR_MAPS_READY_CONDITION.1.notify_one();
 
@@ -491,6 +787,83 @@ fn on_key_up(call_helper: &mut RunningScriptCallHelper, param: u8) -> Result<Run
     continue_if_ok(called)
 }
 
+fn on_input_filter(
+    call_helper: &mut RunningScriptCallHelper,
+    code: u32,
+    value: i32,
+    reply_tx: Sender<input_filter::Verdict>,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call_returning::<_, LuaValue>(FUNCTION_ON_INPUT_FILTER, (code, value));
+
+    let verdict = match &called {
+        Ok(Some(result)) => input_filter::Verdict::from_lua_value(result).unwrap_or_else(|| {
+            ratelimited::warn!(
+                "Invalid result of {} in file {}, expected nil, a boolean or a key code",
+                FUNCTION_ON_INPUT_FILTER,
+                call_helper.file_name
+            );
+
+            input_filter::Verdict::Pass
+        }),
+
+        _ => input_filter::Verdict::Pass,
+    };
+
+    // the reply is discarded if the filter exceeded its time budget
+    reply_tx.send(verdict).ok();
+
+    match called {
+        Ok(_r) => Ok(RunningScriptResult::Continue),
+        Err(_e) => Ok(RunningScriptResult::TerminateWithErrors),
+    }
+}
+
+fn on_chord(
+    call_helper: &mut RunningScriptCallHelper,
+    name: String,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(FUNCTION_ON_CHORD, name);
+
+    continue_if_ok(called)
+}
+
+fn on_route(
+    call_helper: &mut RunningScriptCallHelper,
+    name: String,
+    device: u64,
+    code: u8,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(FUNCTION_ON_ROUTE, (name, device, code));
+
+    continue_if_ok(called)
+}
+
+fn on_lock_state_changed(
+    call_helper: &mut RunningScriptCallHelper,
+    lock_state: LockState,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(
+        FUNCTION_ON_LOCK_STATE_CHANGED,
+        (
+            lock_state.caps_lock,
+            lock_state.num_lock,
+            lock_state.scroll_lock,
+        ),
+    );
+
+    continue_if_ok(called)
+}
+
+fn on_layout_changed(
+    call_helper: &mut RunningScriptCallHelper,
+    group: u32,
+    name: String,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(FUNCTION_ON_LAYOUT_CHANGED, (group, name));
+
+    continue_if_ok(called)
+}
+
 fn on_keyboard_hid_event(
     call_helper: &mut RunningScriptCallHelper,
     param: KeyboardHidEvent,
@@ -634,6 +1007,66 @@ fn on_unload(call_helper: &mut RunningScriptCallHelper) -> Result<RunningScriptR
     }
 }
 
+fn on_headset_event(
+    call_helper: &mut RunningScriptCallHelper,
+    device: u64,
+    event_type: u8,
+    value: u32,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(FUNCTION_ON_HEADSET_EVENT, (device, event_type, value));
+
+    continue_if_ok(called)
+}
+
+fn on_device_attached(
+    call_helper: &mut RunningScriptCallHelper,
+    class: String,
+    id: String,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(FUNCTION_ON_DEVICE_ATTACHED, (class, id));
+
+    continue_if_ok(called)
+}
+
+fn on_device_removed(
+    call_helper: &mut RunningScriptCallHelper,
+    class: String,
+    id: String,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(FUNCTION_ON_DEVICE_REMOVED, (class, id));
+
+    continue_if_ok(called)
+}
+
+fn on_window_focused(
+    call_helper: &mut RunningScriptCallHelper,
+    class: String,
+    title: String,
+    app_id: String,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(FUNCTION_ON_WINDOW_FOCUSED, (class, title, app_id));
+
+    continue_if_ok(called)
+}
+
+fn on_lid_changed(
+    call_helper: &mut RunningScriptCallHelper,
+    closed: bool,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(FUNCTION_ON_LID_CHANGED, closed);
+
+    continue_if_ok(called)
+}
+
+fn on_displays_changed(
+    call_helper: &mut RunningScriptCallHelper,
+    displays: Vec<String>,
+) -> Result<RunningScriptResult> {
+    let called = call_helper.call(FUNCTION_ON_DISPLAYS_CHANGED, (displays,));
+
+    continue_if_ok(called)
+}
+
 fn on_apply_parameters(
     call_helper: &mut RunningScriptCallHelper,
     parameter_values: Vec<PlainParameter>,
@@ -0,0 +1,479 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Evaluation of `shader` effects. The WGSL compute shader of an effect is run once per
+//! frame, with one invocation per cell of the canvas. The interface of a shader is:
+//!
+//! ```wgsl
+//! struct Globals { ticks: u32, time: f32, width: u32, height: u32 }
+//!
+//! @group(0) @binding(0) var<uniform> globals: Globals;
+//! @group(0) @binding(1) var<storage, read> params: array<u32>;
+//! @group(0) @binding(2) var<storage, read_write> canvas: array<vec4<f32>>;
+//!
+//! @compute @workgroup_size(8, 8)
+//! fn main(@builtin(global_invocation_id) id: vec3<u32>) { ... }
+//! ```
+//!
+//! `params` holds the numeric parameters of the effect in alphabetical order of their
+//! names, floats have to be converted with `bitcast<f32>()`
+
+use flume::Receiver;
+use log::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::scripting::inspect;
+use crate::scripting::parameters::{PlainParameter, TypedValue};
+use crate::scripting::script::{
+    self, Message, RunScriptResult, LOCAL_LED_MAP, LOCAL_LED_MAP_MODIFIED,
+};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// The maximum number of parameters that are passed to a shader
+pub const MAX_SHADER_PARAMETERS: usize = 16;
+
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+pub enum ShaderError {
+    #[error("The GPU backend is unavailable: {msg}")]
+    BackendUnavailable { msg: String },
+
+    #[error("Could not compile the shader: {msg}")]
+    CompileError { msg: String },
+
+    #[error("Could not evaluate the shader: {msg}")]
+    EvalError { msg: String },
+}
+
+/// Returns `true` if `shader` effects shall be evaluated on the GPU, otherwise the
+/// Lua script of the effect is run instead
+pub fn is_enabled() -> bool {
+    if !cfg!(feature = "gpu") {
+        return false;
+    }
+
+    let config = crate::CONFIG.lock();
+
+    config
+        .as_ref()
+        .and_then(|config| config.get::<bool>("global.enable_gpu_effects").ok())
+        .unwrap_or(true)
+}
+
+/// Runs the `shader` effect of `script_file` until it is unloaded. An error is returned
+/// if the shader could not be compiled or evaluated, in which case the caller should run
+/// the Lua script of the effect instead
+pub fn run_shader(
+    thread_idx: usize,
+    script_file: &Path,
+    shader_file: &Path,
+    parameter_values: &mut BTreeMap<String, PlainParameter>,
+    rx: &Receiver<Message>,
+) -> Result<RunScriptResult> {
+    let source = fs::read_to_string(shader_file)?;
+    let shader = gpu::ShaderPipeline::new(&shader_file.to_string_lossy(), &source)?;
+
+    info!("Running shader: {}", shader_file.display());
+
+    inspect::begin(thread_idx, script_file, parameter_values.values());
+
+    let started_at = Instant::now();
+    let mut params = pack_parameters(parameter_values);
+
+    loop {
+        let Ok(msg) = rx.recv() else {
+            return Ok(RunScriptResult::TerminatedGracefully);
+        };
+
+        match msg {
+            Message::Tick(ticks) => {
+                let time = started_at.elapsed().as_secs_f32();
+
+                match shader.eval(ticks, time, &params) {
+                    Ok(colors) => {
+                        LOCAL_LED_MAP
+                            .with(|local_map| local_map.borrow_mut().copy_from_slice(&colors));
+                        LOCAL_LED_MAP_MODIFIED.with(|f| *f.borrow_mut() = true);
+                    }
+
+                    Err(e) => {
+                        inspect::record_error(thread_idx, &e);
+
+                        return Err(e);
+                    }
+                }
+            }
+
+            Message::RealizeColorMap(index) => {
+                script::realize_color_map(index)?;
+            }
+
+            Message::Quit(_) => script::signal_quit_completed(),
+
+            Message::Unload => {
+                inspect::stopped(thread_idx);
+
+                return Ok(RunScriptResult::TerminatedGracefully);
+            }
+
+            Message::SetParameters {
+                parameter_values: new_parameter_values,
+            } => {
+                new_parameter_values.iter().for_each(|pv| {
+                    parameter_values.insert(pv.name.clone(), pv.clone());
+                });
+
+                inspect::update_parameters(thread_idx, &new_parameter_values);
+
+                params = pack_parameters(parameter_values);
+            }
+
+            // shaders do not react to input events, but the event dispatch waits until all
+            // VMs have processed them
+            msg => script::acknowledge_input_event(msg),
+        }
+    }
+}
+
+/// Pack the numeric parameters in alphabetical order of their names, string parameters are skipped
+fn pack_parameters(parameter_values: &BTreeMap<String, PlainParameter>) -> Vec<u32> {
+    let mut result = parameter_values
+        .values()
+        .filter_map(|pv| match pv.value {
+            TypedValue::Int(value) => Some(value as i32 as u32),
+            TypedValue::Float(value) => Some((value as f32).to_bits()),
+            TypedValue::Bool(value) => Some(value as u32),
+            TypedValue::Color(value) => Some(value),
+            TypedValue::String(_) => None,
+        })
+        .take(MAX_SHADER_PARAMETERS)
+        .collect::<Vec<_>>();
+
+    result.resize(MAX_SHADER_PARAMETERS, 0);
+
+    result
+}
+
+#[cfg(feature = "gpu")]
+mod gpu {
+    use lazy_static::lazy_static;
+    use log::*;
+    use std::sync::Arc;
+
+    use crate::constants;
+    use crate::hwdevices::RGBA;
+
+    use super::{Result, ShaderError, MAX_SHADER_PARAMETERS};
+
+    /// The workgroup size that shaders have to declare, in both dimensions
+    const SHADER_WORKGROUP_SIZE: u32 = 8;
+
+    /// Size of the canvas buffer in bytes, one `vec4<f32>` per cell
+    const CANVAS_BUFFER_SIZE: u64 = (constants::CANVAS_SIZE * 4 * 4) as u64;
+
+    lazy_static! {
+        /// The GPU device, it is initialized on first use and shared by all shaders
+        static ref CONTEXT: Option<Arc<GpuContext>> = GpuContext::new()
+            .map(Arc::new)
+            .map_err(|e| warn!("Could not initialize the GPU backend: {}", e))
+            .ok();
+    }
+
+    struct GpuContext {
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    }
+
+    impl GpuContext {
+        fn new() -> Result<Self> {
+            let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+
+            let adapter =
+                pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::LowPower,
+                    compatible_surface: None,
+                    force_fallback_adapter: false,
+                }))
+                .ok_or_else(|| ShaderError::BackendUnavailable {
+                    msg: "No suitable GPU adapter found".to_owned(),
+                })?;
+
+            info!("Using GPU adapter: {}", adapter.get_info().name);
+
+            let (device, queue) = pollster::block_on(adapter.request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("eruption"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::downlevel_defaults(),
+                },
+                None,
+            ))
+            .map_err(|e| ShaderError::BackendUnavailable { msg: e.to_string() })?;
+
+            Ok(Self { device, queue })
+        }
+    }
+
+    /// A compiled shader, along with the buffers that it operates on
+    pub struct ShaderPipeline {
+        context: Arc<GpuContext>,
+        pipeline: wgpu::ComputePipeline,
+        bind_group: wgpu::BindGroup,
+        globals_buffer: wgpu::Buffer,
+        params_buffer: wgpu::Buffer,
+        canvas_buffer: wgpu::Buffer,
+        readback_buffer: wgpu::Buffer,
+    }
+
+    impl ShaderPipeline {
+        pub fn new(label: &str, source: &str) -> Result<Self> {
+            let context = CONTEXT
+                .as_ref()
+                .ok_or_else(|| ShaderError::BackendUnavailable {
+                    msg: "No GPU device available".to_owned(),
+                })?
+                .clone();
+
+            let device = &context.device;
+
+            // capture validation errors, instead of panicking in the default error handler
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+
+            let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            };
+
+            let bind_group_layout =
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(label),
+                    entries: &[
+                        buffer_entry(0, wgpu::BufferBindingType::Uniform),
+                        buffer_entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                        buffer_entry(2, wgpu::BufferBindingType::Storage { read_only: false }),
+                    ],
+                });
+
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: "main",
+            });
+
+            if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+                let msg = match e {
+                    wgpu::Error::Validation { description, .. } => description,
+                    e => e.to_string(),
+                };
+
+                return Err(ShaderError::CompileError { msg }.into());
+            }
+
+            let buffer = |size, usage| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage,
+                    mapped_at_creation: false,
+                })
+            };
+
+            let globals_buffer = buffer(
+                16,
+                wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            );
+
+            let params_buffer = buffer(
+                (MAX_SHADER_PARAMETERS * 4) as u64,
+                wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            );
+
+            let canvas_buffer = buffer(
+                CANVAS_BUFFER_SIZE,
+                wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            );
+
+            let readback_buffer = buffer(
+                CANVAS_BUFFER_SIZE,
+                wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            );
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: globals_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: canvas_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+            Ok(Self {
+                context,
+                pipeline,
+                bind_group,
+                globals_buffer,
+                params_buffer,
+                canvas_buffer,
+                readback_buffer,
+            })
+        }
+
+        /// Run the shader once and read back the resulting canvas
+        pub fn eval(&self, ticks: u32, time: f32, params: &[u32]) -> Result<Vec<RGBA>> {
+            let GpuContext { device, queue } = self.context.as_ref();
+
+            let globals = [
+                ticks,
+                time.to_bits(),
+                constants::CANVAS_WIDTH as u32,
+                constants::CANVAS_HEIGHT as u32,
+            ];
+
+            queue.write_buffer(&self.globals_buffer, 0, &to_bytes(&globals));
+            queue.write_buffer(&self.params_buffer, 0, &to_bytes(params));
+
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: None,
+                    timestamp_writes: None,
+                });
+
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &self.bind_group, &[]);
+                pass.dispatch_workgroups(
+                    (constants::CANVAS_WIDTH as u32).div_ceil(SHADER_WORKGROUP_SIZE),
+                    (constants::CANVAS_HEIGHT as u32).div_ceil(SHADER_WORKGROUP_SIZE),
+                    1,
+                );
+            }
+
+            encoder.copy_buffer_to_buffer(
+                &self.canvas_buffer,
+                0,
+                &self.readback_buffer,
+                0,
+                CANVAS_BUFFER_SIZE,
+            );
+
+            queue.submit(Some(encoder.finish()));
+
+            let slice = self.readback_buffer.slice(..);
+            let (tx, rx) = flume::bounded(1);
+
+            slice.map_async(wgpu::MapMode::Read, move |result| {
+                tx.send(result).ok();
+            });
+
+            device.poll(wgpu::Maintain::Wait);
+
+            rx.recv()?
+                .map_err(|e| ShaderError::EvalError { msg: e.to_string() })?;
+
+            let colors = {
+                let data = slice.get_mapped_range();
+
+                data.chunks_exact(4)
+                    .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect::<Vec<_>>()
+                    .chunks_exact(4)
+                    .map(to_rgba)
+                    .collect()
+            };
+
+            self.readback_buffer.unmap();
+
+            Ok(colors)
+        }
+    }
+
+    /// Convert a color of the shader, with components in the range [0.0, 1.0]
+    fn to_rgba(color: &[f32]) -> RGBA {
+        let component = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        RGBA {
+            r: component(color[0]),
+            g: component(color[1]),
+            b: component(color[2]),
+            a: component(color[3]),
+        }
+    }
+
+    fn to_bytes(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+}
+
+/// CPU fallback, used when Eruption has been built without the `gpu` feature
+#[cfg(not(feature = "gpu"))]
+mod gpu {
+    use super::{Result, ShaderError};
+    use crate::hwdevices::RGBA;
+
+    pub struct ShaderPipeline;
+
+    impl ShaderPipeline {
+        pub fn new(_label: &str, _source: &str) -> Result<Self> {
+            Err(ShaderError::BackendUnavailable {
+                msg: "Eruption has been built without the gpu feature".to_owned(),
+            }
+            .into())
+        }
+
+        pub fn eval(&self, _ticks: u32, _time: f32, _params: &[u32]) -> Result<Vec<RGBA>> {
+            unreachable!()
+        }
+    }
+}
@@ -0,0 +1,142 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use flume::{Receiver, Sender};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::constants;
+
+use super::manifest::NativeEffectKind;
+use super::parameters::PlainParameter;
+use super::script;
+
+lazy_static! {
+    /// Idle Lua threads, each one holds a pre-initialized Lua VM and waits for a script to run.
+    /// Handing a script to an idle thread avoids the cost of spawning a thread and of setting
+    /// up a new Lua VM, when switching profiles
+    static ref IDLE_THREADS: Arc<Mutex<Vec<Sender<Assignment>>>> = Arc::new(Mutex::new(vec![]));
+}
+
+/// A script that shall be run by a Lua thread
+pub struct Assignment {
+    pub thread_idx: usize,
+    pub lua_rx: Receiver<script::Message>,
+    pub script_file: PathBuf,
+    /// The WGSL shader of a `shader` effect, it is evaluated instead of the Lua script if possible
+    pub shader_file: Option<PathBuf>,
+    /// The built-in effect of a `native` effect, it is run instead of a Lua script
+    pub native_effect: Option<NativeEffectKind>,
+    pub parameter_values: BTreeMap<String, PlainParameter>,
+}
+
+/// Hand the `assignment` to an idle Lua thread. If no thread is available, the
+/// assignment is given back to the caller, so that a new thread may be spawned
+pub fn assign_to_idle_thread(mut assignment: Assignment) -> Option<Assignment> {
+    loop {
+        let thread_tx = IDLE_THREADS.lock().pop();
+
+        match thread_tx {
+            Some(thread_tx) => match thread_tx.send(assignment) {
+                Ok(()) => return None,
+
+                // the thread went away, try the next one
+                Err(e) => assignment = e.into_inner(),
+            },
+
+            None => return Some(assignment),
+        }
+    }
+}
+
+/// Return a Lua thread to the pool of idle threads, returns `false` if the pool is
+/// already full, in which case the thread should terminate
+pub fn return_to_pool(thread_tx: Sender<Assignment>) -> bool {
+    let mut idle_threads = IDLE_THREADS.lock();
+
+    if idle_threads.len() < constants::LUA_VM_POOL_SIZE {
+        idle_threads.push(thread_tx);
+
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assignment(script_file: &str) -> Assignment {
+        let (_lua_tx, lua_rx) = flume::unbounded();
+
+        Assignment {
+            thread_idx: 0,
+            lua_rx,
+            script_file: PathBuf::from(script_file),
+            shader_file: None,
+            native_effect: None,
+            parameter_values: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn assignments_are_handed_to_idle_threads() {
+        // no idle thread is available
+        assert!(assign_to_idle_thread(assignment("a.lua")).is_some());
+
+        let (thread_tx, thread_rx) = flume::unbounded();
+        assert!(return_to_pool(thread_tx));
+
+        assert!(assign_to_idle_thread(assignment("b.lua")).is_none());
+        assert_eq!(
+            thread_rx.try_recv().unwrap().script_file,
+            PathBuf::from("b.lua")
+        );
+
+        // threads that went away are skipped
+        let (thread_tx, thread_rx) = flume::unbounded();
+        assert!(return_to_pool(thread_tx));
+        drop(thread_rx);
+
+        let assignment = assign_to_idle_thread(assignment("c.lua")).unwrap();
+        assert_eq!(assignment.script_file, PathBuf::from("c.lua"));
+
+        // the size of the pool is limited
+        let receivers = (0..constants::LUA_VM_POOL_SIZE)
+            .map(|_| {
+                let (thread_tx, thread_rx) = flume::unbounded();
+                assert!(return_to_pool(thread_tx));
+
+                thread_rx
+            })
+            .collect::<Vec<_>>();
+
+        let (thread_tx, _thread_rx) = flume::unbounded();
+        assert!(!return_to_pool(thread_tx));
+
+        IDLE_THREADS.lock().clear();
+        drop(receivers);
+    }
+}
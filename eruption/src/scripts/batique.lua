@@ -39,10 +39,11 @@ function on_tick(delta)
         for i = zone_start, zone_end do
             local x = i / (zone_end - zone_start)
             local y = i / (zone_end - zone_start)
+            local time = ticks * get_key_speed(i) / time_scale
 
             local val = super_simplex_noise((x / coord_scale),
                                             (y / coord_scale),
-                                            ticks / time_scale)
+                                            time)
 
             color_map[i] = gradient_color_at(grad, val)
         end
@@ -0,0 +1,94 @@
+-- SPDX-License-Identifier: GPL-3.0-or-later
+--
+-- This file is part of Eruption.
+--
+-- Eruption is free software: you can redistribute it and/or modify
+-- it under the terms of the GNU General Public License as published by
+-- the Free Software Foundation, either version 3 of the License, or
+-- (at your option) any later version.
+--
+-- Eruption is distributed in the hope that it will be useful,
+-- but WITHOUT ANY WARRANTY without even the implied warranty of
+-- MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+-- GNU General Public License for more details.
+--
+-- You should have received a copy of the GNU General Public License
+-- along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+--
+-- Copyright (c) 2019-2022, The Eruption Development Team
+--
+require "declarations"
+require "debug"
+
+-- global state variables --
+color_map = {}
+highlight_ttl = {}
+labels = {}
+
+-- utility functions --
+local function label_color(key_index)
+    local color = get_key_label_color(key_index)
+
+    if color == nil then color = color_label end
+
+    return color
+end
+
+local function show_label(key_index)
+    local label = labels[key_index]
+
+    if label == nil or not has_display() then return end
+
+    local width = get_display_width()
+    local height = get_display_height()
+
+    local text_width, text_height = rasterize_text(label)
+
+    local x = math.floor((width - text_width) / 2)
+    local y = math.floor((height - text_height) / 2)
+
+    display_clear(color_background)
+    display_draw_text(x, y, label, label_color(key_index))
+    display_submit()
+end
+
+-- event handler functions --
+function on_startup(config)
+    for i = 1, canvas_size do
+        color_map[i] = 0x00000000
+        highlight_ttl[i] = 0
+    end
+
+    labels = get_key_labels()
+
+    for key_index, _ in pairs(labels) do
+        color_map[key_index] = label_color(key_index)
+    end
+
+    submit_color_map(color_map)
+end
+
+function on_apply_parameter(parameters)
+    -- update state
+    on_startup(nil)
+end
+
+function on_key_down(key_index)
+    if labels[key_index] == nil then return end
+
+    highlight_ttl[key_index] = highlight_duration
+    show_label(key_index)
+end
+
+function on_tick(delta)
+    for key_index, _ in pairs(labels) do
+        if highlight_ttl[key_index] > 0 then
+            highlight_ttl[key_index] = highlight_ttl[key_index] - 1
+            color_map[key_index] = color_highlight
+        else
+            color_map[key_index] = label_color(key_index)
+        end
+    end
+
+    submit_color_map(color_map)
+end
@@ -118,7 +118,7 @@ function device_specific_key_highlights_indicators()
     end
 
     -- Easy Shift+ overlay
-    if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and game_mode_enabled then
+    if modifier_map[CAPS_LOCK] and easy_shift_enabled() and game_mode_enabled then
         if audio_muted then
             color_map_highlight[key_to_index['MUTE']] = COLOR_MUTE_AUDIO_MUTED
             color_map_overlay[key_to_index['MUTE']] = COLOR_MUTE_AUDIO_MUTED
@@ -155,7 +155,7 @@ function on_macro_key_down(index)
 end
 
 function update_color_state()
-    if ENABLE_EASY_SHIFT and game_mode_enabled and modifier_map[CAPS_LOCK] then
+    if easy_shift_enabled() and game_mode_enabled and modifier_map[CAPS_LOCK] then
         -- Easy Shift+ key has been pressed
 
         -- highlight all keys
@@ -270,7 +270,7 @@ function update_color_state()
             color_map_highlight[key_name_to_index("GAME_MODE")] =
                 COLOR_FUNCTION_KEY_SPECIAL -- SCROLL LOCK/Game Mode
 
-            if ENABLE_EASY_SHIFT and game_mode_enabled then
+            if easy_shift_enabled() and game_mode_enabled then
                 color_map_highlight[key_name_to_index("CAPS_LOCK")] =
                     COLOR_FUNCTION_KEY_SPECIAL -- Easy Shift+
             end
@@ -35,6 +35,12 @@ FN = 8
 -- import user configuration
 require "macros/modifiers"
 
+-- the Easy Shift+ layer may be locked by the eruption daemon, e.g. while the user is AFK
+function easy_shift_enabled()
+    return ENABLE_EASY_SHIFT and
+               not load_bool_transient("global.easy_shift_locked", false)
+end
+
 -- initialize remapping tables
 REMAPPING_TABLE = {} -- level 1 remapping table (No modifier keys applied)
 MACRO_TABLE = {} -- level 1 macro table (No modifier keys applied)
@@ -161,7 +167,7 @@ function on_hid_event(event_type, arg1)
         -- "Easy Shift+" key event (CAPS LOCK pressed while in game mode)
         modifier_map[CAPS_LOCK] = is_pressed
 
-        if ENABLE_EASY_SHIFT and game_mode_enabled then
+        if easy_shift_enabled() and game_mode_enabled then
             debug("Macros: Easy Shift+ key event registered")
 
             -- forcefully disable other overlays
@@ -375,7 +381,7 @@ function on_mouse_hid_event(event_type, arg1)
 
         if game_mode_enabled then
             -- call complex macros on the Easy Shift+ layer (layer 4)
-            if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and
+            if modifier_map[CAPS_LOCK] and easy_shift_enabled() and
                 EASY_SHIFT_MOUSE_DPI_MACRO_TABLE[ACTIVE_EASY_SHIFT_LAYER][dpi_slot] ~=
                 nil then
 
@@ -389,7 +395,7 @@ function on_mouse_hid_event(event_type, arg1)
 
         if game_mode_enabled then
             -- call complex macros on the Easy Shift+ layer (layer 4)
-            if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and
+            if modifier_map[CAPS_LOCK] and easy_shift_enabled() and
                 EASY_SHIFT_MOUSE_HID_DOWN_MACRO_TABLE[ACTIVE_EASY_SHIFT_LAYER][button_index] ~=
                 nil then
 
@@ -405,7 +411,7 @@ function on_mouse_hid_event(event_type, arg1)
 
         if game_mode_enabled then
             -- call complex macros on the Easy Shift+ layer (layer 4)
-            if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and
+            if modifier_map[CAPS_LOCK] and easy_shift_enabled() and
                 EASY_SHIFT_MOUSE_HID_UP_MACRO_TABLE[ACTIVE_EASY_SHIFT_LAYER][button_index] ~=
                 nil then
 
@@ -426,7 +432,7 @@ function on_key_down(key_index)
         modifier_map[CAPS_LOCK] = true
 
         -- consume the CAPS_LOCK key while in game mode
-        if ENABLE_EASY_SHIFT and game_mode_enabled then consume_key() end
+        if easy_shift_enabled() and game_mode_enabled then consume_key() end
     end
 
     if key_index == key_name_to_index("LEFT_SHIFT") then
@@ -485,7 +491,7 @@ function on_key_down(key_index)
     end
 
     -- switch Easy Shift+ layers via Caps Lock + macro keys
-    if ENABLE_EASY_SHIFT and game_mode_enabled then
+    if easy_shift_enabled() and game_mode_enabled then
         if modifier_map[CAPS_LOCK] and key_index == key_name_to_index("INSERT") then
             do_switch_easy_shift_layer(0)
         elseif modifier_map[CAPS_LOCK] and key_index ==
@@ -517,7 +523,7 @@ function on_key_down(key_index)
 
     if game_mode_enabled then
         -- call complex macros on the Easy Shift+ layer (layer 4)
-        if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and
+        if modifier_map[CAPS_LOCK] and easy_shift_enabled() and
             EASY_SHIFT_MACRO_TABLE[ACTIVE_EASY_SHIFT_LAYER][key_index] ~= nil then
             -- consume the original key press
             consume_key()
@@ -536,7 +542,7 @@ function on_key_up(key_index)
         modifier_map[CAPS_LOCK] = false
 
         -- consume CAPS_LOCK key while in game mode
-        if ENABLE_EASY_SHIFT and game_mode_enabled then consume_key() end
+        if easy_shift_enabled() and game_mode_enabled then consume_key() end
     end
 
     if key_index == key_name_to_index("LEFT_SHIFT") then
@@ -576,7 +582,7 @@ function on_mouse_button_down(button_index)
     trace("Macros: Mouse down: Button: " .. button_index)
 
     -- call complex macros on the Easy Shift+ layer (layer 4)
-    if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and game_mode_enabled and
+    if modifier_map[CAPS_LOCK] and easy_shift_enabled() and game_mode_enabled and
         EASY_SHIFT_MOUSE_DOWN_MACRO_TABLE[ACTIVE_EASY_SHIFT_LAYER][button_index] ~=
         nil then
         -- consume the original mouse click
@@ -591,7 +597,7 @@ function on_mouse_button_up(button_index)
     trace("Macros: Mouse up: Button: " .. button_index)
 
     -- call complex macros on the Easy Shift+ layer (layer 4)
-    if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and game_mode_enabled and
+    if modifier_map[CAPS_LOCK] and easy_shift_enabled() and game_mode_enabled and
         EASY_SHIFT_MOUSE_UP_MACRO_TABLE[ACTIVE_EASY_SHIFT_LAYER][button_index] ~=
         nil then
         -- consume the original mouse click
@@ -606,7 +612,7 @@ function on_mouse_wheel(direction)
     trace("Macros: Mouse wheel: Direction: " .. direction)
 
     -- call complex macros on the Easy Shift+ layer (layer 4)
-    if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and game_mode_enabled and
+    if modifier_map[CAPS_LOCK] and easy_shift_enabled() and game_mode_enabled and
         EASY_SHIFT_MOUSE_WHEEL_MACRO_TABLE[ACTIVE_EASY_SHIFT_LAYER][direction] ~=
         nil then
         -- consume the original mouse wheel event
@@ -624,7 +630,7 @@ end
 
 -- perform a simple remapping
 function simple_remapping(key_index, down)
-    if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and game_mode_enabled then
+    if modifier_map[CAPS_LOCK] and easy_shift_enabled() and game_mode_enabled then
         code = EASY_SHIFT_REMAPPING_TABLE[ACTIVE_EASY_SHIFT_LAYER][key_index]
         if code ~= nil then inject_key(code, down) end
     else
@@ -635,7 +641,7 @@ end
 
 -- perform a simple remapping (for mouse events)
 function simple_mouse_remapping(button_index, down)
-    if modifier_map[CAPS_LOCK] and ENABLE_EASY_SHIFT and game_mode_enabled then
+    if modifier_map[CAPS_LOCK] and easy_shift_enabled() and game_mode_enabled then
         code =
             EASY_SHIFT_MOUSE_HID_REMAPPING_TABLE[ACTIVE_EASY_SHIFT_LAYER][button_index]
         if code ~= nil then inject_mouse_button(code, down) end
@@ -32,9 +32,10 @@ function on_tick(delta)
         for i = 1, canvas_size do
             local x = i / canvas_width
             local y = i / canvas_height
+            local time = ticks * get_key_speed(i) / time_scale
 
             local val = perlin_noise(x / coord_scale, y / coord_scale,
-                                     ticks / time_scale)
+                                     time)
 
             val = lerp(0, 360, val)
 
@@ -35,7 +35,9 @@ function on_tick(delta)
     -- animate gradient
     if ticks % gradient_speed == 0 then
         for i = 1, canvas_size do
-            color_map[i] = hsla_to_color((i * hue_multiplier) + ticks,
+            local hue = (i * hue_multiplier) + ticks * get_key_speed(i)
+
+            color_map[i] = hsla_to_color(hue,
                                          color_saturation, color_lightness,
                                          lerp(0, 255, opacity))
         end
@@ -0,0 +1,102 @@
+-- SPDX-License-Identifier: GPL-3.0-or-later
+--
+-- This file is part of Eruption.
+--
+-- Eruption is free software: you can redistribute it and/or modify
+-- it under the terms of the GNU General Public License as published by
+-- the Free Software Foundation, either version 3 of the License, or
+-- (at your option) any later version.
+--
+-- Eruption is distributed in the hope that it will be useful,
+-- but WITHOUT ANY WARRANTY without even the implied warranty of
+-- MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+-- GNU General Public License for more details.
+--
+-- You should have received a copy of the GNU General Public License
+-- along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+--
+-- Copyright (c) 2019-2022, The Eruption Development Team
+--
+require "declarations"
+require "debug"
+
+-- global state variables --
+color_map = {}
+zone_keys = {}
+watched_units = {}
+failed_units = {}
+ticks = 0
+refresh_ticks = 0
+
+-- utility functions --
+local function parse_units(list)
+    local result = {}
+
+    for unit in string.gmatch(list, "[^,%s]+") do table.insert(result, unit) end
+
+    return result
+end
+
+local function update_unit_states()
+    failed_units = {}
+
+    for _, unit in ipairs(watched_units) do
+        if get_unit_state(unit) == "failed" then
+            table.insert(failed_units, unit)
+        end
+    end
+
+    if #failed_units > 0 then
+        trace("Unit status: Failed units: " .. table.concat(failed_units, ", "))
+    end
+end
+
+-- event handler functions --
+function on_startup(config)
+    for i = 1, canvas_size do color_map[i] = 0x00000000 end
+
+    watched_units = parse_units(units)
+
+    zone_keys = get_zone(zone)
+    if zone_keys == nil then
+        warn("Unit status: Unknown zone '" .. zone .. "', using the whole keyboard")
+
+        zone_keys = {}
+        for i = keyboard_zone_start, keyboard_zone_end do
+            table.insert(zone_keys, i)
+        end
+    end
+
+    update_unit_states()
+end
+
+function on_apply_parameter(parameters)
+    -- update state
+    on_startup(nil)
+end
+
+function on_tick(delta)
+    ticks = ticks + delta
+    refresh_ticks = refresh_ticks + delta
+
+    -- re-check the watched units approximately every second, the ticks may advance
+    -- by more than one, so an exact multiple of the frame rate may be skipped
+    if refresh_ticks >= target_fps then
+        refresh_ticks = 0
+        update_unit_states()
+    end
+
+    local color
+    if #failed_units > 0 then
+        local r, g, b, alpha = color_to_rgba(color_failed)
+        local val = (sin(ticks * pulse_speed / target_fps) + 1) / 2
+
+        color = rgba_to_color(r, g, b, clamp(alpha * val, 0, 255))
+    else
+        color = color_ok
+    end
+
+    for _, key_index in ipairs(zone_keys) do color_map[key_index] = color end
+
+    submit_color_map(color_map)
+end
@@ -0,0 +1,412 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use nix::unistd::{Uid, User};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::dbus_interface::perms;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SdkClientError {
+    #[error("User {user} is not allowed to connect to the SDK socket")]
+    UserDenied { user: String },
+
+    #[error("Invalid or missing SDK access token")]
+    InvalidToken {},
+
+    #[error("Unknown SDK client: {id}")]
+    UnknownClient { id: u64 },
+
+    #[error("The SDK access token file is empty: {file}")]
+    EmptyTokenFile { file: String },
+}
+
+lazy_static! {
+    /// SDK clients that are currently connected
+    static ref CLIENTS: Arc<RwLock<Vec<Client>>> = Arc::new(RwLock::new(Vec::new()));
+
+    static ref POLICY: Arc<RwLock<AccessPolicy>> = Arc::new(RwLock::new(AccessPolicy::default()));
+
+    /// Identifier of the next SDK client
+    static ref NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+}
+
+/// An SDK client, as returned by `GetSdkClients`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SdkClientInfo {
+    pub id: u64,
+    pub pid: i32,
+    pub uid: u32,
+    pub user: String,
+
+    /// Point in time of the connection, in seconds since the epoch
+    pub connected_at: u64,
+
+    /// Protocol version that has been negotiated during the handshake, 0 for legacy clients
+    pub protocol_version: u32,
+
+    /// Set once the client presented a valid access token, or if no token is required
+    pub authenticated: bool,
+}
+
+#[derive(Debug)]
+struct Client {
+    info: SdkClientInfo,
+    kicked: Arc<AtomicBool>,
+}
+
+/// Decides which users may connect to the SDK socket
+#[derive(Debug, Clone, Default)]
+struct AccessPolicy {
+    /// User names or numeric user ids, an empty list allows all users
+    allowed_users: Vec<String>,
+
+    /// User names or numeric user ids, takes precedence over `allowed_users`
+    denied_users: Vec<String>,
+
+    /// Access token that clients have to present during the handshake
+    token: Option<String>,
+
+    /// Set if the policy could not be loaded, only root may connect
+    deny_all: bool,
+}
+
+impl AccessPolicy {
+    /// A policy that only allows root to connect
+    fn deny_all() -> Self {
+        Self {
+            deny_all: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// A connected SDK client, the client is unregistered when it is dropped
+#[derive(Debug)]
+pub struct ClientHandle {
+    pub id: u64,
+    pid: i32,
+    uid: u32,
+    kicked: Arc<AtomicBool>,
+
+    /// Cached result of the permission check for the injection of input events
+    may_inject_events: Mutex<Option<bool>>,
+}
+
+impl ClientHandle {
+    /// Returns `true` if the client shall be disconnected
+    pub fn is_kicked(&self) -> bool {
+        self.kicked.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` if the client may issue requests, without presenting a token first
+    pub fn is_authenticated(&self) -> bool {
+        CLIENTS
+            .read()
+            .iter()
+            .any(|c| c.info.id == self.id && c.info.authenticated)
+    }
+
+    /// Returns `true` if the client may inject synthetic input events. This requires the
+    /// `org.eruption.manage` permission, the result of the check is cached for the lifetime
+    /// of the connection
+    pub fn may_inject_events(&self) -> bool {
+        let mut may_inject_events = self.may_inject_events.lock();
+
+        *may_inject_events.get_or_insert_with(|| {
+            is_root(self.uid)
+                || perms::has_process_permission(self.pid as u32, self.uid, "org.eruption.manage")
+                    .unwrap_or_else(|e| {
+                        warn!(
+                            "Could not check the permissions of SDK client {}: {}",
+                            self.id, e
+                        );
+
+                        false
+                    })
+        })
+    }
+
+    /// Record the result of the handshake, and verify the access token if one is required
+    pub fn hello(&self, protocol_version: u32, token: &str) -> Result<()> {
+        let authenticated = is_root(self.uid) || check_token(&POLICY.read(), token);
+
+        if let Some(client) = CLIENTS.write().iter_mut().find(|c| c.info.id == self.id) {
+            client.info.protocol_version = protocol_version;
+            client.info.authenticated = authenticated;
+        }
+
+        if authenticated {
+            Ok(())
+        } else {
+            Err(SdkClientError::InvalidToken {}.into())
+        }
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        CLIENTS.write().retain(|c| c.info.id != self.id);
+    }
+}
+
+fn is_root(uid: u32) -> bool {
+    uid == 0
+}
+
+fn user_name(uid: u32) -> String {
+    User::from_uid(Uid::from_raw(uid))
+        .ok()
+        .flatten()
+        .map(|user| user.name)
+        .unwrap_or_else(|| format!("{}", uid))
+}
+
+/// Returns `true` if `entry` (a user name or a numeric user id) refers to the user `uid`
+fn matches_user(entry: &str, uid: u32, name: &str) -> bool {
+    entry.parse::<u32>().map(|u| u == uid).unwrap_or(false) || entry == name
+}
+
+/// Returns `true` if the user `uid` may connect, root is always allowed
+fn is_user_allowed(policy: &AccessPolicy, uid: u32, name: &str) -> bool {
+    if is_root(uid) {
+        return true;
+    }
+
+    if policy.deny_all {
+        return false;
+    }
+
+    if policy
+        .denied_users
+        .iter()
+        .any(|entry| matches_user(entry, uid, name))
+    {
+        return false;
+    }
+
+    policy.allowed_users.is_empty()
+        || policy
+            .allowed_users
+            .iter()
+            .any(|entry| matches_user(entry, uid, name))
+}
+
+fn check_token(policy: &AccessPolicy, token: &str) -> bool {
+    match &policy.token {
+        // compare in constant time
+        Some(expected) => {
+            expected.len() == token.len()
+                && expected
+                    .bytes()
+                    .zip(token.bytes())
+                    .fold(0, |acc, (a, b)| acc | (a ^ b))
+                    == 0
+        }
+
+        None => true,
+    }
+}
+
+/// Register a newly connected client, identified by the credentials of its socket. Fails
+/// if the user is not allowed to connect
+pub fn register_client(pid: i32, uid: u32) -> Result<ClientHandle> {
+    let policy = POLICY.read().clone();
+    let user = user_name(uid);
+
+    if !is_user_allowed(&policy, uid, &user) {
+        return Err(SdkClientError::UserDenied { user }.into());
+    }
+
+    let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst);
+    let kicked = Arc::new(AtomicBool::new(false));
+
+    let connected_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    CLIENTS.write().push(Client {
+        info: SdkClientInfo {
+            id,
+            pid,
+            uid,
+            user,
+            connected_at,
+            protocol_version: 0,
+            authenticated: is_root(uid) || policy.token.is_none(),
+        },
+        kicked: kicked.clone(),
+    });
+
+    Ok(ClientHandle {
+        id,
+        pid,
+        uid,
+        kicked,
+        may_inject_events: Mutex::new(None),
+    })
+}
+
+/// Returns the SDK clients that are currently connected
+pub fn get_clients() -> Vec<SdkClientInfo> {
+    CLIENTS.read().iter().map(|c| c.info.clone()).collect()
+}
+
+/// Disconnect the SDK client `id`
+pub fn kick_client(id: u64) -> Result<()> {
+    let clients = CLIENTS.read();
+    let client = clients
+        .iter()
+        .find(|c| c.info.id == id)
+        .ok_or(SdkClientError::UnknownClient { id })?;
+
+    info!(
+        "Disconnecting SDK client {} (pid: {}, user: {})",
+        id, client.info.pid, client.info.user
+    );
+
+    client.kicked.store(true, Ordering::SeqCst);
+
+    Ok(())
+}
+
+/// Read the access token from `token_file`, an empty file is an error
+fn read_token(token_file: &str) -> Result<String> {
+    let token = fs::read_to_string(token_file)?.trim().to_string();
+
+    if token.is_empty() {
+        return Err(SdkClientError::EmptyTokenFile {
+            file: token_file.to_owned(),
+        }
+        .into());
+    }
+
+    Ok(token)
+}
+
+/// Load the access policy of the SDK socket from the global configuration. If a token file
+/// has been configured but can't be read, only root is allowed to connect
+pub fn load_policy() -> Result<()> {
+    let config = crate::CONFIG.lock();
+    let config = config.as_ref().unwrap();
+
+    let token = match config.get::<String>("sdk.token_file") {
+        Ok(token_file) => match read_token(&token_file) {
+            Ok(token) => Some(token),
+
+            Err(e) => {
+                error!(
+                    "Could not read the SDK access token, only root may connect to the SDK socket: {}",
+                    e
+                );
+
+                *POLICY.write() = AccessPolicy::deny_all();
+
+                return Err(e);
+            }
+        },
+
+        Err(_e) => None,
+    };
+
+    let policy = AccessPolicy {
+        allowed_users: config
+            .get::<Vec<String>>("sdk.allowed_users")
+            .unwrap_or_default(),
+        denied_users: config
+            .get::<Vec<String>>("sdk.denied_users")
+            .unwrap_or_default(),
+        token,
+        deny_all: false,
+    };
+
+    if policy.token.is_some() {
+        info!("SDK clients are required to present an access token");
+    }
+
+    *POLICY.write() = policy;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deny_list_takes_precedence() {
+        let policy = AccessPolicy {
+            allowed_users: vec!["alice".to_string(), "1001".to_string()],
+            denied_users: vec!["1001".to_string()],
+            ..Default::default()
+        };
+
+        assert!(is_user_allowed(&policy, 1000, "alice"));
+        assert!(!is_user_allowed(&policy, 1001, "bob"));
+        assert!(!is_user_allowed(&policy, 1002, "carol"));
+
+        // root is always allowed
+        assert!(is_user_allowed(&policy, 0, "root"));
+
+        // an empty allow list allows everybody who is not denied
+        let policy = AccessPolicy {
+            allowed_users: vec![],
+            ..policy
+        };
+
+        assert!(is_user_allowed(&policy, 1002, "carol"));
+        assert!(!is_user_allowed(&policy, 1001, "bob"));
+    }
+
+    #[test]
+    fn unreadable_token_file_denies_all_users() {
+        let policy = AccessPolicy::deny_all();
+
+        assert!(!is_user_allowed(&policy, 1000, "alice"));
+        assert!(is_user_allowed(&policy, 0, "root"));
+
+        assert!(read_token("/nonexistent/eruption-sdk-token").is_err());
+    }
+
+    #[test]
+    fn token_is_verified() {
+        let policy = AccessPolicy {
+            token: Some("secret".to_string()),
+            ..Default::default()
+        };
+
+        assert!(check_token(&policy, "secret"));
+        assert!(!check_token(&policy, "secreT"));
+        assert!(!check_token(&policy, ""));
+
+        assert!(check_token(&AccessPolicy::default(), ""));
+    }
+}
@@ -0,0 +1,126 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::hwdevices::DeviceTrait;
+use crate::scripting::script;
+use crate::{hotplug, init_keyboard_device, init_misc_device, init_mouse_device, input_grab};
+
+lazy_static! {
+    /// Set if the daemon shall stop all device I/O
+    static ref STANDBY_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+    /// Set if the devices have been closed, only modified by the device I/O thread
+    static ref STANDBY_ACTIVE: AtomicBool = AtomicBool::new(false);
+}
+
+/// Returns `true` if the daemon is in standby, or is about to enter it
+pub fn is_active() -> bool {
+    STANDBY_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Enter or leave standby. In standby, the HID devices are closed and the input devices are
+/// released, so that other software may use them, while the profiles and the scripts keep
+/// running. The transition is performed by the device I/O thread, before the next frame is
+/// rendered. Returns `false` if the daemon already is in the requested state
+pub fn set_active(enable: bool) -> bool {
+    let changed = STANDBY_REQUESTED.swap(enable, Ordering::SeqCst) != enable;
+
+    if changed {
+        if enable {
+            info!("Entering standby, releasing all devices");
+        } else {
+            info!("Resuming from standby");
+        }
+    }
+
+    changed
+}
+
+/// Perform a pending transition from or to standby. Must be called by the device I/O
+/// thread. Returns `true` if the daemon is in standby
+pub fn apply() -> bool {
+    let requested = STANDBY_REQUESTED.load(Ordering::SeqCst);
+
+    if STANDBY_ACTIVE.load(Ordering::SeqCst) != requested {
+        if requested {
+            input_grab::set_suspended(true);
+
+            for_each_device(close_device);
+        } else {
+            reopen_devices();
+
+            input_grab::set_suspended(false);
+
+            hotplug::attach_deferred_devices();
+
+            // force a re-render of the LED maps
+            script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+        }
+
+        STANDBY_ACTIVE.store(requested, Ordering::SeqCst);
+    }
+
+    requested
+}
+
+fn for_each_device(f: fn(&mut dyn DeviceTrait)) {
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        f(device.write().as_device_mut());
+    }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        f(device.write().as_device_mut());
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        f(device.write().as_device_mut());
+    }
+}
+
+fn close_device(device: &mut dyn DeviceTrait) {
+    device.close_all().unwrap_or_else(|e| {
+        warn!(
+            "Could not close the device 0x{:04x}:0x{:04x}: {}",
+            device.get_usb_vid(),
+            device.get_usb_pid(),
+            e
+        )
+    });
+}
+
+/// Open and initialize all devices again, like at startup
+fn reopen_devices() {
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        init_keyboard_device(device);
+    }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        init_mouse_device(device);
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        init_misc_device(device);
+    }
+}
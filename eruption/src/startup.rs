@@ -0,0 +1,154 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::Mutex;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::scripting::bytecode_cache;
+use crate::{plugins, profiles};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Point in time when the daemon has been started
+    static ref STARTED_AT: Instant = Instant::now();
+
+    /// Durations of the startup stages that have been completed so far
+    static ref SPANS: Arc<Mutex<Vec<(&'static str, Duration)>>> = Arc::new(Mutex::new(vec![]));
+
+    /// Set as soon as the first frame has been sent to the devices
+    static ref FIRST_FRAME_SENT: AtomicBool = AtomicBool::new(false);
+}
+
+/// Measures the duration of a startup stage, call `end()` when the stage is completed
+#[must_use]
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+}
+
+impl Span {
+    pub fn end(self) {
+        let elapsed = self.start.elapsed();
+
+        debug!(
+            "Startup: {} took {} milliseconds",
+            self.name,
+            elapsed.as_millis()
+        );
+
+        SPANS.lock().push((self.name, elapsed));
+    }
+}
+
+/// Start the startup timer, should be called as early as possible
+pub fn begin() {
+    lazy_static::initialize(&STARTED_AT);
+}
+
+/// Start measuring the duration of the startup stage `name`
+pub fn span(name: &'static str) -> Span {
+    Span {
+        name,
+        start: Instant::now(),
+    }
+}
+
+/// Log the total startup time along with the durations of all startup stages
+pub fn log_summary() {
+    info!(
+        "Startup completed in {} milliseconds",
+        STARTED_AT.elapsed().as_millis()
+    );
+
+    for (name, elapsed) in SPANS.lock().iter() {
+        info!("  {}: {} milliseconds", name, elapsed.as_millis());
+    }
+}
+
+/// Record that a frame has been sent to the devices, the first frame marks the
+/// point in time when the LEDs light up
+pub fn frame_sent() {
+    if !FIRST_FRAME_SENT.swap(true, Ordering::Relaxed) {
+        info!(
+            "First frame sent {} milliseconds after startup",
+            STARTED_AT.elapsed().as_millis()
+        );
+    }
+}
+
+/// Load the profile of the active slot and compile its scripts in the background, while
+/// the devices are being initialized. The main loop picks up the precompiled chunks from
+/// the bytecode cache when it activates the profile
+pub fn spawn_profile_preload_thread(profile_file: PathBuf) -> Result<()> {
+    thread::Builder::new()
+        .name("preload".into())
+        .spawn(move || {
+            let span = span("profile preloading");
+
+            match profiles::Profile::load_fully(&profile_file) {
+                Ok(profile) => {
+                    for manifest in profile.manifests.values() {
+                        bytecode_cache::precompile(&manifest.script_file).unwrap_or_else(|e| {
+                            debug!(
+                                "Could not precompile {}: {}",
+                                manifest.script_file.display(),
+                                e
+                            )
+                        });
+                    }
+                }
+
+                Err(e) => debug!(
+                    "Could not preload the profile {}: {}",
+                    profile_file.display(),
+                    e
+                ),
+            }
+
+            span.end();
+        })?;
+
+    Ok(())
+}
+
+/// Load the persisted state of the scripts in the background, the returned handle
+/// needs to be joined before the first profile is activated
+pub fn spawn_persistence_loader_thread() -> Result<JoinHandle<()>> {
+    let handle = thread::Builder::new()
+        .name("persistence".into())
+        .spawn(|| {
+            let span = span("loading persisted state");
+
+            plugins::PersistencePlugin::load_persistent_data()
+                .unwrap_or_else(|e| warn!("Could not load persisted state: {}", e));
+
+            span.end();
+        })?;
+
+    Ok(handle)
+}
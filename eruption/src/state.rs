@@ -24,15 +24,15 @@ use lazy_static::lazy_static;
 use log::*;
 use parking_lot::RwLock;
 use serde::Serialize;
-use std::collections::HashMap;
-use std::fs;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::color_scheme::ColorScheme;
+use crate::hwdevices::DeviceTrait;
 use crate::plugins::audio;
-use crate::{constants, util};
+use crate::{afk, blending, color_transitions, constants, device_id, state_file};
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -50,6 +50,7 @@ lazy_static! {
     pub static ref STATE: Arc<RwLock<Option<config::Config>>> = Arc::new(RwLock::new(None));
 }
 
+/// The stable identifier of a device, see `device_id::device_id()`
 pub type DeviceMakeModelSerial = String;
 
 #[derive(Serialize)]
@@ -58,13 +59,29 @@ struct State {
     active_slot: usize,
     slot_names: Vec<String>,
     profiles: Vec<PathBuf>,
+
+    /// Profiles that are blended into the slots
+    blends: Vec<blending::SlotBlend>,
+
     enable_sfx: bool,
 
+    /// Global volume of sound effects, in percent
+    sfx_volume: i64,
+
+    /// The audio stream that is visualized, "output" or "microphone"
+    audio_source: String,
+
+    /// Threshold of the noise gate of the microphone input in dBFS
+    noise_gate_threshold: i64,
+
     /// Global brightness
     brightness: i64,
 
+    /// Lengths of the idle periods of the user in seconds, the adaptive AFK timeout is learned from them
+    afk_idle_periods: Vec<u64>,
+
     /// Device specific brightness
-    device_brightness: HashMap<DeviceMakeModelSerial, i32>,
+    device_brightness: BTreeMap<DeviceMakeModelSerial, i32>,
 }
 
 pub fn init_global_runtime_state() -> Result<()> {
@@ -90,18 +107,32 @@ pub fn init_global_runtime_state() -> Result<()> {
     // load state file
     let state_path = PathBuf::from(constants::STATE_DIR).join("eruption.state");
 
-    let state = Config::builder()
-        .add_source(config::File::new(
-            state_path.to_str().unwrap(),
-            config::FileFormat::Toml,
-        ))
-        .set_default("active_slot", 0)?
-        .set_default("enable_sfx", false)?
-        .set_default("brightness", 85)?
-        .build()
-        .map_err(|e| StateError::StateLoadError {
-            description: format!("{}", e),
-        })?;
+    let state = state_file::load(&state_path, |data| {
+        let state = Config::builder()
+            .add_source(config::File::from_str(data, config::FileFormat::Toml))
+            .set_default("active_slot", 0)?
+            .set_default("enable_sfx", false)?
+            .set_default("sfx_volume", constants::DEFAULT_SFX_VOLUME)?
+            .set_default("audio_source", "output")?
+            .set_default(
+                "noise_gate_threshold",
+                constants::DEFAULT_NOISE_GATE_THRESHOLD,
+            )?
+            .set_default("brightness", 85)?
+            .build()
+            .map_err(|e| StateError::StateLoadError {
+                description: format!("{}", e),
+            })?;
+
+        // a truncated state file may still parse, so make sure the slots are present
+        state
+            .get::<Vec<PathBuf>>("profiles")
+            .map_err(|e| StateError::StateLoadError {
+                description: format!("{}", e),
+            })?;
+
+        Ok(state)
+    })?;
 
     *STATE.write() = Some(state);
 
@@ -125,6 +156,24 @@ pub fn init_global_runtime_state() -> Result<()> {
         })
         .unwrap_or_else(|_| warn!("Invalid saved state: profiles"));
 
+    if let Ok(blends) = STATE
+        .read()
+        .as_ref()
+        .unwrap()
+        .get::<Vec<blending::SlotBlend>>("blends")
+    {
+        blending::set_slot_blends(blends);
+    }
+
+    if let Ok(idle_periods) = STATE
+        .read()
+        .as_ref()
+        .unwrap()
+        .get::<Vec<u64>>("afk_idle_periods")
+    {
+        afk::set_idle_periods(idle_periods);
+    }
+
     crate::ACTIVE_SLOT.store(
         STATE
             .read()
@@ -145,6 +194,39 @@ pub fn init_global_runtime_state() -> Result<()> {
         Ordering::SeqCst,
     );
 
+    audio::SFX_VOLUME.store(
+        STATE
+            .read()
+            .as_ref()
+            .unwrap()
+            .get::<i64>("sfx_volume")
+            .unwrap()
+            .clamp(0, 100),
+        Ordering::SeqCst,
+    );
+
+    match STATE
+        .read()
+        .as_ref()
+        .unwrap()
+        .get::<String>("audio_source")
+        .unwrap()
+        .parse::<audio::AudioSource>()
+    {
+        Ok(source) => audio::set_audio_source(source),
+        Err(e) => warn!("Invalid saved state: {}", e),
+    }
+
+    audio::set_noise_gate_threshold(
+        STATE
+            .read()
+            .as_ref()
+            .unwrap()
+            .get::<i64>("noise_gate_threshold")
+            .unwrap()
+            .clamp(-96, 0),
+    );
+
     *slot_names = STATE
         .read()
         .as_ref()
@@ -162,122 +244,98 @@ pub fn init_global_runtime_state() -> Result<()> {
 }
 
 pub fn init_global_runtime_state_late() -> Result<()> {
-    // TODO: retain inactive device's brightness values across
-    //       restarts of the Eruption daemon
-
-    let empty = Config::builder().build()?;
-
-    if let Ok(device_brightness) = STATE
-        .read()
-        .as_ref()
-        .unwrap_or(&empty)
-        .get_table("device_brightness")
-    {
-        for device in &*crate::KEYBOARD_DEVICES.read() {
-            let make = format!("0x{:x}", device.read().get_usb_vid());
-            let model = format!("0x{:x}", device.read().get_usb_pid());
-            let serial = device.read().get_serial().unwrap_or("").to_string();
-
-            let val = config::Value::new(None, 100);
-
-            let brightness = device_brightness
-                .get(&format!("{}:{}:{}", make, model, serial))
-                .unwrap_or(&val);
-
-            let brightness = brightness.clone().into_int().unwrap_or(100) as i32;
-
-            debug!("{}:{}:{} Brightness: {}", make, model, serial, brightness);
-
-            device.write().set_local_brightness(brightness)?;
-        }
-
-        for device in &*crate::MOUSE_DEVICES.read() {
-            let make = format!("0x{:x}", device.read().get_usb_vid());
-            let model = format!("0x{:x}", device.read().get_usb_pid());
-            let serial = device.read().get_serial().unwrap_or("").to_string();
-
-            let val = config::Value::new(None, 100);
-
-            let brightness = device_brightness
-                .get(&format!("{}:{}:{}", make, model, serial))
-                .unwrap_or(&val);
-
-            let brightness = brightness.clone().into_int().unwrap_or(100) as i32;
-
-            debug!("{}:{}:{} Brightness: {}", make, model, serial, brightness);
+    for device in &*crate::KEYBOARD_DEVICES.read() {
+        let brightness = get_saved_device_brightness(device.read().as_device());
+        device.write().set_local_brightness(brightness)?;
+    }
 
-            device.write().set_local_brightness(brightness)?;
-        }
+    for device in &*crate::MOUSE_DEVICES.read() {
+        let brightness = get_saved_device_brightness(device.read().as_device());
+        device.write().set_local_brightness(brightness)?;
+    }
 
-        for device in &*crate::MISC_DEVICES.read() {
-            let make = format!("0x{:x}", device.read().get_usb_vid());
-            let model = format!("0x{:x}", device.read().get_usb_pid());
-            let serial = device.read().get_serial().unwrap_or("").to_string();
+    for device in &*crate::MISC_DEVICES.read() {
+        let brightness = get_saved_device_brightness(device.read().as_device());
+        device.write().set_local_brightness(brightness)?;
+    }
 
-            let val = config::Value::new(None, 100);
+    Ok(())
+}
 
-            let brightness = device_brightness
-                .get(&format!("{}:{}:{}", make, model, serial))
-                .unwrap_or(&val);
+/// Returns the device specific brightness values of the state file, including
+/// the values of devices that are currently not connected
+fn get_saved_device_brightness_values() -> BTreeMap<DeviceMakeModelSerial, i32> {
+    STATE
+        .read()
+        .as_ref()
+        .and_then(|state| state.get_table("device_brightness").ok())
+        .map(|table| {
+            table
+                .into_iter()
+                .filter_map(|(key, value)| value.into_int().ok().map(|v| (key, v as i32)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-            let brightness = brightness.clone().into_int().unwrap_or(100) as i32;
+/// Returns the saved brightness of a device, or 100 percent if no brightness has been saved
+pub fn get_saved_device_brightness(device: &dyn DeviceTrait) -> i32 {
+    let brightness =
+        *device_id::lookup(&get_saved_device_brightness_values(), device).unwrap_or(&100);
 
-            debug!("{}:{}:{} Brightness: {}", make, model, serial, brightness);
+    debug!(
+        "{} Brightness: {}",
+        device_id::device_id(device),
+        brightness
+    );
 
-            device.write().set_local_brightness(brightness)?;
-        }
-    }
+    brightness
+}
 
-    Ok(())
+/// Returns `true` if the state file contains saved settings of `device`, i.e. the
+/// device has been connected before
+pub fn is_known_device(device: &dyn DeviceTrait) -> bool {
+    device_id::lookup(&get_saved_device_brightness_values(), device).is_some()
 }
 
 pub fn save_runtime_state() -> Result<()> {
     let state_path = PathBuf::from(constants::STATE_DIR).join("eruption.state");
 
-    let mut device_brightness = HashMap::new();
+    // retain the brightness of devices that are currently not connected
+    let mut device_brightness = get_saved_device_brightness_values();
 
     for device in &*crate::KEYBOARD_DEVICES.read() {
-        let make = format!("0x{:x}", device.read().get_usb_vid());
-        let model = format!("0x{:x}", device.read().get_usb_pid());
-        let serial = device.read().get_serial().unwrap_or("").to_string();
-
-        let brightness = device.read().get_local_brightness()?;
+        let device = device.read();
 
-        debug!("{}:{}:{} Brightness: {}", make, model, serial, brightness);
-
-        device_brightness.insert(format!("{}:{}:{}", make, model, serial), brightness);
+        let key = device_id::migrate(&mut device_brightness, device.as_device());
+        device_brightness.insert(key, device.get_local_brightness()?);
     }
 
     for device in &*crate::MOUSE_DEVICES.read() {
-        let make = format!("0x{:x}", device.read().get_usb_vid());
-        let model = format!("0x{:x}", device.read().get_usb_pid());
-        let serial = device.read().get_serial().unwrap_or("").to_string();
-
-        let brightness = device.read().get_local_brightness()?;
-
-        debug!("{}:{}:{} Brightness: {}", make, model, serial, brightness);
+        let device = device.read();
 
-        device_brightness.insert(format!("{}:{}:{}", make, model, serial), brightness);
+        let key = device_id::migrate(&mut device_brightness, device.as_device());
+        device_brightness.insert(key, device.get_local_brightness()?);
     }
 
     for device in &*crate::MISC_DEVICES.read() {
-        let make = format!("0x{:x}", device.read().get_usb_vid());
-        let model = format!("0x{:x}", device.read().get_usb_pid());
-        let serial = device.read().get_serial().unwrap_or("").to_string();
+        let device = device.read();
 
-        let brightness = device.read().get_local_brightness()?;
-
-        debug!("{}:{}:{} Brightness: {}", make, model, serial, brightness);
-
-        device_brightness.insert(format!("{}:{}:{}", make, model, serial), brightness);
+        let key = device_id::migrate(&mut device_brightness, device.as_device());
+        device_brightness.insert(key, device.get_local_brightness()?);
     }
 
     let config = State {
         active_slot: crate::ACTIVE_SLOT.load(Ordering::SeqCst),
         slot_names: crate::SLOT_NAMES.lock().clone(),
         profiles: crate::SLOT_PROFILES.lock().as_ref().unwrap().clone(),
+        blends: blending::get_slot_blends(),
         enable_sfx: audio::ENABLE_SFX.load(Ordering::SeqCst),
+        sfx_volume: audio::SFX_VOLUME.load(Ordering::SeqCst),
+        audio_source: audio::get_audio_source().to_string(),
+        noise_gate_threshold: audio::NOISE_GATE_THRESHOLD.load(Ordering::SeqCst),
         brightness: crate::BRIGHTNESS.load(Ordering::SeqCst) as i64,
+        afk_idle_periods: afk::get_idle_periods(),
         device_brightness,
     };
 
@@ -285,7 +343,7 @@ pub fn save_runtime_state() -> Result<()> {
         description: format!("{}", e),
     })?;
 
-    fs::write(state_path, toml)?;
+    state_file::write(&state_path, &toml)?;
 
     Ok(())
 }
@@ -316,8 +374,26 @@ fn perform_sanity_checks() {
 pub fn save_color_schemes() -> Result<()> {
     let file_name = PathBuf::from(&constants::STATE_DIR).join("color-schemes.state");
 
-    let data = toml::to_string_pretty(&*crate::NAMED_COLOR_SCHEMES.read())?;
-    util::write_file(&file_name, &data)?;
+    let mut color_schemes = crate::NAMED_COLOR_SCHEMES.read().clone();
+
+    // save the final colors of color schemes that are still transitioning
+    color_schemes.extend(color_transitions::get_targets());
+
+    // color schemes that are being previewed have not been committed yet
+    for (name, original) in crate::COLOR_SCHEME_PREVIEWS.read().iter() {
+        match original {
+            Some(color_scheme) => {
+                color_schemes.insert(name.clone(), color_scheme.clone());
+            }
+
+            None => {
+                color_schemes.remove(name);
+            }
+        }
+    }
+
+    let data = toml::to_string_pretty(&color_schemes)?;
+    state_file::write(&file_name, &data)?;
 
     Ok(())
 }
@@ -325,8 +401,8 @@ pub fn save_color_schemes() -> Result<()> {
 pub fn load_color_schemes() -> Result<()> {
     let file_name = PathBuf::from(&constants::STATE_DIR).join("color-schemes.state");
 
-    let data = fs::read_to_string(file_name)?;
-    let color_schemes: HashMap<String, ColorScheme> = toml::from_str(&data)?;
+    let color_schemes: HashMap<String, ColorScheme> =
+        state_file::load(&file_name, |data| Ok(toml::from_str(data)?))?;
 
     *crate::NAMED_COLOR_SCHEMES.write() = color_schemes;
 
@@ -0,0 +1,337 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+#![allow(dead_code)]
+
+use log::*;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::constants;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Prefix of the first line of a state file, followed by the CRC-32 of the remaining lines.
+/// It is a TOML comment, so that state files stay readable by other tools
+const CHECKSUM_PREFIX: &str = "# eruption-checksum: ";
+
+#[derive(Debug, thiserror::Error)]
+pub enum StateFileError {
+    #[error("Checksum mismatch: expected {expected:08x}, found {actual:08x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    #[error("Invalid checksum: {description}")]
+    InvalidChecksum { description: String },
+
+    #[error("No valid generation of the state file {path} is left")]
+    NoValidGeneration { path: String },
+}
+
+/// Logged whenever a state file could not be used, and an older generation has been loaded
+/// instead
+#[derive(Debug, Clone, Serialize)]
+struct RecoveryEvent {
+    file: PathBuf,
+
+    /// The generation that has been loaded, 1 is the most recent previous generation
+    recovered_generation: usize,
+
+    /// The files that have been skipped, along with the reason
+    discarded: Vec<(PathBuf, String)>,
+
+    /// The file the corrupted state has been moved to, for later inspection
+    preserved_as: Option<PathBuf>,
+}
+
+/// Returns the path of the generation `generation` of the state file `path`, generation 0
+/// being the state file itself
+fn generation_path(path: &Path, generation: usize) -> PathBuf {
+    with_suffix(path, &format!(".{}", generation), generation == 0)
+}
+
+fn with_suffix(path: &Path, suffix: &str, keep: bool) -> PathBuf {
+    if keep {
+        return path.to_path_buf();
+    }
+
+    let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    file_name.push(suffix);
+
+    path.with_file_name(file_name)
+}
+
+/// Prepend the checksum of `data`
+fn seal(data: &str) -> String {
+    format!(
+        "{}{:08x}\n{}",
+        CHECKSUM_PREFIX,
+        crc32fast::hash(data.as_bytes()),
+        data
+    )
+}
+
+/// Verify the checksum of the contents of a state file and strip it. Files without a
+/// checksum have been written by previous versions of Eruption, they are accepted as is
+fn unseal(contents: &str) -> Result<&str> {
+    let (header, data) = match contents.strip_prefix(CHECKSUM_PREFIX) {
+        Some(rest) => rest.split_once('\n').unwrap_or((rest, "")),
+        None => return Ok(contents),
+    };
+
+    let expected =
+        u32::from_str_radix(header.trim(), 16).map_err(|e| StateFileError::InvalidChecksum {
+            description: format!("{}", e),
+        })?;
+
+    let actual = crc32fast::hash(data.as_bytes());
+
+    if expected != actual {
+        return Err(StateFileError::ChecksumMismatch { expected, actual }.into());
+    }
+
+    Ok(data)
+}
+
+/// Read and verify a single generation of a state file
+fn read_verified(path: &Path) -> Result<String> {
+    let contents = fs::read_to_string(path)?;
+
+    Ok(unseal(&contents)?.to_string())
+}
+
+/// Keep the current state file as the most recent previous generation, and drop the
+/// oldest one. Corrupted state files are not kept, so they can't push out good generations
+fn rotate_generations(path: &Path) -> Result<()> {
+    if read_verified(path).is_err() {
+        return Ok(());
+    }
+
+    for generation in (1..constants::STATE_FILE_GENERATIONS).rev() {
+        let from = generation_path(path, generation);
+
+        if from.exists() {
+            fs::rename(&from, generation_path(path, generation + 1))?;
+        }
+    }
+
+    if constants::STATE_FILE_GENERATIONS > 0 {
+        fs::copy(path, generation_path(path, 1))?;
+    }
+
+    Ok(())
+}
+
+/// Atomically replace the state file `path` with `data`. The data is written to a temporary
+/// file first, that is renamed over the state file once it has been flushed to disk, so a
+/// crash leaves either the previous or the new state file behind, but never a truncated one
+pub fn write<P: AsRef<Path>>(path: &P, data: &str) -> Result<()> {
+    let path = path.as_ref();
+
+    log::info!("Writing to file: {}", &path.display());
+
+    rotate_generations(path).unwrap_or_else(|e| {
+        warn!(
+            "Could not keep the previous generation of {}: {}",
+            path.display(),
+            e
+        )
+    });
+
+    replace(path, seal(data).as_bytes())
+}
+
+/// Atomically restore the state file `path` to `contents`, as previously read from disk.
+/// Unlike `write`, the contents are kept verbatim and no generation is rotated
+pub fn restore<P: AsRef<Path>>(path: &P, contents: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+
+    log::info!("Restoring file: {}", &path.display());
+
+    replace(path, contents)
+}
+
+/// Write `contents` to a temporary file, and rename it over `path` once it has been flushed
+fn replace(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp_path = with_suffix(path, ".tmp", false);
+
+    {
+        let mut file = File::create(&tmp_path)?;
+
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+
+    fs::rename(&tmp_path, path)?;
+
+    // make the rename durable
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// Load the state file `path`, parsing it with `parse`. If the state file is missing,
+/// truncated, or fails to parse, the previous generations are tried, newest first. The
+/// corrupted state file is preserved with the suffix `.corrupt`, and a recovery event is
+/// logged. Fails with `io::ErrorKind::NotFound` if no generation exists at all
+pub fn load<P, T, F>(path: &P, parse: F) -> Result<T>
+where
+    P: AsRef<Path>,
+    F: Fn(&str) -> Result<T>,
+{
+    let path = path.as_ref();
+
+    let mut discarded = Vec::new();
+
+    for generation in 0..=constants::STATE_FILE_GENERATIONS {
+        let candidate = generation_path(path, generation);
+
+        if !candidate.exists() {
+            continue;
+        }
+
+        match read_verified(&candidate).and_then(|data| parse(&data)) {
+            Ok(result) => {
+                if generation > 0 {
+                    recover(path, generation, discarded);
+                }
+
+                return Ok(result);
+            }
+
+            Err(e) => {
+                warn!("Skipping the state file {}: {}", candidate.display(), e);
+
+                discarded.push((candidate, format!("{}", e)));
+            }
+        }
+    }
+
+    if discarded.is_empty() {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}: No such file or directory", path.display()),
+        )
+        .into())
+    } else {
+        Err(StateFileError::NoValidGeneration {
+            path: path.display().to_string(),
+        }
+        .into())
+    }
+}
+
+/// Move a corrupted state file out of the way, and log a recovery event
+fn recover(path: &Path, generation: usize, discarded: Vec<(PathBuf, String)>) {
+    let preserved_as = if discarded.iter().any(|(p, _)| p == path) {
+        let corrupt_path = with_suffix(path, ".corrupt", false);
+
+        match fs::rename(path, &corrupt_path) {
+            Ok(()) => Some(corrupt_path),
+
+            Err(e) => {
+                error!(
+                    "Could not preserve the corrupted state file {}: {}",
+                    path.display(),
+                    e
+                );
+
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let event = RecoveryEvent {
+        file: path.to_path_buf(),
+        recovered_generation: generation,
+        discarded,
+        preserved_as,
+    };
+
+    warn!(
+        "Recovered the state file {} from a previous generation: {}",
+        path.display(),
+        serde_json::to_string(&event).unwrap_or_else(|_| format!("{:?}", event))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_number(data: &str) -> Result<u32> {
+        Ok(data.trim().parse::<u32>()?)
+    }
+
+    #[test]
+    fn checksums_are_verified() {
+        let sealed = seal("active_slot = 1\n");
+
+        assert_eq!(unseal(&sealed).unwrap(), "active_slot = 1\n");
+
+        // state files of previous versions have no checksum
+        assert_eq!(unseal("active_slot = 1\n").unwrap(), "active_slot = 1\n");
+
+        let truncated = &sealed[..sealed.len() - 3];
+        assert!(unseal(truncated).is_err());
+    }
+
+    #[test]
+    fn falls_back_to_previous_generations() {
+        let dir = std::env::temp_dir().join(format!("eruption-state-file-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("test.state");
+
+        assert_eq!(
+            load(&path, parse_number)
+                .unwrap_err()
+                .downcast_ref::<io::Error>()
+                .map(|e| e.kind()),
+            Some(io::ErrorKind::NotFound)
+        );
+
+        write(&path, "1").unwrap();
+        write(&path, "2").unwrap();
+        write(&path, "3").unwrap();
+
+        assert_eq!(load(&path, parse_number).unwrap(), 3);
+
+        // a truncated state file is replaced by the most recent previous generation
+        fs::write(&path, "# eruption-checksum: 0000").unwrap();
+
+        assert_eq!(load(&path, parse_number).unwrap(), 2);
+        assert!(with_suffix(&path, ".corrupt", false).exists());
+
+        // state files that fail to parse are skipped as well
+        fs::write(generation_path(&path, 1), seal("garbage")).unwrap();
+
+        assert_eq!(load(&path, parse_number).unwrap(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
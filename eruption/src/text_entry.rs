@@ -0,0 +1,514 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use evdev_rs::enums::EV_KEY;
+use log::*;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use crate::plugins::macros;
+use crate::{constants, indicators};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TextEntryError {
+    #[error("The virtual keyboard is not available")]
+    UinputError,
+
+    #[error("Could not run wtype: {msg}")]
+    WtypeError { msg: String },
+}
+
+/// The keymaps that characters are looked up in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Keymap {
+    Us,
+    German,
+}
+
+impl Keymap {
+    /// Select the keymap from the configuration, or else from the active keyboard layout
+    fn active() -> Self {
+        let configured = crate::CONFIG
+            .lock()
+            .as_ref()
+            .and_then(|config| config.get::<String>("text_entry.keymap").ok());
+
+        let name = configured.unwrap_or_else(|| indicators::get_keyboard_layout().name);
+        let name = name.to_lowercase();
+
+        if name == "de" || name.starts_with("german") {
+            Keymap::German
+        } else {
+            Keymap::Us
+        }
+    }
+
+    fn lookup(self, c: char) -> Option<Keystroke> {
+        match self {
+            Keymap::Us => lookup_us(c),
+            Keymap::German => lookup_german(c),
+        }
+    }
+}
+
+/// How characters are entered that are not part of the keymap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fallback {
+    /// Enter the code point via Ctrl+Shift+U, as supported by GTK and IBus
+    Unicode,
+
+    /// Run `wtype`, requires access to the Wayland session of the user
+    Wtype,
+
+    /// Skip the character
+    None,
+}
+
+impl Fallback {
+    fn from_config() -> Self {
+        let fallback = crate::CONFIG
+            .lock()
+            .as_ref()
+            .and_then(|config| config.get::<String>("text_entry.fallback").ok());
+
+        match fallback.as_deref() {
+            None | Some("unicode") => Fallback::Unicode,
+            Some("wtype") => Fallback::Wtype,
+            Some("none") => Fallback::None,
+
+            Some(fallback) => {
+                warn!("Invalid text entry fallback: {}", fallback);
+
+                Fallback::Unicode
+            }
+        }
+    }
+}
+
+/// A key press along with the modifiers that have to be held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Keystroke {
+    key: EV_KEY,
+    shift: bool,
+    altgr: bool,
+}
+
+impl Keystroke {
+    fn plain(key: EV_KEY) -> Self {
+        Self {
+            key,
+            shift: false,
+            altgr: false,
+        }
+    }
+
+    fn shift(key: EV_KEY) -> Self {
+        Self {
+            key,
+            shift: true,
+            altgr: false,
+        }
+    }
+
+    fn altgr(key: EV_KEY) -> Self {
+        Self {
+            key,
+            shift: false,
+            altgr: true,
+        }
+    }
+    /// Caps Lock inverts the shift level of the keys of letters, so the shift level of the
+    /// keystroke of the character `c` is inverted as well while Caps Lock is active
+    fn with_caps_lock(self, c: char, caps_lock: bool) -> Self {
+        // letters like 'ß' have no single character counterpart, and are not affected
+        let (upper, lower) = (c.to_uppercase(), c.to_lowercase());
+        let is_letter = upper.len() == 1 && lower.len() == 1 && !upper.eq(lower);
+
+        if caps_lock && is_letter && !self.altgr {
+            Self {
+                shift: !self.shift,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+}
+
+/// Type the UTF-8 string `text` on the virtual keyboard. Characters are looked up in the
+/// keymap of the active keyboard layout, others are entered using the configured fallback
+pub fn type_text(text: &str) {
+    let text = text.to_owned();
+
+    thread::Builder::new()
+        .name("uinput/text".to_owned())
+        .spawn(move || {
+            let keymap = Keymap::active();
+            let fallback = Fallback::from_config();
+            let caps_lock = indicators::get_lock_state().caps_lock;
+
+            for c in text.chars() {
+                let result = match keymap.lookup(c) {
+                    Some(keystroke) => type_keystroke(keystroke.with_caps_lock(c, caps_lock)),
+
+                    None => match fallback {
+                        Fallback::Unicode => type_code_point(keymap, c, caps_lock),
+                        Fallback::Wtype => run_wtype(c),
+
+                        Fallback::None => {
+                            debug!("Skipping character not in the keymap: {:?}", c);
+
+                            Ok(())
+                        }
+                    },
+                };
+
+                if let Err(e) = result {
+                    error!("Could not type the text: {}", e);
+
+                    break;
+                }
+            }
+        })
+        .unwrap_or_else(|e| {
+            error!("Could not spawn a thread: {}", e);
+            panic!()
+        });
+}
+
+fn inject(key: EV_KEY, down: bool) -> Result<()> {
+    macros::UINPUT_TX
+        .read()
+        .as_ref()
+        .ok_or(TextEntryError::UinputError)?
+        .send(macros::Message::InjectKey {
+            key: key as u32,
+            down,
+        })
+        .map_err(|_| TextEntryError::UinputError)?;
+
+    // give the consumers of the virtual keyboard a chance to keep up
+    thread::sleep(Duration::from_millis(
+        constants::TEXT_ENTRY_KEY_DELAY_MILLIS,
+    ));
+
+    Ok(())
+}
+
+fn type_keystroke(keystroke: Keystroke) -> Result<()> {
+    if keystroke.shift {
+        inject(EV_KEY::KEY_LEFTSHIFT, true)?;
+    }
+
+    if keystroke.altgr {
+        inject(EV_KEY::KEY_RIGHTALT, true)?;
+    }
+
+    inject(keystroke.key, true)?;
+    inject(keystroke.key, false)?;
+
+    if keystroke.altgr {
+        inject(EV_KEY::KEY_RIGHTALT, false)?;
+    }
+
+    if keystroke.shift {
+        inject(EV_KEY::KEY_LEFTSHIFT, false)?;
+    }
+
+    Ok(())
+}
+
+/// Enter the code point of `c` in hex, after pressing Ctrl+Shift+U
+fn type_code_point(keymap: Keymap, c: char, caps_lock: bool) -> Result<()> {
+    inject(EV_KEY::KEY_LEFTCTRL, true)?;
+    inject(EV_KEY::KEY_LEFTSHIFT, true)?;
+    inject(EV_KEY::KEY_U, true)?;
+    inject(EV_KEY::KEY_U, false)?;
+    inject(EV_KEY::KEY_LEFTSHIFT, false)?;
+    inject(EV_KEY::KEY_LEFTCTRL, false)?;
+
+    for digit in format!("{:x}", c as u32).chars() {
+        if let Some(keystroke) = keymap.lookup(digit) {
+            type_keystroke(keystroke.with_caps_lock(digit, caps_lock))?;
+        }
+    }
+
+    type_keystroke(Keystroke::plain(EV_KEY::KEY_SPACE))
+}
+
+fn run_wtype(c: char) -> Result<()> {
+    let status = Command::new("wtype")
+        .arg("--")
+        .arg(c.to_string())
+        .status()
+        .map_err(|e| TextEntryError::WtypeError { msg: e.to_string() })?;
+
+    if !status.success() {
+        return Err(TextEntryError::WtypeError {
+            msg: format!("wtype exited with {}", status),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Returns the key that is labeled with the letter `c` on a QWERTY keyboard
+fn qwerty_letter_key(c: char) -> Option<EV_KEY> {
+    let key = match c {
+        'a' => EV_KEY::KEY_A,
+        'b' => EV_KEY::KEY_B,
+        'c' => EV_KEY::KEY_C,
+        'd' => EV_KEY::KEY_D,
+        'e' => EV_KEY::KEY_E,
+        'f' => EV_KEY::KEY_F,
+        'g' => EV_KEY::KEY_G,
+        'h' => EV_KEY::KEY_H,
+        'i' => EV_KEY::KEY_I,
+        'j' => EV_KEY::KEY_J,
+        'k' => EV_KEY::KEY_K,
+        'l' => EV_KEY::KEY_L,
+        'm' => EV_KEY::KEY_M,
+        'n' => EV_KEY::KEY_N,
+        'o' => EV_KEY::KEY_O,
+        'p' => EV_KEY::KEY_P,
+        'q' => EV_KEY::KEY_Q,
+        'r' => EV_KEY::KEY_R,
+        's' => EV_KEY::KEY_S,
+        't' => EV_KEY::KEY_T,
+        'u' => EV_KEY::KEY_U,
+        'v' => EV_KEY::KEY_V,
+        'w' => EV_KEY::KEY_W,
+        'x' => EV_KEY::KEY_X,
+        'y' => EV_KEY::KEY_Y,
+        'z' => EV_KEY::KEY_Z,
+
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+/// Returns the key of the digit `c` in the number row
+fn digit_key(c: char) -> Option<EV_KEY> {
+    let key = match c {
+        '1' => EV_KEY::KEY_1,
+        '2' => EV_KEY::KEY_2,
+        '3' => EV_KEY::KEY_3,
+        '4' => EV_KEY::KEY_4,
+        '5' => EV_KEY::KEY_5,
+        '6' => EV_KEY::KEY_6,
+        '7' => EV_KEY::KEY_7,
+        '8' => EV_KEY::KEY_8,
+        '9' => EV_KEY::KEY_9,
+        '0' => EV_KEY::KEY_0,
+
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+/// Keys that are the same on all supported keymaps
+fn lookup_common(c: char) -> Option<Keystroke> {
+    match c {
+        ' ' => Some(Keystroke::plain(EV_KEY::KEY_SPACE)),
+        '\n' => Some(Keystroke::plain(EV_KEY::KEY_ENTER)),
+        '\t' => Some(Keystroke::plain(EV_KEY::KEY_TAB)),
+
+        _ => digit_key(c).map(Keystroke::plain),
+    }
+}
+
+fn lookup_us(c: char) -> Option<Keystroke> {
+    if c.is_ascii_lowercase() {
+        return qwerty_letter_key(c).map(Keystroke::plain);
+    } else if c.is_ascii_uppercase() {
+        return qwerty_letter_key(c.to_ascii_lowercase()).map(Keystroke::shift);
+    }
+
+    let keystroke = match c {
+        '!' => Keystroke::shift(EV_KEY::KEY_1),
+        '@' => Keystroke::shift(EV_KEY::KEY_2),
+        '#' => Keystroke::shift(EV_KEY::KEY_3),
+        '$' => Keystroke::shift(EV_KEY::KEY_4),
+        '%' => Keystroke::shift(EV_KEY::KEY_5),
+        '^' => Keystroke::shift(EV_KEY::KEY_6),
+        '&' => Keystroke::shift(EV_KEY::KEY_7),
+        '*' => Keystroke::shift(EV_KEY::KEY_8),
+        '(' => Keystroke::shift(EV_KEY::KEY_9),
+        ')' => Keystroke::shift(EV_KEY::KEY_0),
+        '-' => Keystroke::plain(EV_KEY::KEY_MINUS),
+        '_' => Keystroke::shift(EV_KEY::KEY_MINUS),
+        '=' => Keystroke::plain(EV_KEY::KEY_EQUAL),
+        '+' => Keystroke::shift(EV_KEY::KEY_EQUAL),
+        '[' => Keystroke::plain(EV_KEY::KEY_LEFTBRACE),
+        '{' => Keystroke::shift(EV_KEY::KEY_LEFTBRACE),
+        ']' => Keystroke::plain(EV_KEY::KEY_RIGHTBRACE),
+        '}' => Keystroke::shift(EV_KEY::KEY_RIGHTBRACE),
+        '\\' => Keystroke::plain(EV_KEY::KEY_BACKSLASH),
+        '|' => Keystroke::shift(EV_KEY::KEY_BACKSLASH),
+        ';' => Keystroke::plain(EV_KEY::KEY_SEMICOLON),
+        ':' => Keystroke::shift(EV_KEY::KEY_SEMICOLON),
+        '\'' => Keystroke::plain(EV_KEY::KEY_APOSTROPHE),
+        '"' => Keystroke::shift(EV_KEY::KEY_APOSTROPHE),
+        '`' => Keystroke::plain(EV_KEY::KEY_GRAVE),
+        '~' => Keystroke::shift(EV_KEY::KEY_GRAVE),
+        ',' => Keystroke::plain(EV_KEY::KEY_COMMA),
+        '<' => Keystroke::shift(EV_KEY::KEY_COMMA),
+        '.' => Keystroke::plain(EV_KEY::KEY_DOT),
+        '>' => Keystroke::shift(EV_KEY::KEY_DOT),
+        '/' => Keystroke::plain(EV_KEY::KEY_SLASH),
+        '?' => Keystroke::shift(EV_KEY::KEY_SLASH),
+
+        _ => return lookup_common(c),
+    };
+
+    Some(keystroke)
+}
+
+fn lookup_german(c: char) -> Option<Keystroke> {
+    // QWERTZ: Y and Z are swapped
+    let qwertz = |c: char| match c {
+        'y' => Some(EV_KEY::KEY_Z),
+        'z' => Some(EV_KEY::KEY_Y),
+        c => qwerty_letter_key(c),
+    };
+
+    if c.is_ascii_lowercase() {
+        return qwertz(c).map(Keystroke::plain);
+    } else if c.is_ascii_uppercase() {
+        return qwertz(c.to_ascii_lowercase()).map(Keystroke::shift);
+    }
+
+    let keystroke = match c {
+        '!' => Keystroke::shift(EV_KEY::KEY_1),
+        '"' => Keystroke::shift(EV_KEY::KEY_2),
+        '§' => Keystroke::shift(EV_KEY::KEY_3),
+        '$' => Keystroke::shift(EV_KEY::KEY_4),
+        '%' => Keystroke::shift(EV_KEY::KEY_5),
+        '&' => Keystroke::shift(EV_KEY::KEY_6),
+        '/' => Keystroke::shift(EV_KEY::KEY_7),
+        '(' => Keystroke::shift(EV_KEY::KEY_8),
+        ')' => Keystroke::shift(EV_KEY::KEY_9),
+        '=' => Keystroke::shift(EV_KEY::KEY_0),
+        '²' => Keystroke::altgr(EV_KEY::KEY_2),
+        '³' => Keystroke::altgr(EV_KEY::KEY_3),
+        '{' => Keystroke::altgr(EV_KEY::KEY_7),
+        '[' => Keystroke::altgr(EV_KEY::KEY_8),
+        ']' => Keystroke::altgr(EV_KEY::KEY_9),
+        '}' => Keystroke::altgr(EV_KEY::KEY_0),
+        'ß' => Keystroke::plain(EV_KEY::KEY_MINUS),
+        '?' => Keystroke::shift(EV_KEY::KEY_MINUS),
+        '\\' => Keystroke::altgr(EV_KEY::KEY_MINUS),
+        '@' => Keystroke::altgr(EV_KEY::KEY_Q),
+        '€' => Keystroke::altgr(EV_KEY::KEY_E),
+        'ü' => Keystroke::plain(EV_KEY::KEY_LEFTBRACE),
+        'Ü' => Keystroke::shift(EV_KEY::KEY_LEFTBRACE),
+        '+' => Keystroke::plain(EV_KEY::KEY_RIGHTBRACE),
+        '*' => Keystroke::shift(EV_KEY::KEY_RIGHTBRACE),
+        '~' => Keystroke::altgr(EV_KEY::KEY_RIGHTBRACE),
+        'ö' => Keystroke::plain(EV_KEY::KEY_SEMICOLON),
+        'Ö' => Keystroke::shift(EV_KEY::KEY_SEMICOLON),
+        'ä' => Keystroke::plain(EV_KEY::KEY_APOSTROPHE),
+        'Ä' => Keystroke::shift(EV_KEY::KEY_APOSTROPHE),
+        '#' => Keystroke::plain(EV_KEY::KEY_BACKSLASH),
+        '\'' => Keystroke::shift(EV_KEY::KEY_BACKSLASH),
+        '°' => Keystroke::shift(EV_KEY::KEY_GRAVE),
+        '<' => Keystroke::plain(EV_KEY::KEY_102ND),
+        '>' => Keystroke::shift(EV_KEY::KEY_102ND),
+        '|' => Keystroke::altgr(EV_KEY::KEY_102ND),
+        ',' => Keystroke::plain(EV_KEY::KEY_COMMA),
+        ';' => Keystroke::shift(EV_KEY::KEY_COMMA),
+        '.' => Keystroke::plain(EV_KEY::KEY_DOT),
+        ':' => Keystroke::shift(EV_KEY::KEY_DOT),
+        '-' => Keystroke::plain(EV_KEY::KEY_SLASH),
+        '_' => Keystroke::shift(EV_KEY::KEY_SLASH),
+        'µ' => Keystroke::altgr(EV_KEY::KEY_M),
+
+        _ => return lookup_common(c),
+    };
+
+    Some(keystroke)
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev_rs::enums::EV_KEY;
+
+    use super::{Keymap, Keystroke};
+
+    #[test]
+    fn keymap_lookup() {
+        assert_eq!(
+            Keymap::Us.lookup('a'),
+            Some(Keystroke::plain(EV_KEY::KEY_A))
+        );
+        assert_eq!(
+            Keymap::Us.lookup('Z'),
+            Some(Keystroke::shift(EV_KEY::KEY_Z))
+        );
+        assert_eq!(
+            Keymap::Us.lookup('@'),
+            Some(Keystroke::shift(EV_KEY::KEY_2))
+        );
+
+        assert_eq!(
+            Keymap::German.lookup('z'),
+            Some(Keystroke::plain(EV_KEY::KEY_Y))
+        );
+        assert_eq!(
+            Keymap::German.lookup('@'),
+            Some(Keystroke::altgr(EV_KEY::KEY_Q))
+        );
+        assert_eq!(
+            Keymap::German.lookup('7'),
+            Some(Keystroke::plain(EV_KEY::KEY_7))
+        );
+
+        assert_eq!(Keymap::Us.lookup('ä'), None);
+        assert_eq!(Keymap::German.lookup('é'), None);
+    }
+
+    #[test]
+    fn caps_lock_inverts_the_shift_level_of_letters() {
+        let type_char = |c: char| Keymap::German.lookup(c).unwrap().with_caps_lock(c, true);
+
+        assert_eq!(type_char('a'), Keystroke::shift(EV_KEY::KEY_A));
+        assert_eq!(type_char('Z'), Keystroke::plain(EV_KEY::KEY_Y));
+        assert_eq!(type_char('7'), Keystroke::plain(EV_KEY::KEY_7));
+        assert_eq!(type_char('ß'), Keystroke::plain(EV_KEY::KEY_MINUS));
+        assert_eq!(type_char('Ö'), Keystroke::plain(EV_KEY::KEY_SEMICOLON));
+        assert_eq!(type_char('@'), Keystroke::altgr(EV_KEY::KEY_Q));
+
+        assert_eq!(
+            Keymap::Us.lookup('!').unwrap().with_caps_lock('!', true),
+            Keystroke::shift(EV_KEY::KEY_1)
+        );
+        assert_eq!(
+            Keymap::Us.lookup('a').unwrap().with_caps_lock('a', false),
+            Keystroke::plain(EV_KEY::KEY_A)
+        );
+    }
+}
@@ -23,19 +23,28 @@ use evdev_rs::enums::EV_SYN;
 use evdev_rs::{Device, DeviceWrapper, GrabMode};
 use flume::{unbounded, Receiver, Sender};
 use log::{debug, error, info, trace, warn};
+use nix::poll::{poll, PollFd, PollFlags};
+use parking_lot::RwLock;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fs::File;
-use std::path::Path;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::util::ratelimited;
+use crate::key_filter::{self, KeyFilter};
+use crate::util::{self, ratelimited};
 use crate::{
-    constants, dbus_interface, hwdevices, macros, plugins, script,
-    scripting::parameters::PlainParameter, sdk_support, uleds, DeviceAction, EvdevError,
-    KeyboardDevice, MainError, MouseDevice, COLOR_MAPS_READY_CONDITION, FAILED_TXS, KEY_STATES,
-    LUA_TXS, QUIT, REQUEST_FAILSAFE_MODE, RGBA, SDK_SUPPORT_ACTIVE, ULEDS_SUPPORT_ACTIVE,
+    afk, backlight, blending, brightness_curve, canvas_layout, compositor, constants,
+    dbus_interface, device_id, game_mode, hardware_mode, health, hwdevices, indicators, input_grab,
+    macros, pacing, plugins, power, privacy, quarantine, script,
+    scripting::manifest::NativeEffectKind, scripting::native,
+    scripting::parameters::PlainParameter, scripting::shader, scripting::vm_pool, sdk_support,
+    standby, startup, transform, uleds, zone_controls, DeviceAction, EvdevError, KeyboardDevice,
+    MainError, MouseDevice, COLOR_MAPS_READY_CONDITION, FAILED_TXS, KEY_STATES, LUA_TXS, QUIT,
+    REQUEST_FAILSAFE_MODE, RGBA, ULEDS_SUPPORT_ACTIVE,
 };
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -43,11 +52,20 @@ pub type Result<T> = std::result::Result<T, eyre::Error>;
 #[derive(Debug, Clone)]
 pub enum DbusApiEvent {
     ProfilesChanged,
+    ProfileAssigned(usize, PathBuf),
     ActiveProfileChanged,
+    ProfileSwitchStarted(u64, PathBuf),
+    ProfileSwitchCompleted(u64, PathBuf, bool),
     ActiveSlotChanged,
     BrightnessChanged,
+    GameModeChanged,
+    HardwareModeChanged,
+    AfkChanged,
     DeviceStatusChanged,
     DeviceHotplug((u16, u16), bool),
+    LockStateChanged,
+    LayoutChanged,
+    FrameRateChanged,
 }
 
 /// Spawns the D-Bus API thread and executes it's main loop
@@ -75,22 +93,50 @@ pub fn spawn_dbus_api_thread(
                     Ok(result) => match result {
                         DbusApiEvent::ProfilesChanged => dbus.notify_profiles_changed()?,
 
+                        DbusApiEvent::ProfileAssigned(slot, profile_file) => {
+                            dbus.notify_profile_assigned(slot, &profile_file)?
+                        }
+
                         DbusApiEvent::ActiveProfileChanged => {
                             dbus.notify_active_profile_changed()?
                         }
 
+                        DbusApiEvent::ProfileSwitchStarted(id, profile_file) => {
+                            dbus.notify_profile_switch_started(id, &profile_file)?
+                        }
+
+                        DbusApiEvent::ProfileSwitchCompleted(id, profile_file, succeeded) => {
+                            dbus.notify_profile_switch_completed(id, &profile_file, succeeded)?
+                        }
+
                         DbusApiEvent::ActiveSlotChanged => dbus.notify_active_slot_changed()?,
 
                         DbusApiEvent::BrightnessChanged => dbus.notify_brightness_changed()?,
 
+                        DbusApiEvent::GameModeChanged => dbus.notify_game_mode_changed()?,
+
+                        DbusApiEvent::HardwareModeChanged => dbus.notify_hardware_mode_changed()?,
+
+                        DbusApiEvent::AfkChanged => dbus.notify_afk_changed()?,
+
                         DbusApiEvent::DeviceStatusChanged => dbus.notify_device_status_changed()?,
 
                         DbusApiEvent::DeviceHotplug(device_info, remove) => {
                             dbus.notify_device_hotplug(device_info, remove)?
                         }
+
+                        DbusApiEvent::LockStateChanged => dbus.notify_lock_state_changed()?,
+
+                        DbusApiEvent::LayoutChanged => dbus.notify_layout_changed()?,
+
+                        DbusApiEvent::FrameRateChanged => dbus.notify_frame_rate_changed()?,
                     },
 
                     Err(_e) => {
+                        dbus.send_pending_replies().unwrap_or_else(|e| {
+                            error!("Could not send the pending D-Bus replies: {}", e)
+                        });
+
                         event_received = dbus.get_next_event_timeout(0).unwrap_or_else(|e| {
                             error!("Could not get the next D-Bus event: {}", e);
 
@@ -118,6 +164,8 @@ pub fn spawn_keyboard_input_thread(
             #[cfg(feature = "profiling")]
             coz::thread_init();
 
+            let input_id = device_id::device_id(keyboard_device.read().as_device());
+
             let device = match hwdevices::get_input_dev_from_udev(usb_vid, usb_pid) {
                 Ok(filename) => match File::open(filename.clone()) {
                     Ok(devfile) => match Device::new_from_file(devfile) {
@@ -142,10 +190,15 @@ pub fn spawn_keyboard_input_thread(
 
                             // info!("Unique identifier: {}", device.uniq().unwrap_or("<n/a>"));
 
-                            info!("Grabbing the keyboard device exclusively");
-                            let _ = device
-                                .grab(GrabMode::Grab)
-                                .map_err(|e| error!("Could not grab the device: {}", e));
+                            // devices that have been released at runtime stay released
+                            if input_grab::register_handle(&input_id, device.file()) {
+                                info!("Grabbing the keyboard device exclusively");
+                                let _ = device
+                                    .grab(GrabMode::Grab)
+                                    .map_err(|e| error!("Could not grab the device: {}", e));
+                            } else {
+                                info!("Not grabbing the keyboard device, it has been released");
+                            }
 
                             device
                         }
@@ -159,6 +212,9 @@ pub fn spawn_keyboard_input_thread(
                 Err(_e) => return Err(EvdevError::UdevError {}.into()),
             };
 
+            // debounces the keys and repeats held keys, if configured for the device
+            let mut filter = KeyFilter::default();
+
             loop {
                 // check if we shall terminate the input thread, before we poll the keyboard
                 if QUIT.load(Ordering::SeqCst) {
@@ -174,10 +230,70 @@ pub fn spawn_keyboard_input_thread(
                     break Ok(());
                 }
 
+                let filter_config = key_filter::get_key_filter(keyboard_device.read().as_device());
+
+                // wait for the next event, but only until the key filter has to correct a
+                // key that settled, or has to repeat a held key. libevdev may already have
+                // buffered the remaining events of a frame, these are read without waiting
+                if let Some(deadline) = filter
+                    .next_deadline(&filter_config)
+                    .filter(|_| !device.has_event_pending())
+                {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    let timeout_millis = ((timeout.as_micros() + 999) / 1000) as i32;
+
+                    let mut fds = [PollFd::new(device.file().as_raw_fd(), PollFlags::POLLIN)];
+
+                    let ready = match poll(&mut fds, timeout_millis) {
+                        Ok(ready) => ready,
+
+                        // we have been interrupted by a signal, start over
+                        Err(nix::errno::Errno::EINTR) => continue,
+
+                        Err(e) => return Err(e.into()),
+                    };
+
+                    if ready == 0 {
+                        for (code, value) in filter.poll(&filter_config, Instant::now()) {
+                            send_synthesized_key_event(&kbd_tx, &keyboard_device, code, value)
+                                .unwrap_or_else(|e| {
+                                    ratelimited::error!(
+                                        "Could not send a synthesized key event: {}",
+                                        e
+                                    )
+                                });
+                        }
+
+                        continue;
+                    }
+                }
+
                 match device.next_event(evdev_rs::ReadFlag::NORMAL | evdev_rs::ReadFlag::BLOCKING) {
                     Ok(k) => {
                         trace!("Key event: {:?}", k.1);
 
+                        // the OS receives the events of a released device directly
+                        if !input_grab::is_grabbed(&input_id) {
+                            *crate::LAST_INPUT_TIME.lock() = Instant::now();
+
+                            continue;
+                        }
+
+                        if let evdev_rs::enums::EventCode::EV_KEY(ref code) = k.1.event_code {
+                            if !filter.filter(
+                                &filter_config,
+                                *code as u32,
+                                k.1.value,
+                                Instant::now(),
+                            ) {
+                                trace!("Key event has been dropped by the key filter");
+
+                                *crate::LAST_INPUT_TIME.lock() = Instant::now();
+
+                                continue;
+                            }
+                        }
+
                         // reset "to be dropped" flag
                         macros::DROP_CURRENT_KEY.store(false, Ordering::SeqCst);
 
@@ -261,6 +377,46 @@ pub fn spawn_keyboard_input_thread(
     Ok(())
 }
 
+/// Send a key event that has been synthesized by the key filter to the main thread,
+/// followed by a `SYN_REPORT`, just like the events of the device
+fn send_synthesized_key_event(
+    kbd_tx: &Sender<Option<evdev_rs::InputEvent>>,
+    keyboard_device: &KeyboardDevice,
+    code: u32,
+    value: i32,
+) -> Result<()> {
+    let key = evdev_rs::enums::int_to_ev_key(code).ok_or(EvdevError::EvdevEventError {})?;
+
+    if value != 2 {
+        let index = keyboard_device.read().ev_key_to_key_index(key) as usize;
+
+        if let Some(v) = KEY_STATES.write().get_mut(index) {
+            *v = value > 0;
+        }
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let time = evdev_rs::TimeVal::new(now.as_secs() as i64, now.subsec_micros() as i64);
+
+    kbd_tx.send(Some(evdev_rs::InputEvent::new(
+        &time,
+        &evdev_rs::enums::EventCode::EV_KEY(key),
+        value,
+    )))?;
+
+    kbd_tx.send(Some(evdev_rs::InputEvent::new(
+        &time,
+        &evdev_rs::enums::EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+        0,
+    )))?;
+
+    *crate::LAST_INPUT_TIME.lock() = Instant::now();
+
+    Ok(())
+}
+
 /// Spawns the mouse events thread and executes it's main loop
 pub fn spawn_mouse_input_thread(
     mouse_tx: Sender<Option<evdev_rs::InputEvent>>,
@@ -275,6 +431,8 @@ pub fn spawn_mouse_input_thread(
             #[cfg(feature = "profiling")]
             coz::thread_init();
 
+            let input_id = device_id::device_id(mouse_device.read().as_device());
+
             let device = match hwdevices::get_input_dev_from_udev(usb_vid, usb_pid) {
                 Ok(filename) => match File::open(filename.clone()) {
                     Ok(devfile) => match Device::new_from_file(devfile) {
@@ -299,10 +457,15 @@ pub fn spawn_mouse_input_thread(
 
                             // info!("Unique identifier: {}", device.uniq().unwrap_or("<n/a>"));
 
-                            info!("Grabbing the mouse device exclusively");
-                            let _ = device
-                                .grab(GrabMode::Grab)
-                                .map_err(|e| error!("Could not grab the device: {}", e));
+                            // devices that have been released at runtime stay released
+                            if input_grab::register_handle(&input_id, device.file()) {
+                                info!("Grabbing the mouse device exclusively");
+                                let _ = device
+                                    .grab(GrabMode::Grab)
+                                    .map_err(|e| error!("Could not grab the device: {}", e));
+                            } else {
+                                info!("Not grabbing the mouse device, it has been released");
+                            }
 
                             device
                         }
@@ -326,6 +489,13 @@ pub fn spawn_mouse_input_thread(
                     Ok(k) => {
                         // trace!("Mouse event: {:?}", k.1);
 
+                        // the OS receives the events of a released device directly
+                        if !input_grab::is_grabbed(&input_id) {
+                            *crate::LAST_INPUT_TIME.lock() = Instant::now();
+
+                            continue;
+                        }
+
                         // reset "to be dropped" flag
                         macros::DROP_CURRENT_MOUSE_INPUT.store(false, Ordering::SeqCst);
 
@@ -629,6 +799,8 @@ pub fn spawn_misc_input_thread(
             #[cfg(feature = "profiling")]
             coz::thread_init();
 
+            let input_id = device_id::device_id(misc_device.read().as_device());
+
             let device = match hwdevices::get_input_dev_from_udev(usb_vid, usb_pid) {
                 Ok(filename) => match File::open(filename.clone()) {
                     Ok(devfile) => match Device::new_from_file(devfile) {
@@ -653,10 +825,15 @@ pub fn spawn_misc_input_thread(
 
                             // info!("Unique identifier: {}", device.uniq().unwrap_or("<n/a>"));
 
-                            info!("Grabbing the misc device input exclusively");
-                            let _ = device
-                                .grab(GrabMode::Grab)
-                                .map_err(|e| error!("Could not grab the device: {}", e));
+                            // devices that have been released at runtime stay released
+                            if input_grab::register_handle(&input_id, device.file()) {
+                                info!("Grabbing the misc device input exclusively");
+                                let _ = device
+                                    .grab(GrabMode::Grab)
+                                    .map_err(|e| error!("Could not grab the device: {}", e));
+                            } else {
+                                info!("Not grabbing the misc device, it has been released");
+                            }
 
                             device
                         }
@@ -680,6 +857,13 @@ pub fn spawn_misc_input_thread(
                     Ok(k) => {
                         trace!("Misc event: {:?}", k.1);
 
+                        // the OS receives the events of a released device directly
+                        if !input_grab::is_grabbed(&input_id) {
+                            *crate::LAST_INPUT_TIME.lock() = Instant::now();
+
+                            continue;
+                        }
+
                         // reset "to be dropped" flag
                         // macros::DROP_CURRENT_MISC_INPUT.store(false, Ordering::SeqCst);
 
@@ -764,60 +948,531 @@ pub fn spawn_lua_thread(
     thread_idx: usize,
     lua_rx: Receiver<script::Message>,
     script_file: &Path,
+    shader_file: Option<&Path>,
+    native_effect: Option<NativeEffectKind>,
     parameters: &[PlainParameter],
 ) -> Result<()> {
     info!("Loading Lua script: {}", script_file.display());
 
-    let builder = thread::Builder::new().name(format!(
-        "{}:{}",
-        thread_idx,
-        script_file.file_name().unwrap().to_string_lossy(),
-    ));
-
-    let script_file = script_file.to_path_buf();
-    let mut parameter_values: BTreeMap<String, PlainParameter> = parameters
+    let parameter_values: BTreeMap<String, PlainParameter> = parameters
         .iter()
         .map(|pv| (pv.name.clone(), pv.clone()))
         .collect();
 
+    let assignment = vm_pool::Assignment {
+        thread_idx,
+        lua_rx,
+        script_file: script_file.to_path_buf(),
+        shader_file: shader_file.map(Path::to_path_buf),
+        native_effect,
+        parameter_values,
+    };
+
+    // prefer an idle thread from the pool, it already holds an initialized Lua VM
+    match vm_pool::assign_to_idle_thread(assignment) {
+        None => Ok(()),
+        Some(assignment) => spawn_lua_pool_thread(assignment),
+    }
+}
+
+/// Spawns a new Lua thread that runs the script of `assignment`. After the script
+/// terminated gracefully, the thread prepares a fresh Lua VM and returns to the pool
+/// of idle threads, to run the scripts of the next profile
+fn spawn_lua_pool_thread(assignment: vm_pool::Assignment) -> Result<()> {
+    static THREAD_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let pool_thread_name = format!("lua-vm:{}", THREAD_COUNTER.fetch_add(1, Ordering::SeqCst));
+    let builder = thread::Builder::new().name(pool_thread_name.clone());
+
+    let (thread_tx, thread_rx) = unbounded();
+
     builder.spawn(move || -> Result<()> {
         #[cfg(feature = "profiling")]
         coz::thread_init();
 
+        let mut next_assignment = Some(assignment);
+        let mut lua_ctx = None;
+
         loop {
-            let result = script::run_script(&script_file, &mut parameter_values, &lua_rx);
+            let vm_pool::Assignment {
+                thread_idx,
+                lua_rx,
+                script_file,
+                mut shader_file,
+                native_effect,
+                mut parameter_values,
+            } = match next_assignment.take() {
+                Some(assignment) => assignment,
+
+                None => match thread_rx.recv() {
+                    Ok(assignment) => assignment,
+                    Err(_e) => return Ok(()),
+                },
+            };
 
-            match result {
-                Ok(script::RunScriptResult::RestartScript) => {
-                    debug!("Restarting script {}", script_file.to_string_lossy());
-                }
+            debug!(
+                "Running script {} on a pooled thread",
+                script_file.display()
+            );
+
+            // name the thread after its script, like the threads of the scripts are named
+            // when they are not pooled
+            util::set_thread_name(&format!(
+                "{}:{}",
+                thread_idx,
+                script_file
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            ));
+
+            script::reset_thread_locals();
+
+            if !shader::is_enabled() {
+                shader_file = None;
+            }
+
+            loop {
+                let result = match (native_effect, &shader_file) {
+                    (Some(kind), _) => native::run_native(
+                        thread_idx,
+                        &script_file,
+                        kind,
+                        &mut parameter_values,
+                        &lua_rx,
+                    ),
+
+                    (None, Some(shader)) => shader::run_shader(
+                        thread_idx,
+                        &script_file,
+                        shader,
+                        &mut parameter_values,
+                        &lua_rx,
+                    ),
+
+                    (None, None) => script::run_script(
+                        thread_idx,
+                        &script_file,
+                        &mut parameter_values,
+                        &lua_rx,
+                        lua_ctx.take(),
+                    ),
+                };
+
+                let result = result.or_else(|e| {
+                    if shader_file.take().is_some() {
+                        // fall back to running the Lua script of the effect on the CPU
+                        warn!(
+                            "Shader of {} failed, falling back to the Lua script: {}",
+                            script_file.display(),
+                            e
+                        );
+
+                        Ok(script::RunScriptResult::RestartScript)
+                    } else {
+                        Err(e)
+                    }
+                });
 
-                Ok(script::RunScriptResult::TerminatedGracefully) => return Ok(()),
+                match result {
+                    Ok(script::RunScriptResult::RestartScript) => {
+                        debug!("Restarting script {}", script_file.to_string_lossy());
+                    }
+
+                    Ok(script::RunScriptResult::TerminatedGracefully) => break,
+
+                    Ok(script::RunScriptResult::TerminatedWithErrors) => {
+                        error!("Script execution failed");
+
+                        LUA_TXS.write().get_mut(thread_idx).unwrap().is_failed = true;
+                        REQUEST_FAILSAFE_MODE.store(true, Ordering::SeqCst);
 
-                Ok(script::RunScriptResult::TerminatedWithErrors) => {
-                    error!("Script execution failed");
+                        return Err(MainError::ScriptExecError {}.into());
+                    }
+
+                    Err(_e) => {
+                        error!("Script execution failed due to an unknown error");
 
-                    LUA_TXS.write().get_mut(thread_idx).unwrap().is_failed = true;
-                    REQUEST_FAILSAFE_MODE.store(true, Ordering::SeqCst);
+                        LUA_TXS.write().get_mut(thread_idx).unwrap().is_failed = true;
+                        REQUEST_FAILSAFE_MODE.store(true, Ordering::SeqCst);
 
-                    return Err(MainError::ScriptExecError {}.into());
+                        return Err(MainError::ScriptExecError {}.into());
+                    }
                 }
+            }
 
-                Err(_e) => {
-                    error!("Script execution failed due to an unknown error");
+            if QUIT.load(Ordering::SeqCst) {
+                return Ok(());
+            }
 
-                    LUA_TXS.write().get_mut(thread_idx).unwrap().is_failed = true;
-                    REQUEST_FAILSAFE_MODE.store(true, Ordering::SeqCst);
+            util::set_thread_name(&pool_thread_name);
 
-                    return Err(MainError::ScriptExecError {}.into());
+            // prepare a fresh Lua VM for the next script, before becoming available
+            match script::new_vm() {
+                Ok(vm) => lua_ctx = Some(vm),
+                Err(e) => {
+                    error!("Could not initialize a Lua VM: {}", e);
+
+                    return Ok(());
                 }
             }
+
+            if !vm_pool::return_to_pool(thread_tx.clone()) {
+                // the pool is full already
+                return Ok(());
+            }
         }
     })?;
 
     Ok(())
 }
 
+/// Send the auxiliary display surface to the device, but only if it features an LCD/OLED screen
+fn send_display_framebuffer(device: &mut dyn hwdevices::DeviceTrait, framebuffer: &[RGBA]) {
+    if let Some(display) = device.as_display_device_mut() {
+        let result = hwdevices::scale_framebuffer(
+            framebuffer,
+            (constants::DISPLAY_WIDTH, constants::DISPLAY_HEIGHT),
+            display.get_display_resolution(),
+        )
+        .and_then(|framebuffer| display.send_framebuffer(&framebuffer));
+
+        result.unwrap_or_else(|e| {
+            ratelimited::error!("Error sending framebuffer to a device: {}", e)
+        });
+    }
+}
+
+/// Scale the brightness of the controlled zones of the composited LED map, then blend the
+/// keys that are inhibited by game mode and the lock indicators on top of it. The zones
+/// that are hidden by privacy mode are blacked out last
+fn blend_overlays(led_map: &mut [RGBA]) {
+    // scale the brightness of the zones that are controlled by the active profile
+    zone_controls::apply_brightness(led_map);
+
+    if let Some((keys, color)) = game_mode::get_inhibited_keys() {
+        // render the keys that are inhibited by game mode
+        let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+
+        #[rustfmt::skip]
+        let color = RGBA {
+            r: ((((color >> 16) & 0xff) as f32 * brightness as f32 / 100.0) as u32) as u8,
+            g: ((((color >> 8) & 0xff) as f32 * brightness as f32 / 100.0) as u32) as u8,
+            b: (((color & 0xff) as f32 * brightness as f32 / 100.0) as u32) as u8,
+            a: ((color >> 24) & 0xff) as u8,
+        };
+
+        for chunks in led_map.chunks_exact_mut(constants::CANVAS_SIZE) {
+            for index in keys.iter().map(|index| *index as usize) {
+                // key indices start at 1
+                if (1..=constants::CANVAS_SIZE).contains(&index) {
+                    chunks[index - 1] = color;
+                }
+            }
+        }
+    }
+
+    if let Some((keys, color)) = indicators::get_indicator_keys() {
+        // tint the keys of the active lock indicators
+        let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+
+        let fg = RGBA {
+            r: ((color >> 16) & 0xff) as u8,
+            g: ((color >> 8) & 0xff) as u8,
+            b: (color & 0xff) as u8,
+            a: ((color >> 24) & 0xff) as u8,
+        };
+
+        for chunks in led_map.chunks_exact_mut(constants::CANVAS_SIZE) {
+            for index in keys.iter().map(|index| *index as usize) {
+                // key indices start at 1
+                if (1..=constants::CANVAS_SIZE).contains(&index) {
+                    let bg = chunks[index - 1];
+
+                    #[rustfmt::skip]
+                    let color = RGBA {
+                        r: ((((fg.a as f32) * fg.r as f32 + (255 - fg.a) as f32 * bg.r as f32).floor() * brightness as f32 / 100.0) as u32 >> 8) as u8,
+                        g: ((((fg.a as f32) * fg.g as f32 + (255 - fg.a) as f32 * bg.g as f32).floor() * brightness as f32 / 100.0) as u32 >> 8) as u8,
+                        b: ((((fg.a as f32) * fg.b as f32 + (255 - fg.a) as f32 * bg.b as f32).floor() * brightness as f32 / 100.0) as u32 >> 8) as u8,
+                        a: bg.a.max(fg.a),
+                    };
+
+                    chunks[index - 1] = color;
+                }
+            }
+        }
+    }
+
+    if let Some(keys) = privacy::get_blacked_out_keys() {
+        for chunks in led_map.chunks_exact_mut(constants::CANVAS_SIZE) {
+            for index in keys.iter().copied() {
+                // key indices start at 1
+                if (1..=constants::CANVAS_SIZE).contains(&index) {
+                    chunks[index - 1] = RGBA {
+                        r: 0,
+                        g: 0,
+                        b: 0,
+                        a: chunks[index - 1].a,
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// Scale the colors of a LED map by `factor`, or pass it through unaltered if `factor` is `None`
+fn dim_led_map(led_map: &[RGBA], factor: Option<f32>) -> Cow<'_, [RGBA]> {
+    match factor {
+        Some(factor) => Cow::Owned(
+            led_map
+                .iter()
+                .map(|color| RGBA {
+                    r: (color.r as f32 * factor) as u8,
+                    g: (color.g as f32 * factor) as u8,
+                    b: (color.b as f32 * factor) as u8,
+                    a: color.a,
+                })
+                .collect(),
+        ),
+
+        None => Cow::Borrowed(led_map),
+    }
+}
+
+/// Send the LED map to all devices, honoring the maximum update rate of each device.
+/// If `flush_only` is set, the LED map is only sent to the devices that have a deferred frame.
+/// If the SDK client targeted its canvas at a subset of the devices, the other devices
+/// receive `untargeted_led_map` instead
+fn send_led_map_to_devices(
+    led_map: &[RGBA],
+    untargeted_led_map: Option<&[RGBA]>,
+    flush_only: bool,
+) {
+    let keyboard_devices = crate::KEYBOARD_DEVICES.read();
+    let mouse_devices = crate::MOUSE_DEVICES.read();
+    let misc_devices = crate::MISC_DEVICES.read();
+
+    // dim the LED maps while power saving is active, while the user is AFK, or along with the
+    // display backlight
+    let brightness_factor = [
+        power::get_brightness_factor(),
+        afk::get_brightness_factor(),
+        backlight::get_brightness_factor(),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(|a, b| a * b);
+
+    let led_map = dim_led_map(led_map, brightness_factor);
+    let untargeted_led_map =
+        untargeted_led_map.map(|led_map| dim_led_map(led_map, brightness_factor));
+
+    let select_led_map = |index: u64| -> &[RGBA] {
+        match &untargeted_led_map {
+            Some(untargeted_led_map) if !sdk_support::is_target_device(index) => untargeted_led_map,
+            _ => &led_map,
+        }
+    };
+
+    // devices are indexed in the order: keyboards, mice, misc devices
+    let mouse_base = keyboard_devices.len() as u64;
+
+    // the parts of the canvas that are shown by the keyboards, if the canvas is tiled
+    let layout = canvas_layout::get_active_layout(&keyboard_devices);
+    let misc_base = mouse_base + mouse_devices.len() as u64;
+
+    for (index, keyboard_device) in keyboard_devices.iter().enumerate() {
+        let index = index as u64;
+
+        send_led_map_to_device(
+            keyboard_device,
+            DeviceKind::Keyboard,
+            index,
+            &canvas_layout::tile_led_map(layout.as_ref(), index as usize, select_led_map(index)),
+            flush_only,
+        );
+    }
+
+    for (index, mouse_device) in mouse_devices.iter().enumerate() {
+        let index = mouse_base + index as u64;
+
+        send_led_map_to_device(
+            mouse_device,
+            DeviceKind::Mouse,
+            index,
+            select_led_map(index),
+            flush_only,
+        );
+    }
+
+    for (index, misc_device) in misc_devices.iter().enumerate() {
+        let index = misc_base + index as u64;
+
+        send_led_map_to_device(
+            misc_device,
+            DeviceKind::Misc,
+            index,
+            select_led_map(index),
+            flush_only,
+        );
+    }
+}
+
+/// The class of a device, as far as sending LED maps is concerned
+#[derive(Debug, Clone, Copy)]
+enum DeviceKind {
+    Keyboard,
+    Mouse,
+    Misc,
+}
+
+impl DeviceKind {
+    /// The label of the device in the profiler spans
+    fn label(self) -> &'static str {
+        match self {
+            DeviceKind::Keyboard => "kbd",
+            DeviceKind::Mouse => "mouse",
+            DeviceKind::Misc => "misc",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DeviceKind::Keyboard => "keyboard",
+            DeviceKind::Mouse => "mouse",
+            DeviceKind::Misc => "misc",
+        }
+    }
+}
+
+/// The operations of the device classes that are required to send them an LED map
+trait LedMapDevice {
+    fn as_device(&self) -> &dyn hwdevices::DeviceTrait;
+    fn as_device_mut(&mut self) -> &mut dyn hwdevices::DeviceTrait;
+    fn get_local_brightness(&self) -> Result<i32>;
+    fn send_led_map(&mut self, led_map: &[RGBA]) -> Result<()>;
+}
+
+macro_rules! impl_led_map_device {
+    ($device_trait:ident) => {
+        impl LedMapDevice for dyn hwdevices::$device_trait + Sync + Send {
+            fn as_device(&self) -> &dyn hwdevices::DeviceTrait {
+                hwdevices::DeviceTrait::as_device(self)
+            }
+
+            fn as_device_mut(&mut self) -> &mut dyn hwdevices::DeviceTrait {
+                hwdevices::DeviceTrait::as_device_mut(self)
+            }
+
+            fn get_local_brightness(&self) -> Result<i32> {
+                <Self as hwdevices::$device_trait>::get_local_brightness(self)
+            }
+
+            fn send_led_map(&mut self, led_map: &[RGBA]) -> Result<()> {
+                <Self as hwdevices::$device_trait>::send_led_map(self, led_map)
+            }
+        }
+    };
+}
+
+impl_led_map_device!(KeyboardDeviceTrait);
+impl_led_map_device!(MouseDeviceTrait);
+impl_led_map_device!(MiscDeviceTrait);
+
+/// Send the LED map to the device `index`, honoring its maximum update rate. Uninitialized
+/// devices are re-initialized instead. If `flush_only` is set, the LED map is only sent if
+/// the device has a deferred frame
+fn send_led_map_to_device<D: LedMapDevice + ?Sized>(
+    device: &RwLock<Box<D>>,
+    kind: DeviceKind,
+    index: u64,
+    led_map: &[RGBA],
+    flush_only: bool,
+) {
+    if flush_only && !pacing::is_flush_due(index) {
+        return;
+    }
+
+    let Some(mut device) = device.try_write() else {
+        pacing::frame_dropped(index);
+
+        debug!("Skipped rendering a frame to a device, because we could not acquire a lock");
+
+        return;
+    };
+
+    if quarantine::is_parked(device.as_device()) {
+        // the device failed too often, leave it alone until it is retried
+        return;
+    }
+
+    let Ok(is_initialized) = device.as_device().is_initialized() else {
+        warn!("Could not query device status");
+
+        return;
+    };
+
+    if is_initialized {
+        if !flush_only && !pacing::submit_frame(index, device.as_device()) {
+            // the frame has been deferred, it will be coalesced with the next one
+            return;
+        }
+
+        let transformed_led_map = transform::transform_led_map(device.as_device(), led_map);
+        let transformed_led_map = brightness_curve::correct_led_map(
+            device.as_device(),
+            device.get_local_brightness().unwrap_or(100),
+            &transformed_led_map,
+        );
+
+        #[cfg(feature = "metrics")]
+        let write_start = Instant::now();
+
+        let result = tracing::trace_span!(
+            "hid write",
+            label = %format_args!("{}:{}", kind.label(), index)
+        )
+        .in_scope(|| device.send_led_map(&transformed_led_map));
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_usb_write(index, write_start.elapsed());
+
+        if let Err(e) = result {
+            pacing::frame_dropped(index);
+            health::record_event(device.as_device(), health::HealthEvent::FailedWrite);
+
+            ratelimited::error!("Error sending LED map to a device: {}", e);
+
+            if device.as_device().has_failed().unwrap_or(true) {
+                ratelimited::warn!("Trying to unplug the failed device");
+
+                // we need to terminate and then re-enter the main loop to update all global state
+                crate::REENTER_MAIN_LOOP.store(true, Ordering::SeqCst);
+            }
+        } else {
+            pacing::frame_sent(index);
+            quarantine::record_success(device.as_device());
+            health::record_success(device.as_device());
+        }
+    } else if !flush_only && health::begin_reinit_attempt(device.as_device()) {
+        ratelimited::warn!("Skipping uninitialized device, trying to re-initialize it now...");
+
+        let hidapi = crate::HIDAPI.read();
+        let hidapi = hidapi.as_ref().unwrap();
+
+        device.as_device_mut().open(hidapi).unwrap_or_else(|e| {
+            ratelimited::error!("Error opening the {} device: {}", kind.name(), e);
+        });
+
+        // send initialization handshake
+        ratelimited::info!("Initializing {} device...", kind.name());
+        device
+            .as_device_mut()
+            .send_init_sequence()
+            .unwrap_or_else(|e| ratelimited::error!("Could not initialize the device: {}", e));
+    }
+}
+
 pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
     let builder = thread::Builder::new().name("dev-io/all".to_owned());
 
@@ -828,6 +1483,9 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
         // stores the generation number of the frame that is currently visible on the keyboard
         let saved_frame_generation = AtomicUsize::new(0);
 
+        // the most recent LED map of the devices that are not targeted by the SDK client
+        let mut last_untargeted_led_map: Option<Vec<RGBA>> = None;
+
         // used to calculate frames per second
         let mut fps_counter: i32 = 0;
         let mut fps_timer = Instant::now();
@@ -839,8 +1497,29 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
                 break Ok(());
             }
 
-            match dev_io_rx.recv() {
+            let message = dev_io_rx.recv();
+
+            // perform a pending transition from or to standby, before the next frame is rendered
+            let standby = matches!(message, Ok(DeviceAction::RenderNow)) && standby::apply();
+
+            // the same holds for transitions from or to hardware mode, while not in standby
+            let hardware_mode =
+                !standby && matches!(message, Ok(DeviceAction::RenderNow)) && hardware_mode::apply();
+
+            match message {
                 Ok(message) => match message {
+                    DeviceAction::RenderNow if standby => {
+                        // the devices have been released, no device I/O is performed
+                    }
+
+                    DeviceAction::RenderNow if hardware_mode => {
+                        // streaming is suspended, the devices show their firmware effects
+                    }
+
+                    DeviceAction::RenderNow if crate::DEVICE_SELF_TEST_ACTIVE.load(Ordering::SeqCst) => {
+                        // a device self-test is in progress, it takes exclusive control of the LEDs
+                    }
+
                     DeviceAction::RenderNow  => {
                         let current_frame_generation = script::FRAME_GENERATION_COUNTER.load(Ordering::SeqCst);
                         if saved_frame_generation.load(Ordering::SeqCst) < current_frame_generation {
@@ -860,37 +1539,74 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
                                 }; constants::CANVAS_SIZE],
                             );
 
-                            // instruct Lua VMs to realize their color maps,
-                            // e.g. to blend their local color maps with the canvas
-                            *COLOR_MAPS_READY_CONDITION.0.lock() = LUA_TXS.read().len() - FAILED_TXS.read().len();
+                            // instruct the Lua VMs to realize their color maps, they submit them
+                            // in parallel, each one as a layer of its own
+                            script::prepare_layers(LUA_TXS.read().len());
 
-                            for (index, lua_tx) in LUA_TXS.read().iter().enumerate() {
-                                // if this tx failed previously, then skip it completely
-                                if !FAILED_TXS.read().contains(&index) {
-                                    // guarantee the right order of execution for the alpha blend
-                                    // operations, so we have to wait for the current Lua VM to
-                                    // complete its blending code, before continuing
-                                    let mut pending = COLOR_MAPS_READY_CONDITION.0.lock();
+                            {
+                                let _span = tracing::trace_span!("realize").entered();
 
-                                    lua_tx
-                                        .send(script::Message::RealizeColorMap)
-                                        .unwrap_or_else(|e| {
+                                let mut pending = COLOR_MAPS_READY_CONDITION.0.lock();
+                                *pending = LUA_TXS.read().len() - FAILED_TXS.read().len();
+
+                                for (index, lua_tx) in LUA_TXS.read().iter().enumerate() {
+                                    // if this tx failed previously, then skip it completely
+                                    if !FAILED_TXS.read().contains(&index) {
+                                        if let Err(e) = lua_tx.send(script::Message::RealizeColorMap(index)) {
                                             error!("Send error during realization of color maps: {}", e);
                                             FAILED_TXS.write().insert(index);
-                                        });
 
-                                    let result = COLOR_MAPS_READY_CONDITION.1.wait_for(
-                                        &mut pending,
-                                        Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
-                                    );
+                                            *pending = pending.saturating_sub(1);
+                                        }
+                                    } else {
+                                        drop_frame = true;
+                                    }
+                                }
+
+                                // wait until all Lua VMs submitted their layers
+                                let deadline = Instant::now() + Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS);
 
-                                    if result.timed_out() {
+                                while *pending > 0 {
+                                    if COLOR_MAPS_READY_CONDITION.1.wait_until(&mut pending, deadline).timed_out() {
                                         drop_frame = true;
                                         warn!("Frame dropped: Timeout while waiting for a lock!");
                                         break;
                                     }
-                                } else {
-                                    drop_frame = true;
+                                }
+                            }
+
+                            // compose the layers in the order of the manifests of the profile, so
+                            // that the output is deterministic
+                            {
+                                let _span = tracing::trace_span!("compose").entered();
+
+                                let brightness = script::layer_brightness();
+
+                                let layers = script::LAYERS.lock();
+                                let mut led_map = script::LED_MAP.write();
+
+                                // the canvas of the primary profile, if a second profile is blended in
+                                let mut primary_layer = None;
+
+                                for (lua_tx, layer) in LUA_TXS.read().iter().zip(layers.iter()) {
+                                    // the scripts of the blended profile render onto a canvas of their own
+                                    if lua_tx.is_secondary && primary_layer.is_none() {
+                                        primary_layer = Some(led_map.clone());
+                                        led_map.fill(RGBA {
+                                            r: 0,
+                                            g: 0,
+                                            b: 0,
+                                            a: 0,
+                                        });
+                                    }
+
+                                    if layer.submitted {
+                                        script::blend_layer(&mut led_map, &layer.led_map, brightness);
+                                    }
+                                }
+
+                                if let Some(primary_layer) = primary_layer {
+                                    blending::blend_layers(&primary_layer, &mut led_map);
                                 }
                             }
 
@@ -917,27 +1633,28 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
                                 }
                             }
 
-                            if SDK_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
-                                // finally, blend the LED map of the SDK support plugin
-                                let sdk_led_map = sdk_support::LED_MAP.read();
-                                let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+                            // devices that are not targeted by the SDK client receive a LED map
+                            // without the canvas of the SDK support plugin
+                            let mut untargeted_led_map = if sdk_support::is_canvas_visible()
+                                && sdk_support::TARGET_DEVICES.read().is_some()
+                            {
+                                Some(script::LED_MAP.read().clone())
+                            } else {
+                                None
+                            };
 
-                                for chunks in script::LED_MAP.write().chunks_exact_mut(constants::CANVAS_SIZE) {
-                                    for (idx, background) in chunks.iter_mut().enumerate() {
-                                        let bg = &background;
-                                        let fg = sdk_led_map[idx];
+                            // blend the layers of the other producers on top of the profile,
+                            // in the order of their priorities
+                            compositor::compose(&mut script::LED_MAP.write(), true);
 
-                                        #[rustfmt::skip]
-                                        let color = RGBA {
-                                            r: ((((fg.a as f32) * fg.r as f32 + (255 - fg.a) as f32 * bg.r as f32).floor() * brightness as f32 / 100.0) as u32 >> 8) as u8,
-                                            g: ((((fg.a as f32) * fg.g as f32 + (255 - fg.a) as f32 * bg.g as f32).floor() * brightness as f32 / 100.0) as u32 >> 8) as u8,
-                                            b: ((((fg.a as f32) * fg.b as f32 + (255 - fg.a) as f32 * bg.b as f32).floor() * brightness as f32 / 100.0) as u32 >> 8) as u8,
-                                            a: fg.a,
-                                        };
+                            if let Some(led_map) = untargeted_led_map.as_mut() {
+                                compositor::compose(led_map, false);
+                            }
 
-                                        *background = color;
-                                    }
-                                }
+                            blend_overlays(&mut script::LED_MAP.write());
+
+                            if let Some(led_map) = untargeted_led_map.as_mut() {
+                                blend_overlays(led_map);
                             }
 
                             // number of pending blend ops should have reached zero by now
@@ -952,129 +1669,57 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
 
                             // send the final (combined) color map to all of the devices
                             if !drop_frame {
-                                for keyboard_device in crate::KEYBOARD_DEVICES.read().iter() {
-                                    if let Some(mut device) = keyboard_device.try_write() {
-                                        if let Ok(is_initialized) = device.is_initialized() {
-                                            if is_initialized {
-                                                if let Err(e) = device.send_led_map(&script::LED_MAP.read()) {
-                                                    ratelimited::error!("Error sending LED map to a device: {}", e);
-
-                                                    if device.has_failed().unwrap_or(true) {
-                                                        ratelimited::warn!("Trying to unplug the failed device");
-
-                                                        // we need to terminate and then re-enter the main loop to update all global state
-                                                        crate::REENTER_MAIN_LOOP.store(true, Ordering::SeqCst);
-                                                    }
-                                                }
-                                            } else {
-                                                ratelimited::warn!("Skipping uninitialized device, trying to re-initialize it now...");
-
-                                                let hidapi = crate::HIDAPI.read();
-                                                let hidapi = hidapi.as_ref().unwrap();
-
-                                                device.open(hidapi).unwrap_or_else(|e| {
-                                                    ratelimited::error!("Error opening the keyboard device: {}", e);
-                                                });
-
-                                                // send initialization handshake
-                                                ratelimited::info!("Initializing keyboard device...");
-                                                device
-                                                    .send_init_sequence()
-                                                    .unwrap_or_else(|e| ratelimited::error!("Could not initialize the device: {}", e));
-                                            }
-                                        } else {
-                                            warn!("Could not query device status");
+                                send_led_map_to_devices(
+                                    &script::LED_MAP.read(),
+                                    untargeted_led_map.as_deref(),
+                                    false,
+                                );
+
+                                // send the auxiliary display surface to all devices that feature an LCD/OLED screen
+                                if script::DISPLAY_FRAMEBUFFER_MODIFIED.swap(false, Ordering::SeqCst) {
+                                    let framebuffer = script::DISPLAY_FRAMEBUFFER.read();
+
+                                    for keyboard_device in crate::KEYBOARD_DEVICES.read().iter() {
+                                        if let Some(mut device) = keyboard_device.try_write() {
+                                            send_display_framebuffer(device.as_device_mut(), &framebuffer);
                                         }
-                                    } else {
-                                        debug!("Skipped rendering a frame to a device, because we could not acquire a lock");
                                     }
-                                }
 
-                                for mouse_device in crate::MOUSE_DEVICES.read().iter() {
-                                    if let Some(mut device) = mouse_device.try_write() {
-                                        if let Ok(is_initialized) = device.is_initialized() {
-                                            if is_initialized {
-                                                if let Err(e) = device.send_led_map(&script::LED_MAP.read()) {
-                                                    ratelimited::error!("Error sending LED map to a device: {}", e);
-
-                                                    if device.has_failed().unwrap_or(true) {
-                                                        ratelimited::warn!("Trying to unplug the failed device");
-
-                                                        // we need to terminate and then re-enter the main loop to update all global state
-                                                        crate::REENTER_MAIN_LOOP.store(true, Ordering::SeqCst);
-                                                    }
-                                                }
-                                            } else {
-                                                ratelimited::warn!("Skipping uninitialized device, trying to re-initialize it now...");
-
-                                                let hidapi = crate::HIDAPI.read();
-                                                let hidapi = hidapi.as_ref().unwrap();
-
-                                                device.open(hidapi).unwrap_or_else(|e| {
-                                                    ratelimited::error!("Error opening the mouse device: {}", e);
-                                                });
-
-                                                // send initialization handshake
-                                                ratelimited::info!("Initializing mouse device...");
-                                                device
-                                                    .send_init_sequence()
-                                                    .unwrap_or_else(|e| ratelimited::error!("Could not initialize the device: {}", e));
-                                            }
-                                        } else {
-                                            warn!("Could not query device status");
+                                    for mouse_device in crate::MOUSE_DEVICES.read().iter() {
+                                        if let Some(mut device) = mouse_device.try_write() {
+                                            send_display_framebuffer(device.as_device_mut(), &framebuffer);
                                         }
-                                    } else {
-                                        debug!("Skipped rendering a frame to a device, because we could not acquire a lock");
                                     }
-                                }
 
-                                for misc_device in crate::MISC_DEVICES.read().iter() {
-                                    if let Some(mut device) = misc_device.try_write() {
-                                        if let Ok(is_initialized) = device.is_initialized() {
-                                            if is_initialized {
-                                                if let Err(e) = device.send_led_map(&script::LED_MAP.read()) {
-                                                    ratelimited::error!("Error sending LED map to a device: {}", e);
-
-                                                    if device.has_failed().unwrap_or(true) {
-                                                        ratelimited::warn!("Trying to unplug the failed device");
-
-                                                        // we need to terminate and then re-enter the main loop to update all global state
-                                                        crate::REENTER_MAIN_LOOP.store(true, Ordering::SeqCst);
-                                                    }
-                                                }
-                                            } else {
-                                                ratelimited::warn!("Skipping uninitialized device, trying to re-initialize it now...");
-
-                                                let hidapi = crate::HIDAPI.read();
-                                                let hidapi = hidapi.as_ref().unwrap();
-
-                                                device.open(hidapi).unwrap_or_else(|e| {
-                                                    ratelimited::error!("Error opening the misc device: {}", e);
-                                                });
-
-                                                // send initialization handshake
-                                                ratelimited::info!("Initializing misc device...");
-                                                device
-                                                    .send_init_sequence()
-                                                    .unwrap_or_else(|e| ratelimited::error!("Could not initialize the device: {}", e));
-                                            }
-                                        } else {
-                                            warn!("Could not query device status");
+                                    for misc_device in crate::MISC_DEVICES.read().iter() {
+                                        if let Some(mut device) = misc_device.try_write() {
+                                            send_display_framebuffer(device.as_device_mut(), &framebuffer);
                                         }
-                                    } else {
-                                        debug!("Skipped rendering a frame to a device, because we could not acquire a lock");
                                     }
                                 }
 
+                                // log the time it took until the LEDs lit up
+                                startup::frame_sent();
+
                                 // update the current frame generation
                                 saved_frame_generation.store(current_frame_generation, Ordering::SeqCst);
 
                                 script::LAST_RENDERED_LED_MAP
                                     .write()
                                     .copy_from_slice(&script::LED_MAP.read());
+
+                                last_untargeted_led_map = untargeted_led_map;
                             }
 
                             fps_counter += 1;
+                        } else if pacing::is_any_flush_due() {
+                            // no new frame has been rendered, but some of the rate limited
+                            // devices did not receive the most recent frame yet
+                            send_led_map_to_devices(
+                                &script::LAST_RENDERED_LED_MAP.read(),
+                                last_untargeted_led_map.as_deref(),
+                                true,
+                            );
                         }
 
                         // calculate and log fps each second
@@ -0,0 +1,405 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::hwdevices::{DeviceTrait, RGBA};
+use crate::scripting::script;
+use crate::state::DeviceMakeModelSerial;
+use crate::{constants, device_id, state_file, util};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransformError {
+    #[error("Invalid canvas transform: {description}")]
+    InvalidTransform { description: String },
+}
+
+lazy_static! {
+    /// Canvas transforms of all devices that have been configured so far, persisted across restarts
+    pub static ref DEVICE_TRANSFORMS: Arc<RwLock<BTreeMap<DeviceMakeModelSerial, CanvasTransform>>> =
+        Arc::new(RwLock::new(BTreeMap::new()));
+}
+
+/// A transformation of the canvas that is applied before the LED map is sent to a
+/// device, e.g. for keyboards that are mounted vertically, or for left-handed mice.
+///
+/// The canvas is scaled around its center, then rotated clockwise, mirrored and
+/// finally moved by the offset, which is specified in "pixels" of the canvas
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CanvasTransform {
+    /// Clockwise rotation in degrees, one of 0, 90, 180 or 270
+    #[serde(default)]
+    pub rotation: u16,
+
+    #[serde(default)]
+    pub mirror_x: bool,
+
+    #[serde(default)]
+    pub mirror_y: bool,
+
+    #[serde(default)]
+    pub offset_x: i32,
+
+    #[serde(default)]
+    pub offset_y: i32,
+
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl Default for CanvasTransform {
+    fn default() -> Self {
+        Self {
+            rotation: 0,
+            mirror_x: false,
+            mirror_y: false,
+            offset_x: 0,
+            offset_y: 0,
+            scale: default_scale(),
+        }
+    }
+}
+
+impl CanvasTransform {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if ![0, 90, 180, 270].contains(&self.rotation) {
+            return Err(TransformError::InvalidTransform {
+                description: format!("Unsupported rotation: {}", self.rotation),
+            }
+            .into());
+        }
+
+        if !(self.scale.is_finite() && self.scale > 0.0) {
+            return Err(TransformError::InvalidTransform {
+                description: format!("Invalid scale factor: {}", self.scale),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the position on the source canvas, that the "pixel" at `(x, y)` of the
+    /// transformed canvas shows, or `None` if it lies outside of the source canvas
+    fn source_position(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let width = constants::CANVAS_WIDTH as f64;
+        let height = constants::CANVAS_HEIGHT as f64;
+
+        // work on normalized coordinates relative to the center of the canvas, so
+        // that a rotation by 90 degrees stretches the canvas to the same aspect ratio
+        let mut u = (x as f64 - self.offset_x as f64 + 0.5) / width - 0.5;
+        let mut v = (y as f64 - self.offset_y as f64 + 0.5) / height - 0.5;
+
+        if self.mirror_x {
+            u = -u;
+        }
+
+        if self.mirror_y {
+            v = -v;
+        }
+
+        // undo the clockwise rotation
+        (u, v) = match self.rotation {
+            90 => (v, -u),
+            180 => (-u, -v),
+            270 => (-v, u),
+            _ => (u, v),
+        };
+
+        u /= self.scale;
+        v /= self.scale;
+
+        let x = ((u + 0.5) * width).floor();
+        let y = ((v + 0.5) * height).floor();
+
+        if (0.0..width).contains(&x) && (0.0..height).contains(&y) {
+            Some((x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+
+    /// Apply the transform to a LED map. Indices of the canvas are in column major
+    /// order, like the key indices of the keyboards
+    pub fn apply(&self, led_map: &[RGBA]) -> Vec<RGBA> {
+        let mut result = vec![
+            RGBA {
+                r: 0x00,
+                g: 0x00,
+                b: 0x00,
+                a: 0x00,
+            };
+            led_map.len()
+        ];
+
+        for x in 0..constants::CANVAS_WIDTH {
+            for y in 0..constants::CANVAS_HEIGHT {
+                if let Some((sx, sy)) = self.source_position(x, y) {
+                    let index = util::canvas_index(x, y);
+                    let source = util::canvas_index(sx, sy);
+
+                    if let (Some(color), Some(target)) =
+                        (led_map.get(source), result.get_mut(index))
+                    {
+                        *target = *color;
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl fmt::Display for CanvasTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_identity() {
+            return write!(f, "none");
+        }
+
+        let mut parts = vec![];
+
+        if self.rotation != 0 {
+            parts.push(format!("rotate={}", self.rotation));
+        }
+
+        if self.mirror_x {
+            parts.push("mirror-x".to_string());
+        }
+
+        if self.mirror_y {
+            parts.push("mirror-y".to_string());
+        }
+
+        if self.offset_x != 0 || self.offset_y != 0 {
+            parts.push(format!("offset={}:{}", self.offset_x, self.offset_y));
+        }
+
+        if self.scale != 1.0 {
+            parts.push(format!("scale={}", self.scale));
+        }
+
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+impl FromStr for CanvasTransform {
+    type Err = eyre::Error;
+
+    /// Parse a transform like e.g. "rotate=90,mirror-x,offset=2:0,scale=1.5", or "none"
+    fn from_str(s: &str) -> Result<Self> {
+        let mut result = Self::default();
+
+        let invalid = |part: &str| TransformError::InvalidTransform {
+            description: part.to_string(),
+        };
+
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some(("rotate", value)) => {
+                    result.rotation = value.parse().map_err(|_| invalid(part))?;
+                }
+
+                Some(("offset", value)) => {
+                    let (x, y) = value.split_once(':').ok_or_else(|| invalid(part))?;
+
+                    result.offset_x = x.parse().map_err(|_| invalid(part))?;
+                    result.offset_y = y.parse().map_err(|_| invalid(part))?;
+                }
+
+                Some(("scale", value)) => {
+                    result.scale = value.parse().map_err(|_| invalid(part))?;
+                }
+
+                None if part == "mirror-x" => result.mirror_x = true,
+                None if part == "mirror-y" => result.mirror_y = true,
+                None if part == "none" => result = Self::default(),
+
+                _ => return Err(invalid(part).into()),
+            }
+        }
+
+        result.validate()?;
+
+        Ok(result)
+    }
+}
+
+/// Returns the canvas transform of a device
+pub fn get_transform(device: &dyn DeviceTrait) -> CanvasTransform {
+    device_id::lookup(&DEVICE_TRANSFORMS.read(), device)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Assign the canvas transform of a device, and persist it
+pub fn set_transform(device: &dyn DeviceTrait, transform: CanvasTransform) -> Result<()> {
+    transform.validate()?;
+
+    {
+        let mut device_transforms = DEVICE_TRANSFORMS.write();
+        let key = device_id::migrate(&mut device_transforms, device);
+
+        if transform.is_identity() {
+            device_transforms.remove(&key);
+        } else {
+            device_transforms.insert(key, transform);
+        }
+    }
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    save_device_transforms()
+}
+
+/// Apply the canvas transform of a device to a LED map, the LED map is passed
+/// through unaltered if no transform has been configured for the device
+pub fn transform_led_map<'a>(device: &dyn DeviceTrait, led_map: &'a [RGBA]) -> Cow<'a, [RGBA]> {
+    if DEVICE_TRANSFORMS.read().is_empty() {
+        return Cow::Borrowed(led_map);
+    }
+
+    let transform = get_transform(device);
+
+    if transform.is_identity() {
+        Cow::Borrowed(led_map)
+    } else {
+        Cow::Owned(transform.apply(led_map))
+    }
+}
+
+pub fn save_device_transforms() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("device-transforms.state");
+
+    let data = toml::to_string_pretty(&*DEVICE_TRANSFORMS.read())?;
+    state_file::write(&file_name, &data)?;
+
+    Ok(())
+}
+
+pub fn load_device_transforms() -> Result<()> {
+    let file_name = PathBuf::from(&constants::STATE_DIR).join("device-transforms.state");
+
+    let device_transforms: BTreeMap<DeviceMakeModelSerial, CanvasTransform> =
+        state_file::load(&file_name, |data| Ok(toml::from_str(data)?))?;
+
+    *DEVICE_TRANSFORMS.write() = device_transforms
+        .into_iter()
+        .filter(|(_, transform)| transform.validate().is_ok())
+        .collect();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CanvasTransform;
+    use crate::constants;
+    use crate::hwdevices::RGBA;
+
+    fn test_led_map() -> Vec<RGBA> {
+        (0..constants::CANVAS_SIZE)
+            .map(|i| RGBA {
+                r: (i % 256) as u8,
+                g: (i / 256) as u8,
+                b: 0x00,
+                a: 0xff,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_and_format() {
+        let transform = "rotate=90,mirror-x,offset=2:-1,scale=1.5"
+            .parse::<CanvasTransform>()
+            .unwrap();
+
+        assert_eq!(transform.rotation, 90);
+        assert!(transform.mirror_x);
+        assert!(!transform.mirror_y);
+        assert_eq!((transform.offset_x, transform.offset_y), (2, -1));
+        assert_eq!(transform.scale, 1.5);
+
+        assert_eq!(
+            transform.to_string().parse::<CanvasTransform>().unwrap(),
+            transform
+        );
+
+        assert!("none".parse::<CanvasTransform>().unwrap().is_identity());
+        assert!("rotate=45".parse::<CanvasTransform>().is_err());
+        assert!("scale=0".parse::<CanvasTransform>().is_err());
+        assert!("flip".parse::<CanvasTransform>().is_err());
+    }
+
+    #[test]
+    fn rotate_and_mirror() {
+        let led_map = test_led_map();
+        let last = constants::CANVAS_SIZE - 1;
+
+        let identity = CanvasTransform::default().apply(&led_map);
+        assert_eq!(identity[0].r, led_map[0].r);
+
+        // a rotation by 180 degrees swaps the corners of the canvas
+        let rotated = "rotate=180"
+            .parse::<CanvasTransform>()
+            .unwrap()
+            .apply(&led_map);
+        assert_eq!(rotated[0].r, led_map[last].r);
+        assert_eq!(rotated[last].r, led_map[0].r);
+
+        // mirroring along both axes is equivalent to a rotation by 180 degrees
+        let mirrored = "mirror-x,mirror-y"
+            .parse::<CanvasTransform>()
+            .unwrap()
+            .apply(&led_map);
+        assert!(mirrored
+            .iter()
+            .zip(rotated.iter())
+            .all(|(a, b)| a.r == b.r && a.g == b.g));
+
+        // pixels that are moved off the canvas are left dark
+        let moved = "offset=1:0"
+            .parse::<CanvasTransform>()
+            .unwrap()
+            .apply(&led_map);
+        assert_eq!(moved[0].a, 0x00);
+        assert_eq!(moved[constants::CANVAS_HEIGHT].r, led_map[0].r);
+    }
+}
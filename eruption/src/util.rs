@@ -22,8 +22,10 @@
 use nix::fcntl::{flock, open, FlockArg, OFlag};
 use nix::sys::stat::Mode;
 use nix::unistd::{ftruncate, getpid, write};
+use std::cell::RefCell;
+use std::ffi::CString;
 use std::path::{Path, PathBuf};
-use std::{fs, io};
+use std::{fs, io, thread};
 
 use crate::constants;
 
@@ -79,6 +81,12 @@ pub fn get_manifest_for(script_file: &Path) -> PathBuf {
     manifest_path
 }
 
+/// Returns the index of the cell at (`x`, `y`) of the canvas. The canvas is laid out in
+/// column major order, just like the key indices of the keyboards
+pub fn canvas_index(x: usize, y: usize) -> usize {
+    x * constants::CANVAS_HEIGHT + y
+}
+
 pub fn file_exists<P: AsRef<Path>>(p: P) -> bool {
     p.as_ref().exists()
 }
@@ -195,6 +203,75 @@ pub fn match_script_path<P: AsRef<Path>>(script_file: &P) -> Result<PathBuf> {
     .into())
 }
 
+/// Resolve the script `script_file` to its canonical path, that has to reside in one of the
+/// script directories. Relative paths are looked up in the script directories
+pub fn resolve_script_path<P: AsRef<Path>>(script_file: &P) -> Result<PathBuf> {
+    let script_file = script_file.as_ref();
+
+    let script_path = if script_file.is_absolute() {
+        script_file.to_path_buf()
+    } else {
+        match_script_path(&script_file)?
+    };
+
+    let script_path = fs::canonicalize(&script_path).map_err(|e| UtilError::FileReadError {
+        description: format!("{}: {}", script_path.display(), e),
+        source: e,
+    })?;
+
+    let is_in_script_dirs = get_script_dirs()
+        .iter()
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .any(|dir| script_path.starts_with(dir));
+
+    if is_in_script_dirs {
+        Ok(script_path)
+    } else {
+        Err(UtilError::FileNotFound {
+            description: format!(
+                "Not located in the script directories: {}",
+                script_file.display()
+            ),
+        }
+        .into())
+    }
+}
+
+thread_local! {
+    /// The name of the current thread, if it has been renamed after it has been spawned
+    static THREAD_NAME: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Rename the current thread, e.g. after a pooled thread has been assigned a new task. The
+/// name of the OS thread is truncated to the 15 bytes that are supported by the kernel
+pub fn set_thread_name(name: &str) {
+    if let Ok(os_name) = CString::new(truncate_thread_name(name)) {
+        unsafe {
+            libc::pthread_setname_np(libc::pthread_self(), os_name.as_ptr());
+        }
+    }
+
+    THREAD_NAME.with(|thread_name| *thread_name.borrow_mut() = Some(name.to_owned()));
+}
+
+/// Returns the name of the current thread
+pub fn get_thread_name() -> String {
+    THREAD_NAME
+        .with(|thread_name| thread_name.borrow().clone())
+        .or_else(|| thread::current().name().map(str::to_owned))
+        .unwrap_or_else(|| "unnamed".to_owned())
+}
+
+fn truncate_thread_name(name: &str) -> &str {
+    let mut len = name.len().min(constants::MAX_THREAD_NAME_LEN);
+
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    &name[..len]
+}
+
 /// Provide a simple means to rate-limit log output
 pub mod ratelimited {
     use lazy_static::lazy_static;
@@ -409,3 +486,32 @@ pub mod ratelimited {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threads_can_be_renamed() {
+        thread::Builder::new()
+            .name("lua-vm:0".to_string())
+            .spawn(|| {
+                assert_eq!(get_thread_name(), "lua-vm:0");
+
+                set_thread_name("0:a-very-long-script-name.lua");
+                assert_eq!(get_thread_name(), "0:a-very-long-script-name.lua");
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(truncate_thread_name("0:short.lua"), "0:short.lua");
+        assert_eq!(
+            truncate_thread_name("0:a-very-long-script.lua"),
+            "0:a-very-long-s"
+        );
+
+        // names are not truncated within a multi-byte character
+        assert_eq!(truncate_thread_name("0:aaaaaaaaaaaaä"), "0:aaaaaaaaaaaa");
+    }
+}
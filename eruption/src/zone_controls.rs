@@ -0,0 +1,352 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Runtime controls of the brightness and the animation speed of zones, like e.g. dimming
+//! the numpad. The controls belong to a profile and are persisted in its state file. The
+//! brightness of a zone is scaled after all layers have been composited, while the speed
+//! factor is made available to the scripts, which scale their animations accordingly
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::hwdevices::RGBA;
+use crate::profiles::{Profile, ZoneControl};
+use crate::scripting::script;
+use crate::{constants, zones};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ZoneControlError {
+    #[error("Unknown zone: {name}")]
+    UnknownZone { name: String },
+
+    #[error("Invalid zone control: {description}")]
+    InvalidZoneControl { description: String },
+
+    #[error("No profile is active")]
+    NoActiveProfile,
+}
+
+lazy_static! {
+    /// The file name of the active profile and its zone controls, keyed by the name of the
+    /// zone, or `None` while the failsafe profile is active
+    static ref ZONE_CONTROLS: Arc<RwLock<Option<(PathBuf, BTreeMap<String, ZoneControl>)>>> =
+        Arc::new(RwLock::new(None));
+
+    /// The zone controls of the active profile, resolved to the keys of the canvas, or
+    /// `None` if the active profile does not control any zones
+    static ref KEY_CONTROLS: Arc<RwLock<Option<Vec<KeyControl>>>> = Arc::new(RwLock::new(None));
+}
+
+impl ZoneControl {
+    fn validate(&self) -> Result<()> {
+        if !(0..=100).contains(&self.brightness) {
+            return Err(ZoneControlError::InvalidZoneControl {
+                description: format!(
+                    "The brightness must be between 0 and 100 percent: {}",
+                    self.brightness
+                ),
+            }
+            .into());
+        }
+
+        if !(constants::MIN_ZONE_SPEED..=constants::MAX_ZONE_SPEED).contains(&self.speed) {
+            return Err(ZoneControlError::InvalidZoneControl {
+                description: format!(
+                    "The speed must be between {} and {}: {}",
+                    constants::MIN_ZONE_SPEED,
+                    constants::MAX_ZONE_SPEED,
+                    self.speed
+                ),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// The combined zone controls of a single key
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct KeyControl {
+    /// Brightness factor in the range `0.0..=1.0`
+    brightness: f32,
+
+    speed: f64,
+}
+
+impl Default for KeyControl {
+    fn default() -> Self {
+        Self {
+            brightness: 1.0,
+            speed: 1.0,
+        }
+    }
+}
+
+/// Resolve the zone controls `controls` to the keys of the canvas. The controls of
+/// overlapping zones are multiplied
+fn resolve_key_controls(
+    controls: &BTreeMap<String, ZoneControl>,
+    get_zone: impl Fn(&str) -> Option<Vec<usize>>,
+) -> Vec<KeyControl> {
+    let mut result = vec![KeyControl::default(); constants::CANVAS_SIZE];
+
+    for (name, control) in controls {
+        let Some(keys) = get_zone(name) else {
+            warn!("Skipping the controls of the unknown zone: {}", name);
+            continue;
+        };
+
+        for index in keys {
+            // key indices start at 1
+            if let Some(key) = index.checked_sub(1).and_then(|i| result.get_mut(i)) {
+                key.brightness *= control.brightness as f32 / 100.0;
+                key.speed *= control.speed;
+            }
+        }
+    }
+
+    result
+}
+
+/// Recompute the controls of the keys, e.g. after the active profile has been switched, or
+/// after the zones changed because devices have been hotplugged
+pub fn update_key_controls() {
+    let controls = get_zone_controls();
+
+    *KEY_CONTROLS.write() = if controls.is_empty() {
+        None
+    } else {
+        Some(resolve_key_controls(&controls, zones::get_zone))
+    };
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Apply the zone controls of the profile `profile`, `None` for the failsafe profile
+pub fn set_profile(profile: Option<&Profile>) {
+    *ZONE_CONTROLS.write() = profile.map(|profile| {
+        let controls = profile
+            .zone_controls
+            .iter()
+            .filter(|(name, control)| match control.validate() {
+                Ok(()) => true,
+
+                Err(e) => {
+                    warn!("Skipping the controls of zone '{}': {}", name, e);
+                    false
+                }
+            })
+            .map(|(name, control)| (name.clone(), *control))
+            .collect();
+
+        (profile.profile_file.clone(), controls)
+    });
+
+    update_key_controls();
+}
+
+/// Returns the zone controls of the active profile, keyed by the name of the zone
+pub fn get_zone_controls() -> BTreeMap<String, ZoneControl> {
+    ZONE_CONTROLS
+        .read()
+        .as_ref()
+        .map(|(_, controls)| controls.clone())
+        .unwrap_or_default()
+}
+
+/// Assign the brightness and speed of the zone `name` of the active profile, and persist them
+pub fn set_zone_control(name: &str, control: ZoneControl) -> Result<()> {
+    control.validate()?;
+
+    if zones::get_zone(name).is_none() {
+        return Err(ZoneControlError::UnknownZone {
+            name: name.to_owned(),
+        }
+        .into());
+    }
+
+    let (profile_file, controls) = {
+        let mut zone_controls = ZONE_CONTROLS.write();
+
+        let (profile_file, controls) = zone_controls
+            .as_mut()
+            .ok_or(ZoneControlError::NoActiveProfile)?;
+
+        if control == ZoneControl::default() {
+            controls.remove(name);
+        } else {
+            controls.insert(name.to_owned(), control);
+        }
+
+        (profile_file.clone(), controls.clone())
+    };
+
+    info!(
+        "Zone '{}': brightness {}%, speed {}",
+        name, control.brightness, control.speed
+    );
+
+    update_key_controls();
+
+    save_zone_controls(&profile_file, controls)
+}
+
+/// Restore the default brightness and speed of the zone `name` of the active profile,
+/// returns `false` if the zone has not been controlled
+pub fn reset_zone_control(name: &str) -> Result<bool> {
+    if !get_zone_controls().contains_key(name) {
+        return Ok(false);
+    }
+
+    set_zone_control(name, ZoneControl::default())?;
+
+    Ok(true)
+}
+
+/// Returns the factor that scales the speed of the animations on the key `key_index`
+pub fn get_key_speed(key_index: usize) -> f64 {
+    KEY_CONTROLS
+        .read()
+        .as_ref()
+        .and_then(|controls| controls.get(key_index.checked_sub(1)?))
+        .map_or(1.0, |control| control.speed)
+}
+
+/// Scale the brightness of the controlled zones of each canvas of `led_map`
+pub fn apply_brightness(led_map: &mut [RGBA]) {
+    if let Some(controls) = KEY_CONTROLS.read().as_ref() {
+        scale_brightness(led_map, controls);
+    }
+}
+
+fn scale_brightness(led_map: &mut [RGBA], controls: &[KeyControl]) {
+    for chunks in led_map.chunks_exact_mut(constants::CANVAS_SIZE) {
+        for (color, control) in chunks.iter_mut().zip(controls.iter()) {
+            if control.brightness < 1.0 {
+                color.r = (color.r as f32 * control.brightness) as u8;
+                color.g = (color.g as f32 * control.brightness) as u8;
+                color.b = (color.b as f32 * control.brightness) as u8;
+            }
+        }
+    }
+}
+
+/// Store the zone controls `controls` in the state of the active profile `profile_file`
+fn save_zone_controls(profile_file: &Path, controls: BTreeMap<String, ZoneControl>) -> Result<()> {
+    let mut active_profile = crate::ACTIVE_PROFILE.lock();
+
+    match active_profile.as_mut() {
+        Some(profile) if profile.profile_file == *profile_file => {
+            profile.zone_controls = controls;
+            profile.save_params()
+        }
+
+        // the profile has been switched in the meantime
+        _ => Err(ZoneControlError::NoActiveProfile.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_zone(name: &str) -> Option<Vec<usize>> {
+        match name {
+            "numpad" => Some(vec![1, 2]),
+            "wasd" => Some(vec![2, 3]),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn overlapping_zones_are_multiplied() {
+        let controls = BTreeMap::from([
+            (
+                "numpad".to_string(),
+                ZoneControl {
+                    brightness: 50,
+                    speed: 1.0,
+                },
+            ),
+            (
+                "wasd".to_string(),
+                ZoneControl {
+                    brightness: 50,
+                    speed: 2.0,
+                },
+            ),
+            ("unknown".to_string(), ZoneControl::default()),
+        ]);
+
+        let keys = resolve_key_controls(&controls, get_zone);
+
+        assert_eq!(keys[0].brightness, 0.5);
+        assert_eq!((keys[1].brightness, keys[1].speed), (0.25, 2.0));
+        assert_eq!(keys[2].speed, 2.0);
+        assert_eq!(keys[3], KeyControl::default());
+
+        let mut led_map = vec![
+            RGBA {
+                r: 200,
+                g: 100,
+                b: 0,
+                a: 255,
+            };
+            constants::CANVAS_SIZE * 2
+        ];
+
+        scale_brightness(&mut led_map, &keys);
+
+        // each canvas of the LED map is scaled
+        for chunks in led_map.chunks_exact(constants::CANVAS_SIZE) {
+            assert_eq!((chunks[0].r, chunks[0].g, chunks[0].a), (100, 50, 255));
+            assert_eq!(chunks[1].r, 50);
+            assert_eq!(chunks[3].r, 200);
+        }
+    }
+
+    #[test]
+    fn invalid_controls_are_rejected() {
+        assert!(ZoneControl::default().validate().is_ok());
+
+        assert!(ZoneControl {
+            brightness: 101,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+
+        assert!(ZoneControl {
+            speed: 0.0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,156 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::{constants, zone_controls};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ZoneError {
+    #[error("Invalid zone definition: {msg}")]
+    InvalidZone { msg: String },
+}
+
+lazy_static! {
+    /// User defined zones, as configured in the [[zones]] sections of eruption.conf
+    static ref USER_ZONES: Arc<RwLock<Vec<Zone>>> = Arc::new(RwLock::new(vec![]));
+}
+
+/// A named region on the canvas, like e.g. the WASD keys or the numpad
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Zone {
+    pub name: String,
+
+    /// Key indices of the keys/LEDs that make up the zone, as used by Lua scripts
+    pub keys: Vec<usize>,
+}
+
+impl Zone {
+    pub fn new(name: &str, keys: &[usize]) -> Self {
+        Self {
+            name: name.to_string(),
+            keys: keys.to_vec(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(ZoneError::InvalidZone {
+                msg: "The name must not be empty".to_string(),
+            }
+            .into());
+        }
+
+        if let Some(key) = self
+            .keys
+            .iter()
+            .find(|key| !(1..=constants::CANVAS_SIZE).contains(*key))
+        {
+            return Err(ZoneError::InvalidZone {
+                msg: format!("Key index out of range: {}", key),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Load the user defined zones from the configuration
+pub fn load_zones() -> Result<()> {
+    let config = crate::CONFIG.lock();
+    let config = config.as_ref().unwrap();
+
+    let definitions = config.get::<Vec<Zone>>("zones").unwrap_or_else(|_| vec![]);
+
+    let mut zones = vec![];
+
+    for zone in definitions {
+        match zone.validate() {
+            Ok(()) => zones.push(zone),
+            Err(e) => error!("Skipping zone '{}': {}", zone.name, e),
+        }
+    }
+
+    info!("Loaded {} user defined zone(s)", zones.len());
+
+    *USER_ZONES.write() = zones;
+
+    // the zone controls of the active profile may refer to the user defined zones
+    zone_controls::update_key_controls();
+
+    Ok(())
+}
+
+/// Returns all zones, the zones shipped with the drivers of the connected devices,
+/// extended by the user defined zones. User defined zones replace driver supplied
+/// zones of the same name
+pub fn get_zones() -> Vec<Zone> {
+    let mut result = BTreeMap::new();
+
+    for device in crate::KEYBOARD_DEVICES.read().iter() {
+        for zone in device.read().get_zones() {
+            result.insert(zone.name.clone(), zone);
+        }
+    }
+
+    for device in crate::MOUSE_DEVICES.read().iter() {
+        let zones = device.read().get_zones();
+
+        if zones.is_empty() {
+            // mice without driver supplied zones: the whole area of the canvas that is
+            // reserved for mice
+            let keys = (constants::MAX_KEYS + 1..=constants::CANVAS_SIZE).collect::<Vec<_>>();
+            result.insert("mouse".to_string(), Zone::new("mouse", &keys));
+        }
+
+        for zone in zones {
+            result.insert(zone.name.clone(), zone);
+        }
+    }
+
+    for device in crate::MISC_DEVICES.read().iter() {
+        for zone in device.read().get_zones() {
+            result.insert(zone.name.clone(), zone);
+        }
+    }
+
+    for zone in USER_ZONES.read().iter() {
+        result.insert(zone.name.clone(), zone.clone());
+    }
+
+    result.into_values().collect()
+}
+
+/// Returns the key indices of the zone `name`
+pub fn get_zone(name: &str) -> Option<Vec<usize>> {
+    get_zones()
+        .into_iter()
+        .find(|zone| zone.name == name)
+        .map(|zone| zone.keys)
+}
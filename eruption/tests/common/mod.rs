@@ -0,0 +1,92 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+#![allow(dead_code)]
+
+pub mod uhid;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, MutexGuard};
+
+/// Tests that run the daemon may not run in parallel, since each instance of the
+/// daemon would try to bind all of the virtual devices
+static DAEMON_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn lock_daemon() -> MutexGuard<'static, ()> {
+    DAEMON_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An instance of the Eruption daemon, that is run against a temporary configuration.
+/// The daemon is killed when it is dropped
+pub struct Daemon {
+    child: Child,
+    config_dir: PathBuf,
+}
+
+impl Daemon {
+    pub fn spawn() -> io::Result<Self> {
+        let source_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+        let config_dir = std::env::temp_dir().join(format!("eruption-test-{}", std::process::id()));
+        fs::create_dir_all(&config_dir)?;
+
+        let config = format!(
+            r#"[global]
+driver_maturity_level = "stable"
+
+profile_dirs = ["{profiles}"]
+script_dirs = ["{scripts}"]
+
+keyboard_variant = "ISO"
+
+enable_mouse = false
+grab_mouse = false
+"#,
+            profiles = source_dir.join("../support/profiles/").display(),
+            scripts = source_dir.join("src/scripts/").display()
+        );
+
+        let config_file = config_dir.join("eruption.conf");
+        fs::write(&config_file, config)?;
+
+        let child = Command::new(env!("CARGO_BIN_EXE_eruption"))
+            .arg("-c")
+            .arg(&config_file)
+            .env("RUST_LOG", "info")
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        Ok(Self { child, config_dir })
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+
+        let _ = fs::remove_dir_all(&self.config_dir);
+    }
+}
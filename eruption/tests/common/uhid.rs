@@ -0,0 +1,327 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Virtual HID devices based on the Linux uhid interface, used to emulate supported
+//! devices without the need for real hardware. Requires access to `/dev/uhid`
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const UHID_DEVICE: &str = "/dev/uhid";
+
+// event types, see linux/uhid.h
+const UHID_DESTROY: u32 = 1;
+const UHID_OUTPUT: u32 = 6;
+const UHID_GET_REPORT: u32 = 9;
+const UHID_GET_REPORT_REPLY: u32 = 10;
+const UHID_CREATE2: u32 = 11;
+const UHID_SET_REPORT: u32 = 13;
+const UHID_SET_REPORT_REPLY: u32 = 14;
+
+/// Size of `struct uhid_event`, the largest member of the union is `struct uhid_create2_req`
+const UHID_EVENT_SIZE: usize = 4 + 128 + 64 + 64 + 2 + 2 + 4 + 4 + 4 + 4 + 4096;
+
+const BUS_USB: u16 = 0x03;
+
+const EIO: u16 = 5;
+
+/// A report descriptor of a vendor defined device with 64 byte input and output reports
+pub const VENDOR_REPORT_DESCRIPTOR: &[u8] = &[
+    0x06, 0x00, 0xff, // Usage Page (Vendor Defined 0xFF00)
+    0x09, 0x01, // Usage (0x01)
+    0xa1, 0x01, // Collection (Application)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xff, 0x00, //   Logical Maximum (255)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x40, //   Report Count (64)
+    0x09, 0x01, //   Usage (0x01)
+    0x81, 0x02, //   Input (Data,Var,Abs)
+    0x95, 0x40, //   Report Count (64)
+    0x09, 0x01, //   Usage (0x01)
+    0x91, 0x02, //   Output (Data,Var,Abs)
+    0xc0, // End Collection
+];
+
+/// A report that has been sent to a virtual device by the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Report {
+    /// An output report, e.g. written via `hid_write()`
+    Output(Vec<u8>),
+
+    /// A feature report, e.g. sent via `hid_send_feature_report()`
+    Feature(Vec<u8>),
+}
+
+impl Report {
+    pub fn data(&self) -> &[u8] {
+        match self {
+            Report::Output(data) | Report::Feature(data) => data,
+        }
+    }
+}
+
+/// Describes a virtual device. The interface number is encoded into the physical path
+/// of the device, just like the usbhid driver does it for real USB devices
+#[derive(Debug, Clone)]
+pub struct DeviceSpec {
+    pub name: String,
+    pub vendor: u16,
+    pub product: u16,
+    pub serial: String,
+    pub interface: i32,
+    pub descriptor: Vec<u8>,
+
+    /// Replies to `GET_REPORT` requests, indexed by the report id
+    pub feature_replies: HashMap<u8, Vec<u8>>,
+}
+
+impl DeviceSpec {
+    pub fn new(name: &str, vendor: u16, product: u16, interface: i32) -> Self {
+        Self {
+            name: name.to_string(),
+            vendor,
+            product,
+            serial: "uhid-test".to_string(),
+            interface,
+            descriptor: VENDOR_REPORT_DESCRIPTOR.to_vec(),
+            feature_replies: HashMap::new(),
+        }
+    }
+
+    /// Reply with `data` when the host requests the feature report `report_id`
+    pub fn feature_reply(mut self, report_id: u8, data: &[u8]) -> Self {
+        self.feature_replies.insert(report_id, data.to_vec());
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct Reports {
+    received: Mutex<Vec<Report>>,
+    cond: Condvar,
+}
+
+/// A virtual HID device, the device is destroyed when it is dropped
+pub struct VirtualDevice {
+    file: Arc<Mutex<File>>,
+    reports: Arc<Reports>,
+    quit: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl VirtualDevice {
+    /// Create a new virtual device
+    pub fn create(spec: DeviceSpec) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(UHID_DEVICE)?;
+
+        file.write_all(&create2_event(&spec))?;
+
+        let file = Arc::new(Mutex::new(file));
+        let reports = Arc::new(Reports::default());
+        let quit = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let file = file.clone();
+            let reports = reports.clone();
+            let quit = quit.clone();
+
+            thread::Builder::new()
+                .name(format!("uhid:{}", spec.interface))
+                .spawn(move || {
+                    event_loop(&file, &spec, &reports, &quit)
+                        .unwrap_or_else(|e| eprintln!("uhid event loop failed: {e}"))
+                })?
+        };
+
+        Ok(Self {
+            file,
+            reports,
+            quit,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns all reports that have been received so far
+    pub fn reports(&self) -> Vec<Report> {
+        self.reports.received.lock().unwrap().clone()
+    }
+
+    /// Wait until `predicate` holds for the reports that have been received so far
+    pub fn wait_for<F>(&self, timeout: Duration, predicate: F) -> Option<Vec<Report>>
+    where
+        F: Fn(&[Report]) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        let mut received = self.reports.received.lock().unwrap();
+
+        loop {
+            if predicate(&received) {
+                return Some(received.clone());
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                return None;
+            }
+
+            received = self
+                .reports
+                .cond
+                .wait_timeout(received, remaining)
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+impl Drop for VirtualDevice {
+    fn drop(&mut self) {
+        self.quit.store(true, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        let mut event = [0u8; UHID_EVENT_SIZE];
+        event[0..4].copy_from_slice(&UHID_DESTROY.to_ne_bytes());
+
+        let _ = self.file.lock().unwrap().write_all(&event);
+    }
+}
+
+fn event_loop(
+    file: &Mutex<File>,
+    spec: &DeviceSpec,
+    reports: &Reports,
+    quit: &AtomicBool,
+) -> io::Result<()> {
+    let fd = file.lock().unwrap().as_raw_fd();
+
+    while !quit.load(Ordering::SeqCst) {
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        let result = unsafe { libc::poll(&mut pollfd, 1, 100) };
+
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        } else if result == 0 {
+            continue;
+        }
+
+        let mut event = [0u8; UHID_EVENT_SIZE];
+        let mut file = file.lock().unwrap();
+
+        if file.read(&mut event)? < 4 {
+            continue;
+        }
+
+        let event_type = u32::from_ne_bytes(event[0..4].try_into().unwrap());
+
+        match event_type {
+            UHID_OUTPUT => {
+                let size = u16::from_ne_bytes([event[4100], event[4101]]) as usize;
+
+                push_report(reports, Report::Output(event[4..4 + size].to_vec()));
+            }
+
+            UHID_SET_REPORT => {
+                let id = &event[4..8];
+                let size = u16::from_ne_bytes([event[10], event[11]]) as usize;
+
+                push_report(reports, Report::Feature(event[12..12 + size].to_vec()));
+
+                let mut reply = [0u8; UHID_EVENT_SIZE];
+                reply[0..4].copy_from_slice(&UHID_SET_REPORT_REPLY.to_ne_bytes());
+                reply[4..8].copy_from_slice(id);
+
+                file.write_all(&reply)?;
+            }
+
+            UHID_GET_REPORT => {
+                let id = &event[4..8];
+                let report_id = event[8];
+
+                let mut reply = [0u8; UHID_EVENT_SIZE];
+                reply[0..4].copy_from_slice(&UHID_GET_REPORT_REPLY.to_ne_bytes());
+                reply[4..8].copy_from_slice(id);
+
+                match spec.feature_replies.get(&report_id) {
+                    Some(data) => {
+                        reply[10..12].copy_from_slice(&(data.len() as u16).to_ne_bytes());
+                        reply[12..12 + data.len()].copy_from_slice(data);
+                    }
+
+                    None => reply[8..10].copy_from_slice(&EIO.to_ne_bytes()),
+                }
+
+                file.write_all(&reply)?;
+            }
+
+            _ => { /* start, stop, open and close events are of no interest */ }
+        }
+    }
+
+    Ok(())
+}
+
+fn push_report(reports: &Reports, report: Report) {
+    reports.received.lock().unwrap().push(report);
+    reports.cond.notify_all();
+}
+
+fn create2_event(spec: &DeviceSpec) -> Vec<u8> {
+    fn copy_str(dest: &mut [u8], s: &str) {
+        let len = s.len().min(dest.len() - 1);
+        dest[..len].copy_from_slice(&s.as_bytes()[..len]);
+    }
+
+    let mut event = vec![0u8; UHID_EVENT_SIZE];
+    event[0..4].copy_from_slice(&UHID_CREATE2.to_ne_bytes());
+
+    let phys = format!("usb-uhid-{}/input{}", spec.serial, spec.interface);
+
+    copy_str(&mut event[4..132], &spec.name);
+    copy_str(&mut event[132..196], &phys);
+    copy_str(&mut event[196..260], &spec.serial);
+
+    event[260..262].copy_from_slice(&(spec.descriptor.len() as u16).to_ne_bytes());
+    event[262..264].copy_from_slice(&BUS_USB.to_ne_bytes());
+    event[264..268].copy_from_slice(&(spec.vendor as u32).to_ne_bytes());
+    event[268..272].copy_from_slice(&(spec.product as u32).to_ne_bytes());
+
+    event[280..280 + spec.descriptor.len()].copy_from_slice(&spec.descriptor);
+
+    event
+}
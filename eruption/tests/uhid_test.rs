@@ -0,0 +1,156 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Runs the Eruption daemon against virtual uhid devices that emulate supported hardware.
+//!
+//! These tests require root privileges (or write access to `/dev/uhid`), a running system
+//! D-Bus with the Eruption policy installed, and the hidraw backend of hidapi, since the
+//! libusb backend does not see virtual devices:
+//!
+//! `sudo -E cargo test -p eruption --no-default-features --features hidapi-hidraw -- --ignored`
+
+mod common;
+
+use std::time::Duration;
+
+use common::uhid::{DeviceSpec, Report, VirtualDevice};
+use common::Daemon;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+mod roccat_vulcan_1xx {
+    use super::*;
+
+    const USB_VID: u16 = 0x1e7d;
+    const USB_PID: u16 = 0x3098;
+
+    const CTRL_INTERFACE: i32 = 1;
+    const LED_INTERFACE: i32 = 3;
+
+    const NUM_KEYS: usize = 143;
+
+    /// Create the control and the LED sub-devices of the keyboard
+    fn create_devices() -> (VirtualDevice, VirtualDevice) {
+        let ctrl = VirtualDevice::create(
+            DeviceSpec::new("ROCCAT Vulcan 100 (uhid)", USB_VID, USB_PID, CTRL_INTERFACE)
+                // the device is ready
+                .feature_reply(0x04, &[0x04, 0x01, 0x00, 0x00])
+                // device info, firmware version 1.36
+                .feature_reply(0x0f, &[0x0f, 0x06, 0x88, 0x00, 0x00, 0x00]),
+        )
+        .expect("Could not create the virtual control device");
+
+        let led = VirtualDevice::create(DeviceSpec::new(
+            "ROCCAT Vulcan 100 (uhid)",
+            USB_VID,
+            USB_PID,
+            LED_INTERFACE,
+        ))
+        .expect("Could not create the virtual LED device");
+
+        (ctrl, led)
+    }
+
+    /// Decode the LED maps that have been sent to the LED sub-device. A LED map is
+    /// transferred in 7 output reports of 64 bytes, the first one starts with a header
+    fn decode_led_maps(reports: &[Report]) -> Vec<Vec<(u8, u8, u8)>> {
+        let mut result = vec![];
+        let mut buffer: Vec<u8> = vec![];
+
+        for report in reports {
+            let data = report.data();
+
+            // the report id 0 may be passed through to the device
+            let data = if data.len() == 65 { &data[1..] } else { data };
+
+            if data.starts_with(&[0xa1, 0x01, 0x01, 0xb4]) {
+                buffer.clear();
+            }
+
+            buffer.extend_from_slice(data);
+
+            if buffer.len() >= 448 {
+                let led_map = (0..NUM_KEYS)
+                    .map(|i| {
+                        let offset = ((i / 12) * 36) + (i % 12) + 4;
+
+                        (buffer[offset], buffer[offset + 12], buffer[offset + 24])
+                    })
+                    .collect();
+
+                result.push(led_map);
+                buffer.clear();
+            }
+        }
+
+        result
+    }
+
+    #[test]
+    #[ignore = "requires /dev/uhid, a system D-Bus and the hidraw backend of hidapi"]
+    fn test_init_sequence() {
+        let _lock = common::lock_daemon();
+
+        let (ctrl, _led) = create_devices();
+        let _daemon = Daemon::spawn().expect("Could not start the daemon");
+
+        let expected = [0x15, 0x05, 0x07, 0x0a, 0x0b, 0x06, 0x09, 0x0d, 0x13];
+
+        let reports = ctrl
+            .wait_for(TIMEOUT, |reports| reports.len() >= expected.len())
+            .expect("The device has not been initialized");
+
+        let report_ids = reports
+            .iter()
+            .filter_map(|report| match report {
+                Report::Feature(data) => data.first().copied(),
+                Report::Output(_) => None,
+            })
+            .take(expected.len())
+            .collect::<Vec<_>>();
+
+        assert_eq!(report_ids, expected);
+    }
+
+    #[test]
+    #[ignore = "requires /dev/uhid, a system D-Bus and the hidraw backend of hidapi"]
+    fn test_led_maps() {
+        let _lock = common::lock_daemon();
+
+        let (_ctrl, led) = create_devices();
+        let _daemon = Daemon::spawn().expect("Could not start the daemon");
+
+        // wait for a couple of frames of the render pipeline
+        let reports = led
+            .wait_for(TIMEOUT, |reports| decode_led_maps(reports).len() >= 10)
+            .expect("No LED maps have been received");
+
+        let led_maps = decode_led_maps(&reports);
+
+        assert!(led_maps.iter().all(|led_map| led_map.len() == NUM_KEYS));
+        assert!(
+            led_maps
+                .iter()
+                .any(|led_map| led_map.iter().any(|color| *color != (0, 0, 0))),
+            "All received LED maps are black"
+        );
+    }
+}
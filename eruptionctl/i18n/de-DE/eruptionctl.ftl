@@ -34,12 +34,16 @@ scripts-about = Script bezogene Unterbefehle
 effects-about = Spezialeffekte wie z.B. Ambient, Bildoverlays oder Animationen
 param-about = Lesen oder schreiben von Parameterwerten von aktuell laufenden Scripten
 rules-about = Automatisierungsregeln anzeigen und verwalten
+canvas-about = Tasten vorübergehend einfärben, z.B. aus Shell-Scripten
+batch-about = Eine Liste von Operationen auf einmal anwenden, alle Änderungen werden zurückgenommen, falls eine davon fehlschlägt
+zones-about = Helligkeit und Animationsgeschwindigkeit von Zonen anpassen, z.B. des Ziffernblocks
 
 rules-list = Alle Automatisierungsregeln anzeigen
 rules-add = Eine neue Automatisierungsregel hinzufügen
 rules-remove = Eine vorhandene Automatisierungsregel entfernen
 rules-enable = Eine vorhandene Automatisierungsregel aktivieren
 rules-disable = Eine vorhandene Automatisierungsregel deaktivieren
+rules-move = Eine vorhandene Automatisierungsregel verschieben
 
 dumping-devices = Liste der durch Eruption kontrollierten Geräte
 keyboard-devices = Tastaturen:
@@ -34,12 +34,16 @@ scripts-about = Script related sub-commands
 effects-about = Special effects like Ambient, image overlays or animations
 param-about = Get or set script parameters on the currently active profile
 rules-about = Rules related sub-commands
+canvas-about = Temporarily paint keys, e.g. from shell scripts
+batch-about = Apply a list of operations at once, all changes are rolled back if one of them fails
+zones-about = Scale the brightness and animation speed of zones, like e.g. the numpad
 
 rules-list = List all available rules
 rules-add = Create a new rule
 rules-remove = Remove an existing rule by its index
 rules-enable = Mark an existing rule as enabled
 rules-disable = Mark an existing rule as disabled
+rules-move = Move an existing rule to another position
 
 dumping-devices = Dumping Eruption managed devices list
 keyboard-devices = Keyboard devices:
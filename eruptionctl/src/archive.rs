@@ -0,0 +1,329 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::constants;
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// The version of the archive format, archives of newer versions are rejected
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error(
+        "Unsupported archive format version {version}, the newest supported version is {supported}"
+    )]
+    UnsupportedVersion { version: u32, supported: u32 },
+
+    #[error("Unknown section: {name}")]
+    UnknownSection { name: String },
+
+    #[error("Refusing to restore a file outside of the configuration directories: {path}")]
+    InvalidPath { path: String },
+}
+
+/// The parts of the configuration that may be restored selectively
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Section {
+    /// The main configuration file `eruption.conf`
+    Config,
+
+    /// Slot assignments and the remaining runtime state of the daemon
+    Slots,
+
+    /// Device specific state, like canvas transforms or key filters
+    Devices,
+
+    /// Named color schemes
+    ColorSchemes,
+
+    /// Rules of the process monitor, of the current user
+    Rules,
+}
+
+impl Section {
+    pub const ALL: [Section; 5] = [
+        Section::Config,
+        Section::Slots,
+        Section::Devices,
+        Section::ColorSchemes,
+        Section::Rules,
+    ];
+
+    /// Returns the files that belong to the section
+    pub fn files(&self) -> Vec<PathBuf> {
+        let state_dir = PathBuf::from(constants::STATE_DIR);
+
+        match self {
+            Section::Config => vec![PathBuf::from(constants::DEFAULT_CONFIG_FILE)],
+
+            Section::Slots => vec![state_dir.join("eruption.state")],
+
+            Section::Devices => [
+                "device-transforms.state",
+                "device-brightness-curves.state",
+                "device-key-filters.state",
+                "canvas-layout.state",
+            ]
+            .iter()
+            .map(|file_name| state_dir.join(file_name))
+            .collect(),
+
+            Section::ColorSchemes => vec![state_dir.join("color-schemes.state")],
+
+            Section::Rules => rules_file().into_iter().collect(),
+        }
+    }
+
+    /// Returns `true` if the files of the section are owned by root
+    pub fn is_system_wide(&self) -> bool {
+        !matches!(self, Section::Rules)
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Section::Config => "config",
+            Section::Slots => "slots",
+            Section::Devices => "devices",
+            Section::ColorSchemes => "color-schemes",
+            Section::Rules => "rules",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Section {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Section::ALL
+            .iter()
+            .find(|section| section.to_string() == s.trim())
+            .copied()
+            .ok_or_else(|| {
+                ArchiveError::UnknownSection {
+                    name: s.to_string(),
+                }
+                .into()
+            })
+    }
+}
+
+/// Returns the rules file of the process monitor of the current user
+fn rules_file() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+
+    Some(
+        PathBuf::from(home)
+            .join(".local/share/eruption-process-monitor")
+            .join("process-monitor.rules"),
+    )
+}
+
+/// A single file of the archive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub section: Section,
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// A backup of the configuration of the daemon, that may be restored on another machine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigArchive {
+    /// The version of the archive format
+    pub format_version: u32,
+
+    /// The version of Eruption that created the archive
+    pub eruption_version: String,
+
+    /// Time of the export, in seconds since the epoch
+    pub created: u64,
+
+    #[serde(default)]
+    pub files: Vec<ArchiveEntry>,
+}
+
+impl ConfigArchive {
+    /// Collect the files of all sections, files that do not exist are skipped
+    pub fn collect() -> Result<Self> {
+        let mut files = vec![];
+
+        for section in Section::ALL {
+            for path in section.files() {
+                if path.exists() {
+                    let contents = fs::read_to_string(&path)?;
+
+                    files.push(ArchiveEntry {
+                        section,
+                        path,
+                        contents,
+                    });
+                }
+            }
+        }
+
+        Ok(Self {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            eruption_version: env!("CARGO_PKG_VERSION").to_string(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            files,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)?;
+        let result: Self = toml::from_str(&data)?;
+
+        if result.format_version > ARCHIVE_FORMAT_VERSION {
+            return Err(ArchiveError::UnsupportedVersion {
+                version: result.format_version,
+                supported: ARCHIVE_FORMAT_VERSION,
+            }
+            .into());
+        }
+
+        Ok(result)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = toml::to_string_pretty(self)?;
+        fs::write(path, data)?;
+
+        Ok(())
+    }
+
+    /// Returns `true` if the archive has been created by a different release of Eruption
+    pub fn is_foreign_version(&self) -> bool {
+        release(&self.eruption_version) != release(env!("CARGO_PKG_VERSION"))
+    }
+
+    /// Returns the entries of the sections `sections`, or of all sections if empty
+    pub fn entries<'a>(
+        &'a self,
+        sections: &'a [Section],
+    ) -> impl Iterator<Item = &'a ArchiveEntry> + 'a {
+        self.files
+            .iter()
+            .filter(move |entry| sections.is_empty() || sections.contains(&entry.section))
+    }
+
+    /// Returns the file an entry is restored to. Entries are matched by their file name
+    /// against the files of their section, so that e.g. the rules of a user may be
+    /// restored to a different home directory
+    pub fn target_path(entry: &ArchiveEntry) -> Result<PathBuf> {
+        entry
+            .section
+            .files()
+            .into_iter()
+            .find(|path| path.file_name().is_some() && path.file_name() == entry.path.file_name())
+            .ok_or_else(|| {
+                ArchiveError::InvalidPath {
+                    path: entry.path.display().to_string(),
+                }
+                .into()
+            })
+    }
+
+    /// Write the file of an entry, returns the path of the restored file
+    pub fn restore(entry: &ArchiveEntry) -> Result<PathBuf> {
+        let path = Self::target_path(entry)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&path, &entry.contents)?;
+
+        Ok(path)
+    }
+}
+
+/// Returns the major and minor version of a release, like e.g. "0.5" for "0.5.1"
+fn release(version: &str) -> String {
+    version.split('.').take(2).collect::<Vec<_>>().join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sections() {
+        for section in Section::ALL {
+            assert_eq!(section.to_string().parse::<Section>().unwrap(), section);
+        }
+
+        assert!("profiles".parse::<Section>().is_err());
+    }
+
+    #[test]
+    fn select_sections() {
+        let entry = |section, path: &str| ArchiveEntry {
+            section,
+            path: PathBuf::from(path),
+            contents: String::new(),
+        };
+
+        let archive = ConfigArchive {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            eruption_version: "0.0.1".to_string(),
+            created: 0,
+            files: vec![
+                entry(Section::Config, "/etc/eruption/eruption.conf"),
+                entry(Section::Slots, "/var/lib/eruption/eruption.state"),
+                entry(
+                    Section::ColorSchemes,
+                    "/var/lib/eruption/color-schemes.state",
+                ),
+            ],
+        };
+
+        assert!(archive.is_foreign_version());
+        assert_eq!(archive.entries(&[]).count(), 3);
+        assert_eq!(archive.entries(&[Section::Slots]).count(), 1);
+        assert_eq!(archive.entries(&[Section::Devices]).count(), 0);
+
+        // files outside of the known locations are never written
+        let invalid = entry(Section::Config, "/etc/passwd");
+        assert!(ConfigArchive::target_path(&invalid).is_err());
+
+        let moved = entry(Section::Slots, "/srv/eruption/eruption.state");
+        assert_eq!(
+            ConfigArchive::target_path(&moved).unwrap(),
+            PathBuf::from(constants::STATE_DIR).join("eruption.state")
+        );
+    }
+}
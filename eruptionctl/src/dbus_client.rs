@@ -25,9 +25,12 @@ use crate::{
     color_scheme::{ColorScheme, ColorSchemeExt},
     constants,
 };
+use csscolorparser::Color;
 use dbus::blocking::Connection;
+use dbus::message::MatchRule;
 use dbus::nonblock;
 use dbus_tokio::connection;
+use std::sync::atomic::Ordering;
 use std::{sync::Arc, time::Duration};
 
 type Result<T> = std::result::Result<T, eyre::Error>;
@@ -53,6 +56,67 @@ pub async fn dbus_system_bus(
     Ok(proxy)
 }
 
+/// A subscription to signals of the Eruption daemon, as used by the `--watch` modes
+pub struct SignalSubscription {
+    _connection: Arc<nonblock::SyncConnection>,
+    _matches: Vec<nonblock::MsgMatch>,
+    rx: tokio::sync::mpsc::UnboundedReceiver<()>,
+}
+
+impl SignalSubscription {
+    /// Wait until one of the subscribed signals is received. Returns `false` if the
+    /// user pressed ctrl+c or the connection has been closed
+    pub async fn next(&mut self) -> bool {
+        loop {
+            if crate::QUIT.load(Ordering::SeqCst) {
+                return false;
+            }
+
+            match tokio::time::timeout(Duration::from_millis(250), self.rx.recv()).await {
+                Ok(Some(())) => return true,
+                Ok(None) => return false,
+
+                // timeout, check for ctrl+c
+                Err(_) => (),
+            }
+        }
+    }
+}
+
+/// Subscribe to the signals `signals` of the Eruption daemon, specified as pairs of
+/// interface and member names
+pub async fn subscribe_system_signals(signals: &[(&str, &str)]) -> Result<SignalSubscription> {
+    let (resource, conn) = connection::new_system_sync()?;
+
+    tokio::spawn(async {
+        let err = resource.await;
+        panic!("Lost connection to D-Bus: {}", err);
+    });
+
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut matches = Vec::new();
+
+    for (interface, member) in signals {
+        let rule = MatchRule::new_signal(*interface, *member)
+            .with_sender("org.eruption")
+            .static_clone();
+
+        let tx = tx.clone();
+        let signal_match = conn
+            .add_match(rule)
+            .await?
+            .cb(move |_msg, _: ()| tx.send(()).is_ok());
+
+        matches.push(signal_match);
+    }
+
+    Ok(SignalSubscription {
+        _connection: conn,
+        _matches: matches,
+        rx,
+    })
+}
+
 /// Returns a connection to the D-Bus session bus using the specified `path`
 pub async fn dbus_session_bus<'a>(
     dest: &'a str,
@@ -140,6 +204,42 @@ pub fn set_parameter(
     Ok(())
 }
 
+/// Get the file name of the currently active profile
+pub fn get_active_profile() -> Result<String> {
+    use profile::OrgEruptionProfile;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/profile",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS as u64),
+    );
+
+    let result = proxy.active_profile()?;
+
+    Ok(result)
+}
+
+/// Rebind the gradient and color parameters of a profile to a named color scheme
+pub fn apply_named_color_scheme(
+    profile_file: &str,
+    scheme_name: &str,
+    persist: bool,
+) -> Result<()> {
+    use profile::OrgEruptionProfile;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/profile",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS as u64),
+    );
+
+    let _result = proxy.apply_named_color_scheme(profile_file, scheme_name, persist)?;
+
+    Ok(())
+}
+
 // TODO: This currently fails with a dbus error, use util::get_slot_names() for now
 /// Fetches all slot names
 // pub fn get_slot_names() -> Result<Vec<String>> {
@@ -188,6 +288,27 @@ pub fn get_color_schemes() -> Result<Vec<String>> {
     Ok(result)
 }
 
+/// Fetches the colors of a named color scheme or a stock gradient
+pub fn get_color_scheme(name: &str) -> Result<ColorScheme> {
+    use self::config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let config_proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS as u64),
+    );
+
+    let data = config_proxy.get_color_scheme(name)?;
+
+    let colors = data
+        .chunks_exact(4)
+        .map(|c| Color::from_linear_rgba8(c[0], c[1], c[2], c[3]))
+        .collect();
+
+    Ok(ColorScheme { colors })
+}
+
 pub fn set_color_scheme(name: &str, color_scheme: &ColorScheme) -> Result<()> {
     use self::config::OrgEruptionConfig;
 
@@ -439,6 +560,12 @@ mod profile {
             param_name: &str,
             value: &str,
         ) -> Result<bool, dbus::Error>;
+        fn apply_named_color_scheme(
+            &self,
+            profile_file: &str,
+            scheme_name: &str,
+            persist: bool,
+        ) -> Result<bool, dbus::Error>;
         fn switch_profile(&self, filename: &str) -> Result<bool, dbus::Error>;
         fn active_profile(&self) -> Result<String, dbus::Error>;
     }
@@ -466,6 +593,20 @@ mod profile {
             .map(|r: (bool,)| r.0)
         }
 
+        fn apply_named_color_scheme(
+            &self,
+            profile_file: &str,
+            scheme_name: &str,
+            persist: bool,
+        ) -> Result<bool, dbus::Error> {
+            self.method_call(
+                "org.eruption.Profile",
+                "ApplyNamedColorScheme",
+                (profile_file, scheme_name, persist),
+            )
+            .map(|r: (bool,)| r.0)
+        }
+
         fn switch_profile(&self, filename: &str) -> Result<bool, dbus::Error> {
             self.method_call("org.eruption.Profile", "SwitchProfile", (filename,))
                 .map(|r: (bool,)| r.0)
@@ -649,6 +790,7 @@ mod config {
 
     pub trait OrgEruptionConfig {
         fn get_color_schemes(&self) -> Result<Vec<String>, dbus::Error>;
+        fn get_color_scheme(&self, name: &str) -> Result<Vec<u8>, dbus::Error>;
         fn ping(&self) -> Result<bool, dbus::Error>;
         fn ping_privileged(&self) -> Result<bool, dbus::Error>;
         fn remove_color_scheme(&self, name: &str) -> Result<bool, dbus::Error>;
@@ -692,6 +834,11 @@ mod config {
                 .map(|r: (Vec<String>,)| r.0)
         }
 
+        fn get_color_scheme(&self, name: &str) -> Result<Vec<u8>, dbus::Error> {
+            self.method_call("org.eruption.Config", "GetColorScheme", (name,))
+                .map(|r: (Vec<u8>,)| r.0)
+        }
+
         fn ping(&self) -> Result<bool, dbus::Error> {
             self.method_call("org.eruption.Config", "Ping", ())
                 .map(|r: (bool,)| r.0)
@@ -28,13 +28,16 @@ use std::env;
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::{process, sync::Arc};
 
+mod archive;
 mod color_scheme;
 mod constants;
 mod dbus_client;
 mod device;
 mod profiles;
 mod scripting;
+mod state_file;
 mod subcommands;
+mod swarm;
 mod translations;
 mod util;
 
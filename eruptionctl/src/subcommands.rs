@@ -19,6 +19,8 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+mod batch;
+mod canvas;
 mod color_schemes;
 mod completions;
 mod config;
@@ -31,6 +33,9 @@ mod rules;
 mod scripts;
 mod status;
 mod switch;
+mod zones;
+
+use std::path::PathBuf;
 
 use crate::translations::tr;
 
@@ -41,8 +46,12 @@ type Result<T> = std::result::Result<T, eyre::Error>;
 pub enum Subcommands {
     #[clap(display_order = 0, about(tr!("status-about")))]
     Status {
+        /// Update the status in place, each time the Eruption daemon reports a change
+        #[clap(short, long, global = true)]
+        watch: bool,
+
         #[clap(subcommand)]
-        command: status::StatusSubcommands,
+        command: Option<status::StatusSubcommands>,
     },
 
     #[clap(display_order = 1, about(tr!("switch-about")))]
@@ -106,13 +115,35 @@ pub enum Subcommands {
         command: rules::RulesSubcommands,
     },
 
-    #[clap(display_order = 11, hide = true, about(tr!("completions-about")))]
+    #[clap(display_order = 11, about(tr!("canvas-about")))]
+    Canvas {
+        #[clap(subcommand)]
+        command: canvas::CanvasSubcommands,
+    },
+
+    #[clap(display_order = 12, about(tr!("batch-about")))]
+    Batch {
+        /// A YAML or JSON file with a list of operations, use '-' to read from stdin
+        file: PathBuf,
+
+        /// Only validate the file, do not apply the operations
+        #[clap(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    #[clap(display_order = 13, about(tr!("zones-about")))]
+    Zones {
+        #[clap(subcommand)]
+        command: zones::ZonesSubcommands,
+    },
+
+    #[clap(display_order = 14, hide = true, about(tr!("completions-about")))]
     Completions { shell: clap_complete::Shell },
 }
 
 pub async fn handle_command(subcommand: Subcommands) -> Result<()> {
     match subcommand {
-        Subcommands::Status { command } => status::handle_command(command).await,
+        Subcommands::Status { watch, command } => status::handle_command(command, watch).await,
         Subcommands::Switch { command } => switch::handle_command(command).await,
         Subcommands::Config { command } => config::handle_command(command).await,
         Subcommands::Devices { command } => devices::handle_command(command).await,
@@ -127,6 +158,9 @@ pub async fn handle_command(subcommand: Subcommands) -> Result<()> {
         Subcommands::Names { command } => names::handle_command(command).await,
         Subcommands::Effects { command } => effects::handle_command(command).await,
         Subcommands::Rules { command } => rules::handle_command(command).await,
+        Subcommands::Canvas { command } => canvas::handle_command(command).await,
+        Subcommands::Batch { file, dry_run } => batch::handle_command(file, dry_run).await,
+        Subcommands::Zones { command } => zones::handle_command(command).await,
         Subcommands::Completions { shell } => completions::handle_command(shell).await,
     }
 }
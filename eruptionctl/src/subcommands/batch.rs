@@ -0,0 +1,194 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use color_eyre::Help;
+use colored::*;
+use eyre::Context;
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::dbus_client::dbus_system_bus;
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BatchError {
+    #[error("Invalid batch file: {description}")]
+    InvalidFile { description: String },
+
+    #[error("Invalid operation #{index}: {description}")]
+    InvalidOperation { index: usize, description: String },
+}
+
+/// The operations that are understood by the Eruption daemon, along with their required fields
+const OPERATIONS: &[(&str, &[&str])] = &[
+    ("switch-slot", &["slot"]),
+    ("set-parameter", &["script", "parameter", "value"]),
+    ("set-brightness", &["brightness"]),
+    ("apply-scheme", &["scheme"]),
+];
+
+pub async fn handle_command(file: PathBuf, dry_run: bool) -> Result<()> {
+    let data = if file == Path::new("-") {
+        let mut data = String::new();
+        io::stdin().read_to_string(&mut data)?;
+
+        data
+    } else {
+        fs::read_to_string(&file)
+            .wrap_err_with(|| format!("Could not read the file {}", file.display()))?
+    };
+
+    let operations = parse_operations(&data)?;
+    let num_operations = validate_operations(&operations)?;
+
+    if dry_run {
+        println!(
+            "The batch is valid: {} operations",
+            format!("{}", num_operations).bold()
+        );
+
+        return Ok(());
+    }
+
+    let applied = execute_batch(&operations.to_string())
+        .await
+        .wrap_err("Could not execute the batch")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    println!("Applied {} operations", format!("{}", applied).bold());
+
+    Ok(())
+}
+
+/// Parse a list of operations from JSON, or from YAML
+fn parse_operations(data: &str) -> Result<serde_json::Value> {
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(data) {
+        return Ok(value);
+    }
+
+    let documents = YamlLoader::load_from_str(data).map_err(|e| BatchError::InvalidFile {
+        description: format!("{}", e),
+    })?;
+
+    match documents.first() {
+        Some(document) => yaml_to_json(document),
+
+        None => Err(BatchError::InvalidFile {
+            description: "The file is empty".to_string(),
+        }
+        .into()),
+    }
+}
+
+fn yaml_to_json(yaml: &Yaml) -> Result<serde_json::Value> {
+    let invalid = |description: String| BatchError::InvalidFile { description };
+
+    let result = match yaml {
+        Yaml::Null => serde_json::Value::Null,
+        Yaml::Boolean(value) => serde_json::Value::Bool(*value),
+        Yaml::Integer(value) => serde_json::Value::from(*value),
+        Yaml::String(value) => serde_json::Value::String(value.clone()),
+
+        Yaml::Real(value) => value
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| invalid(format!("Invalid number: {}", value)))?,
+
+        Yaml::Array(values) => serde_json::Value::Array(
+            values
+                .iter()
+                .map(yaml_to_json)
+                .collect::<Result<Vec<_>>>()?,
+        ),
+
+        Yaml::Hash(entries) => {
+            let mut result = serde_json::Map::new();
+
+            for (key, value) in entries.iter() {
+                let key = match key {
+                    Yaml::String(key) => key.clone(),
+                    Yaml::Integer(key) => format!("{}", key),
+                    key => return Err(invalid(format!("Invalid key: {:?}", key)).into()),
+                };
+
+                result.insert(key, yaml_to_json(value)?);
+            }
+
+            serde_json::Value::Object(result)
+        }
+
+        value => return Err(invalid(format!("Unsupported value: {:?}", value)).into()),
+    };
+
+    Ok(result)
+}
+
+/// Check that all operations are known to the daemon, and that no required fields are
+/// missing, so that typos are reported before anything is applied. Returns the number
+/// of operations
+fn validate_operations(operations: &serde_json::Value) -> Result<usize> {
+    let operations = operations
+        .as_array()
+        .ok_or_else(|| BatchError::InvalidFile {
+            description: "Expected a list of operations".to_string(),
+        })?;
+
+    for (index, operation) in operations.iter().enumerate() {
+        let invalid = |description: String| BatchError::InvalidOperation {
+            index: index + 1,
+            description,
+        };
+
+        let op = operation
+            .get("op")
+            .and_then(|op| op.as_str())
+            .ok_or_else(|| invalid("The field 'op' is missing".to_string()))?;
+
+        let (_, required) = OPERATIONS
+            .iter()
+            .find(|(name, _)| *name == op)
+            .ok_or_else(|| invalid(format!("Unknown operation '{}'", op)))?;
+
+        for field in required.iter() {
+            if operation.get(field).is_none() {
+                return Err(invalid(format!("The field '{}' is missing", field)).into());
+            }
+        }
+    }
+
+    Ok(operations.len())
+}
+
+/// Execute a batch of operations in the daemon, returns the number of applied operations
+async fn execute_batch(operations: &str) -> Result<u64> {
+    let (result,): (u64,) = dbus_system_bus("/org/eruption/config")
+        .await?
+        .method_call("org.eruption.Config", "ExecuteBatch", (operations,))
+        .await?;
+
+    Ok(result)
+}
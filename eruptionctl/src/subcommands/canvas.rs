@@ -0,0 +1,229 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::time::Duration;
+
+use color_eyre::Help;
+use eruption_sdk::canvas::{Canvas, CANVAS_SIZE};
+use eruption_sdk::color::Color;
+use eruption_sdk::connection::{Connection, ConnectionType};
+use eyre::Context;
+
+use crate::color_scheme::ColorSchemeExt;
+use crate::{constants, dbus_client};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CanvasError {
+    #[error("Invalid key or zone: {description}")]
+    InvalidKey { description: String },
+
+    #[error("Invalid color: {description}")]
+    InvalidColor { description: String },
+
+    #[error("The color scheme does not contain any colors: {description}")]
+    EmptyColorScheme { description: String },
+}
+
+/// Sub-commands of the "canvas" command
+#[derive(Debug, clap::Parser)]
+pub enum CanvasSubcommands {
+    /// Paint a single key, or all keys of a zone
+    #[clap(display_order = 0)]
+    Set {
+        /// The index of the key, or the name of a zone like e.g. "wasd"
+        key: String,
+
+        /// A CSS color definition, like e.g. "red" or "#ff0000"
+        color: String,
+
+        /// Remove the overlay after n milliseconds, 0 keeps it until it is replaced
+        #[clap(short, long, default_value_t = constants::DEFAULT_OVERLAY_TIMEOUT_MILLIS)]
+        timeout: u64,
+    },
+
+    /// Paint all keys
+    #[clap(display_order = 1)]
+    Fill {
+        /// A CSS color definition, like e.g. "red" or "#ff0000"
+        color: String,
+
+        /// Remove the overlay after n milliseconds, 0 keeps it until it is replaced
+        #[clap(short, long, default_value_t = constants::DEFAULT_OVERLAY_TIMEOUT_MILLIS)]
+        timeout: u64,
+    },
+
+    /// Paint a gradient across all keys, using a named color scheme or a stock gradient
+    #[clap(display_order = 2)]
+    Gradient {
+        /// The name of the color scheme, like e.g. "rainbow-smooth"
+        scheme: String,
+
+        /// Remove the overlay after n milliseconds, 0 keeps it until it is replaced
+        #[clap(short, long, default_value_t = constants::DEFAULT_OVERLAY_TIMEOUT_MILLIS)]
+        timeout: u64,
+    },
+
+    /// Remove the current overlay
+    #[clap(display_order = 3)]
+    Clear,
+}
+
+pub async fn handle_command(command: CanvasSubcommands) -> Result<()> {
+    match command {
+        CanvasSubcommands::Set {
+            key,
+            color,
+            timeout,
+        } => set_command(key, color, timeout).await,
+        CanvasSubcommands::Fill { color, timeout } => fill_command(color, timeout).await,
+        CanvasSubcommands::Gradient { scheme, timeout } => gradient_command(scheme, timeout).await,
+        CanvasSubcommands::Clear => clear_command().await,
+    }
+}
+
+async fn set_command(key: String, color: String, timeout: u64) -> Result<()> {
+    let connection = connect()?;
+
+    let color = parse_color(&color)?;
+    let keys = resolve_keys(&connection, &key)?;
+
+    let mut canvas = Canvas::new();
+
+    for key in keys {
+        // key indices start at 1, like in Lua scripts
+        canvas[key - 1] = color.clone();
+    }
+
+    connection.submit_overlay(&canvas, Duration::from_millis(timeout))?;
+
+    Ok(())
+}
+
+async fn fill_command(color: String, timeout: u64) -> Result<()> {
+    let connection = connect()?;
+
+    let mut canvas = Canvas::new();
+    canvas.fill(parse_color(&color)?);
+
+    connection.submit_overlay(&canvas, Duration::from_millis(timeout))?;
+
+    Ok(())
+}
+
+async fn gradient_command(scheme: String, timeout: u64) -> Result<()> {
+    let color_scheme = dbus_client::get_color_scheme(&scheme)
+        .wrap_err("Could not query the color scheme")
+        .suggestion("Please run 'eruptionctl color-schemes list' to show all color schemes")?;
+
+    let num_colors = color_scheme.num_colors();
+
+    if num_colors == 0 {
+        return Err(CanvasError::EmptyColorScheme {
+            description: scheme,
+        }
+        .into());
+    }
+
+    let connection = connect()?;
+
+    let mut canvas = Canvas::new();
+
+    for index in 0..CANVAS_SIZE {
+        // position of the key on the gradient, the colors of the scheme are spaced evenly
+        let pos = index as f64 / (CANVAS_SIZE - 1) as f64 * (num_colors - 1) as f64;
+
+        let lower = color_scheme.color_rgba_at(pos.floor() as usize)?;
+        let upper = color_scheme.color_rgba_at((pos.ceil() as usize).min(num_colors - 1))?;
+
+        let (r, g, b, a) = lower
+            .interpolate_rgb(&upper, pos.fract())
+            .to_linear_rgba_u8();
+
+        canvas[index] = Color::new(r, g, b, a);
+    }
+
+    connection.submit_overlay(&canvas, Duration::from_millis(timeout))?;
+
+    Ok(())
+}
+
+async fn clear_command() -> Result<()> {
+    let connection = connect()?;
+
+    // a fully transparent overlay removes the current overlay
+    connection.submit_overlay(&Canvas::new(), Duration::ZERO)?;
+
+    Ok(())
+}
+
+/// Connect to the SDK support plugin of the Eruption daemon
+fn connect() -> Result<Connection> {
+    let connection = Connection::new(ConnectionType::Local)?;
+
+    connection
+        .connect()
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    Ok(connection)
+}
+
+/// Parse a CSS color definition
+fn parse_color(color: &str) -> Result<Color> {
+    let color = csscolorparser::parse(color).map_err(|e| CanvasError::InvalidColor {
+        description: format!("{color}: {e}"),
+    })?;
+
+    let (r, g, b, a) = color.to_linear_rgba_u8();
+
+    Ok(Color::new(r, g, b, a))
+}
+
+/// Returns the indices of the keys that are specified by `key`,
+/// either a single key index or the name of a zone
+fn resolve_keys(connection: &Connection, key: &str) -> Result<Vec<usize>> {
+    if let Ok(index) = key.parse::<usize>() {
+        if (1..=CANVAS_SIZE).contains(&index) {
+            Ok(vec![index])
+        } else {
+            Err(CanvasError::InvalidKey {
+                description: format!("Key index out of range: {index}"),
+            }
+            .into())
+        }
+    } else {
+        let zone = connection
+            .get_zones()?
+            .into_iter()
+            .find(|zone| zone.name == key)
+            .ok_or_else(|| CanvasError::InvalidKey {
+                description: format!("Unknown zone: {key}"),
+            })?;
+
+        Ok(zone
+            .keys
+            .into_iter()
+            .filter(|index| (1..=CANVAS_SIZE).contains(index))
+            .collect())
+    }
+}
@@ -49,6 +49,21 @@ pub enum ColorSchemesSubcommands {
         #[clap(subcommand)]
         command: ColorSchemeImportSubcommands,
     },
+
+    /// Apply a named color scheme to the gradient and color parameters of a profile
+    #[clap(display_order = 4)]
+    Apply {
+        /// The name of the color scheme
+        name: String,
+
+        /// The profile file, defaults to the currently active profile
+        #[clap(short, long)]
+        profile: Option<String>,
+
+        /// Save the changed parameters to the profile state
+        #[clap(required = false, short = 's', long, default_value = "false")]
+        persist: bool,
+    },
 }
 
 /// Sub-commands of the "colorscheme" command
@@ -71,6 +86,11 @@ pub async fn handle_command(command: ColorSchemesSubcommands) -> Result<()> {
         ColorSchemesSubcommands::Add { name, colors } => add_command(name, colors).await,
         ColorSchemesSubcommands::Remove { name } => remove_command(name).await,
         ColorSchemesSubcommands::Import { command } => import_command(command).await,
+        ColorSchemesSubcommands::Apply {
+            name,
+            profile,
+            persist,
+        } => apply_command(name, profile, persist).await,
     }
 }
 
@@ -122,6 +142,23 @@ async fn remove_command(name: String) -> Result<()> {
     Ok(())
 }
 
+async fn apply_command(name: String, profile: Option<String>, persist: bool) -> Result<()> {
+    let profile_file = match profile {
+        Some(profile) => profile,
+        None => dbus_client::get_active_profile()?,
+    };
+
+    println!(
+        "Applying color scheme {} to profile: {}",
+        name.bold(),
+        profile_file.bold()
+    );
+
+    dbus_client::apply_named_color_scheme(&profile_file, &name, persist)?;
+
+    Ok(())
+}
+
 async fn import_command(command: ColorSchemeImportSubcommands) -> Result<()> {
     match command {
         ColorSchemeImportSubcommands::Pywal {
@@ -23,7 +23,9 @@ use color_eyre::Help;
 use colored::*;
 use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
 use eyre::Context;
+use std::path::PathBuf;
 
+use crate::archive::{ConfigArchive, Section};
 use crate::dbus_client::dbus_system_bus;
 
 type Result<T> = std::result::Result<T, eyre::Error>;
@@ -38,12 +40,80 @@ pub enum ConfigSubcommands {
     /// Get or set the state of SoundFX
     #[clap(display_order = 1)]
     Soundfx { enable: Option<bool> },
+
+    /// Get or set the volume of SoundFX in percent
+    #[clap(display_order = 2)]
+    SoundfxVolume { volume: Option<i64> },
+
+    /// Get or set the state of game mode, inhibits the keys configured in the active profile
+    #[clap(display_order = 3)]
+    GameMode { enable: Option<bool> },
+
+    /// Get or set the state of hardware mode, suspends streaming and returns the devices to their firmware effects
+    #[clap(display_order = 4)]
+    HardwareMode { enable: Option<bool> },
+
+    /// Get or set the power saving mode, one of "auto", "on" or "off"
+    #[clap(display_order = 5)]
+    PowerSaving { mode: Option<String> },
+
+    /// Get or set the source of the audio visualization, either "output" or "microphone"
+    #[clap(display_order = 6)]
+    AudioSource { source: Option<String> },
+
+    /// Get or set the threshold of the noise gate of the microphone input in dBFS
+    #[clap(display_order = 7)]
+    NoiseGate {
+        #[clap(allow_hyphen_values = true)]
+        threshold: Option<i64>,
+    },
+
+    /// Stop all device I/O and release the devices to other software, the profiles keep running
+    #[clap(display_order = 8)]
+    Standby,
+
+    /// Re-open the devices after standby
+    #[clap(display_order = 9)]
+    Resume,
+
+    /// Get the state of AFK mode and the time until the AFK timeout expires, or enter/leave AFK mode
+    #[clap(display_order = 10)]
+    Afk { enable: Option<bool> },
+
+    /// Export eruption.conf, the slot assignments, device state, color schemes and process-monitor rules to an archive
+    #[clap(display_order = 11)]
+    Export { file: PathBuf },
+
+    /// Restore the configuration from an archive, the Eruption daemon has to be stopped
+    #[clap(display_order = 12)]
+    Import {
+        file: PathBuf,
+
+        /// Only restore these sections: config, slots, devices, color-schemes or rules
+        #[clap(long, value_delimiter = ',')]
+        only: Vec<String>,
+
+        /// Restore archives that have been created by a different release of Eruption
+        #[clap(short, long)]
+        force: bool,
+    },
 }
 
 pub async fn handle_command(command: ConfigSubcommands) -> Result<()> {
     match command {
         ConfigSubcommands::Brightness { brightness } => brightness_command(brightness).await,
         ConfigSubcommands::Soundfx { enable } => sound_fx_command(enable).await,
+        ConfigSubcommands::SoundfxVolume { volume } => sound_fx_volume_command(volume).await,
+        ConfigSubcommands::GameMode { enable } => game_mode_command(enable).await,
+        ConfigSubcommands::HardwareMode { enable } => hardware_mode_command(enable).await,
+        ConfigSubcommands::PowerSaving { mode } => power_saving_command(mode).await,
+        ConfigSubcommands::AudioSource { source } => audio_source_command(source).await,
+        ConfigSubcommands::NoiseGate { threshold } => noise_gate_command(threshold).await,
+        ConfigSubcommands::Standby => standby_command(true).await,
+        ConfigSubcommands::Resume => standby_command(false).await,
+        ConfigSubcommands::Afk { enable } => afk_command(enable).await,
+        ConfigSubcommands::Export { file } => export_command(file).await,
+        ConfigSubcommands::Import { file, only, force } => import_command(file, only, force).await,
     }
 }
 
@@ -87,6 +157,259 @@ async fn sound_fx_command(enable: Option<bool>) -> Result<()> {
     Ok(())
 }
 
+async fn sound_fx_volume_command(volume: Option<i64>) -> Result<()> {
+    if let Some(volume) = volume {
+        set_sound_fx_volume(volume)
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+    } else {
+        let result = get_sound_fx_volume()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+        println!(
+            "{}",
+            format!("SoundFX volume: {}", format!("{}%", result).bold())
+        );
+    }
+
+    Ok(())
+}
+
+async fn game_mode_command(enable: Option<bool>) -> Result<()> {
+    if let Some(enable) = enable {
+        set_game_mode(enable)
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+    } else {
+        let result = get_game_mode()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+        println!(
+            "{}",
+            format!("Game mode enabled: {}", format!("{}", result).bold())
+        );
+    }
+
+    Ok(())
+}
+
+async fn hardware_mode_command(enable: Option<bool>) -> Result<()> {
+    if let Some(enable) = enable {
+        set_hardware_mode(enable)
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+    } else {
+        let result = get_hardware_mode()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+        println!(
+            "{}",
+            format!("Hardware mode enabled: {}", format!("{}", result).bold())
+        );
+    }
+
+    Ok(())
+}
+
+async fn standby_command(enable: bool) -> Result<()> {
+    let changed = set_standby(enable)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    match (enable, changed) {
+        (true, true) => println!("Entering standby, all devices have been released"),
+        (true, false) => println!("Already in standby"),
+        (false, true) => println!("Resuming from standby"),
+        (false, false) => println!("Not in standby"),
+    }
+
+    Ok(())
+}
+
+async fn afk_command(enable: Option<bool>) -> Result<()> {
+    if let Some(enable) = enable {
+        let changed = set_afk(enable)
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+
+        match (enable, changed) {
+            (true, true) => println!("Entering AFK mode, it is left with the next input"),
+            (true, false) => println!("Already in AFK mode"),
+            (false, true) => println!("Leaving AFK mode"),
+            (false, false) => println!("Not in AFK mode"),
+        }
+    } else {
+        let (afk, timeout, remaining) = get_afk()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+
+        println!("{}", format!("AFK: {}", format!("{}", afk).bold()));
+
+        if timeout > 0 {
+            println!(
+                "{}",
+                format!("AFK timeout: {}", format!("{} seconds", timeout).bold())
+            );
+
+            if !afk {
+                println!(
+                    "{}",
+                    format!(
+                        "Time remaining: {}",
+                        format!("{} seconds", remaining).bold()
+                    )
+                );
+            }
+        } else {
+            println!("AFK timeout: {}", "disabled".bold());
+        }
+    }
+
+    Ok(())
+}
+
+async fn export_command(file: PathBuf) -> Result<()> {
+    let archive = ConfigArchive::collect()
+        .wrap_err("Could not read the configuration")
+        .suggestion("Please run this command as root, to be able to read all state files")?;
+
+    archive
+        .save(&file)
+        .wrap_err_with(|| format!("Could not write the archive {}", file.display()))?;
+
+    for entry in archive.files.iter() {
+        println!("{}: {}", entry.section, entry.path.display());
+    }
+
+    println!(
+        "Exported {} files to {}",
+        archive.files.len(),
+        file.display().to_string().bold()
+    );
+
+    Ok(())
+}
+
+async fn import_command(file: PathBuf, only: Vec<String>, force: bool) -> Result<()> {
+    let archive = ConfigArchive::load(&file)
+        .wrap_err_with(|| format!("Could not read the archive {}", file.display()))?;
+
+    let sections = only
+        .iter()
+        .map(|section| section.parse::<Section>())
+        .collect::<Result<Vec<_>>>()
+        .suggestion("Valid sections are: config, slots, devices, color-schemes and rules")?;
+
+    if archive.is_foreign_version() && !force {
+        return Err(eyre::eyre!(
+            "The archive has been created by Eruption {}, this is Eruption {}",
+            archive.eruption_version,
+            env!("CARGO_PKG_VERSION")
+        ))
+        .suggestion(
+            "Use --force to restore it anyway, or restore only some of the sections with --only",
+        );
+    }
+
+    let entries = archive.entries(&sections).collect::<Vec<_>>();
+
+    if entries.is_empty() {
+        println!("The archive contains none of the selected sections");
+
+        return Ok(());
+    }
+
+    // the daemon saves its state on shutdown, so it would overwrite the restored files
+    if entries.iter().any(|entry| entry.section.is_system_wide()) && is_daemon_running().await {
+        return Err(eyre::eyre!("The Eruption daemon is running")).suggestion(
+            "Please stop the daemon first with 'sudo systemctl stop eruption.service', or restore only the rules with --only rules",
+        );
+    }
+
+    for entry in entries.iter() {
+        let path = ConfigArchive::restore(entry)
+            .wrap_err_with(|| format!("Could not restore {}", entry.path.display()))
+            .suggestion("System wide files may only be restored by root")?;
+
+        println!("{}: {}", entry.section, path.display());
+    }
+
+    println!("Restored {} files", entries.len());
+
+    if entries.iter().any(|entry| entry.section == Section::Rules) {
+        println!("Please restart the process monitor, to load the restored rules");
+    }
+
+    Ok(())
+}
+
+async fn power_saving_command(mode: Option<String>) -> Result<()> {
+    if let Some(mode) = mode {
+        set_power_saving(mode)
+            .await
+            .wrap_err("Could not set the power saving mode")
+            .suggestion("Please specify one of 'auto', 'on' or 'off'")?;
+    } else {
+        let (mode, source) = get_power_saving()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+        println!("{}", format!("Power saving mode: {}", mode.bold()));
+        println!("{}", format!("Power source: {}", source.bold()));
+    }
+
+    Ok(())
+}
+
+async fn audio_source_command(source: Option<String>) -> Result<()> {
+    if let Some(source) = source {
+        set_audio_source(source)
+            .await
+            .wrap_err("Could not set the audio source")
+            .suggestion("Please specify either 'output' or 'microphone'")?;
+    } else {
+        let result = get_audio_source()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+        println!("{}", format!("Audio source: {}", result.bold()));
+    }
+
+    Ok(())
+}
+
+async fn noise_gate_command(threshold: Option<i64>) -> Result<()> {
+    if let Some(threshold) = threshold {
+        set_noise_gate_threshold(threshold)
+            .await
+            .wrap_err("Could not set the threshold of the noise gate")
+            .suggestion("Please specify a value between -96 and 0 dBFS")?;
+    } else {
+        let result = get_noise_gate_threshold()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+        println!(
+            "{}",
+            format!(
+                "Noise gate threshold: {}",
+                format!("{} dBFS", result).bold()
+            )
+        );
+    }
+
+    Ok(())
+}
+
 /// Get the current brightness value
 async fn get_brightness() -> Result<i64> {
     let result = dbus_system_bus("/org/eruption/config")
@@ -130,3 +453,181 @@ async fn set_sound_fx(enabled: bool) -> Result<()> {
 
     Ok(())
 }
+
+/// Get the current volume of SoundFX
+async fn get_sound_fx_volume() -> Result<i64> {
+    let result = dbus_system_bus("/org/eruption/config")
+        .await?
+        .get("org.eruption.Config", "SfxVolume")
+        .await?;
+
+    Ok(result)
+}
+
+/// Set the volume of SoundFX to `volume` percent
+async fn set_sound_fx_volume(volume: i64) -> Result<()> {
+    let arg = Box::new(volume);
+
+    dbus_system_bus("/org/eruption/config")
+        .await?
+        .set("org.eruption.Config", "SfxVolume", arg)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns true when game mode is enabled
+async fn get_game_mode() -> Result<bool> {
+    let result = dbus_system_bus("/org/eruption/config")
+        .await?
+        .get("org.eruption.Config", "GameMode")
+        .await?;
+
+    Ok(result)
+}
+
+/// Set game mode state to `enabled`
+async fn set_game_mode(enabled: bool) -> Result<()> {
+    let arg = Box::new(enabled);
+
+    dbus_system_bus("/org/eruption/config")
+        .await?
+        .set("org.eruption.Config", "GameMode", arg)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns true when streaming is suspended
+async fn get_hardware_mode() -> Result<bool> {
+    let result = dbus_system_bus("/org/eruption/config")
+        .await?
+        .get("org.eruption.Config", "HardwareMode")
+        .await?;
+
+    Ok(result)
+}
+
+/// Set hardware mode state to `enabled`
+async fn set_hardware_mode(enabled: bool) -> Result<()> {
+    let arg = Box::new(enabled);
+
+    dbus_system_bus("/org/eruption/config")
+        .await?
+        .set("org.eruption.Config", "HardwareMode", arg)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns `true` if the Eruption daemon replies on the D-Bus
+async fn is_daemon_running() -> bool {
+    let reply: Result<(bool,)> = async {
+        Ok(dbus_system_bus("/org/eruption/config")
+            .await?
+            .method_call("org.eruption.Config", "Ping", ())
+            .await?)
+    }
+    .await;
+
+    reply.is_ok()
+}
+
+/// Enter or leave standby, returns false if the daemon already is in the requested state
+async fn set_standby(enable: bool) -> Result<bool> {
+    let method = if enable { "Standby" } else { "Resume" };
+
+    let (result,): (bool,) = dbus_system_bus("/org/eruption/config")
+        .await?
+        .method_call("org.eruption.Config", method, ())
+        .await?;
+
+    Ok(result)
+}
+
+/// Returns the AFK state, the AFK timeout and the number of seconds until it expires
+async fn get_afk() -> Result<(bool, u64, u64)> {
+    let proxy = dbus_system_bus("/org/eruption/config").await?;
+
+    let afk = proxy.get("org.eruption.Config", "Afk").await?;
+    let timeout = proxy.get("org.eruption.Config", "AfkTimeout").await?;
+    let remaining = proxy.get("org.eruption.Config", "AfkTimeRemaining").await?;
+
+    Ok((afk, timeout, remaining))
+}
+
+/// Enter or leave AFK mode, returns false if AFK mode already is in the requested state
+async fn set_afk(enable: bool) -> Result<bool> {
+    let (result,): (bool,) = dbus_system_bus("/org/eruption/config")
+        .await?
+        .method_call("org.eruption.Config", "SetAfk", (enable,))
+        .await?;
+
+    Ok(result)
+}
+
+/// Returns the power saving mode and the current power source
+async fn get_power_saving() -> Result<(String, String)> {
+    let proxy = dbus_system_bus("/org/eruption/config").await?;
+
+    let mode = proxy.get("org.eruption.Config", "PowerSaving").await?;
+    let source = proxy.get("org.eruption.Config", "PowerSource").await?;
+
+    Ok((mode, source))
+}
+
+/// Set the power saving mode to `mode`
+async fn set_power_saving(mode: String) -> Result<()> {
+    let arg = Box::new(mode);
+
+    dbus_system_bus("/org/eruption/config")
+        .await?
+        .set("org.eruption.Config", "PowerSaving", arg)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the source of the audio visualization
+async fn get_audio_source() -> Result<String> {
+    let result = dbus_system_bus("/org/eruption/config")
+        .await?
+        .get("org.eruption.Config", "AudioSource")
+        .await?;
+
+    Ok(result)
+}
+
+/// Set the source of the audio visualization to `source`
+async fn set_audio_source(source: String) -> Result<()> {
+    let arg = Box::new(source);
+
+    dbus_system_bus("/org/eruption/config")
+        .await?
+        .set("org.eruption.Config", "AudioSource", arg)
+        .await?;
+
+    Ok(())
+}
+
+/// Get the threshold of the noise gate in dBFS
+async fn get_noise_gate_threshold() -> Result<i64> {
+    let result = dbus_system_bus("/org/eruption/config")
+        .await?
+        .get("org.eruption.Config", "NoiseGateThreshold")
+        .await?;
+
+    Ok(result)
+}
+
+/// Set the threshold of the noise gate to `threshold` dBFS
+async fn set_noise_gate_threshold(threshold: i64) -> Result<()> {
+    let arg = Box::new(threshold);
+
+    dbus_system_bus("/org/eruption/config")
+        .await?
+        .set("org.eruption.Config", "NoiseGateThreshold", arg)
+        .await?;
+
+    Ok(())
+}
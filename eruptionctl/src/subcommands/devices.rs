@@ -20,8 +20,9 @@
 */
 
 use std::collections::HashMap;
+use std::io;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use color_eyre::Help;
 use colored::*;
@@ -31,7 +32,7 @@ use comfy_table::{Cell, CellAlignment, ContentArrangement, Table};
 use eyre::Context;
 use std::sync::atomic::Ordering;
 
-use crate::dbus_client::dbus_system_bus;
+use crate::dbus_client::{self, dbus_system_bus};
 use crate::device;
 use crate::tr;
 
@@ -50,7 +51,13 @@ pub enum DevicesSubcommands {
 
     /// Get status of a specific device
     #[clap(display_order = 2)]
-    Status { device: String },
+    Status {
+        device: String,
+
+        /// Update the status in place, each time the Eruption daemon reports a change
+        #[clap(short, long)]
+        watch: bool,
+    },
 
     /// Get or set the device specific brightness of the LEDs
     #[clap(display_order = 3)]
@@ -70,7 +77,7 @@ pub enum DevicesSubcommands {
     #[clap(display_order = 5)]
     Dpi { device: String, dpi: Option<i32> },
 
-    /// Get or set the bus poll rate
+    /// Get or set the USB polling rate in Hz (125, 250, 500 or 1000)
     #[clap(display_order = 6)]
     Rate { device: String, rate: Option<i32> },
 
@@ -81,7 +88,8 @@ pub enum DevicesSubcommands {
         enable: Option<bool>,
     },
 
-    /// Get or set the DCU parameter (applicable for some mice)
+    /// Get or set the lift-off distance (DCU) of the sensor, from 0 (lowest) to 3 (highest)
+    /// (applicable for some mice)
     #[clap(display_order = 8)]
     Distance { device: String, param: Option<i32> },
 
@@ -91,13 +99,113 @@ pub enum DevicesSubcommands {
         device: String,
         enable: Option<bool>,
     },
+
+    /// Run a self-test and hardware diagnostics of a specific device
+    #[clap(display_order = 10)]
+    SelfTest { device: String },
+
+    /// Show failure statistics of all devices, like disconnects or failed writes
+    #[clap(display_order = 11)]
+    Health {
+        /// Reset the failure statistics of all devices, and release them from quarantine
+        #[clap(short, long)]
+        reset: bool,
+    },
+
+    /// Get or set the canvas transform, e.g. for keyboards that are mounted vertically
+    #[clap(display_order = 12)]
+    Transform {
+        device: String,
+
+        /// Like e.g. "rotate=90,mirror-x,offset=2:0,scale=1.5", or "none" to reset it
+        transform: Option<String>,
+    },
+
+    /// List or set the LEDs of a device that are not part of the canvas, like e.g. a logo LED
+    #[clap(display_order = 13)]
+    AuxLeds {
+        device: String,
+
+        /// The name of the LED to set
+        name: Option<String>,
+
+        /// The color of the LED, like e.g. "#ff0000"
+        color: Option<String>,
+    },
+
+    /// Switch the LED ring of a headset on or off
+    #[clap(display_order = 14)]
+    LedRing {
+        device: String,
+        enable: Option<bool>,
+    },
+
+    /// Get or set whether Eruption grabs the input device exclusively, release it to hand it back to the OS
+    #[clap(display_order = 15)]
+    Grab {
+        device: String,
+        enable: Option<bool>,
+    },
+
+    /// Get or set the key debounce filter and the key repeat rate of a keyboard
+    #[clap(display_order = 16)]
+    KeyFilter {
+        device: String,
+
+        /// Like e.g. "debounce=8,repeat=300:30", or "none" to reset it
+        filter: Option<String>,
+
+        /// Switch the key filter on, without changing its settings
+        #[clap(long)]
+        enable: bool,
+
+        /// Switch the key filter off, without changing its settings
+        #[clap(long, conflicts_with = "enable")]
+        disable: bool,
+    },
+
+    /// Get or set the brightness curve of a device, that maps the brightness to the perceived brightness
+    #[clap(display_order = 17)]
+    BrightnessCurve {
+        device: String,
+
+        /// One of "cie", "linear" or "gamma=<exponent>", or "default" to use the global curve
+        curve: Option<String>,
+    },
+
+    /// Show the arrangement of the keyboards on the shared canvas, or stretch the canvas across all keyboards
+    #[clap(display_order = 18)]
+    Layout {
+        /// Stretch the canvas across all keyboards, or show the whole canvas on each keyboard
+        tiling: Option<bool>,
+
+        /// Forget all placements, and line up the keyboards from left to right
+        #[clap(short, long)]
+        reset: bool,
+    },
+
+    /// Move a keyboard to a position on the shared canvas, in keys
+    #[clap(display_order = 19)]
+    Place {
+        device: String,
+
+        #[clap(allow_hyphen_values = true)]
+        x: i32,
+
+        #[clap(allow_hyphen_values = true)]
+        y: i32,
+    },
+
+    /// Calibrate the sensor of a mouse to the surface it is used on (applicable for some mice)
+    #[clap(display_order = 20)]
+    Calibrate { device: String },
 }
 
 pub async fn handle_command(command: DevicesSubcommands) -> Result<()> {
     match command {
         DevicesSubcommands::List => list_command().await,
         DevicesSubcommands::Info { device } => info_command(device).await,
-        DevicesSubcommands::Status { device } => status_command(device).await,
+        DevicesSubcommands::Status { device, watch } => status_command(device, watch).await,
         DevicesSubcommands::Profile { device, profile } => profile_command(device, profile).await,
         DevicesSubcommands::Dpi { device, dpi } => dpi_command(device, dpi).await,
         DevicesSubcommands::Rate { device, rate } => rate_command(device, rate).await,
@@ -109,6 +217,30 @@ pub async fn handle_command(command: DevicesSubcommands) -> Result<()> {
         DevicesSubcommands::Brightness { device, brightness } => {
             brightness_command(device, brightness).await
         }
+        DevicesSubcommands::SelfTest { device } => self_test_command(device).await,
+        DevicesSubcommands::Health { reset } => health_command(reset).await,
+        DevicesSubcommands::Transform { device, transform } => {
+            transform_command(device, transform).await
+        }
+        DevicesSubcommands::AuxLeds {
+            device,
+            name,
+            color,
+        } => aux_leds_command(device, name, color).await,
+        DevicesSubcommands::LedRing { device, enable } => led_ring_command(device, enable).await,
+        DevicesSubcommands::Grab { device, enable } => grab_command(device, enable).await,
+        DevicesSubcommands::KeyFilter {
+            device,
+            filter,
+            enable,
+            disable,
+        } => key_filter_command(device, filter, enable, disable).await,
+        DevicesSubcommands::BrightnessCurve { device, curve } => {
+            brightness_curve_command(device, curve).await
+        }
+        DevicesSubcommands::Layout { tiling, reset } => layout_command(tiling, reset).await,
+        DevicesSubcommands::Place { device, x, y } => place_command(device, x, y).await,
+        DevicesSubcommands::Calibrate { device } => calibrate_command(device).await,
     }
 }
 
@@ -121,11 +253,14 @@ async fn list_command() -> Result<()> {
         .wrap_err("Could not connect to the Eruption daemon")
         .suggestion("Please verify that the Eruption daemon is running")?;
 
+    let ids = get_device_ids().await.unwrap_or_default();
+
     if verbose > 0 {
         println!(
             "
 Use the `eruptionctl devices list` sub-command to find out the index of the device that
-you want to operate on. All the other device-related commands require a device index.
+you want to operate on. All the other device-related commands require a device index,
+or the stable ID of the device, that does not change when devices are plugged or unplugged.
 
 Examples:
 
@@ -152,7 +287,7 @@ $ eruptionctl devices dpi 1
         for (_index, dev) in keyboards.iter().enumerate() {
             if verbose > 0 {
                 println!(
-                    "Index: {}: ID: {}:{} {} {}",
+                    "Index: {}: ID: {}:{} {} {} ({})",
                     format!("{:02}", base_index).bold(),
                     format!("{:04x}", dev.0),
                     format!("{:04x}", dev.1),
@@ -161,7 +296,10 @@ $ eruptionctl devices dpi 1
                         .bold(),
                     device::get_device_model(dev.0, dev.1)
                         .unwrap_or("<unknown model>")
-                        .bold()
+                        .bold(),
+                    ids.get(base_index)
+                        .map(|id| id.as_str())
+                        .unwrap_or("<unknown>")
                 );
             } else {
                 println!(
@@ -188,7 +326,7 @@ $ eruptionctl devices dpi 1
         for (_index, dev) in mice.iter().enumerate() {
             if verbose > 0 {
                 println!(
-                    "Index: {}: ID: {}:{} {} {}",
+                    "Index: {}: ID: {}:{} {} {} ({})",
                     format!("{:02}", base_index).bold(),
                     format!("{:04x}", dev.0),
                     format!("{:04x}", dev.1),
@@ -197,7 +335,10 @@ $ eruptionctl devices dpi 1
                         .bold(),
                     device::get_device_model(dev.0, dev.1)
                         .unwrap_or("<unknown model>")
-                        .bold()
+                        .bold(),
+                    ids.get(base_index)
+                        .map(|id| id.as_str())
+                        .unwrap_or("<unknown>")
                 );
             } else {
                 println!(
@@ -224,7 +365,7 @@ $ eruptionctl devices dpi 1
         for (_index, dev) in misc.iter().enumerate() {
             if verbose > 0 {
                 println!(
-                    "Index: {}: ID: {}:{} {} {}",
+                    "Index: {}: ID: {}:{} {} {} ({})",
                     format!("{:02}", base_index).bold(),
                     format!("{:04x}", dev.0),
                     format!("{:04x}", dev.1),
@@ -233,7 +374,10 @@ $ eruptionctl devices dpi 1
                         .bold(),
                     device::get_device_model(dev.0, dev.1)
                         .unwrap_or("<unknown model>")
-                        .bold()
+                        .bold(),
+                    ids.get(base_index)
+                        .map(|id| id.as_str())
+                        .unwrap_or("<unknown>")
                 );
             } else {
                 println!(
@@ -256,7 +400,7 @@ $ eruptionctl devices dpi 1
 }
 
 async fn info_command(device: String) -> Result<()> {
-    let device = device.parse::<u64>()?;
+    let device = resolve_device(&device).await?;
 
     print_device_header(device)
         .await
@@ -270,14 +414,214 @@ async fn info_command(device: String) -> Result<()> {
     Ok(())
 }
 
-async fn status_command(device: String) -> Result<()> {
-    let device = device.parse::<u64>()?;
+async fn self_test_command(device: String) -> Result<()> {
+    let device = resolve_device(&device).await?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    println!("Running self-test, the LEDs of the device will cycle through the RGB primaries...");
+
+    let report = run_self_test(device).await?;
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Test", "Result"]);
+
+    let hid_roundtrip = match report.hid_roundtrip_ok {
+        Some(true) => "passed".green().to_string(),
+        Some(false) => "FAILED".red().bold().to_string(),
+        None => "not supported by driver".italic().to_string(),
+    };
+
+    let failed_writes = if report.failed_writes.is_empty() {
+        "none".green().to_string()
+    } else {
+        report
+            .failed_writes
+            .iter()
+            .map(|index| index.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+            .red()
+            .bold()
+            .to_string()
+    };
+
+    table.add_row(vec![
+        Cell::new("HID report round-trip"),
+        Cell::new(hid_roundtrip),
+    ]);
+    table.add_row(vec![
+        Cell::new("LED map writes"),
+        Cell::new(report.writes.to_string()),
+    ]);
+    table.add_row(vec![
+        Cell::new("Write latency (avg)"),
+        Cell::new(format!("{} µs", report.write_latency_avg_micros)),
+    ]);
+    table.add_row(vec![
+        Cell::new("Write latency (max)"),
+        Cell::new(format!("{} µs", report.write_latency_max_micros)),
+    ]);
+    table.add_row(vec![
+        Cell::new("Failed writes (LED indices)"),
+        Cell::new(failed_writes),
+    ]);
+
+    println!("{}", table);
+
+    for error in report.errors.iter() {
+        eprintln!("{} {}", "Error:".red().bold(), error);
+    }
+
+    Ok(())
+}
+
+async fn health_command(reset: bool) -> Result<()> {
+    if reset {
+        reset_device_health()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+
+        println!("Failure statistics of all devices have been reset, and all devices have been released from quarantine");
+
+        return Ok(());
+    }
+
+    let devices = get_device_health()
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    // devices that could not be initialized at startup, or only after retrying
+    for status in get_device_init_status().await?.iter() {
+        match &status.status {
+            Some(init) if init.state == "failed" => eprintln!(
+                "{} Device {} ({}) could not be initialized after {} attempts: {}",
+                "Error:".red().bold(),
+                status.index,
+                status.device,
+                init.attempts,
+                init.last_error.as_deref().unwrap_or("unknown error")
+            ),
+
+            Some(init) if init.attempts > 1 => println!(
+                "Device {} ({}) has been initialized after {} attempts",
+                status.index, status.device, init.attempts
+            ),
+
+            _ => (),
+        }
+    }
+
+    if devices.is_empty() {
+        println!("No failures have been recorded so far");
+
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            "Device",
+            "Index",
+            "Disconnects",
+            "Failed writes",
+            "Re-inits",
+            "Failed inits",
+            "Last failure",
+            "Quarantine",
+        ]);
+
+    for status in devices.iter() {
+        let index = match status.index {
+            Some(index) => index.to_string(),
+            None => "not connected".italic().to_string(),
+        };
+
+        let last_failure = match status.health.last_failure {
+            Some(t) => format!("{} seconds ago", now.saturating_sub(t)),
+            None => "never".to_string(),
+        };
+
+        let quarantine = match &status.quarantine {
+            Some(quarantine) if quarantine.retry_in_secs == 0 => "retrying".to_string(),
+            Some(quarantine) => format!("retry in {} seconds", quarantine.retry_in_secs),
+            None => "-".to_string(),
+        };
+
+        let device = if status.quarantine.is_some() {
+            status.device.red().bold().to_string()
+        } else if status.warnings.is_empty() {
+            status.device.clone()
+        } else {
+            status.device.yellow().bold().to_string()
+        };
+
+        table.add_row(vec![
+            Cell::new(device),
+            Cell::new(index).set_alignment(CellAlignment::Right),
+            Cell::new(status.health.disconnects).set_alignment(CellAlignment::Right),
+            Cell::new(status.health.failed_writes).set_alignment(CellAlignment::Right),
+            Cell::new(status.health.reinit_attempts).set_alignment(CellAlignment::Right),
+            Cell::new(status.health.failed_inits).set_alignment(CellAlignment::Right),
+            Cell::new(last_failure),
+            Cell::new(quarantine),
+        ]);
+    }
+
+    println!("{}", table);
+
+    for status in devices.iter() {
+        for warning in status.warnings.iter() {
+            eprintln!(
+                "{} {}: {}",
+                "Warning:".yellow().bold(),
+                status.device,
+                warning
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn status_command(device: String, watch: bool) -> Result<()> {
+    let device = resolve_device(&device).await?;
 
     print_device_header(device)
         .await
         .wrap_err("Could not connect to the Eruption daemon")
         .suggestion("Please verify that the Eruption daemon is running")?;
 
+    let mut subscription = if watch {
+        Some(
+            dbus_client::subscribe_system_signals(&[(
+                "org.eruption.Device",
+                "DeviceStatusChanged",
+            )])
+            .await
+            .wrap_err("Could not subscribe to the signals of the Eruption daemon")?,
+        )
+    } else {
+        None
+    };
+
     let term = console::Term::stdout();
 
     // stores how many lines we printed in the previous iteration
@@ -319,18 +663,22 @@ async fn status_command(device: String) -> Result<()> {
 
         println!("{}", table);
 
-        if !crate::REPEAT.load(Ordering::SeqCst) || crate::QUIT.load(Ordering::SeqCst) {
+        if let Some(subscription) = subscription.as_mut() {
+            if !subscription.next().await {
+                break;
+            }
+        } else if !crate::REPEAT.load(Ordering::SeqCst) || crate::QUIT.load(Ordering::SeqCst) {
             break;
+        } else {
+            thread::sleep(Duration::from_millis(250));
         }
-
-        thread::sleep(Duration::from_millis(250));
     }
 
     Ok(())
 }
 
 async fn profile_command(device: String, profile: Option<i32>) -> Result<()> {
-    let device = device.parse::<u64>()?;
+    let device = resolve_device(&device).await?;
 
     print_device_header(device)
         .await
@@ -351,7 +699,7 @@ async fn profile_command(device: String, profile: Option<i32>) -> Result<()> {
 }
 
 async fn dpi_command(device: String, dpi: Option<i32>) -> Result<()> {
-    let device = device.parse::<u64>()?;
+    let device = resolve_device(&device).await?;
 
     print_device_header(device)
         .await
@@ -372,7 +720,7 @@ async fn dpi_command(device: String, dpi: Option<i32>) -> Result<()> {
 }
 
 async fn rate_command(device: String, rate: Option<i32>) -> Result<()> {
-    let device = device.parse::<u64>()?;
+    let device = resolve_device(&device).await?;
 
     print_device_header(device)
         .await
@@ -386,14 +734,14 @@ async fn rate_command(device: String, rate: Option<i32>) -> Result<()> {
     } else {
         let result = get_device_config(device, "rate").await?;
 
-        println!("{}", format!("Poll rate: {}", result.bold()));
+        println!("{}", format!("Poll rate: {} Hz", result.bold()));
     }
 
     Ok(())
 }
 
 async fn distance_command(device: String, param: Option<i32>) -> Result<()> {
-    let device = device.parse::<u64>()?;
+    let device = resolve_device(&device).await?;
 
     print_device_header(device)
         .await
@@ -407,14 +755,47 @@ async fn distance_command(device: String, param: Option<i32>) -> Result<()> {
     } else {
         let result = get_device_config(device, "dcu").await?;
 
-        println!("{}", format!("DCU config: {}", result.bold()));
+        println!("{}", format!("Lift-off distance (DCU): {}", result.bold()));
+    }
+
+    Ok(())
+}
+
+async fn calibrate_command(device: String) -> Result<()> {
+    let device = resolve_device(&device).await?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    set_device_config(device, "surface-calibration", "start")
+        .await
+        .wrap_err("Could not start the surface calibration")?;
+
+    println!("Move the mouse across the whole surface, then press Enter to apply the calibration,");
+    println!("or enter 'c' to cancel it");
+
+    let mut input = String::new();
+    let cancel = io::stdin().read_line(&mut input).map_or(true, |len| {
+        len == 0 || input.trim().eq_ignore_ascii_case("c")
+    });
+
+    if cancel {
+        set_device_config(device, "surface-calibration", "cancel").await?;
+
+        println!("The surface calibration has been cancelled");
+    } else {
+        set_device_config(device, "surface-calibration", "accept").await?;
+
+        println!("The surface calibration has been applied");
     }
 
     Ok(())
 }
 
 async fn angle_snapping_command(device: String, enable: Option<bool>) -> Result<()> {
-    let device = device.parse::<u64>()?;
+    let device = resolve_device(&device).await?;
 
     print_device_header(device)
         .await
@@ -435,7 +816,7 @@ async fn angle_snapping_command(device: String, enable: Option<bool>) -> Result<
 }
 
 async fn debounce_command(device: String, enable: Option<bool>) -> Result<()> {
-    let device = device.parse::<u64>()?;
+    let device = resolve_device(&device).await?;
 
     print_device_header(device)
         .await
@@ -455,8 +836,66 @@ async fn debounce_command(device: String, enable: Option<bool>) -> Result<()> {
     Ok(())
 }
 
+async fn led_ring_command(device: String, enable: Option<bool>) -> Result<()> {
+    let device = resolve_device(&device).await?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    if let Some(enable) = enable {
+        let value = &format!("{}", enable);
+
+        set_device_config(device, "led-ring", value)
+            .await
+            .wrap_err("Could not switch the LED ring")
+            .suggestion("Please verify that the device is a headset")?
+    } else {
+        let result = get_device_config(device, "led-ring")
+            .await
+            .wrap_err("Could not query the LED ring")
+            .suggestion("Please verify that the device is a headset")?;
+
+        println!("{}", format!("LED ring enabled: {}", result.bold()));
+    }
+
+    Ok(())
+}
+
+async fn grab_command(device: String, enable: Option<bool>) -> Result<()> {
+    let device = resolve_device(&device).await?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    if let Some(enable) = enable {
+        set_input_grab(device, enable)
+            .await
+            .wrap_err("Could not change the input grab")
+            .suggestion("Please verify that the device features an input device")?
+    } else {
+        let result = get_input_grab(device)
+            .await
+            .wrap_err("Could not query the input grab")
+            .suggestion("Please verify that the device features an input device")?;
+
+        println!(
+            "{}",
+            format!(
+                "Input grabbed exclusively: {}",
+                format!("{}", result).bold()
+            )
+        );
+    }
+
+    Ok(())
+}
+
 async fn brightness_command(device: String, brightness: Option<i64>) -> Result<()> {
-    let device = device.parse::<u64>()?;
+    let device = resolve_device(&device).await?;
 
     print_device_header(device)
         .await
@@ -476,6 +915,157 @@ async fn brightness_command(device: String, brightness: Option<i64>) -> Result<(
     Ok(())
 }
 
+async fn transform_command(device: String, transform: Option<String>) -> Result<()> {
+    let device = resolve_device(&device).await?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    if let Some(transform) = transform {
+        set_device_config(device, "transform", &transform)
+            .await
+            .wrap_err("Could not set the canvas transform")
+            .suggestion("Valid transforms are e.g. 'rotate=90', 'mirror-x', 'mirror-y', 'offset=2:0' or 'scale=1.5', separated by commas")?
+    } else {
+        let result = get_device_config(device, "transform").await?;
+
+        println!("Canvas transform: {}", result.bold());
+    }
+
+    Ok(())
+}
+
+async fn brightness_curve_command(device: String, curve: Option<String>) -> Result<()> {
+    let device = resolve_device(&device).await?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    if let Some(curve) = curve {
+        set_device_config(device, "brightness-curve", &curve)
+            .await
+            .wrap_err("Could not set the brightness curve")
+            .suggestion(
+                "Valid curves are 'cie', 'linear', 'gamma=<exponent>' (0.1 - 5.0) or 'default'",
+            )?
+    } else {
+        let result = get_device_config(device, "brightness-curve").await?;
+
+        println!("Brightness curve: {}", result.bold());
+    }
+
+    Ok(())
+}
+
+async fn key_filter_command(
+    device: String,
+    filter: Option<String>,
+    enable: bool,
+    disable: bool,
+) -> Result<()> {
+    let device = resolve_device(&device).await?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    if let Some(filter) = filter {
+        set_device_config(device, "key-filter", &filter)
+            .await
+            .wrap_err("Could not set the key filter")
+            .suggestion("Valid filters are e.g. 'debounce=8' or 'repeat=300:30' (delay in milliseconds and repeats per second), separated by commas")?
+    }
+
+    if enable || disable {
+        set_device_config(device, "key-filter-enabled", &format!("{}", enable))
+            .await
+            .wrap_err("Could not switch the key filter")?
+    }
+
+    let result = get_device_config(device, "key-filter").await?;
+    let enabled = get_device_config(device, "key-filter-enabled").await?;
+
+    println!(
+        "Key filter: {} ({})",
+        result.bold(),
+        if enabled == "true" {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+
+    Ok(())
+}
+
+async fn aux_leds_command(
+    device: String,
+    name: Option<String>,
+    color: Option<String>,
+) -> Result<()> {
+    let device = resolve_device(&device).await?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    match (name, color) {
+        (Some(name), Some(color)) => {
+            let color = u32::from_str_radix(color.trim_start_matches('#'), 16)
+                .wrap_err_with(|| format!("Invalid color: {color}"))
+                .suggestion(
+                    "Please specify the color in hexadecimal notation, like e.g. '#ff0000'",
+                )?;
+
+            set_aux_led(device, &name, color)
+                .await
+                .wrap_err_with(|| format!("Could not set the auxiliary LED '{name}'"))
+                .suggestion("Please run 'eruptionctl devices aux-leds <device>' to show the auxiliary LEDs of the device")?;
+        }
+
+        (Some(_name), None) => {
+            eprintln!("Please specify the color of the LED");
+        }
+
+        _ => {
+            let aux_leds = get_aux_leds(device).await?;
+
+            if aux_leds.is_empty() {
+                println!("{}", "<The device has no auxiliary LEDs>".italic());
+            } else {
+                let mut table = Table::new();
+                table
+                    .load_preset(UTF8_FULL)
+                    .apply_modifier(UTF8_ROUND_CORNERS)
+                    .set_content_arrangement(ContentArrangement::Dynamic)
+                    .set_header(vec!["Name", "Type", "Color"]);
+
+                for led in aux_leds {
+                    table.add_row(vec![
+                        Cell::new(led.name),
+                        Cell::new(if led.monochrome { "on/off" } else { "RGB" }),
+                        Cell::new(
+                            led.color
+                                .map(|c| format!("#{c:06x}"))
+                                .unwrap_or_else(|| "firmware default".to_string()),
+                        ),
+                    ]);
+                }
+
+                println!("{table}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Enumerate all available devices
 async fn get_devices() -> Result<(Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u16, u16)>)> {
     let ((keyboards, mice, misc),): ((Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u16, u16)>),) =
@@ -487,6 +1077,110 @@ async fn get_devices() -> Result<(Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u16, u1
     Ok((keyboards, mice, misc))
 }
 
+/// Get the stable identifiers of all connected devices, ordered by their device index
+async fn get_device_ids() -> Result<Vec<String>> {
+    let (ids,): (Vec<String>,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetDeviceIds", ())
+        .await?;
+
+    Ok(ids)
+}
+
+/// Returns the index of a device, specified either by its index or by its stable ID
+async fn layout_command(tiling: Option<bool>, reset: bool) -> Result<()> {
+    if reset {
+        reset_canvas_layout()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+
+        println!("All keyboards have been lined up from left to right");
+    }
+
+    if let Some(tiling) = tiling {
+        set_canvas_tiling(tiling)
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+    }
+
+    let layout = get_canvas_layout()
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    let tiling = if layout.tiling {
+        "enabled".bold().to_string()
+    } else {
+        "disabled".bold().to_string()
+    };
+
+    println!("Canvas tiling: {}", tiling);
+
+    if layout.tiles.is_empty() {
+        println!("No keyboards are connected");
+
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Index", "Device", "Position", "Size", "Placement"]);
+
+    for (index, tile) in layout.tiles.iter().enumerate() {
+        table.add_row(vec![
+            Cell::new(index).set_alignment(CellAlignment::Right),
+            Cell::new(&tile.device),
+            Cell::new(format!("{}:{}", tile.x, tile.y)),
+            Cell::new(format!("{}x{}", tile.width, tile.height)),
+            Cell::new(if tile.auto { "automatic" } else { "manual" }),
+        ]);
+    }
+
+    println!("{table}");
+
+    Ok(())
+}
+
+async fn place_command(device: String, x: i32, y: i32) -> Result<()> {
+    let device = resolve_device(&device).await?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    set_canvas_placement(device, x, y)
+        .await
+        .wrap_err("Could not move the device on the canvas")
+        .suggestion("Only keyboards may be placed on the canvas")?;
+
+    println!("Device {} has been moved to {}:{}", device, x, y);
+
+    Ok(())
+}
+
+async fn resolve_device(device: &str) -> Result<u64> {
+    if let Ok(index) = device.parse::<u64>() {
+        Ok(index)
+    } else {
+        let (index,): (u64,) = dbus_system_bus("/org/eruption/devices")
+            .await?
+            .method_call("org.eruption.Device", "FindDevice", (device.to_owned(),))
+            .await
+            .wrap_err_with(|| format!("Unknown device: {device}"))
+            .suggestion(
+                "Please run 'eruptionctl -v devices list' to show the IDs of all devices",
+            )?;
+
+        Ok(index)
+    }
+}
+
 /// Get a device specific config param
 async fn get_device_config(device: u64, param: &str) -> Result<String> {
     let (result,): (String,) = dbus_system_bus("/org/eruption/devices")
@@ -513,6 +1207,218 @@ async fn get_device_status(device: u64) -> Result<HashMap<String, String>> {
     Ok(result)
 }
 
+/// Results of a device self-test, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct SelfTestReport {
+    hid_roundtrip_ok: Option<bool>,
+    writes: usize,
+    write_latency_avg_micros: u64,
+    write_latency_max_micros: u64,
+    failed_writes: Vec<usize>,
+    errors: Vec<String>,
+}
+
+async fn run_self_test(device: u64) -> Result<SelfTestReport> {
+    let (report,): (String,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "RunSelfTest", (device,))
+        .await?;
+
+    let result: SelfTestReport = serde_json::from_str(&report)?;
+
+    Ok(result)
+}
+
+/// Failure statistics of a single device, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct DeviceHealth {
+    disconnects: u64,
+    failed_writes: u64,
+    reinit_attempts: u64,
+    #[serde(default)]
+    failed_inits: u64,
+    last_failure: Option<u64>,
+}
+
+/// Quarantine state of a device that failed too often, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct QuarantineStatus {
+    retry_in_secs: u64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DeviceHealthStatus {
+    device: String,
+    index: Option<u64>,
+    health: DeviceHealth,
+    warnings: Vec<String>,
+    #[serde(default)]
+    quarantine: Option<QuarantineStatus>,
+}
+
+/// Get the failure statistics of all devices
+async fn get_device_health() -> Result<Vec<DeviceHealthStatus>> {
+    let (status,): (String,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetDeviceHealth", ())
+        .await?;
+
+    let result: Vec<DeviceHealthStatus> = serde_json::from_str(&status)?;
+
+    Ok(result)
+}
+
+/// The area of the shared canvas that is covered by a keyboard, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct Tile {
+    device: String,
+    x: i32,
+    y: i32,
+    width: usize,
+    height: usize,
+    auto: bool,
+}
+
+/// The arrangement of the keyboards on the shared canvas, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct CanvasLayout {
+    tiling: bool,
+    tiles: Vec<Tile>,
+}
+
+/// Get the arrangement of the keyboards on the shared canvas
+async fn get_canvas_layout() -> Result<CanvasLayout> {
+    let (layout,): (String,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetCanvasLayout", ())
+        .await?;
+
+    let result: CanvasLayout = serde_json::from_str(&layout)?;
+
+    Ok(result)
+}
+
+/// Stretch the canvas across all keyboards, or show the whole canvas on each keyboard
+async fn set_canvas_tiling(enable: bool) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "SetCanvasTiling", (enable,))
+        .await?;
+
+    Ok(())
+}
+
+/// Move a keyboard to a position on the shared canvas
+async fn set_canvas_placement(device: u64, x: i32, y: i32) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "SetCanvasPlacement", (device, x, y))
+        .await?;
+
+    Ok(())
+}
+
+/// Forget the placements of all keyboards
+async fn reset_canvas_layout() -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "ResetCanvasLayout", ())
+        .await?;
+
+    Ok(())
+}
+
+/// Initialization status of a device, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct InitStatus {
+    state: String,
+    attempts: u32,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DeviceInitStatus {
+    device: String,
+    index: u64,
+    status: Option<InitStatus>,
+}
+
+/// Get the initialization status of all connected devices
+async fn get_device_init_status() -> Result<Vec<DeviceInitStatus>> {
+    let (status,): (String,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetDeviceInitStatus", ())
+        .await?;
+
+    let result: Vec<DeviceInitStatus> = serde_json::from_str(&status)?;
+
+    Ok(result)
+}
+
+/// Reset the failure statistics of all devices
+async fn reset_device_health() -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "ResetDeviceHealth", ())
+        .await?;
+
+    Ok(())
+}
+
+/// An auxiliary LED of a device, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct AuxLed {
+    name: String,
+    monochrome: bool,
+    color: Option<u32>,
+}
+
+/// Get the auxiliary LEDs of a device
+async fn get_aux_leds(device: u64) -> Result<Vec<AuxLed>> {
+    let (aux_leds,): (String,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetAuxLeds", (device,))
+        .await?;
+
+    let result: Vec<AuxLed> = serde_json::from_str(&aux_leds)?;
+
+    Ok(result)
+}
+
+/// Set the color of an auxiliary LED of a device
+async fn set_aux_led(device: u64, name: &str, color: u32) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call(
+            "org.eruption.Device",
+            "SetAuxLed",
+            (device, name.to_owned(), color),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Returns whether the input device of a device is grabbed exclusively
+async fn get_input_grab(device: u64) -> Result<bool> {
+    let (result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetInputGrab", (device,))
+        .await?;
+
+    Ok(result)
+}
+
+/// Grab the input device of a device exclusively, or hand it back to the OS
+async fn set_input_grab(device: u64, grab: bool) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "SetInputGrab", (device, grab))
+        .await?;
+
+    Ok(())
+}
+
 /// Set a device specific config param
 async fn set_device_config(device: u64, param: &str, value: &str) -> Result<()> {
     let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
@@ -140,6 +140,14 @@ fn list_specified_parameter(profile: &Profile, manifest: &Manifest, parameter: S
         table.add_row(profile_parameter_row(&manifest.name, profile_parameter));
         println!("{table}");
 
+        if let Some(constraints) = profile_parameter
+            .manifest
+            .as_ref()
+            .and_then(|m| m.get_constraints())
+        {
+            println!("Allowed values: {}", constraints);
+        }
+
         if let TypedValue::Color(_) = profile_parameter.value {
             println!("{}", warning_about_hash_because_i_always_forget);
         }
@@ -151,6 +159,10 @@ fn list_specified_parameter(profile: &Profile, manifest: &Manifest, parameter: S
                 table.add_row(manifest_parameter_row(&manifest.name, manifest_param));
                 println!("{table}");
 
+                if let Some(constraints) = manifest_param.manifest.get_constraints() {
+                    println!("Allowed values: {}", constraints);
+                }
+
                 if let ManifestValue::Color { .. } = manifest_param.manifest {
                     println!("{}", warning_about_hash_because_i_always_forget);
                 }
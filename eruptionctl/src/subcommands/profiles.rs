@@ -21,9 +21,12 @@
 
 use color_eyre::Help;
 use colored::*;
-use eyre::Context;
+use eyre::{eyre, Context};
+use std::fs;
+use std::path::PathBuf;
 
 use crate::dbus_client::dbus_system_bus;
+use crate::swarm::{self, SwarmProfile};
 use crate::util;
 
 type Result<T> = std::result::Result<T, eyre::Error>;
@@ -42,6 +45,10 @@ pub enum ProfilesSubcommands {
     /// Edit a profile
     #[clap(display_order = 2)]
     Edit { profile_name: String },
+
+    /// Import a profile in the Swarm interchange format, a JSON description of the settings of ROCCAT Swarm
+    #[clap(display_order = 3)]
+    ImportSwarm { file_name: PathBuf },
 }
 
 pub async fn handle_command(command: ProfilesSubcommands) -> Result<()> {
@@ -49,6 +56,7 @@ pub async fn handle_command(command: ProfilesSubcommands) -> Result<()> {
         ProfilesSubcommands::Edit { profile_name } => edit_command(profile_name).await,
         ProfilesSubcommands::List => list_command().await,
         ProfilesSubcommands::Info { profile_name } => info_command(profile_name).await,
+        ProfilesSubcommands::ImportSwarm { file_name } => import_swarm_command(file_name).await,
     }
 }
 
@@ -91,6 +99,79 @@ async fn info_command(profile_name: String) -> Result<()> {
     Ok(())
 }
 
+async fn import_swarm_command(file_name: PathBuf) -> Result<()> {
+    println!(
+        "Importing Swarm profile from: {}",
+        file_name.display().to_string().bold()
+    );
+
+    let json_data = fs::read_to_string(&file_name)?;
+    let swarm_profile: SwarmProfile = serde_json::from_str(&json_data)
+        .wrap_err("Could not parse the Swarm profile")
+        .suggestion("Please verify that the file uses the Swarm interchange format")?;
+
+    let file_stem = swarm::file_stem_for(&swarm_profile.name);
+
+    let profile_file = util::get_profile_dirs()
+        .first()
+        .ok_or_else(|| eyre!("Could not determine the profile directory"))?
+        .join(format!("{}.profile", file_stem));
+
+    let macro_module = format!("macros/swarm-{}", file_stem);
+    let user_script_dir = util::get_user_script_dir()
+        .ok_or_else(|| eyre!("Could not determine the script directory of the user"))?;
+
+    let macro_file = user_script_dir
+        .join("lib")
+        .join(format!("{}.lua", macro_module));
+
+    if profile_file.exists() {
+        return Err(eyre!(
+            "The profile file {} exists already",
+            profile_file.display()
+        ));
+    }
+
+    let import = swarm::convert(&swarm_profile, &profile_file, &macro_module)?;
+
+    if let Some(macros) = &import.macros {
+        if let Some(dir) = macro_file.parent() {
+            fs::create_dir_all(dir)
+                .wrap_err_with(|| format!("Could not create {}", dir.display()))?;
+        }
+
+        fs::write(&macro_file, macros)
+            .wrap_err_with(|| format!("Could not write {}", macro_file.display()))?;
+
+        println!("Macro definitions: {}", macro_file.display());
+
+        if !util::get_script_dirs().contains(&user_script_dir) {
+            println!(
+                "{} Please review the macro definitions, and add {} to 'script_dirs' in eruption.conf",
+                "Note:".yellow(),
+                user_script_dir.display()
+            );
+        }
+    }
+
+    import.profile.save()?;
+
+    println!("Profile: {}", profile_file.display().to_string().bold());
+
+    for warning in import.warnings.iter() {
+        println!("{} {}", "Warning:".yellow(), warning);
+    }
+
+    if let Some(dpi) = import.dpi {
+        println!(
+            "The profile uses {} DPI, you may apply it via: eruptionctl devices dpi <device> {}",
+            dpi, dpi
+        );
+    }
+
+    Ok(())
+}
+
 /// Enumerate all available profiles
 async fn get_profiles() -> Result<Vec<(String, String)>> {
     let (result,): (Vec<(String, String)>,) = dbus_system_bus("/org/eruption/profile")
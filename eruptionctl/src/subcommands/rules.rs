@@ -43,7 +43,13 @@ pub enum RulesSubcommands {
 
     /// Add a new rule
     #[clap(display_order = 1, about(tr!("rules-add")))]
-    Add { rule: Vec<String> },
+    Add {
+        /// Apply an exec rule to the descendants of a matched process too
+        #[clap(short, long)]
+        descendants: bool,
+
+        rule: Vec<String>,
+    },
 
     /// Remove a rule by its index
     #[clap(display_order = 2, about(tr!("rules-remove")))]
@@ -56,6 +62,10 @@ pub enum RulesSubcommands {
     /// Mark a rule as disabled
     #[clap(display_order = 4, about(tr!("rules-disable")))]
     Disable { rule_index: usize },
+
+    /// Move a rule to another position
+    #[clap(display_order = 5, about(tr!("rules-move")))]
+    Move { rule_index: usize, new_index: usize },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -94,6 +104,12 @@ pub enum Selector {
         mode: WindowFocusedSelectorMode,
         regex: String,
     },
+    External {
+        regex: String,
+    },
+    Hardware {
+        regex: String,
+    },
 }
 
 impl fmt::Display for Selector {
@@ -115,6 +131,14 @@ impl fmt::Display for Selector {
                     regex.to_string().bold()
                 )?;
             }
+
+            Selector::External { regex } => {
+                write!(f, "On external event: '{}'", regex.to_string().bold())?;
+            }
+
+            Selector::Hardware { regex } => {
+                write!(f, "On hardware event: '{}'", regex.to_string().bold())?;
+            }
         };
 
         Ok(())
@@ -125,6 +149,7 @@ impl fmt::Display for Selector {
 pub enum Action {
     SwitchToProfile { profile_name: String },
     SwitchToSlot { slot_index: u64 },
+    EnablePrivacyMode { zones: Vec<String> },
 }
 
 impl fmt::Display for Action {
@@ -141,6 +166,14 @@ impl fmt::Display for Action {
                     format!("{}", slot_index + 1).bold()
                 )?;
             }
+
+            Action::EnablePrivacyMode { zones } => {
+                if zones.is_empty() {
+                    write!(f, "Enable privacy mode")?;
+                } else {
+                    write!(f, "Enable privacy mode: {}", zones.join(", ").bold())?;
+                }
+            }
         };
 
         Ok(())
@@ -154,6 +187,12 @@ pub struct RuleMetadata {
 
     /// Set to true if the rule is auto-generated
     pub internal: bool,
+
+    /// Optional time window, e.g.: `09:00-17:00/mon-fri`
+    pub time_window: Option<String>,
+
+    /// Apply the rule to the descendants of a matched process too
+    pub include_descendants: bool,
 }
 
 impl std::default::Default for RuleMetadata {
@@ -161,6 +200,8 @@ impl std::default::Default for RuleMetadata {
         RuleMetadata {
             enabled: true,
             internal: false,
+            time_window: None,
+            include_descendants: false,
         }
     }
 }
@@ -170,6 +211,14 @@ impl fmt::Display for RuleMetadata {
         write!(f, "enabled: {}", self.enabled)?;
         write!(f, ", internal: {}", self.internal)?;
 
+        if self.include_descendants {
+            write!(f, ", descendants")?;
+        }
+
+        if let Some(time_window) = &self.time_window {
+            write!(f, ", time: {}", time_window)?;
+        }
+
         Ok(())
     }
 }
@@ -185,6 +234,9 @@ rules add exec <comm> [<profile-name.profile>|<slot number>]
 rules add exec gnome-calc.* /var/lib/eruption/profiles/profile1.profile
 rules add exec gnome-calc.* 2
 
+Apply the rule to all processes spawned by the matched process, until they exited:
+rules add --descendants exec steam 3
+
 
 Window:
 rules add window-[class|instance|name] <regex> [<profile-name.profile>|<slot number>]
@@ -193,6 +245,36 @@ rules add window-name '.*YouTube.*Mozilla Firefox' /var/lib/eruption/profiles/pr
 rules add window-instance gnome-calculator 2
 
 You may want to use the command line tool `xprop` to find the relevant information
+
+Black out the whole canvas or some zones (privacy mode) while a matching window is focused:
+rules add window-[class|instance|name] <regex> privacy[:<zone>[,<zone>...]]
+
+rules add window-class keepassxc privacy
+rules add window-name '.*Online Banking.*' privacy:numbers,numpad
+
+
+External events:
+rules add external <regex> [<profile-name.profile>|<slot number>]
+
+rules add external build-failed alert.profile
+
+Events are pushed by third-party scripts via the D-Bus method
+org.eruption.process_monitor.Events.PushEvent
+
+
+Hardware events, the lid of laptops and hotplugging of external displays:
+rules add hardware [lid-closed|lid-opened|docked|undocked] [<profile-name.profile>|<slot number>]
+rules add hardware display-[connected|disconnected]/<connector regex> [<profile-name.profile>|<slot number>]
+
+rules add hardware docked desk.profile
+rules add hardware 'display-connected/HDMI-.*' 2
+
+
+Time windows:
+rules add <sensor> <selector> [<profile-name.profile>|<slot number>] HH:MM-HH:MM[/<weekdays>]
+
+rules add exec gnome-calc.* 2 09:00-17:00/mon-fri
+rules add window-class firefox 3 18:00-01:00/sat,sun
 "#
     );
 }
@@ -200,10 +282,14 @@ You may want to use the command line tool `xprop` to find the relevant informati
 pub async fn handle_command(command: RulesSubcommands) -> Result<()> {
     match command {
         RulesSubcommands::List => list_command().await,
-        RulesSubcommands::Add { rule } => add_command(&rule).await,
+        RulesSubcommands::Add { descendants, rule } => add_command(&rule, descendants).await,
         RulesSubcommands::Remove { rule_index } => remove_command(rule_index).await,
         RulesSubcommands::Enable { rule_index } => enable_command(rule_index).await,
         RulesSubcommands::Disable { rule_index } => disable_command(rule_index).await,
+        RulesSubcommands::Move {
+            rule_index,
+            new_index,
+        } => move_command(rule_index, new_index).await,
     }
 }
 
@@ -227,8 +313,8 @@ async fn list_command() -> Result<()> {
     Ok(())
 }
 
-async fn add_command(rule: &[String]) -> Result<()> {
-    if rule.len() != 3 {
+async fn add_command(rule: &[String], descendants: bool) -> Result<()> {
+    if rule.len() != 3 && rule.len() != 4 {
         eprintln!("Malformed rule definition");
         print_usage_examples();
     } else {
@@ -238,179 +324,147 @@ async fn add_command(rule: &[String]) -> Result<()> {
         let metadata = RuleMetadata {
             enabled: true,
             internal: false,
+            time_window: rule.get(3).cloned(),
+            include_descendants: descendants,
+        };
+
+        let (new_selector, _new_metadata, new_action) = parse_rule(&(
+            sensor.clone(),
+            selector.clone(),
+            action,
+            metadata.to_string(),
+        ))?;
+
+        if descendants && !matches!(new_selector, Selector::ProcessExec { .. }) {
+            eprintln!("Only exec rules may apply to descendants of a process");
+            print_usage_examples();
+
+            return Ok(());
         }
-        .to_string();
 
-        let (new_selector, _new_metadata, mut new_action) =
-            parse_rule(&(sensor, selector, action, metadata))?;
+        if matches!(new_action, Action::EnablePrivacyMode { .. })
+            && !matches!(new_selector, Selector::WindowFocused { .. })
+        {
+            eprintln!("Only window rules may enable privacy mode");
+            print_usage_examples();
 
-        // slot indices are 0-based
-        if let Action::SwitchToSlot { slot_index } = new_action {
-            new_action = Action::SwitchToSlot {
-                slot_index: slot_index.saturating_sub(1),
-            };
+            return Ok(());
         }
 
-        let rules = enum_rules().await?;
-        let new_rule_index = rules.len().saturating_sub(1);
+        let action = match new_action {
+            Action::SwitchToProfile { profile_name } => profile_name,
 
-        let mut result: IndexMap<Selector, (RuleMetadata, Action)> = IndexMap::new();
+            // slot indices are 0-based
+            Action::SwitchToSlot { slot_index } => format!("{}", slot_index.saturating_sub(1)),
 
-        for (index, (selector, (metadata, action))) in rules.iter().enumerate() {
-            if index == new_rule_index {
-                if !metadata.internal {
-                    result.insert(selector.clone(), (metadata.clone(), action.clone()));
+            Action::EnablePrivacyMode { zones } => {
+                if zones.is_empty() {
+                    "privacy".to_string()
+                } else {
+                    format!("privacy:{}", zones.join(","))
                 }
-
-                let new_metadata = RuleMetadata {
-                    enabled: true,
-                    internal: false,
-                };
-
-                result.insert(
-                    new_selector.clone(),
-                    (new_metadata.clone(), new_action.clone()),
-                );
-            } else if !metadata.internal {
-                result.insert(selector.clone(), (metadata.clone(), action.clone()));
             }
+        };
+
+        let mut metadata_val = "enabled,user-defined".to_string();
+
+        if metadata.include_descendants {
+            metadata_val.push_str(",descendants");
+        }
+
+        // the time window has to be the last element
+        if let Some(time_window) = &metadata.time_window {
+            metadata_val.push_str(&format!(",time={time_window}"));
         }
 
-        set_rules(&result).await?;
+        let (index,): (u64,) = dbus_session_bus(
+            "org.eruption.process_monitor",
+            "/org/eruption/process_monitor/rules",
+        )
+        .await?
+        .method_call(
+            "org.eruption.process_monitor.Rules",
+            "AddRule",
+            (sensor, selector, action, metadata_val),
+        )
+        .await?;
+
+        println!("Added rule: {index}");
     }
 
     Ok(())
 }
 
 async fn remove_command(rule_index: usize) -> Result<()> {
-    let rules = enum_rules().await?;
-    let mut result = IndexMap::new();
-
-    for (index, (selector, (metadata, action))) in rules.iter().enumerate() {
-        if index == rule_index || metadata.internal {
-            continue;
-        } else if !metadata.internal {
-            result.insert(selector.clone(), (metadata.clone(), action.clone()));
-        }
-    }
-
-    set_rules(&result).await?;
+    let (_status,): (bool,) = dbus_session_bus(
+        "org.eruption.process_monitor",
+        "/org/eruption/process_monitor/rules",
+    )
+    .await?
+    .method_call(
+        "org.eruption.process_monitor.Rules",
+        "RemoveRule",
+        (rule_index as u64,),
+    )
+    .await?;
 
     Ok(())
 }
 
 async fn enable_command(rule_index: usize) -> Result<()> {
-    let rules = enum_rules().await?;
-    let mut result = IndexMap::new();
-
-    for (index, (selector, (metadata, action))) in rules.iter().enumerate() {
-        if index == rule_index && !metadata.internal {
-            let metadata = RuleMetadata {
-                enabled: true,
-                ..metadata.clone()
-            };
-
-            result.insert(selector.clone(), (metadata.clone(), action.clone()));
-        } else if !metadata.internal {
-            result.insert(selector.clone(), (metadata.clone(), action.clone()));
-        }
-    }
-
-    set_rules(&result).await?;
-
-    Ok(())
+    set_rule_enabled(rule_index, true).await
 }
 
 async fn disable_command(rule_index: usize) -> Result<()> {
-    let rules = enum_rules().await?;
-    let mut result = IndexMap::new();
-
-    for (index, (selector, (metadata, action))) in rules.iter().enumerate() {
-        if index == rule_index && !metadata.internal {
-            let metadata = RuleMetadata {
-                enabled: false,
-                ..metadata.clone()
-            };
-
-            result.insert(selector.clone(), (metadata.clone(), action.clone()));
-        } else if !metadata.internal {
-            result.insert(selector.clone(), (metadata.clone(), action.clone()));
-        }
-    }
-
-    set_rules(&result).await?;
-
-    Ok(())
+    set_rule_enabled(rule_index, false).await
 }
 
-async fn enum_rules() -> Result<IndexMap<Selector, (RuleMetadata, Action)>> {
-    let (result,): (Vec<(String, String, String, String)>,) = dbus_session_bus(
+async fn move_command(rule_index: usize, new_index: usize) -> Result<()> {
+    let (_status,): (bool,) = dbus_session_bus(
         "org.eruption.process_monitor",
         "/org/eruption/process_monitor/rules",
     )
     .await?
-    .method_call("org.eruption.process_monitor.Rules", "EnumRules", ())
+    .method_call(
+        "org.eruption.process_monitor.Rules",
+        "MoveRule",
+        (rule_index as u64, new_index as u64),
+    )
     .await?;
 
-    let rules = parse_rules(&result)?;
-
-    Ok(rules)
+    Ok(())
 }
 
-async fn set_rules(rules: &IndexMap<Selector, (RuleMetadata, Action)>) -> Result<()> {
-    let mut generated_rules: Vec<(String, String, String, String)> = Vec::new();
-
-    for (_index, (selector, (metadata, action))) in rules.iter().enumerate() {
-        let (sensor, selector) = match selector {
-            Selector::ProcessExec { comm } => ("exec".to_string(), comm.to_owned()),
-
-            Selector::WindowFocused { mode, regex } => match mode {
-                WindowFocusedSelectorMode::WindowClass => {
-                    ("window-class".to_string(), regex.to_owned())
-                }
-
-                WindowFocusedSelectorMode::WindowInstance => {
-                    ("window-instance".to_string(), regex.to_owned())
-                }
-
-                WindowFocusedSelectorMode::WindowName => {
-                    ("window-name".to_string(), regex.to_owned())
-                }
-            },
-        };
-
-        let action = match action {
-            Action::SwitchToProfile { profile_name } => profile_name.to_owned(),
-            Action::SwitchToSlot { slot_index } => format!("{slot_index}"),
-        };
-
-        let metadata = format!(
-            "({}, user-defined)",
-            if metadata.enabled {
-                "enabled"
-            } else {
-                "disabled"
-            }
-        );
-
-        generated_rules.push((sensor, selector, action, metadata));
-    }
-
-    dbus_session_bus(
+async fn set_rule_enabled(rule_index: usize, enabled: bool) -> Result<()> {
+    let (_status,): (bool,) = dbus_session_bus(
         "org.eruption.process_monitor",
         "/org/eruption/process_monitor/rules",
     )
     .await?
     .method_call(
         "org.eruption.process_monitor.Rules",
-        "SetRules",
-        (generated_rules,),
+        "SetRuleEnabled",
+        (rule_index as u64, enabled),
     )
     .await?;
 
     Ok(())
 }
 
+async fn enum_rules() -> Result<IndexMap<Selector, (RuleMetadata, Action)>> {
+    let (result,): (Vec<(String, String, String, String)>,) = dbus_session_bus(
+        "org.eruption.process_monitor",
+        "/org/eruption/process_monitor/rules",
+    )
+    .await?
+    .method_call("org.eruption.process_monitor.Rules", "EnumRules", ())
+    .await?;
+
+    let rules = parse_rules(&result)?;
+
+    Ok(rules)
+}
+
 fn parse_rule(rule: &(String, String, String, String)) -> Result<(Selector, RuleMetadata, Action)> {
     let sensor = &rule.0;
     let selector = &rule.1;
@@ -425,7 +479,19 @@ fn parse_rule(rule: &(String, String, String, String)) -> Result<(Selector, Rule
 
     let internal = metadata.contains("internal");
 
-    let parsed_metadata = RuleMetadata { enabled, internal };
+    let include_descendants = metadata.contains("descendants");
+
+    let time_window = metadata.split_once("time=").map(|(_, s)| {
+        s.trim_end_matches(|c: char| c == ')' || c.is_whitespace())
+            .to_owned()
+    });
+
+    let parsed_metadata = RuleMetadata {
+        enabled,
+        internal,
+        time_window,
+        include_descendants,
+    };
 
     // parse sensor and selector
     if sensor.contains("exec") {
@@ -447,6 +513,14 @@ fn parse_rule(rule: &(String, String, String, String)) -> Result<(Selector, Rule
             mode: WindowFocusedSelectorMode::WindowName,
             regex: selector.to_owned(),
         });
+    } else if sensor.contains("external") {
+        parsed_selector = Some(Selector::External {
+            regex: selector.to_owned(),
+        });
+    } else if sensor.contains("hardware") {
+        parsed_selector = Some(Selector::Hardware {
+            regex: selector.to_owned(),
+        });
     }
 
     // parse action
@@ -455,6 +529,27 @@ fn parse_rule(rule: &(String, String, String, String)) -> Result<(Selector, Rule
             description: "Syntax error in selector".to_owned(),
         }
         .into())
+    } else if let Some(zones) = action.strip_prefix("privacy") {
+        let zones = match zones.strip_prefix(':') {
+            Some(zones) => zones
+                .split(',')
+                .map(|zone| zone.trim().to_string())
+                .filter(|zone| !zone.is_empty())
+                .collect(),
+
+            None if zones.is_empty() => vec![],
+
+            None => {
+                return Err(RuleError::Parse {
+                    description: "Syntax error in action".to_owned(),
+                }
+                .into())
+            }
+        };
+
+        parsed_action = Action::EnablePrivacyMode { zones };
+
+        Ok((parsed_selector.unwrap(), parsed_metadata, parsed_action))
     } else if action.contains(".profile") {
         parsed_action = Action::SwitchToProfile {
             profile_name: action.to_owned(),
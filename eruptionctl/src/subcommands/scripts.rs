@@ -19,11 +19,19 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use color_eyre::Help;
 use colored::*;
+use comfy_table::modifiers::UTF8_ROUND_CORNERS;
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::{Cell, CellAlignment, ContentArrangement, Table};
+use eyre::Context;
 use same_file::is_same_file;
 
+use crate::dbus_client::dbus_system_bus;
 use crate::scripting::manifest::{self, Manifest};
 use crate::util;
 
@@ -43,6 +51,20 @@ pub enum ScriptsSubcommands {
     /// Edit a Lua script file
     #[clap(display_order = 2)]
     Edit { script_name: String },
+
+    /// Inspect the state of a running Lua script of the active profile
+    #[clap(display_order = 3)]
+    Inspect {
+        /// The index of the script within the active profile
+        index: u64,
+    },
+
+    /// Check a Lua script and its manifest for errors, without running it
+    #[clap(display_order = 4)]
+    Lint {
+        /// The Lua script file, its manifest is expected alongside as `<file>.manifest`
+        file: PathBuf,
+    },
 }
 
 pub async fn handle_command(command: ScriptsSubcommands) -> Result<()> {
@@ -50,6 +72,8 @@ pub async fn handle_command(command: ScriptsSubcommands) -> Result<()> {
         ScriptsSubcommands::Edit { script_name } => edit_command(script_name).await,
         ScriptsSubcommands::List => list_command().await,
         ScriptsSubcommands::Info { script_name } => info_command(script_name).await,
+        ScriptsSubcommands::Inspect { index } => inspect_command(index).await,
+        ScriptsSubcommands::Lint { file } => lint_command(file).await,
     }
 }
 
@@ -73,15 +97,19 @@ async fn list_command() -> Result<()> {
 async fn info_command(script_name: String) -> Result<()> {
     match find_script_by_name(&script_name) {
         Some(script) => {
-            let empty = vec![];
+            let metadata = script.get_metadata();
             println!(
-                "Lua script:\t{} ({})\nDaemon version:\t{}\nAuthor:\t\t{}\nDescription:\t{}\nTags:\t\t{:?}",
-                script.name,
-                script.version,
+                "Lua script:\t{} ({})\nDaemon version:\t{}\nAuthor:\t\t{}\nLicense:\t{}\nHomepage:\t{}\nDescription:\t{}\nTags:\t\t{:?}\nPreview:\t{:?} {}",
+                metadata.name,
+                metadata.version,
                 script.min_supported_version,
-                script.author,
-                script.description,
-                script.tags.as_ref().unwrap_or(&empty),
+                metadata.author,
+                metadata.license.as_deref().unwrap_or("Unknown"),
+                metadata.homepage.as_deref().unwrap_or("-"),
+                metadata.description,
+                metadata.tags,
+                metadata.preview.animation,
+                metadata.preview.colors.join(", "),
             );
         }
         None => eprintln!("Script not found."),
@@ -90,6 +118,222 @@ async fn info_command(script_name: String) -> Result<()> {
     Ok(())
 }
 
+async fn inspect_command(index: u64) -> Result<()> {
+    let inspection = get_script_inspection(index)
+        .await
+        .wrap_err("Could not inspect the script")
+        .suggestion("Please verify that the Eruption daemon is running, and that the active profile contains the script")?;
+
+    let state = if inspection.running {
+        "running".green()
+    } else if inspection.last_error.is_some() {
+        "failed".red()
+    } else {
+        "stopped".normal()
+    };
+
+    println!(
+        "Lua script:\t{} ({})\nState:\t\t{}\nFrame cost:\t{:.1} µs ({:.1}% of the frame budget)",
+        inspection.script_file.display().to_string().bold(),
+        inspection.index,
+        state,
+        inspection.frame_cost_micros,
+        inspection.frame_budget_percent,
+    );
+
+    println!("\n{}", "Event handlers:".bold());
+
+    if inspection.handlers.is_empty() {
+        println!("{}", "<No event handlers defined>".italic());
+    } else {
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .apply_modifier(UTF8_ROUND_CORNERS)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec!["Handler", "Calls", "Avg. time (µs)", "Max. time (µs)"]);
+
+        for handler in inspection.handlers.iter() {
+            let stats = inspection.handler_stats.get(handler);
+
+            let calls = stats.map(|s| s.calls).unwrap_or(0);
+            let average = stats
+                .filter(|s| s.calls > 0)
+                .map(|s| s.total_micros / s.calls)
+                .unwrap_or(0);
+            let max = stats.map(|s| s.max_micros).unwrap_or(0);
+
+            table.add_row(vec![
+                Cell::new(handler),
+                Cell::new(calls).set_alignment(CellAlignment::Right),
+                Cell::new(average).set_alignment(CellAlignment::Right),
+                Cell::new(max).set_alignment(CellAlignment::Right),
+            ]);
+        }
+
+        println!("{}", table);
+    }
+
+    println!("\n{}", "Parameters:".bold());
+
+    if inspection.parameters.is_empty() {
+        println!("{}", "<No parameters declared>".italic());
+    } else {
+        for (name, value) in inspection.parameters.iter() {
+            println!("{}: {}", name, value.bold());
+        }
+    }
+
+    if let Some(error) = inspection.last_error {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        println!(
+            "\n{} ({} seconds ago)\n{}",
+            "Last error:".red().bold(),
+            now.saturating_sub(error.timestamp),
+            error.message
+        );
+
+        if let Some(traceback) = error.traceback {
+            println!("\n{}\n{}", "Stack traceback:".bold(), traceback);
+        }
+    }
+
+    Ok(())
+}
+
+async fn lint_command(file: PathBuf) -> Result<()> {
+    let script = std::fs::read_to_string(&file)
+        .wrap_err_with(|| format!("Could not read the script file {}", file.display()))?;
+
+    let mut manifest_file = file.clone().into_os_string();
+    manifest_file.push(".manifest");
+
+    // an empty manifest denotes a script without one
+    let manifest = std::fs::read_to_string(&manifest_file).unwrap_or_default();
+
+    let file_name = file
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let diagnostics = lint_script(&file_name, &script, &manifest)
+        .await
+        .wrap_err("Could not lint the script")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    let mut errors = 0;
+
+    for diagnostic in diagnostics.iter() {
+        let location = match diagnostic.line {
+            Some(line) => format!("{}:{}", file.display(), line),
+            None => format!("{}", file.display()),
+        };
+
+        let severity = match diagnostic.severity {
+            Severity::Error => {
+                errors += 1;
+                "error".red().bold()
+            }
+            Severity::Warning => "warning".yellow().bold(),
+            Severity::Style => "style".normal(),
+        };
+
+        println!("{}: {}: {}", location.bold(), severity, diagnostic.message);
+    }
+
+    if diagnostics.is_empty() {
+        println!(
+            "{}: {}",
+            file.display().to_string().bold(),
+            "No issues found".green()
+        );
+    }
+
+    if errors > 0 {
+        Err(eyre::eyre!("The script contains {} error(s)", errors))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Error,
+    Warning,
+    Style,
+}
+
+/// A finding of the linter, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct Diagnostic {
+    severity: Severity,
+    line: Option<usize>,
+    message: String,
+}
+
+/// An error that terminated a script, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct ScriptError {
+    message: String,
+    traceback: Option<String>,
+    timestamp: u64,
+}
+
+/// Timing statistics of a single handler function
+#[derive(Debug, serde::Deserialize)]
+struct HandlerStats {
+    calls: u64,
+    total_micros: u64,
+    max_micros: u64,
+}
+
+/// Debug information of a running script, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct ScriptInspection {
+    index: usize,
+    script_file: PathBuf,
+    running: bool,
+    handlers: Vec<String>,
+    parameters: BTreeMap<String, String>,
+    handler_stats: BTreeMap<String, HandlerStats>,
+    frame_cost_micros: f64,
+    frame_budget_percent: f64,
+    last_error: Option<ScriptError>,
+}
+
+/// Get the debug information of a running script
+async fn get_script_inspection(index: u64) -> Result<ScriptInspection> {
+    let (inspection,): (String,) = dbus_system_bus("/org/eruption/profile")
+        .await?
+        .method_call("org.eruption.Profile", "InspectScript", (index,))
+        .await?;
+
+    let result: ScriptInspection = serde_json::from_str(&inspection)?;
+
+    Ok(result)
+}
+
+/// Lint a Lua script and its manifest
+async fn lint_script(file_name: &str, script: &str, manifest: &str) -> Result<Vec<Diagnostic>> {
+    let (diagnostics,): (String,) = dbus_system_bus("/org/eruption/profile")
+        .await?
+        .method_call(
+            "org.eruption.Profile",
+            "LintScript",
+            (file_name, script, manifest),
+        )
+        .await?;
+
+    let result: Vec<Diagnostic> = serde_json::from_str(&diagnostics)?;
+
+    Ok(result)
+}
+
 /// Enumerate all available scripts
 fn get_script_list() -> Result<Vec<(String, String)>> {
     let scripts = manifest::get_scripts()?;
@@ -21,10 +21,18 @@
 
 use color_eyre::Help;
 use colored::*;
+use comfy_table::modifiers::UTF8_ROUND_CORNERS;
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::{Cell, ContentArrangement, Table};
 use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
 use eyre::Context;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::dbus_client::dbus_system_bus;
+use crate::dbus_client::{self, dbus_system_bus};
+use crate::device;
 
 type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -38,36 +46,406 @@ pub enum StatusSubcommands {
     /// Shows the currently active slot
     #[clap(display_order = 1)]
     Slot,
+
+    /// Shows the clients that are connected to the SDK socket, or disconnects a client
+    #[clap(display_order = 2)]
+    SdkClients {
+        /// The id of the client to disconnect
+        #[clap(long)]
+        kick: Option<u64>,
+    },
+
+    /// Shows whether the daemon runs in safe mode, or leaves safe mode
+    #[clap(display_order = 3)]
+    SafeMode {
+        /// Leave safe mode and switch back to the profile of the active slot
+        #[clap(long)]
+        exit: bool,
+    },
+
+    /// Dumps the recorded profiling spans as folded stacks, e.g. for use with inferno-flamegraph
+    #[clap(display_order = 4)]
+    DumpProfile {
+        /// The file to write the folded stacks to
+        file: PathBuf,
+
+        /// Number of seconds of recorded data to dump
+        #[clap(long, default_value = "60")]
+        seconds: u64,
+    },
+}
+
+/// Signals that cause the status to be refreshed in watch mode
+const WATCHED_SIGNALS: &[(&str, &str)] = &[
+    ("org.eruption.Profile", "ActiveProfileChanged"),
+    ("org.eruption.Slot", "ActiveSlotChanged"),
+    ("org.eruption.Device", "DeviceStatusChanged"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Overview,
+    Profile,
+    Slot,
 }
 
-pub async fn handle_command(command: StatusSubcommands) -> Result<()> {
+pub async fn handle_command(command: Option<StatusSubcommands>, watch: bool) -> Result<()> {
     match command {
-        StatusSubcommands::Profile => profile_command().await,
-        StatusSubcommands::Slot => slot_command().await,
+        None => show_status(View::Overview, watch).await,
+        Some(StatusSubcommands::Profile) => show_status(View::Profile, watch).await,
+        Some(StatusSubcommands::Slot) => show_status(View::Slot, watch).await,
+        Some(StatusSubcommands::SdkClients { kick }) => sdk_clients_command(kick).await,
+        Some(StatusSubcommands::SafeMode { exit }) => safe_mode_command(exit).await,
+        Some(StatusSubcommands::DumpProfile { file, seconds }) => {
+            dump_profile_command(file, seconds).await
+        }
+    }
+}
+
+/// Print the requested status, and in watch mode re-print it in place each time
+/// the Eruption daemon signals a change
+async fn show_status(view: View, watch: bool) -> Result<()> {
+    let mut subscription = if watch {
+        Some(
+            dbus_client::subscribe_system_signals(WATCHED_SIGNALS)
+                .await
+                .wrap_err("Could not subscribe to the signals of the Eruption daemon")?,
+        )
+    } else {
+        None
+    };
+
+    let term = console::Term::stdout();
+
+    // stores how many lines we printed in the previous iteration
+    let mut prev = 0;
+
+    loop {
+        let output = render_status(view)
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+
+        term.clear_last_lines(prev)?;
+        prev = output.lines().count();
+
+        println!("{output}");
+
+        match subscription.as_mut() {
+            Some(subscription) if subscription.next().await => continue,
+            _ => break,
+        }
     }
+
+    Ok(())
 }
 
-async fn profile_command() -> Result<()> {
-    let profile_name = get_active_profile()
+async fn render_status(view: View) -> Result<String> {
+    match view {
+        View::Profile => {
+            let profile_name = get_active_profile().await?;
+
+            Ok(format!("Current profile: {}", profile_name.bold()))
+        }
+
+        View::Slot => {
+            let index = get_active_slot().await? + 1;
+
+            Ok(format!("Current slot: {}", format!("{}", index).bold()))
+        }
+
+        View::Overview => {
+            let profile_name = get_active_profile().await?;
+            let index = get_active_slot().await? + 1;
+
+            let (keyboards, mice, misc) = get_devices().await?;
+
+            let mut table = Table::new();
+            table
+                .load_preset(UTF8_FULL)
+                .apply_modifier(UTF8_ROUND_CORNERS)
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_header(vec!["Index", "Device", "Battery", "Signal"]);
+
+            for (index, (usb_vid, usb_pid)) in keyboards
+                .iter()
+                .chain(mice.iter())
+                .chain(misc.iter())
+                .enumerate()
+            {
+                let status = get_device_status(index as u64).await.unwrap_or_default();
+
+                let percent = |key: &str| {
+                    status
+                        .get(key)
+                        .map(|v| format!("{v}%"))
+                        .unwrap_or_else(|| "-".to_string())
+                };
+
+                table.add_row(vec![
+                    Cell::new(format!("{:02}", index)),
+                    Cell::new(format!(
+                        "{} {}",
+                        device::get_device_make(*usb_vid, *usb_pid).unwrap_or("<unknown make>"),
+                        device::get_device_model(*usb_vid, *usb_pid).unwrap_or("<unknown model>")
+                    )),
+                    Cell::new(percent("battery-level-percent")),
+                    Cell::new(percent("signal-strength-percent")),
+                ]);
+            }
+
+            let safe_mode = match get_safe_mode().await {
+                Ok(status) if status.active => format!(
+                    "{}\n",
+                    "Running in safe mode, user scripts are disabled"
+                        .bold()
+                        .red()
+                ),
+
+                _ => String::new(),
+            };
+
+            Ok(format!(
+                "{safe_mode}Current profile: {}\nCurrent slot: {}\n{table}",
+                profile_name.bold(),
+                format!("{}", index).bold()
+            ))
+        }
+    }
+}
+
+async fn sdk_clients_command(kick: Option<u64>) -> Result<()> {
+    if let Some(id) = kick {
+        kick_sdk_client(id)
+            .await
+            .wrap_err_with(|| format!("Could not disconnect the SDK client {id}"))
+            .suggestion(
+                "Please run 'eruptionctl status sdk-clients' to show the connected clients",
+            )?;
+
+        println!("Disconnected the SDK client {}", format!("{id}").bold());
+
+        return Ok(());
+    }
+
+    let clients = get_sdk_clients()
         .await
         .wrap_err("Could not connect to the Eruption daemon")
         .suggestion("Please verify that the Eruption daemon is running")?;
-    println!("Current profile: {}", profile_name.bold());
+
+    if clients.is_empty() {
+        println!("{}", "<No SDK clients are connected>".italic());
+    } else {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .apply_modifier(UTF8_ROUND_CORNERS)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                "Id",
+                "PID",
+                "User",
+                "Protocol",
+                "Authenticated",
+                "Connected",
+            ]);
+
+        for client in clients {
+            let secs = now.saturating_sub(client.connected_at);
+
+            table.add_row(vec![
+                Cell::new(client.id),
+                Cell::new(client.pid),
+                Cell::new(format!("{} ({})", client.user, client.uid)),
+                Cell::new(client.protocol_version),
+                Cell::new(if client.authenticated { "yes" } else { "no" }),
+                Cell::new(format!("{}m {}s ago", secs / 60, secs % 60)),
+            ]);
+        }
+
+        println!("{table}");
+    }
 
     Ok(())
 }
 
-async fn slot_command() -> Result<()> {
-    let index = get_active_slot()
+async fn safe_mode_command(exit: bool) -> Result<()> {
+    if exit {
+        let left = exit_safe_mode()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+
+        if left {
+            println!("Left safe mode, switching back to the profile of the active slot");
+        } else {
+            println!("The Eruption daemon does not run in safe mode");
+        }
+
+        return Ok(());
+    }
+
+    let status = get_safe_mode()
         .await
         .wrap_err("Could not connect to the Eruption daemon")
-        .suggestion("Please verify that the Eruption daemon is running")?
-        + 1;
-    println!("Current slot: {}", format!("{}", index).bold());
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    match status.reason {
+        Some(reason) if status.active => println!(
+            "Safe mode: {} ({})",
+            "active".bold().red(),
+            match reason {
+                SafeModeReason::Requested => "requested on the command line",
+                SafeModeReason::CrashLoop => "the daemon failed to start up repeatedly",
+            }
+        ),
+
+        _ => println!("Safe mode: {}", "inactive".bold()),
+    }
+
+    println!(
+        "Failed startups: {} (threshold: {})",
+        status.failed_startups,
+        if status.threshold > 0 {
+            format!("{}", status.threshold)
+        } else {
+            "disabled".to_string()
+        }
+    );
 
     Ok(())
 }
 
+async fn dump_profile_command(file: PathBuf, seconds: u64) -> Result<()> {
+    let stacks = dump_profile(seconds)
+        .await
+        .wrap_err("Could not dump the profile")
+        .suggestion("Please verify that the span profiler is enabled in eruption.conf")?;
+
+    fs::write(&file, &stacks)
+        .wrap_err_with(|| format!("Could not write the profile to {}", file.display()))?;
+
+    println!(
+        "Wrote {} stacks to {}, render them with e.g. 'inferno-flamegraph < {} > flamegraph.svg'",
+        stacks.lines().count(),
+        file.display(),
+        file.display()
+    );
+
+    Ok(())
+}
+
+/// The reason why the daemon runs in safe mode
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SafeModeReason {
+    Requested,
+    CrashLoop,
+}
+
+/// Safe mode state, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct SafeModeStatus {
+    active: bool,
+    reason: Option<SafeModeReason>,
+    failed_startups: u32,
+    threshold: u32,
+}
+
+/// Get the safe mode state of the daemon
+async fn get_safe_mode() -> Result<SafeModeStatus> {
+    let (status,): (String,) = dbus_system_bus("/org/eruption/status")
+        .await?
+        .method_call("org.eruption.Status", "GetSafeMode", ())
+        .await?;
+
+    let result: SafeModeStatus = serde_json::from_str(&status)?;
+
+    Ok(result)
+}
+
+/// Leave safe mode, returns false if the daemon did not run in safe mode
+async fn exit_safe_mode() -> Result<bool> {
+    let (result,): (bool,) = dbus_system_bus("/org/eruption/status")
+        .await?
+        .method_call("org.eruption.Status", "ExitSafeMode", ())
+        .await?;
+
+    Ok(result)
+}
+
+/// Returns the recorded profiling spans of the last `seconds` seconds as folded stacks
+async fn dump_profile(seconds: u64) -> Result<String> {
+    let (result,): (String,) = dbus_system_bus("/org/eruption/status")
+        .await?
+        .method_call("org.eruption.Status", "DumpProfile", (seconds,))
+        .await?;
+
+    Ok(result)
+}
+
+/// A client of the SDK socket, as reported by the Eruption daemon
+#[derive(Debug, serde::Deserialize)]
+struct SdkClient {
+    id: u64,
+    pid: i32,
+    uid: u32,
+    user: String,
+    connected_at: u64,
+    protocol_version: u32,
+    authenticated: bool,
+}
+
+/// Get the clients that are connected to the SDK socket
+async fn get_sdk_clients() -> Result<Vec<SdkClient>> {
+    let (clients,): (String,) = dbus_system_bus("/org/eruption/status")
+        .await?
+        .method_call("org.eruption.Status", "GetSdkClients", ())
+        .await?;
+
+    let result: Vec<SdkClient> = serde_json::from_str(&clients)?;
+
+    Ok(result)
+}
+
+/// Disconnect a client of the SDK socket
+async fn kick_sdk_client(id: u64) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/status")
+        .await?
+        .method_call("org.eruption.Status", "KickSdkClient", (id,))
+        .await?;
+
+    Ok(())
+}
+
+/// Get the USB IDs of the connected keyboards, mice and misc devices
+async fn get_devices() -> Result<(Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u16, u16)>)> {
+    let ((keyboards, mice, misc),): ((Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u16, u16)>),) =
+        dbus_system_bus("/org/eruption/devices")
+            .await?
+            .method_call("org.eruption.Device", "GetManagedDevices", ())
+            .await?;
+
+    Ok((keyboards, mice, misc))
+}
+
+/// Get device specific status
+async fn get_device_status(device: u64) -> Result<HashMap<String, String>> {
+    let (status,): (String,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetDeviceStatus", (device,))
+        .await?;
+
+    let result: HashMap<String, String> = serde_json::from_str(&status)?;
+
+    Ok(result)
+}
+
 /// Get the name of the currently active profile
 async fn get_active_profile() -> Result<String> {
     let result: String = dbus_system_bus("/org/eruption/profile")
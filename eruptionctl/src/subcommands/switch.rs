@@ -40,12 +40,43 @@ pub enum SwitchSubcommands {
     /// Switch slots
     #[clap(display_order = 1)]
     Slot { index: usize },
+
+    /// Blend a second profile into a slot, both profiles are run simultaneously
+    #[clap(display_order = 2)]
+    Blend {
+        /// The index of the slot, starting at 1
+        index: usize,
+
+        /// The profile to blend in, omit it to remove the blended profile from the slot
+        profile_name: Option<String>,
+
+        /// Weight of the blended profile, from 0.0 to 1.0
+        #[clap(short, long, default_value_t = 0.5)]
+        weight: f64,
+    },
+
+    /// Fade the blend weight of the active slot
+    #[clap(display_order = 3)]
+    Fade {
+        /// Weight of the blended profile, from 0.0 to 1.0
+        weight: f64,
+
+        /// Duration of the transition in milliseconds
+        #[clap(short, long, default_value_t = 0)]
+        duration: u64,
+    },
 }
 
 pub async fn handle_command(command: SwitchSubcommands) -> Result<()> {
     match command {
         SwitchSubcommands::Profile { profile_name } => profile_command(profile_name).await,
         SwitchSubcommands::Slot { index } => slot_command(index).await,
+        SwitchSubcommands::Blend {
+            index,
+            profile_name,
+            weight,
+        } => blend_command(index, profile_name, weight).await,
+        SwitchSubcommands::Fade { weight, duration } => fade_command(weight, duration).await,
     }
 }
 
@@ -98,6 +129,79 @@ async fn slot_command(index: usize) -> Result<()> {
     Ok(())
 }
 
+async fn blend_command(index: usize, profile_name: Option<String>, weight: f64) -> Result<()> {
+    if !(1..=constants::NUM_SLOTS).contains(&index) {
+        eprintln!(
+            "Slot index out of bounds. Valid range is: {}-{}",
+            1,
+            constants::NUM_SLOTS
+        );
+
+        return Ok(());
+    }
+
+    let profile_path = match profile_name {
+        Some(profile_name) => {
+            let profile_path = PathBuf::from(&profile_name);
+
+            let profile_path = if profile_path.is_file() {
+                Ok(profile_path.canonicalize()?)
+            } else {
+                util::match_profile_path(&profile_name)
+            };
+
+            match profile_path {
+                Ok(profile_path) => {
+                    println!(
+                        "Blending profile {} into slot {} ({:.0}%)",
+                        profile_path.display().to_string().bold(),
+                        format!("{}", index).bold(),
+                        weight * 100.0
+                    );
+
+                    profile_path.to_string_lossy().to_string()
+                }
+
+                Err(_e) => {
+                    eprintln!("No matches found");
+
+                    return Ok(());
+                }
+            }
+        }
+
+        None => {
+            println!(
+                "Removing the blended profile from slot {}",
+                format!("{}", index).bold()
+            );
+
+            String::new()
+        }
+    };
+
+    set_slot_blend(index - 1, &profile_path, weight)
+        .await
+        .wrap_err("Could not set the blended profile")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    Ok(())
+}
+
+async fn fade_command(weight: f64, duration: u64) -> Result<()> {
+    println!(
+        "Fading the blend weight to {}%",
+        format!("{:.0}", weight * 100.0).bold()
+    );
+
+    fade_blend_weight(weight, duration)
+        .await
+        .wrap_err("Could not fade the blend weight")
+        .suggestion("Please verify that the active slot blends two profiles")?;
+
+    Ok(())
+}
+
 /// Switch the currently active profile
 async fn switch_profile(name: &str) -> Result<()> {
     let file_name = name.to_owned();
@@ -119,3 +223,31 @@ async fn switch_slot(index: usize) -> Result<()> {
 
     Ok(())
 }
+
+/// Blend the profile `profile_file` into the slot `index`, an empty file name removes the blend
+async fn set_slot_blend(index: usize, profile_file: &str, weight: f64) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/slot")
+        .await?
+        .method_call(
+            "org.eruption.Slot",
+            "SetSlotBlend",
+            (index as u64, profile_file, weight),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Fade the blend weight of the active slot
+async fn fade_blend_weight(weight: f64, duration_millis: u64) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/slot")
+        .await?
+        .method_call(
+            "org.eruption.Slot",
+            "FadeBlendWeight",
+            (weight, duration_millis),
+        )
+        .await?;
+
+    Ok(())
+}
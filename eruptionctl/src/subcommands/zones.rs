@@ -0,0 +1,186 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use color_eyre::Help;
+use colored::*;
+use eruption_sdk::connection::{Connection, ConnectionType};
+use eyre::Context;
+
+use crate::dbus_client::dbus_system_bus;
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Sub-commands of the "zones" command
+#[derive(Debug, clap::Parser)]
+pub enum ZonesSubcommands {
+    /// Show all zones, along with their brightness and speed in the active profile
+    #[clap(display_order = 0)]
+    List,
+
+    /// Scale the brightness and/or the animation speed of a zone of the active profile
+    #[clap(display_order = 1)]
+    Set {
+        /// The name of the zone, like e.g. "numpad"
+        zone: String,
+
+        /// Brightness of the zone in percent, relative to the global brightness
+        #[clap(short, long)]
+        brightness: Option<i32>,
+
+        /// Factor that scales the speed of the animations of the zone, like e.g. 2.0
+        #[clap(short, long)]
+        speed: Option<f64>,
+    },
+
+    /// Restore the default brightness and speed of a zone of the active profile
+    #[clap(display_order = 2)]
+    Reset {
+        /// The name of the zone, like e.g. "numpad"
+        zone: String,
+    },
+}
+
+pub async fn handle_command(command: ZonesSubcommands) -> Result<()> {
+    match command {
+        ZonesSubcommands::List => list_command().await,
+        ZonesSubcommands::Set {
+            zone,
+            brightness,
+            speed,
+        } => set_command(zone, brightness, speed).await,
+        ZonesSubcommands::Reset { zone } => reset_command(zone).await,
+    }
+}
+
+async fn list_command() -> Result<()> {
+    let controls = get_zone_controls()
+        .await
+        .wrap_err("Could not query the zone controls")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    let connection = Connection::new(ConnectionType::Local)?;
+    connection
+        .connect()
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    let zones = connection.get_zones()?;
+
+    for zone in zones {
+        let (brightness, speed) = controls
+            .iter()
+            .find(|(name, _, _)| *name == zone.name)
+            .map_or((100, 1.0), |(_, brightness, speed)| (*brightness, *speed));
+
+        println!(
+            "{}: {} keys, brightness {}%, speed {}",
+            zone.name.bold(),
+            zone.keys.len(),
+            brightness,
+            format!("{:.2}", speed).bold()
+        );
+    }
+
+    Ok(())
+}
+
+async fn set_command(zone: String, brightness: Option<i32>, speed: Option<f64>) -> Result<()> {
+    // keep the current values of the settings that have not been specified
+    let (_, current_brightness, current_speed) = get_zone_controls()
+        .await
+        .wrap_err("Could not query the zone controls")
+        .suggestion("Please verify that the Eruption daemon is running")?
+        .into_iter()
+        .find(|(name, _, _)| *name == zone)
+        .unwrap_or((zone.clone(), 100, 1.0));
+
+    let brightness = brightness.unwrap_or(current_brightness);
+    let speed = speed.unwrap_or(current_speed);
+
+    println!(
+        "Setting zone {} to brightness {}%, speed {}",
+        zone.bold(),
+        brightness,
+        format!("{:.2}", speed).bold()
+    );
+
+    set_zone_control(&zone, brightness, speed)
+        .await
+        .wrap_err("Could not set the zone control")
+        .suggestion("Please run 'eruptionctl zones list' to show all zones")?;
+
+    Ok(())
+}
+
+async fn reset_command(zone: String) -> Result<()> {
+    let reset = reset_zone_control(&zone)
+        .await
+        .wrap_err("Could not reset the zone control")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    if reset {
+        println!(
+            "Restored the default brightness and speed of zone {}",
+            zone.bold()
+        );
+    } else {
+        println!(
+            "The zone {} is not controlled by the active profile",
+            zone.bold()
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the zone controls of the active profile
+async fn get_zone_controls() -> Result<Vec<(String, i32, f64)>> {
+    let (result,): (Vec<(String, i32, f64)>,) = dbus_system_bus("/org/eruption/profile")
+        .await?
+        .method_call("org.eruption.Profile", "GetZoneControls", ())
+        .await?;
+
+    Ok(result)
+}
+
+/// Assign the brightness and speed of a zone of the active profile
+async fn set_zone_control(zone: &str, brightness: i32, speed: f64) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/profile")
+        .await?
+        .method_call(
+            "org.eruption.Profile",
+            "SetZoneControl",
+            (zone, brightness, speed),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Restore the default brightness and speed of a zone of the active profile
+async fn reset_zone_control(zone: &str) -> Result<bool> {
+    let (result,): (bool,) = dbus_system_bus("/org/eruption/profile")
+        .await?
+        .method_call("org.eruption.Profile", "ResetZoneControl", (zone,))
+        .await?;
+
+    Ok(result)
+}
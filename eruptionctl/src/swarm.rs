@@ -0,0 +1,541 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Conversion of profiles in the Swarm interchange format to Eruption profiles
+//!
+//! The ROCCAT Swarm software stores its profiles in a proprietary format, which is not
+//! supported. The interchange format is a JSON description of the lighting, the macros and
+//! the DPI stages of a Swarm profile, that has to be transcribed from the settings shown by
+//! Swarm. Keys of the lighting layers are identified by Eruption key indices, the keys of
+//! macros by the Windows virtual key codes that Swarm records
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::profiles::{KeyLabel, MouseBinding, Profile};
+use crate::scripting::parameters::{ProfileConfiguration, ProfileParameter, TypedValue};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SwarmError {
+    #[error("Invalid color '{color}' in layer '{layer}'")]
+    InvalidColor { layer: String, color: String },
+}
+
+/// A profile in the Swarm interchange format
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SwarmProfile {
+    pub name: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    #[serde(default)]
+    pub lighting: SwarmLighting,
+
+    #[serde(default)]
+    pub macros: Vec<SwarmMacro>,
+
+    #[serde(default)]
+    pub dpi: Option<SwarmDpi>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SwarmLighting {
+    /// Color of all keys that are not part of a layer
+    #[serde(default)]
+    pub base: Option<String>,
+
+    /// Layers of per-key colors, later layers are painted on top of earlier ones
+    #[serde(default)]
+    pub layers: Vec<SwarmLayer>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SwarmLayer {
+    #[serde(default)]
+    pub name: String,
+
+    pub color: String,
+
+    /// Eruption key indices of the keys of the layer
+    pub keys: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SwarmMacro {
+    pub name: String,
+
+    /// Button index of the mouse button that the macro is assigned to
+    #[serde(default)]
+    pub button: Option<u8>,
+
+    pub events: Vec<SwarmMacroEvent>,
+}
+
+/// A recorded key event of a macro; keys are identified by Windows virtual key codes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SwarmMacroEvent {
+    pub vk: u32,
+    pub down: bool,
+
+    /// Delay before the event in milliseconds
+    #[serde(default)]
+    pub delay: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SwarmDpi {
+    pub stages: Vec<SwarmDpiStage>,
+
+    /// Index of the stage that is active after switching to the profile
+    #[serde(default)]
+    pub active: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SwarmDpiStage {
+    pub dpi: i32,
+
+    #[serde(default = "default_stage_enabled")]
+    pub enabled: bool,
+}
+
+fn default_stage_enabled() -> bool {
+    true
+}
+
+/// The result of the conversion of a Swarm profile
+#[derive(Debug, Clone)]
+pub struct SwarmImport {
+    pub profile: Profile,
+
+    /// Lua source of the macro definitions, `None` if the profile does not define macros
+    pub macros: Option<String>,
+
+    /// The DPI of the active stage, it may be applied via `eruptionctl devices dpi`
+    pub dpi: Option<i32>,
+
+    /// Parts of the Swarm profile that could not be converted
+    pub warnings: Vec<String>,
+}
+
+/// Make `text` safe for use in a Lua comment, control characters like e.g. line breaks
+/// would end the comment
+fn lua_comment(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+/// Returns the file name of an imported profile, derived from its name
+pub fn file_stem_for(name: &str) -> String {
+    let stem = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+
+    let stem = stem
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if stem.is_empty() {
+        "swarm-import".to_string()
+    } else {
+        stem
+    }
+}
+
+/// Convert the Swarm profile `swarm` to an Eruption profile that will be saved to
+/// `profile_file`. Macros are converted to the Lua module `macro_module`, like e.g.
+/// "macros/swarm-fps", that is required by the "Macros" script of the profile
+pub fn convert(
+    swarm: &SwarmProfile,
+    profile_file: &Path,
+    macro_module: &str,
+) -> Result<SwarmImport> {
+    let mut warnings = Vec::new();
+
+    let mut profile = Profile {
+        profile_file: profile_file.to_path_buf(),
+        name: swarm.name.clone(),
+        description: if swarm.description.is_empty() {
+            "Imported from the Swarm interchange format".to_string()
+        } else {
+            swarm.description.clone()
+        },
+        active_scripts: vec![],
+        config: ProfileConfiguration::new(),
+        ..Default::default()
+    };
+
+    // color layers
+    if let Some(base) = &swarm.lighting.base {
+        profile.active_scripts.push(PathBuf::from("solid.lua"));
+        profile.config.set_parameter(
+            "Solid Color",
+            ProfileParameter {
+                name: "color_background".to_string(),
+                value: TypedValue::Color(parse_color("base", base)?),
+                manifest: None,
+            },
+        );
+    }
+
+    for layer in swarm.lighting.layers.iter() {
+        let color = parse_color(&layer.name, &layer.color)?;
+
+        for key in layer.keys.iter() {
+            // later layers are painted on top of earlier ones
+            profile.labels.retain(|label| label.key != *key);
+            profile.labels.push(KeyLabel {
+                key: *key,
+                label: layer.name.clone(),
+                color: Some(color),
+            });
+        }
+    }
+
+    if !profile.labels.is_empty() {
+        profile.labels.sort_by_key(|label| label.key);
+        profile.active_scripts.push(PathBuf::from("key-labels.lua"));
+    }
+
+    // macros
+    let macros = if swarm.macros.is_empty() {
+        None
+    } else {
+        profile.active_scripts.push(PathBuf::from("macros.lua"));
+        profile.config.set_parameter(
+            "Macros",
+            ProfileParameter {
+                name: "requires".to_string(),
+                value: TypedValue::String(macro_module.to_string()),
+                manifest: None,
+            },
+        );
+
+        let mut source = String::new();
+
+        writeln!(
+            source,
+            "-- Macro definitions of the profile '{}',",
+            lua_comment(&swarm.name)
+        )?;
+        writeln!(source, "-- imported from the Swarm interchange format")?;
+        writeln!(source, "require \"macros/user-macros\"")?;
+
+        for (index, swarm_macro) in swarm.macros.iter().enumerate() {
+            writeln!(source)?;
+            writeln!(source, "-- {}", lua_comment(&swarm_macro.name))?;
+            writeln!(source, "function swarm_macro_{}()", index + 1)?;
+
+            for event in swarm_macro.events.iter() {
+                match vk_to_ev_key(event.vk) {
+                    Some(ev_key) => {
+                        if event.delay > 0 {
+                            writeln!(source, "    delay({})", event.delay)?;
+                        }
+
+                        writeln!(source, "    inject_key({}, {})", ev_key, event.down)?;
+                    }
+
+                    None => warnings.push(format!(
+                        "Macro '{}': Skipped unsupported virtual key code 0x{:02x}",
+                        swarm_macro.name, event.vk
+                    )),
+                }
+            }
+
+            writeln!(source, "end")?;
+
+            if let Some(button) = swarm_macro.button {
+                match mouse_binding_keys(swarm_macro) {
+                    Some(keys) => profile.mouse_bindings.push(MouseBinding {
+                        button,
+                        keys,
+                        remap: None,
+                        action: None,
+                    }),
+
+                    None => warnings.push(format!(
+                        "Macro '{}': Only key combinations may be bound to mouse buttons, use \
+                         the function 'swarm_macro_{}()' instead",
+                        swarm_macro.name,
+                        index + 1
+                    )),
+                }
+            }
+        }
+
+        Some(source)
+    };
+
+    // DPI stages
+    let dpi = swarm.dpi.as_ref().and_then(|dpi| {
+        let stage = dpi
+            .stages
+            .get(dpi.active)
+            .filter(|stage| stage.enabled)
+            .or_else(|| dpi.stages.iter().find(|stage| stage.enabled));
+
+        if dpi.stages.iter().filter(|stage| stage.enabled).count() > 1 {
+            warnings.push(
+                "DPI stages are not supported by Eruption profiles, only the DPI of the active \
+                 stage has been imported"
+                    .to_string(),
+            );
+        }
+
+        stage.map(|stage| stage.dpi)
+    });
+
+    Ok(SwarmImport {
+        profile,
+        macros,
+        dpi,
+        warnings,
+    })
+}
+
+/// Parse a color like "#ff0000" to ARGB
+fn parse_color(layer: &str, color: &str) -> Result<u32> {
+    let rgba = csscolorparser::parse(color)
+        .map_err(|_| SwarmError::InvalidColor {
+            layer: layer.to_string(),
+            color: color.to_string(),
+        })?
+        .to_rgba8();
+
+    Ok(u32::from_be_bytes([rgba[3], rgba[0], rgba[1], rgba[2]]))
+}
+
+/// Returns the EV_KEY codes of a macro that presses a key combination only, in the
+/// order in which the keys are pressed. Returns `None` for all other macros
+fn mouse_binding_keys(swarm_macro: &SwarmMacro) -> Option<Vec<u32>> {
+    let mut keys = Vec::new();
+    let mut released = false;
+
+    for event in swarm_macro.events.iter() {
+        let ev_key = vk_to_ev_key(event.vk)?;
+
+        if event.down {
+            // a key that is pressed after another key has been released, or a key
+            // that is pressed twice, is not part of a key combination
+            if released || keys.contains(&ev_key) {
+                return None;
+            }
+
+            keys.push(ev_key);
+        } else {
+            released = true;
+        }
+    }
+
+    if keys.is_empty() {
+        None
+    } else {
+        Some(keys)
+    }
+}
+
+/// Map a Windows virtual key code to the respective EV_KEY code
+fn vk_to_ev_key(vk: u32) -> Option<u32> {
+    const LETTERS: [u32; 26] = [
+        30, 48, 46, 32, 18, 33, 34, 35, 23, 36, 37, 38, 50, 49, 24, 25, 16, 19, 31, 20, 22, 47, 17,
+        45, 21, 44,
+    ];
+
+    let ev_key = match vk {
+        // A - Z
+        0x41..=0x5a => LETTERS[(vk - 0x41) as usize],
+
+        // 0 - 9
+        0x30 => 11,
+        0x31..=0x39 => vk - 0x31 + 2,
+
+        // F1 - F12
+        0x70..=0x79 => vk - 0x70 + 59,
+        0x7a => 87,
+        0x7b => 88,
+
+        0x08 => 14,  // Backspace
+        0x09 => 15,  // Tab
+        0x0d => 28,  // Enter
+        0x14 => 58,  // Caps Lock
+        0x1b => 1,   // Escape
+        0x20 => 57,  // Space
+        0x21 => 104, // Page Up
+        0x22 => 109, // Page Down
+        0x23 => 107, // End
+        0x24 => 102, // Home
+        0x25 => 105, // Left
+        0x26 => 103, // Up
+        0x27 => 106, // Right
+        0x28 => 108, // Down
+        0x2d => 110, // Insert
+        0x2e => 111, // Delete
+
+        0x10 | 0xa0 => 42, // (Left) Shift
+        0xa1 => 54,        // Right Shift
+        0x11 | 0xa2 => 29, // (Left) Ctrl
+        0xa3 => 97,        // Right Ctrl
+        0x12 | 0xa4 => 56, // (Left) Alt
+        0xa5 => 100,       // Right Alt
+        0x5b => 125,       // Left Super
+        0x5c => 126,       // Right Super
+
+        _ => return None,
+    };
+
+    Some(ev_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SWARM_PROFILE: &str = r##"
+{
+    "name": "FPS Games",
+    "lighting": {
+        "base": "#000000",
+        "layers": [
+            { "name": "WASD", "color": "#ff0000", "keys": [30, 44, 45, 46] },
+            { "name": "Reload", "color": "#00ff00", "keys": [46] }
+        ]
+    },
+    "macros": [
+        {
+            "name": "Copy",
+            "button": 4,
+            "events": [
+                { "vk": 17, "down": true },
+                { "vk": 67, "down": true },
+                { "vk": 67, "down": false },
+                { "vk": 17, "down": false }
+            ]
+        },
+        {
+            "name": "Greet",
+            "button": 5,
+            "events": [
+                { "vk": 72, "down": true },
+                { "vk": 72, "down": false },
+                { "vk": 73, "down": true, "delay": 20 },
+                { "vk": 73, "down": false }
+            ]
+        }
+    ],
+    "dpi": {
+        "stages": [
+            { "dpi": 400 },
+            { "dpi": 800 },
+            { "dpi": 1600, "enabled": false }
+        ],
+        "active": 1
+    }
+}
+"##;
+
+    #[test]
+    fn converts_swarm_profile() -> Result<()> {
+        let swarm: SwarmProfile = serde_json::from_str(SWARM_PROFILE)?;
+
+        let import = convert(
+            &swarm,
+            Path::new("fps-games.profile"),
+            "macros/swarm-fps-games",
+        )?;
+
+        assert_eq!(
+            import.profile.active_scripts,
+            vec![
+                PathBuf::from("solid.lua"),
+                PathBuf::from("key-labels.lua"),
+                PathBuf::from("macros.lua")
+            ]
+        );
+
+        // the later layer is painted on top of the earlier one
+        assert_eq!(import.profile.labels.len(), 4);
+        assert_eq!(import.profile.labels[3].key, 46);
+        assert_eq!(import.profile.labels[3].color, Some(0xff00ff00));
+
+        // only the key combination may be bound to a mouse button
+        assert_eq!(import.profile.mouse_bindings.len(), 1);
+        assert_eq!(import.profile.mouse_bindings[0].button, 4);
+        assert_eq!(import.profile.mouse_bindings[0].keys, vec![29, 46]);
+
+        let macros = import.macros.unwrap();
+        assert!(macros.contains("function swarm_macro_2()"));
+        assert!(macros.contains("    delay(20)\n    inject_key(23, true)"));
+
+        assert_eq!(import.dpi, Some(800));
+        assert_eq!(import.warnings.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn names_can_not_inject_code() -> Result<()> {
+        let swarm: SwarmProfile = serde_json::from_str(
+            r#"{
+                "name": "Profile\nos.execute('id')",
+                "macros": [
+                    {
+                        "name": "Macro\r\nos.execute('id')\n--",
+                        "events": [{ "vk": 65, "down": true }, { "vk": 65, "down": false }]
+                    }
+                ]
+            }"#,
+        )?;
+
+        let import = convert(&swarm, Path::new("profile.profile"), "macros/swarm-profile")?;
+        let macros = import.macros.unwrap();
+
+        assert!(macros
+            .lines()
+            .filter(|line| line.contains("os.execute"))
+            .all(|line| line.starts_with("--")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn derives_file_stem_from_name() {
+        assert_eq!(file_stem_for("FPS Games (2)"), "fps-games-2");
+        assert_eq!(file_stem_for("***"), "swarm-import");
+    }
+}
@@ -19,6 +19,7 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -64,6 +65,15 @@ pub fn get_script_dirs() -> Vec<PathBuf> {
     )
 }
 
+/// Returns the script directory of the current user, like e.g. `~/.local/share/eruption/scripts`
+pub fn get_user_script_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .map(|dir| dir.join("eruption/scripts"))
+}
+
 fn get_dirs(config_key: &str, fallback_dir: &str, fallback_description: &str) -> Vec<PathBuf> {
     let config = crate::CONFIG.lock();
 
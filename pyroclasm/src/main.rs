@@ -54,6 +54,7 @@ mod highlighting;
 mod profiles;
 mod resources;
 mod scripting;
+mod state_file;
 mod subcommands;
 mod threads;
 mod translations;
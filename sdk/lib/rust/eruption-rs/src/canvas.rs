@@ -22,9 +22,10 @@
 use crate::color::Color;
 use std::ops;
 
-const CANVAS_SIZE: usize = 144 + 36;
+/// The number of "pixels" on the canvas
+pub const CANVAS_SIZE: usize = 144 + 36;
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Canvas {
     pub(crate) data: Vec<Color>,
 }
@@ -20,11 +20,17 @@
 */
 
 use crate::canvas::Canvas;
-use crate::hardware::HotplugInfo;
-use crate::transport::{LocalTransport, ServerStatus, Transport};
+use crate::hardware::{HotplugInfo, KeyPosition, Zone};
+use crate::transport::{
+    CanvasOptions, CanvasSnapshot, FrameObserver, InputEvent, LocalTransport, NamedParameter,
+    ProtocolInfo, ServerStatus, SimulatedState, SimulatedTransport, SlotInfo, Transport,
+};
 use crate::Result;
 use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::ops::Deref;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Connection {
@@ -38,6 +44,12 @@ impl Connection {
         })
     }
 
+    fn with_transport(transport: impl Transport + 'static) -> Self {
+        Self {
+            con: Arc::new(Mutex::new(transport)),
+        }
+    }
+
     pub fn connect(&self) -> Result<()> {
         self.con.lock().connect()
     }
@@ -46,14 +58,91 @@ impl Connection {
         self.con.lock().disconnect()
     }
 
+    /// Returns the protocol version and the capabilities that have been negotiated
+    /// with the server during `connect()`
+    pub fn get_protocol_info(&self) -> ProtocolInfo {
+        self.con.lock().get_protocol_info()
+    }
+
     pub fn submit_canvas(&self, canvas: &Canvas) -> Result<()> {
         self.con.lock().submit_canvas(canvas)
     }
 
+    /// Submit a canvas that updates a range of keys only, and/or is shown on a subset
+    /// of the devices only. Partial updates fall back to submitting the whole canvas
+    /// if the server does not support them; device targeting fails in that case
+    pub fn submit_canvas_with(&self, canvas: &Canvas, options: &CanvasOptions) -> Result<()> {
+        self.con.lock().submit_canvas_with(canvas, options)
+    }
+
+    /// Submit a temporary overlay that is blended on top of all other effects, the overlay
+    /// is removed after `timeout` has elapsed. A timeout of zero keeps the overlay until it
+    /// is replaced; submitting a fully transparent canvas removes the overlay
+    pub fn submit_overlay(&self, canvas: &Canvas, timeout: Duration) -> Result<()> {
+        self.con.lock().submit_overlay(canvas, timeout)
+    }
+
+    /// Capture the current lighting state, e.g. before flashing a notification. The
+    /// snapshot contains the canvas as it is currently shown on the devices
+    pub fn snapshot_canvas(&self) -> Result<CanvasSnapshot> {
+        self.con.lock().snapshot_canvas()
+    }
+
+    /// Return to the lighting state that has been captured by `snapshot_canvas()`, the
+    /// active profile keeps running. Each snapshot may be restored once; returns `false`
+    /// if the snapshot is unknown to the server
+    pub fn restore_canvas(&self, snapshot: &CanvasSnapshot) -> Result<bool> {
+        self.con.lock().restore_canvas(snapshot)
+    }
+
+    /// Returns the slot that is currently active, along with the profile that is assigned
+    /// to it. Requires the `SLOT_TARGETING` capability
+    pub fn get_active_slot(&self) -> Result<SlotInfo> {
+        self.con.lock().get_active_slot()
+    }
+
+    /// Inject a synthetic input event into the event pipeline of the daemon, as if it had
+    /// been reported by the first keyboard or mouse. Returns once the event handlers of all
+    /// scripts have completed. Requires the `EVENT_INJECTION` capability and the
+    /// `org.eruption.manage` permission
+    pub fn inject_event(&self, event: InputEvent) -> Result<()> {
+        self.con.lock().inject_event(event)
+    }
+
+    /// Publish the named parameter `name`, that the scripts of the active profile may read
+    /// via `get_sdk_parameter(name)`. The value is clamped to the range `min` .. `max`.
+    /// Registering an existing parameter again replaces it. Parameters are removed when
+    /// the connection is closed
+    pub fn register_named_parameter(
+        &self,
+        name: &str,
+        min: f64,
+        max: f64,
+        value: f64,
+    ) -> Result<bool> {
+        self.con
+            .lock()
+            .register_named_parameter(name, min, max, value)
+    }
+
+    /// Update the value of a named parameter, returns `false` if it has not been registered
+    pub fn set_named_parameter(&self, name: &str, value: f64) -> Result<bool> {
+        self.con.lock().set_named_parameter(name, value)
+    }
+
     pub fn get_server_status(&self) -> Result<ServerStatus> {
         self.con.lock().get_server_status()
     }
 
+    pub fn get_zones(&self) -> Result<Vec<Zone>> {
+        self.con.lock().get_zones()
+    }
+
+    /// Returns the physical positions of the keys of the connected keyboards
+    pub fn get_key_positions(&self) -> Result<Vec<KeyPosition>> {
+        self.con.lock().get_key_positions()
+    }
+
     pub fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()> {
         self.con.lock().notify_device_hotplug(hotplug_info)
     }
@@ -81,3 +170,110 @@ pub enum ConnectionType {
 fn make_transport(_connection_type: &ConnectionType) -> Result<impl Transport> {
     LocalTransport::new()
 }
+
+/// A connection that does not require a running Eruption daemon, e.g. for unit tests
+/// that run in CI. It provides the same API as a regular `Connection` (via `Deref`),
+/// but renders all submitted canvases into memory, where they may be inspected
+#[derive(Clone)]
+pub struct SimulatedConnection {
+    connection: Connection,
+    state: Arc<Mutex<SimulatedState>>,
+}
+
+impl SimulatedConnection {
+    pub fn new() -> Result<Self> {
+        let transport = SimulatedTransport::new()?;
+        let state = transport.state.clone();
+
+        Ok(Self {
+            connection: Connection::with_transport(transport),
+            state,
+        })
+    }
+
+    /// Register an observer that gets notified about each submitted frame,
+    /// replacing the previous observer
+    pub fn set_frame_observer(&self, observer: impl FrameObserver + 'static) {
+        self.state.lock().observer = Some(Box::new(observer));
+    }
+
+    pub fn clear_frame_observer(&self) {
+        self.state.lock().observer = None;
+    }
+
+    /// Set the zones that are reported by `get_zones()`
+    pub fn set_zones(&self, zones: Vec<Zone>) {
+        self.state.lock().zones = zones;
+    }
+
+    /// Set the key positions that are reported by `get_key_positions()`
+    pub fn set_key_positions(&self, key_positions: Vec<KeyPosition>) {
+        self.state.lock().key_positions = key_positions;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state.lock().connected
+    }
+
+    /// Returns the number of canvases and overlays that have been submitted so far
+    pub fn frame_count(&self) -> u64 {
+        self.state.lock().frame_count
+    }
+
+    /// Returns the most recently submitted canvas
+    pub fn canvas(&self) -> Canvas {
+        self.state.lock().canvas.clone()
+    }
+
+    /// Returns the devices that the most recent canvas has been targeted at,
+    /// `None` for all devices
+    pub fn target_devices(&self) -> Option<Vec<u64>> {
+        self.state.lock().target_devices.clone()
+    }
+
+    /// Returns the slots that the most recent canvas has been targeted at,
+    /// `None` for all slots
+    pub fn target_slots(&self) -> Option<Vec<u32>> {
+        self.state.lock().target_slots.clone()
+    }
+
+    /// Switch the simulated daemon to the slot `index` (starting at 0)
+    pub fn set_active_slot(&self, index: u32) {
+        self.state.lock().active_slot = index;
+    }
+
+    /// Returns the current overlay and its timeout. Timeouts are recorded but not
+    /// enforced, so that tests do not depend on timing
+    pub fn overlay(&self) -> Option<(Canvas, Duration)> {
+        self.state.lock().overlay.clone()
+    }
+
+    /// Returns the canvas with the current overlay blended on top of it,
+    /// like it would be shown on the devices in the active slot
+    pub fn rendered_canvas(&self) -> Canvas {
+        self.state.lock().rendered_canvas()
+    }
+
+    /// Returns the input events that have been injected via `inject_event()`, oldest first
+    pub fn injected_events(&self) -> Vec<InputEvent> {
+        self.state.lock().injected_events.clone()
+    }
+
+    /// Returns the named parameters that have been registered via `register_named_parameter()`
+    pub fn named_parameters(&self) -> HashMap<String, NamedParameter> {
+        self.state.lock().named_parameters.clone()
+    }
+
+    /// Returns the devices that have been announced via `notify_device_hotplug()`
+    pub fn hotplugged_devices(&self) -> Vec<HotplugInfo> {
+        self.state.lock().hotplugged_devices.clone()
+    }
+}
+
+impl Deref for SimulatedConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.connection
+    }
+}
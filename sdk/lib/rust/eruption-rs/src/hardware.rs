@@ -43,3 +43,23 @@ pub struct HotplugInfo {
     pub usb_vid: u16,
     pub usb_pid: u16,
 }
+
+/// A named group of keys, e.g. the WASD cluster or the numpad
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Zone {
+    pub name: String,
+
+    /// Key indices (1-based) of the keys that belong to the zone
+    pub keys: Vec<usize>,
+}
+
+/// The physical position of a key, e.g. for effects that are based on distances
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct KeyPosition {
+    /// Key index (1-based)
+    pub key: usize,
+
+    /// Center of the key in millimeters, relative to the top left corner of the key area
+    pub x: f32,
+    pub y: f32,
+}
@@ -20,8 +20,12 @@
 */
 
 use crate::canvas::Canvas;
-use crate::hardware::HotplugInfo;
-use crate::transport::{ServerStatus, Transport};
+use crate::color::Color;
+use crate::hardware::{HotplugInfo, KeyPosition, Zone};
+use crate::transport::{
+    CanvasOptions, CanvasSnapshot, Capabilities, InputEvent, ProtocolInfo, ServerStatus, SlotInfo,
+    Transport, PROTOCOL_VERSION,
+};
 use crate::{util, Result};
 use eyre::eyre;
 use parking_lot::Mutex;
@@ -32,17 +36,22 @@ use std::io::{Cursor, Write};
 use std::mem::MaybeUninit;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 pub mod protocol {
     include!(concat!(env!("OUT_DIR"), "/sdk_support.rs"));
 }
 
 const SOCKET_ADDRESS: &str = "/run/eruption/control.sock";
+
+/// Environment variable that holds the access token, if the server requires one
+const TOKEN_ENV_VAR: &str = "ERUPTION_SDK_TOKEN";
 const MAX_BUF: usize = 4096;
 
 #[derive(Debug, Clone)]
 pub struct LocalTransport {
     pub(crate) socket: Arc<Mutex<Socket>>,
+    pub(crate) protocol_info: ProtocolInfo,
 }
 
 impl LocalTransport {
@@ -53,8 +62,93 @@ impl LocalTransport {
                 Type::SEQPACKET,
                 None,
             )?)),
+            protocol_info: ProtocolInfo::default(),
         })
     }
+
+    /// Send a request to the server and wait for the response
+    fn call(&self, request: &protocol::Request) -> Result<protocol::Response> {
+        let mut buf = Vec::new();
+        request.encode_length_delimited(&mut buf)?;
+
+        // send data
+        let socket = self.socket.lock();
+        match socket.send(&buf) {
+            Ok(_n) => {
+                // read response
+                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+
+                match socket.recv(&mut tmp) {
+                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
+
+                    Ok(_n) => {
+                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
+
+                        Ok(protocol::Response::decode_length_delimited(
+                            &mut Cursor::new(&tmp),
+                        )?)
+                    }
+
+                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
+                }
+            }
+
+            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        }
+    }
+
+    /// Negotiate the protocol version and the capabilities with the server. Servers that
+    /// predate the handshake would drop the connection on an unknown request, so we only
+    /// perform the handshake if the server reports a protocol version in its status
+    fn handshake(&self) -> Result<ProtocolInfo> {
+        let status = self.get_server_status()?;
+
+        if status.protocol_version == 0 {
+            return Ok(ProtocolInfo::default());
+        }
+
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::Hello(
+                protocol::HelloRequest {
+                    protocol_version: PROTOCOL_VERSION,
+                    capabilities: Capabilities::ALL.0,
+                    token: std::env::var(TOKEN_ENV_VAR).unwrap_or_default(),
+                },
+            )),
+        };
+
+        if let Some(protocol::response::ResponseMessage::Hello(hello_response)) =
+            self.call(&request)?.response_message
+        {
+            Ok(ProtocolInfo {
+                version: hello_response.protocol_version,
+                capabilities: Capabilities(hello_response.capabilities) & Capabilities::ALL,
+            })
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    /// Encode the colors of a canvas, the payload is compressed if the server supports it
+    fn encode_canvas(&self, colors: &[Color]) -> (Vec<u8>, protocol::Compression) {
+        let bytes: Vec<u8> = colors
+            .iter()
+            .flat_map(|c| vec![c.r(), c.g(), c.b(), c.a()])
+            .collect();
+
+        if self
+            .protocol_info
+            .capabilities
+            .contains(Capabilities::COMPRESSION_LZ4)
+        {
+            (
+                lz4_flex::compress_prepend_size(&bytes),
+                protocol::Compression::Lz4,
+            )
+        } else {
+            (bytes, protocol::Compression::None)
+        }
+    }
 }
 
 impl Transport for LocalTransport {
@@ -62,6 +156,8 @@ impl Transport for LocalTransport {
         let addr = SockAddr::unix(SOCKET_ADDRESS)?;
         self.socket.lock().connect(&addr)?;
 
+        self.protocol_info = self.handshake()?;
+
         Ok(())
     }
 
@@ -72,6 +168,10 @@ impl Transport for LocalTransport {
         Ok(())
     }
 
+    fn get_protocol_info(&self) -> ProtocolInfo {
+        self.protocol_info
+    }
+
     fn get_server_status(&self) -> Result<ServerStatus> {
         let request = protocol::Request {
             request_message: Some(protocol::request::RequestMessage::Status(
@@ -101,6 +201,7 @@ impl Transport for LocalTransport {
                         {
                             Ok(ServerStatus {
                                 server: status_response.description,
+                                protocol_version: status_response.protocol_version,
                             })
                         } else {
                             Err(eyre!("Unexpected response"))
@@ -157,6 +258,34 @@ impl Transport for LocalTransport {
         }
     }
 
+    fn get_active_slot(&self) -> Result<SlotInfo> {
+        if !self
+            .protocol_info
+            .capabilities
+            .contains(Capabilities::SLOT_TARGETING)
+        {
+            return Err(eyre!("Slot targeting is not supported by Eruption"));
+        }
+
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::ActiveSlot(
+                protocol::ActiveSlotRequest {},
+            )),
+        };
+
+        if let Some(protocol::response::ResponseMessage::ActiveSlot(active_slot_response)) =
+            self.call(&request)?.response_message
+        {
+            Ok(SlotInfo {
+                index: active_slot_response.slot,
+                name: active_slot_response.slot_name,
+                profile_file: PathBuf::from(active_slot_response.profile_file),
+            })
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
     fn switch_profile(&self, profile_file: &Path) -> Result<bool> {
         let request = protocol::Request {
             request_message: Some(protocol::request::RequestMessage::SwitchProfile(
@@ -253,15 +382,74 @@ impl Transport for LocalTransport {
     }
 
     fn submit_canvas(&self, canvas: &Canvas) -> Result<()> {
-        let bytes: Vec<u8> = canvas
-            .data
-            .iter()
-            .flat_map(|c| vec![c.r(), c.g(), c.b(), c.a()])
-            .collect();
+        self.submit_canvas_with(canvas, &CanvasOptions::default())
+    }
+
+    fn submit_canvas_with(&self, canvas: &Canvas, options: &CanvasOptions) -> Result<()> {
+        let capabilities = self.protocol_info.capabilities;
+
+        if options.devices.is_some() && !capabilities.contains(Capabilities::DEVICE_TARGETING) {
+            return Err(eyre!("Device targeting is not supported by Eruption"));
+        }
+
+        if options.slots.is_some() && !capabilities.contains(Capabilities::SLOT_TARGETING) {
+            return Err(eyre!("Slot targeting is not supported by Eruption"));
+        }
+
+        let keys = match &options.keys {
+            Some(keys) if keys.start > keys.end || keys.end > canvas.data.len() => {
+                return Err(eyre!("Invalid range of keys: {:?}", keys));
+            }
+
+            // servers without support for partial updates receive the whole canvas
+            Some(keys) if capabilities.contains(Capabilities::PARTIAL_UPDATES) => keys.clone(),
+
+            _ => 0..canvas.data.len(),
+        };
+
+        let partial = keys != (0..canvas.data.len());
+        let (bytes, compression) = self.encode_canvas(&canvas.data[keys.clone()]);
+
+        let mut set_canvas_request = protocol::SetCanvasRequest {
+            canvas: bytes,
+            partial,
+            offset: keys.start as u32,
+            devices: options.devices.clone().unwrap_or_default(),
+            slots: options.slots.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+
+        set_canvas_request.set_compression(compression);
 
         let request = protocol::Request {
             request_message: Some(protocol::request::RequestMessage::SetCanvas(
-                protocol::SetCanvasRequest { canvas: bytes },
+                set_canvas_request,
+            )),
+        };
+
+        if let Some(protocol::response::ResponseMessage::SetCanvas(_set_canvas_response)) =
+            self.call(&request)?.response_message
+        {
+            Ok(())
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn submit_overlay(&self, canvas: &Canvas, timeout: Duration) -> Result<()> {
+        let (bytes, compression) = self.encode_canvas(&canvas.data);
+
+        let mut set_overlay_request = protocol::SetOverlayRequest {
+            canvas: bytes,
+            timeout_millis: timeout.as_millis() as u64,
+            ..Default::default()
+        };
+
+        set_overlay_request.set_compression(compression);
+
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::SetOverlay(
+                set_overlay_request,
             )),
         };
 
@@ -282,8 +470,8 @@ impl Transport for LocalTransport {
                         let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
                         let result =
                             protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
-                        if let Some(protocol::response::ResponseMessage::SetCanvas(
-                            _set_canvas_response,
+                        if let Some(protocol::response::ResponseMessage::SetOverlay(
+                            _set_overlay_response,
                         )) = result.response_message
                         {
                             Ok(())
@@ -300,6 +488,300 @@ impl Transport for LocalTransport {
         }
     }
 
+    fn snapshot_canvas(&self) -> Result<CanvasSnapshot> {
+        if !self
+            .protocol_info
+            .capabilities
+            .contains(Capabilities::CANVAS_SNAPSHOTS)
+        {
+            return Err(eyre!("Canvas snapshots are not supported by Eruption"));
+        }
+
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::SnapshotCanvas(
+                protocol::SnapshotCanvasRequest {},
+            )),
+        };
+
+        if let Some(protocol::response::ResponseMessage::SnapshotCanvas(snapshot_response)) =
+            self.call(&request)?.response_message
+        {
+            let mut canvas = Canvas::new();
+
+            for (color, chunk) in canvas
+                .data
+                .iter_mut()
+                .zip(snapshot_response.canvas.chunks_exact(4))
+            {
+                *color = Color::new(chunk[0], chunk[1], chunk[2], chunk[3]);
+            }
+
+            Ok(CanvasSnapshot {
+                id: snapshot_response.snapshot_id,
+                canvas,
+            })
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn restore_canvas(&self, snapshot: &CanvasSnapshot) -> Result<bool> {
+        if !self
+            .protocol_info
+            .capabilities
+            .contains(Capabilities::CANVAS_SNAPSHOTS)
+        {
+            return Err(eyre!("Canvas snapshots are not supported by Eruption"));
+        }
+
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::RestoreCanvas(
+                protocol::RestoreCanvasRequest {
+                    snapshot_id: snapshot.id,
+                },
+            )),
+        };
+
+        if let Some(protocol::response::ResponseMessage::RestoreCanvas(restore_response)) =
+            self.call(&request)?.response_message
+        {
+            Ok(restore_response.restored)
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn inject_event(&self, event: InputEvent) -> Result<()> {
+        if !self
+            .protocol_info
+            .capabilities
+            .contains(Capabilities::EVENT_INJECTION)
+        {
+            return Err(eyre!("Event injection is not supported by Eruption"));
+        }
+
+        let (event_type, index) = match event {
+            InputEvent::KeyDown(index) => (protocol::InputEventType::KeyDown, index),
+            InputEvent::KeyUp(index) => (protocol::InputEventType::KeyUp, index),
+            InputEvent::MouseButtonDown(index) => {
+                (protocol::InputEventType::MouseButtonDown, index)
+            }
+            InputEvent::MouseButtonUp(index) => (protocol::InputEventType::MouseButtonUp, index),
+        };
+
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::InjectEvent(
+                protocol::InjectEventRequest {
+                    event_type: event_type as i32,
+                    index: index as u32,
+                },
+            )),
+        };
+
+        if let Some(protocol::response::ResponseMessage::InjectEvent(inject_response)) =
+            self.call(&request)?.response_message
+        {
+            if inject_response.injected {
+                Ok(())
+            } else {
+                Err(eyre!("Eruption refused to inject the event: {:?}", event))
+            }
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn get_zones(&self) -> Result<Vec<Zone>> {
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::GetZones(
+                protocol::GetZonesRequest {},
+            )),
+        };
+
+        let mut buf = Vec::new();
+        request.encode_length_delimited(&mut buf)?;
+
+        // send data
+        let socket = self.socket.lock();
+        match socket.send(&buf) {
+            Ok(_n) => {
+                // read response
+                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+
+                match socket.recv(&mut tmp) {
+                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
+
+                    Ok(_n) => {
+                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
+                        let result =
+                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
+                        if let Some(protocol::response::ResponseMessage::GetZones(
+                            get_zones_response,
+                        )) = result.response_message
+                        {
+                            Ok(get_zones_response
+                                .zones
+                                .into_iter()
+                                .map(|zone| Zone {
+                                    name: zone.name,
+                                    keys: zone.keys.iter().map(|k| *k as usize).collect(),
+                                })
+                                .collect())
+                        } else {
+                            Err(eyre!("Unexpected response"))
+                        }
+                    }
+
+                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
+                }
+            }
+
+            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        }
+    }
+
+    fn get_key_positions(&self) -> Result<Vec<KeyPosition>> {
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::GetKeyPositions(
+                protocol::GetKeyPositionsRequest {},
+            )),
+        };
+
+        let mut buf = Vec::new();
+        request.encode_length_delimited(&mut buf)?;
+
+        // send data
+        let socket = self.socket.lock();
+        match socket.send(&buf) {
+            Ok(_n) => {
+                // read response
+                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+
+                match socket.recv(&mut tmp) {
+                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
+
+                    Ok(_n) => {
+                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
+                        let result =
+                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
+                        if let Some(protocol::response::ResponseMessage::GetKeyPositions(
+                            get_key_positions_response,
+                        )) = result.response_message
+                        {
+                            Ok(get_key_positions_response
+                                .positions
+                                .into_iter()
+                                .map(|p| KeyPosition {
+                                    key: p.key as usize,
+                                    x: p.x,
+                                    y: p.y,
+                                })
+                                .collect())
+                        } else {
+                            Err(eyre!("Unexpected response"))
+                        }
+                    }
+
+                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
+                }
+            }
+
+            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        }
+    }
+
+    fn register_named_parameter(&self, name: &str, min: f64, max: f64, value: f64) -> Result<bool> {
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::RegisterNamedParameter(
+                protocol::RegisterNamedParameterRequest {
+                    name: name.to_string(),
+                    min,
+                    max,
+                    value,
+                },
+            )),
+        };
+
+        let mut buf = Vec::new();
+        request.encode_length_delimited(&mut buf)?;
+
+        // send data
+        let socket = self.socket.lock();
+        match socket.send(&buf) {
+            Ok(_n) => {
+                // read response
+                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+
+                match socket.recv(&mut tmp) {
+                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
+
+                    Ok(_n) => {
+                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
+                        let result =
+                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
+                        if let Some(protocol::response::ResponseMessage::RegisterNamedParameter(
+                            register_named_parameter_response,
+                        )) = result.response_message
+                        {
+                            Ok(register_named_parameter_response.registered)
+                        } else {
+                            Err(eyre!("Unexpected response"))
+                        }
+                    }
+
+                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
+                }
+            }
+
+            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        }
+    }
+
+    fn set_named_parameter(&self, name: &str, value: f64) -> Result<bool> {
+        let request = protocol::Request {
+            request_message: Some(protocol::request::RequestMessage::SetNamedParameter(
+                protocol::SetNamedParameterRequest {
+                    name: name.to_string(),
+                    value,
+                },
+            )),
+        };
+
+        let mut buf = Vec::new();
+        request.encode_length_delimited(&mut buf)?;
+
+        // send data
+        let socket = self.socket.lock();
+        match socket.send(&buf) {
+            Ok(_n) => {
+                // read response
+                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+
+                match socket.recv(&mut tmp) {
+                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
+
+                    Ok(_n) => {
+                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
+                        let result =
+                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
+                        if let Some(protocol::response::ResponseMessage::SetNamedParameter(
+                            set_named_parameter_response,
+                        )) = result.response_message
+                        {
+                            Ok(set_named_parameter_response.accepted)
+                        } else {
+                            Err(eyre!("Unexpected response"))
+                        }
+                    }
+
+                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
+                }
+            }
+
+            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        }
+    }
+
     fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()> {
         let config = bincode::config::standard();
         let bytes: Vec<u8> = bincode::encode_to_vec(hotplug_info, config).unwrap();
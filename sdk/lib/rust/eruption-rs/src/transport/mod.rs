@@ -20,21 +20,148 @@
 */
 
 use std::collections::HashMap;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use crate::canvas::Canvas;
-use crate::hardware::HotplugInfo;
+use crate::hardware::{HotplugInfo, KeyPosition, Zone};
 use crate::Result;
 
 mod local;
+mod simulated;
 pub use local::*;
+pub use simulated::*;
+
+/// Version of the wire protocol that is implemented by this library
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Optional features of the wire protocol, negotiated during the handshake
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities(pub u32);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+
+    /// Canvas payloads may be compressed with LZ4
+    pub const COMPRESSION_LZ4: Self = Self(1);
+
+    /// A canvas may update a contiguous range of keys only
+    pub const PARTIAL_UPDATES: Self = Self(2);
+
+    /// A canvas may be shown on a subset of the devices only
+    pub const DEVICE_TARGETING: Self = Self(4);
+
+    /// The composited canvas may be captured, and the captured state may be restored later
+    pub const CANVAS_SNAPSHOTS: Self = Self(8);
+
+    /// The active slot may be queried, and a canvas may be shown in a subset of the slots only
+    pub const SLOT_TARGETING: Self = Self(16);
+
+    /// Synthetic input events may be injected, requires the `org.eruption.manage` permission
+    pub const EVENT_INJECTION: Self = Self(32);
+
+    /// All capabilities that are supported by this library
+    pub const ALL: Self = Self(1 | 2 | 4 | 8 | 16 | 32);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+/// The result of the protocol handshake
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolInfo {
+    /// Protocol version of the server, 0 if the server does not support the handshake
+    pub version: u32,
+
+    /// Capabilities that are supported by both the client and the server
+    pub capabilities: Capabilities,
+}
+
+/// Options of a canvas update
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CanvasOptions {
+    /// Update the keys in this range (0-based indices into the canvas) only,
+    /// all other keys keep their current colors
+    pub keys: Option<Range<usize>>,
+
+    /// Indices of the devices that show the canvas, `None` for all devices
+    pub devices: Option<Vec<u64>>,
+
+    /// Indices of the slots (starting at 0) in which the canvas is shown, `None` for all
+    /// slots. While another slot is active, the server suppresses the canvas
+    pub slots: Option<Vec<u32>>,
+}
+
+/// The slot that is currently active on the server
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SlotInfo {
+    /// Index of the slot, starting at 0
+    pub index: u32,
+
+    pub name: String,
+
+    /// The profile that is assigned to the slot
+    pub profile_file: PathBuf,
+}
+
+/// A named parameter that has been published via `register_named_parameter()`, the
+/// scripts of the active profile may read it via `get_sdk_parameter(name)`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct NamedParameter {
+    pub min: f64,
+    pub max: f64,
+
+    /// The current value, clamped to the range `min` .. `max`
+    pub value: f64,
+}
+
+/// A synthetic input event, see `inject_event()`. Keys and buttons are identified by the
+/// indices that are passed to the event handlers of the Lua scripts, like e.g. `on_key_down`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyDown(u8),
+    KeyUp(u8),
+    MouseButtonDown(u8),
+    MouseButtonUp(u8),
+}
+
+/// A snapshot of the lighting state, captured via `snapshot_canvas()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanvasSnapshot {
+    /// Identifies the snapshot on the server
+    pub id: u32,
+
+    /// The canvas as it has been shown on the devices at the time of the snapshot
+    pub canvas: Canvas,
+}
 
 pub trait Transport {
     fn connect(&mut self) -> Result<()>;
     fn disconnect(&mut self) -> Result<()>;
 
+    fn get_protocol_info(&self) -> ProtocolInfo;
+
     fn get_server_status(&self) -> Result<ServerStatus>;
     fn get_active_profile(&self) -> Result<PathBuf>;
+    fn get_active_slot(&self) -> Result<SlotInfo>;
     fn switch_profile(&self, profile_file: &Path) -> Result<bool>;
     fn set_parameters(
         &self,
@@ -43,6 +170,15 @@ pub trait Transport {
         parameter_values: HashMap<String, String>,
     ) -> Result<()>;
     fn submit_canvas(&self, canvas: &Canvas) -> Result<()>;
+    fn submit_canvas_with(&self, canvas: &Canvas, options: &CanvasOptions) -> Result<()>;
+    fn submit_overlay(&self, canvas: &Canvas, timeout: Duration) -> Result<()>;
+    fn snapshot_canvas(&self) -> Result<CanvasSnapshot>;
+    fn restore_canvas(&self, snapshot: &CanvasSnapshot) -> Result<bool>;
+    fn get_zones(&self) -> Result<Vec<Zone>>;
+    fn get_key_positions(&self) -> Result<Vec<KeyPosition>>;
+    fn inject_event(&self, event: InputEvent) -> Result<()>;
+    fn register_named_parameter(&self, name: &str, min: f64, max: f64, value: f64) -> Result<bool>;
+    fn set_named_parameter(&self, name: &str, value: f64) -> Result<bool>;
 
     fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()>;
 }
@@ -50,4 +186,7 @@ pub trait Transport {
 #[derive(Debug, Default, Clone)]
 pub struct ServerStatus {
     pub server: String,
+
+    /// Protocol version of the server, 0 if the server predates the handshake
+    pub protocol_version: u32,
 }
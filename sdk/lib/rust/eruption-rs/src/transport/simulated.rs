@@ -0,0 +1,425 @@
+/*  SPDX-License-Identifier: LGPL-3.0-or-later  */
+
+/*
+    This file is part of the Eruption SDK.
+
+    The Eruption SDK is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Lesser General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    The Eruption SDK is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Lesser General Public License for more details.
+
+    You should have received a copy of the GNU Lesser General Public License
+    along with the Eruption SDK.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::hardware::{HotplugInfo, KeyPosition, Zone};
+use crate::transport::{
+    CanvasOptions, CanvasSnapshot, Capabilities, InputEvent, NamedParameter, ProtocolInfo,
+    ServerStatus, SlotInfo, Transport, PROTOCOL_VERSION,
+};
+use crate::Result;
+use eyre::eyre;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Max number of named parameters that may be registered, like in the daemon
+const MAX_NAMED_PARAMETERS: usize = 64;
+
+/// A frame that has been submitted to a simulated connection
+#[derive(Debug, Clone, Copy)]
+pub enum Frame<'a> {
+    /// A canvas submitted via `submit_canvas()`, partial updates are
+    /// merged into the previous canvas
+    Canvas(&'a Canvas),
+
+    /// An overlay submitted via `submit_overlay()`
+    Overlay {
+        canvas: &'a Canvas,
+        timeout: Duration,
+    },
+}
+
+/// Gets notified about each frame that is submitted to a simulated connection
+pub trait FrameObserver: Send {
+    fn on_frame(&mut self, frame: &Frame);
+}
+
+impl<F> FrameObserver for F
+where
+    F: FnMut(&Frame) + Send,
+{
+    fn on_frame(&mut self, frame: &Frame) {
+        self(frame)
+    }
+}
+
+/// The state of the simulated daemon
+#[derive(Default)]
+pub(crate) struct SimulatedState {
+    pub(crate) connected: bool,
+
+    pub(crate) canvas: Canvas,
+    pub(crate) target_devices: Option<Vec<u64>>,
+    pub(crate) target_slots: Option<Vec<u32>>,
+    pub(crate) overlay: Option<(Canvas, Duration)>,
+    pub(crate) frame_count: u64,
+
+    /// Snapshots captured via `snapshot_canvas()`, along with the overlay at that time
+    pub(crate) snapshots: HashMap<u32, SimulatedSnapshot>,
+    pub(crate) next_snapshot_id: u32,
+
+    pub(crate) active_profile: PathBuf,
+    pub(crate) active_slot: u32,
+    pub(crate) parameters: HashMap<(PathBuf, PathBuf), HashMap<String, String>>,
+    pub(crate) zones: Vec<Zone>,
+    pub(crate) key_positions: Vec<KeyPosition>,
+    pub(crate) hotplugged_devices: Vec<HotplugInfo>,
+    pub(crate) injected_events: Vec<InputEvent>,
+    pub(crate) named_parameters: HashMap<String, NamedParameter>,
+
+    pub(crate) observer: Option<Box<dyn FrameObserver>>,
+}
+
+/// The state of the simulated daemon at the time a snapshot has been captured
+pub(crate) struct SimulatedSnapshot {
+    canvas: Canvas,
+    target_devices: Option<Vec<u64>>,
+    target_slots: Option<Vec<u32>>,
+    overlay: Option<(Canvas, Duration)>,
+}
+
+impl SimulatedState {
+    fn ensure_connected(&self) -> Result<()> {
+        if self.connected {
+            Ok(())
+        } else {
+            Err(eyre!("Not connected to Eruption"))
+        }
+    }
+
+    /// Returns `true` if the canvas is shown in the active slot
+    pub(crate) fn is_canvas_visible(&self) -> bool {
+        match &self.target_slots {
+            Some(slots) => slots.contains(&self.active_slot),
+            None => true,
+        }
+    }
+
+    /// Returns the canvas with the current overlay blended on top of it. While another
+    /// slot is active than the canvas has been targeted at, the overlay is shown only
+    pub(crate) fn rendered_canvas(&self) -> Canvas {
+        let mut result = if self.is_canvas_visible() {
+            self.canvas.clone()
+        } else {
+            Canvas::new()
+        };
+
+        if let Some((overlay, _timeout)) = &self.overlay {
+            for (dst, src) in result.data.iter_mut().zip(overlay.data.iter()) {
+                *dst = blend(dst, src);
+            }
+        }
+
+        result
+    }
+}
+
+/// A transport that does not require a running Eruption daemon. Submitted
+/// canvases are rendered into memory, where they may be inspected by tests
+#[derive(Clone)]
+pub struct SimulatedTransport {
+    pub(crate) state: Arc<Mutex<SimulatedState>>,
+}
+
+impl SimulatedTransport {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            state: Arc::new(Mutex::new(SimulatedState {
+                canvas: Canvas::new(),
+                ..Default::default()
+            })),
+        })
+    }
+}
+
+impl Transport for SimulatedTransport {
+    fn connect(&mut self) -> Result<()> {
+        self.state.lock().connected = true;
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        let mut state = self.state.lock();
+
+        // named parameters vanish along with the client, like in the daemon
+        state.connected = false;
+        state.named_parameters.clear();
+
+        Ok(())
+    }
+
+    fn get_protocol_info(&self) -> ProtocolInfo {
+        ProtocolInfo {
+            version: PROTOCOL_VERSION,
+            capabilities: Capabilities::ALL,
+        }
+    }
+
+    fn get_server_status(&self) -> Result<ServerStatus> {
+        self.state.lock().ensure_connected()?;
+
+        Ok(ServerStatus {
+            server: "Eruption (simulated)".to_string(),
+            protocol_version: PROTOCOL_VERSION,
+        })
+    }
+
+    fn get_active_profile(&self) -> Result<PathBuf> {
+        let state = self.state.lock();
+        state.ensure_connected()?;
+
+        Ok(state.active_profile.clone())
+    }
+
+    fn get_active_slot(&self) -> Result<SlotInfo> {
+        let state = self.state.lock();
+        state.ensure_connected()?;
+
+        Ok(SlotInfo {
+            index: state.active_slot,
+            name: format!("Profile Slot {}", state.active_slot + 1),
+            profile_file: state.active_profile.clone(),
+        })
+    }
+
+    fn switch_profile(&self, profile_file: &Path) -> Result<bool> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        state.active_profile = profile_file.to_path_buf();
+
+        Ok(true)
+    }
+
+    fn set_parameters(
+        &self,
+        profile_file: &Path,
+        script_file: &Path,
+        parameter_values: HashMap<String, String>,
+    ) -> Result<()> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        state
+            .parameters
+            .entry((profile_file.to_path_buf(), script_file.to_path_buf()))
+            .or_default()
+            .extend(parameter_values);
+
+        Ok(())
+    }
+
+    fn submit_canvas(&self, canvas: &Canvas) -> Result<()> {
+        self.submit_canvas_with(canvas, &CanvasOptions::default())
+    }
+
+    fn submit_canvas_with(&self, canvas: &Canvas, options: &CanvasOptions) -> Result<()> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        let state = &mut *state;
+
+        match &options.keys {
+            Some(keys) if keys.start > keys.end || keys.end > canvas.data.len() => {
+                return Err(eyre!("Invalid range of keys: {:?}", keys));
+            }
+
+            Some(keys) => {
+                state.canvas.data[keys.clone()].clone_from_slice(&canvas.data[keys.clone()])
+            }
+
+            None => state.canvas = canvas.clone(),
+        }
+
+        state.target_devices = options.devices.clone();
+        state.target_slots = options.slots.clone();
+        state.frame_count += 1;
+
+        if let Some(observer) = state.observer.as_mut() {
+            observer.on_frame(&Frame::Canvas(&state.canvas));
+        }
+
+        Ok(())
+    }
+
+    fn submit_overlay(&self, canvas: &Canvas, timeout: Duration) -> Result<()> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        // a fully transparent overlay removes the current overlay, like in the daemon
+        if canvas.data.iter().all(|c| c.a() == 0) {
+            state.overlay = None;
+        } else {
+            state.overlay = Some((canvas.clone(), timeout));
+        }
+
+        state.frame_count += 1;
+
+        if let Some(observer) = state.observer.as_mut() {
+            observer.on_frame(&Frame::Overlay { canvas, timeout });
+        }
+
+        Ok(())
+    }
+
+    fn snapshot_canvas(&self) -> Result<CanvasSnapshot> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        state.next_snapshot_id += 1;
+        let id = state.next_snapshot_id;
+
+        let snapshot = SimulatedSnapshot {
+            canvas: state.canvas.clone(),
+            target_devices: state.target_devices.clone(),
+            target_slots: state.target_slots.clone(),
+            overlay: state.overlay.clone(),
+        };
+
+        state.snapshots.insert(id, snapshot);
+
+        Ok(CanvasSnapshot {
+            id,
+            canvas: state.rendered_canvas(),
+        })
+    }
+
+    fn restore_canvas(&self, snapshot: &CanvasSnapshot) -> Result<bool> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        let state = &mut *state;
+
+        match state.snapshots.remove(&snapshot.id) {
+            Some(snapshot) => {
+                state.canvas = snapshot.canvas;
+                state.target_devices = snapshot.target_devices;
+                state.target_slots = snapshot.target_slots;
+                state.overlay = snapshot.overlay;
+                state.frame_count += 1;
+
+                if let Some(observer) = state.observer.as_mut() {
+                    observer.on_frame(&Frame::Canvas(&state.canvas));
+                }
+
+                Ok(true)
+            }
+
+            None => Ok(false),
+        }
+    }
+
+    fn get_zones(&self) -> Result<Vec<Zone>> {
+        let state = self.state.lock();
+        state.ensure_connected()?;
+
+        Ok(state.zones.clone())
+    }
+
+    fn get_key_positions(&self) -> Result<Vec<KeyPosition>> {
+        let state = self.state.lock();
+        state.ensure_connected()?;
+
+        Ok(state.key_positions.clone())
+    }
+
+    fn inject_event(&self, event: InputEvent) -> Result<()> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        state.injected_events.push(event);
+
+        Ok(())
+    }
+
+    fn register_named_parameter(&self, name: &str, min: f64, max: f64, value: f64) -> Result<bool> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        if name.is_empty() || !min.is_finite() || !max.is_finite() || min > max || value.is_nan() {
+            return Ok(false);
+        }
+
+        if !state.named_parameters.contains_key(name)
+            && state.named_parameters.len() >= MAX_NAMED_PARAMETERS
+        {
+            return Ok(false);
+        }
+
+        state.named_parameters.insert(
+            name.to_string(),
+            NamedParameter {
+                min,
+                max,
+                value: value.clamp(min, max),
+            },
+        );
+
+        Ok(true)
+    }
+
+    fn set_named_parameter(&self, name: &str, value: f64) -> Result<bool> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        if value.is_nan() {
+            return Ok(false);
+        }
+
+        match state.named_parameters.get_mut(name) {
+            Some(parameter) => {
+                parameter.value = value.clamp(parameter.min, parameter.max);
+
+                Ok(true)
+            }
+
+            None => Ok(false),
+        }
+    }
+
+    fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()> {
+        let mut state = self.state.lock();
+        state.ensure_connected()?;
+
+        state.hotplugged_devices.push(hotplug_info.clone());
+
+        Ok(())
+    }
+}
+
+/// Alpha-blend the color `src` on top of `dst`
+fn blend(dst: &Color, src: &Color) -> Color {
+    let alpha = src.a() as u32;
+
+    let mix = |d: u8, s: u8| ((s as u32 * alpha + d as u32 * (255 - alpha)) / 255) as u8;
+
+    Color::new(
+        mix(dst.r(), src.r()),
+        mix(dst.g(), src.g()),
+        mix(dst.b(), src.b()),
+        dst.a().max(src.a()),
+    )
+}
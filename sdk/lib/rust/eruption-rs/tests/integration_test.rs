@@ -18,3 +18,265 @@
 
     Copyright (c) 2019-2022, The Eruption Development Team
 */
+
+use eruption_sdk::canvas::Canvas;
+use eruption_sdk::color::Color;
+use eruption_sdk::connection::SimulatedConnection;
+use eruption_sdk::hardware::{KeyPosition, Zone};
+use eruption_sdk::transport::{CanvasOptions, Frame, InputEvent, NamedParameter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn simulated_connection_renders_canvas() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+
+    let mut canvas = Canvas::new();
+    canvas.fill(Color::new(255, 0, 0, 255));
+
+    // submitting a canvas requires a connection, like with a real daemon
+    assert!(connection.submit_canvas(&canvas).is_err());
+
+    connection.connect()?;
+    connection.submit_canvas(&canvas)?;
+
+    assert_eq!(connection.canvas(), canvas);
+    assert_eq!(connection.frame_count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn simulated_connection_blends_overlay() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+    connection.connect()?;
+
+    let mut canvas = Canvas::new();
+    canvas.fill(Color::new(0, 0, 255, 255));
+    connection.submit_canvas(&canvas)?;
+
+    let mut overlay = Canvas::new();
+    overlay[0] = Color::new(255, 0, 0, 255);
+    connection.submit_overlay(&overlay, Duration::from_millis(500))?;
+
+    let rendered = connection.rendered_canvas();
+    assert_eq!(rendered[0], Color::new(255, 0, 0, 255));
+    assert_eq!(rendered[1], Color::new(0, 0, 255, 255));
+
+    // a fully transparent overlay removes the current overlay
+    connection.submit_overlay(&Canvas::new(), Duration::ZERO)?;
+    assert!(connection.overlay().is_none());
+    assert_eq!(connection.rendered_canvas(), canvas);
+
+    Ok(())
+}
+
+#[test]
+fn simulated_connection_notifies_frame_observer() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+    connection.connect()?;
+
+    let frames = Arc::new(AtomicUsize::new(0));
+    let overlays = Arc::new(AtomicUsize::new(0));
+
+    let (frames_clone, overlays_clone) = (frames.clone(), overlays.clone());
+    connection.set_frame_observer(move |frame: &Frame| {
+        frames_clone.fetch_add(1, Ordering::SeqCst);
+
+        if let Frame::Overlay { .. } = frame {
+            overlays_clone.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+
+    connection.submit_canvas(&Canvas::new())?;
+    connection.submit_overlay(&Canvas::new(), Duration::ZERO)?;
+
+    assert_eq!(frames.load(Ordering::SeqCst), 2);
+    assert_eq!(overlays.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+#[test]
+fn simulated_connection_reports_zones() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+    connection.connect()?;
+
+    let zones = vec![Zone {
+        name: "wasd".to_string(),
+        keys: vec![30, 44, 45, 46],
+    }];
+
+    connection.set_zones(zones.clone());
+
+    assert_eq!(connection.get_zones()?, zones);
+
+    Ok(())
+}
+
+#[test]
+fn simulated_connection_reports_key_positions() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+    connection.connect()?;
+
+    let key_positions = vec![KeyPosition {
+        key: 1,
+        x: 9.525,
+        y: 9.525,
+    }];
+
+    connection.set_key_positions(key_positions.clone());
+
+    assert_eq!(connection.get_key_positions()?, key_positions);
+
+    Ok(())
+}
+
+#[test]
+fn simulated_connection_clamps_named_parameters() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+    connection.connect()?;
+
+    assert!(connection.register_named_parameter("game_health", 0.0, 100.0, 100.0)?);
+    assert!(connection.set_named_parameter("game_health", 150.0)?);
+    assert!(!connection.set_named_parameter("game_mana", 50.0)?);
+    assert!(!connection.register_named_parameter("game_mana", 100.0, 0.0, 50.0)?);
+
+    assert_eq!(
+        connection.named_parameters().get("game_health"),
+        Some(&NamedParameter {
+            min: 0.0,
+            max: 100.0,
+            value: 100.0,
+        })
+    );
+    assert_eq!(connection.named_parameters().len(), 1);
+
+    connection.disconnect()?;
+
+    assert!(connection.named_parameters().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn simulated_connection_merges_partial_updates() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+    connection.connect()?;
+
+    let mut canvas = Canvas::new();
+    canvas.fill(Color::new(0, 0, 255, 255));
+    connection.submit_canvas(&canvas)?;
+
+    let mut update = Canvas::new();
+    update.fill(Color::new(255, 0, 0, 255));
+
+    let options = CanvasOptions {
+        keys: Some(2..4),
+        devices: Some(vec![0]),
+        slots: None,
+    };
+
+    connection.submit_canvas_with(&update, &options)?;
+
+    let rendered = connection.canvas();
+    assert_eq!(rendered[1], Color::new(0, 0, 255, 255));
+    assert_eq!(rendered[2], Color::new(255, 0, 0, 255));
+    assert_eq!(rendered[3], Color::new(255, 0, 0, 255));
+    assert_eq!(rendered[4], Color::new(0, 0, 255, 255));
+    assert_eq!(connection.target_devices(), Some(vec![0]));
+
+    // ranges outside of the canvas are rejected
+    let options = CanvasOptions {
+        keys: Some(0..usize::MAX),
+        devices: None,
+        slots: None,
+    };
+
+    assert!(connection.submit_canvas_with(&update, &options).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn simulated_connection_restores_snapshot() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+    connection.connect()?;
+
+    let mut canvas = Canvas::new();
+    canvas.fill(Color::new(0, 255, 0, 255));
+    connection.submit_canvas(&canvas)?;
+
+    let snapshot = connection.snapshot_canvas()?;
+    assert_eq!(snapshot.canvas, canvas);
+
+    // flash a notification
+    let mut flash = Canvas::new();
+    flash.fill(Color::new(255, 255, 255, 255));
+    connection.submit_canvas(&flash)?;
+    connection.submit_overlay(&flash, Duration::ZERO)?;
+
+    assert!(connection.restore_canvas(&snapshot)?);
+    assert_eq!(connection.rendered_canvas(), canvas);
+    assert!(connection.overlay().is_none());
+
+    // each snapshot may be restored once
+    assert!(!connection.restore_canvas(&snapshot)?);
+
+    Ok(())
+}
+
+#[test]
+fn simulated_connection_suppresses_canvas_in_other_slots() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+    connection.connect()?;
+
+    let mut canvas = Canvas::new();
+    canvas.fill(Color::new(255, 0, 0, 255));
+
+    let options = CanvasOptions {
+        slots: Some(vec![1]),
+        ..Default::default()
+    };
+
+    connection.submit_canvas_with(&canvas, &options)?;
+    assert_eq!(connection.target_slots(), Some(vec![1]));
+
+    // the canvas is suppressed while another slot is active
+    assert_eq!(connection.get_active_slot()?.index, 0);
+    assert_eq!(connection.rendered_canvas(), Canvas::new());
+
+    connection.set_active_slot(1);
+
+    let slot = connection.get_active_slot()?;
+    assert_eq!(slot.index, 1);
+    assert_eq!(slot.name, "Profile Slot 2");
+    assert_eq!(connection.rendered_canvas(), canvas);
+
+    Ok(())
+}
+
+#[test]
+fn simulated_connection_records_injected_events() -> eruption_sdk::Result<()> {
+    let connection = SimulatedConnection::new()?;
+
+    assert!(connection.inject_event(InputEvent::KeyDown(1)).is_err());
+
+    connection.connect()?;
+
+    connection.inject_event(InputEvent::KeyDown(1))?;
+    connection.inject_event(InputEvent::KeyUp(1))?;
+    connection.inject_event(InputEvent::MouseButtonDown(2))?;
+
+    assert_eq!(
+        connection.injected_events(),
+        vec![
+            InputEvent::KeyDown(1),
+            InputEvent::KeyUp(1),
+            InputEvent::MouseButtonDown(2)
+        ]
+    );
+
+    Ok(())
+}
@@ -82,6 +82,7 @@ cargo build --release --verbose
 %{__mkdir_p} %{buildroot}/%{_docdir}/%{ShortName}
 %{__mkdir_p} %{buildroot}/%{_datarootdir}/icons/hicolor/scalable/apps
 %{__mkdir_p} %{buildroot}/%{_datarootdir}/%{ShortName}/sfx
+%{__mkdir_p} %{buildroot}/%{_datarootdir}/%{ShortName}/defaults
 %{__mkdir_p} %{buildroot}/%{_datarootdir}/%{ShortName}/i18n
 %{__mkdir_p} %{buildroot}/%{_datarootdir}/applications/
 %{__mkdir_p} %{buildroot}/%{_datarootdir}/icons/hicolor/64x64/apps/
@@ -150,8 +151,6 @@ cp -a %{_builddir}/%{name}-releases-v%{version}/support/systemd/eruption-audio-p
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/systemd/eruption-audio-proxy.service %{buildroot}/%{_userunitdir}/
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/systemd/eruption-process-monitor.preset %{buildroot}/%{_userpresetdir}/50-eruption-process-monitor.preset
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/systemd/eruption-process-monitor.service %{buildroot}/%{_userunitdir}/
-cp -a %{_builddir}/%{name}-releases-v%{version}/support/systemd/eruption-hotplug-helper.preset %{buildroot}/%{_presetdir}/50-eruption-hotplug-helper.preset
-cp -a %{_builddir}/%{name}-releases-v%{version}/support/systemd/eruption-hotplug-helper.service %{buildroot}/%{_unitdir}/
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/animal-blobby.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/animal-blobby-swirl.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/animal-breathing-1.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
@@ -200,6 +199,7 @@ cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/rainbow-wave.pr
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/red-fx.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/red-wave.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/ripple-rainbow.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
+cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/screensaver.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/snake.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/solid-wave.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/profiles/solid.profile %{buildroot}/%{_sharedstatedir}/%{ShortName}/profiles/
@@ -224,6 +224,7 @@ cp -a %{_builddir}/%{name}-releases-v%{version}/support/sfx/phaser1.wav %{buildr
 cp -a %{_builddir}/%{name}-releases-v%{version}/support/sfx/phaser2.wav %{buildroot}/%{_datarootdir}/%{ShortName}/sfx/phaser2.wav
 ln -s phaser1.wav %{buildroot}/%{_datarootdir}/%{ShortName}/sfx/key-down.wav
 ln -s phaser2.wav %{buildroot}/%{_datarootdir}/%{ShortName}/sfx/key-up.wav
+cp -a %{_builddir}/%{name}-releases-v%{version}/support/defaults/devices.toml %{buildroot}/%{_datarootdir}/%{ShortName}/defaults/devices.toml
 
 cp -ra %{_builddir}/%{name}-releases-v%{version}/eruption/src/scripts %{buildroot}/%{_datarootdir}/%{ShortName}/
 
@@ -242,7 +243,6 @@ install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eru
 install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eruption-netfx %{buildroot}/%{_bindir}/eruption-netfx
 install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eruption-util %{buildroot}/%{_bindir}/eruption-util
 install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eruption-debug-tool %{buildroot}/%{_bindir}/eruption-debug-tool
-install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eruption-hotplug-helper %{buildroot}/%{_bindir}/eruption-hotplug-helper
 install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eruption-fx-proxy %{buildroot}/%{_bindir}/eruption-fx-proxy
 install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eruption-audio-proxy %{buildroot}/%{_bindir}/eruption-audio-proxy
 install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eruption-process-monitor %{buildroot}/%{_bindir}/eruption-process-monitor
@@ -300,7 +300,6 @@ install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eru
 %{_bindir}/eruption-netfx
 %{_bindir}/eruption-util
 %{_bindir}/eruption-debug-tool
-%{_bindir}/eruption-hotplug-helper
 %{_bindir}/eruption-fx-proxy
 %{_bindir}/eruption-audio-proxy
 %caps(cap_net_admin=ep) %{_bindir}/eruption-process-monitor
@@ -312,8 +311,6 @@ install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eru
 %{_userpresetdir}/50-eruption-audio-proxy.preset
 %{_userunitdir}/eruption-process-monitor.service
 %{_userpresetdir}/50-eruption-process-monitor.preset
-%{_unitdir}/eruption-hotplug-helper.service
-%{_presetdir}/50-eruption-hotplug-helper.preset
 %{_bindir}/eruption-gui-gtk3
 %{_datarootdir}/applications/eruption-gui-gtk3.desktop
 %{_datarootdir}/icons/hicolor/64x64/apps/eruption-gui.png
@@ -396,6 +393,7 @@ install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eru
 %{_sharedstatedir}/%{ShortName}/profiles/red-fx.profile
 %{_sharedstatedir}/%{ShortName}/profiles/red-wave.profile
 %{_sharedstatedir}/%{ShortName}/profiles/ripple-rainbow.profile
+%{_sharedstatedir}/%{ShortName}/profiles/screensaver.profile
 %{_sharedstatedir}/%{ShortName}/profiles/snake.profile
 %{_sharedstatedir}/%{ShortName}/profiles/solid-wave.profile
 %{_sharedstatedir}/%{ShortName}/profiles/solid.profile
@@ -537,8 +535,16 @@ install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eru
 %{_datarootdir}/%{ShortName}/scripts/ripple.lua.manifest
 %{_datarootdir}/%{ShortName}/scripts/solid.lua
 %{_datarootdir}/%{ShortName}/scripts/solid.lua.manifest
+%{_datarootdir}/%{ShortName}/scripts/screensaver-fire.lua.manifest
+%{_datarootdir}/%{ShortName}/scripts/screensaver-matrix.lua.manifest
+%{_datarootdir}/%{ShortName}/scripts/screensaver-plasma.lua.manifest
+%{_datarootdir}/%{ShortName}/scripts/screensaver-starfield.lua.manifest
 %{_datarootdir}/%{ShortName}/scripts/stock-gradient.lua
 %{_datarootdir}/%{ShortName}/scripts/stock-gradient.lua.manifest
+%{_datarootdir}/%{ShortName}/scripts/key-labels.lua
+%{_datarootdir}/%{ShortName}/scripts/key-labels.lua.manifest
+%{_datarootdir}/%{ShortName}/scripts/unit-status.lua
+%{_datarootdir}/%{ShortName}/scripts/unit-status.lua.manifest
 %{_datarootdir}/%{ShortName}/scripts/stripes.lua
 %{_datarootdir}/%{ShortName}/scripts/stripes.lua.manifest
 %{_datarootdir}/%{ShortName}/scripts/swirl-perlin.lua
@@ -572,5 +578,6 @@ install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eru
 %{_datarootdir}/%{ShortName}/sfx/phaser2.wav
 %{_datarootdir}/%{ShortName}/sfx/key-down.wav
 %{_datarootdir}/%{ShortName}/sfx/key-up.wav
+%{_datarootdir}/%{ShortName}/defaults/devices.toml
 
 %changelog
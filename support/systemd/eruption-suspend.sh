@@ -22,21 +22,11 @@
 if [ "$1" = "pre" ] ; then
     # prepare Eruption for system sleep
 
-    touch /run/lock/eruption-hotplug-helper.lock
-
-    systemctl stop eruption-hotplug-helper.service
     systemctl stop eruption.service
-
-    touch /run/lock/eruption-sleep.lock
 else
-    # wake up Eruption after system sleep
+    # wake up Eruption after system sleep, the connected devices will be
+    # in an indeterminate state, so start with a fresh instance of the daemon
 
-    systemctl reset-failed eruption-hotplug-helper.service
     systemctl reset-failed eruption.service
-
-    rm /run/lock/eruption-hotplug-helper.lock
-
-    systemctl start eruption-hotplug-helper.service
-
-    rm /run/lock/eruption-sleep.lock
+    systemctl start eruption.service
 fi
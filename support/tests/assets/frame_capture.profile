@@ -18,7 +18,12 @@
 #    Copyright (c) 2019-2022, The Eruption Development Team
 
 
-fallback_language = "en-US"
+id = '2b7c5f0e-0b4e-4d7a-9c55-6f3d1e8a2c41'
+name = 'Frame Capture'
+description = 'Profile for the tests of the frame capture harness'
+active_scripts = [ 'frame_capture_test.lua' ]
 
-[fluent]
-assets_dir = "i18n/"
+[[config."Frame Capture Test"]]
+type = 'color'
+name = 'color_background'
+value = 0xff204060
@@ -0,0 +1,47 @@
+-- SPDX-License-Identifier: GPL-3.0-or-later
+--
+-- This file is part of Eruption.
+--
+-- Eruption is free software: you can redistribute it and/or modify
+-- it under the terms of the GNU General Public License as published by
+-- the Free Software Foundation, either version 3 of the License, or
+-- (at your option) any later version.
+--
+-- Eruption is distributed in the hope that it will be useful,
+-- but WITHOUT ANY WARRANTY without even the implied warranty of
+-- MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+-- GNU General Public License for more details.
+--
+-- You should have received a copy of the GNU General Public License
+-- along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+--
+-- Copyright (c) 2019-2022, The Eruption Development Team
+
+
+-- A deterministic effect for the tests of the frame capture harness: the first LED
+-- counts the ticks in its blue channel, pressed keys are lit with the highlight color
+
+color_map = {}
+ticks = 0
+
+function on_startup(config)
+    for i = 1, get_canvas_size() do
+        color_map[i] = color_background
+    end
+
+    submit_color_map(color_map)
+end
+
+function on_key_down(key_index)
+    color_map[key_index] = color_highlight
+
+    submit_color_map(color_map)
+end
+
+function on_tick(delta)
+    ticks = ticks + delta
+
+    color_map[1] = 0xff000000 + ticks
+
+    submit_color_map(color_map)
+end
@@ -18,4 +18,7 @@
 #    Copyright (c) 2019-2022, The Eruption Development Team
 
 
-disable eruption-hotplug-helper.service
+id = '7e1d9a34-5c2f-4f8b-a6d0-3b9e4c7f1a52'
+name = 'Solid Color'
+description = 'Renders the shipped script solid.lua'
+active_scripts = [ 'solid.lua' ]